@@ -0,0 +1,340 @@
+use bevy::prelude::*;
+
+use super::balance::Balance;
+use super::beams::Laser;
+use super::boarding::{self, BoardShip, Boarding};
+use super::countermeasures::{Countermeasure, DeployCountermeasure};
+use super::dialogue::CommsMessage;
+use super::jamming::Jammer;
+use super::modules;
+use super::physics::{Attitude, Kinimatics, SimulationSet};
+use super::sensors::{Contact, ContactKind, Contacts, SensorBundle};
+use super::shields::Shield;
+use super::ships::{
+    self, burn_towards, Controlled, Engine, Faction, Hull, LaunchMissile, MissileLauncher,
+    ShipSprites,
+};
+
+pub struct AiPlugin;
+
+impl Plugin for AiPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(
+            ai_decision_system
+                .after(super::sensors::sensor_system)
+                .in_set(SimulationSet),
+        )
+        .add_system(
+            ai_control_system
+                .after(ai_decision_system)
+                .before(super::physics::kinimatics_system)
+                .in_set(SimulationSet),
+        )
+        .add_system(
+            ai_boarding_system
+                .after(ai_decision_system)
+                .before(super::boarding::board_system)
+                .in_set(SimulationSet),
+        );
+    }
+}
+
+/// What an AI ship is currently trying to do.
+#[derive(Reflect, Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AiState {
+    /// No hostiles in sight. Loiter around `patrol_center`.
+    #[default]
+    Patrol,
+    /// A hostile ship has been detected, but is out of weapons range. Close the distance.
+    Intercept,
+    /// Match velocity with the target and launch missiles at it.
+    Attack,
+    /// A missile is closing in. Burn perpendicular to it, throwing out
+    /// countermeasures.
+    Evade,
+    /// Low on fuel or badly damaged. Run from the nearest hostile.
+    Flee,
+}
+
+/// :COMPONENT: Flies a ship without a user script, using a small state machine
+/// driven by the ship's sensor [`Contacts`], fuel, and hull.
+#[derive(Reflect, Component, Clone)]
+#[reflect(Component)]
+pub struct AiController {
+    pub state: AiState,
+    pub target: Option<Entity>,
+    pub patrol_center: Vec3,
+    pub patrol_radius: f32,
+    /// Distance to a hostile ship at which the AI stops closing and starts shooting.
+    pub attack_range: f32,
+    /// Distance to an incoming missile at which the AI starts evading it.
+    pub evade_range: f32,
+    /// The AI flees when it has less fuel than this...
+    pub bingo_fuel: f32,
+    /// ... or when its hull integrity drops below this.
+    pub flee_integrity: f32,
+}
+
+impl Default for AiController {
+    fn default() -> Self {
+        Self {
+            state: AiState::Patrol,
+            target: None,
+            patrol_center: Vec3::ZERO,
+            patrol_radius: 200.0,
+            attack_range: 400.0,
+            evade_range: 150.0,
+            bingo_fuel: 100.0,
+            flee_integrity: 25.0,
+        }
+    }
+}
+
+/// Spawns a hostile, AI controlled ship which patrols around where it was spawned.
+pub fn spawn_ai_ship(
+    commands: &mut Commands,
+    sprites: &ShipSprites,
+    translation: Vec3,
+    velocity: Vec3,
+) -> Entity {
+    let ship = ships::spawn_ship(commands, sprites, translation, velocity);
+    commands
+        .entity(ship)
+        .insert((
+            Faction::HOSTILE,
+            MissileLauncher::default(),
+            Countermeasure::default(),
+            Laser::default(),
+            Shield::default(),
+            Jammer::default(),
+            SensorBundle::default(),
+            AiController {
+                patrol_center: translation,
+                ..Default::default()
+            },
+        ))
+        .with_children(|ship| {
+            modules::laser_loadout(ship);
+            modules::shield_loadout(ship);
+            modules::solar_loadout(ship);
+        });
+    ship
+}
+
+/// :SYSTEM: Picks a state for each AI, based on what it can see and how it's doing.
+/// AIs hail the player when they start chasing or running from them.
+#[allow(clippy::type_complexity)]
+fn ai_decision_system(
+    mut ais: Query<(
+        Entity,
+        &mut AiController,
+        &Contacts,
+        &Faction,
+        &Engine,
+        &Hull,
+        Option<&Name>,
+        Option<&mut Jammer>,
+    )>,
+    player: Query<(), With<Controlled>>,
+    mut comms: EventWriter<CommsMessage>,
+) {
+    for (entity, mut ai, contacts, faction, engine, hull, name, jammer) in ais.iter_mut() {
+        let threat = contacts.0.iter().find(|c| {
+            c.kind == ContactKind::Missile
+                && c.target == Some(entity)
+                && c.distance < ai.evade_range
+        });
+        let enemy = contacts.nearest_hostile(faction, ContactKind::Ship);
+
+        let (state, target) = if engine.fuel < ai.bingo_fuel || hull.integrity < ai.flee_integrity {
+            (AiState::Flee, enemy.map(|c| c.entity))
+        } else if let Some(missile) = threat {
+            (AiState::Evade, Some(missile.entity))
+        } else if let Some(enemy) = enemy {
+            if enemy.distance < ai.attack_range {
+                (AiState::Attack, Some(enemy.entity))
+            } else {
+                (AiState::Intercept, Some(enemy.entity))
+            }
+        } else {
+            (AiState::Patrol, None)
+        };
+
+        // jam whoever it's after, or running from
+        if let Some(mut jammer) = jammer {
+            let jamming = state != AiState::Patrol;
+            if jammer.enabled != jamming {
+                jammer.enabled = jamming;
+            }
+        }
+
+        if ai.state == state && ai.target == target {
+            continue;
+        }
+
+        let hail = match state {
+            AiState::Intercept if ai.state != AiState::Attack => Some("comms.ai.intercept"),
+            AiState::Flee if ai.state != AiState::Flee => Some("comms.ai.flee"),
+            _ => None,
+        };
+        if let (Some(text), Some(t)) = (hail, target) {
+            if player.contains(t) {
+                comms.send(CommsMessage {
+                    sender: name
+                        .map_or("comms.sender.unknown", |n| n.as_str())
+                        .to_string(),
+                    portrait: Some("ship_1.png".to_string()),
+                    text: text.to_string(),
+                    ..Default::default()
+                });
+            }
+        }
+
+        ai.state = state;
+        ai.target = target;
+    }
+}
+
+/// :SYSTEM: Sends a boarding party over to the ship an AI is attacking, as soon
+/// as it's crippled and alongside (see [`boarding::boardable`]). AIs hail the
+/// player when they board them.
+#[allow(clippy::type_complexity)]
+fn ai_boarding_system(
+    ais: Query<
+        (
+            Entity,
+            &AiController,
+            &Transform,
+            &Kinimatics,
+            Option<&Name>,
+        ),
+        Without<Boarding>,
+    >,
+    targets: Query<(&Transform, &Kinimatics, &Hull)>,
+    player: Query<(), With<Controlled>>,
+    mut boards: EventWriter<BoardShip>,
+    mut comms: EventWriter<CommsMessage>,
+    balance: Res<Balance>,
+) {
+    for (entity, ai, transform, kin, name) in ais.iter() {
+        let Some(target) = ai.target.filter(|_| ai.state == AiState::Attack) else {
+            continue;
+        };
+        let Ok((t, k, hull)) = targets.get(target) else {
+            continue;
+        };
+        let offset = t.translation - transform.translation;
+        if !boarding::boardable(&balance.boarding, hull, offset, k.velocity - kin.velocity) {
+            continue;
+        }
+
+        boards.send(BoardShip {
+            captor: entity,
+            prize: target,
+        });
+        if player.contains(target) {
+            comms.send(CommsMessage {
+                sender: name
+                    .map_or("comms.sender.unknown", |n| n.as_str())
+                    .to_string(),
+                portrait: Some("ship_1.png".to_string()),
+                text: "comms.ai.board".to_string(),
+                ..Default::default()
+            });
+        }
+    }
+}
+
+/// :SYSTEM: Flies each AI ship according to its current state.
+#[allow(clippy::type_complexity)]
+pub fn ai_control_system(
+    mut ais: Query<(
+        Entity,
+        &AiController,
+        &Contacts,
+        &Kinimatics,
+        &Transform,
+        &mut Attitude,
+        &mut Engine,
+        Option<&MissileLauncher>,
+        Option<&mut Laser>,
+        Option<&Boarding>,
+    )>,
+    mut launches: EventWriter<LaunchMissile>,
+    mut deploys: EventWriter<DeployCountermeasure>,
+    time: Res<Time>,
+) {
+    const PATROL_SPEED: f32 = 30.0;
+    const INTERCEPT_SPEED: f32 = 80.0;
+    const EVADE_SPEED: f32 = 60.0;
+    const FLEE_SPEED: f32 = 100.0;
+    /// Velocity error which maps to full throttle.
+    const FULL_THROTTLE_ERROR: f32 = 50.0;
+
+    for (
+        entity,
+        ai,
+        contacts,
+        kin,
+        transform,
+        mut attitude,
+        mut engine,
+        launcher,
+        laser,
+        boarding,
+    ) in ais.iter_mut()
+    {
+        let position = transform.translation;
+        let target: Option<&Contact> = ai
+            .target
+            .and_then(|t| contacts.0.iter().find(|c| c.entity == t));
+
+        // the beam only hits if the target is in its arc, so hold the trigger
+        // for the whole attack and let the AI's turning bring it to bear
+        if let Some(mut laser) = laser {
+            laser.target = ai.target;
+            laser.firing = ai.state == AiState::Attack && target.is_some() && boarding.is_none();
+        }
+
+        // the change in velocity the AI wants to make
+        let dv = match (ai.state, target) {
+            (AiState::Patrol, _) | (_, None) => {
+                let to_center = ai.patrol_center - position;
+                if to_center.length() > ai.patrol_radius {
+                    to_center.normalize() * PATROL_SPEED - kin.velocity
+                } else {
+                    Vec3::ZERO
+                }
+            }
+            (AiState::Intercept, Some(t)) => {
+                t.velocity + (t.position - position).normalize_or_zero() * INTERCEPT_SPEED
+                    - kin.velocity
+            }
+            (AiState::Attack, Some(t)) => {
+                // hold fire on a ship that's being boarded
+                if launcher.is_some_and(|l| l.ready()) && boarding.is_none() {
+                    launches.send(LaunchMissile {
+                        shooter: entity,
+                        target: t.entity,
+                    });
+                }
+                t.velocity - kin.velocity
+            }
+            (AiState::Evade, Some(t)) => {
+                deploys.send(DeployCountermeasure { ship: entity });
+                let away = position - t.position;
+                Vec3::new(-away.y, away.x, 0.0).normalize_or_zero() * EVADE_SPEED
+            }
+            (AiState::Flee, Some(t)) => (position - t.position).normalize_or_zero() * FLEE_SPEED,
+        };
+
+        burn_towards(
+            transform,
+            &mut attitude,
+            &mut engine,
+            dv,
+            FULL_THROTTLE_ERROR,
+            time.delta_seconds(),
+        );
+    }
+}
@@ -0,0 +1,335 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+use super::clock::SimulationClock;
+use super::level::{AstroObject, OnRails};
+use super::physics::{GravityAffected, GravitySource, Kinimatics, PhysicsConfig};
+use super::propagation::{self, PropagatedBody};
+use super::ships::Controlled;
+use super::star_system::Dormant;
+
+pub struct AssistPlannerPlugin;
+
+impl Plugin for AssistPlannerPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<AssistPlanner>()
+            .add_system(assist_planner_system);
+    }
+}
+
+/// Length of each propagation step the planner takes, in seconds.
+const STEP: f32 = 0.5;
+
+/// Departure times tried across the window...
+const DEPARTURES: usize = 8;
+/// ...and burn directions tried at each of them.
+const HEADINGS: usize = 24;
+
+/// Most candidates kept from a search.
+const MAX_CANDIDATES: usize = 12;
+
+/// A candidate arrives if it comes within this many radii of the target...
+const ARRIVAL_RADII: f32 = 5.0;
+/// ...and flies by every other body it comes within this many radii of.
+const FLYBY_RADII: f32 = 10.0;
+
+/// A trajectory the planner found which reaches the target.
+#[derive(Clone, Debug)]
+pub struct Candidate {
+    /// Seconds from now the burn is made.
+    pub departure: f32,
+    /// Degrees clockwise from up the burn is pointed.
+    pub heading: f32,
+    /// The bodies flown by on the way, in order, as indices into the bodies
+    /// searched through.
+    pub flybys: Vec<usize>,
+    /// Seconds from the burn to the closest approach to the target.
+    pub flight_time: f32,
+    /// Speed relative to the target at the closest approach.
+    pub arrival_speed: f32,
+    /// Distance from the target's center at the closest approach.
+    pub miss: f32,
+}
+
+/// What the planner searches through.
+#[derive(Clone, Copy, Debug)]
+pub struct SearchParameters {
+    /// Seconds from now the departure window opens...
+    pub window_start: f32,
+    /// ...and how long it stays open.
+    pub window_length: f32,
+    /// Size of the departure burn, made all at once.
+    pub delta_v: f32,
+    /// How long after the burn to follow each candidate for.
+    pub flight_time: f32,
+}
+
+impl Default for SearchParameters {
+    fn default() -> Self {
+        Self {
+            window_start: 0.0,
+            window_length: 120.0,
+            delta_v: 20.0,
+            flight_time: 600.0,
+        }
+    }
+}
+
+/// Searches for trajectories from `ship` to `target`, both indices into
+/// `bodies`, for gravity as set by `config`. `radii` are the bodies' sizes,
+/// zero for anything which isn't an [`AstroObject`].
+///
+/// The ship coasts until its departure, burns `delta_v` in one of
+/// [`HEADINGS`] directions, and coasts on through the system, being thrown
+/// about by whatever it passes. The candidates which reach the target come
+/// back slowest arrival first, since those are the ones easiest to stop at
+/// the end.
+pub fn search(
+    bodies: &[PropagatedBody],
+    radii: &[f32],
+    ship: usize,
+    target: usize,
+    parameters: &SearchParameters,
+    config: &PhysicsConfig,
+) -> Vec<Candidate> {
+    let mut state = bodies.to_vec();
+    // the plan is to coast until the burn
+    state[ship].thrust = Vec3::ZERO;
+    state[ship].attitude = None;
+
+    let steps = |seconds: f32| (seconds / STEP).round().max(0.0) as usize;
+    let spacing = parameters.window_length / (DEPARTURES - 1) as f32;
+    let mut elapsed = 0;
+    let mut candidates = Vec::new();
+    for departure in 0..DEPARTURES {
+        let departs = steps(parameters.window_start + spacing * departure as f32);
+        for _ in elapsed..departs {
+            propagation::step(&mut state, STEP, config);
+        }
+        elapsed = elapsed.max(departs);
+
+        for heading in 0..HEADINGS {
+            let angle = std::f32::consts::TAU * heading as f32 / HEADINGS as f32;
+            let mut flight = state.clone();
+            flight[ship].velocity += Vec3::new(angle.sin(), angle.cos(), 0.0) * parameters.delta_v;
+
+            // closest approach to every body, and when it was
+            let mut closest = vec![(f32::MAX, 0); flight.len()];
+            let mut arrival = None;
+            for step in 0..steps(parameters.flight_time) {
+                propagation::step(&mut flight, STEP, config);
+                let at = flight[ship];
+                for (i, body) in flight.iter().enumerate() {
+                    let distance = body.position.distance(at.position);
+                    if i != ship && distance < closest[i].0 {
+                        closest[i] = (distance, step);
+                        if i == target {
+                            arrival = Some((step, (at.velocity - body.velocity).length()));
+                        }
+                    }
+                }
+            }
+
+            let Some((arrived, arrival_speed)) = arrival else {
+                continue;
+            };
+            let miss = closest[target].0;
+            if miss > radii[target] * ARRIVAL_RADII {
+                continue;
+            }
+            let mut flybys: Vec<_> = (0..flight.len())
+                .filter(|i| {
+                    let (distance, when) = closest[*i];
+                    *i != ship
+                        && *i != target
+                        && radii[*i] > 0.0
+                        && when < arrived
+                        && distance <= radii[*i] * FLYBY_RADII
+                })
+                .collect();
+            flybys.sort_by_key(|i| closest[*i].1);
+
+            candidates.push(Candidate {
+                departure: departs as f32 * STEP,
+                heading: angle.to_degrees(),
+                flybys,
+                flight_time: (arrived + 1) as f32 * STEP,
+                arrival_speed,
+                miss,
+            });
+        }
+    }
+
+    candidates.sort_by(|a, b| a.arrival_speed.total_cmp(&b.arrival_speed));
+    candidates.truncate(MAX_CANDIDATES);
+    candidates
+}
+
+/// Resource which holds the state of the gravity-assist planner, which is
+/// opened from the tray.
+#[derive(Resource, Default)]
+pub struct AssistPlanner {
+    pub open: bool,
+    pub target: Option<Entity>,
+    pub parameters: SearchParameters,
+    pub candidates: Vec<Candidate>,
+    /// The bodies the candidates were searched through.
+    pub searched: Vec<Entity>,
+    /// Mission elapsed time the candidates were worked out at.
+    pub searched_at: f64,
+    status: String,
+}
+
+/// :SYSTEM: Shows the gravity-assist planner, and searches for trajectories
+/// from the controlled ship to the chosen body when asked. The search is done
+/// on the spot, from the system as it is, so it can take a moment.
+#[allow(clippy::type_complexity)]
+fn assist_planner_system(
+    mut contexts: EguiContexts,
+    mut planner: ResMut<AssistPlanner>,
+    bodies: Query<
+        (
+            Entity,
+            &Kinimatics,
+            &Transform,
+            Option<&GravitySource>,
+            Option<&GravityAffected>,
+            Option<&OnRails>,
+            Option<&AstroObject>,
+            Option<&Controlled>,
+        ),
+        Without<Dormant>,
+    >,
+    names: Query<&Name>,
+    clock: Res<SimulationClock>,
+    config: Res<PhysicsConfig>,
+) {
+    if !planner.open {
+        return;
+    }
+    let planner = &mut *planner;
+    let name = |entity: Entity| {
+        names
+            .get(entity)
+            .map_or(format!("body {}", entity.index()), |n| n.to_string())
+    };
+
+    let mut searching = false;
+    let mut open = true;
+    egui::Window::new("Gravity-assist planner")
+        .open(&mut open)
+        .resizable(false)
+        .show(contexts.ctx_mut(), |ui| {
+            let current = planner.target.map_or("none".to_string(), name);
+            egui::ComboBox::from_label("target")
+                .selected_text(current)
+                .show_ui(ui, |ui| {
+                    for (entity, ..) in bodies.iter().filter(|b| b.6.is_some()) {
+                        ui.selectable_value(&mut planner.target, Some(entity), name(entity));
+                    }
+                });
+
+            let parameters = &mut planner.parameters;
+            egui::Grid::new("assist_parameters").show(ui, |ui| {
+                for (label, value, suffix) in [
+                    ("window opens in", &mut parameters.window_start, " s"),
+                    ("window length", &mut parameters.window_length, " s"),
+                    ("burn", &mut parameters.delta_v, " m/s"),
+                    ("follow for", &mut parameters.flight_time, " s"),
+                ] {
+                    ui.label(label);
+                    ui.add(
+                        egui::DragValue::new(value)
+                            .suffix(suffix)
+                            .clamp_range(0.0..=f32::MAX),
+                    );
+                    ui.end_row();
+                }
+            });
+            searching = ui.button("Search").clicked();
+            if !planner.status.is_empty() {
+                ui.label(&planner.status);
+            }
+
+            if planner.candidates.is_empty() {
+                return;
+            }
+            ui.separator();
+            let since = (clock.elapsed - planner.searched_at) as f32;
+            egui::Grid::new("assist_candidates")
+                .striped(true)
+                .show(ui, |ui| {
+                    for heading in [
+                        "burn in",
+                        "heading",
+                        "via",
+                        "arrives after",
+                        "passes at",
+                        "arrival speed",
+                    ] {
+                        ui.strong(heading);
+                    }
+                    ui.end_row();
+                    for candidate in planner.candidates.iter() {
+                        let burn = candidate.departure - since;
+                        match burn >= 0.0 {
+                            true => ui.label(format!("{:.0} s", burn)),
+                            false => ui.weak("missed"),
+                        };
+                        ui.label(format!("{:.0}°", candidate.heading));
+                        let via: Vec<_> = candidate
+                            .flybys
+                            .iter()
+                            .map(|i| name(planner.searched[*i]))
+                            .collect();
+                        match via.is_empty() {
+                            true => ui.weak("direct"),
+                            false => ui.label(via.join(", ")),
+                        };
+                        ui.label(format!("{:.0} s", candidate.flight_time));
+                        ui.label(format!("{:.0}", candidate.miss));
+                        ui.label(format!("{:.1}", candidate.arrival_speed));
+                        ui.end_row();
+                    }
+                });
+        });
+    planner.open = open;
+
+    if !searching {
+        return;
+    }
+    let entities: Vec<Entity> = bodies.iter().map(|(entity, ..)| entity).collect();
+    let ship = bodies.iter().position(|b| b.7.is_some());
+    let target = planner
+        .target
+        .and_then(|t| entities.iter().position(|e| *e == t));
+    let (Some(ship), Some(target)) = (ship, target) else {
+        planner.status = "pick a target, and take control of a ship".to_string();
+        return;
+    };
+
+    let radii: Vec<f32> = bodies
+        .iter()
+        .map(|b| b.6.map_or(0.0, |a| a.radius))
+        .collect();
+    let state: Vec<PropagatedBody> = bodies
+        .iter()
+        .map(
+            |(_, kinimatics, transform, source, affected, rails, ..)| PropagatedBody {
+                angle: propagation::angle_of(transform.rotation),
+                source: source.is_some(),
+                affected: affected.is_some(),
+                rails: rails.and_then(|r| entities.iter().position(|e| *e == r.parent)),
+                ..PropagatedBody::new(transform.translation, kinimatics.velocity, kinimatics.mass)
+            },
+        )
+        .collect();
+
+    planner.candidates = search(&state, &radii, ship, target, &planner.parameters, &config);
+    planner.searched = entities;
+    planner.searched_at = clock.elapsed;
+    planner.status = match planner.candidates.len() {
+        0 => "nothing reaches the target; try a bigger burn or a longer flight".to_string(),
+        n => format!("{} ways there", n),
+    };
+}
@@ -0,0 +1,137 @@
+use std::f32::consts::TAU;
+
+use bevy::prelude::*;
+
+use super::level::AstroObject;
+use super::physics::{Kinimatics, PhysicsConfig, SimulationSet};
+use super::star_system::Dormant;
+use super::user_interface::MapIcon;
+
+pub struct AsteroidPlugin;
+
+impl Plugin for AsteroidPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Asteroid>().add_system(
+            asteroid_system
+                .after(super::physics::kinimatics_system)
+                .in_set(SimulationSet),
+        );
+    }
+}
+
+/// :COMPONENT: A rock too small to pull on anything.
+///
+/// Asteroids aren't part of the n-body simulation: they don't have
+/// [`Kinimatics`], and only feel the gravity of astronomical bodies, never of
+/// ships or of each other, so a belt of thousands costs little more than a
+/// handful of ships. They all share one texture, so they are drawn in a single
+/// batch.
+#[derive(Reflect, Component, Clone, Copy, Debug, Default)]
+#[reflect(Component)]
+pub struct Asteroid {
+    pub velocity: Vec3,
+}
+
+/// The texture every asteroid is drawn with.
+pub const ASTEROID_TEXTURE: &str = "dot.png";
+
+/// A small, fast random number generator (splitmix64), so belts come out the
+/// same for the same seed.
+pub struct Rng(pub u64);
+
+impl Rng {
+    /// A number on \[0,1).
+    pub fn next(&mut self) -> f32 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^= z >> 31;
+        (z >> 40) as f32 / (1u64 << 24) as f32
+    }
+}
+
+/// Spawns `count` asteroids on circular orbits between `inner` and `outer`
+/// from a body whose gravitational parameter is `mu`, going around it
+/// counterclockwise. `texture` should
+/// be [`ASTEROID_TEXTURE`].
+#[allow(clippy::too_many_arguments)]
+pub fn spawn_belt(
+    commands: &mut Commands,
+    texture: Handle<Image>,
+    center: Vec3,
+    center_velocity: Vec3,
+    mu: f32,
+    inner: f32,
+    outer: f32,
+    count: usize,
+    seed: u64,
+) {
+    let mut rng = Rng(seed);
+
+    let asteroids: Vec<_> = (0..count)
+        .map(|_| {
+            let radius = inner + (outer - inner) * rng.next();
+            let angle = TAU * rng.next();
+            let out = Vec3::new(angle.cos(), angle.sin(), 0.0);
+            let speed = (mu / radius).sqrt();
+            let size = 1.0 + 2.0 * rng.next();
+
+            (
+                Name::new("Asteroid"),
+                // an asteroid is no more than its sprite, so its scale is
+                // free for the icon to use
+                MapIcon,
+                Asteroid {
+                    velocity: center_velocity + Vec3::new(-out.y, out.x, 0.0) * speed,
+                },
+                SpriteBundle {
+                    sprite: Sprite {
+                        custom_size: Some(Vec2::splat(size)),
+                        color: Color::rgb(0.55, 0.5, 0.45),
+                        ..Default::default()
+                    },
+                    texture: texture.clone(),
+                    transform: Transform::from_translation(center + out * radius),
+                    ..Default::default()
+                },
+            )
+        })
+        .collect();
+    commands.spawn_batch(asteroids);
+}
+
+/// :SYSTEM: Moves every asteroid under the pull of the astronomical bodies.
+#[allow(clippy::type_complexity)]
+fn asteroid_system(
+    bodies: Query<(&Kinimatics, &Transform), (With<AstroObject>, Without<Dormant>)>,
+    mut asteroids: Query<(&mut Asteroid, &mut Transform), (Without<AstroObject>, Without<Dormant>)>,
+    physics: Res<PhysicsConfig>,
+    time: Res<Time>,
+) {
+    let dt = time.delta_seconds();
+    let sources: Vec<(Vec3, f32)> = bodies
+        .iter()
+        .map(|(k, t)| (t.translation, physics.mu(k.mass)))
+        .collect();
+
+    asteroids
+        .par_iter_mut()
+        .for_each_mut(|(mut asteroid, mut transform)| {
+            let position = transform.translation;
+            let acceleration: Vec3 = sources
+                .iter()
+                .map(|(at, mu)| {
+                    let offset = *at - position;
+                    let distance = offset.length();
+                    if distance > 0.0 {
+                        offset * *mu / distance.powi(3)
+                    } else {
+                        Vec3::ZERO
+                    }
+                })
+                .sum();
+            asteroid.velocity += acceleration * dt;
+            transform.translation += asteroid.velocity * dt;
+        });
+}
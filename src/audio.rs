@@ -0,0 +1,430 @@
+//! Sound effects. The game ships without sound files, so every sound is
+//! synthesized as it plays (see [`Synth`]).
+
+use std::f32::consts::TAU;
+use std::time::Duration;
+
+use bevy::{
+    audio::{AddAudioSource, AudioSink, Decodable, Source},
+    prelude::*,
+    reflect::TypeUuid,
+};
+use bevy_egui::{egui, EguiContexts};
+
+use super::physics::{ApplyImpulse, Kinimatics, SimulationSet};
+use super::seeker::{Seeker, SeekerState};
+use super::ships::{Controlled, Detonation, Engine, Missile};
+use super::user_interface::CollisionWarning;
+
+pub struct AudioPlugin;
+
+impl Plugin for AudioPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_audio_source::<Synth>()
+            .init_resource::<AudioSettings>()
+            .add_startup_system(sounds_startup_system)
+            .add_system(engine_sound_system)
+            .add_system(warning_sound_system.after(SimulationSet))
+            .add_system(impact_sound_system.after(SimulationSet))
+            .add_system(click_sound_system)
+            .add_system(audio_panel_system);
+    }
+}
+
+/// Samples per second the synthesizer plays at.
+const SAMPLE_RATE: u32 = 44_100;
+
+/// Seconds between the beeps of the missile lock warning.
+const LOCK_WARNING_INTERVAL: f32 = 0.6;
+
+/// Smallest change in velocity an impulse makes which can be heard.
+const IMPACT_THRESHOLD: f32 = 2.0;
+
+/// How far from the middle of the screen sounds fade to half as loud, in
+/// screen widths.
+const HEARING_DISTANCE: f32 = 0.5;
+
+/// The shape of the wave a [`Synth`] plays.
+#[derive(Clone, Copy, Debug)]
+pub enum Waveform {
+    Sine,
+    Square,
+    Noise,
+    /// A low sine with its octave and some noise on top, for engines.
+    Rumble,
+}
+
+/// A sound which is made up as it plays, rather than loaded from a file. It
+/// slides from one pitch to the other over its length, fading out if it
+/// `decays`.
+#[derive(TypeUuid, Clone, Debug)]
+#[uuid = "2f6b8c1d-5e3a-4b9f-a7d2-8c4e1f0b6a37"]
+pub struct Synth {
+    pub waveform: Waveform,
+    /// Pitch at the start and the end, in hertz.
+    pub frequency: (f32, f32),
+    /// In seconds.
+    pub duration: f32,
+    pub decays: bool,
+    pub volume: f32,
+}
+
+impl Synth {
+    fn new(waveform: Waveform, frequency: f32, duration: f32) -> Self {
+        Self {
+            waveform,
+            frequency: (frequency, frequency),
+            duration,
+            decays: true,
+            volume: 1.0,
+        }
+    }
+}
+
+impl Decodable for Synth {
+    type DecoderItem = f32;
+    type Decoder = SynthDecoder;
+
+    fn decoder(&self) -> Self::Decoder {
+        SynthDecoder {
+            synth: self.clone(),
+            sample: 0,
+            samples: (self.duration * SAMPLE_RATE as f32) as u32,
+            phase: 0.0,
+            noise: 0x9e37_79b9,
+        }
+    }
+}
+
+/// Plays a [`Synth`], one sample at a time.
+pub struct SynthDecoder {
+    synth: Synth,
+    sample: u32,
+    samples: u32,
+    /// How far through the current cycle of the wave it is, from 0 to 1.
+    phase: f32,
+    /// State of the noise generator (xorshift).
+    noise: u32,
+}
+
+impl SynthDecoder {
+    /// Noise between -1 and 1.
+    fn noise(&mut self) -> f32 {
+        self.noise ^= self.noise << 13;
+        self.noise ^= self.noise >> 17;
+        self.noise ^= self.noise << 5;
+        self.noise as f32 / u32::MAX as f32 * 2.0 - 1.0
+    }
+}
+
+impl Iterator for SynthDecoder {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.sample >= self.samples {
+            return None;
+        }
+        let t = self.sample as f32 / self.samples as f32;
+        let (from, to) = self.synth.frequency;
+        self.phase = (self.phase + (from + (to - from) * t) / SAMPLE_RATE as f32).fract();
+        let value = match self.synth.waveform {
+            Waveform::Sine => (self.phase * TAU).sin(),
+            Waveform::Square => match self.phase < 0.5 {
+                true => 0.5,
+                false => -0.5,
+            },
+            Waveform::Noise => self.noise(),
+            Waveform::Rumble => {
+                0.6 * (self.phase * TAU).sin()
+                    + 0.3 * (self.phase * 2.0 * TAU).sin()
+                    + 0.1 * self.noise()
+            }
+        };
+        let envelope = match self.synth.decays {
+            true => (1.0 - t) * (1.0 - t),
+            false => 1.0,
+        };
+        self.sample += 1;
+        Some(value * envelope * self.synth.volume)
+    }
+}
+
+impl Source for SynthDecoder {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        Some(Duration::from_secs_f32(self.synth.duration))
+    }
+}
+
+/// What a sound is for, which decides which volume it's played at.
+#[derive(Clone, Copy, Debug)]
+pub enum Channel {
+    Engine,
+    Effects,
+    Interface,
+}
+
+/// Resource which holds how loud each kind of sound is played. The audio
+/// panel, opened from the tray, changes it.
+#[derive(Resource)]
+pub struct AudioSettings {
+    pub open: bool,
+    pub muted: bool,
+    pub master: f32,
+    pub engine: f32,
+    pub effects: f32,
+    pub interface: f32,
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        Self {
+            open: false,
+            muted: false,
+            master: 0.8,
+            engine: 0.5,
+            effects: 0.8,
+            interface: 0.5,
+        }
+    }
+}
+
+impl AudioSettings {
+    /// How loud to play sounds on `channel`, from 0 to 1.
+    pub fn volume(&self, channel: Channel) -> f32 {
+        if self.muted {
+            return 0.0;
+        }
+        self.master
+            * match channel {
+                Channel::Engine => self.engine,
+                Channel::Effects => self.effects,
+                Channel::Interface => self.interface,
+            }
+    }
+}
+
+/// Resource which holds every sound the game makes.
+#[derive(Resource)]
+pub struct Sounds {
+    /// The engine's hum, which plays all the time, and is turned up and down
+    /// with the controlled ship's throttle.
+    engine_sink: Handle<AudioSink>,
+    lock: Handle<Synth>,
+    proximity: Handle<Synth>,
+    explosion: Handle<Synth>,
+    impact: Handle<Synth>,
+    click: Handle<Synth>,
+}
+
+/// :SYSTEM: Makes every sound, and starts the engine hum, silent.
+fn sounds_startup_system(
+    mut commands: Commands,
+    mut synths: ResMut<Assets<Synth>>,
+    sinks: Res<Assets<AudioSink>>,
+    audio: Res<Audio<Synth>>,
+) {
+    // a whole number of cycles, so the loop doesn't click
+    let engine = synths.add(Synth {
+        decays: false,
+        ..Synth::new(Waveform::Rumble, 55.0, 1.0)
+    });
+    let engine_sink = audio.play_with_settings(engine, PlaybackSettings::LOOP.with_volume(0.0));
+    commands.insert_resource(Sounds {
+        engine_sink: sinks.get_handle(engine_sink),
+        lock: synths.add(Synth {
+            volume: 0.4,
+            ..Synth::new(Waveform::Square, 880.0, 0.12)
+        }),
+        proximity: synths.add(Synth {
+            frequency: (660.0, 440.0),
+            volume: 0.4,
+            ..Synth::new(Waveform::Square, 660.0, 0.4)
+        }),
+        explosion: synths.add(Synth::new(Waveform::Noise, 0.0, 1.2)),
+        impact: synths.add(Synth {
+            frequency: (120.0, 40.0),
+            ..Synth::new(Waveform::Sine, 120.0, 0.3)
+        }),
+        click: synths.add(Synth {
+            volume: 0.3,
+            ..Synth::new(Waveform::Sine, 1400.0, 0.03)
+        }),
+    });
+}
+
+/// :SYSTEM: Turns the engine hum up, and its pitch with it, as the controlled
+/// ship's throttle opens.
+fn engine_sound_system(
+    sounds: Option<Res<Sounds>>,
+    sinks: Res<Assets<AudioSink>>,
+    settings: Res<AudioSettings>,
+    player: Query<&Engine, With<Controlled>>,
+    time: Res<Time>,
+) {
+    let Some(sink) = sounds.and_then(|s| sinks.get(&s.engine_sink)) else {
+        return;
+    };
+    let throttle = player
+        .get_single()
+        .ok()
+        .filter(|e| e.fuel > 0.0)
+        .map_or(0.0, |e| e.throttle_fraction().clamp(0.0, 1.0));
+    // nothing's burning while the game stands still
+    let throttle = if time.is_paused() { 0.0 } else { throttle };
+    sink.set_volume(settings.volume(Channel::Engine) * throttle);
+    sink.set_speed(0.8 + 0.6 * throttle);
+}
+
+/// How loud something at `at` sounds, from where the camera is looking. Sounds
+/// fade with distance, measured in screen widths, so zooming out lets more of
+/// the map be heard.
+fn loudness(at: Vec3, cameras: &Query<(&GlobalTransform, &OrthographicProjection)>) -> f32 {
+    let Ok((camera, ortho)) = cameras.get_single() else {
+        return 1.0;
+    };
+    let width = ortho.area.width().max(1.0);
+    let distance = at.truncate().distance(camera.translation().truncate()) / width;
+    1.0 / (1.0 + distance / HEARING_DISTANCE)
+}
+
+/// :SYSTEM: Beeps while a missile is locked on to the controlled ship, and
+/// sounds an alarm when its course projection finds it about to hit
+/// something.
+#[allow(clippy::too_many_arguments)]
+fn warning_sound_system(
+    sounds: Option<Res<Sounds>>,
+    settings: Res<AudioSettings>,
+    audio: Res<Audio<Synth>>,
+    player: Query<Entity, With<Controlled>>,
+    missiles: Query<(&Missile, &Seeker)>,
+    mut warnings: EventReader<CollisionWarning>,
+    mut since_beep: Local<f32>,
+    time: Res<Time>,
+) {
+    let Some(sounds) = sounds else {
+        return;
+    };
+    let volume = settings.volume(Channel::Effects);
+    let player = player.get_single().ok();
+
+    let locked = missiles
+        .iter()
+        .any(|(m, s)| m.target.is_some() && m.target == player && s.state == SeekerState::Locked);
+    *since_beep += time.delta_seconds();
+    if locked && *since_beep >= LOCK_WARNING_INTERVAL {
+        *since_beep = 0.0;
+        audio.play_with_settings(
+            sounds.lock.clone(),
+            PlaybackSettings::ONCE.with_volume(volume),
+        );
+    }
+
+    if warnings.iter().any(|w| Some(w.ship) == player) {
+        audio.play_with_settings(
+            sounds.proximity.clone(),
+            PlaybackSettings::ONCE.with_volume(volume),
+        );
+    }
+}
+
+/// :SYSTEM: Plays detonations and hard knocks, quieter the further they are
+/// from where the camera is looking.
+fn impact_sound_system(
+    sounds: Option<Res<Sounds>>,
+    settings: Res<AudioSettings>,
+    audio: Res<Audio<Synth>>,
+    mut detonations: EventReader<Detonation>,
+    mut impulses: EventReader<ApplyImpulse>,
+    bodies: Query<(&Transform, &Kinimatics)>,
+    cameras: Query<(&GlobalTransform, &OrthographicProjection)>,
+) {
+    let Some(sounds) = sounds else {
+        detonations.clear();
+        impulses.clear();
+        return;
+    };
+    let volume = settings.volume(Channel::Effects);
+
+    for blast in detonations.iter() {
+        let loud = volume * loudness(blast.position, &cameras);
+        // bigger blasts boom lower
+        let speed = (10.0 / blast.blast_radius.max(1.0)).clamp(0.5, 1.5);
+        audio.play_with_settings(
+            sounds.explosion.clone(),
+            PlaybackSettings::ONCE.with_volume(loud).with_speed(speed),
+        );
+    }
+
+    for impulse in impulses.iter() {
+        let Ok((transform, kin)) = bodies.get(impulse.entity) else {
+            continue;
+        };
+        let dv = impulse.impulse.length() / kin.mass;
+        if !dv.is_finite() || dv < IMPACT_THRESHOLD {
+            continue;
+        }
+        let at = impulse.point.unwrap_or(transform.translation);
+        let loud = volume * loudness(at, &cameras) * (dv / 20.0).min(1.0);
+        audio.play_with_settings(
+            sounds.impact.clone(),
+            PlaybackSettings::ONCE.with_volume(loud),
+        );
+    }
+}
+
+/// :SYSTEM: Clicks whenever a click lands on the UI.
+fn click_sound_system(
+    mut contexts: EguiContexts,
+    sounds: Option<Res<Sounds>>,
+    settings: Res<AudioSettings>,
+    audio: Res<Audio<Synth>>,
+) {
+    let Some(sounds) = sounds else {
+        return;
+    };
+    let ctx = contexts.ctx_mut();
+    if ctx.input(|i| i.pointer.any_click()) && ctx.is_pointer_over_area() {
+        audio.play_with_settings(
+            sounds.click.clone(),
+            PlaybackSettings::ONCE.with_volume(settings.volume(Channel::Interface)),
+        );
+    }
+}
+
+/// Draws the mute switch and a volume slider for each channel.
+pub fn volume_controls(ui: &mut egui::Ui, settings: &mut AudioSettings) {
+    ui.checkbox(&mut settings.muted, "mute");
+    for (label, volume) in [
+        ("master", &mut settings.master),
+        ("engine", &mut settings.engine),
+        ("effects", &mut settings.effects),
+        ("interface", &mut settings.interface),
+    ] {
+        ui.add(egui::Slider::new(volume, 0.0..=1.0).text(label));
+    }
+}
+
+/// :SYSTEM: Shows the volume controls.
+fn audio_panel_system(mut contexts: EguiContexts, mut settings: ResMut<AudioSettings>) {
+    if !settings.open {
+        return;
+    }
+    let mut open = true;
+    egui::Window::new("Audio")
+        .open(&mut open)
+        .resizable(false)
+        .show(contexts.ctx_mut(), |ui| volume_controls(ui, &mut settings));
+    settings.open = open;
+}
@@ -0,0 +1,108 @@
+use bevy::prelude::*;
+
+use super::docking::DockingPort;
+use super::physics::{Attitude, Kinimatics, SimulationSet};
+use super::ships::{burn_towards, steer_towards, Engine};
+
+pub struct AutopilotPlugin;
+
+impl Plugin for AutopilotPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(
+            autopilot_system
+                .after(super::ai::ai_control_system)
+                .after(super::scripting::ship_program_system)
+                .before(super::physics::kinimatics_system)
+                .in_set(SimulationSet),
+        );
+    }
+}
+
+/// Speed, relative to the goal, at which the autopilot closes the distance.
+const APPROACH_SPEED: f32 = 40.0;
+
+/// Distance from the goal at which the autopilot starts to slow its approach.
+const APPROACH_DISTANCE: f32 = 100.0;
+
+/// Velocity error which maps to full throttle.
+const FULL_THROTTLE_ERROR: f32 = 30.0;
+
+/// Fraction of the docking range ships hold off at before trying to dock.
+const DOCKING_STANDOFF: f32 = 0.6;
+
+/// Where the autopilot is trying to be, and how fast it should be moving once
+/// it's there.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Goal {
+    pub position: Vec3,
+    pub velocity: Vec3,
+}
+
+/// :COMPONENT: Flies a ship to a goal, and keeps it there. While it has a
+/// goal, it overrides the engine settings of whatever else is flying the ship.
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct Autopilot {
+    pub goal: Option<Goal>,
+}
+
+impl Autopilot {
+    /// Holds off `target` on the side the ship is coming from, matching its
+    /// velocity. Once the ship is close and slow enough, points it at the target
+    /// and returns true, at which point it's ready to dock.
+    pub fn approach_for_docking(
+        &mut self,
+        transform: &Transform,
+        attitude: &mut Attitude,
+        velocity: Vec3,
+        target: Goal,
+        port: &DockingPort,
+        dt: f32,
+    ) -> bool {
+        let offset = transform.translation - target.position;
+        self.goal = Some(Goal {
+            position: target.position + offset.normalize_or_zero() * port.range * DOCKING_STANDOFF,
+            velocity: target.velocity,
+        });
+
+        let slow = (velocity - target.velocity).length() < port.max_relative_speed;
+        if offset.length() < port.range && slow {
+            steer_towards(transform, attitude, -offset, std::f32::consts::PI, dt);
+            return true;
+        }
+        false
+    }
+}
+
+/// :SYSTEM: Flies each ship with an autopilot goal towards it, easing off as it
+/// gets close.
+pub fn autopilot_system(
+    mut ships: Query<(
+        &Autopilot,
+        &Kinimatics,
+        &Transform,
+        &mut Attitude,
+        &mut Engine,
+    )>,
+    time: Res<Time>,
+) {
+    for (autopilot, kin, transform, mut attitude, mut engine) in ships.iter_mut() {
+        let Some(goal) = autopilot.goal else {
+            continue;
+        };
+
+        let offset = goal.position - transform.translation;
+        let approach = offset.normalize_or_zero()
+            * APPROACH_SPEED
+            * (offset.length() / APPROACH_DISTANCE).min(1.0);
+        let dv = goal.velocity + approach - kin.velocity;
+
+        burn_towards(
+            transform,
+            &mut attitude,
+            &mut engine,
+            dv,
+            FULL_THROTTLE_ERROR,
+            time.delta_seconds(),
+        );
+    }
+}
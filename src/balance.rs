@@ -0,0 +1,390 @@
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+use serde::{Deserialize, Serialize};
+
+use super::modules::WeaponMount;
+use super::power::PowerConsumer;
+use super::ships::{MissileKind, MissileLauncher};
+
+pub struct BalancePlugin;
+
+impl Plugin for BalancePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<Balance>()
+            .init_asset_loader::<BalanceLoader>()
+            .init_resource::<Balance>()
+            .add_startup_system(startup_system)
+            .add_system(reload_balance_system)
+            .add_system(apply_balance_system.after(reload_balance_system));
+    }
+}
+
+/// Where the balance file lives, under `assets`.
+const BALANCE_PATH: &str = "combat.balance.ron";
+
+/// Resource which holds every number that decides how combat plays out.
+///
+/// The numbers are read from `assets/combat.balance.ron` at startup, and again
+/// whenever the file changes, so they can be tuned while the game is running.
+/// Anything left out of the file keeps its default.
+#[derive(Resource, TypeUuid, Serialize, Deserialize, Clone, Debug)]
+#[uuid = "6f0c5a8e-3b7d-4c61-9f2e-1d84b7a2c953"]
+#[serde(default)]
+pub struct Balance {
+    pub missile: MissileBalance,
+    pub torpedo: MissileBalance,
+    pub mine: MissileBalance,
+    pub decoy: MissileBalance,
+    pub launcher: LauncherBalance,
+    pub seeker: SeekerBalance,
+    pub countermeasure: CountermeasureBalance,
+    pub beam: BeamBalance,
+    pub boarding: BoardingBalance,
+}
+
+impl Default for Balance {
+    fn default() -> Self {
+        Self {
+            missile: MissileBalance::default(),
+            torpedo: MissileBalance::torpedo(),
+            mine: MissileBalance::mine(),
+            decoy: MissileBalance::decoy(),
+            launcher: LauncherBalance::default(),
+            seeker: SeekerBalance::default(),
+            countermeasure: CountermeasureBalance::default(),
+            beam: BeamBalance::default(),
+            boarding: BoardingBalance::default(),
+        }
+    }
+}
+
+impl Balance {
+    /// The numbers for `kind` of missile.
+    pub fn ordnance(&self, kind: MissileKind) -> &MissileBalance {
+        match kind {
+            MissileKind::Missile => &self.missile,
+            MissileKind::Torpedo => &self.torpedo,
+            MissileKind::Mine => &self.mine,
+            MissileKind::Decoy => &self.decoy,
+        }
+    }
+}
+
+/// How hard missiles hit, and how far they fly. Torpedoes, mines and decoys
+/// each have their own; anything a variant leaves out of the file takes the
+/// plain missile's default, not the variant's.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct MissileBalance {
+    /// Damage dealt to a hull at the center of the blast.
+    pub damage: f32,
+    pub blast_radius: f32,
+    /// How quickly damage falls off towards the edge of the blast. One falls
+    /// off linearly; higher keeps more of the damage near the center.
+    pub falloff: f32,
+    /// Missiles detonate once they're this fraction of their blast radius
+    /// from their target.
+    pub proximity_fuse: f32,
+    /// Seconds after launch that a missile self destructs.
+    pub lifetime: f32,
+    pub mass: f32,
+    pub fuel: f32,
+    pub burn_rate: f32,
+    pub max_thrust: f32,
+    /// Fraction of the launcher's launch speed it leaves at.
+    pub launch_fraction: f32,
+    /// Chance, from 0 to 1, that a full strength flare right next to it pulls
+    /// it off its target (see [`CountermeasureBalance`]).
+    pub susceptibility: f32,
+}
+
+impl Default for MissileBalance {
+    fn default() -> Self {
+        Self {
+            damage: 60.0,
+            blast_radius: 15.0,
+            falloff: 1.0,
+            proximity_fuse: 0.5,
+            lifetime: 30.0,
+            mass: 1.0,
+            fuel: 100.0,
+            burn_rate: 10.0,
+            max_thrust: 40.0,
+            launch_fraction: 1.0,
+            susceptibility: 0.6,
+        }
+    }
+}
+
+impl MissileBalance {
+    pub fn torpedo() -> Self {
+        Self {
+            damage: 200.0,
+            blast_radius: 30.0,
+            lifetime: 60.0,
+            mass: 4.0,
+            burn_rate: 5.0,
+            max_thrust: 60.0,
+            launch_fraction: 0.5,
+            susceptibility: 0.3,
+            ..Default::default()
+        }
+    }
+
+    pub fn mine() -> Self {
+        Self {
+            damage: 120.0,
+            blast_radius: 25.0,
+            proximity_fuse: 1.0,
+            lifetime: 600.0,
+            mass: 2.0,
+            fuel: 0.0,
+            burn_rate: 0.0,
+            max_thrust: 0.0,
+            launch_fraction: 0.0,
+            susceptibility: 0.0,
+            ..Default::default()
+        }
+    }
+
+    pub fn decoy() -> Self {
+        Self {
+            damage: 0.0,
+            blast_radius: 0.0,
+            lifetime: 20.0,
+            burn_rate: 5.0,
+            max_thrust: 20.0,
+            susceptibility: 0.0,
+            ..Default::default()
+        }
+    }
+
+    /// Fraction of the full damage dealt `distance` from a blast of `radius`.
+    pub fn damage_fraction(&self, distance: f32, radius: f32) -> f32 {
+        (1.0 - distance / radius).max(0.0).powf(self.falloff)
+    }
+}
+
+/// How missile launchers are stocked and fired.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct LauncherBalance {
+    /// Missiles a newly fitted launcher carries.
+    pub ammo: u32,
+    /// Seconds between launches.
+    pub reload_time: f32,
+    /// Speed of a missile relative to the launching ship.
+    pub launch_speed: f32,
+    /// Heat dumped into the ship by each launch, in joules.
+    pub launch_heat: f32,
+    /// Power drawn by each weapon mount, in watts.
+    pub mount_power: f32,
+}
+
+impl Default for LauncherBalance {
+    fn default() -> Self {
+        Self {
+            ammo: 4,
+            reload_time: 5.0,
+            launch_speed: 20.0,
+            launch_heat: 600.0,
+            mount_power: 20.0,
+        }
+    }
+}
+
+/// How missiles home in on their targets.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct SeekerBalance {
+    /// Speed, relative to the target, which missiles try to close at.
+    pub closing_speed: f32,
+    /// Fastest a missile can turn, in radians per second.
+    pub turn_rate: f32,
+    /// Missiles only burn while pointed within this many radians of where they
+    /// want to go.
+    pub burn_cone: f32,
+    /// Radians either side of the nose the seeker head can see.
+    pub fov: f32,
+    /// Furthest the seeker head can see.
+    pub range: f32,
+    /// Seconds the target has to be held in view to lock on.
+    pub lock_time: f32,
+}
+
+impl Default for SeekerBalance {
+    fn default() -> Self {
+        Self {
+            closing_speed: 150.0,
+            turn_rate: 2.0 * std::f32::consts::PI,
+            burn_cone: 0.3,
+            fov: 0.8,
+            range: 1000.0,
+            lock_time: 0.5,
+        }
+    }
+}
+
+/// Which guided missiles a flare can pull off their target (see
+/// [`Flare`](super::countermeasures::Flare)).
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct CountermeasureBalance {
+    /// Furthest a missile can be from a flare and still be fooled by it.
+    pub range: f32,
+    /// Missiles only fall for flares within this many radians of their
+    /// target.
+    pub cone: f32,
+}
+
+impl Default for CountermeasureBalance {
+    fn default() -> Self {
+        Self {
+            range: 250.0,
+            cone: 0.8,
+        }
+    }
+}
+
+/// How lasers hit, and what they cost to fire (see
+/// [`Laser`](super::beams::Laser)).
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct BeamBalance {
+    /// Furthest a beam reaches.
+    pub range: f32,
+    /// Damage dealt to a hull for each second the beam is held on it.
+    pub damage: f32,
+    /// Beams swing onto the ship's target when it's within this many radians
+    /// of the nose, and fire straight ahead otherwise.
+    pub arc: f32,
+    /// Heat dumped into the ship while firing, in watts.
+    pub heat: f32,
+    /// Power drawn by each laser mount while it's idle, in watts...
+    pub idle_power: f32,
+    /// ...and while it's firing.
+    pub firing_power: f32,
+}
+
+impl Default for BeamBalance {
+    fn default() -> Self {
+        Self {
+            range: 300.0,
+            damage: 25.0,
+            arc: 0.3,
+            heat: 1500.0,
+            idle_power: 5.0,
+            firing_power: 150.0,
+        }
+    }
+}
+
+/// When a ship can be boarded, and how long it takes to take it (see
+/// [`Boarding`](super::boarding::Boarding)).
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct BoardingBalance {
+    /// A ship can only be boarded once its hull is down to this fraction of
+    /// its integrity.
+    pub hull_fraction: f32,
+    /// Furthest the boarding ship can be from its prize...
+    pub range: f32,
+    /// ...and fastest it can be moving relative to it.
+    pub max_speed: f32,
+    /// Seconds the boarding party takes to take the ship.
+    pub time: f32,
+}
+
+impl Default for BoardingBalance {
+    fn default() -> Self {
+        Self {
+            hull_fraction: 0.25,
+            range: 30.0,
+            max_speed: 5.0,
+            time: 10.0,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct BalanceLoader;
+
+impl AssetLoader for BalanceLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let balance: Balance = ron::de::from_bytes(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(balance));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["balance.ron"]
+    }
+}
+
+/// Resource which keeps the balance file loaded, so it is watched for changes.
+#[derive(Resource)]
+struct BalanceHandle(Handle<Balance>);
+
+fn startup_system(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(BalanceHandle(asset_server.load(BALANCE_PATH)));
+}
+
+/// :SYSTEM: Puts the balance file into effect whenever it loads or changes.
+fn reload_balance_system(
+    mut events: EventReader<AssetEvent<Balance>>,
+    assets: Res<Assets<Balance>>,
+    handle: Res<BalanceHandle>,
+    mut balance: ResMut<Balance>,
+) {
+    for event in events.iter() {
+        let (AssetEvent::Created { handle: changed } | AssetEvent::Modified { handle: changed }) =
+            event
+        else {
+            continue;
+        };
+        if *changed != handle.0 {
+            continue;
+        }
+        if let Some(loaded) = assets.get(changed) {
+            *balance = loaded.clone();
+            info!("loaded combat balance from {}", BALANCE_PATH);
+        }
+    }
+}
+
+/// :SYSTEM: Brings launchers and weapon mounts in line with the balance, when
+/// they are fitted and whenever the balance changes. Launchers keep the ammo
+/// they have left.
+fn apply_balance_system(
+    balance: Res<Balance>,
+    mut launchers: Query<&mut MissileLauncher>,
+    mut mounts: Query<(&mut PowerConsumer, Ref<WeaponMount>)>,
+) {
+    let launcher_balance = &balance.launcher;
+    for mut launcher in launchers.iter_mut() {
+        if launcher.is_added() {
+            launcher.ammo = launcher_balance.ammo;
+        } else if !balance.is_changed() {
+            continue;
+        }
+        launcher.reload_time = launcher_balance.reload_time;
+        launcher.launch_speed = launcher_balance.launch_speed;
+        launcher.launch_heat = launcher_balance.launch_heat;
+    }
+
+    for (mut consumer, fitted) in mounts.iter_mut() {
+        if fitted.is_added() || balance.is_changed() {
+            consumer.draw = launcher_balance.mount_power;
+        }
+    }
+}
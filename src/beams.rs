@@ -0,0 +1,220 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+use super::balance::Balance;
+use super::heat::Thermal;
+use super::input_map::{Action, InputMap};
+use super::level::AstroObject;
+use super::modules::BeamMount;
+use super::net::NetPlayer;
+use super::physics::SimulationSet;
+use super::power::{PowerConsumer, PowerGrid};
+use super::sandbox::Sandbox;
+use super::shields::Shield;
+use super::ships::{Controlled, Hull, PlayerShip};
+use super::targeting::Target;
+
+pub struct BeamsPlugin;
+
+impl Plugin for BeamsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            (
+                player_laser_system.before(beam_system),
+                beam_system.before(super::modules::loadout_system),
+            )
+                .in_set(SimulationSet),
+        )
+        .add_system(beam_draw_system);
+    }
+}
+
+/// How close a beam has to pass to a ship to hit it.
+const HIT_RADIUS: f32 = 5.0;
+
+/// :COMPONENT: Fires the ship's [`BeamMount`]s. Unlike a missile, the beam
+/// hits the first ship or body along it the instant it fires, and keeps
+/// damaging a ship for as long as it's held on it.
+///
+/// The cost is heat and power: while firing, each mount dumps heat into the
+/// ship and draws far more power than it does idle (see
+/// [`BeamBalance`](super::balance::BeamBalance)). A brownout weakens the beam,
+/// and an overheating ship can't fire it at all.
+#[derive(Reflect, Component, Default, Clone, Copy, Debug)]
+#[reflect(Component)]
+pub struct Laser {
+    /// Whether the trigger is held.
+    pub firing: bool,
+    /// What to swing the beam onto, when it's close enough to the nose.
+    pub target: Option<Entity>,
+    /// Where the beam stopped on the last tick, if it fired.
+    pub end: Option<Vec3>,
+    /// What the beam hit on the last tick.
+    pub hit: Option<Entity>,
+}
+
+/// :SYSTEM: Fires the controlled ship's laser while the key is held, at its
+/// [`Target`] if it has one.
+#[allow(clippy::type_complexity)]
+fn player_laser_system(
+    mut player: Query<(&mut Laser, Option<&Target>), (With<Controlled>, Without<NetPlayer>)>,
+    input: Res<Input<KeyCode>>,
+    map: Res<InputMap>,
+) {
+    for (mut laser, target) in player.iter_mut() {
+        laser.firing = map.pressed(Action::FireLaser, &input);
+        laser.target = target.map(|t| t.0);
+    }
+}
+
+/// :SYSTEM: Traces each firing laser out to whatever it hits first, damages
+/// it, and charges the ship for the shot.
+#[allow(clippy::type_complexity)]
+fn beam_system(
+    mut ships: Query<(
+        Entity,
+        &mut Laser,
+        &Transform,
+        &Children,
+        Option<&PowerGrid>,
+        Option<&mut Thermal>,
+        Option<&PlayerShip>,
+    )>,
+    mut mounts: Query<&mut PowerConsumer, With<BeamMount>>,
+    mut hulls: Query<(
+        Entity,
+        &Transform,
+        &mut Hull,
+        Option<&PlayerShip>,
+        Option<&mut Shield>,
+    )>,
+    bodies: Query<(&Transform, &AstroObject)>,
+    sandbox: Res<Sandbox>,
+    balance: Res<Balance>,
+    time: Res<Time>,
+) {
+    let stats = &balance.beam;
+    let dt = time.delta_seconds();
+
+    for (entity, mut laser, transform, children, grid, thermal, shooter) in ships.iter_mut() {
+        let supply = grid.map_or(1.0, |g| g.supply);
+        let derating = thermal.as_ref().map_or(1.0, |t| t.derating());
+        let firing = laser.firing && supply > 0.0 && derating >= 1.0;
+
+        let mut beams = 0;
+        let mut mount_iter = mounts.iter_many_mut(children);
+        while let Some(mut consumer) = mount_iter.fetch_next() {
+            let draw = match firing && consumer.enabled {
+                true => stats.firing_power,
+                false => stats.idle_power,
+            };
+            if consumer.draw != draw {
+                consumer.draw = draw;
+            }
+            if consumer.enabled {
+                beams += 1;
+            }
+        }
+
+        if !firing || beams == 0 {
+            if laser.end.is_some() || laser.hit.is_some() {
+                laser.end = None;
+                laser.hit = None;
+            }
+            continue;
+        }
+
+        let origin = transform.translation;
+        let nose = transform.rotation.mul_vec3(Vec3::Y);
+        let direction = laser
+            .target
+            .and_then(|t| hulls.get(t).ok())
+            .map(|(_, t, ..)| t.translation - origin)
+            .filter(|to| to.length() <= stats.range && nose.angle_between(*to) <= stats.arc)
+            .map_or(nose, |to| to.normalize());
+
+        // distance along the beam to wherever `at` blocks it, if it does
+        let along = |at: Vec3, radius: f32| {
+            let offset = at - origin;
+            let along = offset.dot(direction);
+            let miss = (offset - direction * along).length();
+            (along >= 0.0 && miss <= radius)
+                .then(|| (along - (radius * radius - miss * miss).sqrt()).max(0.0))
+        };
+
+        let mut reach = stats.range;
+        let mut hit = None;
+        for (other, t, ..) in hulls.iter() {
+            match along(t.translation, HIT_RADIUS) {
+                Some(d) if other != entity && d < reach => {
+                    reach = d;
+                    hit = Some(other);
+                }
+                _ => (),
+            }
+        }
+        for (t, body) in bodies.iter() {
+            if let Some(d) = along(t.translation, body.radius).filter(|d| *d < reach) {
+                reach = d;
+                hit = None;
+            }
+        }
+
+        let strength = beams as f32 * supply * dt;
+        if let Some((_, t, mut hull, player, shield)) = hit.and_then(|h| hulls.get_mut(h).ok()) {
+            if !sandbox.exempts(player.is_some()) {
+                let mut damage = stats.damage * strength;
+                if let Some(mut shield) = shield {
+                    damage = shield.absorb(t.rotation, origin - t.translation, damage);
+                }
+                hull.integrity -= damage;
+            }
+        }
+        match thermal {
+            Some(mut thermal) if !sandbox.exempts(shooter.is_some()) => {
+                thermal.add_heat(stats.heat * strength)
+            }
+            _ => (),
+        }
+        laser.end = Some(origin + direction * reach);
+        laser.hit = hit;
+    }
+}
+
+/// :SYSTEM: Draws each beam that fired on the last tick.
+fn beam_draw_system(
+    mut contexts: EguiContexts,
+    lasers: Query<(&Laser, &Transform)>,
+    cameras: Query<(&Camera, &GlobalTransform), With<Camera2d>>,
+) {
+    let Ok((camera, camera_transform)) = cameras.get_single() else {
+        return;
+    };
+    let Some(viewport) = camera.logical_viewport_size() else {
+        return;
+    };
+    // the viewport has +Y up, egui has it down
+    let to_screen = |p: Vec3| {
+        camera
+            .world_to_viewport(camera_transform, p)
+            .map(|at| egui::pos2(at.x, viewport.y - at.y))
+    };
+
+    let painter = contexts
+        .ctx_mut()
+        .layer_painter(egui::LayerId::background());
+    for (laser, transform) in lasers.iter() {
+        let Some(end) = laser.end else {
+            continue;
+        };
+        if let (Some(from), Some(to)) = (to_screen(transform.translation), to_screen(end)) {
+            // a soft glow under a bright core
+            let glow = egui::Color32::from_rgba_unmultiplied(255, 60, 40, 80);
+            painter.line_segment([from, to], egui::Stroke::new(5.0, glow));
+            painter.line_segment([from, to], egui::Stroke::new(1.5, egui::Color32::WHITE));
+            if laser.hit.is_some() {
+                painter.circle_filled(to, 4.0, egui::Color32::from_rgb(255, 200, 120));
+            }
+        }
+    }
+}
@@ -0,0 +1,158 @@
+use std::collections::{HashMap, HashSet};
+
+use bevy::prelude::*;
+
+use super::clock::SimulationClock;
+use super::physics::SimulationSet;
+use super::scripting::{ShipProgram, Value};
+use super::ships::Faction;
+
+pub struct BlackboardPlugin;
+
+impl Plugin for BlackboardPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Relay>()
+            .add_event::<Publish>()
+            .add_system(
+                relay_system
+                    .after(super::scripting::ship_program_system)
+                    .in_set(SimulationSet),
+            );
+    }
+}
+
+/// Speed at which updates to a blackboard travel between ships.
+pub const SIGNAL_SPEED: f32 = 300.0;
+
+/// One value on a blackboard, and when it was published.
+#[derive(Clone, Debug)]
+pub struct Entry {
+    pub values: Vec<Value>,
+    /// Mission elapsed time the values were published. Newer entries win.
+    pub published: f64,
+}
+
+/// :COMPONENT: A ship's copy of the blackboard it shares with the rest of its
+/// faction. Ships without one don't take part.
+///
+/// Anything a ship publishes lands on its own copy straight away, then reaches
+/// the others over comms, taking longer the further away they are. Copies can
+/// disagree for a while, but settle on the most recently published values.
+#[derive(Component, Default, Clone, Debug)]
+pub struct Blackboard {
+    pub entries: HashMap<String, Entry>,
+    /// Keys whose updates raise a `board_update` event on the ship's program.
+    pub subscriptions: HashSet<String>,
+}
+
+impl Blackboard {
+    /// Files an entry, unless a newer one is already here. Returns whether the
+    /// entry was accepted.
+    fn store(&mut self, key: &str, entry: Entry) -> bool {
+        if self
+            .entries
+            .get(key)
+            .is_some_and(|e| e.published > entry.published)
+        {
+            return false;
+        }
+        self.entries.insert(key.to_string(), entry);
+        true
+    }
+
+    /// Publishes values from this ship, returning the update to send to everyone
+    /// else. The ship's own program isn't told about its own updates.
+    pub fn publish(&mut self, from: Entity, key: &str, values: Vec<Value>, now: f64) -> Publish {
+        let entry = Entry {
+            values,
+            published: now,
+        };
+        self.store(key, entry.clone());
+        Publish {
+            from,
+            key: key.to_string(),
+            entry,
+        }
+    }
+}
+
+/// :EVENT: Sent when a ship publishes something to its faction's blackboard.
+#[derive(Clone, Debug)]
+pub struct Publish {
+    pub from: Entity,
+    pub key: String,
+    pub entry: Entry,
+}
+
+struct Delivery {
+    to: Entity,
+    key: String,
+    entry: Entry,
+    arrives: f64,
+}
+
+/// Resource which holds blackboard updates that are still in flight.
+#[derive(Resource, Default)]
+pub struct Relay {
+    in_flight: Vec<Delivery>,
+}
+
+/// :SYSTEM: Sends blackboard updates on their way, and delivers the ones which
+/// have arrived. Ships subscribed to an updated key get a `board_update` event
+/// with the key.
+#[allow(clippy::type_complexity)]
+fn relay_system(
+    mut relay: ResMut<Relay>,
+    mut published: EventReader<Publish>,
+    mut boards: Query<(
+        Entity,
+        &mut Blackboard,
+        &GlobalTransform,
+        Option<&Faction>,
+        Option<&mut ShipProgram>,
+    )>,
+    clock: Res<SimulationClock>,
+) {
+    let now = clock.elapsed;
+
+    for update in published.iter() {
+        let Ok((_, _, origin, faction, _)) = boards.get(update.from) else {
+            continue;
+        };
+        let (origin, faction) = (origin.translation(), faction.copied());
+
+        for (entity, _, transform, other_faction, _) in boards.iter() {
+            if entity == update.from || other_faction.copied() != faction {
+                continue;
+            }
+            let distance = origin.distance(transform.translation());
+            relay.in_flight.push(Delivery {
+                to: entity,
+                key: update.key.clone(),
+                entry: update.entry.clone(),
+                arrives: now + (distance / SIGNAL_SPEED) as f64,
+            });
+        }
+    }
+
+    let (arrived, in_flight) = relay
+        .in_flight
+        .drain(..)
+        .partition::<Vec<_>, _>(|d| d.arrives <= now);
+    relay.in_flight = in_flight;
+
+    for delivery in arrived {
+        // ships that have been destroyed, or left the blackboard, miss out
+        let Ok((_, mut board, _, _, program)) = boards.get_mut(delivery.to) else {
+            continue;
+        };
+        let subscribed = board.subscriptions.contains(&delivery.key);
+        if board.store(&delivery.key, delivery.entry) && subscribed {
+            if let Some(mut program) = program {
+                program
+                    .vm
+                    .raise("board_update", vec![Value::Str(delivery.key)]);
+            }
+        }
+    }
+}
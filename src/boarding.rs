@@ -0,0 +1,257 @@
+use bevy::prelude::*;
+
+use super::ai::AiController;
+use super::balance::{Balance, BoardingBalance};
+use super::input_map::{Action, InputMap};
+use super::net::NetPlayer;
+use super::physics::{Kinimatics, SimulationSet};
+use super::scripting::{ScriptEvent, ShipProgram, Value};
+use super::sensors::SensorBundle;
+use super::ships::{Controlled, Faction, Hull, PlayerShip};
+use super::targeting::Target;
+
+pub struct BoardingPlugin;
+
+impl Plugin for BoardingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<BoardShip>().add_systems(
+            (
+                player_board_system.before(board_system),
+                board_system,
+                boarding_system.after(board_system),
+            )
+                .in_set(SimulationSet),
+        );
+    }
+}
+
+/// :COMPONENT: A boarding party from this ship is working its way through
+/// `prize`. The ship has to stay alongside while it does: if it drifts out of
+/// range, or the prize gets away or changes hands first, the boarding fails.
+/// Once `time` is up, the prize is captured.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct Boarding {
+    pub prize: Entity,
+    /// Seconds the boarding party has been aboard.
+    pub progress: f32,
+    /// Seconds it needs to take the ship.
+    pub time: f32,
+}
+
+/// :EVENT: Requests that `captor` starts boarding `prize`. Ignored unless the
+/// prize is hostile and can be boarded (see [`boardable`]).
+#[derive(Clone, Copy, Debug)]
+pub struct BoardShip {
+    pub captor: Entity,
+    pub prize: Entity,
+}
+
+/// Whether a ship with `hull`, `offset` from the captor and moving at
+/// `relative_velocity` to it, can be boarded.
+pub fn boardable(
+    stats: &BoardingBalance,
+    hull: &Hull,
+    offset: Vec3,
+    relative_velocity: Vec3,
+) -> bool {
+    hull.integrity <= hull.max_integrity * stats.hull_fraction
+        && offset.length() <= stats.range
+        && relative_velocity.length() <= stats.max_speed
+}
+
+/// :SYSTEM: Boards the controlled ship's target.
+#[allow(clippy::type_complexity)]
+fn player_board_system(
+    player: Query<(Entity, &Target), (With<Controlled>, Without<NetPlayer>)>,
+    input: Res<Input<KeyCode>>,
+    map: Res<InputMap>,
+    mut boards: EventWriter<BoardShip>,
+) {
+    if !map.just_pressed(Action::Board, &input) {
+        return;
+    }
+    for (captor, target) in player.iter() {
+        boards.send(BoardShip {
+            captor,
+            prize: target.0,
+        });
+    }
+}
+
+/// :SYSTEM: Sends a boarding party over for each [`BoardShip`] that can go
+/// ahead.
+///
+/// A `boarding_started` event is raised with the captor and the prize, on the
+/// captor's program as `boarding_started` with the prize, and on the prize's
+/// as `boarded` with the captor.
+#[allow(clippy::type_complexity)]
+pub fn board_system(
+    mut commands: Commands,
+    mut requests: EventReader<BoardShip>,
+    ships: Query<(
+        &Transform,
+        &Kinimatics,
+        Option<&Faction>,
+        Option<&Hull>,
+        Option<&Boarding>,
+    )>,
+    mut programs: Query<&mut ShipProgram>,
+    mut script_events: EventWriter<ScriptEvent>,
+    balance: Res<Balance>,
+) {
+    let stats = &balance.boarding;
+    for request in requests.iter() {
+        let (Ok(captor), Ok(prize)) = (ships.get(request.captor), ships.get(request.prize)) else {
+            continue;
+        };
+        let (transform, kin, faction, _, boarding) = captor;
+        let (prize_transform, prize_kin, prize_faction, hull, _) = prize;
+        let hostile = faction
+            .copied()
+            .unwrap_or_default()
+            .is_hostile_to(&prize_faction.copied().unwrap_or_default());
+        let Some(hull) = hull.filter(|_| hostile && boarding.is_none()) else {
+            continue;
+        };
+        if !boardable(
+            stats,
+            hull,
+            prize_transform.translation - transform.translation,
+            prize_kin.velocity - kin.velocity,
+        ) {
+            continue;
+        }
+
+        commands.entity(request.captor).insert(Boarding {
+            prize: request.prize,
+            progress: 0.0,
+            time: stats.time,
+        });
+        announce(
+            "boarding_started",
+            ("boarding_started", "boarded"),
+            *request,
+            &mut programs,
+            &mut script_events,
+        );
+    }
+}
+
+/// :SYSTEM: Moves each boarding party along, and hands the prize over to the
+/// captor once it's done.
+///
+/// The prize takes the captor's [`Faction`]. Taken by a player's ship, it
+/// joins the player's ships, and its AI is relieved; taken by anyone else, it
+/// gets an AI of its own, which patrols around where it was taken.
+///
+/// Captures are raised as `ship_captured`, on the captor's program as
+/// `captured` and on the prize's as `captured_by`. A boarding which fails is
+/// raised as `boarding_failed`, on both programs, each with the other ship.
+#[allow(clippy::type_complexity)]
+fn boarding_system(
+    mut commands: Commands,
+    mut captors: Query<(
+        Entity,
+        &mut Boarding,
+        &Transform,
+        &Kinimatics,
+        Option<&Faction>,
+        Option<&PlayerShip>,
+    )>,
+    prizes: Query<(&Transform, &Kinimatics, &Hull, Option<&Faction>)>,
+    mut programs: Query<&mut ShipProgram>,
+    mut script_events: EventWriter<ScriptEvent>,
+    balance: Res<Balance>,
+    time: Res<Time>,
+) {
+    let stats = &balance.boarding;
+    for (captor, mut boarding, transform, kin, faction, player) in captors.iter_mut() {
+        let pair = BoardShip {
+            captor,
+            prize: boarding.prize,
+        };
+        let faction = faction.copied().unwrap_or_default();
+        let held = prizes
+            .get(boarding.prize)
+            .ok()
+            .filter(|(t, k, hull, f)| {
+                faction.is_hostile_to(&f.copied().unwrap_or_default())
+                    && boardable(
+                        stats,
+                        hull,
+                        t.translation - transform.translation,
+                        k.velocity - kin.velocity,
+                    )
+            })
+            .map(|(t, ..)| t.translation);
+
+        let Some(position) = held else {
+            commands.entity(captor).remove::<Boarding>();
+            announce(
+                "boarding_failed",
+                ("boarding_failed", "boarding_failed"),
+                pair,
+                &mut programs,
+                &mut script_events,
+            );
+            continue;
+        };
+
+        boarding.progress += time.delta_seconds();
+        if boarding.progress < boarding.time {
+            continue;
+        }
+
+        commands.entity(captor).remove::<Boarding>();
+        let mut prize = commands.entity(boarding.prize);
+        prize.insert(faction);
+        match player {
+            Some(_) => {
+                prize.remove::<AiController>().insert(PlayerShip);
+            }
+            None => {
+                prize.remove::<(PlayerShip, Controlled)>().insert((
+                    AiController {
+                        patrol_center: position,
+                        ..Default::default()
+                    },
+                    SensorBundle::default(),
+                ));
+            }
+        }
+        announce(
+            "ship_captured",
+            ("captured", "captured_by"),
+            pair,
+            &mut programs,
+            &mut script_events,
+        );
+    }
+}
+
+/// Raises `name` for the mission with the captor and the prize, and the pair
+/// of `raised` names on the captor's and the prize's programs, each with the
+/// other ship.
+fn announce(
+    name: &str,
+    raised: (&str, &str),
+    ships: BoardShip,
+    programs: &mut Query<&mut ShipProgram>,
+    script_events: &mut EventWriter<ScriptEvent>,
+) {
+    for (ship, raised, other) in [
+        (ships.captor, raised.0, ships.prize),
+        (ships.prize, raised.1, ships.captor),
+    ] {
+        if let Ok(mut program) = programs.get_mut(ship) {
+            program.vm.raise(raised, vec![Value::from_entity(other)]);
+        }
+    }
+    script_events.send(ScriptEvent {
+        name: name.to_string(),
+        args: vec![
+            Value::from_entity(ships.captor),
+            Value::from_entity(ships.prize),
+        ],
+    });
+}
@@ -0,0 +1,232 @@
+//! Campaigns: missions played one after another, where the player's ship
+//! carries on from each mission into the next with whatever fuel, damage,
+//! cargo and modules it finished with.
+//!
+//! A campaign is described in `assets/campaigns/<file>`. Start the game with
+//! `--campaign=<file>` to play one.
+
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use serde::{Deserialize, Serialize};
+
+use super::director::MissionDirector;
+use super::impactor::Impactor;
+use super::level::AstroObject;
+use super::missions::{ObjectiveStatus, Objectives};
+use super::physics::Kinimatics;
+use super::ships::Controlled;
+
+pub struct CampaignPlugin;
+
+impl Plugin for CampaignPlugin {
+    fn build(&self, app: &mut App) {
+        let Some(path) =
+            std::env::args().find_map(|a| a.strip_prefix("--campaign=").map(|p| p.to_string()))
+        else {
+            return;
+        };
+        match Campaign::load(&path) {
+            Ok(campaign) => {
+                app.insert_resource(CampaignProgress::new(campaign))
+                    .add_startup_system(
+                        campaign_startup_system.after(super::director::startup_system),
+                    )
+                    .add_system(campaign_panel_system);
+            }
+            Err(e) => warn!("couldn't load campaign {}: {}", path, e),
+        }
+    }
+}
+
+/// When a [`Branch`] is taken, judged once the mission is over.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub enum When {
+    Won,
+    Lost,
+    /// The objective with this key was completed.
+    Completed(String),
+    /// The objective with this key was failed.
+    Failed(String),
+}
+
+impl When {
+    fn holds(&self, objectives: &Objectives) -> bool {
+        let status = |key: &str| {
+            objectives
+                .objectives
+                .iter()
+                .find(|o| o.key == key)
+                .map(|o| o.status)
+        };
+        match self {
+            When::Won => objectives.outcome() == ObjectiveStatus::Complete,
+            When::Lost => objectives.outcome() == ObjectiveStatus::Failed,
+            When::Completed(key) => status(key) == Some(ObjectiveStatus::Complete),
+            When::Failed(key) => status(key) == Some(ObjectiveStatus::Failed),
+        }
+    }
+}
+
+/// Goes on to the mission called `next` when `when` holds.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Branch {
+    pub when: When,
+    pub next: String,
+}
+
+/// One mission of a campaign, and where the campaign goes from it.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CampaignMission {
+    /// The mission's director script, relative to the assets folder.
+    pub script: String,
+    /// Tried in order, and the first which holds is taken. The campaign ends
+    /// if none of them do.
+    #[serde(default)]
+    pub branches: Vec<Branch>,
+}
+
+/// A campaign descriptor.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Campaign {
+    pub title: String,
+    /// The mission the campaign starts with.
+    pub start: String,
+    pub missions: HashMap<String, CampaignMission>,
+}
+
+impl Campaign {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let path = format!("assets/campaigns/{}", path);
+        let campaign: Self = std::fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|s| ron::from_str(&s).map_err(|e| e.to_string()))?;
+        campaign.check()?;
+        Ok(campaign)
+    }
+
+    /// Makes sure every mission named in the campaign is in it.
+    fn check(&self) -> Result<(), String> {
+        let named = std::iter::once(&self.start).chain(
+            self.missions
+                .values()
+                .flat_map(|m| m.branches.iter().map(|b| &b.next)),
+        );
+        for name in named {
+            if !self.missions.contains_key(name) {
+                return Err(format!("no mission called \"{}\"", name));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Resource which holds the campaign being played, and how far through it
+/// the player is.
+#[derive(Resource)]
+pub struct CampaignProgress {
+    pub campaign: Campaign,
+    /// The mission being played.
+    pub current: String,
+    /// The missions played so far, and whether each one was won.
+    pub played: Vec<(String, bool)>,
+}
+
+impl CampaignProgress {
+    pub fn new(campaign: Campaign) -> Self {
+        Self {
+            current: campaign.start.clone(),
+            campaign,
+            played: Vec::new(),
+        }
+    }
+
+    fn mission(&self) -> &CampaignMission {
+        &self.campaign.missions[&self.current]
+    }
+
+    /// The mission to go on to, now the current one is over, if the campaign
+    /// goes on.
+    fn next(&self, objectives: &Objectives) -> Option<&str> {
+        self.mission()
+            .branches
+            .iter()
+            .find(|b| b.when.holds(objectives))
+            .map(|b| b.next.as_str())
+    }
+}
+
+/// :SYSTEM: Starts the campaign's first mission, in place of the one given on
+/// the command line.
+fn campaign_startup_system(
+    mut commands: Commands,
+    progress: Res<CampaignProgress>,
+    asset_server: Res<AssetServer>,
+) {
+    commands.insert_resource(MissionDirector::new(
+        asset_server.load(progress.mission().script.as_str()),
+    ));
+}
+
+/// :SYSTEM: Once a mission is over, shows where the campaign goes next, and
+/// moves on to it when the player's ready.
+///
+/// Moving on clears away everything the mission left flying, besides the
+/// planets and the player's ship. The ship is carried into the next mission
+/// as it is, with its fuel, damage, cargo and modules.
+#[allow(clippy::type_complexity)]
+fn campaign_panel_system(
+    mut commands: Commands,
+    mut contexts: EguiContexts,
+    mut progress: ResMut<CampaignProgress>,
+    mut objectives: ResMut<Objectives>,
+    leftovers: Query<
+        (Entity, Option<&AstroObject>, Option<&Impactor>),
+        (With<Kinimatics>, Without<Parent>, Without<Controlled>),
+    >,
+    asset_server: Res<AssetServer>,
+) {
+    if objectives.ended_at.is_none() {
+        return;
+    }
+    let won = objectives.outcome() == ObjectiveStatus::Complete;
+    let next = progress.next(&objectives).map(|n| n.to_string());
+
+    let mut advance = false;
+    egui::Window::new(progress.campaign.title.as_str())
+        .collapsible(false)
+        .anchor(egui::Align2::CENTER_TOP, [0.0, 40.0])
+        .show(contexts.ctx_mut(), |ui| {
+            for (name, won) in progress.played.iter() {
+                ui.label(format!("{} {}", if *won { "[x]" } else { "[-]" }, name));
+            }
+            ui.label(format!(
+                "{} {}",
+                if won { "[x]" } else { "[-]" },
+                progress.current
+            ));
+            ui.separator();
+            match &next {
+                Some(next) => advance = ui.button(format!("On to {}", next)).clicked(),
+                None => {
+                    ui.label("The campaign is over.");
+                }
+            }
+        });
+    let (true, Some(next)) = (advance, next) else {
+        return;
+    };
+
+    let current = std::mem::replace(&mut progress.current, next);
+    progress.played.push((current, won));
+    for (entity, astro, impactor) in leftovers.iter() {
+        if astro.is_none() || impactor.is_some() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+    *objectives = Objectives::default();
+    commands.insert_resource(MissionDirector::new(
+        asset_server.load(progress.mission().script.as_str()),
+    ));
+}
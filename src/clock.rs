@@ -0,0 +1,105 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use serde::{Deserialize, Serialize};
+
+use super::physics::SimulationSet;
+
+pub struct ClockPlugin;
+
+impl Plugin for ClockPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SimulationClock>()
+            .register_type::<SimulationClock>()
+            .add_system(
+                clock_system
+                    .before(super::physics::kinimatics_system)
+                    .in_set(SimulationSet),
+            )
+            .add_system(clock_display_system);
+    }
+}
+
+/// 2187-01-01 00:00:00 UTC, in seconds since the unix epoch.
+pub const DEFAULT_EPOCH: f64 = 6_847_891_200.0;
+
+/// Resource which keeps the universe's time.
+///
+/// Unlike [`Time`], the clock only moves while the simulation does, so it is
+/// what logs, scheduled commands, and scenario events should be timestamped with.
+#[derive(Resource, Reflect, Serialize, Deserialize, Clone, Copy, Debug)]
+#[reflect(Resource)]
+pub struct SimulationClock {
+    /// Mission elapsed time, in seconds.
+    pub elapsed: f64,
+    /// Date and time the mission started, in seconds since the unix epoch.
+    pub epoch: f64,
+}
+
+impl Default for SimulationClock {
+    fn default() -> Self {
+        Self {
+            elapsed: 0.0,
+            epoch: DEFAULT_EPOCH,
+        }
+    }
+}
+
+impl SimulationClock {
+    /// Mission elapsed time, in seconds.
+    pub fn met(&self) -> f32 {
+        self.elapsed as f32
+    }
+
+    /// The current date and time in the universe, as `YYYY-MM-DD hh:mm:ss`.
+    pub fn date(&self) -> String {
+        let seconds = (self.epoch + self.elapsed).floor() as i64;
+        let (days, time) = (seconds.div_euclid(86_400), seconds.rem_euclid(86_400));
+        let (year, month, day) = civil_from_days(days);
+        format!(
+            "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+            year,
+            month,
+            day,
+            time / 3600,
+            time / 60 % 60,
+            time % 60
+        )
+    }
+}
+
+/// Formats a mission elapsed time as `T+hh:mm:ss`.
+pub fn format_met(seconds: f32) -> String {
+    let s = seconds.max(0.0) as u64;
+    format!("T+{:02}:{:02}:{:02}", s / 3600, s / 60 % 60, s % 60)
+}
+
+/// Converts days since 1970-01-01 to a (year, month, day) date, in the
+/// proleptic Gregorian calendar.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    // see http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + (month <= 2) as i64;
+    (year, month, day)
+}
+
+/// :SYSTEM: Moves the clock forward with the simulation.
+pub fn clock_system(mut clock: ResMut<SimulationClock>, time: Res<Time>) {
+    clock.elapsed += time.delta_seconds_f64();
+}
+
+/// :SYSTEM: Shows the date, and the mission elapsed time.
+fn clock_display_system(mut contexts: EguiContexts, clock: Res<SimulationClock>) {
+    egui::Area::new("clock")
+        .anchor(egui::Align2::RIGHT_TOP, [-8.0, 8.0])
+        .show(contexts.ctx_mut(), |ui| {
+            ui.label(clock.date());
+            ui.label(format_met(clock.met()));
+        });
+}
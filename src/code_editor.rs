@@ -0,0 +1,304 @@
+use bevy::prelude::*;
+use bevy_egui::{
+    egui::{self, text::LayoutJob, FontId, TextFormat},
+    EguiContexts, EguiSet,
+};
+
+use super::profiles::Profile;
+use super::scheduler::Scheduler;
+use super::scripting::{self, ScriptSource, ShipProgram, VmState};
+use super::ships::{Controlled, Engine};
+
+pub struct CodeEditorPlugin;
+
+impl Plugin for CodeEditorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CodeEditor>()
+            .add_system(
+                keyboard_capture_system
+                    .in_base_set(CoreSet::PreUpdate)
+                    .after(EguiSet::ProcessInput),
+            )
+            .add_system(code_editor_system);
+    }
+}
+
+/// Words the script parser treats as instructions.
+const INSTRUCTIONS: &[&str] = &[
+    "set", "add", "sub", "mul", "div", "mod", "min", "max", "neg", "abs", "sqrt", "sin", "cos",
+    "jmp", "jeq", "jne", "jlt", "jle", "jgt", "jge", "gosub", "return", "call", "print", "on",
+    "wait", "yield", "halt",
+];
+
+/// Resource which holds the state of the program editor. F4 opens and closes it.
+///
+/// The editor works on a copy of the selected ship's program. Running it
+/// compiles the copy into a new source, just for that ship, so the file on
+/// disk and any other ships running it are left alone.
+#[derive(Resource, Default)]
+pub struct CodeEditor {
+    pub open: bool,
+    ship: Option<Entity>,
+    buffer: String,
+    /// The source `buffer` was copied from.
+    loaded: Option<Handle<ScriptSource>>,
+    /// Name the buffer is saved to the profile's program library under.
+    library_name: String,
+}
+
+impl CodeEditor {
+    /// The ship whose program is being edited.
+    pub fn ship(&self) -> Option<Entity> {
+        self.ship
+    }
+}
+
+/// :SYSTEM: Keeps keystrokes meant for a text box from flying the ship.
+pub fn keyboard_capture_system(mut contexts: EguiContexts, mut input: ResMut<Input<KeyCode>>) {
+    if contexts.ctx_mut().wants_keyboard_input() {
+        input.reset_all();
+    }
+}
+
+/// Colors a program's source the way the script parser reads it.
+fn highlight(source: &str) -> LayoutJob {
+    let font = FontId::monospace(13.0);
+    let mut job = LayoutJob::default();
+    let mut push = |text: &str, color: egui::Color32| {
+        job.append(text, 0.0, TextFormat::simple(font.clone(), color));
+    };
+
+    for line in source.split_inclusive('\n') {
+        // comments start at the first `;` which isn't inside a string
+        let mut in_string = false;
+        let comment_start = line.find(|c| {
+            in_string ^= c == '"';
+            c == ';' && !in_string
+        });
+        let (code, comment) = line.split_at(comment_start.unwrap_or(line.len()));
+
+        let mut first = true;
+        let mut rest = code;
+        while !rest.is_empty() {
+            let start = rest.len() - rest.trim_start().len();
+            if start > 0 {
+                push(&rest[..start], egui::Color32::GRAY);
+                rest = &rest[start..];
+                continue;
+            }
+
+            let end = if let Some(string) = rest.strip_prefix('"') {
+                string.find('"').map_or(rest.len(), |i| i + 2)
+            } else {
+                rest.find(char::is_whitespace).unwrap_or(rest.len())
+            };
+            let token = &rest[..end];
+            let color = if token.starts_with('"') {
+                egui::Color32::from_rgb(206, 145, 120)
+            } else if token.parse::<f64>().is_ok() {
+                egui::Color32::from_rgb(181, 206, 168)
+            } else if first && token.ends_with(':') {
+                egui::Color32::from_rgb(220, 220, 170)
+            } else if first && INSTRUCTIONS.contains(&token) {
+                egui::Color32::from_rgb(86, 156, 214)
+            } else if token == "->" {
+                egui::Color32::GRAY
+            } else {
+                egui::Color32::LIGHT_GRAY
+            };
+            push(token, color);
+            // a label can share its line with an instruction
+            first = first && token.ends_with(':');
+            rest = &rest[end..];
+        }
+
+        if !comment.is_empty() {
+            push(comment, egui::Color32::from_rgb(106, 153, 85));
+        }
+    }
+    job
+}
+
+/// :SYSTEM: Shows the program editor.
+#[allow(clippy::type_complexity)]
+fn code_editor_system(
+    mut contexts: EguiContexts,
+    mut editor: ResMut<CodeEditor>,
+    mut programs: Query<(
+        Entity,
+        &mut ShipProgram,
+        Option<&mut Engine>,
+        Option<&Name>,
+        Option<&Controlled>,
+    )>,
+    mut sources: ResMut<Assets<ScriptSource>>,
+    mut scheduler: ResMut<Scheduler>,
+    mut profile: ResMut<Profile>,
+    input: Res<Input<KeyCode>>,
+) {
+    if input.just_pressed(KeyCode::F4) {
+        editor.open = !editor.open;
+    }
+    if !editor.open {
+        return;
+    }
+    let editor = &mut *editor;
+
+    // default to the player's ship, or failing that, any ship with a program
+    if !editor.ship.is_some_and(|s| programs.contains(s)) {
+        editor.ship = programs
+            .iter()
+            .max_by_key(|(_, _, _, _, controlled)| controlled.is_some())
+            .map(|(entity, ..)| entity);
+        editor.loaded = None;
+    }
+
+    let label = |entity: Entity, name: Option<&Name>| match name {
+        Some(name) => format!("{} ({})", name, entity.index()),
+        None => format!("ship {}", entity.index()),
+    };
+
+    let mut open = true;
+    egui::Window::new("Program editor")
+        .open(&mut open)
+        .default_size([480.0, 520.0])
+        .show(contexts.ctx_mut(), |ui| {
+            let current = editor
+                .ship
+                .and_then(|s| programs.get(s).ok())
+                .map_or("none".to_string(), |(e, _, _, name, _)| label(e, name));
+            egui::ComboBox::from_label("Ship")
+                .selected_text(current)
+                .show_ui(ui, |ui| {
+                    for (entity, _, _, name, _) in programs.iter() {
+                        let selected = ui.selectable_value(
+                            &mut editor.ship,
+                            Some(entity),
+                            label(entity, name),
+                        );
+                        if selected.changed() {
+                            editor.loaded = None;
+                        }
+                    }
+                });
+
+            let Some(Ok((entity, mut program, engine, _, _))) =
+                editor.ship.map(|s| programs.get_mut(s))
+            else {
+                ui.label("No ship is running a program.");
+                return;
+            };
+
+            if editor.loaded.as_ref() != Some(&program.source) {
+                if let Some(source) = sources.get(&program.source) {
+                    editor.buffer = source.0.clone();
+                    editor.loaded = Some(program.source.clone());
+                }
+            }
+
+            ui.horizontal(|ui| {
+                let run = ui.button("Run").clicked();
+                let stop = ui.button("Stop").clicked();
+                if run {
+                    let source = sources.add(ScriptSource(editor.buffer.clone()));
+                    editor.loaded = Some(source.clone());
+                    program.source = source;
+                    program.reset();
+                    program.console.push("restarted".to_string());
+                    scripting::release_controls(
+                        entity,
+                        engine.map(|e| e.into_inner()),
+                        &mut scheduler,
+                    );
+                } else if stop {
+                    program.stop();
+                    program.console.push("stopped".to_string());
+                    scripting::release_controls(
+                        entity,
+                        engine.map(|e| e.into_inner()),
+                        &mut scheduler,
+                    );
+                }
+
+                let (color, state) = match program.vm.state() {
+                    VmState::Running | VmState::Waiting(_) => (egui::Color32::GREEN, "running"),
+                    VmState::Break => (egui::Color32::YELLOW, "at breakpoint"),
+                    VmState::Halted => (egui::Color32::GRAY, "halted"),
+                    VmState::Faulted(_) => (egui::Color32::RED, "faulted"),
+                };
+                ui.colored_label(color, state);
+                let meter = program.meter;
+                let color = if meter.overran {
+                    egui::Color32::YELLOW
+                } else {
+                    egui::Color32::GRAY
+                };
+                ui.colored_label(
+                    color,
+                    format!(
+                        "{} instructions, {} µs",
+                        meter.instructions,
+                        meter.time.as_micros()
+                    ),
+                );
+            });
+
+            ui.horizontal(|ui| {
+                egui::ComboBox::from_id_source("library")
+                    .selected_text("Library")
+                    .show_ui(ui, |ui| {
+                        for (name, source) in profile.programs.iter() {
+                            if ui.selectable_label(false, name).clicked() {
+                                editor.buffer = source.clone();
+                                editor.library_name = name.clone();
+                            }
+                        }
+                    });
+                ui.text_edit_singleline(&mut editor.library_name);
+                if ui.button("Save").clicked() && !editor.library_name.is_empty() {
+                    profile
+                        .programs
+                        .insert(editor.library_name.clone(), editor.buffer.clone());
+                    let line = match profile.save() {
+                        Ok(()) => format!("saved {} to the library", editor.library_name),
+                        Err(e) => format!("error: couldn't save the library: {}", e),
+                    };
+                    program.console.push(line);
+                }
+            });
+
+            let mut layouter = |ui: &egui::Ui, source: &str, wrap_width: f32| {
+                let mut job = highlight(source);
+                job.wrap.max_width = wrap_width;
+                ui.fonts(|f| f.layout_job(job))
+            };
+            egui::ScrollArea::vertical()
+                .id_source("source")
+                .max_height(340.0)
+                .show(ui, |ui| {
+                    ui.add(
+                        egui::TextEdit::multiline(&mut editor.buffer)
+                            .code_editor()
+                            .desired_width(f32::INFINITY)
+                            .desired_rows(20)
+                            .layouter(&mut layouter),
+                    );
+                });
+
+            ui.separator();
+            ui.label("Console");
+            egui::ScrollArea::vertical()
+                .id_source("console")
+                .stick_to_bottom(true)
+                .show(ui, |ui| {
+                    for line in program.console.iter() {
+                        if line.starts_with("error:") {
+                            ui.colored_label(egui::Color32::RED, line);
+                        } else {
+                            ui.monospace(line);
+                        }
+                    }
+                });
+        });
+    editor.open = open;
+}
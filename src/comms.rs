@@ -0,0 +1,180 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+
+use super::blackboard::SIGNAL_SPEED;
+use super::clock::SimulationClock;
+use super::jamming::Jamming;
+use super::physics::SimulationSet;
+use super::scripting::{ShipProgram, Value};
+
+pub struct CommsPlugin;
+
+impl Plugin for CommsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Airwaves>()
+            .register_type::<Transceiver>()
+            .add_system(
+                comms_system
+                    .after(super::scripting::ship_program_system)
+                    .in_set(SimulationSet),
+            );
+    }
+}
+
+/// Largest payload a single message can carry, in bytes.
+pub const MAX_PAYLOAD: usize = 256;
+
+/// Most messages a transceiver holds, either waiting to go out or waiting to be
+/// read. Past this, new outgoing messages are refused, and the oldest incoming
+/// ones are dropped.
+pub const QUEUE_LIMIT: usize = 32;
+
+/// A message sent between ships.
+#[derive(Clone, Debug)]
+pub struct Message {
+    pub from: Entity,
+    /// The ship the message is for, or `None` for anyone listening.
+    pub to: Option<Entity>,
+    pub payload: Vec<u8>,
+}
+
+/// :COMPONENT: A ship's radio, for sending messages to other ships.
+///
+/// Messages go out in the order they were queued, no faster than the
+/// transceiver's bandwidth allows, or [`Jamming`] lets through. They reach every ship within `range` (or
+/// just the one they're addressed to), taking longer the further they travel.
+#[derive(Reflect, Component, Clone, Debug)]
+#[reflect(Component)]
+pub struct Transceiver {
+    pub range: f32,
+    /// Bytes sent per second.
+    pub bandwidth: f32,
+    /// Bytes which can be sent right now. Builds up to one second's worth, or
+    /// one full message, while the transceiver is idle.
+    credit: f32,
+    #[reflect(ignore)]
+    outbox: VecDeque<Message>,
+    #[reflect(ignore)]
+    pub inbox: VecDeque<Message>,
+}
+
+impl Default for Transceiver {
+    fn default() -> Self {
+        Self {
+            range: 1000.0,
+            bandwidth: 512.0,
+            credit: 0.0,
+            outbox: VecDeque::new(),
+            inbox: VecDeque::new(),
+        }
+    }
+}
+
+impl Transceiver {
+    /// Queues a message to go out.
+    pub fn send(&mut self, message: Message) -> Result<(), String> {
+        if message.payload.len() > MAX_PAYLOAD {
+            return Err(format!(
+                "a message can't carry more than {} bytes",
+                MAX_PAYLOAD
+            ));
+        }
+        if self.outbox.len() >= QUEUE_LIMIT {
+            return Err("too many messages waiting to be sent".to_string());
+        }
+        self.outbox.push_back(message);
+        Ok(())
+    }
+
+    fn receive(&mut self, message: Message) {
+        if self.inbox.len() >= QUEUE_LIMIT {
+            self.inbox.pop_front();
+        }
+        self.inbox.push_back(message);
+    }
+}
+
+struct InFlight {
+    to: Entity,
+    message: Message,
+    arrives: f64,
+}
+
+/// Resource which holds messages that have been sent, but haven't arrived.
+#[derive(Resource, Default)]
+pub struct Airwaves {
+    in_flight: Vec<InFlight>,
+}
+
+/// :SYSTEM: Sends queued messages as bandwidth allows, and delivers the ones
+/// which have arrived. Ships with a program get a `message` event, with the
+/// sender, for each one.
+#[allow(clippy::type_complexity)]
+fn comms_system(
+    mut airwaves: ResMut<Airwaves>,
+    mut radios: Query<(
+        Entity,
+        &mut Transceiver,
+        &GlobalTransform,
+        Option<&mut ShipProgram>,
+        Option<&Jamming>,
+    )>,
+    clock: Res<SimulationClock>,
+    time: Res<Time>,
+) {
+    let now = clock.elapsed;
+    let dt = time.delta_seconds();
+
+    let mut sent = Vec::new();
+    for (entity, mut radio, transform, _, jamming) in radios.iter_mut() {
+        let bandwidth = radio.bandwidth * jamming.map_or(1.0, |j| j.clarity());
+        // a full size message always fits, however slow the link
+        let burst = bandwidth.max(MAX_PAYLOAD as f32);
+        radio.credit = (radio.credit + bandwidth * dt).min(burst);
+        while radio
+            .outbox
+            .front()
+            .is_some_and(|m| m.payload.len() as f32 <= radio.credit)
+        {
+            let message = radio.outbox.pop_front().unwrap();
+            radio.credit -= message.payload.len() as f32;
+            sent.push((entity, transform.translation(), radio.range, message));
+        }
+    }
+
+    for (from, origin, range, message) in sent {
+        for (entity, _, transform, ..) in radios.iter() {
+            if entity == from || message.to.is_some_and(|to| to != entity) {
+                continue;
+            }
+            let distance = origin.distance(transform.translation());
+            if distance > range {
+                continue;
+            }
+            airwaves.in_flight.push(InFlight {
+                to: entity,
+                message: message.clone(),
+                arrives: now + (distance / SIGNAL_SPEED) as f64,
+            });
+        }
+    }
+
+    let (arrived, in_flight) = airwaves
+        .in_flight
+        .drain(..)
+        .partition::<Vec<_>, _>(|m| m.arrives <= now);
+    airwaves.in_flight = in_flight;
+
+    for delivery in arrived {
+        let Ok((_, mut radio, _, program, _)) = radios.get_mut(delivery.to) else {
+            continue;
+        };
+        if let Some(mut program) = program {
+            program
+                .vm
+                .raise("message", vec![Value::from_entity(delivery.message.from)]);
+        }
+        radio.receive(delivery.message);
+    }
+}
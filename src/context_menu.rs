@@ -0,0 +1,241 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+use super::docking::DockingPort;
+use super::level::AstroObject;
+use super::orders::{self, Order, OrderQueue, OrdersPanel};
+use super::physics::Kinimatics;
+use super::ships::{Controlled, Ship};
+use super::star_system::Dormant;
+use super::targeting::Target;
+
+pub struct ContextMenuPlugin;
+
+impl Plugin for ContextMenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ContextMenu>()
+            .add_system(context_menu_system)
+            .add_system(inspect_panel_system.after(context_menu_system));
+    }
+}
+
+/// How close the pointer has to be to a ship or body to open its menu, in
+/// pixels.
+const GRAB_RADIUS: f32 = 12.0;
+
+/// Resource which holds the state of the context menu.
+///
+/// Right clicking a ship or body opens a menu for it. Target locks the
+/// controlled ship on to it (see [`Target`]). The rest are orders: attack it,
+/// follow it, dock with it or intercept it. They go to the ships selected in
+/// the [`OrdersPanel`], or to the controlled ship if none are, and are queued
+/// up behind what the ships were doing if shift is held. Inspect opens a
+/// window showing where it is and how it's moving.
+#[derive(Resource, Default)]
+pub struct ContextMenu {
+    /// Entity the menu is open for.
+    pub target: Option<Entity>,
+    /// Where the menu was opened, in egui's screen coordinates.
+    at: egui::Pos2,
+    /// Entity being shown in the inspect window.
+    pub inspecting: Option<Entity>,
+}
+
+/// :SYSTEM: Opens the context menu on whatever's right clicked, and gives
+/// orders from it.
+#[allow(clippy::type_complexity, clippy::too_many_arguments)]
+pub fn context_menu_system(
+    mut commands: Commands,
+    mut contexts: EguiContexts,
+    mut menu: ResMut<ContextMenu>,
+    mouse: Res<Input<MouseButton>>,
+    keys: Res<Input<KeyCode>>,
+    windows: Query<&Window>,
+    cameras: Query<(&Camera, &GlobalTransform), With<Camera2d>>,
+    entities: Query<
+        (
+            Entity,
+            &GlobalTransform,
+            Option<&Name>,
+            Option<&Ship>,
+            Option<&DockingPort>,
+        ),
+        (Or<(With<Ship>, With<AstroObject>)>, Without<Dormant>),
+    >,
+    controlled: Query<Entity, With<Controlled>>,
+    mut queues: Query<&mut OrderQueue>,
+    panel: Res<OrdersPanel>,
+) {
+    let ctx = contexts.ctx_mut();
+    if menu.target.is_some_and(|e| !entities.contains(e)) {
+        menu.target = None;
+    }
+    if keys.just_pressed(KeyCode::Escape) {
+        menu.target = None;
+    }
+
+    // ctrl right click is for orders
+    let ctrl = keys.any_pressed([KeyCode::LControl, KeyCode::RControl]);
+    if mouse.just_pressed(MouseButton::Right) && !ctrl && !ctx.wants_pointer_input() {
+        let Ok((camera, camera_transform)) = cameras.get_single() else {
+            return;
+        };
+        let Some(viewport) = camera.logical_viewport_size() else {
+            return;
+        };
+        let Some(cursor) = windows.get_single().ok().and_then(|w| w.cursor_position()) else {
+            return;
+        };
+        // the viewport has +Y up, egui has it down
+        let pointer = egui::pos2(cursor.x, viewport.y - cursor.y);
+        menu.target = entities
+            .iter()
+            .filter_map(|(entity, transform, ..)| {
+                let at = camera.world_to_viewport(camera_transform, transform.translation())?;
+                let distance = egui::pos2(at.x, viewport.y - at.y).distance(pointer);
+                (distance < GRAB_RADIUS).then_some((entity, distance))
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(entity, _)| entity);
+        menu.at = pointer;
+    }
+
+    let Some((target, _, name, ship, port)) = menu.target.and_then(|e| entities.get(e).ok()) else {
+        return;
+    };
+    let ordered: Vec<Entity> = match panel.selected.is_empty() {
+        true => controlled.iter().collect(),
+        false => panel.selected.clone(),
+    };
+    let can_order = ordered.iter().any(|s| *s != target);
+
+    let mut order = None;
+    let response = egui::Area::new("context_menu")
+        .fixed_pos(menu.at)
+        .order(egui::Order::Foreground)
+        .show(ctx, |ui| {
+            egui::Frame::popup(ui.style()).show(ui, |ui| {
+                ui.set_min_width(100.0);
+                ui.strong(name.map_or(format!("{:?}", target), |n| n.to_string()));
+                ui.separator();
+                let player = controlled.get_single().ok().filter(|p| *p != target);
+                if ui
+                    .add_enabled(player.is_some(), egui::Button::new("Target"))
+                    .clicked()
+                {
+                    if let Some(player) = player {
+                        commands.entity(player).insert(Target(target));
+                    }
+                    menu.target = None;
+                }
+                let ship = ship.is_some() && can_order;
+                if ui.add_enabled(ship, egui::Button::new("Attack")).clicked() {
+                    order = Some(Order::Attack(target));
+                }
+                if ui.add_enabled(ship, egui::Button::new("Follow")).clicked() {
+                    order = Some(Order::Follow {
+                        target,
+                        distance: panel.distance,
+                    });
+                }
+                let dock = port.is_some() && can_order;
+                if ui.add_enabled(dock, egui::Button::new("Dock")).clicked() {
+                    order = Some(Order::Dock(target));
+                }
+                if ui
+                    .add_enabled(can_order, egui::Button::new("Intercept"))
+                    .clicked()
+                {
+                    order = Some(Order::Intercept(target));
+                }
+                if ui.button("Inspect").clicked() {
+                    menu.inspecting = Some(target);
+                    menu.target = None;
+                }
+            });
+        })
+        .response;
+
+    if let Some(order) = order {
+        let replace = !keys.any_pressed([KeyCode::LShift, KeyCode::RShift]);
+        for ship in ordered {
+            orders::give_order(&mut commands, &mut queues, ship, order, replace);
+        }
+        menu.target = None;
+    }
+    if mouse.just_pressed(MouseButton::Left) && !response.hovered() {
+        menu.target = None;
+    }
+}
+
+/// :SYSTEM: Shows where the entity picked with Inspect is, and how it's moving,
+/// relative to the controlled ship as well as absolutely.
+#[allow(clippy::type_complexity)]
+fn inspect_panel_system(
+    mut contexts: EguiContexts,
+    mut menu: ResMut<ContextMenu>,
+    entities: Query<(
+        &GlobalTransform,
+        Option<&Name>,
+        Option<&Kinimatics>,
+        Option<&AstroObject>,
+    )>,
+    controlled: Query<Entity, With<Controlled>>,
+) {
+    let Some(entity) = menu.inspecting else {
+        return;
+    };
+    let Ok((transform, name, kin, body)) = entities.get(entity) else {
+        menu.inspecting = None;
+        return;
+    };
+    let position = transform.translation();
+    let velocity = kin.map_or(Vec3::ZERO, |k| k.velocity);
+    let relative = controlled
+        .get_single()
+        .ok()
+        .filter(|ship| *ship != entity)
+        .and_then(|ship| entities.get(ship).ok())
+        .map(|(t, _, k, _)| {
+            let offset = position - t.translation();
+            let closing = velocity - k.map_or(Vec3::ZERO, |k| k.velocity);
+            (offset.length(), -closing.dot(offset.normalize_or_zero()))
+        });
+
+    let mut open = true;
+    egui::Window::new(name.map_or(format!("{:?}", entity), |n| n.to_string()))
+        .id(egui::Id::new("inspect"))
+        .open(&mut open)
+        .resizable(false)
+        .show(contexts.ctx_mut(), |ui| {
+            egui::Grid::new("inspect_grid").show(ui, |ui| {
+                ui.label("position");
+                ui.label(format!("{:.0}, {:.0}", position.x, position.y));
+                ui.end_row();
+                ui.label("velocity");
+                ui.label(format!("{:.1}, {:.1}", velocity.x, velocity.y));
+                ui.end_row();
+                if let Some(kin) = kin {
+                    ui.label("mass");
+                    ui.label(format!("{:.3e}", kin.mass));
+                    ui.end_row();
+                }
+                if let Some(body) = body {
+                    ui.label("radius");
+                    ui.label(format!("{:.1}", body.radius));
+                    ui.end_row();
+                }
+                if let Some((distance, closing)) = relative {
+                    ui.label("distance");
+                    ui.label(format!("{:.0}", distance));
+                    ui.end_row();
+                    ui.label("closing");
+                    ui.label(format!("{:.1} m/s", closing));
+                    ui.end_row();
+                }
+            });
+        });
+    if !open {
+        menu.inspecting = None;
+    }
+}
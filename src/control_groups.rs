@@ -0,0 +1,108 @@
+use bevy::prelude::*;
+use bevy_egui::EguiContexts;
+
+use super::orders::OrdersPanel;
+use super::ships::Ship;
+
+pub struct ControlGroupsPlugin;
+
+impl Plugin for ControlGroupsPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<ControlGroups>()
+            .add_system(control_group_system.before(super::orders::orders_panel_system));
+    }
+}
+
+/// Number keys for each control group, 0 to 9.
+const GROUP_KEYS: [KeyCode; 10] = [
+    KeyCode::Key0,
+    KeyCode::Key1,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::Key5,
+    KeyCode::Key6,
+    KeyCode::Key7,
+    KeyCode::Key8,
+    KeyCode::Key9,
+];
+
+/// :COMPONENT: Which of the numbered control groups a ship is in, as a bit
+/// per group.
+///
+/// Groups live on the ships themselves, so they're saved and loaded along with
+/// them.
+#[derive(Reflect, Component, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[reflect(Component)]
+pub struct ControlGroups {
+    pub groups: u16,
+}
+
+impl ControlGroups {
+    pub fn contains(&self, group: usize) -> bool {
+        self.groups & (1 << group) != 0
+    }
+
+    pub fn set(&mut self, group: usize, member: bool) {
+        if member {
+            self.groups |= 1 << group;
+        } else {
+            self.groups &= !(1 << group);
+        }
+    }
+
+    /// The groups the ship is in, in order.
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..GROUP_KEYS.len()).filter(|g| self.contains(*g))
+    }
+}
+
+/// :SYSTEM: Handles the control group keys.
+///
+/// Ctrl + number makes the selected ships the group, shift + number adds them
+/// to it, and number on its own selects the group, so the next order goes to
+/// all of it.
+fn control_group_system(
+    mut commands: Commands,
+    mut contexts: EguiContexts,
+    mut panel: ResMut<OrdersPanel>,
+    mut ships: Query<(Entity, Option<&mut ControlGroups>), With<Ship>>,
+    keys: Res<Input<KeyCode>>,
+) {
+    if contexts.ctx_mut().wants_keyboard_input() {
+        return;
+    }
+    let Some(group) = GROUP_KEYS.iter().position(|k| keys.just_pressed(*k)) else {
+        return;
+    };
+    let ctrl = keys.any_pressed([KeyCode::LControl, KeyCode::RControl]);
+    let shift = keys.any_pressed([KeyCode::LShift, KeyCode::RShift]);
+
+    if !ctrl && !shift {
+        panel.selected = ships
+            .iter()
+            .filter(|(_, groups)| groups.as_ref().is_some_and(|g| g.contains(group)))
+            .map(|(entity, _)| entity)
+            .collect();
+        panel.open |= !panel.selected.is_empty();
+        return;
+    }
+
+    for (entity, groups) in ships.iter_mut() {
+        let selected = panel.selected.contains(&entity);
+        match groups {
+            Some(mut groups) => {
+                let member = selected || (shift && groups.contains(group));
+                if groups.contains(group) != member {
+                    groups.set(group, member);
+                }
+            }
+            None if selected => {
+                let mut groups = ControlGroups::default();
+                groups.set(group, true);
+                commands.entity(entity).insert(groups);
+            }
+            None => {}
+        }
+    }
+}
@@ -0,0 +1,251 @@
+use bevy::prelude::*;
+
+use super::asteroid::Rng;
+use super::balance::Balance;
+use super::input_map::{Action, InputMap};
+use super::net::NetPlayer;
+use super::physics::{Kinimatics, KinimaticsBundle, SimulationSet};
+use super::sandbox::Sandbox;
+use super::scheduler::{self, Alarm, Scheduler};
+use super::ships::{Controlled, Faction, Missile, PlayerShip};
+use super::user_interface::MapIcon;
+
+pub struct CountermeasuresPlugin;
+
+impl Plugin for CountermeasuresPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<DeployCountermeasure>()
+            .insert_resource(Seduction(Rng(0)))
+            .add_systems(
+                (
+                    player_countermeasure_system.before(deploy_system),
+                    deploy_system,
+                    seduction_system.after(deploy_system),
+                    burnout_system,
+                )
+                    .in_set(SimulationSet),
+            );
+    }
+}
+
+/// Name of the [`Alarm`] which goes off when a flare burns out.
+const BURNOUT: &str = "burnout";
+
+/// :COMPONENT: Dispenses flares and chaff, which can pull guided missiles off
+/// of the ship (see [`Flare`]).
+#[derive(Reflect, Component, Clone, Copy)]
+#[reflect(Component)]
+pub struct Countermeasure {
+    /// Salvos left.
+    pub charges: u32,
+    /// Flares thrown out with each salvo.
+    pub salvo: u32,
+    /// Seconds between salvos.
+    pub reload_time: f32,
+    /// Seconds until the next salvo is ready.
+    pub cooldown: f32,
+    /// Seconds each flare burns for.
+    pub lifetime: f32,
+    /// Speed flares are thrown out at, relative to the ship.
+    pub eject_speed: f32,
+    /// How convincing each flare is, from 0 to 1. It scales the chance a
+    /// missile takes the bait.
+    pub strength: f32,
+}
+
+impl Default for Countermeasure {
+    fn default() -> Self {
+        Self {
+            charges: 6,
+            salvo: 3,
+            reload_time: 2.0,
+            cooldown: 0.0,
+            lifetime: 4.0,
+            eject_speed: 15.0,
+            strength: 1.0,
+        }
+    }
+}
+
+impl Countermeasure {
+    pub fn ready(&self) -> bool {
+        self.charges > 0 && self.cooldown <= 0.0
+    }
+}
+
+/// :COMPONENT: A flare or a bundle of chaff. It shows up on sensors like
+/// something worth chasing for a few seconds, then burns out. Any missile
+/// which was chasing it is left without a target.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct Flare {
+    /// The ship which threw it out.
+    pub ship: Entity,
+    pub strength: f32,
+}
+
+/// :EVENT: Requests that `ship` throws out a salvo from its [`Countermeasure`].
+pub struct DeployCountermeasure {
+    pub ship: Entity,
+}
+
+/// Resource which holds the dice rolled for each missile a flare tries to
+/// pull away.
+#[derive(Resource)]
+struct Seduction(Rng);
+
+/// :SYSTEM: Throws out a salvo from the controlled ship.
+#[allow(clippy::type_complexity)]
+fn player_countermeasure_system(
+    player: Query<Entity, (With<Controlled>, With<Countermeasure>, Without<NetPlayer>)>,
+    input: Res<Input<KeyCode>>,
+    map: Res<InputMap>,
+    mut deploys: EventWriter<DeployCountermeasure>,
+) {
+    if !map.just_pressed(Action::Countermeasure, &input) {
+        return;
+    }
+    for ship in player.iter() {
+        deploys.send(DeployCountermeasure { ship });
+    }
+}
+
+/// :SYSTEM: Throws out a salvo of flares for each [`DeployCountermeasure`]
+/// whose dispenser is ready, fanned out around the back of the ship.
+#[allow(clippy::type_complexity)]
+fn deploy_system(
+    mut commands: Commands,
+    mut events: EventReader<DeployCountermeasure>,
+    mut dispensers: Query<(
+        &mut Countermeasure,
+        &Transform,
+        &Kinimatics,
+        Option<&Faction>,
+        Option<&PlayerShip>,
+    )>,
+    mut scheduler: ResMut<Scheduler>,
+    time: Res<Time>,
+    sandbox: Res<Sandbox>,
+) {
+    for (mut dispenser, ..) in dispensers.iter_mut() {
+        dispenser.cooldown = (dispenser.cooldown - time.delta_seconds()).max(0.0);
+    }
+
+    for event in events.iter() {
+        let Ok((mut dispenser, transform, kin, faction, player)) = dispensers.get_mut(event.ship)
+        else {
+            continue;
+        };
+        if !dispenser.ready() {
+            continue;
+        }
+        if !sandbox.exempts(player.is_some()) {
+            dispenser.charges -= 1;
+        }
+        dispenser.cooldown = dispenser.reload_time;
+
+        let back = transform.rotation.mul_vec3(Vec3::NEG_Y);
+        let salvo = dispenser.salvo.max(1);
+        for i in 0..salvo {
+            // spread evenly across a right angle
+            let spread = match salvo {
+                1 => 0.0,
+                n => (i as f32 / (n - 1) as f32 - 0.5) * std::f32::consts::FRAC_PI_2,
+            };
+            let out = Quat::from_rotation_z(spread).mul_vec3(back);
+            let flare = commands
+                .spawn((
+                    Name::new("Flare"),
+                    Flare {
+                        ship: event.ship,
+                        strength: dispenser.strength,
+                    },
+                    KinimaticsBundle::build()
+                        .insert_mass(0.1)
+                        .insert_translation(transform.translation + out * 10.0)
+                        .insert_velocity(kin.velocity + out * dispenser.eject_speed),
+                ))
+                .with_children(|p| {
+                    p.spawn((
+                        SpriteBundle {
+                            sprite: Sprite {
+                                color: Color::rgb(1.0, 0.9, 0.5),
+                                custom_size: Some(Vec2::splat(3.0)),
+                                ..Default::default()
+                            },
+                            ..Default::default()
+                        },
+                        MapIcon,
+                    ));
+                })
+                .id();
+            if let Some(faction) = faction {
+                commands.entity(flare).insert(*faction);
+            }
+            scheduler.after(
+                dispenser.lifetime as f64,
+                scheduler::Action::Alarm(Alarm {
+                    entity: flare,
+                    name: BURNOUT,
+                }),
+            );
+        }
+    }
+}
+
+/// :SYSTEM: Gives each guided missile chasing the ship which threw out a new
+/// flare a chance to switch to it.
+///
+/// A missile only sees flares within range of it, and within a cone around
+/// where its target is (see
+/// [`CountermeasureBalance`](super::balance::CountermeasureBalance)). The chance it takes
+/// the bait is its kind's susceptibility, times the flare's strength, falling
+/// off to nothing at the edge of the range.
+fn seduction_system(
+    flares: Query<(Entity, &Flare, &Transform), Added<Flare>>,
+    mut missiles: Query<(&mut Missile, &Transform)>,
+    targets: Query<&Transform, Without<Missile>>,
+    mut seduction: ResMut<Seduction>,
+    balance: Res<Balance>,
+) {
+    let countermeasure = &balance.countermeasure;
+    for (flare, stats, flare_transform) in flares.iter() {
+        for (mut missile, transform) in missiles.iter_mut() {
+            let Some(target) = missile
+                .target
+                .filter(|t| *t == stats.ship && missile.kind.seeks())
+            else {
+                continue;
+            };
+            let Ok(target_transform) = targets.get(target) else {
+                continue;
+            };
+            let to_flare = flare_transform.translation - transform.translation;
+            let to_target = target_transform.translation - transform.translation;
+            let distance = to_flare.length();
+            if distance > countermeasure.range
+                || to_flare.angle_between(to_target) > countermeasure.cone
+            {
+                continue;
+            }
+            let chance = balance.ordnance(missile.kind).susceptibility
+                * stats.strength
+                * (1.0 - distance / countermeasure.range);
+            if seduction.0.next() < chance {
+                missile.target = Some(flare);
+            }
+        }
+    }
+}
+
+/// :SYSTEM: Clears away flares which have burned out.
+fn burnout_system(
+    mut commands: Commands,
+    mut alarms: EventReader<Alarm>,
+    flares: Query<(), With<Flare>>,
+) {
+    for alarm in alarms.iter().filter(|a| a.name == BURNOUT) {
+        if flares.contains(alarm.entity) {
+            commands.entity(alarm.entity).despawn_recursive();
+        }
+    }
+}
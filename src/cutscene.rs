@@ -0,0 +1,194 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+use super::scripting::{ScriptEvent, Value};
+
+pub struct CutscenePlugin;
+
+impl Plugin for CutscenePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Cutscene>()
+            .add_event::<CameraShot>()
+            .add_system(skip_cutscene_system)
+            .add_system(cutscene_camera_system.after(skip_cutscene_system))
+            .add_system(cutscene_caption_system);
+    }
+}
+
+/// Seconds the camera takes to move to each shot, or back to the player.
+pub const PAN_TIME: f32 = 1.5;
+
+/// What a shot points the camera at.
+#[derive(Clone, Copy, Debug)]
+pub enum ShotTarget {
+    Point(Vec3),
+    /// Follows an entity around, for as long as it exists.
+    Entity(Entity),
+}
+
+/// :EVENT: Asks for the camera to be taken away from the player, and pointed at
+/// `target` for `duration` seconds. Shots are played one after the other, and
+/// the player gets the camera back once the last one has finished.
+#[derive(Clone, Copy, Debug)]
+pub struct CameraShot {
+    pub target: ShotTarget,
+    /// Scale of the camera's projection. Zero keeps the current zoom.
+    pub zoom: f32,
+    pub duration: f32,
+}
+
+struct Playing {
+    shot: CameraShot,
+    elapsed: f32,
+    /// Where the camera was when the shot started.
+    from: (Vec3, f32),
+}
+
+/// Resource which holds the shots waiting to be played.
+///
+/// Cutscenes run on real time, so they carry on even if the simulation is
+/// paused or sped up.
+#[derive(Resource, Default)]
+pub struct Cutscene {
+    queue: VecDeque<CameraShot>,
+    current: Option<Playing>,
+    /// Where the camera was before the cutscene took it over.
+    saved: Option<(Vec3, f32)>,
+    returning: bool,
+    skipped: bool,
+}
+
+impl Cutscene {
+    /// Whether the camera currently belongs to a cutscene, rather than the player.
+    pub fn playing(&self) -> bool {
+        self.saved.is_some()
+    }
+
+    /// Drops every remaining shot, and hands the camera straight back.
+    pub fn skip(&mut self) {
+        if self.playing() {
+            self.queue.clear();
+            self.current = None;
+            self.returning = true;
+            self.skipped = true;
+        }
+    }
+}
+
+/// :SYSTEM: Skips the cutscene when the player presses escape or space.
+fn skip_cutscene_system(mut cutscene: ResMut<Cutscene>, input: Res<Input<KeyCode>>) {
+    if input.any_just_pressed([KeyCode::Escape, KeyCode::Space]) {
+        cutscene.skip();
+    }
+}
+
+/// :SYSTEM: Moves the camera through the queued shots, then back to where the
+/// player left it. A `cutscene_end` [`ScriptEvent`] is raised once the player
+/// has the camera back, with 1 if the cutscene was skipped, or 0 if not.
+#[allow(clippy::type_complexity)]
+fn cutscene_camera_system(
+    mut cutscene: ResMut<Cutscene>,
+    mut shots: EventReader<CameraShot>,
+    mut camera: Query<(&mut OrthographicProjection, &mut Transform), With<Camera2d>>,
+    targets: Query<&GlobalTransform>,
+    mut script_events: EventWriter<ScriptEvent>,
+    time: Res<Time>,
+) {
+    let Ok((mut ortho, mut transform)) = camera.get_single_mut() else {
+        return;
+    };
+    let camera_state = (transform.translation, ortho.scale);
+
+    for shot in shots.iter() {
+        cutscene.saved.get_or_insert(camera_state);
+        cutscene.queue.push_back(*shot);
+    }
+
+    let Some(saved) = cutscene.saved else { return };
+
+    if cutscene.current.is_none() {
+        let next = match cutscene.queue.pop_front() {
+            Some(shot) => shot,
+            None if cutscene.skipped => {
+                // skipping doesn't wait for the camera to pan back
+                transform.translation = saved.0;
+                ortho.scale = saved.1;
+                cutscene.returning = false;
+                cutscene.skipped = false;
+                cutscene.saved = None;
+                script_events.send(ScriptEvent {
+                    name: "cutscene_end".to_string(),
+                    args: vec![Value::Num(1.0)],
+                });
+                return;
+            }
+            None if !cutscene.returning => {
+                cutscene.returning = true;
+                CameraShot {
+                    target: ShotTarget::Point(saved.0),
+                    zoom: saved.1,
+                    duration: PAN_TIME,
+                }
+            }
+            None => {
+                cutscene.returning = false;
+                cutscene.saved = None;
+                script_events.send(ScriptEvent {
+                    name: "cutscene_end".to_string(),
+                    args: vec![Value::Num(0.0)],
+                });
+                return;
+            }
+        };
+        cutscene.current = Some(Playing {
+            shot: next,
+            elapsed: 0.0,
+            from: camera_state,
+        });
+    }
+
+    let Some(playing) = cutscene.current.as_mut() else {
+        return;
+    };
+    playing.elapsed += time.raw_delta_seconds();
+
+    let target = match playing.shot.target {
+        ShotTarget::Point(point) => point,
+        ShotTarget::Entity(entity) => targets
+            .get(entity)
+            .map_or(transform.translation, |t| t.translation()),
+    };
+    let zoom = if playing.shot.zoom > 0.0 {
+        playing.shot.zoom
+    } else {
+        playing.from.1
+    };
+
+    // ease in and out of each pan
+    let t = (playing.elapsed / PAN_TIME.min(playing.shot.duration)).clamp(0.0, 1.0);
+    let t = t * t * (3.0 - 2.0 * t);
+
+    let z = transform.translation.z;
+    transform.translation = playing.from.0.lerp(target, t);
+    transform.translation.z = z;
+    ortho.scale = playing.from.1 + (zoom - playing.from.1) * t;
+
+    if playing.elapsed >= playing.shot.duration {
+        cutscene.current = None;
+    }
+}
+
+/// :SYSTEM: Lets the player know they can skip the cutscene.
+fn cutscene_caption_system(mut contexts: EguiContexts, cutscene: Res<Cutscene>) {
+    if !cutscene.playing() || cutscene.returning {
+        return;
+    }
+
+    egui::Area::new("cutscene_caption")
+        .anchor(egui::Align2::CENTER_BOTTOM, [0.0, -16.0])
+        .show(contexts.ctx_mut(), |ui| {
+            ui.weak("press space to skip");
+        });
+}
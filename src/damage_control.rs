@@ -0,0 +1,285 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+use super::modules::{BeamMount, Module, SensorArray, ShieldGenerator, Thruster, WeaponMount};
+use super::physics::SimulationSet;
+use super::ships::{CargoHold, Commodity, Controlled, Hull};
+
+pub struct DamageControlPlugin;
+
+impl Plugin for DamageControlPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<DamageControlPanel>()
+            .add_system(
+                damage_control_system
+                    .before(super::modules::loadout_system)
+                    .in_set(SimulationSet),
+            )
+            .add_system(damage_control_panel_system);
+    }
+}
+
+/// The commodity damage control uses up.
+pub const SPARE_PARTS: &str = "spare_parts";
+
+/// Something damage control can work on.
+#[derive(Reflect, FromReflect, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Repair {
+    Hull,
+    Thrusters,
+    Sensors,
+    Weapons,
+    Shields,
+    /// Every other module: tanks, reactors, batteries and radiators.
+    Modules,
+}
+
+impl Repair {
+    pub const ALL: [Repair; 6] = [
+        Repair::Hull,
+        Repair::Thrusters,
+        Repair::Sensors,
+        Repair::Weapons,
+        Repair::Shields,
+        Repair::Modules,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Repair::Hull => "hull",
+            Repair::Thrusters => "thrusters",
+            Repair::Sensors => "sensors",
+            Repair::Weapons => "weapons",
+            Repair::Shields => "shields",
+            Repair::Modules => "modules",
+        }
+    }
+
+    pub fn parse(name: &str) -> Result<Self, String> {
+        Self::ALL
+            .into_iter()
+            .find(|r| r.label() == name)
+            .ok_or_else(|| format!("unknown repair `{}`", name))
+    }
+}
+
+/// :COMPONENT: The ship's repair drones. Once the ship has gone `calm_time`
+/// seconds without its hull taking a hit, they patch up the hull and any
+/// damaged modules, at `rate` points a second, using up spare parts from the
+/// [`CargoHold`] as they go. They work through `priority` in order, only
+/// moving on once everything ahead is fully repaired.
+///
+/// Destroyed modules are gone for good; only damaged ones can be repaired.
+#[derive(Reflect, Component, Clone, Debug)]
+#[reflect(Component)]
+pub struct DamageControl {
+    /// Integrity restored per second.
+    pub rate: f32,
+    /// Tonnes of spare parts used up for each point of integrity.
+    pub parts_per_point: f32,
+    /// Seconds without a hit before repairs start.
+    pub calm_time: f32,
+    /// Seconds since the hull last took a hit.
+    pub since_hit: f32,
+    /// What to repair first. Anything left out comes last.
+    pub priority: Vec<Repair>,
+    /// The hull's integrity as of the last tick, to spot hits.
+    last_hull: f32,
+}
+
+impl Default for DamageControl {
+    fn default() -> Self {
+        Self {
+            rate: 2.0,
+            parts_per_point: 0.01,
+            calm_time: 5.0,
+            since_hit: 0.0,
+            priority: Repair::ALL.to_vec(),
+            last_hull: 0.0,
+        }
+    }
+}
+
+impl DamageControl {
+    pub fn repairing(&self) -> bool {
+        self.since_hit >= self.calm_time
+    }
+
+    /// Moves `repairs` to the front of the priorities, in order.
+    pub fn prioritize(&mut self, repairs: &[Repair]) {
+        self.priority.retain(|r| !repairs.contains(r));
+        self.priority.splice(0..0, repairs.iter().copied());
+    }
+
+    /// The priorities, with anything left out added on the end.
+    fn order(&self) -> impl Iterator<Item = Repair> + '_ {
+        let rest = Repair::ALL
+            .into_iter()
+            .filter(|r| !self.priority.contains(r));
+        self.priority.iter().copied().chain(rest)
+    }
+}
+
+/// Resource which holds whether the damage control panel is open. It's opened
+/// from the tray.
+#[derive(Resource, Default)]
+pub struct DamageControlPanel {
+    pub open: bool,
+}
+
+/// What a module is, as far as damage control is concerned.
+fn repair_of(
+    thruster: Option<&Thruster>,
+    array: Option<&SensorArray>,
+    mount: Option<&WeaponMount>,
+    beam: Option<&BeamMount>,
+    shield: Option<&ShieldGenerator>,
+) -> Repair {
+    match (thruster, array, mount, beam, shield) {
+        (Some(_), ..) => Repair::Thrusters,
+        (_, Some(_), ..) => Repair::Sensors,
+        (_, _, Some(_), ..) | (_, _, _, Some(_), _) => Repair::Weapons,
+        (.., Some(_)) => Repair::Shields,
+        _ => Repair::Modules,
+    }
+}
+
+/// :SYSTEM: Keeps track of when each ship was last hit, and repairs the ships
+/// which have been left alone long enough.
+#[allow(clippy::type_complexity)]
+fn damage_control_system(
+    mut ships: Query<(
+        &mut DamageControl,
+        &mut Hull,
+        Option<&Children>,
+        Option<&mut CargoHold>,
+        Option<&Commodity>,
+    )>,
+    mut modules: Query<(
+        &mut Module,
+        Option<&Thruster>,
+        Option<&SensorArray>,
+        Option<&WeaponMount>,
+        Option<&BeamMount>,
+        Option<&ShieldGenerator>,
+    )>,
+    time: Res<Time>,
+) {
+    let dt = time.delta_seconds();
+
+    for (mut control, mut hull, children, hold, commodity) in ships.iter_mut() {
+        let control = &mut *control;
+        if hull.integrity < control.last_hull {
+            control.since_hit = 0.0;
+        } else {
+            control.since_hit += dt;
+        }
+        control.last_hull = hull.integrity;
+        if !control.repairing() {
+            continue;
+        }
+
+        // what the spare parts on board are good for
+        let parts = match (hold.as_deref(), commodity) {
+            _ if control.parts_per_point <= 0.0 => f32::INFINITY,
+            (Some(h), Some(c)) if c.0 == SPARE_PARTS => h.cargo / control.parts_per_point,
+            _ => 0.0,
+        };
+        let mut budget = (control.rate * dt).min(parts);
+        let mut used = 0.0;
+
+        for repair in control.order() {
+            if budget <= 0.0 {
+                break;
+            }
+            if repair == Repair::Hull {
+                let points = (hull.max_integrity - hull.integrity).clamp(0.0, budget);
+                if points > 0.0 {
+                    hull.integrity += points;
+                    budget -= points;
+                    used += points;
+                }
+                continue;
+            }
+            let mut module_iter = modules.iter_many_mut(children.into_iter().flatten());
+            while let Some((mut module, thruster, array, mount, beam, shield)) =
+                module_iter.fetch_next()
+            {
+                if repair_of(thruster, array, mount, beam, shield) != repair {
+                    continue;
+                }
+                let points = (Module::MAX_INTEGRITY - module.integrity).clamp(0.0, budget);
+                if points > 0.0 {
+                    module.integrity += points;
+                    budget -= points;
+                    used += points;
+                }
+            }
+        }
+        // repairs don't count as the hull recovering from a hit
+        control.last_hull = hull.integrity;
+
+        if let Some(mut hold) = hold.filter(|_| used > 0.0 && parts.is_finite()) {
+            hold.cargo = (hold.cargo - used * control.parts_per_point).max(0.0);
+        }
+    }
+}
+
+/// :SYSTEM: Shows the controlled ship's damage control, and lets the player
+/// reorder its priorities.
+#[allow(clippy::type_complexity)]
+fn damage_control_panel_system(
+    mut contexts: EguiContexts,
+    mut panel: ResMut<DamageControlPanel>,
+    mut player: Query<
+        (&mut DamageControl, &Hull, &CargoHold, Option<&Commodity>),
+        With<Controlled>,
+    >,
+) {
+    let panel = &mut *panel;
+    egui::Window::new("Damage control")
+        .open(&mut panel.open)
+        .resizable(false)
+        .show(contexts.ctx_mut(), |ui| {
+            let Ok((mut control, hull, hold, commodity)) = player.get_single_mut() else {
+                ui.weak("no ship");
+                return;
+            };
+            ui.label(format!(
+                "hull {:.0}/{:.0}",
+                hull.integrity, hull.max_integrity
+            ));
+            let parts = match commodity {
+                Some(c) if c.0 == SPARE_PARTS => hold.cargo,
+                _ => 0.0,
+            };
+            ui.label(format!("{:.2} t spare parts", parts));
+            match control.repairing() {
+                true => ui.label("repairing"),
+                false => ui.colored_label(
+                    egui::Color32::YELLOW,
+                    format!(
+                        "under fire, repairs in {:.0} s",
+                        control.calm_time - control.since_hit
+                    ),
+                ),
+            };
+            ui.separator();
+
+            let order: Vec<Repair> = control.order().collect();
+            let mut raised = None;
+            for (i, repair) in order.iter().enumerate() {
+                ui.horizontal(|ui| {
+                    if ui.add_enabled(i > 0, egui::Button::new("⏶")).clicked() {
+                        raised = Some(i);
+                    }
+                    ui.label(format!("{}. {}", i + 1, repair.label()));
+                });
+            }
+            if let Some(i) = raised {
+                let mut order = order;
+                order.swap(i - 1, i);
+                control.priority = order;
+            }
+        });
+}
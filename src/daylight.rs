@@ -0,0 +1,138 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use std::f32::consts::{FRAC_PI_2, PI};
+
+use super::level::{AstroObject, Star};
+use super::physics::SimulationSet;
+use super::scripting::{ScriptEvent, Value};
+use super::star_system::Dormant;
+use super::surface_base::SurfaceBase;
+use super::user_interface::MapIcon;
+
+pub struct DaylightPlugin;
+
+impl Plugin for DaylightPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(sunlight_system.in_set(SimulationSet))
+            .add_system(night_shade_system);
+    }
+}
+
+/// How dark the night side of a planet is drawn.
+const NIGHT_ALPHA: u8 = 150;
+
+/// The star nearest `position`, if there is one.
+pub fn nearest_star<'a>(
+    stars: impl Iterator<Item = &'a Transform>,
+    position: Vec3,
+) -> Option<Vec3> {
+    stars.map(|t| t.translation).min_by(|a, b| {
+        a.distance_squared(position)
+            .total_cmp(&b.distance_squared(position))
+    })
+}
+
+/// Whether `point`, on the surface of a body at `center`, is on the side facing
+/// a star at `star`.
+pub fn sunlit(center: Vec3, point: Vec3, star: Vec3) -> bool {
+    (point - center).dot(star - center) > 0.0
+}
+
+/// Whether a planet, one of `bodies`, stands between `point` and a star at
+/// `star`.
+pub fn in_shadow<'a>(
+    point: Vec3,
+    star: Vec3,
+    mut bodies: impl Iterator<Item = (&'a Transform, &'a AstroObject)>,
+) -> bool {
+    let ray = star - point;
+    let length = ray.length();
+    if length <= 0.0 {
+        return false;
+    }
+    let direction = ray / length;
+    bodies.any(|(t, astro)| {
+        let along = (t.translation - point).dot(direction);
+        let closest = point + direction * along.clamp(0.0, length);
+        along > 0.0 && closest.distance(t.translation) < astro.radius
+    })
+}
+
+/// :SYSTEM: Keeps track of which surface bases are in daylight, as their
+/// planets turn. Raises `sunrise` and `sunset` with the base when that
+/// changes.
+fn sunlight_system(
+    mut bases: Query<(Entity, &mut SurfaceBase, &Transform, &Parent)>,
+    bodies: Query<&Transform, (With<AstroObject>, Without<SurfaceBase>)>,
+    stars: Query<&Transform, (With<Star>, Without<SurfaceBase>)>,
+    mut script_events: EventWriter<ScriptEvent>,
+) {
+    for (entity, mut base, local, parent) in bases.iter_mut() {
+        let Ok(body) = bodies.get(parent.get()) else {
+            continue;
+        };
+        let Some(star) = nearest_star(stars.iter(), body.translation) else {
+            continue;
+        };
+        let point = body.mul_transform(*local).translation;
+        let lit = sunlit(body.translation, point, star);
+        if base.sunlit == lit {
+            continue;
+        }
+        base.sunlit = lit;
+        script_events.send(ScriptEvent {
+            name: if lit { "sunrise" } else { "sunset" }.to_string(),
+            args: vec![Value::from_entity(entity)],
+        });
+    }
+}
+
+/// :SYSTEM: Shades the side of each planet facing away from its star, so it's
+/// easy to tell which way the sun is.
+#[allow(clippy::type_complexity)]
+fn night_shade_system(
+    mut contexts: EguiContexts,
+    bodies: Query<(&Transform, &Children), (With<AstroObject>, Without<Star>, Without<Dormant>)>,
+    stars: Query<&Transform, With<Star>>,
+    icons: Query<&Sprite, With<MapIcon>>,
+    cameras: Query<(&Camera, &GlobalTransform), With<Camera2d>>,
+) {
+    let Ok((camera, camera_transform)) = cameras.get_single() else {
+        return;
+    };
+    let Some(viewport) = camera.logical_viewport_size() else {
+        return;
+    };
+
+    let painter = contexts
+        .ctx_mut()
+        .layer_painter(egui::LayerId::background());
+    for (transform, children) in bodies.iter() {
+        let Some(star) = nearest_star(stars.iter(), transform.translation) else {
+            continue;
+        };
+        let Some(at) = camera.world_to_viewport(camera_transform, transform.translation) else {
+            continue;
+        };
+        // planets are drawn as icons, so shade over the icon
+        let Some(size) = icons.iter_many(children).find_map(|s| s.custom_size) else {
+            continue;
+        };
+        // the viewport has +Y up, egui has it down
+        let center = egui::pos2(at.x, viewport.y - at.y);
+        let towards = star - transform.translation;
+        let noon = (-towards.y).atan2(towards.x);
+
+        let points = (0..=16)
+            .map(|i| {
+                let angle = noon + FRAC_PI_2 + PI * i as f32 / 16.0;
+                center + egui::vec2(angle.cos(), angle.sin()) * size.x / 2.0
+            })
+            .collect();
+        painter.add(egui::Shape::convex_polygon(
+            points,
+            egui::Color32::from_black_alpha(NIGHT_ALPHA),
+            egui::Stroke::NONE,
+        ));
+    }
+}
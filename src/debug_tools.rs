@@ -0,0 +1,560 @@
+//! Developer tools for poking at the simulation.
+//!
+//! Most of these break the normal rules of the simulation, and aren't
+//! deterministic. The plugin is only added to debug builds.
+
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy::time::TimeUpdateStrategy;
+use bevy_egui::{
+    egui::{self, plot},
+    EguiContexts,
+};
+
+use super::balance::Balance;
+use super::clock::SimulationClock;
+use super::level::AstroObject;
+use super::physics::{Kinimatics, SimulationSet};
+use super::scenario_editor::ScenarioEditor;
+use super::seeker::{Seeker, SeekerState};
+use super::ships::{Engine, Missile, Ship};
+use super::star_system::Dormant;
+
+pub struct DebugToolsPlugin;
+
+impl Plugin for DebugToolsPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<TimeDilation>()
+            .init_resource::<StepThrough>()
+            .configure_set(SimulationSet.run_if(simulation_running))
+            .init_resource::<History>()
+            .add_system(time_dilation_panel_system)
+            .add_system(
+                history_system
+                    .after(super::physics::kinimatics_system)
+                    .in_set(SimulationSet),
+            )
+            .add_system(history_panel_system)
+            .add_system(step_through_panel_system)
+            .init_resource::<Gizmos>()
+            .add_system(gizmo_system)
+            .add_system(seeker_debug_system)
+            .add_system(step_through_system.in_base_set(CoreSet::Last));
+    }
+}
+
+/// Length of the tick the simulation advances by in step-through mode.
+pub const STEP_TICK: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+/// Resource which holds the simulation still, and lets it advance one tick at a
+/// time. F9 pauses and resumes the simulation, F10 steps it while paused.
+///
+/// While paused, the [`SimulationSet`] doesn't run at all and [`Time`] stands
+/// still; everything else, like the UI and the inspector, carries on. The
+/// selected body's [`Gizmos`] are shown, to set its velocity by hand.
+#[derive(Resource, Default)]
+pub struct StepThrough {
+    pub paused: bool,
+    /// Set for the one frame in which the paused simulation is allowed a tick.
+    stepping: bool,
+    step_requested: bool,
+    /// Ticks taken since the simulation was last paused.
+    pub steps: u32,
+}
+
+impl StepThrough {
+    /// Advances the paused simulation by one tick, at the end of this frame.
+    pub fn step(&mut self) {
+        if self.paused {
+            self.step_requested = true;
+        }
+    }
+}
+
+fn simulation_running(step_through: Res<StepThrough>) -> bool {
+    !step_through.paused || step_through.stepping
+}
+
+/// :SYSTEM: Pauses, resumes, and steps the simulation. Runs at the end of each
+/// frame, so a step covers the whole of the next frame, with [`Time`] moving
+/// forward by exactly [`STEP_TICK`].
+pub fn step_through_system(
+    mut step_through: ResMut<StepThrough>,
+    mut time: ResMut<Time>,
+    mut strategy: ResMut<TimeUpdateStrategy>,
+    input: Res<Input<KeyCode>>,
+) {
+    // a step just finished, hold still again
+    if step_through.stepping {
+        step_through.stepping = false;
+        *strategy = TimeUpdateStrategy::Automatic;
+    }
+
+    if input.just_pressed(KeyCode::F9) {
+        step_through.paused = !step_through.paused;
+        step_through.steps = 0;
+    }
+
+    if input.just_pressed(KeyCode::F10) {
+        step_through.step();
+    }
+
+    if step_through.step_requested {
+        step_through.step_requested = false;
+        step_through.stepping = true;
+        step_through.steps += 1;
+        *strategy = TimeUpdateStrategy::ManualDuration(STEP_TICK);
+    }
+
+    // time only moves while the simulation does
+    if step_through.paused && !step_through.stepping {
+        time.pause();
+    } else {
+        time.unpause();
+    }
+}
+
+/// :SYSTEM: Shows whether the simulation is paused, with buttons for the
+/// step-through controls.
+fn step_through_panel_system(
+    mut contexts: EguiContexts,
+    mut step_through: ResMut<StepThrough>,
+    clock: Res<SimulationClock>,
+) {
+    if !step_through.paused {
+        return;
+    }
+
+    egui::Window::new("Step-through (debug)").show(contexts.ctx_mut(), |ui| {
+        ui.label(format!(
+            "Paused at {:.3}s, {} steps taken",
+            clock.met(),
+            step_through.steps
+        ));
+        ui.horizontal(|ui| {
+            if ui.button("Step (F10)").clicked() {
+                step_through.step();
+            }
+            if ui.button("Resume (F9)").clicked() {
+                step_through.paused = false;
+            }
+        });
+    });
+}
+
+/// :COMPONENT: **Debug only.** Scales the time step used to integrate this
+/// entity's motion: `0.0` freezes it in place, `0.5` runs it at half speed, and
+/// so on. Gravity between the entity and everything else is unaffected.
+///
+/// Time dilated entities drift out of step with the rest of the world, so this
+/// must never be used for gameplay.
+#[derive(Reflect, Component, Clone, Copy)]
+#[reflect(Component)]
+pub struct TimeDilation(pub f32);
+
+impl Default for TimeDilation {
+    fn default() -> Self {
+        Self(1.0)
+    }
+}
+
+impl TimeDilation {
+    pub fn scaled(&self, dt: f32) -> f32 {
+        dt * self.0.max(0.0)
+    }
+}
+
+/// :SYSTEM: Lets the user pick an entity and freeze, slow, or speed it up.
+#[allow(clippy::type_complexity)]
+fn time_dilation_panel_system(
+    mut commands: Commands,
+    mut contexts: EguiContexts,
+    bodies: Query<(Entity, Option<&Name>, Option<&TimeDilation>), With<Kinimatics>>,
+    mut selected: Local<Option<Entity>>,
+) {
+    egui::Window::new("Time dilation (debug)")
+        .default_open(false)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.colored_label(
+                egui::Color32::YELLOW,
+                "Non-deterministic: dilated entities fall out of step with the world.",
+            );
+
+            entity_picker(
+                ui,
+                "dilation_entity",
+                bodies.iter().map(|(e, name, _)| (e, name)),
+                &mut selected,
+            );
+
+            let Some(Ok((entity, _, dilation))) = selected.map(|e| bodies.get(e)) else {
+                return;
+            };
+
+            let mut scale = dilation.copied().unwrap_or_default().0;
+            ui.horizontal(|ui| {
+                let slider = ui.add(egui::Slider::new(&mut scale, 0.0..=4.0).text("time scale"));
+                if ui.button("Freeze").clicked() {
+                    scale = 0.0;
+                }
+                if ui.button("Reset").clicked() {
+                    commands.entity(entity).remove::<TimeDilation>();
+                } else if slider.changed() || scale == 0.0 {
+                    commands.entity(entity).insert(TimeDilation(scale));
+                }
+            });
+        });
+}
+
+/// Shows a drop down for picking one of `entities`, labelled by name where it has one.
+fn entity_picker<'a>(
+    ui: &mut egui::Ui,
+    id: &str,
+    entities: impl Iterator<Item = (Entity, Option<&'a Name>)>,
+    selected: &mut Option<Entity>,
+) {
+    let label = |entity: Entity, name: Option<&Name>| match name {
+        Some(name) => format!("{} ({:?})", name, entity),
+        None => format!("{:?}", entity),
+    };
+
+    let entities: Vec<_> = entities.collect();
+    let current = entities
+        .iter()
+        .find(|(e, _)| Some(*e) == *selected)
+        .map_or("none".to_string(), |(e, name)| label(*e, *name));
+
+    egui::ComboBox::from_id_source(id)
+        .selected_text(current)
+        .show_ui(ui, |ui| {
+            for (entity, name) in entities {
+                ui.selectable_value(selected, Some(entity), label(entity, name));
+            }
+        });
+}
+
+/// Number of ticks of history kept, ten seconds at 60 ticks per second.
+pub const HISTORY_LENGTH: usize = 600;
+
+/// What one entity was doing on one tick.
+#[derive(Clone, Copy, Debug)]
+pub struct Sample {
+    /// Mission elapsed time of the tick, in seconds.
+    pub time: f32,
+    pub throttle: f32,
+    pub thrust: f32,
+    pub heading: f32,
+    pub velocity: Vec3,
+    pub acceleration: Vec3,
+    /// Net force on the entity, gravity included.
+    pub force: Vec3,
+}
+
+/// Resource holding the last [`HISTORY_LENGTH`] ticks of whichever entity is
+/// being watched, so you can go back and see what it was doing.
+#[derive(Resource, Default)]
+pub struct History {
+    pub entity: Option<Entity>,
+    pub samples: VecDeque<Sample>,
+}
+
+/// :SYSTEM: Records what the watched entity did this tick.
+fn history_system(
+    mut history: ResMut<History>,
+    bodies: Query<(&Kinimatics, &Transform, Option<&Engine>)>,
+    clock: Res<SimulationClock>,
+) {
+    let Some(entity) = history.entity else { return };
+    let Ok((kin, transform, engine)) = bodies.get(entity) else {
+        return;
+    };
+
+    let (_, _, heading) = transform.rotation.to_euler(EulerRot::XYZ);
+    let sample = Sample {
+        time: clock.met(),
+        throttle: engine.map_or(0.0, |e| e.throttle_fraction()),
+        thrust: engine.map_or(0.0, |e| e.thrust()),
+        heading,
+        velocity: kin.velocity,
+        acceleration: kin.acceleration,
+        force: kin.acceleration * kin.mass,
+    };
+
+    if history.samples.len() == HISTORY_LENGTH {
+        history.samples.pop_front();
+    }
+    history.samples.push_back(sample);
+}
+
+/// :SYSTEM: Picks which entity to watch, and plots its history. Dragging the
+/// scrubber shows the exact values at that tick on every plot.
+fn history_panel_system(
+    mut contexts: EguiContexts,
+    mut history: ResMut<History>,
+    bodies: Query<(Entity, Option<&Name>), With<Kinimatics>>,
+    mut scrub: Local<Option<usize>>,
+) {
+    egui::Window::new("History (debug)")
+        .default_open(false)
+        .show(contexts.ctx_mut(), |ui| {
+            let mut entity = history.entity;
+            entity_picker(ui, "history_entity", bodies.iter(), &mut entity);
+            if entity != history.entity {
+                history.entity = entity;
+                history.samples.clear();
+                *scrub = None;
+            }
+
+            let samples = &history.samples;
+            if samples.is_empty() {
+                ui.weak("no history recorded yet");
+                return;
+            }
+
+            // follow the latest tick, unless the user has scrubbed back
+            let last = samples.len() - 1;
+            let mut index = scrub.unwrap_or(last).min(last);
+            ui.horizontal(|ui| {
+                let slider = ui.add(egui::Slider::new(&mut index, 0..=last).show_value(false));
+                if slider.changed() {
+                    *scrub = (index != last).then_some(index);
+                }
+                if ui.button("Latest").clicked() {
+                    *scrub = None;
+                    index = last;
+                }
+            });
+
+            let at = samples[index];
+            ui.label(format!("t = {:.2}s", at.time));
+
+            let sparkline = |ui: &mut egui::Ui, name: &str, value: fn(&Sample) -> f32| {
+                ui.label(format!("{}: {:.3}", name, value(&at)));
+                plot::Plot::new(name)
+                    .height(48.0)
+                    .show_axes([false, true])
+                    .allow_drag(false)
+                    .allow_zoom(false)
+                    .allow_scroll(false)
+                    .show(ui, |plot_ui| {
+                        plot_ui.line(plot::Line::new(
+                            samples
+                                .iter()
+                                .map(|s| [s.time as f64, value(s) as f64])
+                                .collect::<plot::PlotPoints>(),
+                        ));
+                        plot_ui.vline(plot::VLine::new(at.time as f64));
+                    });
+            };
+
+            sparkline(ui, "throttle", |s| s.throttle);
+            sparkline(ui, "thrust", |s| s.thrust);
+            sparkline(ui, "heading", |s| s.heading);
+            sparkline(ui, "speed", |s| s.velocity.length());
+            sparkline(ui, "acceleration", |s| s.acceleration.length());
+            sparkline(ui, "net force", |s| s.force.length());
+        });
+}
+
+/// How close the pointer has to be to grab a gizmo, in pixels.
+const GRAB_RADIUS: f32 = 10.0;
+
+/// Velocity arrows point to where a body will be this many seconds from now.
+const VELOCITY_SECONDS: f32 = 1.0;
+
+/// Acceleration arrows show how much the velocity changes over this many
+/// seconds.
+const ACCELERATION_SECONDS: f32 = 10.0;
+
+/// Resource holding the body whose gizmos are shown while the simulation is
+/// paused.
+///
+/// Clicking a body picks it, and shows its velocity and acceleration as
+/// arrows. Dragging the tip of the velocity arrow sets the body's velocity,
+/// so orbits can be tried out without going near the inspector. The
+/// acceleration arrow can't be dragged, since the physics works it out afresh
+/// every tick.
+#[derive(Resource, Default)]
+pub struct Gizmos {
+    pub selected: Option<Entity>,
+    dragging: bool,
+}
+
+/// :SYSTEM: Draws the selected body's gizmos while the simulation is paused,
+/// and drags its velocity arrow around. Stays out of the way of the
+/// [`ScenarioEditor`], which has arrows of its own.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn gizmo_system(
+    mut contexts: EguiContexts,
+    mut gizmos: ResMut<Gizmos>,
+    step_through: Res<StepThrough>,
+    editor: Res<ScenarioEditor>,
+    mouse: Res<Input<MouseButton>>,
+    windows: Query<&Window>,
+    cameras: Query<(&Camera, &GlobalTransform), With<Camera2d>>,
+    mut bodies: Query<
+        (Entity, &Transform, &mut Kinimatics),
+        (Or<(With<AstroObject>, With<Ship>)>, Without<Dormant>),
+    >,
+) {
+    if !step_through.paused || editor.active {
+        gizmos.selected = None;
+        gizmos.dragging = false;
+        return;
+    }
+    let Ok((camera, camera_transform)) = cameras.get_single() else {
+        return;
+    };
+    let Some(viewport) = camera.logical_viewport_size() else {
+        return;
+    };
+    // the viewport has +Y up, egui has it down
+    let to_screen = |p: Vec3| {
+        camera
+            .world_to_viewport(camera_transform, p)
+            .map(|at| egui::pos2(at.x, viewport.y - at.y))
+    };
+    let cursor = windows.get_single().ok().and_then(|w| w.cursor_position());
+    let pointer = cursor
+        .and_then(|c| camera.viewport_to_world(camera_transform, c))
+        .map(|ray| ray.origin.truncate().extend(0.0));
+    let pointer_screen = cursor.map(|c| egui::pos2(c.x, viewport.y - c.y));
+    let ctx = contexts.ctx_mut();
+    if gizmos.selected.is_some_and(|e| !bodies.contains(e)) {
+        gizmos.selected = None;
+    }
+
+    if let Some((_, transform, kin)) = gizmos.selected.and_then(|e| bodies.get(e).ok()) {
+        let at = transform.translation;
+        let painter = ctx.layer_painter(egui::LayerId::background());
+        if let (Some(from), Some(velocity), Some(acceleration)) = (
+            to_screen(at),
+            to_screen(at + kin.velocity * VELOCITY_SECONDS),
+            to_screen(at + kin.acceleration * ACCELERATION_SECONDS),
+        ) {
+            let color = egui::Color32::YELLOW;
+            painter.circle_stroke(from, GRAB_RADIUS, (1.0, color));
+            painter.arrow(
+                from,
+                acceleration - from,
+                egui::Stroke::new(1.0, egui::Color32::LIGHT_RED),
+            );
+            painter.arrow(from, velocity - from, egui::Stroke::new(1.0, color));
+            painter.circle_filled(velocity, 3.0, color);
+        }
+    }
+
+    let (Some(pointer), Some(pointer_screen)) = (pointer, pointer_screen) else {
+        return;
+    };
+    if mouse.just_pressed(MouseButton::Left) && !ctx.wants_pointer_input() {
+        let near = |p: Vec3| to_screen(p).is_some_and(|s| s.distance(pointer_screen) < GRAB_RADIUS);
+
+        // the tip of the arrow comes first, so long as it's clear of the body
+        gizmos.dragging = gizmos
+            .selected
+            .and_then(|e| bodies.get(e).ok())
+            .is_some_and(|(_, t, k)| {
+                let tip = t.translation + k.velocity * VELOCITY_SECONDS;
+                near(tip) && !near(t.translation)
+            });
+        if !gizmos.dragging {
+            gizmos.selected = bodies
+                .iter()
+                .filter(|(_, t, _)| near(t.translation))
+                .min_by(|(_, a, _), (_, b, _)| {
+                    let a = a.translation.distance_squared(pointer);
+                    let b = b.translation.distance_squared(pointer);
+                    a.total_cmp(&b)
+                })
+                .map(|(e, ..)| e);
+        }
+    }
+    if !mouse.pressed(MouseButton::Left) {
+        gizmos.dragging = false;
+    }
+
+    if let (true, Some(selected)) = (gizmos.dragging, gizmos.selected) {
+        if let Ok((_, transform, mut kin)) = bodies.get_mut(selected) {
+            kin.velocity = (pointer - transform.translation) / VELOCITY_SECONDS;
+        }
+    }
+}
+
+/// :SYSTEM: Lists every missile's seeker, with its state, target and progress
+/// towards a lock. With cones turned on, each seeker's field of view is drawn
+/// on the map out to its range, coloured by its state.
+#[allow(clippy::type_complexity)]
+fn seeker_debug_system(
+    mut contexts: EguiContexts,
+    missiles: Query<(Entity, &Missile, &Seeker, &Transform)>,
+    cameras: Query<(&Camera, &GlobalTransform), With<Camera2d>>,
+    balance: Res<Balance>,
+    mut cones: Local<bool>,
+) {
+    let ctx = contexts.ctx_mut();
+    let stats = &balance.seeker;
+    let color = |state| match state {
+        SeekerState::Idle => egui::Color32::GRAY,
+        SeekerState::Acquiring => egui::Color32::YELLOW,
+        SeekerState::Locked => egui::Color32::RED,
+        SeekerState::Lost => egui::Color32::LIGHT_BLUE,
+    };
+
+    egui::Window::new("Seekers (debug)")
+        .default_open(false)
+        .show(ctx, |ui| {
+            ui.checkbox(&mut cones, "show cones");
+            egui::Grid::new("seekers").striped(true).show(ui, |ui| {
+                for (entity, missile, seeker, _) in missiles.iter() {
+                    ui.label(format!("{:?} {}", entity, missile.kind.label()));
+                    ui.colored_label(color(seeker.state), seeker.state.label());
+                    ui.label(
+                        seeker
+                            .target
+                            .map_or("-".to_string(), |t| format!("{:?}", t)),
+                    );
+                    ui.label(format!("{:.2}/{:.2}s", seeker.progress, stats.lock_time));
+                    ui.end_row();
+                }
+            });
+        });
+
+    if !*cones {
+        return;
+    }
+    let Ok((camera, camera_transform)) = cameras.get_single() else {
+        return;
+    };
+    let Some(viewport) = camera.logical_viewport_size() else {
+        return;
+    };
+    // the viewport has +Y up, egui has it down
+    let to_screen = |p: Vec3| {
+        camera
+            .world_to_viewport(camera_transform, p)
+            .map(|at| egui::pos2(at.x, viewport.y - at.y))
+    };
+    let painter = ctx.layer_painter(egui::LayerId::background());
+    for (_, _, seeker, transform) in missiles.iter() {
+        if seeker.state == SeekerState::Idle {
+            continue;
+        }
+        let stroke = egui::Stroke::new(1.0, color(seeker.state));
+        let at = transform.translation;
+        let edge = |angle: f32| {
+            let direction = (transform.rotation * Quat::from_rotation_z(angle)).mul_vec3(Vec3::Y);
+            at + direction * stats.range
+        };
+        if let (Some(from), Some(left), Some(right)) = (
+            to_screen(at),
+            to_screen(edge(stats.fov)),
+            to_screen(edge(-stats.fov)),
+        ) {
+            painter.line_segment([from, left], stroke);
+            painter.line_segment([from, right], stroke);
+        }
+    }
+}
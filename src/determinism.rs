@@ -0,0 +1,209 @@
+use bevy::prelude::*;
+use bevy::time::TimeUpdateStrategy;
+use bevy_egui::{egui, EguiContexts};
+
+use super::clock::SimulationClock;
+use super::fast_forward::FastForward;
+use super::physics::{Kinimatics, SimulationSet};
+use super::realtime::TICK;
+
+pub struct DeterminismPlugin;
+
+impl Plugin for DeterminismPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Determinism::new(
+            std::env::args().any(|a| a == "--deterministic"),
+        ))
+        .register_type::<StableId>()
+        // new bodies get their ids in time to be stepped and hashed that frame
+        .add_systems(
+            (stable_id_system, apply_system_buffers)
+                .chain()
+                .before(SimulationSet),
+        )
+        .add_system(checksum_system.after(SimulationSet))
+        .add_system(
+            fixed_step_system
+                .in_base_set(CoreSet::Last)
+                .after(super::debug_tools::step_through_system)
+                .after(super::fast_forward::pace_system),
+        )
+        .add_system(checksum_panel_system);
+    }
+}
+
+/// Where the checksum log is written.
+const CHECKSUM_LOG_PATH: &str = "checksums.log";
+
+/// :COMPONENT: An id which, unlike [`Entity`], comes out the same on every run
+/// of the same scenario. Handed out to everything that moves, in deterministic
+/// mode.
+#[derive(Reflect, Component, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+#[reflect(Component)]
+pub struct StableId(pub u64);
+
+/// Resource which keeps the simulation reproducible, for script competitions
+/// and replays. Start the game with `--deterministic` to turn it on.
+///
+/// Every frame advances the simulation by exactly [`TICK`], whatever the frame
+/// rate, and physics works through entities in [`StableId`] order. After each
+/// tick the state of everything that moves is hashed into a checksum; two runs
+/// of the same scenario have stayed in step for as long as their checksums
+/// agree.
+#[derive(Resource)]
+pub struct Determinism {
+    pub enabled: bool,
+    pub tick: u64,
+    pub checksum: u64,
+    /// Checksum after each tick, for comparing with another run.
+    pub log: Vec<u64>,
+    next_id: u64,
+    last_elapsed: f64,
+    status: String,
+}
+
+impl Determinism {
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            tick: 0,
+            checksum: 0,
+            log: Vec::new(),
+            next_id: 0,
+            last_elapsed: 0.0,
+            status: String::new(),
+        }
+    }
+
+    /// Writes the log out as one `tick checksum` line per tick, so two runs can
+    /// be compared with `diff`.
+    pub fn save_log(&self, path: &str) -> Result<(), String> {
+        let text: String = self
+            .log
+            .iter()
+            .enumerate()
+            .map(|(tick, checksum)| format!("{} {:016x}\n", tick + 1, checksum))
+            .collect();
+        std::fs::write(path, text).map_err(|e| e.to_string())
+    }
+}
+
+/// 64 bit FNV-1a. Unlike the standard library's hashers, it's guaranteed to
+/// come out the same on every platform and Rust version.
+struct Fnv(u64);
+
+impl Fnv {
+    fn new() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.0 ^= *b as u64;
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn write_f32(&mut self, x: f32) {
+        self.write(&x.to_bits().to_le_bytes());
+    }
+
+    fn write_vec3(&mut self, v: Vec3) {
+        self.write_f32(v.x);
+        self.write_f32(v.y);
+        self.write_f32(v.z);
+    }
+}
+
+/// :SYSTEM: Gives everything that moves a [`StableId`], in deterministic mode.
+/// Entities spawned in the same frame are numbered in order of where they are,
+/// since the order their spawn commands ran in can differ between runs.
+#[allow(clippy::type_complexity)]
+fn stable_id_system(
+    mut commands: Commands,
+    mut determinism: ResMut<Determinism>,
+    new: Query<(Entity, &Transform), (With<Kinimatics>, Without<StableId>)>,
+) {
+    if !determinism.enabled {
+        return;
+    }
+    let mut new: Vec<_> = new.iter().collect();
+    new.sort_by(|(_, a), (_, b)| {
+        let (a, b) = (a.translation, b.translation);
+        a.x.total_cmp(&b.x)
+            .then(a.y.total_cmp(&b.y))
+            .then(a.z.total_cmp(&b.z))
+    });
+    for (entity, _) in new {
+        commands
+            .entity(entity)
+            .insert(StableId(determinism.next_id));
+        determinism.next_id += 1;
+    }
+}
+
+/// :SYSTEM: Hashes the position, velocity and mass of everything that moves
+/// into the checksum, once for each tick the simulation takes.
+fn checksum_system(
+    mut determinism: ResMut<Determinism>,
+    clock: Res<SimulationClock>,
+    bodies: Query<(&StableId, &Transform, &Kinimatics)>,
+) {
+    if !determinism.enabled || clock.elapsed == determinism.last_elapsed {
+        return;
+    }
+    determinism.last_elapsed = clock.elapsed;
+    determinism.tick += 1;
+
+    let mut bodies: Vec<_> = bodies.iter().collect();
+    bodies.sort_by_key(|(id, ..)| **id);
+    let mut hash = Fnv::new();
+    hash.write(&determinism.tick.to_le_bytes());
+    hash.write(&clock.elapsed.to_bits().to_le_bytes());
+    for (id, transform, kin) in bodies {
+        hash.write(&id.0.to_le_bytes());
+        hash.write_vec3(transform.translation);
+        hash.write_vec3(kin.velocity);
+        hash.write_f32(kin.mass);
+    }
+    determinism.checksum = hash.0;
+    determinism.log.push(hash.0);
+}
+
+/// :SYSTEM: Fixes the simulation's time step, in deterministic mode. Runs at
+/// the very end of the frame, after anything else which sets the time step,
+/// and leaves skipping ahead alone.
+fn fixed_step_system(
+    determinism: Res<Determinism>,
+    fast_forward: Res<FastForward>,
+    mut strategy: ResMut<TimeUpdateStrategy>,
+) {
+    if !determinism.enabled || fast_forward.skipping() {
+        return;
+    }
+    *strategy = TimeUpdateStrategy::ManualDuration(TICK);
+}
+
+/// :SYSTEM: Shows the tick and checksum, in deterministic mode.
+fn checksum_panel_system(mut contexts: EguiContexts, mut determinism: ResMut<Determinism>) {
+    if !determinism.enabled {
+        return;
+    }
+
+    // above the heat gauge, which has the corner
+    egui::Area::new("determinism")
+        .anchor(egui::Align2::LEFT_BOTTOM, [8.0, -64.0])
+        .show(contexts.ctx_mut(), |ui| {
+            ui.label(format!("DETERMINISTIC tick {}", determinism.tick));
+            ui.monospace(format!("{:016x}", determinism.checksum));
+            if ui.small_button("Save checksums").clicked() {
+                determinism.status = match determinism.save_log(CHECKSUM_LOG_PATH) {
+                    Ok(()) => format!("saved to {}", CHECKSUM_LOG_PATH),
+                    Err(e) => format!("couldn't save: {}", e),
+                };
+            }
+            if !determinism.status.is_empty() {
+                ui.label(&determinism.status);
+            }
+        });
+}
@@ -0,0 +1,173 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+use super::clock::{format_met, SimulationClock};
+use super::localization::Localization;
+use super::scripting::{ScriptEvent, Value};
+
+pub struct DialoguePlugin;
+
+impl Plugin for DialoguePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CommsLog>()
+            .add_event::<CommsMessage>()
+            .add_system(receive_comms_system)
+            .add_system(comms_panel_system.after(receive_comms_system));
+    }
+}
+
+/// :EVENT: A text message sent to the player, by the mission director or by an NPC.
+///
+/// `sender`, `text`, and `choices` are localization keys (see [`Localization`]).
+#[derive(Clone, Debug, Default)]
+pub struct CommsMessage {
+    pub sender: String,
+    /// Asset path of an image to show next to the message.
+    pub portrait: Option<String>,
+    pub text: String,
+    /// If not empty, the player must pick one of these to dismiss the message.
+    pub choices: Vec<String>,
+    /// When the player picks a choice, a `reply` [`ScriptEvent`] is raised with
+    /// this tag and the index of the choice.
+    pub reply_tag: Option<String>,
+}
+
+#[derive(Clone, Debug)]
+pub struct LogEntry {
+    pub message: CommsMessage,
+    /// Mission elapsed time when the message arrived.
+    pub received: f32,
+    pub acknowledged: bool,
+    /// Index of the choice the player picked, if any.
+    pub reply: Option<usize>,
+}
+
+/// Resource which keeps every message the player has received. Messages are
+/// shown one at a time, oldest first, until the player acknowledges them.
+#[derive(Resource, Default)]
+pub struct CommsLog {
+    pub entries: Vec<LogEntry>,
+}
+
+impl CommsLog {
+    /// Index of the oldest message the player hasn't acknowledged.
+    pub fn current(&self) -> Option<usize> {
+        self.entries.iter().position(|e| !e.acknowledged)
+    }
+
+    pub fn queued(&self) -> usize {
+        self.entries.iter().filter(|e| !e.acknowledged).count()
+    }
+}
+
+/// :SYSTEM: Files incoming messages into the [`CommsLog`].
+fn receive_comms_system(
+    mut messages: EventReader<CommsMessage>,
+    mut log: ResMut<CommsLog>,
+    clock: Res<SimulationClock>,
+) {
+    for message in messages.iter() {
+        log.entries.push(LogEntry {
+            message: message.clone(),
+            received: clock.met(),
+            acknowledged: false,
+            reply: None,
+        });
+    }
+}
+
+/// :SYSTEM: Shows the current message, any choices it offers, and the message history.
+fn comms_panel_system(
+    mut contexts: EguiContexts,
+    mut log: ResMut<CommsLog>,
+    mut script_events: EventWriter<ScriptEvent>,
+    localization: Res<Localization>,
+    asset_server: Res<AssetServer>,
+    mut portraits: Local<HashMap<String, egui::TextureId>>,
+) {
+    if log.entries.is_empty() {
+        return;
+    }
+
+    let current = log.current();
+    let queued = log.queued();
+
+    // register portraits with egui before borrowing the context for drawing
+    if let Some(path) = current.and_then(|i| log.entries[i].message.portrait.clone()) {
+        portraits
+            .entry(path)
+            .or_insert_with_key(|path| contexts.add_image(asset_server.load(path.as_str())));
+    }
+
+    let tr = |key: &str| localization.get(key).to_string();
+    let mut reply = None;
+    let mut acknowledge = false;
+
+    egui::Window::new(tr("ui.comms.title")).show(contexts.ctx_mut(), |ui| {
+        if let Some(i) = current {
+            let message = &log.entries[i].message;
+
+            ui.horizontal(|ui| {
+                if let Some(texture) = message.portrait.as_ref().and_then(|p| portraits.get(p)) {
+                    ui.image(*texture, [48.0, 48.0]);
+                }
+                ui.vertical(|ui| {
+                    ui.strong(tr(&message.sender));
+                    ui.label(tr(&message.text));
+                });
+            });
+
+            ui.horizontal(|ui| {
+                if message.choices.is_empty() {
+                    acknowledge = ui.button(tr("ui.comms.acknowledge")).clicked();
+                }
+                for (n, choice) in message.choices.iter().enumerate() {
+                    if ui.button(tr(choice)).clicked() {
+                        reply = Some(n);
+                    }
+                }
+            });
+
+            if queued > 1 {
+                ui.weak(format!("({} {})", queued - 1, tr("ui.comms.queued")));
+            }
+        }
+
+        ui.collapsing(tr("ui.comms.history"), |ui| {
+            egui::ScrollArea::vertical()
+                .max_height(200.0)
+                .show(ui, |ui| {
+                    for entry in log.entries.iter().filter(|e| e.acknowledged) {
+                        ui.label(format!(
+                            "[{}] {}: {}",
+                            format_met(entry.received),
+                            tr(&entry.message.sender),
+                            tr(&entry.message.text)
+                        ));
+                        if let Some(choice) = entry.reply {
+                            ui.weak(format!("> {}", tr(&entry.message.choices[choice])));
+                        }
+                    }
+                });
+        });
+    });
+
+    let Some(i) = current else { return };
+
+    if let Some(choice) = reply {
+        let entry = &mut log.entries[i];
+        entry.acknowledged = true;
+        entry.reply = Some(choice);
+
+        if let Some(tag) = entry.message.reply_tag.clone() {
+            script_events.send(ScriptEvent {
+                name: "reply".to_string(),
+                args: vec![Value::Str(tag), Value::Num(choice as f64)],
+            });
+        }
+    } else if acknowledge {
+        log.entries[i].acknowledged = true;
+    }
+}
@@ -0,0 +1,1030 @@
+use std::time::Duration;
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+
+use super::ai;
+use super::asteroid;
+use super::autopilot::Autopilot;
+use super::blackboard::Blackboard;
+use super::clock::SimulationClock;
+use super::cutscene::{CameraShot, ShotTarget};
+use super::dialogue::CommsMessage;
+use super::flyby::DeltaVLedger;
+use super::impactor::{self, Body};
+use super::level::{self, AstroObject, OnRails, Star};
+use super::logistics::{Depot, Freighter};
+use super::missions::{Condition, Objective, ObjectiveStatus, Objectives};
+use super::perturbation::{Perturbations, StationKeeping};
+use super::physics::{Kinimatics, PhysicsConfig, SimulationSet};
+use super::scenes;
+use super::scheduler::{Action, Scheduler};
+use super::scripting::{
+    self, find_body, BodySnapshot, CpuBudget, Program, ScriptEvent, ScriptHost, ScriptSource,
+    ShipProgram, Value, Vm, VmState,
+};
+use super::ships::{self, Controlled, Faction, MissileLauncher, PlayerShip, Ship, ShipSprites};
+use super::star_system::{InSystem, JumpDrive, JumpPoint, StarSystems};
+use super::station;
+use super::surface_base::{self, Pad, SurfaceBase};
+use super::trade::{Accounts, Listing, Market};
+use super::trails::Trail;
+
+pub struct DirectorPlugin;
+
+impl Plugin for DirectorPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_startup_system(startup_system)
+            .add_system(director_system.in_set(SimulationSet));
+    }
+}
+
+/// Maximum number of instructions the director may execute in a single frame.
+/// This is much higher than a ship's budget, since there is only ever one director.
+pub const DIRECTOR_INSTRUCTION_BUDGET: usize = 4096;
+
+/// Resource which holds the scenario's director script.
+///
+/// Unlike ship programs, the director isn't attached to anything in the world.
+/// It is allowed to spawn and remove entities, set objectives, and talk to the
+/// player, which makes it the tool for building missions on top of a level.
+///
+/// Besides the game's events, the director gets `start` once its script is
+/// loaded, and `tick` every frame, with the frame's time step and the mission
+/// elapsed time. Ticks don't pile up behind a slow handler.
+#[derive(Resource)]
+pub struct MissionDirector {
+    pub source: Handle<ScriptSource>,
+    program: Option<Program>,
+    pub vm: Vm,
+}
+
+impl MissionDirector {
+    pub fn new(source: Handle<ScriptSource>) -> Self {
+        Self {
+            source,
+            program: None,
+            vm: Vm::default(),
+        }
+    }
+}
+
+/// Mission played when none is given on the command line.
+const DEFAULT_MISSION: &str = "missions/default.sasm";
+
+pub fn startup_system(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let mission = std::env::args().skip(1).find(|a| !a.starts_with("--"));
+    commands.insert_resource(MissionDirector::new(
+        asset_server.load(mission.as_deref().unwrap_or(DEFAULT_MISSION)),
+    ));
+}
+
+/// The functions available to the director. These are a superset of what a
+/// ship needs to know about the world, plus the ability to change it.
+struct DirectorHost<'a, 'w, 's> {
+    commands: &'a mut Commands<'w, 's>,
+    asset_server: &'a AssetServer,
+    sprites: &'a ShipSprites,
+    bodies: &'a [BodySnapshot],
+    /// The ship the player is flying, if they have one.
+    player: Option<Entity>,
+    /// Astronomical bodies, for working out courses.
+    astro: &'a [Body],
+    /// Landing pads of the surface bases.
+    pads: &'a [Pad],
+    objectives: &'a mut Objectives,
+    /// Messages sent by the script this frame.
+    messages: Vec<CommsMessage>,
+    /// Camera shots queued by the script this frame.
+    shots: Vec<CameraShot>,
+    clock: &'a mut SimulationClock,
+    scheduler: &'a mut Scheduler,
+    perturbations: &'a mut Perturbations,
+    star_systems: &'a mut StarSystems,
+    accounts: &'a mut Accounts,
+    markets: &'a Query<'a, 'a, &'static Market>,
+}
+
+impl<'a, 'w, 's> DirectorHost<'a, 'w, 's> {
+    fn spawn_ship(
+        &mut self,
+        translation: Vec3,
+        velocity: Vec3,
+        program: Option<&Value>,
+    ) -> Result<Entity, String> {
+        let ship = ships::spawn_ship(self.commands, self.sprites, translation, velocity);
+        if let Some(path) = program {
+            let source = self.asset_server.load(path.as_str()?);
+            self.commands.entity(ship).insert(ShipProgram::new(source));
+        }
+        Ok(ship)
+    }
+
+    fn objective(
+        &mut self,
+        args: &[Value],
+        condition: Option<Condition>,
+    ) -> Result<Vec<Value>, String> {
+        self.objectives.add(Objective::new(
+            string(args, 0)?.to_string(),
+            string(args, 1)?.to_string(),
+            condition,
+        ));
+        Ok(vec![])
+    }
+}
+
+fn num(args: &[Value], i: usize) -> Result<f32, String> {
+    args.get(i)
+        .ok_or_else(|| format!("missing argument {}", i + 1))?
+        .as_num()
+        .map(|n| n as f32)
+}
+
+fn entity(args: &[Value], i: usize) -> Result<Entity, String> {
+    args.get(i)
+        .ok_or_else(|| format!("missing argument {}", i + 1))?
+        .as_entity()
+}
+
+fn string(args: &[Value], i: usize) -> Result<&str, String> {
+    args.get(i)
+        .ok_or_else(|| format!("missing argument {}", i + 1))?
+        .as_str()
+}
+
+impl<'a, 'w, 's> ScriptHost for DirectorHost<'a, 'w, 's> {
+    fn call(&mut self, function: &str, args: &[Value]) -> Result<Vec<Value>, String> {
+        match function {
+            // spawn_ship x y vx vy [program] -> id
+            "spawn_ship" => {
+                let ship = self.spawn_ship(
+                    Vec3::new(num(args, 0)?, num(args, 1)?, 0.0),
+                    Vec3::new(num(args, 2)?, num(args, 3)?, 0.0),
+                    args.get(4),
+                )?;
+                Ok(vec![Value::from_entity(ship)])
+            }
+            // spawn_enemy x y vx vy -> id
+            "spawn_enemy" => {
+                let ship = ai::spawn_ai_ship(
+                    self.commands,
+                    self.sprites,
+                    Vec3::new(num(args, 0)?, num(args, 1)?, 0.0),
+                    Vec3::new(num(args, 2)?, num(args, 3)?, 0.0),
+                );
+                Ok(vec![Value::from_entity(ship)])
+            }
+            // spawn_wave count x y radius [program] -> count
+            //
+            // Spawns ships spread evenly on a circle around (x, y), at rest.
+            "spawn_wave" => {
+                let count = num(args, 0)?.max(0.0) as usize;
+                let center = Vec3::new(num(args, 1)?, num(args, 2)?, 0.0);
+                let radius = num(args, 3)?;
+                for i in 0..count {
+                    let angle = std::f32::consts::TAU * i as f32 / count as f32;
+                    let offset = Vec3::new(angle.cos(), angle.sin(), 0.0) * radius;
+                    self.spawn_ship(center + offset, Vec3::ZERO, args.get(4))?;
+                }
+                Ok(vec![Value::Num(count as f64)])
+            }
+            "despawn" => {
+                let entity = args.first().ok_or("`despawn` needs an id")?.as_entity()?;
+                if let Some(e) = self.commands.get_entity(entity) {
+                    e.despawn_recursive();
+                }
+                Ok(vec![])
+            }
+            "exists" => {
+                let found = find_body(self.bodies, args.first().ok_or("`exists` needs an id")?);
+                Ok(vec![Value::Num(found.is_ok() as u8 as f64)])
+            }
+            "position" => {
+                let b = find_body(self.bodies, args.first().ok_or("`position` needs an id")?)?;
+                Ok(vec![
+                    Value::Num(b.position.x as f64),
+                    Value::Num(b.position.y as f64),
+                ])
+            }
+            "player" => {
+                let player = self.player.ok_or("the player has no ship")?;
+                Ok(vec![Value::from_entity(player)])
+            }
+            "velocity" => {
+                let b = find_body(self.bodies, args.first().ok_or("`velocity` needs an id")?)?;
+                Ok(vec![
+                    Value::Num(b.velocity.x as f64),
+                    Value::Num(b.velocity.y as f64),
+                ])
+            }
+            // objective key text
+            //
+            // Sets an objective for the script to complete or fail itself.
+            "objective" => self.objective(args, None),
+            // objective_orbit key text ship body radius tolerance
+            //
+            // Completes once `ship`'s periapsis and apoapsis around `body` are
+            // both within `tolerance` of `radius`.
+            "objective_orbit" => {
+                let condition = Condition::Orbit {
+                    ship: entity(args, 2)?,
+                    body: entity(args, 3)?,
+                    radius: num(args, 4)?,
+                    tolerance: num(args, 5)?,
+                };
+                self.objective(args, Some(condition))
+            }
+            // objective_destroy key text target
+            "objective_destroy" => {
+                let condition = Condition::Destroy {
+                    target: entity(args, 2)?,
+                };
+                self.objective(args, Some(condition))
+            }
+            // objective_survive key text ship seconds
+            //
+            // Completes if `ship` is still around `seconds` from now, and
+            // fails if it isn't.
+            "objective_survive" => {
+                let condition = Condition::Survive {
+                    ship: entity(args, 2)?,
+                    until: self.clock.elapsed + num(args, 3)? as f64,
+                };
+                self.objective(args, Some(condition))
+            }
+            // objective_deliver key text depot tonnes
+            //
+            // Completes once `tonnes` more cargo is in `depot`'s hold.
+            "objective_deliver" => {
+                let condition = Condition::Deliver {
+                    depot: entity(args, 2)?,
+                    tonnes: num(args, 3)?,
+                    baseline: None,
+                };
+                self.objective(args, Some(condition))
+            }
+            // objective_land key text ship base
+            //
+            // Completes once `ship` is down at the surface base `base`.
+            "objective_land" => {
+                let condition = Condition::Land {
+                    ship: entity(args, 2)?,
+                    base: entity(args, 3)?,
+                };
+                self.objective(args, Some(condition))
+            }
+            "complete" => {
+                self.objectives
+                    .set_status(string(args, 0)?, ObjectiveStatus::Complete)?;
+                Ok(vec![])
+            }
+            "fail" => {
+                self.objectives
+                    .set_status(string(args, 0)?, ObjectiveStatus::Failed)?;
+                Ok(vec![])
+            }
+            "remove_objective" => {
+                self.objectives.remove(string(args, 0)?);
+                Ok(vec![])
+            }
+            // say sender text
+            "say" => {
+                self.messages.push(CommsMessage {
+                    sender: string(args, 0)?.to_string(),
+                    text: string(args, 1)?.to_string(),
+                    ..Default::default()
+                });
+                Ok(vec![])
+            }
+            // ask tag sender text choice...
+            //
+            // The player's answer raises a `reply` event with the tag and the
+            // index of the choice.
+            "ask" => {
+                let choices = args
+                    .get(3..)
+                    .unwrap_or_default()
+                    .iter()
+                    .map(|c| c.as_str().map(str::to_string))
+                    .collect::<Result<Vec<_>, _>>()?;
+                if choices.is_empty() {
+                    return Err("`ask` needs at least one choice".to_string());
+                }
+                self.messages.push(CommsMessage {
+                    sender: string(args, 1)?.to_string(),
+                    text: string(args, 2)?.to_string(),
+                    choices,
+                    reply_tag: Some(string(args, 0)?.to_string()),
+                    ..Default::default()
+                });
+                Ok(vec![])
+            }
+            // camera_pan x y zoom seconds
+            //
+            // Takes the camera away from the player for a cutscene. Shots are
+            // played in order, then the camera returns to the player, raising
+            // a `cutscene_end` event. A zoom of 0 keeps the current zoom.
+            "camera_pan" => {
+                self.shots.push(CameraShot {
+                    target: ShotTarget::Point(Vec3::new(num(args, 0)?, num(args, 1)?, 0.0)),
+                    zoom: num(args, 2)?,
+                    duration: num(args, 3)?,
+                });
+                Ok(vec![])
+            }
+            // camera_follow id zoom seconds
+            "camera_follow" => {
+                let entity = args.first().ok_or("`camera_follow` needs an id")?;
+                self.shots.push(CameraShot {
+                    target: ShotTarget::Entity(entity.as_entity()?),
+                    zoom: num(args, 1)?,
+                    duration: num(args, 2)?,
+                });
+                Ok(vec![])
+            }
+            // name id text
+            //
+            // Names `id`, for its label on the map.
+            "name" => {
+                let name = Name::new(string(args, 1)?.to_string());
+                self.commands.entity(entity(args, 0)?).insert(name);
+                Ok(vec![])
+            }
+            // planet x y -> id
+            //
+            // Finds the astronomical body closest to (x, y).
+            "planet" => {
+                let point = Vec3::new(num(args, 0)?, num(args, 1)?, 0.0);
+                let nearest = self
+                    .astro
+                    .iter()
+                    .min_by(|a, b| {
+                        let (a, b) = (a.position.distance(point), b.position.distance(point));
+                        a.total_cmp(&b)
+                    })
+                    .ok_or("there are no astronomical bodies")?;
+                Ok(vec![Value::from_entity(nearest.entity)])
+            }
+            // spawn_impactor target seconds speed heading deflection [objective] -> id
+            //
+            // Spawns an asteroid on course to hit `target` in `seconds`, at
+            // `speed` relative to the target, travelling along `heading` (in
+            // radians). It has to be pushed at least `deflection` clear of the
+            // target's surface to miss, which completes `objective` if given,
+            // and fails it otherwise. Raises `impact` or `impactor_passed`.
+            "spawn_impactor" => {
+                let target = args.first().ok_or("`spawn_impactor` needs a target")?;
+                let objective = match args.get(5) {
+                    Some(key) => Some(key.as_str()?.to_string()),
+                    None => None,
+                };
+                let impactor = impactor::spawn_impactor(
+                    self.commands,
+                    self.asset_server,
+                    self.astro,
+                    target.as_entity()?,
+                    num(args, 1)?,
+                    Vec2::from_angle(num(args, 3)?).extend(0.0) * num(args, 2)?,
+                    num(args, 4)?,
+                    objective,
+                )?;
+                Ok(vec![Value::from_entity(impactor)])
+            }
+            // spawn_body x y vx vy mass radius [rotation_rate] -> id
+            //
+            // Spawns a planet, or anything else big enough to pull on ships,
+            // turning at `rotation_rate` radians per second.
+            "spawn_body" => {
+                let radius = num(args, 5)?;
+                let sprite = SpriteBundle {
+                    sprite: Sprite {
+                        custom_size: Some(Vec2::splat(radius * 2.0)),
+                        ..Default::default()
+                    },
+                    texture: self.asset_server.load("planet.png"),
+                    ..Default::default()
+                };
+                let body = level::spawn_body(
+                    self.commands,
+                    sprite,
+                    radius,
+                    num(args, 4)?,
+                    Vec3::new(num(args, 0)?, num(args, 1)?, 0.0),
+                    Vec3::new(num(args, 2)?, num(args, 3)?, 0.0),
+                );
+                if let Some(rate) = args.get(6) {
+                    self.commands.entity(body).insert(AstroObject {
+                        radius,
+                        rotation_rate: rate.as_num()? as f32,
+                    });
+                }
+                Ok(vec![Value::from_entity(body)])
+            }
+            // star id
+            //
+            // Makes a body the star which lights the system.
+            "star" => {
+                self.commands.entity(entity(args, 0)?).insert(Star);
+                Ok(vec![])
+            }
+            // spawn_scene path
+            //
+            // Spawns everything in a scene file saved from the scenes panel,
+            // once it's loaded.
+            "spawn_scene" => {
+                scenes::spawn_scene(self.commands, self.asset_server, string(args, 0)?);
+                Ok(vec![])
+            }
+            // spawn_belt body inner outer count [seed]
+            //
+            // Spawns a belt of `count` asteroids orbiting `body`, between
+            // `inner` and `outer` from it.
+            "spawn_belt" => {
+                let body = self
+                    .astro
+                    .iter()
+                    .find(|b| Some(b.entity) == args.first().and_then(|a| a.as_entity().ok()))
+                    .ok_or("`spawn_belt` needs an astronomical body")?;
+                let count = num(args, 3)?.max(0.0) as usize;
+                let seed = args.get(4).map_or(Ok(0.0), |s| s.as_num())? as u64;
+                asteroid::spawn_belt(
+                    self.commands,
+                    self.asset_server.load(asteroid::ASTEROID_TEXTURE),
+                    body.position,
+                    body.velocity,
+                    body.mu,
+                    num(args, 1)?,
+                    num(args, 2)?,
+                    count,
+                    seed,
+                );
+                Ok(vec![Value::Num(count as f64)])
+            }
+            // spawn_station body radius angle [ports] -> id
+            //
+            // Spawns a station on a circular orbit of `radius` around `body`,
+            // `angle` radians around from its +X. Ships docked to it are
+            // refueled and repaired, raising `serviced` once they're done.
+            "spawn_station" => {
+                let body = self
+                    .astro
+                    .iter()
+                    .find(|b| Some(b.entity) == args.first().and_then(|a| a.as_entity().ok()))
+                    .ok_or("`spawn_station` needs an astronomical body")?;
+                let ports = args.get(3).map_or(Ok(4.0), |p| p.as_num())?.max(1.0) as u32;
+                let station = station::spawn_station(
+                    self.commands,
+                    self.asset_server.load("ship_1.png"),
+                    body.position,
+                    body.velocity,
+                    body.mu,
+                    num(args, 1)?,
+                    num(args, 2)?,
+                    ports,
+                );
+                Ok(vec![Value::from_entity(station)])
+            }
+            // spawn_base body angle -> id
+            //
+            // Spawns a base on the surface of `body`, `angle` radians round
+            // from its +X, which turns with the body. Ships landing at it are
+            // refueled and repaired, raising `serviced` once they're done.
+            "spawn_base" => {
+                let body = self
+                    .astro
+                    .iter()
+                    .find(|b| Some(b.entity) == args.first().and_then(|a| a.as_entity().ok()))
+                    .ok_or("`spawn_base` needs an astronomical body")?;
+                let base = surface_base::spawn_surface_base(
+                    self.commands,
+                    self.asset_server.load("dot.png"),
+                    body.entity,
+                    body.radius,
+                    num(args, 1)?,
+                );
+                Ok(vec![Value::from_entity(base)])
+            }
+            // spawn_at_base base [program] -> id
+            //
+            // Spawns a ship on the pad of `base`, where it settles onto the
+            // ground.
+            "spawn_at_base" => {
+                let base = entity(args, 0)?;
+                let pad = self
+                    .pads
+                    .iter()
+                    .find(|p| p.base == base)
+                    .ok_or("`spawn_at_base` needs a surface base")?;
+                let ship = self.spawn_ship(pad.position, pad.velocity, args.get(1))?;
+                Ok(vec![Value::from_entity(ship)])
+            }
+            // star_system name -> index
+            //
+            // Adds a star system. Only the one the player is in is simulated.
+            "star_system" => {
+                let index = self.star_systems.add(string(args, 0)?.to_string());
+                Ok(vec![Value::Num(index as f64)])
+            }
+            // in_system id system
+            //
+            // Moves something into another star system, where it stays put.
+            // Everything starts out in the system the player is in.
+            "in_system" => {
+                let system = self.star_systems.check(num(args, 1)? as usize)?;
+                self.commands
+                    .entity(entity(args, 0)?)
+                    .insert(InSystem(system));
+                Ok(vec![])
+            }
+            // jump_point x y to ax ay [system] -> id
+            //
+            // Spawns a jump point at (x, y), in `system` or the player's. Ships
+            // flying into it come out at (ax, ay) in system `to`, raising
+            // `jumped` with the ship and the systems it left and arrived in.
+            "jump_point" => {
+                let to = self.star_systems.check(num(args, 2)? as usize)?;
+                let system = match args.get(5) {
+                    Some(system) => self.star_systems.check(system.as_num()? as usize)?,
+                    None => self.star_systems.active,
+                };
+                let point = JumpPoint {
+                    to,
+                    arrival: Vec3::new(num(args, 3)?, num(args, 4)?, 0.0),
+                    radius: 20.0,
+                };
+                let id = self
+                    .commands
+                    .spawn((
+                        Name::new(format!("Jump point to {}", self.star_systems.names[to])),
+                        point,
+                        InSystem(system),
+                        SpriteBundle {
+                            sprite: Sprite {
+                                custom_size: Some(Vec2::splat(point.radius * 2.0)),
+                                color: Color::rgba(0.6, 0.4, 1.0, 0.6),
+                                ..Default::default()
+                            },
+                            texture: self.asset_server.load("dot.png"),
+                            transform: Transform::from_xyz(num(args, 0)?, num(args, 1)?, 0.0),
+                            ..Default::default()
+                        },
+                    ))
+                    .id();
+                Ok(vec![Value::from_entity(id)])
+            }
+            // jump_drive id [charge_time]
+            //
+            // Fits `id` with a jump drive, which its program can use with `jump`.
+            "jump_drive" => {
+                let drive = JumpDrive {
+                    charge_time: args.get(1).map_or(Ok(10.0), |t| t.as_num())? as f32,
+                    ..Default::default()
+                };
+                self.commands.entity(entity(args, 0)?).insert(drive);
+                Ok(vec![])
+            }
+            // market station commodity stock buy sell
+            //
+            // Lists a commodity at `station`'s market, with `stock` tonnes to
+            // sell, selling at `buy` credits a tonne and buying at `sell`.
+            "market" => {
+                let listing = Listing::new(
+                    string(args, 1)?.to_string(),
+                    num(args, 2)?,
+                    num(args, 3)?,
+                    num(args, 4)?,
+                );
+                self.commands.entity(entity(args, 0)?).add(
+                    move |station: Entity, world: &mut World| {
+                        let Some(mut station) = world.get_entity_mut(station) else {
+                            return;
+                        };
+                        match station.get_mut::<Market>() {
+                            Some(mut market) => market.list(listing),
+                            None => {
+                                let mut market = Market::default();
+                                market.list(listing);
+                                station.insert(market);
+                            }
+                        }
+                    },
+                );
+                Ok(vec![])
+            }
+            // production station commodity rate [equilibrium]
+            //
+            // Has `station` make `rate` tonnes of a commodity it lists an
+            // hour, or use them up if negative. Its prices rise as the stock
+            // falls below `equilibrium`, and fall as it rises above.
+            "production" => {
+                let commodity = string(args, 1)?.to_string();
+                let rate = num(args, 2)?;
+                let equilibrium = args.get(3).map(|e| e.as_num()).transpose()?;
+                self.commands.entity(entity(args, 0)?).add(
+                    move |station: Entity, world: &mut World| {
+                        let Some(mut market) = world.get_mut::<Market>(station) else {
+                            return;
+                        };
+                        let Some(listing) = market
+                            .listings
+                            .iter_mut()
+                            .find(|l| l.commodity == commodity)
+                        else {
+                            return;
+                        };
+                        listing.production = rate;
+                        if let Some(equilibrium) = equilibrium {
+                            listing.equilibrium = equilibrium as f32;
+                        }
+                    },
+                );
+                Ok(vec![])
+            }
+            // price station commodity -> buy sell stock
+            "price" => {
+                let station = entity(args, 0)?;
+                self.markets
+                    .get(station)
+                    .map_err(|_| format!("{} has no market", station.index()))?
+                    .quote(string(args, 1)?)
+            }
+            // credits faction [amount] -> credits
+            //
+            // Gets a faction's credits, or with `amount`, sets them.
+            "credits" => {
+                let faction = Faction(num(args, 0)? as u32);
+                if let Some(amount) = args.get(1) {
+                    *self.accounts.balance_mut(faction) = amount.as_num()? as f32;
+                }
+                Ok(vec![Value::Num(self.accounts.balance(faction) as f64)])
+            }
+            // set_position id x y
+            "set_position" => {
+                let body = find_body(
+                    self.bodies,
+                    args.first().ok_or("`set_position` needs an id")?,
+                )?;
+                let at = Vec3::new(num(args, 1)?, num(args, 2)?, 0.0);
+                self.commands
+                    .entity(body.entity)
+                    .add(move |e: Entity, world: &mut World| {
+                        if let Some(mut transform) = world.get_mut::<Transform>(e) {
+                            transform.translation = at;
+                        }
+                        if let Some(mut trail) = world.get_mut::<Trail>(e) {
+                            trail.clear();
+                        }
+                    });
+                Ok(vec![])
+            }
+            // clear
+            //
+            // Despawns everything that moves, except the player's ship, so the
+            // script can set the level up from scratch.
+            "clear" => {
+                for body in self.bodies.iter().filter(|b| Some(b.entity) != self.player) {
+                    if let Some(e) = self.commands.get_entity(body.entity) {
+                        e.despawn_recursive();
+                    }
+                }
+                Ok(vec![])
+            }
+            // set_velocity id vx vy
+            "set_velocity" => {
+                let body = find_body(
+                    self.bodies,
+                    args.first().ok_or("`set_velocity` needs an id")?,
+                )?;
+                let kinimatics = Kinimatics {
+                    velocity: Vec3::new(num(args, 1)?, num(args, 2)?, 0.0),
+                    mass: body.mass,
+                    ..Default::default()
+                };
+                self.commands.entity(body.entity).insert(kinimatics);
+                Ok(vec![])
+            }
+            // on_rails id parent
+            //
+            // Puts `id` on rails around `parent`, on the orbit it's on now, so
+            // it keeps to it exactly however long the game runs.
+            "on_rails" => {
+                let body = find_body(self.bodies, args.first().ok_or("`on_rails` needs an id")?)?;
+                let parent =
+                    find_body(self.bodies, args.get(1).ok_or("`on_rails` needs a parent")?)?;
+                if body.entity == parent.entity {
+                    return Err("a body can't be on rails around itself".to_string());
+                }
+                self.commands.entity(body.entity).insert(OnRails {
+                    parent: parent.entity,
+                    position: body.position - parent.position,
+                    velocity: body.velocity - parent.velocity,
+                    epoch: self.clock.elapsed,
+                });
+                Ok(vec![])
+            }
+            // off_rails id
+            //
+            // Hands `id` back to the physics.
+            "off_rails" => {
+                self.commands.entity(entity(args, 0)?).remove::<OnRails>();
+                Ok(vec![])
+            }
+            // player_ship id
+            //
+            // Lets the player fly `id` as well as their own ship. Tab, or the
+            // list of their ships, switches between them.
+            "player_ship" => {
+                self.commands.entity(entity(args, 0)?).insert((
+                    PlayerShip,
+                    Faction::PLAYER,
+                    MissileLauncher::default(),
+                ));
+                Ok(vec![])
+            }
+            // trail id length interval
+            //
+            // Keeps the last `length` positions of `id`, one every `interval`
+            // seconds, to draw behind it. A length of 0 stops drawing it.
+            "trail" => {
+                let id = entity(args, 0)?;
+                let length = num(args, 1)?.max(0.0) as usize;
+                if length == 0 {
+                    self.commands.entity(id).remove::<Trail>();
+                } else {
+                    let interval = num(args, 2)? as f64;
+                    self.commands
+                        .entity(id)
+                        .add(move |id: Entity, world: &mut World| {
+                            let Some(mut id) = world.get_entity_mut(id) else {
+                                return;
+                            };
+                            let color = id
+                                .get::<Trail>()
+                                .map_or(Trail::default().color, |t| t.color);
+                            id.insert(Trail::new(length, interval, color));
+                        });
+                }
+                Ok(vec![])
+            }
+            // track_flybys id reference
+            //
+            // Starts keeping track of how much of `id`'s change in velocity
+            // comes from flybys, measured by its orbit around `reference`.
+            // Raises `flyby` with the ship, the gain, and the total.
+            "track_flybys" => {
+                let (Some(id), Some(reference)) = (args.first(), args.get(1)) else {
+                    return Err("`track_flybys` needs an id and a reference body".to_string());
+                };
+                self.commands
+                    .entity(id.as_entity()?)
+                    .insert(DeltaVLedger::new(reference.as_entity()?));
+                Ok(vec![])
+            }
+            // join_blackboard id
+            //
+            // Lets a ship share a blackboard with the rest of its faction.
+            "join_blackboard" => {
+                let entity = args
+                    .first()
+                    .ok_or("`join_blackboard` needs an id")?
+                    .as_entity()?;
+                self.commands.entity(entity).insert(Blackboard::default());
+                Ok(vec![])
+            }
+            // perturbations enabled
+            //
+            // Turns atmospheric drag and the tugging of distant bodies on or off.
+            "perturbations" => {
+                self.perturbations.enabled = num(args, 0)? != 0.0;
+                Ok(vec![])
+            }
+            // station_keeping id body radius tolerance
+            //
+            // Asks `id` to hold an orbit of `radius` around `body`. Raises
+            // `station_drift` and `station_held` as it leaves and returns.
+            "station_keeping" => {
+                let [id, body, ..] = args else {
+                    return Err("`station_keeping` needs an id and a body".to_string());
+                };
+                let station = StationKeeping::new(body.as_entity()?, num(args, 2)?, num(args, 3)?);
+                self.commands.entity(id.as_entity()?).insert(station);
+                Ok(vec![])
+            }
+            // depot id wants
+            //
+            // Lets freighters trade cargo with `id`, which wants to keep
+            // `wants` tonnes on hand.
+            "depot" => {
+                let id = args.first().ok_or("`depot` needs an id")?.as_entity()?;
+                let wants = num(args, 1)?;
+                self.commands.entity(id).insert(Depot { wants });
+                Ok(vec![])
+            }
+            // freighter id
+            //
+            // Hands `id` over to the logistics AI, which flies cargo between
+            // depots. Raises `delivery` with the freighter, both depots and
+            // the tonnes delivered.
+            "freighter" => {
+                let id = args.first().ok_or("`freighter` needs an id")?.as_entity()?;
+                self.commands
+                    .entity(id)
+                    .insert((Freighter::default(), Autopilot::default()));
+                Ok(vec![])
+            }
+            // cpu_budget id instructions microseconds
+            //
+            // Limits how much `id`'s program may run each frame. Going over
+            // raises `cpu_overrun`.
+            "cpu_budget" => {
+                let budget = CpuBudget {
+                    instructions: num(args, 1)?.max(1.0) as usize,
+                    time: Duration::from_micros(num(args, 2)?.max(1.0) as u64),
+                };
+                self.commands.entity(args[0].as_entity()?).insert(budget);
+                Ok(vec![])
+            }
+            "time" => Ok(vec![Value::Num(self.clock.met() as f64)]),
+            // schedule event seconds [period] -> id
+            "schedule" => scripting::schedule(self.scheduler, args, Action::Script),
+            // at event time -> id
+            //
+            // Raises `event` when the mission elapsed time reaches `time`.
+            "at" => {
+                let event = ScriptEvent {
+                    name: string(args, 0)?.to_string(),
+                    args: vec![],
+                };
+                let id = self
+                    .scheduler
+                    .at(num(args, 1)? as f64, Action::Script(event));
+                Ok(vec![Value::Num(id as f64)])
+            }
+            "cancel" => {
+                let id = num(args, 0)? as u64;
+                Ok(vec![Value::Num(self.scheduler.cancel(id) as u8 as f64)])
+            }
+            // set_epoch seconds
+            //
+            // Sets the date the scenario starts on, in seconds since 1970-01-01.
+            "set_epoch" => {
+                let epoch = args.first().ok_or("`set_epoch` needs a date")?.as_num()?;
+                self.clock.epoch = epoch;
+                Ok(vec![])
+            }
+            other => Err(format!("unknown function `{}`", other)),
+        }
+    }
+}
+
+/// World-wide settings the director can change.
+#[derive(SystemParam)]
+struct DirectorResources<'w> {
+    clock: ResMut<'w, SimulationClock>,
+    scheduler: ResMut<'w, Scheduler>,
+    perturbations: ResMut<'w, Perturbations>,
+    star_systems: ResMut<'w, StarSystems>,
+    accounts: ResMut<'w, Accounts>,
+}
+
+/// Astronomical bodies, and the bases on their surfaces.
+#[derive(SystemParam)]
+struct Planets<'w, 's> {
+    astro: Query<
+        'w,
+        's,
+        (
+            Entity,
+            &'static AstroObject,
+            &'static Kinimatics,
+            &'static Transform,
+        ),
+    >,
+    bases: Query<'w, 's, (Entity, &'static Transform, &'static Parent), With<SurfaceBase>>,
+}
+
+/// :SYSTEM: Forwards game events to the director script and runs it for one frame.
+#[allow(clippy::too_many_arguments)]
+fn director_system(
+    mut commands: Commands,
+    director: Option<ResMut<MissionDirector>>,
+    sources: Res<Assets<ScriptSource>>,
+    asset_server: Res<AssetServer>,
+    sprites: Res<ShipSprites>,
+    bodies: Query<(Entity, &Kinimatics, &Transform, Option<&Controlled>)>,
+    planets: Planets,
+    mut objectives: ResMut<Objectives>,
+    mut messages: EventWriter<CommsMessage>,
+    mut shots: EventWriter<CameraShot>,
+    mut events: EventReader<ScriptEvent>,
+    mut destroyed_ships: RemovedComponents<Ship>,
+    mut resources: DirectorResources,
+    markets: Query<&'static Market>,
+    physics: Res<PhysicsConfig>,
+    time: Res<Time>,
+) {
+    let DirectorResources {
+        clock,
+        scheduler,
+        perturbations,
+        star_systems,
+        accounts,
+    } = &mut resources;
+
+    let Some(mut director) = director else { return };
+    let director = &mut *director;
+
+    if director.program.is_none() {
+        let Some(source) = sources.get(&director.source) else {
+            return;
+        };
+        match Program::parse(&source.0) {
+            Ok(p) => {
+                director.vm.bind_callbacks(&p);
+                director.vm.raise("start", vec![]);
+                director.program = Some(p);
+            }
+            Err(e) => {
+                error!("mission director: {}", e);
+                director.program = Some(Program::default());
+                director.vm.fault(e);
+                return;
+            }
+        }
+    }
+
+    for event in events.iter() {
+        director.vm.raise(&event.name, event.args.clone());
+    }
+    for ship in destroyed_ships.iter() {
+        director
+            .vm
+            .raise("ship_destroyed", vec![Value::from_entity(ship)]);
+    }
+    director.vm.raise_unless_pending(
+        "tick",
+        vec![
+            Value::Num(time.delta_seconds() as f64),
+            Value::Num(clock.elapsed),
+        ],
+    );
+
+    let player = bodies
+        .iter()
+        .find(|(.., controlled)| controlled.is_some())
+        .map(|(entity, ..)| entity);
+    let bodies: Vec<BodySnapshot> = bodies
+        .iter()
+        .map(|(entity, kin, transform, _)| BodySnapshot {
+            entity,
+            position: transform.translation,
+            velocity: kin.velocity,
+            mass: kin.mass,
+        })
+        .collect();
+    let Planets { astro, bases } = &planets;
+    let pads: Vec<Pad> = bases
+        .iter()
+        .filter_map(|(base, local, parent)| {
+            let (_, body, kin, transform) = astro.get(parent.get()).ok()?;
+            Some(Pad::new(base, local, body.radius, transform, kin.velocity))
+        })
+        .collect();
+    let astro = impactor::snapshot(astro.iter(), &physics);
+
+    let mut host = DirectorHost {
+        commands: &mut commands,
+        asset_server: &asset_server,
+        sprites: &sprites,
+        bodies: &bodies,
+        player,
+        astro: &astro,
+        pads: &pads,
+        objectives: &mut objectives,
+        messages: Vec::new(),
+        shots: Vec::new(),
+        clock,
+        scheduler,
+        perturbations,
+        star_systems,
+        accounts,
+        markets: &markets,
+    };
+
+    let was_faulted = matches!(director.vm.state(), VmState::Faulted(_));
+    director.vm.run(
+        director.program.as_ref().unwrap(),
+        &mut host,
+        time.delta_seconds(),
+        DIRECTOR_INSTRUCTION_BUDGET,
+    );
+    messages.send_batch(host.messages);
+    shots.send_batch(host.shots);
+
+    for line in director.vm.output.drain(..) {
+        info!("mission director: {}", line);
+    }
+    if let (false, VmState::Faulted(e)) = (was_faulted, director.vm.state()) {
+        error!("mission director: {}", e);
+    }
+}
@@ -0,0 +1,254 @@
+use bevy::prelude::*;
+
+use super::input_map::{Action, InputMap};
+use super::physics::{ApplyImpulse, Kinimatics, SimulationSet};
+use super::scripting::{ScriptEvent, Value};
+use super::ships::{Controlled, Engine, Throttle};
+use super::station::{self, Station};
+
+pub struct DockingPlugin;
+
+impl Plugin for DockingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<DockingRequest>()
+            .add_system(player_docking_system.in_set(SimulationSet))
+            .add_system(
+                docking_system
+                    .after(player_docking_system)
+                    .in_set(SimulationSet),
+            );
+    }
+}
+
+/// :COMPONENT: Lets a ship dock with, or be docked to, other ships.
+///
+/// Docking only succeeds when both ships have a port, they are close enough,
+/// are moving slowly enough relative to each other, and the docking ship is
+/// pointed at its target. A [`Station`] takes as many ships as it has ports,
+/// anything else only one.
+#[derive(Reflect, Component, Clone, Copy)]
+#[reflect(Component)]
+pub struct DockingPort {
+    /// Maximum distance between the two ships.
+    pub range: f32,
+    /// Maximum difference in velocity between the two ships.
+    pub max_relative_speed: f32,
+    /// Maximum angle, in radians, between the docking ship's nose and its target.
+    pub max_misalignment: f32,
+    /// Impulse pushing the two ships apart when undocking.
+    pub separation_impulse: f32,
+}
+
+impl Default for DockingPort {
+    fn default() -> Self {
+        Self {
+            range: 30.0,
+            max_relative_speed: 5.0,
+            max_misalignment: 0.35,
+            separation_impulse: 200.0,
+        }
+    }
+}
+
+/// :COMPONENT: Added to a ship while it is docked to `to`.
+///
+/// A docked ship is a child of the ship it docked with, and doesn't have its
+/// own [`Kinimatics`]: its mass is added to the parent's, and the assembly flies
+/// as one body. The ship's own kinimatics are kept here until it undocks.
+#[derive(Component, Clone, Copy)]
+pub struct Docked {
+    pub to: Entity,
+    kinimatics: Kinimatics,
+}
+
+impl Docked {
+    /// Mass the docked ship adds to the ship it is docked with.
+    pub fn mass(&self) -> f32 {
+        self.kinimatics.mass
+    }
+}
+
+/// :EVENT: Asks for a ship to dock with another ship, or to undock.
+#[derive(Clone, Copy, Debug)]
+pub enum DockingRequest {
+    Dock { ship: Entity, target: Entity },
+    Undock { ship: Entity },
+}
+
+/// Why a docking attempt failed.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum DockingError {
+    OutOfRange,
+    TooFast,
+    Misaligned,
+}
+
+/// Checks whether `ship` can dock with `target` right now.
+pub fn can_dock(
+    ship: (&Transform, &Kinimatics, &DockingPort),
+    target: (&Transform, &Kinimatics, &DockingPort),
+) -> Result<(), DockingError> {
+    let (t1, k1, p1) = ship;
+    let (t2, k2, p2) = target;
+
+    let offset = t2.translation - t1.translation;
+    if offset.length() > p1.range.min(p2.range) {
+        return Err(DockingError::OutOfRange);
+    }
+
+    if (k2.velocity - k1.velocity).length() > p1.max_relative_speed.min(p2.max_relative_speed) {
+        return Err(DockingError::TooFast);
+    }
+
+    let nose = t1.rotation.mul_vec3(Vec3::Y);
+    if nose.angle_between(offset) > p1.max_misalignment {
+        return Err(DockingError::Misaligned);
+    }
+
+    Ok(())
+}
+
+/// :SYSTEM: Lets the player dock the controlled ship with the nearest ship in
+/// range, or undock it, with the keys bound to [`Action::Dock`] and
+/// [`Action::Undock`].
+#[allow(clippy::type_complexity)]
+fn player_docking_system(
+    player: Query<(Entity, &Transform, Option<&Docked>), With<Controlled>>,
+    ports: Query<(Entity, &Transform), (With<DockingPort>, With<Kinimatics>)>,
+    input: Res<Input<KeyCode>>,
+    map: Res<InputMap>,
+    mut requests: EventWriter<DockingRequest>,
+) {
+    for (ship, transform, docked) in player.iter() {
+        if map.just_pressed(Action::Undock, &input) && docked.is_some() {
+            requests.send(DockingRequest::Undock { ship });
+        }
+
+        if map.just_pressed(Action::Dock, &input) && docked.is_none() {
+            let nearest = ports
+                .iter()
+                .filter(|(e, _)| *e != ship)
+                .min_by(|(_, a), (_, b)| {
+                    let da = a.translation.distance_squared(transform.translation);
+                    let db = b.translation.distance_squared(transform.translation);
+                    da.total_cmp(&db)
+                });
+
+            if let Some((target, _)) = nearest {
+                requests.send(DockingRequest::Dock { ship, target });
+            }
+        }
+    }
+}
+
+/// :SYSTEM: Carries out docking and undocking requests.
+#[allow(clippy::type_complexity)]
+fn docking_system(
+    mut commands: Commands,
+    mut requests: EventReader<DockingRequest>,
+    mut ships: Query<(
+        &mut Transform,
+        Option<&mut Kinimatics>,
+        &DockingPort,
+        Option<&Docked>,
+        Option<&mut Engine>,
+    )>,
+    stations: Query<&Station>,
+    mut script_events: EventWriter<ScriptEvent>,
+    mut impulses: EventWriter<ApplyImpulse>,
+) {
+    for request in requests.iter() {
+        match *request {
+            DockingRequest::Dock { ship, target } => {
+                if ship == target {
+                    continue;
+                }
+                let ports = stations.get(target).map_or(1, |s| s.ports);
+                let docked = ships.iter().filter_map(|(.., d, _)| d);
+                if !station::has_free_port(target, ports, docked) {
+                    debug!("{:?} can't dock with {:?}: no free port", ship, target);
+                    continue;
+                }
+
+                let Ok([s, t]) = ships.get_many_mut([ship, target]) else {
+                    continue;
+                };
+                let (mut ship_t, Some(ship_k), ship_p, None, ship_engine) = s else {
+                    continue;
+                };
+                let (target_t, Some(mut target_k), target_p, None, _) = t else {
+                    continue;
+                };
+
+                if let Err(e) = can_dock(
+                    (&*ship_t, &*ship_k, ship_p),
+                    (&*target_t, &*target_k, target_p),
+                ) {
+                    debug!("{:?} can't dock with {:?}: {:?}", ship, target, e);
+                    continue;
+                }
+
+                // momentum is conserved when the two bodies join
+                let total_mass = ship_k.mass + target_k.mass;
+                target_k.velocity = (ship_k.velocity * ship_k.mass
+                    + target_k.velocity * target_k.mass)
+                    / total_mass;
+                target_k.mass = total_mass;
+
+                // express the ship's transform relative to the ship it docked with
+                let inverse = target_t.rotation.inverse();
+                ship_t.translation = inverse.mul_vec3(ship_t.translation - target_t.translation);
+                ship_t.rotation = inverse * ship_t.rotation;
+
+                if let Some(mut engine) = ship_engine {
+                    engine.throttle = Throttle::Fixed(false);
+                }
+
+                commands.entity(ship).remove::<Kinimatics>().insert(Docked {
+                    to: target,
+                    kinimatics: *ship_k,
+                });
+                commands.entity(target).add_child(ship);
+
+                script_events.send(ScriptEvent {
+                    name: "docked".to_string(),
+                    args: vec![Value::from_entity(ship), Value::from_entity(target)],
+                });
+            }
+            DockingRequest::Undock { ship } => {
+                let Ok((_, _, port, Some(docked), _)) = ships.get(ship) else {
+                    continue;
+                };
+                let (port, docked) = (*port, *docked);
+
+                let Ok([s, t]) = ships.get_many_mut([ship, docked.to]) else {
+                    continue;
+                };
+                let (mut ship_t, _, _, _, _) = s;
+                let (target_t, Some(mut target_k), _, _, _) = t else {
+                    continue;
+                };
+
+                *ship_t = target_t.mul_transform(*ship_t);
+
+                // the ship leaves moving with the other, and then the two are
+                // pushed apart
+                let away = (ship_t.translation - target_t.translation).normalize_or_zero();
+                let mut kinimatics = docked.kinimatics;
+                target_k.mass -= kinimatics.mass;
+                kinimatics.velocity = target_k.velocity;
+                let separation = away * port.separation_impulse;
+                impulses.send(ApplyImpulse::new(ship, separation));
+                impulses.send(ApplyImpulse::new(docked.to, -separation));
+
+                commands.entity(docked.to).remove_children(&[ship]);
+                commands.entity(ship).remove::<Docked>().insert(kinimatics);
+
+                script_events.send(ScriptEvent {
+                    name: "undocked".to_string(),
+                    args: vec![Value::from_entity(ship), Value::from_entity(docked.to)],
+                });
+            }
+        }
+    }
+}
@@ -0,0 +1,122 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+use super::clock::SimulationClock;
+use super::trade::Market;
+
+pub struct EconomyPlugin;
+
+impl Plugin for EconomyPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Economy>()
+            .add_system(economy_system)
+            .add_system(economy_panel_system);
+    }
+}
+
+/// Seconds of simulation time between economy ticks.
+const ECONOMY_INTERVAL: f64 = 60.0;
+
+/// How far prices move towards where the stock says they should be, each
+/// tick.
+const PRICE_DRIFT: f32 = 0.1;
+
+/// How strongly prices follow the stock. At 0.5, a quarter of the stock the
+/// station wants doubles the price.
+const ELASTICITY: f32 = 0.5;
+
+/// Furthest prices can stray from their base price, either way, as a
+/// multiple of it.
+const MAX_PRICE_RATIO: f32 = 4.0;
+
+/// Resource which keeps the station economy ticking over. F5 shows prices
+/// everywhere.
+#[derive(Resource, Default)]
+pub struct Economy {
+    pub open: bool,
+    /// Mission elapsed time of the last tick.
+    last_tick: f64,
+}
+
+/// The price a listing is heading for, given its stock.
+fn target_price(base_price: f32, stock: f32, equilibrium: f32) -> f32 {
+    let ratio = (equilibrium.max(1.0) / stock.max(1.0)).powf(ELASTICITY);
+    base_price * ratio.clamp(1.0 / MAX_PRICE_RATIO, MAX_PRICE_RATIO)
+}
+
+/// :SYSTEM: Every [`ECONOMY_INTERVAL`], stations make and use up commodities,
+/// and their prices drift with their stock. Goes by the simulation clock, so
+/// it holds still when the simulation does, but doesn't run with physics.
+fn economy_system(
+    mut economy: ResMut<Economy>,
+    mut markets: Query<&mut Market>,
+    clock: Res<SimulationClock>,
+) {
+    while clock.elapsed - economy.last_tick >= ECONOMY_INTERVAL {
+        economy.last_tick += ECONOMY_INTERVAL;
+        let hours = (ECONOMY_INTERVAL / 3600.0) as f32;
+        for mut market in markets.iter_mut() {
+            for listing in market.listings.iter_mut() {
+                listing.stock = (listing.stock + listing.production * hours).max(0.0);
+                let target = target_price(listing.base_price, listing.stock, listing.equilibrium);
+                let price = listing.price();
+                listing.set_price(price + (target - price) * PRICE_DRIFT);
+            }
+        }
+    }
+}
+
+/// :SYSTEM: Shows every market's prices, and whether they're rising or falling.
+fn economy_panel_system(
+    mut contexts: EguiContexts,
+    mut economy: ResMut<Economy>,
+    markets: Query<(Entity, &Market, Option<&Name>)>,
+    input: Res<Input<KeyCode>>,
+) {
+    if input.just_pressed(KeyCode::F5) {
+        economy.open = !economy.open;
+    }
+    if !economy.open {
+        return;
+    }
+
+    let mut open = true;
+    egui::Window::new("Economy")
+        .open(&mut open)
+        .show(contexts.ctx_mut(), |ui| {
+            if markets.is_empty() {
+                ui.weak("no markets");
+                return;
+            }
+            egui::Grid::new("economy").striped(true).show(ui, |ui| {
+                for heading in ["station", "commodity", "stock", "buy", "sell", ""] {
+                    ui.strong(heading);
+                }
+                ui.end_row();
+                for (entity, market, name) in markets.iter() {
+                    let station = name.map_or(format!("station {}", entity.index()), |n| {
+                        format!("{} ({})", n, entity.index())
+                    });
+                    for listing in &market.listings {
+                        ui.label(&station);
+                        ui.label(&listing.commodity);
+                        ui.label(format!("{:.0} t", listing.stock));
+                        ui.label(format!("{:.1}", listing.buy));
+                        ui.label(format!("{:.1}", listing.sell));
+                        let target =
+                            target_price(listing.base_price, listing.stock, listing.equilibrium);
+                        let price = listing.price();
+                        ui.label(if target > price * 1.01 {
+                            "rising"
+                        } else if target < price * 0.99 {
+                            "falling"
+                        } else {
+                            ""
+                        });
+                        ui.end_row();
+                    }
+                }
+            });
+        });
+    economy.open = open;
+}
@@ -0,0 +1,271 @@
+use std::collections::{HashSet, VecDeque};
+
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+use super::clock::{format_met, SimulationClock};
+use super::cutscene::Cutscene;
+use super::scripting::{ScriptEvent, ShipProgram, Value, VmState};
+use super::ships::Detonation;
+
+pub struct EventLogPlugin;
+
+impl Plugin for EventLogPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GameEventLog>()
+            .add_systems((
+                script_event_log_system,
+                detonation_log_system,
+                script_fault_log_system,
+            ))
+            .add_system(event_log_panel_system);
+    }
+}
+
+/// Oldest entries are dropped once the log holds this many.
+const MAX_ENTRIES: usize = 500;
+
+/// What sort of thing a [`LogEntry`] is about, for filtering the log.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum EventCategory {
+    Collision,
+    Launch,
+    Docking,
+    Script,
+    Objective,
+    Other,
+}
+
+impl EventCategory {
+    pub const ALL: [EventCategory; 6] = [
+        EventCategory::Collision,
+        EventCategory::Launch,
+        EventCategory::Docking,
+        EventCategory::Script,
+        EventCategory::Objective,
+        EventCategory::Other,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            EventCategory::Collision => "collisions",
+            EventCategory::Launch => "launches",
+            EventCategory::Docking => "docking",
+            EventCategory::Script => "scripts",
+            EventCategory::Objective => "objectives",
+            EventCategory::Other => "other",
+        }
+    }
+
+    fn color(self) -> egui::Color32 {
+        match self {
+            EventCategory::Collision => egui::Color32::LIGHT_RED,
+            EventCategory::Launch => egui::Color32::YELLOW,
+            EventCategory::Docking => egui::Color32::LIGHT_BLUE,
+            EventCategory::Script => egui::Color32::from_rgb(255, 140, 0),
+            EventCategory::Objective => egui::Color32::LIGHT_GREEN,
+            EventCategory::Other => egui::Color32::GRAY,
+        }
+    }
+
+    /// The category of the [`ScriptEvent`] called `name`.
+    fn of_script_event(name: &str) -> Self {
+        match name {
+            "impact" | "crashed" | "collision_warning" | "module_destroyed" => {
+                EventCategory::Collision
+            }
+            "launch" => EventCategory::Launch,
+            "docked" | "undocked" | "landed" | "took_off" | "serviced" => EventCategory::Docking,
+            "cpu_overrun" => EventCategory::Script,
+            "objective_complete" | "objective_failed" | "mission_won" | "mission_lost" => {
+                EventCategory::Objective
+            }
+            _ => EventCategory::Other,
+        }
+    }
+}
+
+/// One thing which happened, at mission elapsed time `time`.
+#[derive(Clone, Debug)]
+pub struct LogEntry {
+    pub time: f64,
+    pub category: EventCategory,
+    pub text: String,
+    /// The entity it happened to, which clicking the entry looks at.
+    pub entity: Option<Entity>,
+}
+
+/// Resource which keeps a running log of what's happened in the game, shown
+/// in the event log panel. It's opened from the tray.
+#[derive(Resource)]
+pub struct GameEventLog {
+    pub entries: VecDeque<LogEntry>,
+    pub open: bool,
+    /// Categories which are shown.
+    pub shown: HashSet<EventCategory>,
+}
+
+impl Default for GameEventLog {
+    fn default() -> Self {
+        Self {
+            entries: VecDeque::new(),
+            open: false,
+            shown: EventCategory::ALL.into_iter().collect(),
+        }
+    }
+}
+
+impl GameEventLog {
+    pub fn push(&mut self, entry: LogEntry) {
+        if self.entries.len() >= MAX_ENTRIES {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+}
+
+/// :SYSTEM: Logs the game's [`ScriptEvent`]s. The entity an event is about is
+/// taken to be its first argument.
+fn script_event_log_system(
+    mut log: ResMut<GameEventLog>,
+    mut events: EventReader<ScriptEvent>,
+    entities: Query<Entity>,
+    clock: Res<SimulationClock>,
+) {
+    for event in events.iter() {
+        // arguments are just numbers, so only trust ones which name a live entity
+        let entity = event
+            .args
+            .first()
+            .and_then(|a| a.as_entity().ok())
+            .filter(|e| entities.contains(*e));
+        let args: Vec<_> = event.args.iter().map(Value::to_string).collect();
+        log.push(LogEntry {
+            time: clock.elapsed,
+            category: EventCategory::of_script_event(&event.name),
+            text: format!("{} {}", event.name, args.join(" ")),
+            entity,
+        });
+    }
+}
+
+/// :SYSTEM: Logs missile and mine detonations.
+fn detonation_log_system(
+    mut log: ResMut<GameEventLog>,
+    mut detonations: EventReader<Detonation>,
+    clock: Res<SimulationClock>,
+) {
+    for blast in detonations.iter() {
+        log.push(LogEntry {
+            time: clock.elapsed,
+            category: EventCategory::Collision,
+            text: format!(
+                "detonation at ({:.0}, {:.0}), {:.0} damage",
+                blast.position.x, blast.position.y, blast.damage
+            ),
+            entity: None,
+        });
+    }
+}
+
+/// :SYSTEM: Logs ship programs which fault, once each time they do.
+fn script_fault_log_system(
+    mut log: ResMut<GameEventLog>,
+    programs: Query<(Entity, &ShipProgram, Option<&Name>)>,
+    mut faulted: Local<HashSet<Entity>>,
+    clock: Res<SimulationClock>,
+) {
+    for (entity, program, name) in programs.iter() {
+        let VmState::Faulted(error) = program.vm.state() else {
+            faulted.remove(&entity);
+            continue;
+        };
+        if faulted.insert(entity) {
+            let ship = name.map_or(format!("ship {}", entity.index()), |n| n.to_string());
+            log.push(LogEntry {
+                time: clock.elapsed,
+                category: EventCategory::Script,
+                text: format!("{}: {}", ship, error),
+                entity: Some(entity),
+            });
+        }
+    }
+}
+
+/// :SYSTEM: Shows the event log, newest at the bottom, with a switch for each
+/// category. Clicking an entry moves the view to the entity it's about.
+fn event_log_panel_system(
+    mut contexts: EguiContexts,
+    mut log: ResMut<GameEventLog>,
+    mut cameras: Query<&mut Transform, With<Camera2d>>,
+    transforms: Query<&GlobalTransform>,
+    cutscene: Res<Cutscene>,
+) {
+    if !log.open {
+        return;
+    }
+    let log = &mut *log;
+    let mut look_at = None;
+    let mut open = true;
+    egui::Window::new("Event log")
+        .open(&mut open)
+        .default_size([360.0, 300.0])
+        .show(contexts.ctx_mut(), |ui| {
+            ui.horizontal_wrapped(|ui| {
+                for category in EventCategory::ALL {
+                    let mut shown = log.shown.contains(&category);
+                    if ui.toggle_value(&mut shown, category.label()).changed() {
+                        match shown {
+                            true => log.shown.insert(category),
+                            false => log.shown.remove(&category),
+                        };
+                    }
+                }
+                if ui.small_button("Clear").clicked() {
+                    log.entries.clear();
+                }
+            });
+            ui.separator();
+
+            egui::ScrollArea::vertical()
+                .stick_to_bottom(true)
+                .show(ui, |ui| {
+                    for entry in log.entries.iter() {
+                        if !log.shown.contains(&entry.category) {
+                            continue;
+                        }
+                        let text = egui::RichText::new(format!(
+                            "{} {}",
+                            format_met(entry.time as f32),
+                            entry.text
+                        ))
+                        .color(entry.category.color())
+                        .monospace();
+                        match entry.entity {
+                            Some(entity) if transforms.contains(entity) => {
+                                if ui.link(text).clicked() {
+                                    look_at = Some(entity);
+                                }
+                            }
+                            _ => {
+                                ui.label(text);
+                            }
+                        }
+                    }
+                });
+        });
+    log.open = open;
+
+    // the cutscene has the camera
+    if cutscene.playing() {
+        return;
+    }
+    let (Some(entity), Ok(mut camera)) = (look_at, cameras.get_single_mut()) else {
+        return;
+    };
+    if let Ok(transform) = transforms.get(entity) {
+        let at = transform.translation();
+        camera.translation.x = at.x;
+        camera.translation.y = at.y;
+    }
+}
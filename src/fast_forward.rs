@@ -0,0 +1,348 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy::time::TimeUpdateStrategy;
+use bevy_egui::{egui, EguiContexts};
+
+use super::asteroid::Asteroid;
+use super::clock::SimulationClock;
+use super::orbit::propagate_kepler;
+use super::physics::{Kinimatics, PhysicsConfig, SimulationSet};
+use super::realtime::RealTime;
+use super::scheduler::{self, Alarm, Scheduler};
+use super::scripting::{ScriptEvent, ShipProgram};
+use super::ships::{Engine, LaunchMissile, Ship};
+use super::star_system::Dormant;
+
+pub struct FastForwardPlugin;
+
+impl Plugin for FastForwardPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<FastForward>()
+            .configure_set(SimulationSet.run_if(not_on_rails))
+            .add_system(rails_system.before(SimulationSet))
+            .add_system(tally_system.after(SimulationSet))
+            .add_system(pace_system.in_base_set(CoreSet::Last))
+            .add_system(fast_forward_panel_system);
+    }
+}
+
+/// Longest stretch of time the world is moved along its orbits in one frame.
+const RAILS_CHUNK: f64 = 600.0;
+
+/// Time step the simulation runs with, between stretches on rails, and while
+/// anything is burning.
+const SIMULATED_STEP: f64 = 1.0;
+
+/// How the next frame moves time forward.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+enum Mode {
+    /// Not skipping ahead.
+    #[default]
+    Off,
+    /// The simulation runs as normal, with a long time step.
+    Simulated,
+    /// Every body is moved along its Kepler orbit by this many seconds, and
+    /// nothing else runs, except the scheduler.
+    Rails(f64),
+}
+
+/// What happened while skipping ahead.
+#[derive(Clone, Debug, Default)]
+pub struct SkipSummary {
+    pub skipped: f64,
+    /// How much of `skipped` the world spent on rails.
+    pub on_rails: f64,
+    /// Fuel each ship burned, by name.
+    pub fuel_used: Vec<(String, f32)>,
+    /// Ships which were there when the skip started, but weren't at the end.
+    pub lost: Vec<String>,
+    pub launches: u32,
+    /// How many times each mission event was raised.
+    pub events: BTreeMap<String, u32>,
+}
+
+/// Resource which skips the simulation ahead by hours or days at a time. F8
+/// opens the panel.
+///
+/// The world is moved along its orbits analytically, a long stretch at a time,
+/// whenever nothing is burning. Between stretches, and while anything is
+/// burning, the simulation runs as normal with a long time step, so ships and
+/// their programs get to react. Scheduled events are resolved as their time
+/// comes. Once done, the simulation holds still until the summary is read.
+#[derive(Resource, Default)]
+pub struct FastForward {
+    pub open: bool,
+    mode: Mode,
+    /// Seconds left to skip.
+    remaining: f64,
+    /// Fuel in each ship when the skip started.
+    fuel: Vec<(Entity, String, f32)>,
+    summary: SkipSummary,
+    /// Set once the skip is done, until the player has read it.
+    pub finished: Option<SkipSummary>,
+}
+
+impl FastForward {
+    pub fn skipping(&self) -> bool {
+        self.mode != Mode::Off
+    }
+}
+
+fn not_on_rails(fast_forward: Res<FastForward>) -> bool {
+    !matches!(fast_forward.mode, Mode::Rails(_)) && fast_forward.finished.is_none()
+}
+
+/// :SYSTEM: Moves every body along its orbit around whichever heavier body
+/// pulls on it hardest, while skipping ahead on rails. Heavier bodies go first,
+/// so everything is carried along with what it orbits. Asteroids go last.
+#[allow(clippy::type_complexity, clippy::too_many_arguments)]
+fn rails_system(
+    mut fast_forward: ResMut<FastForward>,
+    mut bodies: Query<(Entity, &mut Kinimatics, &mut Transform), Without<Dormant>>,
+    mut asteroids: Query<(&mut Asteroid, &mut Transform), (Without<Kinimatics>, Without<Dormant>)>,
+    mut clock: ResMut<SimulationClock>,
+    mut scheduler: ResMut<Scheduler>,
+    mut programs: Query<&mut ShipProgram>,
+    mut script_events: EventWriter<ScriptEvent>,
+    mut alarms: EventWriter<Alarm>,
+    physics: Res<PhysicsConfig>,
+) {
+    let Mode::Rails(dt) = fast_forward.mode else {
+        return;
+    };
+
+    let mut before: Vec<(Entity, f32, Vec3, Vec3)> = bodies
+        .iter()
+        .map(|(e, k, t)| (e, k.mass, t.translation, k.velocity))
+        .collect();
+    before.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    let mut after: Vec<(Vec3, Vec3)> = Vec::with_capacity(before.len());
+    for (i, &(_, mass, position, velocity)) in before.iter().enumerate() {
+        let parent = before[..i]
+            .iter()
+            .enumerate()
+            .filter(|(_, p)| p.1 > mass)
+            .max_by(|(_, a), (_, b)| {
+                let pull = |p: &(Entity, f32, Vec3, Vec3)| p.1 / p.2.distance_squared(position);
+                pull(a).total_cmp(&pull(b))
+            })
+            .map(|(j, p)| (j, *p));
+
+        after.push(match parent {
+            Some((j, (_, parent_mass, parent_position, parent_velocity))) => {
+                let (r, v) = propagate_kepler(
+                    position - parent_position,
+                    velocity - parent_velocity,
+                    physics.mu(parent_mass),
+                    dt as f32,
+                );
+                (after[j].0 + r, after[j].1 + v)
+            }
+            None => (position + velocity * dt as f32, velocity),
+        });
+    }
+
+    for (mut asteroid, mut transform) in asteroids.iter_mut() {
+        let position = transform.translation;
+        let parent = before.iter().enumerate().max_by(|(_, a), (_, b)| {
+            let pull = |p: &(Entity, f32, Vec3, Vec3)| p.1 / p.2.distance_squared(position);
+            pull(a).total_cmp(&pull(b))
+        });
+        let (r, v) = match parent {
+            Some((j, &(_, parent_mass, parent_position, parent_velocity))) => {
+                let (r, v) = propagate_kepler(
+                    position - parent_position,
+                    asteroid.velocity - parent_velocity,
+                    physics.mu(parent_mass),
+                    dt as f32,
+                );
+                (after[j].0 + r, after[j].1 + v)
+            }
+            None => (position + asteroid.velocity * dt as f32, asteroid.velocity),
+        };
+        transform.translation = r;
+        asteroid.velocity = v;
+    }
+
+    for ((entity, ..), (position, velocity)) in before.iter().zip(after) {
+        if let Ok((_, mut kin, mut transform)) = bodies.get_mut(*entity) {
+            transform.translation = position;
+            kin.velocity = velocity;
+        }
+    }
+
+    clock.elapsed += dt;
+    let due = scheduler.take_due(clock.elapsed);
+    scheduler::dispatch(
+        &mut scheduler,
+        due,
+        &mut programs,
+        &mut script_events,
+        &mut alarms,
+    );
+    fast_forward.summary.on_rails += dt;
+}
+
+/// :SYSTEM: Keeps count of what happens while skipping ahead.
+fn tally_system(
+    mut fast_forward: ResMut<FastForward>,
+    mut script_events: EventReader<ScriptEvent>,
+    mut launches: EventReader<LaunchMissile>,
+) {
+    if !fast_forward.skipping() {
+        script_events.clear();
+        launches.clear();
+        return;
+    }
+    let summary = &mut fast_forward.summary;
+    for event in script_events.iter() {
+        *summary.events.entry(event.name.clone()).or_default() += 1;
+    }
+    summary.launches += launches.iter().count() as u32;
+}
+
+/// :SYSTEM: Decides how the next frame moves time forward while skipping
+/// ahead, and wraps up once the time is up. Runs at the very end of the frame.
+pub fn pace_system(
+    mut fast_forward: ResMut<FastForward>,
+    mut strategy: ResMut<TimeUpdateStrategy>,
+    engines: Query<&Engine>,
+) {
+    if !fast_forward.skipping() {
+        return;
+    }
+
+    if fast_forward.remaining <= 0.0 {
+        let mut summary = std::mem::take(&mut fast_forward.summary);
+        for (entity, name, fuel) in std::mem::take(&mut fast_forward.fuel) {
+            match engines.get(entity) {
+                Ok(engine) => summary.fuel_used.push((name, fuel - engine.fuel)),
+                Err(_) => summary.lost.push(name),
+            }
+        }
+        fast_forward.finished = Some(summary);
+        fast_forward.mode = Mode::Off;
+        *strategy = TimeUpdateStrategy::Automatic;
+        return;
+    }
+
+    let burning = engines
+        .iter()
+        .any(|e| e.fuel > 0.0 && e.throttle_fraction() > 0.0);
+    // everyone gets a look in after each stretch on rails
+    let mode = if burning || matches!(fast_forward.mode, Mode::Rails(_)) {
+        Mode::Simulated
+    } else {
+        Mode::Rails(fast_forward.remaining.min(RAILS_CHUNK))
+    };
+
+    let step = match mode {
+        Mode::Rails(dt) => {
+            *strategy = TimeUpdateStrategy::ManualDuration(Duration::ZERO);
+            dt
+        }
+        _ => {
+            let step = fast_forward.remaining.min(SIMULATED_STEP);
+            *strategy = TimeUpdateStrategy::ManualDuration(Duration::from_secs_f64(step));
+            step
+        }
+    };
+    fast_forward.remaining -= step;
+    fast_forward.summary.skipped += step;
+    fast_forward.mode = mode;
+}
+
+/// :SYSTEM: Shows the skip ahead panel, its progress, and what happened once
+/// it's done.
+fn fast_forward_panel_system(
+    mut contexts: EguiContexts,
+    mut fast_forward: ResMut<FastForward>,
+    ships: Query<(Entity, &Engine, Option<&Name>), With<Ship>>,
+    realtime: Res<RealTime>,
+    input: Res<Input<KeyCode>>,
+) {
+    if input.just_pressed(KeyCode::F8) {
+        fast_forward.open = !fast_forward.open;
+    }
+    let ctx = contexts.ctx_mut();
+
+    if let Some(summary) = fast_forward.finished.clone() {
+        egui::Window::new("While you were away").show(ctx, |ui| {
+            ui.label(format!(
+                "{:.1} hours passed, {:.0}% of it on rails.",
+                summary.skipped / 3600.0,
+                100.0 * summary.on_rails / summary.skipped.max(1.0)
+            ));
+            egui::Grid::new("fuel_used").show(ui, |ui| {
+                for (name, fuel) in summary.fuel_used.iter().filter(|(_, f)| *f > 0.0) {
+                    ui.label(name);
+                    ui.label(format!("burned {:.0} fuel", fuel));
+                    ui.end_row();
+                }
+            });
+            for name in &summary.lost {
+                ui.colored_label(egui::Color32::LIGHT_RED, format!("{} was lost", name));
+            }
+            if summary.launches > 0 {
+                ui.label(format!("{} missiles launched", summary.launches));
+            }
+            for (event, count) in &summary.events {
+                ui.label(format!("{} x{}", event, count));
+            }
+            if ui.button("Continue").clicked() {
+                fast_forward.finished = None;
+            }
+        });
+        return;
+    }
+
+    if !fast_forward.open {
+        return;
+    }
+    let mut open = true;
+    egui::Window::new("Skip ahead")
+        .open(&mut open)
+        .show(ctx, |ui| {
+            if fast_forward.skipping() {
+                ui.label(format!(
+                    "Skipping, {:.1} hours to go",
+                    fast_forward.remaining / 3600.0
+                ));
+                if ui.button("Stop").clicked() {
+                    fast_forward.remaining = 0.0;
+                }
+                return;
+            }
+            if realtime.enabled {
+                ui.label("Can't skip ahead in real-time mode.");
+                return;
+            }
+
+            let mut skip = None;
+            ui.horizontal(|ui| {
+                if ui.button("1 hour").clicked() {
+                    skip = Some(3600.0);
+                }
+                if ui.button("1 day").clicked() {
+                    skip = Some(86_400.0);
+                }
+            });
+            if let Some(seconds) = skip {
+                fast_forward.remaining = seconds;
+                fast_forward.mode = Mode::Simulated;
+                fast_forward.summary = SkipSummary::default();
+                fast_forward.fuel = ships
+                    .iter()
+                    .map(|(entity, engine, name)| {
+                        let name =
+                            name.map_or(format!("ship {}", entity.index()), |n| n.to_string());
+                        (entity, name, engine.fuel)
+                    })
+                    .collect();
+            }
+        });
+    fast_forward.open = open;
+}
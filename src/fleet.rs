@@ -0,0 +1,268 @@
+use std::f32::consts::TAU;
+
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+use super::autopilot::{Autopilot, Goal};
+use super::physics::{Kinimatics, SimulationSet};
+use super::ships::{Controlled, Ship};
+
+pub struct FleetPlugin;
+
+impl Plugin for FleetPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<FleetPanel>()
+            .register_type::<Fleet>()
+            .register_type::<FormationSlot>()
+            .add_system(
+                formation_system
+                    .before(super::autopilot::autopilot_system)
+                    .in_set(SimulationSet),
+            )
+            .add_system(fleet_panel_system);
+    }
+}
+
+/// The shape a fleet flies in. Offsets are measured from the flagship, along
+/// its nose.
+#[derive(Reflect, Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Formation {
+    /// Side by side with the flagship.
+    #[default]
+    Line,
+    /// Single file behind the flagship.
+    Column,
+    /// A V, with the flagship at its point.
+    Wedge,
+    /// Evenly spaced around the flagship.
+    Ring,
+}
+
+impl Formation {
+    pub const ALL: [Formation; 4] = [
+        Formation::Line,
+        Formation::Column,
+        Formation::Wedge,
+        Formation::Ring,
+    ];
+
+    /// Where the `index`th of `count` ships flies, relative to the flagship
+    /// pointing along +Y.
+    pub fn offset(self, index: usize, count: usize, spacing: f32) -> Vec3 {
+        // ships alternate sides, moving further out every other one
+        let rank = (index / 2 + 1) as f32;
+        let side = if index.is_multiple_of(2) { 1.0 } else { -1.0 };
+        match self {
+            Formation::Line => Vec3::new(side * rank * spacing, 0.0, 0.0),
+            Formation::Column => Vec3::new(0.0, -((index + 1) as f32) * spacing, 0.0),
+            Formation::Wedge => Vec3::new(side * rank * spacing, -rank * spacing, 0.0),
+            Formation::Ring => {
+                let radius = (spacing * count as f32 / TAU).max(spacing);
+                let angle = index as f32 / count as f32 * TAU;
+                Vec3::new(angle.sin(), angle.cos(), 0.0) * radius
+            }
+        }
+    }
+}
+
+/// :COMPONENT: Makes a ship the flagship of a fleet. The rest of the fleet are
+/// the ships whose [`FormationSlot`] points at it.
+#[derive(Reflect, Component, Clone, Copy, Debug)]
+#[reflect(Component)]
+pub struct Fleet {
+    pub formation: Formation,
+    /// Distance between neighbouring ships in the formation.
+    pub spacing: f32,
+}
+
+impl Default for Fleet {
+    fn default() -> Self {
+        Self {
+            formation: Formation::default(),
+            spacing: 40.0,
+        }
+    }
+}
+
+/// :COMPONENT: Puts a ship in formation with a fleet's flagship. The ship
+/// needs an [`Autopilot`] to keep its place.
+#[derive(Reflect, Component, Clone, Copy, Debug)]
+#[reflect(Component)]
+pub struct FormationSlot {
+    pub flagship: Entity,
+}
+
+impl FromWorld for FormationSlot {
+    fn from_world(_world: &mut World) -> Self {
+        Self {
+            flagship: Entity::PLACEHOLDER,
+        }
+    }
+}
+
+/// :SYSTEM: Works out where each ship in a fleet should be, and sets its
+/// autopilot to get it there. Ships whose flagship is gone fall out of
+/// formation.
+fn formation_system(
+    mut commands: Commands,
+    fleets: Query<(&Fleet, &Transform, &Kinimatics)>,
+    mut members: Query<(Entity, &FormationSlot, &mut Autopilot)>,
+) {
+    // slots are handed out in a stable order, so ships don't swap places
+    let mut order: Vec<(Entity, Entity)> = members
+        .iter()
+        .map(|(entity, slot, _)| (slot.flagship, entity))
+        .collect();
+    order.sort();
+
+    for (flagship, entity) in order.iter().copied() {
+        let Ok((_, slot, mut autopilot)) = members.get_mut(entity) else {
+            continue;
+        };
+        let Ok((fleet, transform, kin)) = fleets.get(slot.flagship) else {
+            autopilot.goal = None;
+            commands.entity(entity).remove::<FormationSlot>();
+            continue;
+        };
+
+        let mut mates = order.iter().filter(|(f, _)| *f == flagship);
+        let count = mates.clone().count();
+        let index = mates.position(|(_, e)| *e == entity).unwrap_or(0);
+
+        let offset = fleet.formation.offset(index, count, fleet.spacing);
+        autopilot.goal = Some(Goal {
+            position: transform.translation + transform.rotation.mul_vec3(offset),
+            velocity: kin.velocity,
+        });
+    }
+}
+
+/// Resource which holds the state of the fleet panel. F3 opens and closes it.
+#[derive(Resource, Default)]
+pub struct FleetPanel {
+    pub open: bool,
+    selected: Vec<Entity>,
+}
+
+/// :SYSTEM: Shows the fleet panel, for putting ships into fleets and picking
+/// their formations.
+///
+/// Forming a fleet from the selected ships makes the player's ship the flagship
+/// if it's among them, or otherwise the first one selected.
+#[allow(clippy::type_complexity)]
+fn fleet_panel_system(
+    mut commands: Commands,
+    mut contexts: EguiContexts,
+    mut panel: ResMut<FleetPanel>,
+    ships: Query<
+        (
+            Entity,
+            Option<&Name>,
+            Option<&FormationSlot>,
+            Option<&Controlled>,
+        ),
+        With<Ship>,
+    >,
+    mut fleets: Query<(Entity, &mut Fleet, Option<&Name>)>,
+    input: Res<Input<KeyCode>>,
+) {
+    if input.just_pressed(KeyCode::F3) {
+        panel.open = !panel.open;
+    }
+    if !panel.open {
+        return;
+    }
+    let panel = &mut *panel;
+    panel.selected.retain(|e| ships.contains(*e));
+
+    let label = |entity: Entity, name: Option<&Name>| match name {
+        Some(name) => format!("{} ({})", name, entity.index()),
+        None => format!("ship {}", entity.index()),
+    };
+
+    let mut open = true;
+    egui::Window::new("Fleets")
+        .open(&mut open)
+        .show(contexts.ctx_mut(), |ui| {
+            egui::ScrollArea::vertical()
+                .max_height(200.0)
+                .show(ui, |ui| {
+                    for (entity, name, slot, _) in ships.iter() {
+                        let mut selected = panel.selected.contains(&entity);
+                        let mut text = label(entity, name);
+                        if fleets.contains(entity) {
+                            text += ", flagship";
+                        } else if let Some(slot) = slot {
+                            text += &format!(", with {}", slot.flagship.index());
+                        }
+                        if ui.checkbox(&mut selected, text).changed() {
+                            if selected {
+                                panel.selected.push(entity);
+                            } else {
+                                panel.selected.retain(|e| *e != entity);
+                            }
+                        }
+                    }
+                });
+
+            ui.horizontal(|ui| {
+                let form = ui
+                    .add_enabled(panel.selected.len() > 1, egui::Button::new("Form fleet"))
+                    .clicked();
+                if form {
+                    let flagship = panel
+                        .selected
+                        .iter()
+                        .copied()
+                        .find(|e| ships.get(*e).is_ok_and(|(.., c)| c.is_some()))
+                        .unwrap_or(panel.selected[0]);
+                    commands
+                        .entity(flagship)
+                        .remove::<FormationSlot>()
+                        .insert(Fleet::default());
+                    for member in panel.selected.drain(..).filter(|e| *e != flagship) {
+                        commands
+                            .entity(member)
+                            .remove::<Fleet>()
+                            .insert((FormationSlot { flagship }, Autopilot::default()));
+                    }
+                }
+
+                if ui.button("Leave fleet").clicked() {
+                    for ship in panel.selected.drain(..) {
+                        commands
+                            .entity(ship)
+                            .remove::<FormationSlot>()
+                            .insert(Autopilot::default());
+                    }
+                }
+            });
+
+            ui.separator();
+            for (flagship, mut fleet, name) in fleets.iter_mut() {
+                ui.horizontal(|ui| {
+                    ui.label(label(flagship, name));
+                    egui::ComboBox::from_id_source(flagship)
+                        .selected_text(format!("{:?}", fleet.formation))
+                        .show_ui(ui, |ui| {
+                            for formation in Formation::ALL {
+                                ui.selectable_value(
+                                    &mut fleet.formation,
+                                    formation,
+                                    format!("{:?}", formation),
+                                );
+                            }
+                        });
+                    ui.add(
+                        egui::DragValue::new(&mut fleet.spacing)
+                            .clamp_range(10.0..=200.0)
+                            .prefix("spacing "),
+                    );
+                    if ui.button("Disband").clicked() {
+                        commands.entity(flagship).remove::<Fleet>();
+                    }
+                });
+            }
+        });
+    panel.open = open;
+}
@@ -0,0 +1,159 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+use super::physics::{Kinimatics, PhysicsConfig, SimulationSet};
+use super::scripting::{ScriptEvent, ShipProgram, Value};
+use super::ships::{Controlled, Engine};
+
+pub struct FlybyPlugin;
+
+impl Plugin for FlybyPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<DeltaVLedger>()
+            .add_system(
+                ledger_system
+                    .after(super::physics::kinimatics_system)
+                    .in_set(SimulationSet),
+            )
+            .add_system(ledger_panel_system);
+    }
+}
+
+/// Coasting segments which change the ship's speed by less than this aren't
+/// counted as flybys. Keeps numerical noise out of the ledger.
+const FLYBY_THRESHOLD: f32 = 0.5;
+
+/// :COMPONENT: Keeps track of where a ship's changes in velocity came from:
+/// its own engine, or gravity.
+///
+/// Whenever the engine is off, the ship is coasting, and any change in its
+/// orbital energy around `reference` can only have come from other bodies
+/// pulling on it, which is to say flybys. At the end of each coasting segment,
+/// the change is converted into the speed it is worth at that point, and
+/// added to `flyby`. A `flyby` event is raised on the ship's program, and as a
+/// [`ScriptEvent`], with the gain and the new total.
+#[derive(Reflect, Component, Clone, Copy, Debug)]
+#[reflect(Component)]
+pub struct DeltaVLedger {
+    /// The body orbital energy is measured around, usually the system's star.
+    pub reference: Entity,
+    /// Change in velocity from the engine.
+    pub thrust: f32,
+    /// Change in velocity from gravity, of every kind.
+    pub gravity: f32,
+    /// Speed gained from flybys, net of any lost to them.
+    pub flyby: f32,
+    /// Number of coasting segments which counted as flybys.
+    pub flybys: u32,
+    coasting: bool,
+    /// Orbital energy when the current coasting segment started.
+    coast_energy: f32,
+}
+
+impl DeltaVLedger {
+    pub fn new(reference: Entity) -> Self {
+        Self {
+            reference,
+            thrust: 0.0,
+            gravity: 0.0,
+            flyby: 0.0,
+            flybys: 0,
+            coasting: false,
+            coast_energy: 0.0,
+        }
+    }
+}
+
+impl FromWorld for DeltaVLedger {
+    fn from_world(_world: &mut World) -> Self {
+        Self::new(Entity::PLACEHOLDER)
+    }
+}
+
+/// :SYSTEM: Splits each tracked ship's change in velocity into thrust and
+/// gravity, and scores its flybys.
+#[allow(clippy::type_complexity)]
+fn ledger_system(
+    mut ships: Query<(
+        Entity,
+        &mut DeltaVLedger,
+        &Kinimatics,
+        &Transform,
+        Option<&Engine>,
+        Option<&mut ShipProgram>,
+    )>,
+    bodies: Query<(&Kinimatics, &Transform)>,
+    mut script_events: EventWriter<ScriptEvent>,
+    physics: Res<PhysicsConfig>,
+    time: Res<Time>,
+) {
+    let dt = time.delta_seconds();
+
+    for (entity, mut ledger, kin, transform, engine, program) in ships.iter_mut() {
+        let Ok((reference, reference_transform)) = bodies.get(ledger.reference) else {
+            continue;
+        };
+
+        let thrust = engine.map_or(Vec3::ZERO, |e| {
+            e.thrust_vector(transform.rotation) / kin.mass
+        });
+        ledger.thrust += thrust.length() * dt;
+        ledger.gravity += (kin.acceleration - thrust).length() * dt;
+
+        let speed = (kin.velocity - reference.velocity).length();
+        let distance = transform
+            .translation
+            .distance(reference_transform.translation);
+        let energy = speed * speed / 2.0 - physics.mu(reference.mass) / distance;
+
+        let coasting = thrust == Vec3::ZERO;
+        if coasting && !ledger.coasting {
+            ledger.coast_energy = energy;
+        } else if !coasting && ledger.coasting {
+            // what the segment's change in energy is worth in speed, right here
+            let before = (speed * speed - 2.0 * (energy - ledger.coast_energy)).max(0.0);
+            let gain = speed - before.sqrt();
+            if gain.abs() >= FLYBY_THRESHOLD {
+                ledger.flyby += gain;
+                ledger.flybys += 1;
+
+                let args = vec![Value::Num(gain as f64), Value::Num(ledger.flyby as f64)];
+                if let Some(mut program) = program {
+                    program.vm.raise("flyby", args.clone());
+                }
+                script_events.send(ScriptEvent {
+                    name: "flyby".to_string(),
+                    args: [vec![Value::from_entity(entity)], args].concat(),
+                });
+            }
+        }
+        ledger.coasting = coasting;
+    }
+}
+
+/// :SYSTEM: Shows the player's delta-v ledger, if they have one.
+fn ledger_panel_system(
+    mut contexts: EguiContexts,
+    ledgers: Query<&DeltaVLedger, With<Controlled>>,
+) {
+    let Ok(ledger) = ledgers.get_single() else {
+        return;
+    };
+
+    egui::Window::new("Delta-v").show(contexts.ctx_mut(), |ui| {
+        egui::Grid::new("ledger").show(ui, |ui| {
+            ui.label("engine");
+            ui.label(format!("{:.1}", ledger.thrust));
+            ui.end_row();
+            ui.label("gravity");
+            ui.label(format!("{:.1}", ledger.gravity));
+            ui.end_row();
+            ui.label(format!("flybys ({})", ledger.flybys));
+            ui.strong(format!("{:+.1}", ledger.flyby));
+            ui.end_row();
+        });
+        if ledger.coasting {
+            ui.label("coasting");
+        }
+    });
+}
@@ -0,0 +1,132 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+use super::cutscene::Cutscene;
+use super::input_map::{Action, InputMap};
+use super::physics::SimulationSet;
+use super::ships::{Controlled, Engine, PlayerShip, Throttle};
+use super::star_system::{InSystem, StarSystems};
+
+pub struct FocusPlugin;
+
+impl Plugin for FocusPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<Focus>()
+            .add_system(focus_hotkey_system.before(focus_system))
+            .add_system(focus_system.before(SimulationSet))
+            .add_system(player_ships_panel_system.before(focus_system));
+    }
+}
+
+/// :EVENT: Asks for the player to take over `ship`, which has to be one of
+/// their [`PlayerShip`]s.
+#[derive(Clone, Copy, Debug)]
+pub struct Focus {
+    pub ship: Entity,
+}
+
+/// :SYSTEM: Moves to the next of the player's ships with the key bound to
+/// [`Action::NextShip`], in the order they were spawned.
+fn focus_hotkey_system(
+    ships: Query<(Entity, Option<&Controlled>), With<PlayerShip>>,
+    input: Res<Input<KeyCode>>,
+    map: Res<InputMap>,
+    mut focus: EventWriter<Focus>,
+) {
+    if !map.just_pressed(Action::NextShip, &input) {
+        return;
+    }
+    let mut ships: Vec<_> = ships.iter().collect();
+    if ships.len() < 2 {
+        return;
+    }
+    ships.sort_by_key(|(e, _)| *e);
+    let current = ships.iter().position(|(_, c)| c.is_some()).unwrap_or(0);
+    focus.send(Focus {
+        ship: ships[(current + 1) % ships.len()].0,
+    });
+}
+
+/// :SYSTEM: Hands the controls over to another of the player's ships, and
+/// points the camera at it. The ship left behind cuts its engine, rather than
+/// burning on with whatever keys were last held.
+#[allow(clippy::type_complexity)]
+fn focus_system(
+    mut commands: Commands,
+    mut requests: EventReader<Focus>,
+    mut ships: Query<
+        (
+            Entity,
+            &GlobalTransform,
+            Option<&mut Engine>,
+            Option<&InSystem>,
+            Option<&Controlled>,
+        ),
+        With<PlayerShip>,
+    >,
+    mut cameras: Query<&mut Transform, With<Camera2d>>,
+    mut systems: ResMut<StarSystems>,
+    cutscene: Res<Cutscene>,
+) {
+    let Some(request) = requests.iter().last() else {
+        return;
+    };
+    let Ok((ship, transform, _, system, controlled)) = ships.get(request.ship) else {
+        return;
+    };
+    if controlled.is_some() {
+        return;
+    }
+    let at = transform.translation();
+    if let Some(system) = system {
+        systems.active = system.0;
+    }
+    commands.entity(ship).insert(Controlled);
+
+    for (other, _, engine, _, controlled) in ships.iter_mut() {
+        if other == ship || controlled.is_none() {
+            continue;
+        }
+        commands.entity(other).remove::<Controlled>();
+        if let Some(mut engine) = engine {
+            if let Throttle::Fixed(_) = engine.throttle {
+                engine.throttle = Throttle::Fixed(false);
+            }
+            engine.gimbal = 0.0;
+        }
+    }
+
+    if !cutscene.playing() {
+        if let Ok(mut camera) = cameras.get_single_mut() {
+            camera.translation.x = at.x;
+            camera.translation.y = at.y;
+        }
+    }
+}
+
+/// :SYSTEM: Lists the player's ships, once they have more than one, for them to
+/// pick which to fly.
+#[allow(clippy::type_complexity)]
+fn player_ships_panel_system(
+    mut contexts: EguiContexts,
+    ships: Query<(Entity, Option<&Name>, Option<&Controlled>), With<PlayerShip>>,
+    mut focus: EventWriter<Focus>,
+) {
+    if ships.iter().len() < 2 {
+        return;
+    }
+    let mut ships: Vec<_> = ships.iter().collect();
+    ships.sort_by_key(|(e, ..)| *e);
+
+    egui::Window::new("Your ships")
+        .anchor(egui::Align2::LEFT_TOP, [8.0, 8.0])
+        .resizable(false)
+        .show(contexts.ctx_mut(), |ui| {
+            for (ship, name, controlled) in ships {
+                let text = name.map_or(format!("ship {}", ship.index()), |n| n.to_string());
+                if ui.selectable_label(controlled.is_some(), text).clicked() {
+                    focus.send(Focus { ship });
+                }
+            }
+        });
+}
@@ -0,0 +1,222 @@
+use std::time::Duration;
+
+use bevy::app::{AppExit, PluginGroupBuilder, ScheduleRunnerPlugin, ScheduleRunnerSettings};
+use bevy::prelude::*;
+use bevy::render::{settings::WgpuSettings, RenderPlugin};
+use bevy::time::TimeUpdateStrategy;
+use bevy::window::ExitCondition;
+use bevy::winit::WinitPlugin;
+use serde::Serialize;
+
+use super::clock::SimulationClock;
+use super::director::MissionDirector;
+use super::level::AstroObject;
+use super::missions::Objectives;
+use super::physics::Kinimatics;
+use super::realtime::TICK;
+use super::scripting::{ShipProgram, VmState};
+use super::ships::{Engine, Hull, Ship};
+
+/// Ticks run when `--ticks` isn't given.
+const DEFAULT_TICKS: u64 = 3600;
+
+/// Resource which says how long a headless run lasts, and where its report
+/// goes. Start the game with `--headless` to run without a window, as fast as
+/// the simulation will go, for `--ticks=N` ticks of [`TICK`] each. The report
+/// is written to `--report=PATH`, or printed if that isn't given.
+///
+/// The game exits with status 1 if any objective failed, or any program
+/// faulted, so batches of ship programs can be checked by a script.
+#[derive(Resource, Clone, Debug)]
+pub struct Headless {
+    pub ticks: u64,
+    pub report: Option<String>,
+    pub ticks_run: u64,
+}
+
+impl Headless {
+    /// The headless settings given on the command line, if the game was
+    /// started with `--headless`.
+    pub fn from_args() -> Option<Self> {
+        let args: Vec<String> = std::env::args().collect();
+        if !args.iter().any(|a| a == "--headless") {
+            return None;
+        }
+        let value = |flag: &str| args.iter().find_map(|a| a.strip_prefix(flag));
+        Some(Self {
+            ticks: value("--ticks=")
+                .and_then(|n| n.parse().ok())
+                .unwrap_or(DEFAULT_TICKS),
+            report: value("--report=").map(|p| p.to_string()),
+            ticks_run: 0,
+        })
+    }
+}
+
+/// Turns the default plugins headless: the primary window is never opened,
+/// and nothing is drawn, but the UI still runs against it so every plugin
+/// works as usual.
+pub fn headless_plugins(plugins: PluginGroupBuilder) -> PluginGroupBuilder {
+    plugins
+        .disable::<WinitPlugin>()
+        .set(RenderPlugin {
+            wgpu_settings: WgpuSettings {
+                backends: None,
+                ..Default::default()
+            },
+        })
+        .set(WindowPlugin {
+            exit_condition: ExitCondition::DontExit,
+            close_when_requested: false,
+            ..Default::default()
+        })
+}
+
+pub struct HeadlessPlugin(pub Headless);
+
+impl Plugin for HeadlessPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(self.0.clone())
+            .insert_resource(ScheduleRunnerSettings::run_loop(Duration::ZERO))
+            .insert_resource(TimeUpdateStrategy::ManualDuration(TICK))
+            .add_plugin(ScheduleRunnerPlugin)
+            .add_system(headless_system.in_base_set(CoreSet::Last));
+    }
+}
+
+#[derive(Serialize)]
+struct ObjectiveReport {
+    key: String,
+    text: String,
+    status: String,
+}
+
+#[derive(Serialize)]
+struct ShipReport {
+    name: String,
+    position: (f32, f32),
+    velocity: (f32, f32),
+    fuel: Option<f32>,
+    hull: Option<f32>,
+    program: Option<String>,
+}
+
+#[derive(Serialize)]
+struct BodyReport {
+    name: String,
+    position: (f32, f32),
+    velocity: (f32, f32),
+}
+
+/// Where everything ended up, and how the scripts did, at the end of a
+/// headless run.
+#[derive(Serialize)]
+struct Report {
+    ticks: u64,
+    met: f64,
+    director: Option<String>,
+    objectives: Vec<ObjectiveReport>,
+    ships: Vec<ShipReport>,
+    bodies: Vec<BodyReport>,
+}
+
+fn describe(state: &VmState) -> String {
+    match state {
+        VmState::Faulted(e) => format!("faulted: {}", e),
+        state => format!("{:?}", state).to_lowercase(),
+    }
+}
+
+/// :SYSTEM: Counts off the ticks of a headless run, and once they're up writes
+/// the report and exits.
+#[allow(clippy::type_complexity)]
+fn headless_system(
+    mut headless: ResMut<Headless>,
+    mut exit: EventWriter<AppExit>,
+    clock: Res<SimulationClock>,
+    objectives: Res<Objectives>,
+    director: Option<Res<MissionDirector>>,
+    ships: Query<
+        (
+            Entity,
+            Option<&Name>,
+            &Transform,
+            &Kinimatics,
+            Option<&Engine>,
+            Option<&Hull>,
+            Option<&ShipProgram>,
+        ),
+        With<Ship>,
+    >,
+    bodies: Query<(Entity, Option<&Name>, &Transform, &Kinimatics), With<AstroObject>>,
+) {
+    headless.ticks_run += 1;
+    if headless.ticks_run < headless.ticks {
+        return;
+    }
+
+    let name = |entity: Entity, name: Option<&Name>| {
+        name.map_or(format!("{}", entity.index()), |n| n.to_string())
+    };
+    let report = Report {
+        ticks: headless.ticks_run,
+        met: clock.elapsed,
+        director: director.map(|d| describe(d.vm.state())),
+        objectives: objectives
+            .objectives
+            .iter()
+            .map(|o| ObjectiveReport {
+                key: o.key.clone(),
+                text: o.text.clone(),
+                status: format!("{:?}", o.status).to_lowercase(),
+            })
+            .collect(),
+        ships: ships
+            .iter()
+            .map(|(e, n, transform, kin, engine, hull, program)| ShipReport {
+                name: name(e, n),
+                position: (transform.translation.x, transform.translation.y),
+                velocity: (kin.velocity.x, kin.velocity.y),
+                fuel: engine.map(|e| e.fuel),
+                hull: hull.map(|h| h.integrity),
+                program: program.map(|p| describe(p.vm.state())),
+            })
+            .collect(),
+        bodies: bodies
+            .iter()
+            .map(|(e, n, transform, kin)| BodyReport {
+                name: name(e, n),
+                position: (transform.translation.x, transform.translation.y),
+                velocity: (kin.velocity.x, kin.velocity.y),
+            })
+            .collect(),
+    };
+    let failed = report.objectives.iter().any(|o| o.status == "failed")
+        || report
+            .director
+            .iter()
+            .chain(report.ships.iter().filter_map(|s| s.program.as_ref()))
+            .any(|s| s.starts_with("faulted"));
+
+    let text = match ron::ser::to_string_pretty(&report, ron::ser::PrettyConfig::default()) {
+        Ok(text) => text,
+        Err(e) => {
+            error!("headless: couldn't write the report: {}", e);
+            std::process::exit(1);
+        }
+    };
+    match &headless.report {
+        Some(path) => {
+            if let Err(e) = std::fs::write(path, text) {
+                error!("headless: couldn't write the report to {}: {}", path, e);
+                std::process::exit(1);
+            }
+        }
+        None => println!("{}", text),
+    }
+
+    if failed {
+        std::process::exit(1);
+    }
+    exit.send(AppExit);
+}
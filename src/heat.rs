@@ -0,0 +1,170 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+use super::modules::{Module, Thruster};
+use super::physics::SimulationSet;
+use super::power::PowerConsumer;
+use super::sandbox::Sandbox;
+use super::ships::{Controlled, Engine, PlayerShip};
+
+pub struct HeatPlugin;
+
+impl Plugin for HeatPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(
+            heat_system
+                .before(super::modules::loadout_system)
+                .in_set(SimulationSet),
+        )
+        .add_system(heat_gauge_system);
+    }
+}
+
+/// Temperature of deep space, in kelvin. Radiators can't cool a ship below this.
+pub const AMBIENT_TEMPERATURE: f32 = 3.0;
+
+/// :COMPONENT: How hot a ship is running.
+///
+/// Above `overheat`, the ship's engines are throttled back and its weapons
+/// won't fire, more so the closer it gets to `critical`. Above `critical`, the
+/// modules producing the heat start to take damage.
+#[derive(Reflect, Component, Clone, Copy)]
+#[reflect(Component)]
+pub struct Thermal {
+    /// In kelvin.
+    pub temperature: f32,
+    /// Energy needed to raise the temperature by one kelvin, in joules.
+    pub heat_capacity: f32,
+    pub overheat: f32,
+    pub critical: f32,
+}
+
+impl Default for Thermal {
+    fn default() -> Self {
+        Self {
+            temperature: 300.0,
+            heat_capacity: 20.0,
+            overheat: 600.0,
+            critical: 900.0,
+        }
+    }
+}
+
+impl Thermal {
+    /// Heats (or cools, if negative) the ship by `energy` joules.
+    pub fn add_heat(&mut self, energy: f32) {
+        self.temperature =
+            (self.temperature + energy / self.heat_capacity).max(AMBIENT_TEMPERATURE);
+    }
+
+    /// How well the ship's hardware works at this temperature, on \[0,1\]: one
+    /// up to `overheat`, falling to zero at `critical`.
+    pub fn derating(&self) -> f32 {
+        let excess = (self.temperature - self.overheat) / (self.critical - self.overheat);
+        (1.0 - excess).clamp(0.0, 1.0)
+    }
+}
+
+/// :COMPONENT: A module which produces heat while it is working.
+#[derive(Reflect, Component, Default, Clone, Copy)]
+#[reflect(Component)]
+pub struct HeatSource {
+    /// Heat produced at full power, in watts. Thrusters only produce heat in
+    /// proportion to the engine's throttle.
+    pub output: f32,
+}
+
+/// :COMPONENT: A module which sheds heat into space.
+#[derive(Reflect, Component, Default, Clone, Copy)]
+#[reflect(Component)]
+pub struct Radiator {
+    /// Heat shed per kelvin above [`AMBIENT_TEMPERATURE`], in watts.
+    pub rating: f32,
+}
+
+/// :SYSTEM: Heats each ship with what its modules produce, and cools it with
+/// its radiators. Modules producing heat in a ship above its critical
+/// temperature lose a point of integrity per second for every kelvin over.
+/// In sandbox mode, the player's ships are never heated, only cooled.
+#[allow(clippy::type_complexity)]
+fn heat_system(
+    mut ships: Query<(
+        &Children,
+        &mut Thermal,
+        Option<&Engine>,
+        Option<&PlayerShip>,
+    )>,
+    mut modules: Query<(
+        &mut Module,
+        Option<&HeatSource>,
+        Option<&Radiator>,
+        Option<&Thruster>,
+        Option<&PowerConsumer>,
+    )>,
+    sandbox: Res<Sandbox>,
+    time: Res<Time>,
+) {
+    let dt = time.delta_seconds();
+
+    for (children, mut thermal, engine, player) in ships.iter_mut() {
+        let throttle = engine.map_or(0.0, |e| {
+            if e.thrust() > 0.0 {
+                e.throttle_fraction()
+            } else {
+                0.0
+            }
+        });
+        let overheated = (thermal.temperature - thermal.critical).max(0.0);
+        let exempt = sandbox.exempts(player.is_some());
+
+        let mut heat_flow = 0.0;
+        let mut modules = modules.iter_many_mut(children);
+        while let Some((mut module, source, radiator, thruster, consumer)) = modules.fetch_next() {
+            if consumer.is_some_and(|c| !c.enabled) {
+                continue;
+            }
+
+            if let Some(source) = source {
+                let activity = if thruster.is_some() { throttle } else { 1.0 };
+                heat_flow += source.output * activity;
+
+                if overheated > 0.0 && activity > 0.0 && !exempt {
+                    module.integrity -= overheated * dt;
+                }
+            }
+            if let Some(radiator) = radiator {
+                heat_flow -= radiator.rating * (thermal.temperature - AMBIENT_TEMPERATURE);
+            }
+        }
+
+        // the sandbox player's ship can still cool off, but never heats up
+        if exempt {
+            heat_flow = heat_flow.min(0.0);
+        }
+        thermal.add_heat(heat_flow * dt);
+    }
+}
+
+/// :SYSTEM: Shows how hot the controlled ship is running.
+fn heat_gauge_system(mut contexts: EguiContexts, player: Query<&Thermal, With<Controlled>>) {
+    let Ok(thermal) = player.get_single() else {
+        return;
+    };
+
+    let color = if thermal.temperature >= thermal.critical {
+        egui::Color32::RED
+    } else if thermal.temperature >= thermal.overheat {
+        egui::Color32::YELLOW
+    } else {
+        egui::Color32::LIGHT_GRAY
+    };
+
+    egui::Area::new("heat_gauge")
+        .anchor(egui::Align2::LEFT_BOTTOM, [8.0, -8.0])
+        .show(contexts.ctx_mut(), |ui| {
+            ui.colored_label(color, format!("{:.0} K", thermal.temperature));
+            ui.add(
+                egui::ProgressBar::new(thermal.temperature / thermal.critical).desired_width(120.0),
+            );
+        });
+}
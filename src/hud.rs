@@ -0,0 +1,242 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts, EguiSet};
+
+use super::assist_planner::AssistPlanner;
+use super::audio::AudioSettings;
+use super::autopilot::Autopilot;
+use super::boarding::Boarding;
+use super::damage_control::DamageControlPanel;
+use super::event_log::GameEventLog;
+use super::navigation::{self, STANDARD_GRAVITY};
+use super::physics::Kinimatics;
+use super::presets::PresetMenu;
+use super::ships::{Controlled, Engine, MissileLauncher};
+use super::user_interface::{CourseProjection, UiSettings};
+
+pub struct HudPlugin;
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut App) {
+        // before the rest of the UI, so anything anchored to the bottom of the
+        // screen sits on top of the tray rather than under it
+        app.add_system(
+            hud_system
+                .in_base_set(CoreSet::PreUpdate)
+                .after(EguiSet::BeginFrame),
+        )
+        .add_system(
+            tray_system
+                .in_base_set(CoreSet::PreUpdate)
+                .after(EguiSet::BeginFrame)
+                .before(hud_system),
+        );
+    }
+}
+
+/// Speeds time can be warped to from the tray.
+const TIME_WARPS: [f32; 4] = [1.0, 2.0, 4.0, 8.0];
+
+/// Diameter of the heading indicator, in pixels.
+const DIAL_SIZE: f32 = 48.0;
+
+/// Compass heading of `direction`, in degrees clockwise from up.
+fn heading(direction: Vec3) -> f32 {
+    direction
+        .x
+        .atan2(direction.y)
+        .to_degrees()
+        .rem_euclid(360.0)
+}
+
+/// Draws a dial with the ship's nose in white, and the way it's moving in
+/// green.
+fn heading_dial(ui: &mut egui::Ui, nose: Vec3, velocity: Vec3) {
+    let (rect, _) = ui.allocate_exact_size(egui::Vec2::splat(DIAL_SIZE), egui::Sense::hover());
+    let painter = ui.painter_at(rect);
+    let center = rect.center();
+    let radius = DIAL_SIZE / 2.0 - 2.0;
+    painter.circle_stroke(center, radius, (1.0, egui::Color32::GRAY));
+
+    // the map has +Y up, the screen has it down
+    let point = |v: Vec3| egui::vec2(v.x, -v.y) * radius;
+    if velocity.length_squared() > 0.0 {
+        let prograde = center + point(velocity.normalize());
+        painter.circle_filled(prograde, 3.0, egui::Color32::LIGHT_GREEN);
+    }
+    painter.arrow(
+        center - point(nose) * 0.6,
+        point(nose) * 1.4,
+        egui::Stroke::new(2.0, egui::Color32::WHITE),
+    );
+}
+
+/// :SYSTEM: Shows the controlled ship's speed, acceleration, fuel, delta-v,
+/// throttle, heading, selected hardpoint and boarding party in a tray along
+/// the bottom of the screen. With an autopilot goal, it shows how long the burn to match the
+/// goal's velocity takes too, and it flashes a warning when the course
+/// projection has the ship about to hit something.
+#[allow(clippy::type_complexity)]
+fn hud_system(
+    mut contexts: EguiContexts,
+    player: Query<
+        (
+            &Transform,
+            Option<&Kinimatics>,
+            Option<&Engine>,
+            Option<&Autopilot>,
+            Option<&MissileLauncher>,
+            Option<&Boarding>,
+        ),
+        With<Controlled>,
+    >,
+    projection: Res<CourseProjection>,
+    names: Query<&Name>,
+    time: Res<Time>,
+) {
+    let Ok((transform, kinimatics, engine, autopilot, launcher, boarding)) = player.get_single()
+    else {
+        return;
+    };
+    // docked ships ride along with whatever they're docked to
+    let (velocity, acceleration) =
+        kinimatics.map_or((Vec3::ZERO, Vec3::ZERO), |k| (k.velocity, k.acceleration));
+    let nose = transform.rotation.mul_vec3(Vec3::Y);
+
+    egui::TopBottomPanel::bottom("hud").show(contexts.ctx_mut(), |ui| {
+        ui.horizontal(|ui| {
+            heading_dial(ui, nose, velocity);
+            ui.vertical(|ui| {
+                ui.label(format!("hdg {:03.0}°", heading(nose)));
+                ui.label(format!("{:.1} m/s", velocity.length()));
+                ui.label(format!("{:.2} g", acceleration.length() / STANDARD_GRAVITY));
+            });
+            ui.separator();
+
+            let Some(engine) = engine else {
+                ui.weak("no engine");
+                return;
+            };
+            ui.vertical(|ui| {
+                let fuel = if engine.fuel_capacity > 0.0 {
+                    engine.fuel / engine.fuel_capacity
+                } else {
+                    0.0
+                };
+                ui.label("fuel");
+                ui.add(
+                    egui::ProgressBar::new(fuel)
+                        .desired_width(160.0)
+                        .text(format!("{:.0}", engine.fuel)),
+                );
+            });
+            if let Some(kinimatics) = kinimatics {
+                let budget = navigation::delta_v(engine, kinimatics.mass);
+                ui.vertical(|ui| {
+                    ui.label(format!("Δv {:.0} m/s", budget.total));
+                    ui.label(format!("Isp {:.0} s", budget.isp));
+                    let planned = autopilot
+                        .and_then(|a| a.goal)
+                        .map(|goal| (goal.velocity - kinimatics.velocity).length())
+                        .and_then(|dv| navigation::burn(engine, kinimatics.mass, dv).ok());
+                    match planned {
+                        Some(burn) if burn.fuel > engine.fuel => {
+                            ui.colored_label(egui::Color32::LIGHT_RED, "burn: not enough fuel");
+                        }
+                        Some(burn) => {
+                            ui.label(format!("burn {:.1} s", burn.time));
+                        }
+                        None => {
+                            ui.label(format!("{:.0} s of fuel", budget.burn_time));
+                        }
+                    }
+                });
+            }
+            ui.vertical(|ui| {
+                let throttle = engine.throttle_fraction();
+                ui.label("throttle");
+                ui.add(
+                    egui::ProgressBar::new(throttle)
+                        .desired_width(160.0)
+                        .text(format!("{:.0}%", throttle * 100.0)),
+                );
+            });
+            if let Some(launcher) = launcher.filter(|l| l.mounts > 0) {
+                ui.vertical(|ui| {
+                    ui.label(format!(
+                        "{} {}/{}",
+                        launcher.ordnance().label(),
+                        launcher.selected + 1,
+                        launcher.mounts
+                    ));
+                    ui.label(format!("{} left", launcher.ammo));
+                });
+            }
+            if let Some(boarding) = boarding {
+                ui.vertical(|ui| {
+                    ui.label("boarding");
+                    ui.add(
+                        egui::ProgressBar::new(boarding.progress / boarding.time)
+                            .desired_width(120.0)
+                            .show_percentage(),
+                    );
+                });
+            }
+
+            let soonest = projection
+                .warnings
+                .iter()
+                .min_by(|a, b| a.time.total_cmp(&b.time));
+            if let Some(warning) = soonest {
+                ui.separator();
+                let name = names
+                    .get(warning.other)
+                    .map_or_else(|_| format!("{:?}", warning.other), |n| n.to_string());
+                let text = match warning.impact {
+                    true => format!("IMPACT: {} in {:.1} s", name, warning.time),
+                    false => format!(
+                        "PROXIMITY: {} at {:.0} m in {:.1} s",
+                        name, warning.distance, warning.time
+                    ),
+                };
+                let lit = time.elapsed_seconds().fract() < 0.5;
+                let color = match lit {
+                    true => egui::Color32::RED,
+                    false => egui::Color32::from_rgb(120, 30, 30),
+                };
+                ui.label(egui::RichText::new(text).color(color).strong());
+            }
+        });
+    });
+}
+
+/// :SYSTEM: Shows the tray along the very bottom of the screen, with buttons
+/// to show and hide the course projection, labels and trails, to warp time
+/// (see [`UiSettings`]), and to open the audio and damage control panels, the
+/// gravity-assist planner, the event log and the presets menu.
+fn tray_system(
+    mut contexts: EguiContexts,
+    mut settings: ResMut<UiSettings>,
+    mut audio: ResMut<AudioSettings>,
+    mut repairs: ResMut<DamageControlPanel>,
+    mut planner: ResMut<AssistPlanner>,
+    mut log: ResMut<GameEventLog>,
+    mut presets: ResMut<PresetMenu>,
+) {
+    egui::TopBottomPanel::bottom("tray").show(contexts.ctx_mut(), |ui| {
+        ui.horizontal(|ui| {
+            ui.toggle_value(&mut settings.projection, "Projection");
+            ui.toggle_value(&mut settings.labels, "Labels");
+            ui.toggle_value(&mut settings.trails, "Trails");
+            ui.toggle_value(&mut audio.open, "Audio");
+            ui.toggle_value(&mut repairs.open, "Repairs");
+            ui.toggle_value(&mut planner.open, "Planner");
+            ui.toggle_value(&mut log.open, "Log");
+            ui.toggle_value(&mut presets.open, "Presets");
+            ui.separator();
+            ui.label("time warp");
+            for warp in TIME_WARPS {
+                ui.selectable_value(&mut settings.time_warp, warp, format!("{}×", warp));
+            }
+        });
+    });
+}
@@ -0,0 +1,471 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+use super::level::{AstroObject, AstroObjectBundle};
+use super::missions::{ObjectiveStatus, Objectives};
+use super::physics::{
+    ApplyImpulse, GravitySource, Kinimatics, KinimaticsBundle, PhysicsConfig, SimulationSet,
+};
+use super::sandbox::Sandbox;
+use super::scripting::{ScriptEvent, Value};
+use super::ships::{Detonation, Hull, PlayerShip, Ship};
+use super::user_interface::MapIcon;
+
+pub struct ImpactorPlugin;
+
+impl Plugin for ImpactorPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(
+            impactor_system
+                .after(super::physics::kinimatics_system)
+                .in_set(SimulationSet),
+        )
+        .add_system(
+            deflection_system
+                .after(super::ships::missile_detonation_system)
+                .in_set(SimulationSet),
+        )
+        .add_system(
+            collision_system
+                .after(super::physics::kinimatics_system)
+                .before(impactor_system)
+                .in_set(SimulationSet),
+        )
+        .add_system(impact_countdown_system);
+    }
+}
+
+/// Time step used to predict an impactor's course. This should be about the
+/// same as a frame, so predictions agree with what the simulation will do.
+const PREDICTION_STEP: f32 = 1.0 / 60.0;
+
+/// Momentum a missile hitting an impactor dead center gives it, per point of damage.
+const BLAST_IMPULSE: f32 = 50.0;
+
+/// How close a ship's center has to get to an impactor's surface to touch it.
+const SHIP_RADIUS: f32 = 5.0;
+
+/// Ships can bump into an impactor slower than this without being damaged.
+const SAFE_CONTACT_SPEED: f32 = 5.0;
+
+/// Hull damage per unit of speed above [`SAFE_CONTACT_SPEED`] a ship hits an
+/// impactor with.
+const CRASH_DAMAGE: f32 = 2.0;
+
+pub const IMPACTOR_MASS: f32 = 1000.0;
+pub const IMPACTOR_RADIUS: f32 = 3.0;
+
+/// :COMPONENT: An asteroid heading for a planet. The player's job is to push
+/// it far enough off course that it misses.
+#[derive(Component, Clone, Debug)]
+pub struct Impactor {
+    pub target: Entity,
+    /// How far from the target's surface the impactor has to pass for the
+    /// planet to be safe.
+    pub required_deflection: f32,
+    /// Key of the director objective which succeeds or fails with this impactor.
+    pub objective: Option<String>,
+    /// How far ahead to look for the impact, in seconds.
+    pub horizon: f32,
+    /// Predicted distance between the impactor and the target's surface at
+    /// their closest approach. Zero or less is a hit.
+    pub miss_distance: f32,
+    /// Predicted seconds until the closest approach.
+    pub time_to_approach: f32,
+}
+
+/// A body which pulls on an impactor, as it was at some moment.
+#[derive(Clone, Copy, Debug)]
+pub struct Body {
+    pub entity: Entity,
+    pub position: Vec3,
+    pub velocity: Vec3,
+    /// Standard gravitational parameter.
+    pub mu: f32,
+    pub radius: f32,
+}
+
+/// The closest an impactor comes to its target.
+#[derive(Clone, Copy, Debug)]
+pub struct Approach {
+    /// Distance between their centers.
+    pub distance: f32,
+    /// Seconds from now.
+    pub time: f32,
+}
+
+/// Moves `bodies`, and an optional massless probe (position, velocity), one
+/// step forward, in the same way as the physics engine.
+fn step(bodies: &mut [Body], probe: Option<&mut (Vec3, Vec3)>, dt: f32) {
+    let accelerations: Vec<Vec3> = bodies
+        .iter()
+        .map(|b| gravity_at(bodies, b.position, Some(b.entity)))
+        .collect();
+    if let Some((position, velocity)) = probe {
+        *velocity += gravity_at(bodies, *position, None) * dt;
+        *position += *velocity * dt;
+    }
+    for (body, a) in bodies.iter_mut().zip(accelerations) {
+        body.velocity += a * dt;
+        body.position += body.velocity * dt;
+    }
+}
+
+/// Exactly undoes a [`step`].
+fn unstep(bodies: &mut [Body], probe: &mut (Vec3, Vec3), dt: f32) {
+    for body in bodies.iter_mut() {
+        body.position -= body.velocity * dt;
+    }
+    probe.0 -= probe.1 * dt;
+
+    let accelerations: Vec<Vec3> = bodies
+        .iter()
+        .map(|b| gravity_at(bodies, b.position, Some(b.entity)))
+        .collect();
+    probe.1 -= gravity_at(bodies, probe.0, None) * dt;
+    for (body, a) in bodies.iter_mut().zip(accelerations) {
+        body.velocity -= a * dt;
+    }
+}
+
+/// Acceleration due to gravity at `position`, from every body but `except`.
+/// Bodies don't pull on anything inside of them, which keeps the acceleration
+/// finite at their centers.
+fn gravity_at(bodies: &[Body], position: Vec3, except: Option<Entity>) -> Vec3 {
+    bodies
+        .iter()
+        .filter(|b| Some(b.entity) != except)
+        .map(|b| {
+            let offset = b.position - position;
+            if offset.length() <= b.radius {
+                return Vec3::ZERO;
+            }
+            offset.normalize() * b.mu / offset.length_squared()
+        })
+        .sum()
+}
+
+fn find(bodies: &[Body], target: Entity) -> Result<usize, String> {
+    bodies
+        .iter()
+        .position(|b| b.entity == target)
+        .ok_or_else(|| "the target isn't an astronomical body".to_string())
+}
+
+/// Finds where an impactor has to start, and how fast it has to go, to hit
+/// the center of `target` in `time` seconds, with `arrival` velocity relative
+/// to the target.
+///
+/// Rather than searching for a course from a given starting point, which is
+/// hopeless anywhere near a star, this runs the simulation backwards from the
+/// impact, so the course is exact (at the prediction step).
+pub fn impact_course(
+    bodies: &[Body],
+    target: Entity,
+    time: f32,
+    arrival: Vec3,
+) -> Result<(Vec3, Vec3), String> {
+    let index = find(bodies, target)?;
+    if time <= 0.0 {
+        return Err(format!("can't hit a target in {} seconds", time));
+    }
+
+    let steps = (time / PREDICTION_STEP).ceil() as usize;
+    let mut future = bodies.to_vec();
+    for _ in 0..steps {
+        step(&mut future, None, PREDICTION_STEP);
+    }
+
+    let target = future[index];
+    let mut probe = (target.position, target.velocity + arrival);
+    for _ in 0..steps {
+        unstep(&mut future, &mut probe, PREDICTION_STEP);
+    }
+    Ok(probe)
+}
+
+/// Predicts the closest an impactor with `position` and `velocity` comes to
+/// `target` within `horizon` seconds, stopping early if it hits.
+pub fn closest_approach(
+    bodies: &[Body],
+    target: Entity,
+    position: Vec3,
+    velocity: Vec3,
+    horizon: f32,
+) -> Result<Approach, String> {
+    let index = find(bodies, target)?;
+    let radius = bodies[index].radius + IMPACTOR_RADIUS;
+
+    let mut future = bodies.to_vec();
+    let mut probe = (position, velocity);
+    let mut closest = Approach {
+        distance: position.distance(bodies[index].position),
+        time: 0.0,
+    };
+    for i in 1..=(horizon / PREDICTION_STEP).ceil() as usize {
+        step(&mut future, Some(&mut probe), PREDICTION_STEP);
+        let distance = probe.0.distance(future[index].position);
+        if distance < closest.distance {
+            closest = Approach {
+                distance,
+                time: i as f32 * PREDICTION_STEP,
+            };
+        }
+        if distance < radius {
+            break;
+        }
+    }
+    Ok(closest)
+}
+
+/// Takes a snapshot of every astronomical body.
+pub fn snapshot<'a>(
+    bodies: impl Iterator<Item = (Entity, &'a AstroObject, &'a Kinimatics, &'a Transform)>,
+    physics: &PhysicsConfig,
+) -> Vec<Body> {
+    bodies
+        .map(|(entity, astro, kin, transform)| Body {
+            entity,
+            position: transform.translation,
+            velocity: kin.velocity,
+            mu: physics.mu(kin.mass),
+            radius: astro.radius,
+        })
+        .collect()
+}
+
+/// Spawns an impactor on course to hit `target` in `time` seconds, arriving
+/// with `arrival` velocity relative to the target.
+#[allow(clippy::too_many_arguments)]
+pub fn spawn_impactor(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    bodies: &[Body],
+    target: Entity,
+    time: f32,
+    arrival: Vec3,
+    required_deflection: f32,
+    objective: Option<String>,
+) -> Result<Entity, String> {
+    let (from, velocity) = impact_course(bodies, target, time, arrival)?;
+    let impactor = commands
+        .spawn(AstroObjectBundle {
+            astro_object: AstroObject {
+                radius: IMPACTOR_RADIUS,
+                ..Default::default()
+            },
+            kinimatics_bundle: KinimaticsBundle::build()
+                .insert_mass(IMPACTOR_MASS)
+                .insert_translation(from)
+                .insert_velocity(velocity),
+            ..Default::default()
+        })
+        // predictions treat the impactor as a probe, so it mustn't pull on
+        // anything
+        .remove::<GravitySource>()
+        .insert(Impactor {
+            target,
+            required_deflection,
+            objective,
+            horizon: time * 1.5,
+            miss_distance: 0.0,
+            time_to_approach: time,
+        })
+        .with_children(|p| {
+            p.spawn((
+                SpriteBundle {
+                    sprite: Sprite {
+                        color: Color::GRAY,
+                        custom_size: Some(Vec2::splat(IMPACTOR_RADIUS * 2.0)),
+                        ..Default::default()
+                    },
+                    texture: asset_server.load("planet.png"),
+                    ..Default::default()
+                },
+                MapIcon,
+            ));
+        })
+        .id();
+    Ok(impactor)
+}
+
+/// :SYSTEM: Predicts where each impactor is going.
+///
+/// When an impactor reaches its target, an `impact` [`ScriptEvent`] is raised
+/// with the impactor and the target, and the impactor is destroyed. When it
+/// passes its closest approach instead, `impactor_passed` is raised with the
+/// impactor and the distance it missed by. Either way, its objective (if any)
+/// is completed or failed, depending on whether the miss was wide enough.
+#[allow(clippy::type_complexity)]
+fn impactor_system(
+    mut commands: Commands,
+    mut impactors: Query<(Entity, &mut Impactor, &Kinimatics, &Transform)>,
+    astro: Query<(Entity, &AstroObject, &Kinimatics, &Transform), Without<Impactor>>,
+    mut objectives: ResMut<Objectives>,
+    mut script_events: EventWriter<ScriptEvent>,
+    physics: Res<PhysicsConfig>,
+) {
+    if impactors.is_empty() {
+        return;
+    }
+    let bodies = snapshot(astro.iter(), &physics);
+
+    for (entity, mut impactor, kin, transform) in impactors.iter_mut() {
+        let Some(target) = bodies.iter().find(|b| b.entity == impactor.target) else {
+            commands.entity(entity).remove::<Impactor>();
+            continue;
+        };
+        let surface = target.radius + IMPACTOR_RADIUS;
+
+        let Ok(approach) = closest_approach(
+            &bodies,
+            impactor.target,
+            transform.translation,
+            kin.velocity,
+            impactor.horizon,
+        ) else {
+            continue;
+        };
+        impactor.miss_distance = approach.distance - surface;
+        impactor.time_to_approach = approach.time;
+
+        let (event, args, passed) = if transform.translation.distance(target.position) < surface {
+            commands.entity(entity).despawn_recursive();
+            let args = vec![
+                Value::from_entity(entity),
+                Value::from_entity(target.entity),
+            ];
+            ("impact", args, false)
+        } else if approach.time < PREDICTION_STEP * 2.0 {
+            commands.entity(entity).remove::<Impactor>();
+            let miss = impactor.miss_distance;
+            let args = vec![Value::from_entity(entity), Value::Num(miss as f64)];
+            (
+                "impactor_passed",
+                args,
+                miss >= impactor.required_deflection,
+            )
+        } else {
+            continue;
+        };
+
+        if let Some(key) = &impactor.objective {
+            let status = if passed {
+                ObjectiveStatus::Complete
+            } else {
+                ObjectiveStatus::Failed
+            };
+            if let Err(e) = objectives.set_status(key, status) {
+                warn!("impactor: {}", e);
+            }
+        }
+        script_events.send(ScriptEvent {
+            name: event.to_string(),
+            args,
+        });
+    }
+}
+
+/// :SYSTEM: Knocks impactors caught in a missile's blast off course.
+fn deflection_system(
+    mut detonations: EventReader<Detonation>,
+    impactors: Query<(Entity, &Kinimatics, &Transform), With<Impactor>>,
+    mut impulses: EventWriter<ApplyImpulse>,
+) {
+    for blast in detonations.iter() {
+        for (entity, kin, transform) in impactors.iter() {
+            let strength = blast.damage * BLAST_IMPULSE;
+            let Some(mut push) = ApplyImpulse::from_blast(
+                entity,
+                transform.translation,
+                blast.position,
+                blast.blast_radius,
+                strength,
+            ) else {
+                continue;
+            };
+            // a blast right on top of the impactor pushes it along its course
+            if push.impulse == Vec3::ZERO {
+                push.impulse = -kin.velocity.normalize_or_zero() * strength;
+            }
+            impulses.send(push);
+        }
+    }
+}
+
+/// :SYSTEM: Ships which run into an impactor stick to it, giving it their
+/// momentum. A fast enough crash damages the ship, but a ship resting against
+/// an impactor can push it along with its engine.
+#[allow(clippy::type_complexity)]
+fn collision_system(
+    mut ships: Query<
+        (
+            Entity,
+            &Kinimatics,
+            &Transform,
+            &mut Hull,
+            Option<&PlayerShip>,
+        ),
+        (With<Ship>, Without<Impactor>),
+    >,
+    impactors: Query<(Entity, &Kinimatics, &Transform), With<Impactor>>,
+    mut impulses: EventWriter<ApplyImpulse>,
+    sandbox: Res<Sandbox>,
+) {
+    for (ship_entity, ship, ship_transform, mut hull, player) in ships.iter_mut() {
+        for (rock_entity, rock, transform) in impactors.iter() {
+            let offset = transform.translation - ship_transform.translation;
+            let closing = (ship.velocity - rock.velocity).dot(offset.normalize_or_zero());
+            if offset.length() > IMPACTOR_RADIUS + SHIP_RADIUS || closing <= 0.0 {
+                continue;
+            }
+
+            let momentum = ship.velocity * ship.mass + rock.velocity * rock.mass;
+            let velocity = momentum / (ship.mass + rock.mass);
+            impulses.send(ApplyImpulse::new(
+                ship_entity,
+                (velocity - ship.velocity) * ship.mass,
+            ));
+            impulses.send(ApplyImpulse::new(
+                rock_entity,
+                (velocity - rock.velocity) * rock.mass,
+            ));
+
+            if closing > SAFE_CONTACT_SPEED && !sandbox.exempts(player.is_some()) {
+                hull.integrity -= (closing - SAFE_CONTACT_SPEED) * CRASH_DAMAGE;
+            }
+        }
+    }
+}
+
+/// :SYSTEM: Counts down to each impact, and shows how far off course the
+/// impactor has been pushed.
+fn impact_countdown_system(mut contexts: EguiContexts, impactors: Query<&Impactor>) {
+    if impactors.is_empty() {
+        return;
+    }
+
+    egui::Area::new("impact_countdown")
+        .anchor(egui::Align2::CENTER_TOP, [0.0, 8.0])
+        .show(contexts.ctx_mut(), |ui| {
+            for impactor in impactors.iter() {
+                let on_course = impactor.miss_distance <= 0.0;
+                let (color, status) = if on_course {
+                    (egui::Color32::RED, "IMPACT IN")
+                } else if impactor.miss_distance < impactor.required_deflection {
+                    (egui::Color32::YELLOW, "CLOSE PASS IN")
+                } else {
+                    (egui::Color32::GREEN, "SAFE PASS IN")
+                };
+                ui.colored_label(
+                    color,
+                    format!("{} {:.0}s", status, impactor.time_to_approach),
+                );
+                ui.label(format!(
+                    "deflection {:.1} / {:.1}",
+                    impactor.miss_distance.max(0.0),
+                    impactor.required_deflection
+                ));
+            }
+        });
+}
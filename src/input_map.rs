@@ -0,0 +1,276 @@
+use bevy::prelude::*;
+use bevy::reflect::{DynamicEnum, DynamicVariant, FromReflect};
+use bevy::utils::HashMap;
+use bevy_egui::{egui, EguiContexts};
+use serde::{Deserialize, Serialize};
+
+pub struct InputMapPlugin;
+
+impl Plugin for InputMapPlugin {
+    fn build(&self, app: &mut App) {
+        let map = match InputMap::load(INPUT_MAP_PATH) {
+            Ok(map) => map,
+            Err(e) => {
+                info!("using the default controls ({}: {})", INPUT_MAP_PATH, e);
+                InputMap::default()
+            }
+        };
+        app.insert_resource(map)
+            .init_resource::<ControlsPanel>()
+            .add_system(controls_panel_system);
+    }
+}
+
+/// Where the player's key bindings are kept.
+const INPUT_MAP_PATH: &str = "input.ron";
+
+/// Something the player can do with a key.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Action {
+    ThrustUp,
+    ThrustCut,
+    RotateLeft,
+    RotateRight,
+    GimbalLeft,
+    GimbalRight,
+    Dock,
+    Undock,
+    FireMissile,
+    FireLaser,
+    ToggleProjection,
+    NextShip,
+    NextTarget,
+    NextHardpoint,
+    Countermeasure,
+    Board,
+    Jam,
+    SilentRunning,
+}
+
+impl Action {
+    pub const ALL: &[Action] = &[
+        Action::ThrustUp,
+        Action::ThrustCut,
+        Action::RotateLeft,
+        Action::RotateRight,
+        Action::GimbalLeft,
+        Action::GimbalRight,
+        Action::Dock,
+        Action::Undock,
+        Action::FireMissile,
+        Action::FireLaser,
+        Action::ToggleProjection,
+        Action::NextShip,
+        Action::NextTarget,
+        Action::NextHardpoint,
+        Action::Countermeasure,
+        Action::Board,
+        Action::Jam,
+        Action::SilentRunning,
+    ];
+
+    /// Actions which fly the ship, and are recorded in tutorials.
+    pub const FLIGHT: &[Action] = &[
+        Action::ThrustUp,
+        Action::ThrustCut,
+        Action::RotateLeft,
+        Action::RotateRight,
+        Action::GimbalLeft,
+        Action::GimbalRight,
+        Action::Dock,
+        Action::Undock,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Action::ThrustUp => "Thrust",
+            Action::ThrustCut => "Cut thrust",
+            Action::RotateLeft => "Rotate left",
+            Action::RotateRight => "Rotate right",
+            Action::GimbalLeft => "Gimbal left",
+            Action::GimbalRight => "Gimbal right",
+            Action::Dock => "Dock",
+            Action::Undock => "Undock",
+            Action::FireMissile => "Fire missile",
+            Action::FireLaser => "Fire laser",
+            Action::ToggleProjection => "Toggle projection",
+            Action::NextShip => "Next ship",
+            Action::NextTarget => "Next target",
+            Action::NextHardpoint => "Next hardpoint",
+            Action::Countermeasure => "Countermeasures",
+            Action::Board => "Board target",
+            Action::Jam => "Toggle jammer",
+            Action::SilentRunning => "Silent running",
+        }
+    }
+}
+
+/// Name a key is saved under.
+pub fn key_name(key: KeyCode) -> String {
+    format!("{:?}", key)
+}
+
+/// The key saved under `name`, if there is one.
+pub fn key_from_name(name: &str) -> Option<KeyCode> {
+    KeyCode::from_reflect(&DynamicEnum::new("KeyCode", name, DynamicVariant::Unit))
+}
+
+/// Resource which holds the keys bound to each [`Action`]. Loaded from
+/// `input.ron` if it's there, and rebound with the controls panel (F12).
+#[derive(Resource, Clone, Debug)]
+pub struct InputMap {
+    pub bindings: HashMap<Action, Vec<KeyCode>>,
+}
+
+impl Default for InputMap {
+    fn default() -> Self {
+        use KeyCode::*;
+        let bindings = [
+            (Action::ThrustUp, vec![W, Up]),
+            (Action::ThrustCut, vec![S, Down]),
+            (Action::RotateLeft, vec![A, Left]),
+            (Action::RotateRight, vec![D, Right]),
+            (Action::GimbalLeft, vec![Q]),
+            (Action::GimbalRight, vec![E]),
+            (Action::Dock, vec![K]),
+            (Action::Undock, vec![U]),
+            (Action::FireMissile, vec![R]),
+            (Action::FireLaser, vec![B]),
+            (Action::ToggleProjection, vec![P]),
+            (Action::NextShip, vec![Tab]),
+            (Action::NextTarget, vec![Y]),
+            (Action::NextHardpoint, vec![H]),
+            (Action::Countermeasure, vec![C]),
+            (Action::Board, vec![G]),
+            (Action::Jam, vec![J]),
+            (Action::SilentRunning, vec![V]),
+        ];
+        Self {
+            bindings: bindings.into_iter().collect(),
+        }
+    }
+}
+
+impl InputMap {
+    pub fn keys(&self, action: Action) -> &[KeyCode] {
+        self.bindings.get(&action).map_or(&[], |k| k.as_slice())
+    }
+
+    pub fn pressed(&self, action: Action, input: &Input<KeyCode>) -> bool {
+        input.any_pressed(self.keys(action).iter().copied())
+    }
+
+    pub fn just_pressed(&self, action: Action, input: &Input<KeyCode>) -> bool {
+        input.any_just_pressed(self.keys(action).iter().copied())
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let saved: HashMap<Action, Vec<String>> = std::fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|s| ron::from_str(&s).map_err(|e| e.to_string()))?;
+        // anything the file leaves out keeps its default keys
+        let mut map = Self::default();
+        for (action, names) in saved {
+            let keys = names
+                .iter()
+                .map(|n| key_from_name(n).ok_or_else(|| format!("no key called {}", n)))
+                .collect::<Result<_, _>>()?;
+            map.bindings.insert(action, keys);
+        }
+        Ok(map)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let saved: HashMap<Action, Vec<String>> = self
+            .bindings
+            .iter()
+            .map(|(action, keys)| (*action, keys.iter().map(|k| key_name(*k)).collect()))
+            .collect();
+        let text = ron::ser::to_string_pretty(&saved, ron::ser::PrettyConfig::default())
+            .map_err(|e| e.to_string())?;
+        std::fs::write(path, text).map_err(|e| e.to_string())
+    }
+}
+
+/// Resource which holds the state of the controls panel. F12 opens and closes
+/// it.
+#[derive(Resource, Default)]
+pub struct ControlsPanel {
+    pub open: bool,
+    /// The action waiting for a key, and whether the key is added to its
+    /// bindings rather than replacing them.
+    rebinding: Option<(Action, bool)>,
+    status: String,
+}
+
+/// :SYSTEM: Shows the key bound to each action, and rebinds them. The next key
+/// pressed after choosing an action is bound to it, or Escape leaves it be.
+fn controls_panel_system(
+    mut contexts: EguiContexts,
+    mut panel: ResMut<ControlsPanel>,
+    mut map: ResMut<InputMap>,
+    input: Res<Input<KeyCode>>,
+) {
+    if input.just_pressed(KeyCode::F12) {
+        panel.open = !panel.open;
+    }
+    if !panel.open {
+        panel.rebinding = None;
+        return;
+    }
+
+    if let Some((action, add)) = panel.rebinding {
+        if let Some(key) = input.get_just_pressed().next().copied() {
+            panel.rebinding = None;
+            if key != KeyCode::Escape {
+                let keys = map.bindings.entry(action).or_default();
+                if !add {
+                    keys.clear();
+                }
+                if !keys.contains(&key) {
+                    keys.push(key);
+                }
+            }
+        }
+    }
+
+    let mut open = true;
+    egui::Window::new("Controls")
+        .open(&mut open)
+        .show(contexts.ctx_mut(), |ui| {
+            egui::Grid::new("controls").striped(true).show(ui, |ui| {
+                for action in Action::ALL.iter().copied() {
+                    ui.label(action.label());
+                    if panel.rebinding.is_some_and(|(a, _)| a == action) {
+                        ui.weak("press a key...");
+                    } else {
+                        let keys: Vec<_> = map.keys(action).iter().map(|k| key_name(*k)).collect();
+                        ui.monospace(keys.join(", "));
+                    }
+                    if ui.small_button("Set").clicked() {
+                        panel.rebinding = Some((action, false));
+                    }
+                    if ui.small_button("Add").clicked() {
+                        panel.rebinding = Some((action, true));
+                    }
+                    ui.end_row();
+                }
+            });
+            ui.separator();
+            ui.horizontal(|ui| {
+                if ui.button("Save").clicked() {
+                    panel.status = match map.save(INPUT_MAP_PATH) {
+                        Ok(()) => format!("saved to {}", INPUT_MAP_PATH),
+                        Err(e) => format!("couldn't save: {}", e),
+                    };
+                }
+                if ui.button("Defaults").clicked() {
+                    *map = InputMap::default();
+                }
+            });
+            if !panel.status.is_empty() {
+                ui.label(&panel.status);
+            }
+        });
+    panel.open = open;
+}
@@ -0,0 +1,101 @@
+use bevy::prelude::*;
+
+use super::input_map::{Action, InputMap};
+use super::net::NetPlayer;
+use super::physics::SimulationSet;
+use super::ships::{Controlled, Faction};
+
+pub struct JammingPlugin;
+
+impl Plugin for JammingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            (
+                player_jammer_system.before(jamming_system),
+                jamming_system.before(super::sensors::sensor_system),
+            )
+                .in_set(SimulationSet),
+        );
+    }
+}
+
+/// :COMPONENT: Floods the band with noise, jamming the sensors and radios of
+/// every hostile ship within `range` (see [`Jamming`]). The closer a ship is,
+/// the harder it's jammed.
+#[derive(Reflect, Component, Clone, Copy)]
+#[reflect(Component)]
+pub struct Jammer {
+    pub range: f32,
+    /// How much jamming a ship right on top of the jammer is under.
+    pub strength: f32,
+    pub enabled: bool,
+}
+
+impl Default for Jammer {
+    fn default() -> Self {
+        Self {
+            range: 600.0,
+            strength: 1.0,
+            enabled: false,
+        }
+    }
+}
+
+/// :COMPONENT: How hard the ship is being jammed, summed over every hostile
+/// [`Jammer`] in range. Zero is a clear band.
+///
+/// Jamming cuts the ship's radio bandwidth, and shortens the range its sensor
+/// can burn through to (see
+/// [`burn_through`](super::sensors::burn_through)). Contacts further out than
+/// that are spoofed: they show up, but only roughly where they are.
+#[derive(Reflect, Component, Default, Clone, Copy, Debug)]
+#[reflect(Component)]
+pub struct Jamming(pub f32);
+
+impl Jamming {
+    /// Fraction of a radio's bandwidth which gets through the noise.
+    pub fn clarity(&self) -> f32 {
+        1.0 / (1.0 + self.0)
+    }
+}
+
+/// :SYSTEM: Switches the controlled ship's jammer on and off.
+#[allow(clippy::type_complexity)]
+fn player_jammer_system(
+    mut player: Query<&mut Jammer, (With<Controlled>, Without<NetPlayer>)>,
+    input: Res<Input<KeyCode>>,
+    map: Res<InputMap>,
+) {
+    if !map.just_pressed(Action::Jam, &input) {
+        return;
+    }
+    for mut jammer in player.iter_mut() {
+        jammer.enabled = !jammer.enabled;
+    }
+}
+
+/// :SYSTEM: Works out how hard each ship is being jammed.
+fn jamming_system(
+    jammers: Query<(Entity, &Jammer, &Transform, Option<&Faction>)>,
+    mut ships: Query<(Entity, &mut Jamming, &Transform, Option<&Faction>)>,
+) {
+    for (entity, mut jamming, transform, faction) in ships.iter_mut() {
+        let faction = faction.copied().unwrap_or_default();
+        let level = jammers
+            .iter()
+            .filter(|(other, jammer, _, f)| {
+                *other != entity
+                    && jammer.enabled
+                    && faction.is_hostile_to(&f.copied().unwrap_or_default())
+            })
+            .map(|(_, jammer, t, _)| {
+                let distance = t.translation.distance(transform.translation);
+                jammer.strength * (1.0 - distance / jammer.range).max(0.0)
+            })
+            .sum();
+
+        if jamming.0 != level {
+            jamming.0 = level;
+        }
+    }
+}
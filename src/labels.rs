@@ -0,0 +1,115 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+use super::asteroid::Asteroid;
+use super::level::AstroObject;
+use super::ships::{Controlled, Ship};
+use super::star_system::Dormant;
+use super::user_interface::UiSettings;
+
+pub struct LabelsPlugin;
+
+impl Plugin for LabelsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(label_system);
+    }
+}
+
+/// Pixels between a sprite and its label.
+const LABEL_OFFSET: egui::Vec2 = egui::vec2(10.0, -6.0);
+
+/// "1.2k", "340", and so on.
+pub fn distance_text(distance: f32) -> String {
+    if distance >= 1e6 {
+        format!("{:.1}M", distance / 1e6)
+    } else if distance >= 1e3 {
+        format!("{:.1}k", distance / 1e3)
+    } else {
+        format!("{:.0}", distance)
+    }
+}
+
+/// :SYSTEM: Tags every named ship, body and station on the map with its name,
+/// and its distance from where the camera is looking.
+///
+/// Labels are placed most important first: the controlled ship, then bodies,
+/// then whatever's nearest the middle of the screen. Any label which would
+/// overlap one already placed is left out, so crowded areas thin out as the
+/// map zooms out, and fill back in as it zooms in. L shows and hides them
+/// (see [`UiSettings`]).
+#[allow(clippy::type_complexity)]
+fn label_system(
+    mut contexts: EguiContexts,
+    mut settings: ResMut<UiSettings>,
+    input: Res<Input<KeyCode>>,
+    cameras: Query<(&Camera, &GlobalTransform), With<Camera2d>>,
+    named: Query<
+        (
+            &Name,
+            &GlobalTransform,
+            Option<&Controlled>,
+            Option<&AstroObject>,
+        ),
+        (
+            Or<(With<Ship>, With<AstroObject>, Without<Parent>)>,
+            Without<Asteroid>,
+            Without<Dormant>,
+        ),
+    >,
+) {
+    let ctx = contexts.ctx_mut();
+    if input.just_pressed(KeyCode::L) && !ctx.wants_keyboard_input() {
+        settings.labels = !settings.labels;
+    }
+    if !settings.labels {
+        return;
+    }
+    let Ok((camera, camera_transform)) = cameras.get_single() else {
+        return;
+    };
+    let Some(viewport) = camera.logical_viewport_size() else {
+        return;
+    };
+    let target = camera_transform.translation().truncate();
+
+    let mut candidates: Vec<_> = named
+        .iter()
+        .filter_map(|(name, transform, controlled, body)| {
+            let at = camera.world_to_viewport(camera_transform, transform.translation())?;
+            // the viewport has +Y up, egui has it down
+            let at = egui::pos2(at.x, viewport.y - at.y);
+            let distance = transform.translation().truncate().distance(target);
+            let rank = match (controlled, body) {
+                (Some(_), _) => 0,
+                (_, Some(_)) => 1,
+                _ => 2,
+            };
+            Some((rank, distance, name.as_str(), at))
+        })
+        .collect();
+    candidates.sort_by(|a, b| a.0.cmp(&b.0).then(a.1.total_cmp(&b.1)));
+
+    let painter = ctx.layer_painter(egui::LayerId::background());
+    let screen = egui::Rect::from_min_size(egui::Pos2::ZERO, egui::vec2(viewport.x, viewport.y));
+    let font = egui::FontId::proportional(12.0);
+    let mut placed: Vec<egui::Rect> = Vec::new();
+    for (rank, distance, name, at) in candidates {
+        let text = if rank == 0 {
+            name.to_string()
+        } else {
+            format!("{} {}", name, distance_text(distance))
+        };
+        let color = if rank == 0 {
+            egui::Color32::LIGHT_GREEN
+        } else {
+            egui::Color32::LIGHT_GRAY
+        };
+        let galley = painter.layout_no_wrap(text, font.clone(), color);
+        let rect = egui::Rect::from_min_size(at + LABEL_OFFSET, galley.size());
+        if !screen.intersects(rect) || placed.iter().any(|r| r.intersects(rect)) {
+            continue;
+        }
+        placed.push(rect.expand(2.0));
+        painter.galley(rect.min, galley);
+    }
+}
@@ -0,0 +1,168 @@
+use bevy::prelude::*;
+
+use super::docking::Docked;
+use super::impactor::Impactor;
+use super::level::AstroObject;
+use super::physics::{Kinimatics, SimulationSet};
+use super::sandbox::Sandbox;
+use super::scripting::{ScriptEvent, ShipProgram, Value};
+use super::ships::{Engine, Hull, PlayerShip, Ship, Throttle};
+
+pub struct LandingPlugin;
+
+impl Plugin for LandingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            (
+                landing_system.after(super::physics::kinimatics_system),
+                takeoff_system.after(landing_system),
+            )
+                .in_set(SimulationSet),
+        );
+    }
+}
+
+/// How far a ship's center sits above the ground once it's down.
+pub const SHIP_RADIUS: f32 = 5.0;
+
+/// Ships can come down slower than this, straight towards the ground, without
+/// being damaged.
+pub const SAFE_LANDING_SPEED: f32 = 5.0;
+
+/// Hull damage per unit of speed above [`SAFE_LANDING_SPEED`] a ship hits the
+/// ground with.
+const CRASH_DAMAGE: f32 = 2.0;
+
+/// :COMPONENT: Added to a ship while it is down on the surface of `on`.
+///
+/// Like a [`Docked`] ship, a landed ship is a child of the body, standing on
+/// its tail, and doesn't have its own [`Kinimatics`]: it's carried along, and
+/// turned, with the body. The ship's own kinimatics are kept here until it
+/// takes off, which it does as soon as its engine is opened up.
+#[derive(Component, Clone, Copy)]
+pub struct Landed {
+    pub on: Entity,
+    kinimatics: Kinimatics,
+}
+
+/// :SYSTEM: Puts down every ship which reaches the surface of a body. A ship
+/// coming down faster than [`SAFE_LANDING_SPEED`] crashes, damaging its hull,
+/// but ends up on the ground all the same.
+///
+/// Raises `landed`, or `crashed` with the damage taken as well, with the ship
+/// and the body, and on the ship's program with the body.
+#[allow(clippy::type_complexity)]
+pub fn landing_system(
+    mut commands: Commands,
+    mut ships: Query<
+        (
+            Entity,
+            &Kinimatics,
+            &mut Transform,
+            &mut Hull,
+            Option<&mut Engine>,
+            Option<&mut ShipProgram>,
+            Option<&PlayerShip>,
+        ),
+        (With<Ship>, Without<AstroObject>, Without<Docked>),
+    >,
+    // ships run into impactors rather than landing on them
+    bodies: Query<(Entity, &AstroObject, &Kinimatics, &Transform), Without<Impactor>>,
+    mut script_events: EventWriter<ScriptEvent>,
+    sandbox: Res<Sandbox>,
+) {
+    for (ship, kin, mut transform, mut hull, engine, program, player) in ships.iter_mut() {
+        let touching = bodies.iter().find(|(_, astro, body, t)| {
+            let offset = transform.translation - t.translation;
+            let closing = (body.velocity - kin.velocity).dot(offset.normalize_or_zero());
+            offset.length() <= astro.radius + SHIP_RADIUS && closing > 0.0
+        });
+        let Some((body, astro, body_kin, body_transform)) = touching else {
+            continue;
+        };
+
+        let up = (transform.translation - body_transform.translation).normalize_or_zero();
+        let descent = (body_kin.velocity - kin.velocity).dot(up);
+        let damage = match sandbox.exempts(player.is_some()) {
+            true => 0.0,
+            false => (descent - SAFE_LANDING_SPEED).max(0.0) * CRASH_DAMAGE,
+        };
+        hull.integrity -= damage;
+
+        // stand the ship on its tail, on the surface, relative to the body
+        let inverse = body_transform.rotation.inverse();
+        let up = inverse.mul_vec3(up);
+        transform.translation = up * (astro.radius + SHIP_RADIUS);
+        transform.rotation = Quat::from_rotation_z((-up.x).atan2(up.y));
+
+        if let Some(mut engine) = engine {
+            engine.throttle = Throttle::Fixed(false);
+        }
+
+        commands.entity(ship).remove::<Kinimatics>().insert(Landed {
+            on: body,
+            kinimatics: *kin,
+        });
+        commands.entity(body).add_child(ship);
+
+        let (name, detail) = match descent > SAFE_LANDING_SPEED {
+            true => ("crashed", vec![Value::Num(damage as f64)]),
+            false => ("landed", vec![]),
+        };
+        if let Some(mut program) = program {
+            let args = [vec![Value::from_entity(body)], detail.clone()].concat();
+            program.vm.raise(name, args);
+        }
+        script_events.send(ScriptEvent {
+            name: name.to_string(),
+            args: [
+                vec![Value::from_entity(ship), Value::from_entity(body)],
+                detail,
+            ]
+            .concat(),
+        });
+    }
+}
+
+/// :SYSTEM: Lifts off every landed ship whose engine is opened up. The ship
+/// leaves the ground moving with it; whether it climbs away or settles back
+/// down again is up to its thrust. Raises `took_off`, with the ship and the
+/// body, and on the ship's program with the body.
+fn takeoff_system(
+    mut commands: Commands,
+    mut ships: Query<(
+        Entity,
+        &Landed,
+        &Engine,
+        &mut Transform,
+        Option<&mut ShipProgram>,
+    )>,
+    bodies: Query<(&Kinimatics, &Transform), Without<Landed>>,
+    mut script_events: EventWriter<ScriptEvent>,
+) {
+    for (ship, landed, engine, mut transform, program) in ships.iter_mut() {
+        if engine.thrust() <= 0.0 {
+            continue;
+        }
+        let Ok((body_kin, body_transform)) = bodies.get(landed.on) else {
+            continue;
+        };
+
+        *transform = body_transform.mul_transform(*transform);
+        let mut kinimatics = landed.kinimatics;
+        kinimatics.velocity = body_kin.velocity;
+
+        commands.entity(landed.on).remove_children(&[ship]);
+        commands.entity(ship).remove::<Landed>().insert(kinimatics);
+
+        if let Some(mut program) = program {
+            program
+                .vm
+                .raise("took_off", vec![Value::from_entity(landed.on)]);
+        }
+        script_events.send(ScriptEvent {
+            name: "took_off".to_string(),
+            args: vec![Value::from_entity(ship), Value::from_entity(landed.on)],
+        });
+    }
+}
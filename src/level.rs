@@ -1,11 +1,23 @@
-use super::physics::KinimaticsBundle;
+use super::clock::SimulationClock;
+use super::orbit::propagate_kepler;
+use super::perturbation::Atmosphere;
+use super::physics::{GravitySource, Kinimatics, KinimaticsBundle, PhysicsConfig, SimulationSet};
+use super::star_system::Dormant;
+use super::trails::Trail;
+use super::user_interface::{MapIcon, ProjectTrajectory};
 use bevy::prelude::*;
 
 pub struct LevelPlugin;
 
 impl Plugin for LevelPlugin {
     fn build(&self, app: &mut App) {
-        app.add_startup_system(startup_system);
+        app.add_startup_system(startup_system).add_systems(
+            (
+                rails_system.after(super::physics::kinimatics_system),
+                rotation_system.after(super::physics::kinimatics_system),
+            )
+                .in_set(SimulationSet),
+        );
     }
 
     fn name(&self) -> &str {
@@ -18,23 +30,103 @@ impl Plugin for LevelPlugin {
 #[reflect(Component)]
 pub struct AstroObject {
     pub radius: f32,
+    /// How fast the body turns, in radians per second, anticlockwise.
+    /// Anything on its surface turns with it.
+    pub rotation_rate: f32,
+}
+
+/// :COMPONENT: Marks the body which lights the star system, such as its sun.
+/// Planets are lit on the side facing it (see
+/// [`daylight`](super::daylight)).
+#[derive(Reflect, Component, Default, Clone, Copy)]
+#[reflect(Component)]
+pub struct Star;
+
+/// :COMPONENT: Puts an [`AstroObject`] on rails. Rather than being pulled
+/// along by the physics, it's placed on its Kepler orbit around `parent` every
+/// tick, worked out from where it was relative to it at `epoch`, so planets
+/// and moons can't drift off over a long game. It still pulls on everything
+/// else as normal. Ships are never put on rails.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct OnRails {
+    pub parent: Entity,
+    /// Relative to the parent, at the epoch.
+    pub position: Vec3,
+    /// Relative to the parent, at the epoch.
+    pub velocity: Vec3,
+    /// [`SimulationClock::elapsed`] when the orbit was taken.
+    pub epoch: f64,
+}
+
+impl OnRails {
+    /// Position and velocity relative to the parent at `elapsed`, where the
+    /// parent has gravitational parameter `mu`.
+    pub fn state_at(&self, elapsed: f64, mu: f32, physics: &PhysicsConfig) -> (Vec3, Vec3) {
+        let dt = (elapsed - self.epoch) * physics.time_scale as f64;
+        propagate_kepler(self.position, self.velocity, mu, dt as f32)
+    }
 }
 
 /// :BUNDLE: Provided for convenience. Describes a generic astronomical body.
 #[derive(Bundle, Default)]
 pub struct AstroObjectBundle {
     pub astro_object: AstroObject,
+    pub gravity_source: GravitySource,
+    pub project_trajectory: ProjectTrajectory,
     #[bundle]
     pub kinimatics_bundle: KinimaticsBundle,
 }
 
+/// Spawns an astronomical body, drawn with `sprite`.
+pub fn spawn_body(
+    commands: &mut Commands,
+    sprite: SpriteBundle,
+    radius: f32,
+    mass: f32,
+    translation: Vec3,
+    velocity: Vec3,
+) -> Entity {
+    commands
+        .spawn((
+            AstroObjectBundle {
+                astro_object: AstroObject {
+                    radius,
+                    ..Default::default()
+                },
+                kinimatics_bundle: KinimaticsBundle::build()
+                    .insert_mass(mass)
+                    .insert_translation(translation)
+                    .insert_velocity(velocity),
+                ..Default::default()
+            },
+            Trail::default(),
+        ))
+        .with_children(|p| {
+            p.spawn((sprite, MapIcon));
+        })
+        .id()
+}
+
 /// Resource which contains the sprites used to represents various astronomical
 /// bodies on the display.
 #[derive(Clone, Resource)]
-struct LevelSprites {
+pub struct LevelSprites {
     generic_planet: SpriteBundle,
 }
 
+/// Spawns a generic planet of the given radius.
+pub fn spawn_planet(
+    commands: &mut Commands,
+    sprites: &LevelSprites,
+    radius: f32,
+    mass: f32,
+    translation: Vec3,
+    velocity: Vec3,
+) -> Entity {
+    let sprite = sprites.generic_planet.clone();
+    spawn_body(commands, sprite, radius, mass, translation, velocity)
+}
+
 fn startup_system(
     mut commands: Commands,
     mut materials: ResMut<Assets<ColorMaterial>>,
@@ -43,10 +135,9 @@ fn startup_system(
     let sprite_resource = LevelSprites {
         generic_planet: SpriteBundle {
             sprite: Sprite {
-                custom_size: Some(Vec2::new(20.0, 20.0)),
+                custom_size: Some(Vec2::new(15.0, 15.0)),
                 ..Default::default()
             },
-            transform: Transform::from_scale(Vec3::new(0.75, 0.75, 0.0)),
             texture: asset_server.load("../assets/planet.png"),
             ..Default::default()
         },
@@ -54,34 +145,44 @@ fn startup_system(
 
     commands.insert_resource(sprite_resource.clone());
 
-    fn spawn_planet(
-        commands: &mut Commands,
-        sprite_resource: &LevelSprites,
-        mass: f32,
-        translation: Vec3,
-        velocity: Vec3,
-    ) {
-        commands
-            .spawn(AstroObjectBundle {
-                kinimatics_bundle: KinimaticsBundle::build()
-                    .insert_mass(mass)
-                    .insert_translation(translation)
-                    .insert_velocity(velocity),
-                ..Default::default()
-            })
-            .with_children(|p| {
-                p.spawn(sprite_resource.generic_planet.clone());
-            });
-    }
-
     //spawn_planet(&mut commands, &sprite_resource, 2e16, Vec3::new(100.0, 0.0, 0.0), Vec3::new(0.0, 40.0, 0.0));
     //spawn_planet(&mut commands, &sprite_resource, 2e16, Vec3::new(-100.0, 0.0, 0.0), Vec3::new(0.0, -40.0, 0.0));
 
     // the sun
-    spawn_planet(&mut commands, &sprite_resource, 2e15, Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 0.0));
+    let sun = spawn_planet(
+        &mut commands,
+        &sprite_resource,
+        7.5,
+        2e15,
+        Vec3::new(0.0, 0.0, 0.0),
+        Vec3::new(0.0, 0.0, 0.0),
+    );
+    // really its corona, which drags down anything in a low orbit
+    commands.entity(sun).insert((
+        Name::new("Sun"),
+        Star,
+        Atmosphere {
+            density: 0.01,
+            scale_height: 15.0,
+        },
+    ));
 
     //// Mercury
-    spawn_planet(&mut commands, &sprite_resource, 3.285e8, Vec3::new(0.0, 60.0, 0.0), Vec3::new(-47.9, 0.0, 0.0));
+    let mercury = spawn_planet(
+        &mut commands,
+        &sprite_resource,
+        7.5,
+        3.285e8,
+        Vec3::new(0.0, 60.0, 0.0),
+        Vec3::new(-47.9, 0.0, 0.0),
+    );
+    commands.entity(mercury).insert((
+        Name::new("Mercury"),
+        AstroObject {
+            radius: 7.5,
+            rotation_rate: 0.05,
+        },
+    ));
     //// Venus
     //spawn_planet(&mut commands, &sprite_resource, 4.867e24, Vec3::new(0.0, 100e9, 0.0), Vec3::new(0.0, 35.0e9, 0.0));
     //// Earth
@@ -93,3 +194,67 @@ fn startup_system(
     //// Saturn
     //spawn_planet(&mut commands, &sprite_resource, 5.683e26, Vec3::new(0.0, 1.42e12, 0.0), Vec3::new(0.0, 9.7e9, 0.0));
 }
+
+/// :SYSTEM: Turns every body at its rotation rate.
+fn rotation_system(
+    mut bodies: Query<(&AstroObject, &mut Transform), Without<Dormant>>,
+    time: Res<Time>,
+    physics: Res<PhysicsConfig>,
+) {
+    let dt = time.delta_seconds() * physics.time_scale;
+    for (astro, mut transform) in bodies.iter_mut() {
+        if astro.rotation_rate != 0.0 {
+            transform.rotate_z(astro.rotation_rate * dt);
+        }
+    }
+}
+
+/// :SYSTEM: Moves every body on rails to where its orbit has it now. A body
+/// goes after its parent, so moons follow planets which are on rails too. One
+/// whose parent is gone comes off rails, and is left to the physics.
+#[allow(clippy::type_complexity)]
+fn rails_system(
+    mut commands: Commands,
+    mut bodies: Query<
+        (Entity, &mut Transform, &mut Kinimatics, Option<&OnRails>),
+        Without<Dormant>,
+    >,
+    clock: Res<SimulationClock>,
+    physics: Res<PhysicsConfig>,
+) {
+    let rails: Vec<(Entity, OnRails)> = bodies
+        .iter()
+        .filter_map(|(entity, .., rails)| rails.map(|r| (entity, *r)))
+        .collect();
+    let mut moved = vec![false; rails.len()];
+    loop {
+        let mut progress = false;
+        for (i, (entity, on_rails)) in rails.iter().enumerate() {
+            let parent_waiting = rails
+                .iter()
+                .zip(&moved)
+                .any(|((e, _), moved)| *e == on_rails.parent && !moved);
+            if moved[i] || parent_waiting {
+                continue;
+            }
+            moved[i] = true;
+            progress = true;
+
+            let Ok((_, parent, parent_kin, _)) = bodies.get(on_rails.parent) else {
+                commands.entity(*entity).remove::<OnRails>();
+                continue;
+            };
+            let (parent_position, parent_velocity) = (parent.translation, parent_kin.velocity);
+            let (position, velocity) =
+                on_rails.state_at(clock.elapsed, physics.mu(parent_kin.mass), &physics);
+            if let Ok((_, mut transform, mut kin, _)) = bodies.get_mut(*entity) {
+                transform.translation = parent_position + position;
+                kin.velocity = parent_velocity + velocity;
+            }
+        }
+        // bodies on rails around each other are left to the physics
+        if !progress {
+            break;
+        }
+    }
+}
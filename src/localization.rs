@@ -0,0 +1,42 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+
+pub struct LocalizationPlugin;
+
+impl Plugin for LocalizationPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Localization::load("en"));
+    }
+}
+
+/// Resource which maps text keys (such as `comms.sender.control`) to text in the
+/// player's language.
+///
+/// Tables live in `assets/locale/<language>.ron`. Keys which aren't in the table
+/// are displayed as-is, so untranslated text written directly into scripts still
+/// shows up.
+#[derive(Resource, Default)]
+pub struct Localization {
+    strings: HashMap<String, String>,
+}
+
+impl Localization {
+    pub fn load(language: &str) -> Self {
+        let path = format!("assets/locale/{}.ron", language);
+        let strings = std::fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|s| ron::from_str(&s).map_err(|e| e.to_string()))
+            .unwrap_or_else(|e| {
+                warn!("couldn't load {}: {}", path, e);
+                HashMap::new()
+            });
+
+        Self { strings }
+    }
+
+    /// Looks up `key`, falling back to the key itself.
+    pub fn get<'a>(&'a self, key: &'a str) -> &'a str {
+        self.strings.get(key).map(String::as_str).unwrap_or(key)
+    }
+}
@@ -0,0 +1,256 @@
+use bevy::prelude::*;
+
+use super::autopilot::{Autopilot, Goal};
+use super::docking::{Docked, DockingPort, DockingRequest};
+use super::physics::{Attitude, Kinimatics, SimulationSet};
+use super::refueling::{Stores, TransferLine, TransferRequest};
+use super::scripting::{ScriptEvent, Value};
+use super::ships::CargoHold;
+
+pub struct LogisticsPlugin;
+
+impl Plugin for LogisticsPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Depot>()
+            .register_type::<Freighter>()
+            .add_system(
+                logistics_system
+                    .after(super::autopilot::autopilot_system)
+                    .before(super::physics::kinimatics_system)
+                    .in_set(SimulationSet),
+            );
+    }
+}
+
+/// Smallest load worth flying for, in tonnes.
+const MIN_LOAD: f32 = 5.0;
+
+/// :COMPONENT: Somewhere freighters pick up and drop off cargo. Anything over
+/// `wants` in its [`CargoHold`] is supply, and anything under it is demand.
+#[derive(Reflect, Component, Clone, Copy, Debug, Default)]
+#[reflect(Component)]
+pub struct Depot {
+    /// Tonnes of cargo the depot wants to keep on hand.
+    pub wants: f32,
+}
+
+impl Depot {
+    /// Tonnes the depot can spare, or if negative, is short of.
+    pub fn surplus(&self, hold: &CargoHold) -> f32 {
+        hold.cargo - self.wants
+    }
+}
+
+/// What a freighter is doing.
+#[derive(Reflect, Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LogisticsState {
+    /// Waiting for a depot with cargo to spare, and one short of it.
+    #[default]
+    Idle,
+    /// Flying to `from` to load up.
+    Pickup,
+    /// Docked at `from`, taking on cargo.
+    Loading,
+    /// Flying to `to` to unload.
+    Delivery,
+    /// Docked at `to`, handing over cargo.
+    Unloading,
+}
+
+/// :COMPONENT: Moves cargo from [`Depot`]s with a surplus to ones which are
+/// short, flying with its [`Autopilot`] and docking to load and unload.
+///
+/// Freighters pick the job which moves the most cargo for the distance they
+/// fly, leaving out cargo other freighters are already carrying. Each finished
+/// delivery raises a `delivery` [`ScriptEvent`], with the freighter, both
+/// depots, and the tonnes delivered.
+#[derive(Reflect, Component, Clone, Copy, Debug, Default)]
+#[reflect(Component)]
+pub struct Freighter {
+    pub state: LogisticsState,
+    pub from: Option<Entity>,
+    pub to: Option<Entity>,
+    /// Tonnes the freighter means to carry.
+    pub load: f32,
+    /// Whether the transfer line at the current depot has been connected.
+    connected: bool,
+}
+
+/// :SYSTEM: Plans each freighter's next job, and flies, docks and trades it
+/// through.
+#[allow(clippy::type_complexity)]
+fn logistics_system(
+    mut freighters: Query<
+        (
+            Entity,
+            &mut Freighter,
+            &mut Autopilot,
+            &Transform,
+            &mut Attitude,
+            &CargoHold,
+            Option<&Kinimatics>,
+            Option<&Docked>,
+            Option<&TransferLine>,
+        ),
+        Without<Depot>,
+    >,
+    depots: Query<(
+        Entity,
+        &Depot,
+        &CargoHold,
+        &Transform,
+        &Kinimatics,
+        &DockingPort,
+    )>,
+    mut docking: EventWriter<DockingRequest>,
+    mut transfers: EventWriter<TransferRequest>,
+    mut script_events: EventWriter<ScriptEvent>,
+    time: Res<Time>,
+) {
+    // cargo already spoken for, by depot
+    let mut committed: Vec<(Entity, f32)> = Vec::new();
+    for (_, freighter, ..) in freighters.iter() {
+        if let (Some(from), Some(to)) = (freighter.from, freighter.to) {
+            if matches!(
+                freighter.state,
+                LogisticsState::Pickup | LogisticsState::Loading
+            ) {
+                committed.push((from, freighter.load));
+            }
+            committed.push((to, -freighter.load));
+        }
+    }
+    let available = |committed: &[(Entity, f32)], depot: Entity, surplus: f32| {
+        let spoken_for: f32 = committed
+            .iter()
+            .filter(|(d, _)| *d == depot)
+            .map(|(_, load)| load)
+            .sum();
+        surplus - spoken_for
+    };
+
+    for (entity, mut freighter, mut autopilot, transform, mut attitude, hold, kin, docked, line) in
+        freighters.iter_mut()
+    {
+        match freighter.state {
+            LogisticsState::Idle => {
+                autopilot.goal = None;
+                let room = hold.capacity - hold.cargo;
+
+                let mut best: Option<(f32, Entity, Entity, f32)> = None;
+                for (from, from_depot, from_hold, from_t, ..) in depots.iter() {
+                    let supply = available(&committed, from, from_depot.surplus(from_hold));
+                    for (to, to_depot, to_hold, to_t, ..) in depots.iter() {
+                        let demand = -available(&committed, to, to_depot.surplus(to_hold));
+                        let load = supply.min(demand).min(room);
+                        if from == to || load < MIN_LOAD {
+                            continue;
+                        }
+                        let distance = transform.translation.distance(from_t.translation)
+                            + from_t.translation.distance(to_t.translation);
+                        let score = load / distance.max(1.0);
+                        if best.is_none_or(|(s, ..)| score > s) {
+                            best = Some((score, from, to, load));
+                        }
+                    }
+                }
+
+                if let Some((_, from, to, load)) = best {
+                    *freighter = Freighter {
+                        state: LogisticsState::Pickup,
+                        from: Some(from),
+                        to: Some(to),
+                        load,
+                        connected: false,
+                    };
+                    committed.push((from, load));
+                    committed.push((to, -load));
+                }
+            }
+            LogisticsState::Pickup | LogisticsState::Delivery => {
+                let pickup = freighter.state == LogisticsState::Pickup;
+                let Some(target) = (if pickup { freighter.from } else { freighter.to }) else {
+                    freighter.state = LogisticsState::Idle;
+                    continue;
+                };
+                let Ok((_, _, _, target_t, target_k, port)) = depots.get(target) else {
+                    freighter.state = LogisticsState::Idle;
+                    continue;
+                };
+
+                if docked.is_some_and(|d| d.to == target) {
+                    autopilot.goal = None;
+                    let load = if pickup { -freighter.load } else { hold.cargo };
+                    transfers.send(TransferRequest::Start {
+                        ship: entity,
+                        other: target,
+                        stores: Stores::Cargo,
+                        rate: load.signum() * Stores::Cargo.max_rate(),
+                    });
+                    freighter.connected = false;
+                    freighter.state = if pickup {
+                        LogisticsState::Loading
+                    } else {
+                        LogisticsState::Unloading
+                    };
+                    continue;
+                }
+                let Some(kin) = kin else { continue };
+
+                let target_goal = Goal {
+                    position: target_t.translation,
+                    velocity: target_k.velocity,
+                };
+                let dt = time.delta_seconds();
+                if autopilot.approach_for_docking(
+                    transform,
+                    &mut attitude,
+                    kin.velocity,
+                    target_goal,
+                    port,
+                    dt,
+                ) {
+                    docking.send(DockingRequest::Dock {
+                        ship: entity,
+                        target,
+                    });
+                }
+            }
+            LogisticsState::Loading | LogisticsState::Unloading => {
+                let loading = freighter.state == LogisticsState::Loading;
+                let done = if loading {
+                    hold.cargo >= freighter.load
+                } else {
+                    hold.cargo <= 0.0
+                };
+                freighter.connected |= line.is_some();
+                if !done && (line.is_some() || !freighter.connected) {
+                    continue;
+                }
+
+                transfers.send(TransferRequest::Stop { ship: entity });
+                docking.send(DockingRequest::Undock { ship: entity });
+                freighter.connected = false;
+                if loading && hold.cargo > 0.0 {
+                    freighter.load = hold.cargo;
+                    freighter.state = LogisticsState::Delivery;
+                } else if loading {
+                    *freighter = Freighter::default();
+                } else {
+                    if let (Some(from), Some(to)) = (freighter.from, freighter.to) {
+                        script_events.send(ScriptEvent {
+                            name: "delivery".to_string(),
+                            args: vec![
+                                Value::from_entity(entity),
+                                Value::from_entity(from),
+                                Value::from_entity(to),
+                                Value::Num((freighter.load - hold.cargo) as f64),
+                            ],
+                        });
+                    }
+                    *freighter = Freighter::default();
+                }
+            }
+        }
+    }
+}
@@ -1,28 +1,252 @@
+mod ai;
+mod assist_planner;
+mod asteroid;
+mod audio;
+mod autopilot;
+mod balance;
+mod beams;
+mod blackboard;
+mod boarding;
+mod campaign;
+mod clock;
+mod code_editor;
+mod comms;
+mod context_menu;
+mod control_groups;
+mod countermeasures;
+mod cutscene;
+mod damage_control;
+mod daylight;
+mod debug_tools;
+mod determinism;
+mod dialogue;
+mod director;
+mod docking;
+mod economy;
+mod event_log;
+mod fast_forward;
+mod fleet;
+mod flyby;
+mod focus;
+mod headless;
+mod heat;
+mod hud;
+mod impactor;
+mod input_map;
+mod jamming;
+mod labels;
+mod landing;
 mod level;
+mod localization;
+mod logistics;
+mod minimap;
+mod missions;
+mod modules;
+mod navigation;
+mod net;
+mod orbit;
+mod orders;
+mod pause_menu;
+mod perturbation;
 mod physics;
+mod power;
+mod presets;
+mod profiles;
+mod propagation;
+mod realtime;
+mod refueling;
+mod remote;
+mod replay;
+mod route;
+mod sandbox;
+mod scenario_editor;
+mod scenes;
+mod scheduler;
+mod script_debugger;
+mod scripting;
+mod seeker;
+mod sensors;
+mod server;
+mod shake;
+mod shields;
 mod ships;
+mod spawn_menu;
+mod star_system;
+mod station;
+mod stealth;
+mod surface_base;
+mod targeting;
+mod telemetry;
+mod tournament;
+mod trade;
+mod trails;
+mod tutorial;
 mod user_interface;
+mod vfx;
 
 #[allow(dead_code)]
 use bevy::prelude::*;
 use bevy_inspector_egui::quick::WorldInspectorPlugin;
 
 fn main() {
-    App::new()
-        .add_plugins(DefaultPlugins)
+    let headless = headless::Headless::from_args();
+    let plugins = DefaultPlugins.set(AssetPlugin {
+        // lets ship programs be reloaded while the game is running
+        watch_for_changes: headless.is_none(),
+        ..Default::default()
+    });
+    let plugins = match headless {
+        Some(_) => headless::headless_plugins(plugins),
+        None => plugins,
+    };
 
+    let mut app = App::new();
+    app.add_plugins(plugins)
         .add_plugin(WorldInspectorPlugin::default())
         .register_type::<physics::Kinimatics>()
+        .register_type::<physics::Attitude>()
+        .register_type::<physics::GravitySource>()
+        .register_type::<physics::GravityAffected>()
         .register_type::<ships::Ship>()
         .register_type::<ships::Engine>()
         .register_type::<ships::Throttle>()
+        .register_type::<ships::ThrottleProgram>()
         .register_type::<ships::Missile>()
+        .register_type::<ships::MissileKind>()
+        .register_type::<seeker::Seeker>()
+        .register_type::<seeker::SeekerState>()
+        .register_type::<ships::MissileLauncher>()
+        .register_type::<countermeasures::Countermeasure>()
+        .register_type::<beams::Laser>()
+        .register_type::<shields::Shield>()
+        .register_type::<shields::Quadrant>()
+        .register_type::<ships::CargoHold>()
+        .register_type::<ships::Commodity>()
+        .register_type::<ships::Hull>()
+        .register_type::<damage_control::DamageControl>()
+        .register_type::<damage_control::Repair>()
+        .register_type::<ships::Faction>()
+        .register_type::<sensors::Sensor>()
+        .register_type::<jamming::Jammer>()
+        .register_type::<jamming::Jamming>()
+        .register_type::<stealth::Emissions>()
+        .register_type::<ai::AiController>()
+        .register_type::<docking::DockingPort>()
         .register_type::<level::AstroObject>()
-
+        .register_type::<level::Star>()
+        .register_type::<perturbation::Atmosphere>()
+        .register_type::<modules::Frame>()
+        .register_type::<modules::Module>()
+        .register_type::<modules::Thruster>()
+        .register_type::<modules::FuelTank>()
+        .register_type::<modules::WeaponMount>()
+        .register_type::<modules::BeamMount>()
+        .register_type::<modules::ShieldGenerator>()
+        .register_type::<modules::SensorArray>()
+        .register_type::<power::Reactor>()
+        .register_type::<power::Battery>()
+        .register_type::<power::SolarPanel>()
+        .register_type::<power::PowerConsumer>()
+        .register_type::<power::PowerGrid>()
+        .register_type::<heat::Thermal>()
+        .register_type::<heat::HeatSource>()
+        .register_type::<heat::Radiator>()
+        .register_type::<user_interface::MapIcon>()
+        .register_type::<user_interface::ProjectTrajectory>()
         .insert_resource(ClearColor(Color::rgb_u8(0, 0, 0)))
+        .add_plugin(clock::ClockPlugin)
+        .add_plugin(scheduler::SchedulerPlugin)
+        .add_plugin(balance::BalancePlugin)
+        .add_plugin(sandbox::SandboxPlugin)
         .add_plugin(ships::ShipsPlugin)
         .add_plugin(level::LevelPlugin)
-        .add_plugin(physics::PhysicsPlugin)
+        .add_plugin(physics::PhysicsPlugin::new(
+            physics::PhysicsConfig::load_or_default(physics::PHYSICS_CONFIG_PATH),
+        ))
         .add_plugin(user_interface::UserInterfacePlugin)
-        .run();
+        .add_plugin(scripting::ScriptingPlugin)
+        .add_plugin(localization::LocalizationPlugin)
+        .add_plugin(dialogue::DialoguePlugin)
+        .add_plugin(director::DirectorPlugin)
+        .add_plugin(missions::MissionsPlugin)
+        .add_plugin(sensors::SensorsPlugin)
+        .add_plugin(jamming::JammingPlugin)
+        .add_plugin(stealth::StealthPlugin)
+        .add_plugin(ai::AiPlugin)
+        .add_plugin(docking::DockingPlugin)
+        .add_plugin(landing::LandingPlugin)
+        .add_plugin(refueling::RefuelingPlugin)
+        .add_plugin(modules::ModulesPlugin)
+        .add_plugin(power::PowerPlugin)
+        .add_plugin(heat::HeatPlugin)
+        .add_plugin(cutscene::CutscenePlugin)
+        .add_plugin(impactor::ImpactorPlugin)
+        .add_plugin(perturbation::PerturbationPlugin)
+        .add_plugin(code_editor::CodeEditorPlugin)
+        .add_plugin(blackboard::BlackboardPlugin)
+        .add_plugin(comms::CommsPlugin)
+        .add_plugin(autopilot::AutopilotPlugin)
+        .add_plugin(fleet::FleetPlugin)
+        .add_plugin(route::RoutePlugin)
+        .add_plugin(logistics::LogisticsPlugin)
+        .add_plugin(orders::OrdersPlugin)
+        .add_plugin(context_menu::ContextMenuPlugin)
+        .add_plugin(targeting::TargetingPlugin)
+        .add_plugin(countermeasures::CountermeasuresPlugin)
+        .add_plugin(seeker::SeekerPlugin)
+        .add_plugin(beams::BeamsPlugin)
+        .add_plugin(shields::ShieldsPlugin)
+        .add_plugin(boarding::BoardingPlugin)
+        .add_plugin(damage_control::DamageControlPlugin)
+        .add_plugin(control_groups::ControlGroupsPlugin)
+        .add_plugin(station::StationPlugin)
+        .add_plugin(surface_base::SurfaceBasePlugin)
+        .add_plugin(daylight::DaylightPlugin)
+        .add_plugin(replay::ReplayPlugin)
+        .add_plugin(star_system::StarSystemPlugin)
+        .add_plugin(trade::TradePlugin)
+        .add_plugin(economy::EconomyPlugin)
+        .add_plugin(labels::LabelsPlugin)
+        .add_plugin(hud::HudPlugin)
+        .add_plugin(event_log::EventLogPlugin)
+        .add_plugin(minimap::MinimapPlugin)
+        .add_plugin(trails::TrailsPlugin)
+        .add_plugin(input_map::InputMapPlugin)
+        .add_plugin(focus::FocusPlugin)
+        .add_plugin(spawn_menu::SpawnMenuPlugin)
+        .add_plugin(presets::PresetsPlugin)
+        .add_plugin(profiles::ProfilesPlugin)
+        .add_plugin(pause_menu::PauseMenuPlugin)
+        .add_plugin(scenario_editor::ScenarioEditorPlugin)
+        .add_plugin(scenes::ScenesPlugin)
+        .add_plugin(determinism::DeterminismPlugin)
+        .add_plugin(net::NetPlugin)
+        .add_plugin(server::ServerPlugin)
+        .add_plugin(remote::RemotePlugin)
+        .add_plugin(telemetry::TelemetryPlugin)
+        .add_plugin(fast_forward::FastForwardPlugin)
+        .add_plugin(asteroid::AsteroidPlugin)
+        .add_plugin(flyby::FlybyPlugin)
+        .add_plugin(assist_planner::AssistPlannerPlugin)
+        .add_plugin(realtime::RealTimePlugin)
+        .add_plugin(script_debugger::ScriptDebuggerPlugin)
+        .add_plugin(tutorial::TutorialPlugin)
+        .add_plugin(vfx::VfxPlugin)
+        .add_plugin(audio::AudioPlugin)
+        .add_plugin(shake::ShakePlugin);
+
+    if let Some(headless) = headless {
+        app.add_plugin(headless::HeadlessPlugin(headless));
+    }
+    app.add_plugin(campaign::CampaignPlugin);
+    if let Some(tournament) = tournament::Tournament::from_args() {
+        app.add_plugin(tournament::TournamentPlugin(tournament));
+    }
+
+    // tools which break the rules of the simulation stay out of release builds
+    if cfg!(debug_assertions) {
+        app.add_plugin(debug_tools::DebugToolsPlugin);
+    }
+
+    app.run();
 }
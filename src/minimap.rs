@@ -0,0 +1,140 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+use super::cutscene::Cutscene;
+use super::level::AstroObject;
+use super::ships::{Controlled, Ship};
+use super::star_system::Dormant;
+
+pub struct MinimapPlugin;
+
+impl Plugin for MinimapPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Minimap>().add_system(minimap_system);
+    }
+}
+
+/// Width and height of the minimap, in pixels.
+const MINIMAP_SIZE: f32 = 180.0;
+
+/// Resource which says whether the minimap is shown. M toggles it.
+#[derive(Resource)]
+pub struct Minimap {
+    pub show: bool,
+}
+
+impl Default for Minimap {
+    fn default() -> Self {
+        Self { show: true }
+    }
+}
+
+/// :SYSTEM: Draws every body and ship in the active system in the corner of the
+/// screen, scaled to fit the whole system however far the main view is zoomed,
+/// with the main view outlined. Clicking the minimap moves the main view there.
+#[allow(clippy::type_complexity)]
+fn minimap_system(
+    mut contexts: EguiContexts,
+    mut minimap: ResMut<Minimap>,
+    input: Res<Input<KeyCode>>,
+    mut cameras: Query<(&Camera, &OrthographicProjection, &mut Transform), With<Camera2d>>,
+    objects: Query<
+        (
+            &GlobalTransform,
+            Option<&AstroObject>,
+            Option<&Ship>,
+            Option<&Controlled>,
+        ),
+        (Or<(With<AstroObject>, With<Ship>)>, Without<Dormant>),
+    >,
+    cutscene: Res<Cutscene>,
+) {
+    let ctx = contexts.ctx_mut();
+    if input.just_pressed(KeyCode::M) && !ctx.wants_keyboard_input() {
+        minimap.show = !minimap.show;
+    }
+    if !minimap.show {
+        return;
+    }
+    let Ok((camera, ortho, mut camera_transform)) = cameras.get_single_mut() else {
+        return;
+    };
+    let Some(viewport) = camera.logical_viewport_size() else {
+        return;
+    };
+
+    // fit the whole system, leaving a margin around the edge
+    let (min, max) = objects.iter().fold(
+        (Vec2::splat(f32::MAX), Vec2::splat(f32::MIN)),
+        |(min, max), (t, ..)| {
+            let p = t.translation().truncate();
+            (min.min(p), max.max(p))
+        },
+    );
+    if min.x > max.x {
+        return;
+    }
+    let center = (min + max) / 2.0;
+    let half_extent = ((max - min).max_element() / 2.0).max(1.0) * 1.1;
+
+    egui::Area::new("minimap")
+        .anchor(egui::Align2::RIGHT_TOP, [-8.0, 40.0])
+        .show(ctx, |ui| {
+            let (rect, response) = ui.allocate_exact_size(
+                egui::Vec2::splat(MINIMAP_SIZE),
+                egui::Sense::click_and_drag(),
+            );
+            let painter = ui.painter_at(rect);
+            painter.rect(
+                rect,
+                2.0,
+                egui::Color32::from_black_alpha(200),
+                (1.0, egui::Color32::DARK_GRAY),
+            );
+
+            let scale = MINIMAP_SIZE / 2.0 / half_extent;
+            // the map has +Y up, the screen has it down
+            let to_screen = |p: Vec2| {
+                let offset = (p - center) * scale;
+                rect.center() + egui::vec2(offset.x, -offset.y)
+            };
+            let to_world = |p: egui::Pos2| {
+                let offset = p - rect.center();
+                center + Vec2::new(offset.x, -offset.y) / scale
+            };
+
+            for (transform, body, ship, controlled) in objects.iter() {
+                let at = to_screen(transform.translation().truncate());
+                match (body, ship, controlled) {
+                    (Some(body), ..) => {
+                        let radius = (body.radius * scale).max(2.0);
+                        painter.circle_filled(at, radius, egui::Color32::GRAY);
+                    }
+                    (_, _, Some(_)) => {
+                        painter.circle_filled(at, 3.0, egui::Color32::LIGHT_GREEN);
+                    }
+                    (_, Some(_), _) => {
+                        painter.circle_filled(at, 1.5, egui::Color32::LIGHT_BLUE);
+                    }
+                    _ => {}
+                }
+            }
+
+            let eye = camera_transform.translation.truncate();
+            let half_view = Vec2::new(viewport.x, viewport.y) * ortho.scale / 2.0;
+            let view =
+                egui::Rect::from_two_pos(to_screen(eye - half_view), to_screen(eye + half_view));
+            painter.rect_stroke(view, 0.0, (1.0, egui::Color32::WHITE));
+
+            if cutscene.playing() {
+                return;
+            }
+            if response.clicked() || response.dragged() {
+                if let Some(pointer) = response.interact_pointer_pos() {
+                    let to = to_world(pointer);
+                    camera_transform.translation.x = to.x;
+                    camera_transform.translation.y = to.y;
+                }
+            }
+        });
+}
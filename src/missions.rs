@@ -0,0 +1,344 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+use super::clock::SimulationClock;
+use super::landing::Landed;
+use super::physics::{Kinimatics, PhysicsConfig};
+use super::scripting::{ScriptEvent, Value};
+use super::ships::CargoHold;
+use super::surface_base::{self, SurfaceBase};
+
+pub struct MissionsPlugin;
+
+impl Plugin for MissionsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Objectives>()
+            // after the director's commands have gone through, so what it
+            // spawned along with an objective is there to be checked
+            .add_system(objective_system.in_base_set(CoreSet::PostUpdate))
+            .add_system(mission_panel_system)
+            .add_system(mission_summary_system);
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ObjectiveStatus {
+    Active,
+    Complete,
+    Failed,
+}
+
+impl ObjectiveStatus {
+    fn event(self) -> Option<&'static str> {
+        match self {
+            ObjectiveStatus::Active => None,
+            ObjectiveStatus::Complete => Some("objective_complete"),
+            ObjectiveStatus::Failed => Some("objective_failed"),
+        }
+    }
+}
+
+/// What it takes to complete an objective, checked every tick. Objectives
+/// without one are completed and failed by the director, or whatever set them.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Condition {
+    /// Put `ship` on an orbit around `body` with both its periapsis and its
+    /// apoapsis within `tolerance` of `radius`. Fails if either is destroyed.
+    Orbit {
+        ship: Entity,
+        body: Entity,
+        radius: f32,
+        tolerance: f32,
+    },
+    /// Destroy `target`.
+    Destroy { target: Entity },
+    /// Keep `ship` in one piece until the mission elapsed time reaches `until`.
+    Survive { ship: Entity, until: f64 },
+    /// Get `tonnes` more cargo into `depot`'s hold than it had when the
+    /// objective was first checked. Fails if the depot is destroyed.
+    Deliver {
+        depot: Entity,
+        tonnes: f32,
+        baseline: Option<f32>,
+    },
+    /// Land `ship` at the surface base `base`. Fails if either is destroyed.
+    Land { ship: Entity, base: Entity },
+}
+
+#[derive(Clone, Debug)]
+pub struct Objective {
+    pub key: String,
+    pub text: String,
+    pub status: ObjectiveStatus,
+    pub condition: Option<Condition>,
+    /// How far along the condition is, for the objective list.
+    pub progress: String,
+    /// The status last raised as an event.
+    reported: ObjectiveStatus,
+}
+
+impl Objective {
+    pub fn new(key: String, text: String, condition: Option<Condition>) -> Self {
+        Self {
+            key,
+            text,
+            status: ObjectiveStatus::Active,
+            condition,
+            progress: String::new(),
+            reported: ObjectiveStatus::Active,
+        }
+    }
+}
+
+/// Resource which holds the mission's objectives.
+///
+/// The mission is won once every objective is complete, and lost as soon as
+/// any one of them fails.
+#[derive(Resource, Default)]
+pub struct Objectives {
+    pub objectives: Vec<Objective>,
+    /// When the mission was won or lost.
+    pub ended_at: Option<f64>,
+    /// Set once the player has closed the summary.
+    pub dismissed: bool,
+}
+
+impl Objectives {
+    pub fn set_status(&mut self, key: &str, status: ObjectiveStatus) -> Result<(), String> {
+        let objective = self
+            .objectives
+            .iter_mut()
+            .find(|o| o.key == key)
+            .ok_or_else(|| format!("no objective named \"{}\"", key))?;
+        objective.status = status;
+        Ok(())
+    }
+
+    /// Adds an objective, in place of any other with the same key.
+    pub fn add(&mut self, objective: Objective) {
+        self.remove(&objective.key);
+        self.objectives.push(objective);
+    }
+
+    pub fn remove(&mut self, key: &str) {
+        self.objectives.retain(|o| o.key != key);
+    }
+
+    /// `Complete` if the mission's been won, `Failed` if it's been lost, and
+    /// `Active` otherwise.
+    pub fn outcome(&self) -> ObjectiveStatus {
+        let status = |s| self.objectives.iter().any(|o| o.status == s);
+        if status(ObjectiveStatus::Failed) {
+            ObjectiveStatus::Failed
+        } else if self.objectives.is_empty() || status(ObjectiveStatus::Active) {
+            ObjectiveStatus::Active
+        } else {
+            ObjectiveStatus::Complete
+        }
+    }
+}
+
+/// The periapsis and apoapsis of a body at `offset` from one with gravitational
+/// parameter `mu`, moving at `velocity` relative to it, or `None` if it isn't
+/// in a closed orbit.
+fn apsides(offset: Vec3, velocity: Vec3, mu: f32) -> Option<(f32, f32)> {
+    let r = offset.length();
+    let energy = velocity.length_squared() / 2.0 - mu / r;
+    if energy >= 0.0 || r == 0.0 {
+        return None;
+    }
+    let a = -mu / (2.0 * energy);
+    let h = offset.cross(velocity).length();
+    let e = (1.0 + 2.0 * energy * h * h / (mu * mu)).max(0.0).sqrt();
+    Some((a * (1.0 - e), a * (1.0 + e)))
+}
+
+/// :SYSTEM: Checks each objective's condition, raises `objective_complete` or
+/// `objective_failed` with its key whenever an objective changes, and
+/// `mission_won` or `mission_lost` once the mission is over.
+#[allow(clippy::too_many_arguments)]
+pub fn objective_system(
+    mut objectives: ResMut<Objectives>,
+    bodies: Query<(&Kinimatics, &Transform)>,
+    entities: Query<Entity>,
+    holds: Query<&CargoHold>,
+    landed: Query<(&Landed, &Transform)>,
+    bases: Query<(&SurfaceBase, &Transform, &Parent)>,
+    clock: Res<SimulationClock>,
+    mut script_events: EventWriter<ScriptEvent>,
+    physics: Res<PhysicsConfig>,
+) {
+    let objectives = &mut *objectives;
+    for objective in objectives.objectives.iter_mut() {
+        if let (ObjectiveStatus::Active, Some(condition)) =
+            (objective.status, objective.condition.as_mut())
+        {
+            let (status, progress) = match condition {
+                Condition::Orbit {
+                    ship,
+                    body,
+                    radius,
+                    tolerance,
+                } => match (bodies.get(*ship), bodies.get(*body)) {
+                    (Ok((ship_k, ship_t)), Ok((body_k, body_t))) => {
+                        match apsides(
+                            ship_t.translation - body_t.translation,
+                            ship_k.velocity - body_k.velocity,
+                            physics.mu(body_k.mass),
+                        ) {
+                            Some((pe, ap)) => {
+                                let within = |r: f32| (r - *radius).abs() <= *tolerance;
+                                let status = if within(pe) && within(ap) {
+                                    ObjectiveStatus::Complete
+                                } else {
+                                    ObjectiveStatus::Active
+                                };
+                                (status, format!("pe {:.0}, ap {:.0}", pe, ap))
+                            }
+                            None => (ObjectiveStatus::Active, "not in orbit".to_string()),
+                        }
+                    }
+                    // docked ships fly with whatever they're docked to
+                    _ if entities.contains(*ship) && entities.contains(*body) => {
+                        (ObjectiveStatus::Active, String::new())
+                    }
+                    _ => (ObjectiveStatus::Failed, String::new()),
+                },
+                Condition::Destroy { target } => {
+                    if entities.contains(*target) {
+                        (ObjectiveStatus::Active, String::new())
+                    } else {
+                        (ObjectiveStatus::Complete, String::new())
+                    }
+                }
+                Condition::Survive { ship, until } => {
+                    let left = *until - clock.elapsed;
+                    if !entities.contains(*ship) {
+                        (ObjectiveStatus::Failed, String::new())
+                    } else if left <= 0.0 {
+                        (ObjectiveStatus::Complete, String::new())
+                    } else {
+                        let left = left.ceil() as u64;
+                        let progress = format!("{}:{:02} left", left / 60, left % 60);
+                        (ObjectiveStatus::Active, progress)
+                    }
+                }
+                Condition::Deliver {
+                    depot,
+                    tonnes,
+                    baseline,
+                } => match holds.get(*depot) {
+                    Ok(hold) => {
+                        let delivered = hold.cargo - *baseline.get_or_insert(hold.cargo);
+                        let status = if delivered >= *tonnes {
+                            ObjectiveStatus::Complete
+                        } else {
+                            ObjectiveStatus::Active
+                        };
+                        (status, format!("{:.0}/{:.0} t", delivered.max(0.0), tonnes))
+                    }
+                    Err(_) => (ObjectiveStatus::Failed, String::new()),
+                },
+                Condition::Land { ship, base } => match (landed.get(*ship), bases.get(*base)) {
+                    (Ok(ship), Ok(base)) if surface_base::at_base(base, ship) => {
+                        (ObjectiveStatus::Complete, String::new())
+                    }
+                    _ if entities.contains(*ship) && entities.contains(*base) => {
+                        (ObjectiveStatus::Active, String::new())
+                    }
+                    _ => (ObjectiveStatus::Failed, String::new()),
+                },
+            };
+            objective.status = status;
+            objective.progress = progress;
+        }
+
+        if objective.status != objective.reported {
+            objective.reported = objective.status;
+            if let Some(name) = objective.status.event() {
+                script_events.send(ScriptEvent {
+                    name: name.to_string(),
+                    args: vec![Value::Str(objective.key.clone())],
+                });
+            }
+        }
+    }
+
+    if objectives.ended_at.is_none() {
+        let name = match objectives.outcome() {
+            ObjectiveStatus::Active => return,
+            ObjectiveStatus::Complete => "mission_won",
+            ObjectiveStatus::Failed => "mission_lost",
+        };
+        objectives.ended_at = Some(clock.elapsed);
+        script_events.send(ScriptEvent {
+            name: name.to_string(),
+            args: vec![],
+        });
+    }
+}
+
+/// :SYSTEM: Displays the current objectives.
+fn mission_panel_system(mut contexts: EguiContexts, objectives: Res<Objectives>) {
+    if objectives.objectives.is_empty() {
+        return;
+    }
+
+    egui::Window::new("Mission").show(contexts.ctx_mut(), |ui| {
+        for objective in objectives.objectives.iter() {
+            let mark = match objective.status {
+                ObjectiveStatus::Active => "[ ]",
+                ObjectiveStatus::Complete => "[x]",
+                ObjectiveStatus::Failed => "[-]",
+            };
+            if objective.progress.is_empty() {
+                ui.label(format!("{} {}", mark, objective.text));
+            } else {
+                ui.label(format!(
+                    "{} {} ({})",
+                    mark, objective.text, objective.progress
+                ));
+            }
+        }
+    });
+}
+
+/// :SYSTEM: Shows how the mission went once it's over.
+fn mission_summary_system(mut contexts: EguiContexts, mut objectives: ResMut<Objectives>) {
+    let Some(ended_at) = objectives.ended_at else {
+        return;
+    };
+    if objectives.dismissed {
+        return;
+    }
+
+    let title = match objectives.outcome() {
+        ObjectiveStatus::Failed => "Mission failed",
+        _ => "Mission complete",
+    };
+    egui::Window::new(title)
+        .collapsible(false)
+        .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+        .show(contexts.ctx_mut(), |ui| {
+            let seconds = ended_at.floor() as u64;
+            ui.label(format!(
+                "Mission time {}:{:02}:{:02}",
+                seconds / 3600,
+                seconds / 60 % 60,
+                seconds % 60
+            ));
+            ui.separator();
+            for objective in objectives.objectives.iter() {
+                let (mark, color) = match objective.status {
+                    ObjectiveStatus::Active => ("[ ]", egui::Color32::GRAY),
+                    ObjectiveStatus::Complete => ("[x]", egui::Color32::LIGHT_GREEN),
+                    ObjectiveStatus::Failed => ("[-]", egui::Color32::LIGHT_RED),
+                };
+                ui.colored_label(color, format!("{} {}", mark, objective.text));
+            }
+            ui.separator();
+            if ui.button("Keep playing").clicked() {
+                objectives.dismissed = true;
+            }
+        });
+}
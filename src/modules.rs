@@ -0,0 +1,335 @@
+use bevy::prelude::*;
+
+use super::docking::Docked;
+use super::heat::{HeatSource, Radiator, Thermal};
+use super::physics::{Kinimatics, SimulationSet};
+use super::power::{Battery, PowerConsumer, PowerGrid, Reactor, SolarPanel};
+use super::scripting::{ScriptEvent, Value};
+use super::sensors::Sensor;
+use super::ships::{Engine, MissileKind, MissileLauncher};
+use super::stealth::{Emissions, PASSIVE_RANGE, SILENT_THRUST};
+
+pub struct ModulesPlugin;
+
+impl Plugin for ModulesPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(
+            loadout_system
+                .before(super::physics::kinimatics_system)
+                .in_set(SimulationSet),
+        )
+        .add_system(module_integrity_system.in_set(SimulationSet));
+    }
+}
+
+/// :COMPONENT: The bare structure of a modular ship. Everything else the ship
+/// is made of (engines, tanks, weapons, ...) hangs off of it as child
+/// [`Module`] entities.
+#[derive(Reflect, Component, Default, Clone, Copy)]
+#[reflect(Component)]
+pub struct Frame {
+    /// Mass of the frame alone, without any modules.
+    pub dry_mass: f32,
+}
+
+/// :COMPONENT: A part of a ship. Modules are children of the ship's [`Frame`],
+/// and each one also carries a component saying what the module does.
+///
+/// A module is destroyed when its integrity reaches zero.
+#[derive(Reflect, Component, Clone, Copy)]
+#[reflect(Component)]
+pub struct Module {
+    pub mass: f32,
+    pub integrity: f32,
+}
+
+impl Default for Module {
+    fn default() -> Self {
+        Self {
+            mass: 0.0,
+            integrity: Self::MAX_INTEGRITY,
+        }
+    }
+}
+
+impl Module {
+    /// Integrity of an undamaged module.
+    pub const MAX_INTEGRITY: f32 = 100.0;
+
+    pub fn new(mass: f32) -> Self {
+        Self {
+            mass,
+            ..Default::default()
+        }
+    }
+}
+
+/// :COMPONENT: Adds thrust to the ship's [`Engine`].
+#[derive(Reflect, Component, Default, Clone, Copy)]
+#[reflect(Component)]
+pub struct Thruster {
+    pub max_thrust: f32,
+    /// Fuel burned per second at full throttle.
+    pub burn_rate: f32,
+}
+
+/// :COMPONENT: Holds fuel for the ship's [`Engine`]. Losing a tank loses
+/// whatever fuel no longer fits in the tanks that are left.
+#[derive(Reflect, Component, Default, Clone, Copy)]
+#[reflect(Component)]
+pub struct FuelTank {
+    pub capacity: f32,
+}
+
+/// :COMPONENT: A hardpoint for the ship's [`MissileLauncher`], loaded with
+/// one kind of missile. A launcher without any hardpoints, or without full
+/// power, can't fire.
+#[derive(Reflect, Component, Default, Clone, Copy)]
+#[reflect(Component)]
+pub struct WeaponMount {
+    pub ordnance: MissileKind,
+}
+
+/// :COMPONENT: A hardpoint for the ship's [`Laser`](super::beams::Laser).
+/// Each mount adds a beam's worth of damage, heat and power draw.
+#[derive(Reflect, Component, Default, Clone, Copy)]
+#[reflect(Component)]
+pub struct BeamMount;
+
+/// :COMPONENT: Powers the ship's [`Shield`](super::shields::Shield). The
+/// shield only holds while at least one generator is switched on.
+#[derive(Reflect, Component, Default, Clone, Copy)]
+#[reflect(Component)]
+pub struct ShieldGenerator;
+
+/// :COMPONENT: An antenna for the ship's [`Sensor`]. The sensor reaches as
+/// far as the best antenna on the ship.
+#[derive(Reflect, Component, Default, Clone, Copy)]
+#[reflect(Component)]
+pub struct SensorArray {
+    pub range: f32,
+}
+
+/// Adds the standard set of modules to a ship. Altogether they weigh as much
+/// as the old, single piece ship did, and perform the same.
+pub fn standard_loadout(ship: &mut ChildBuilder) {
+    let draw = |draw| PowerConsumer {
+        draw,
+        ..Default::default()
+    };
+
+    ship.spawn((
+        Name::new("Thruster"),
+        Module::new(20.0),
+        Thruster {
+            max_thrust: 1000.0,
+            burn_rate: 10.0,
+        },
+        draw(40.0),
+        HeatSource { output: 300.0 },
+    ));
+    ship.spawn((
+        Name::new("Fuel tank"),
+        Module::new(25.0),
+        FuelTank { capacity: 1000.0 },
+    ));
+    ship.spawn((
+        Name::new("Reactor"),
+        Module::new(10.0),
+        Reactor { output: 100.0 },
+        HeatSource { output: 50.0 },
+    ));
+    ship.spawn((
+        Name::new("Radiator"),
+        Module::new(5.0),
+        Radiator { rating: 0.17 },
+    ));
+    ship.spawn((
+        Name::new("Battery"),
+        Module::new(5.0),
+        Battery {
+            charge: 500.0,
+            capacity: 500.0,
+        },
+    ));
+    ship.spawn((
+        Name::new("Weapon mount"),
+        Module::new(10.0),
+        WeaponMount::default(),
+        draw(20.0),
+    ));
+    ship.spawn((
+        Name::new("Sensor array"),
+        Module::new(5.0),
+        SensorArray { range: 800.0 },
+        draw(30.0),
+    ));
+}
+
+/// Adds a laser mount to a ship. It's fitted on top of the
+/// [`standard_loadout`], so the ship carries its weight, and its draw.
+pub fn laser_loadout(ship: &mut ChildBuilder) {
+    ship.spawn((
+        Name::new("Laser mount"),
+        Module::new(5.0),
+        BeamMount,
+        PowerConsumer::default(),
+    ));
+}
+
+/// Adds a solar panel to a ship, on top of the [`standard_loadout`]. It faces
+/// out of the ship's left side.
+pub fn solar_loadout(ship: &mut ChildBuilder) {
+    ship.spawn((
+        Name::new("Solar panel"),
+        Module::new(3.0),
+        SolarPanel {
+            rated_output: 60.0,
+            facing: std::f32::consts::FRAC_PI_2,
+            ..Default::default()
+        },
+    ));
+}
+
+/// Adds a shield generator to a ship, on top of the [`standard_loadout`].
+pub fn shield_loadout(ship: &mut ChildBuilder) {
+    ship.spawn((
+        Name::new("Shield generator"),
+        Module::new(5.0),
+        ShieldGenerator,
+        PowerConsumer::default(),
+    ));
+}
+
+/// :SYSTEM: Works out what each modular ship is capable of from the modules it
+/// is carrying, so adding, removing, or destroying a module takes effect
+/// straight away.
+///
+/// The ship's mass is its frame, plus its modules, plus any ships docked to it.
+/// Modules which are switched off don't count towards what the ship can do,
+/// and modules which need power only work as well as the ship's
+/// [`PowerGrid`] can supply them. An overheating ship's thrusters are throttled
+/// back, and its weapons are held (see [`Thermal`]). A ship running silent
+/// holds its thrusters back too, and only listens with its sensors (see
+/// [`Emissions`]).
+#[allow(clippy::type_complexity)]
+pub fn loadout_system(
+    mut ships: Query<(
+        &Frame,
+        Option<&Children>,
+        Option<&PowerGrid>,
+        Option<&Thermal>,
+        &mut Kinimatics,
+        Option<&mut Engine>,
+        Option<&mut Sensor>,
+        Option<&mut MissileLauncher>,
+        Option<&Emissions>,
+    )>,
+    modules: Query<(
+        &Module,
+        Option<&Thruster>,
+        Option<&FuelTank>,
+        Option<&WeaponMount>,
+        Option<&SensorArray>,
+        Option<&PowerConsumer>,
+    )>,
+    docked: Query<&Docked>,
+) {
+    for (frame, children, grid, thermal, mut kin, engine, sensor, launcher, emissions) in
+        ships.iter_mut()
+    {
+        let supply = grid.map_or(1.0, |g| g.supply);
+        let derating = thermal.map_or(1.0, |t| t.derating());
+        let (quiet, listening) = match emissions {
+            Some(e) if e.silent => (SILENT_THRUST, PASSIVE_RANGE),
+            _ => (1.0, 1.0),
+        };
+        let mut mass = frame.dry_mass;
+        let (mut max_thrust, mut burn_rate, mut fuel_capacity) = (0.0, 0.0, 0.0);
+        let (mut hardpoints, mut range) = (Vec::new(), 0.0_f32);
+
+        for &child in children.into_iter().flatten() {
+            if let Ok(d) = docked.get(child) {
+                mass += d.mass();
+            }
+
+            let Ok((module, thruster, tank, mount, array, consumer)) = modules.get(child) else {
+                continue;
+            };
+            mass += module.mass;
+
+            // how well the module is working, given the power it's getting
+            let power = match consumer {
+                Some(c) if !c.enabled => continue,
+                Some(_) => supply,
+                None => 1.0,
+            };
+
+            if let Some(t) = thruster {
+                max_thrust += t.max_thrust * power * derating * quiet;
+                burn_rate += t.burn_rate * power * derating * quiet;
+            }
+            if let Some(t) = tank {
+                fuel_capacity += t.capacity;
+            }
+            if let Some(m) = mount.filter(|_| power >= 1.0 && derating >= 1.0) {
+                hardpoints.push(m.ordnance);
+            }
+            if let Some(a) = array {
+                range = range.max(a.range * power * listening);
+            }
+        }
+
+        // only touch components when something changes, so change detection stays useful
+        if kin.mass != mass {
+            kin.mass = mass;
+        }
+
+        if let Some(mut engine) = engine {
+            if engine.max_thrust != max_thrust
+                || engine.burn_rate != burn_rate
+                || engine.fuel_capacity != fuel_capacity
+            {
+                engine.max_thrust = max_thrust;
+                engine.burn_rate = burn_rate;
+                engine.fuel_capacity = fuel_capacity;
+                engine.fuel = engine.fuel.min(fuel_capacity);
+            }
+        }
+
+        if let Some(mut sensor) = sensor {
+            if sensor.range != range {
+                sensor.range = range;
+            }
+        }
+
+        if let Some(mut launcher) = launcher {
+            let mounts = hardpoints.len() as u32;
+            if launcher.mounts != mounts || launcher.hardpoints != hardpoints {
+                launcher.mounts = mounts;
+                launcher.selected = launcher.selected.min(hardpoints.len().saturating_sub(1));
+                launcher.hardpoints = hardpoints;
+            }
+        }
+    }
+}
+
+/// :SYSTEM: Removes modules which have been destroyed, raising a
+/// `module_destroyed` [`ScriptEvent`] with the ship and the module.
+fn module_integrity_system(
+    mut commands: Commands,
+    modules: Query<(Entity, &Module, &Parent), Changed<Module>>,
+    mut script_events: EventWriter<ScriptEvent>,
+) {
+    for (entity, module, ship) in modules.iter() {
+        if module.integrity > 0.0 {
+            continue;
+        }
+
+        commands.entity(entity).despawn_recursive();
+        script_events.send(ScriptEvent {
+            name: "module_destroyed".to_string(),
+            args: vec![Value::from_entity(ship.get()), Value::from_entity(entity)],
+        });
+    }
+}
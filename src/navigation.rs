@@ -0,0 +1,210 @@
+//! Navigation math for ship programs, and anything else which needs to plan a
+//! course. Everything here works from [`BodySnapshot`]s, and except for
+//! [`hohmann`], ignores gravity: the answers are good over short distances,
+//! and get worse the longer the course, so they're best recomputed often.
+
+use bevy::prelude::*;
+
+use super::physics::PhysicsConfig;
+use super::scripting::BodySnapshot;
+use super::ships::Engine;
+
+/// Acceleration of gravity at Earth's surface, which specific impulse is
+/// measured against.
+pub const STANDARD_GRAVITY: f32 = 9.81;
+
+/// The closest two bodies come, if neither changes course.
+#[derive(Clone, Copy, Debug)]
+pub struct Approach {
+    /// Seconds from now. Zero if they are already moving apart.
+    pub time: f32,
+    pub distance: f32,
+}
+
+/// Finds when, and how close, `target` passes by `own`.
+pub fn closest_approach(own: &BodySnapshot, target: &BodySnapshot) -> Approach {
+    let offset = target.position - own.position;
+    let velocity = target.velocity - own.velocity;
+
+    let speed_squared = velocity.length_squared();
+    let time = if speed_squared > 0.0 {
+        (-offset.dot(velocity) / speed_squared).max(0.0)
+    } else {
+        0.0
+    };
+    Approach {
+        time,
+        distance: (offset + velocity * time).length(),
+    }
+}
+
+/// The shape of an orbit around some body.
+#[derive(Clone, Copy, Debug)]
+pub struct Orbit {
+    /// Half the orbit's longest diameter. Negative for escape trajectories.
+    pub semi_major: f32,
+    /// Zero for a circle, between zero and one for an ellipse, and one or more
+    /// for an escape trajectory.
+    pub eccentricity: f32,
+}
+
+/// Works out the orbit `own` is on around `central`, as if nothing else were
+/// pulling on it.
+pub fn orbit(own: &BodySnapshot, central: &BodySnapshot, physics: &PhysicsConfig) -> Orbit {
+    let mu = physics.mu(central.mass);
+    let r = own.position - central.position;
+    let v = own.velocity - central.velocity;
+
+    let energy = v.length_squared() / 2.0 - mu / r.length();
+    let eccentricity = ((v.length_squared() - mu / r.length()) * r - r.dot(v) * v) / mu;
+    Orbit {
+        semi_major: -mu / (2.0 * energy),
+        eccentricity: eccentricity.length(),
+    }
+}
+
+/// Change in velocity `own` needs to arrive where `target` will be in `time`
+/// seconds.
+pub fn intercept_delta_v(
+    own: &BodySnapshot,
+    target: &BodySnapshot,
+    time: f32,
+) -> Result<Vec3, String> {
+    if time <= 0.0 {
+        return Err(format!("can't intercept in {} seconds", time));
+    }
+    let meeting_point = target.position + target.velocity * time;
+    Ok((meeting_point - own.position) / time - own.velocity)
+}
+
+/// A transfer between two circular orbits around the same body, by way of
+/// an ellipse touching both.
+#[derive(Clone, Copy, Debug)]
+pub struct Hohmann {
+    /// Change in speed needed to leave the first orbit.
+    pub departure: f32,
+    /// Change in speed needed to settle into the second orbit.
+    pub arrival: f32,
+    /// Seconds spent on the transfer orbit.
+    pub time: f32,
+}
+
+/// Estimates a transfer from a circular orbit of radius `from` around
+/// `central`, to one of radius `to`. Speeding up is positive, and slowing
+/// down is negative.
+pub fn hohmann(
+    central: &BodySnapshot,
+    from: f32,
+    to: f32,
+    physics: &PhysicsConfig,
+) -> Result<Hohmann, String> {
+    if from <= 0.0 || to <= 0.0 {
+        return Err("orbits need a radius above zero".to_string());
+    }
+    let mu = physics.mu(central.mass);
+    let semi_major = (from + to) / 2.0;
+
+    // vis-viva, at either end of the transfer ellipse
+    let departure = (mu * (2.0 / from - 1.0 / semi_major)).sqrt() - (mu / from).sqrt();
+    let arrival = (mu / to).sqrt() - (mu * (2.0 / to - 1.0 / semi_major)).sqrt();
+    let time = std::f32::consts::PI * (semi_major.powi(3) / mu).sqrt();
+
+    Ok(Hohmann {
+        departure,
+        arrival,
+        time,
+    })
+}
+
+/// Finds the direction to fire something moving at `speed` (relative to
+/// `own`) so that it hits `target`, along with how long it takes to get
+/// there. Returns `None` if `target` is too fast to ever be caught.
+pub fn lead(own: &BodySnapshot, target: &BodySnapshot, speed: f32) -> Option<(Vec3, f32)> {
+    let offset = target.position - own.position;
+    let velocity = target.velocity - own.velocity;
+
+    // |offset + velocity * t| = speed * t
+    let a = velocity.length_squared() - speed * speed;
+    let b = 2.0 * offset.dot(velocity);
+    let c = offset.length_squared();
+
+    let time = if a.abs() < f32::EPSILON {
+        (b < 0.0).then(|| -c / b)?
+    } else {
+        let discriminant = b * b - 4.0 * a * c;
+        if discriminant < 0.0 {
+            return None;
+        }
+        let root = discriminant.sqrt();
+        [(-b - root) / (2.0 * a), (-b + root) / (2.0 * a)]
+            .into_iter()
+            .filter(|t| *t > 0.0)
+            .reduce(f32::min)?
+    };
+
+    Some(((offset + velocity * time).normalize_or_zero(), time))
+}
+
+/// The heading a ship has to turn to, to point its nose along `direction`.
+/// Matches the `heading` a ship program reads.
+pub fn heading_of(direction: Vec3) -> f32 {
+    (-direction.x).atan2(direction.y)
+}
+
+/// What a ship's fuel is worth.
+#[derive(Clone, Copy, Debug)]
+pub struct DeltaV {
+    /// Change in velocity the ship can make with the fuel it has left.
+    pub total: f32,
+    /// Specific impulse of the engine, in seconds.
+    pub isp: f32,
+    /// Seconds it takes to burn all the fuel at full throttle.
+    pub burn_time: f32,
+}
+
+/// Works out the delta-v of a ship of `mass` with `engine`, by the rocket
+/// equation. Fuel is counted in the same units as mass, on top of `mass`.
+pub fn delta_v(engine: &Engine, mass: f32) -> DeltaV {
+    let exhaust = engine.exhaust_velocity();
+    let fuel = engine.fuel.max(0.0);
+    let total = if mass > 0.0 {
+        exhaust * ((mass + fuel) / mass).ln()
+    } else {
+        0.0
+    };
+    let burn_time = if engine.burn_rate > 0.0 {
+        fuel / engine.burn_rate
+    } else {
+        0.0
+    };
+    DeltaV {
+        total,
+        isp: exhaust / STANDARD_GRAVITY,
+        burn_time,
+    }
+}
+
+/// A burn worked out ahead of time.
+#[derive(Clone, Copy, Debug)]
+pub struct Burn {
+    /// Seconds at full throttle.
+    pub time: f32,
+    /// Fuel used up.
+    pub fuel: f32,
+}
+
+/// How long a ship of `mass` has to burn at full throttle, and how much fuel
+/// it uses, to change its velocity by `dv`. The burn can come out needing
+/// more fuel than the ship has.
+pub fn burn(engine: &Engine, mass: f32, dv: f32) -> Result<Burn, String> {
+    let exhaust = engine.exhaust_velocity();
+    if exhaust <= 0.0 {
+        return Err("the engine can't burn".to_string());
+    }
+    let wet = mass + engine.fuel.max(0.0);
+    let fuel = wet * (1.0 - (-dv.abs() / exhaust).exp());
+    Ok(Burn {
+        time: fuel / engine.burn_rate,
+        fuel,
+    })
+}
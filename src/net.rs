@@ -0,0 +1,658 @@
+use std::collections::BTreeMap;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use super::determinism::{Determinism, StableId};
+use super::input_map::{Action, InputMap};
+use super::physics::{Attitude, Kinimatics, SimulationSet};
+use super::ships::{
+    self, Controlled, Engine, Faction, LaunchMissile, Missile, PlayerShip, Ship, ShipSprites,
+    Throttle,
+};
+
+pub struct NetPlugin;
+
+impl Plugin for NetPlugin {
+    fn build(&self, app: &mut App) {
+        let args: Vec<String> = std::env::args().collect();
+        let value = |flag: &str| args.iter().find_map(|a| a.strip_prefix(flag));
+        let players = value("--players=")
+            .and_then(|n| n.parse().ok())
+            .unwrap_or(2);
+        let lockstep = match (value("--host="), value("--join=")) {
+            (Some(address), _) => Lockstep::host(address, players),
+            (_, Some(address)) => Lockstep::join(address),
+            _ => Lockstep::default(),
+        };
+        // lockstep only works if every peer simulates exactly the same thing
+        if lockstep.networked() {
+            app.world.resource_mut::<Determinism>().enabled = true;
+        }
+        app.insert_resource(lockstep)
+            .configure_set(SimulationSet.run_if(inputs_arrived))
+            .add_system(lockstep_io_system.before(SimulationSet))
+            .add_system(
+                lockstep_start_system
+                    .after(lockstep_io_system)
+                    .before(SimulationSet),
+            )
+            .add_system(
+                lockstep_apply_system
+                    .before(super::physics::kinimatics_system)
+                    .before(super::ships::launch_missile_system)
+                    .in_set(SimulationSet),
+            )
+            .add_system(lockstep_panel_system);
+    }
+}
+
+/// Largest message a peer may send, in bytes.
+pub const MAX_MESSAGE: usize = 64 * 1024;
+
+/// Most a slow peer may fall behind on what it's been sent before it's
+/// dropped, in bytes.
+const MAX_BACKLOG: usize = 1024 * 1024;
+
+/// Ticks between a player's input being sampled and it taking effect, to give
+/// it time to reach the other peers.
+const INPUT_DELAY: u64 = 3;
+
+/// Distance between the ships of players the scenario doesn't have a ship for.
+const EXTRA_SHIP_SPACING: f32 = 40.0;
+
+/// Factions are handed out to players from here, so they're all hostile to
+/// each other.
+pub const FIRST_PLAYER_FACTION: u32 = 100;
+
+/// A TCP connection carrying messages of JSON, each prefixed with its length in
+/// bytes as a big endian `u32`. Never blocks; whatever can't be sent yet waits
+/// for the next [`flush`](Self::flush).
+pub struct Connection {
+    stream: TcpStream,
+    incoming: Vec<u8>,
+    outgoing: Vec<u8>,
+    max_message: usize,
+    max_backlog: usize,
+    /// Set once the other end has gone, or broken the protocol.
+    pub closed: bool,
+}
+
+impl Connection {
+    pub fn new(stream: TcpStream) -> std::io::Result<Self> {
+        stream.set_nonblocking(true)?;
+        let _ = stream.set_nodelay(true);
+        Ok(Self {
+            stream,
+            incoming: Vec::new(),
+            outgoing: Vec::new(),
+            max_message: MAX_MESSAGE,
+            max_backlog: MAX_BACKLOG,
+            closed: false,
+        })
+    }
+
+    /// Raises or lowers the largest message which may be received, and how far
+    /// behind on sending the other end may fall, in bytes.
+    pub fn with_limits(mut self, max_message: usize, max_backlog: usize) -> Self {
+        self.max_message = max_message;
+        self.max_backlog = max_backlog;
+        self
+    }
+
+    pub fn send<T: Serialize>(&mut self, message: &T) {
+        let Ok(body) = serde_json::to_vec(message) else {
+            return;
+        };
+        self.outgoing
+            .extend_from_slice(&(body.len() as u32).to_be_bytes());
+        self.outgoing.extend_from_slice(&body);
+        if self.outgoing.len() > self.max_backlog {
+            warn!("net: a connection fell too far behind, dropping it");
+            self.closed = true;
+        }
+    }
+
+    pub fn flush(&mut self) {
+        while !self.outgoing.is_empty() && !self.closed {
+            match self.stream.write(&self.outgoing) {
+                Ok(0) => self.closed = true,
+                Ok(n) => {
+                    self.outgoing.drain(..n);
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(_) => self.closed = true,
+            }
+        }
+    }
+
+    /// Reads whatever has arrived, and takes the whole messages out of it.
+    pub fn receive<T: DeserializeOwned>(&mut self) -> Vec<Result<T, String>> {
+        let mut chunk = [0; 4096];
+        loop {
+            match self.stream.read(&mut chunk) {
+                Ok(0) => {
+                    self.closed = true;
+                    break;
+                }
+                Ok(n) => self.incoming.extend_from_slice(&chunk[..n]),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(_) => {
+                    self.closed = true;
+                    break;
+                }
+            }
+        }
+
+        let mut messages = Vec::new();
+        while self.incoming.len() >= 4 {
+            let mut length = [0; 4];
+            length.copy_from_slice(&self.incoming[..4]);
+            let length = u32::from_be_bytes(length) as usize;
+            if length > self.max_message {
+                warn!(
+                    "net: got a {} byte message, dropping the connection",
+                    length
+                );
+                self.closed = true;
+                break;
+            }
+            if self.incoming.len() < 4 + length {
+                break;
+            }
+            let body: Vec<u8> = self.incoming.drain(..4 + length).skip(4).collect();
+            messages.push(serde_json::from_slice(&body).map_err(|e| e.to_string()));
+        }
+        messages
+    }
+}
+
+/// What a player did with the controls during one tick.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub struct TickInput {
+    pub thrust: bool,
+    /// -1 turns right, 1 turns left.
+    pub rotate: i8,
+    /// -1 swivels the engine right, 1 left.
+    pub gimbal: i8,
+    /// Fires a missile at the nearest hostile ship.
+    pub fire: bool,
+}
+
+impl TickInput {
+    pub fn sample(map: &InputMap, input: &Input<KeyCode>) -> Self {
+        let axis = |positive, negative| {
+            map.pressed(positive, input) as i8 - map.pressed(negative, input) as i8
+        };
+        Self {
+            thrust: map.pressed(Action::ThrustUp, input) && !map.pressed(Action::ThrustCut, input),
+            rotate: axis(Action::RotateLeft, Action::RotateRight),
+            gimbal: axis(Action::GimbalLeft, Action::GimbalRight),
+            fire: map.just_pressed(Action::FireMissile, input),
+        }
+    }
+
+    /// Turns the ship and sets its engine, for the next tick. Firing is left to
+    /// the caller.
+    pub fn steer(&self, attitude: &mut Attitude, engine: &mut Engine) {
+        engine.throttle = Throttle::Fixed(self.thrust);
+        engine.gimbal = engine.gimbal_limit * self.gimbal as f32;
+        attitude.command(std::f32::consts::PI * self.rotate as f32);
+    }
+}
+
+/// The nearest of `targets` hostile to a ship of `faction` at `at`, other than
+/// the ship itself. Ties go to whichever comes first.
+pub fn nearest_hostile(
+    targets: &[(Entity, Vec3, Faction)],
+    ship: Entity,
+    at: Vec3,
+    faction: Faction,
+) -> Option<Entity> {
+    targets
+        .iter()
+        .filter(|(e, _, f)| *e != ship && faction.is_hostile_to(f))
+        .min_by(|(_, a, _), (_, b, _)| a.distance_squared(at).total_cmp(&b.distance_squared(at)))
+        .map(|(e, ..)| *e)
+}
+
+/// Messages between the host and its clients.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum NetMessage {
+    /// A client asking to play.
+    Join,
+    /// The host telling a client which player it is.
+    Welcome { player: usize, players: usize },
+    /// Everyone's here; the first tick can run.
+    Start,
+    /// A player's input for a tick. The host passes each client's on to all
+    /// the others.
+    Input {
+        tick: u64,
+        player: usize,
+        input: TickInput,
+    },
+}
+
+#[derive(Default)]
+enum Role {
+    /// Playing alone.
+    #[default]
+    Off,
+    Host {
+        listener: TcpListener,
+        /// Each client, and the player it is.
+        clients: Vec<(Connection, usize)>,
+    },
+    Client {
+        host: Connection,
+    },
+}
+
+/// :COMPONENT: Marks the ship flown by one of the players in a lockstep game.
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct NetPlayer(pub usize);
+
+/// Resource which runs a lockstep multiplayer game. One player hosts with
+/// `--host=ADDRESS --players=N`, and the others join with `--join=ADDRESS`,
+/// all playing the same mission.
+///
+/// Nothing but the players' inputs goes over the network. Every peer runs the
+/// same deterministic simulation, and only moves it forward a tick once it has
+/// every player's input for that tick, so they all stay in step. Inputs are
+/// sampled a few ticks ahead of when they take effect, to hide the time they
+/// take to arrive.
+///
+/// Each player flies one of the player's ships, in [`StableId`] order. If the
+/// mission doesn't have enough, more are spawned alongside the first.
+#[derive(Resource, Default)]
+pub struct Lockstep {
+    role: Role,
+    /// Which player this peer is.
+    pub local: usize,
+    pub players: usize,
+    pub started: bool,
+    /// Set once the players' ships have been handed out.
+    assigned: bool,
+    /// The next tick to simulate.
+    pub tick: u64,
+    /// The next tick to sample the local player's input for.
+    next_input: u64,
+    inputs: BTreeMap<u64, Vec<Option<TickInput>>>,
+    status: String,
+}
+
+impl Lockstep {
+    fn host(address: &str, players: usize) -> Self {
+        let listener = TcpListener::bind(address).and_then(|l| l.set_nonblocking(true).map(|_| l));
+        match listener {
+            Ok(listener) => {
+                info!("net: hosting {} players on {}", players, address);
+                Self {
+                    role: Role::Host {
+                        listener,
+                        clients: Vec::new(),
+                    },
+                    players: players.max(1),
+                    status: format!("waiting for players on {}", address),
+                    ..Default::default()
+                }
+            }
+            Err(e) => {
+                error!("net: couldn't host on {}: {}", address, e);
+                Self::default()
+            }
+        }
+    }
+
+    fn join(address: &str) -> Self {
+        match TcpStream::connect(address).and_then(Connection::new) {
+            Ok(mut host) => {
+                info!("net: joining {}", address);
+                host.send(&NetMessage::Join);
+                Self {
+                    role: Role::Client { host },
+                    status: format!("joining {}", address),
+                    ..Default::default()
+                }
+            }
+            Err(e) => {
+                error!("net: couldn't join {}: {}", address, e);
+                Self::default()
+            }
+        }
+    }
+
+    pub fn networked(&self) -> bool {
+        !matches!(self.role, Role::Off)
+    }
+
+    fn record(&mut self, tick: u64, player: usize, input: TickInput) {
+        let players = self.players;
+        let slots = self
+            .inputs
+            .entry(tick)
+            .or_insert_with(|| vec![None; players]);
+        if let Some(slot) = slots.get_mut(player) {
+            *slot = Some(input);
+        }
+    }
+
+    fn begin(&mut self) {
+        self.started = true;
+        // nobody has sent anything for the first few ticks
+        for tick in 0..INPUT_DELAY {
+            for player in 0..self.players {
+                self.record(tick, player, TickInput::default());
+            }
+        }
+        self.next_input = INPUT_DELAY;
+        self.status.clear();
+        info!("net: starting as player {} of {}", self.local, self.players);
+    }
+
+    /// Gives up on the game, and carries on alone.
+    fn abandon(&mut self, reason: &str) {
+        error!("net: {}", reason);
+        self.role = Role::Off;
+        self.status = reason.to_string();
+    }
+}
+
+fn inputs_arrived(lockstep: Res<Lockstep>) -> bool {
+    if !lockstep.networked() {
+        return true;
+    }
+    lockstep.started
+        && lockstep
+            .inputs
+            .get(&lockstep.tick)
+            .is_some_and(|inputs| inputs.iter().all(Option::is_some))
+}
+
+/// :SYSTEM: Lets players join, then samples the local player's input and swaps
+/// inputs with the other peers.
+fn lockstep_io_system(
+    mut lockstep: ResMut<Lockstep>,
+    input: Res<Input<KeyCode>>,
+    map: Res<InputMap>,
+) {
+    let lockstep = &mut *lockstep;
+
+    let mut received = Vec::new();
+    let mut lost = None;
+    let mut start = false;
+    match &mut lockstep.role {
+        Role::Off => return,
+        Role::Host { listener, clients } => {
+            while clients.len() + 1 < lockstep.players {
+                match listener.accept() {
+                    Ok((stream, address)) => {
+                        let Ok(connection) = Connection::new(stream) else {
+                            continue;
+                        };
+                        let player = clients.len() + 1;
+                        info!("net: player {} joined from {}", player, address);
+                        clients.push((connection, player));
+                    }
+                    Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                    Err(e) => {
+                        warn!("net: couldn't accept a player: {}", e);
+                        break;
+                    }
+                }
+            }
+
+            for (connection, player) in clients.iter_mut() {
+                for message in connection.receive::<NetMessage>() {
+                    match message {
+                        Ok(NetMessage::Join) => connection.send(&NetMessage::Welcome {
+                            player: *player,
+                            players: lockstep.players,
+                        }),
+                        // a client only speaks for its own player
+                        Ok(NetMessage::Input { tick, input, .. }) => {
+                            received.push((tick, *player, input))
+                        }
+                        Ok(_) => {}
+                        Err(e) => warn!("net: player {} sent nonsense: {}", player, e),
+                    }
+                }
+                if connection.closed {
+                    lost = Some(format!("player {} left", player));
+                }
+            }
+
+            if !lockstep.started && clients.len() + 1 == lockstep.players {
+                for (connection, _) in clients.iter_mut() {
+                    connection.send(&NetMessage::Start);
+                }
+                start = true;
+            }
+        }
+        Role::Client { host } => {
+            for message in host.receive::<NetMessage>() {
+                match message {
+                    Ok(NetMessage::Welcome { player, players }) => {
+                        lockstep.local = player;
+                        lockstep.players = players;
+                        lockstep.status =
+                            format!("joined as player {}, waiting for the others", player + 1);
+                    }
+                    Ok(NetMessage::Start) => start = true,
+                    Ok(NetMessage::Input {
+                        tick,
+                        player,
+                        input,
+                    }) => received.push((tick, player, input)),
+                    Ok(NetMessage::Join) => {}
+                    Err(e) => warn!("net: the host sent nonsense: {}", e),
+                }
+            }
+            if host.closed {
+                lost = Some("lost the host".to_string());
+            }
+        }
+    }
+
+    if start {
+        lockstep.begin();
+    }
+
+    // only one tick of input a frame, so input keeps pace with the simulation
+    if lockstep.started && lockstep.next_input <= lockstep.tick + INPUT_DELAY {
+        let tick = lockstep.next_input;
+        let local = lockstep.local;
+        let sampled = TickInput::sample(&map, &input);
+        lockstep.next_input += 1;
+        received.push((tick, local, sampled));
+        let message = NetMessage::Input {
+            tick,
+            player: local,
+            input: sampled,
+        };
+        match &mut lockstep.role {
+            Role::Host { clients, .. } => {
+                for (connection, _) in clients.iter_mut() {
+                    connection.send(&message);
+                }
+            }
+            Role::Client { host } => host.send(&message),
+            Role::Off => {}
+        }
+    }
+
+    for &(tick, player, input) in received.iter() {
+        if tick < lockstep.tick {
+            continue;
+        }
+        lockstep.record(tick, player, input);
+    }
+
+    match &mut lockstep.role {
+        Role::Host { clients, .. } => {
+            // pass each client's input on to everyone else
+            for &(tick, player, input) in received.iter().filter(|(_, p, _)| *p != 0) {
+                for (connection, other) in clients.iter_mut() {
+                    if *other != player {
+                        connection.send(&NetMessage::Input {
+                            tick,
+                            player,
+                            input,
+                        });
+                    }
+                }
+            }
+            for (connection, _) in clients.iter_mut() {
+                connection.flush();
+            }
+        }
+        Role::Client { host } => host.flush(),
+        Role::Off => {}
+    }
+
+    if let Some(reason) = lost {
+        lockstep.abandon(&reason);
+    }
+}
+
+/// :SYSTEM: Once the game starts, hands each player one of the player's ships,
+/// spawning more if there aren't enough, and gives the local player the
+/// controls of theirs.
+#[allow(clippy::type_complexity)]
+fn lockstep_start_system(
+    mut commands: Commands,
+    mut lockstep: ResMut<Lockstep>,
+    ships: Query<(Entity, &StableId, &Transform, &Kinimatics), With<PlayerShip>>,
+    controlled: Query<Entity, With<Controlled>>,
+    sprites: Res<ShipSprites>,
+) {
+    if !lockstep.started || lockstep.assigned {
+        return;
+    }
+    lockstep.assigned = true;
+
+    let mut ships: Vec<_> = ships.iter().collect();
+    ships.sort_by_key(|(_, id, ..)| **id);
+    let (origin, velocity) = ships
+        .first()
+        .map_or((Vec3::ZERO, Vec3::ZERO), |(_, _, t, k)| {
+            (t.translation, k.velocity)
+        });
+    let mut ships: Vec<Entity> = ships.into_iter().map(|(e, ..)| e).collect();
+    while ships.len() < lockstep.players {
+        let offset = Vec3::X * EXTRA_SHIP_SPACING * ships.len() as f32;
+        let ship = ships::spawn_ship(&mut commands, &sprites, origin + offset, velocity);
+        commands
+            .entity(ship)
+            .insert((PlayerShip, ships::MissileLauncher::default()));
+        ships.push(ship);
+    }
+
+    for entity in controlled.iter() {
+        commands.entity(entity).remove::<Controlled>();
+    }
+    for (player, ship) in ships.into_iter().take(lockstep.players).enumerate() {
+        commands.entity(ship).insert((
+            NetPlayer(player),
+            Faction(FIRST_PLAYER_FACTION + player as u32),
+        ));
+        if player == lockstep.local {
+            commands.entity(ship).insert(Controlled);
+        }
+    }
+}
+
+/// :SYSTEM: Flies every player's ship with their input for this tick, then
+/// moves on to the next.
+#[allow(clippy::type_complexity)]
+fn lockstep_apply_system(
+    mut lockstep: ResMut<Lockstep>,
+    mut ships: ParamSet<(
+        Query<
+            (Entity, &Transform, Option<&Faction>, Option<&StableId>),
+            (With<Ship>, Without<Missile>),
+        >,
+        Query<(
+            Entity,
+            &NetPlayer,
+            &Transform,
+            &mut Attitude,
+            &mut Engine,
+            &Faction,
+        )>,
+    )>,
+    mut launches: EventWriter<LaunchMissile>,
+) {
+    if !lockstep.networked() {
+        return;
+    }
+    let tick = lockstep.tick;
+    let Some(inputs) = lockstep.inputs.remove(&tick) else {
+        return;
+    };
+    lockstep.tick += 1;
+
+    // sorted, so ties for the nearest target go the same way on every peer
+    let mut targets: Vec<_> = ships
+        .p0()
+        .iter()
+        .map(|(e, t, f, id)| {
+            (
+                id.copied(),
+                (e, t.translation, f.copied().unwrap_or_default()),
+            )
+        })
+        .collect();
+    targets.sort_by_key(|(id, _)| *id);
+    let targets: Vec<_> = targets.into_iter().map(|(_, target)| target).collect();
+
+    for (ship, player, transform, mut attitude, mut engine, faction) in ships.p1().iter_mut() {
+        let Some(Some(input)) = inputs.get(player.0) else {
+            continue;
+        };
+        input.steer(&mut attitude, &mut engine);
+        if input.fire {
+            if let Some(target) = nearest_hostile(&targets, ship, transform.translation, *faction) {
+                launches.send(LaunchMissile {
+                    shooter: ship,
+                    target,
+                });
+            }
+        }
+    }
+}
+
+/// :SYSTEM: Shows which player this is, and whether the game is waiting on
+/// anyone.
+fn lockstep_panel_system(mut contexts: EguiContexts, lockstep: Res<Lockstep>) {
+    if !lockstep.networked() && lockstep.status.is_empty() {
+        return;
+    }
+    egui::Area::new("lockstep")
+        .anchor(egui::Align2::CENTER_TOP, [0.0, 8.0])
+        .show(contexts.ctx_mut(), |ui| {
+            if lockstep.started && lockstep.networked() {
+                ui.label(format!(
+                    "LOCKSTEP player {} of {}, tick {}",
+                    lockstep.local + 1,
+                    lockstep.players,
+                    lockstep.tick
+                ));
+                let waiting = lockstep
+                    .inputs
+                    .get(&lockstep.tick)
+                    .is_none_or(|inputs| inputs.iter().any(Option::is_none));
+                if waiting {
+                    ui.colored_label(egui::Color32::YELLOW, "waiting for the other players");
+                }
+            }
+            if !lockstep.status.is_empty() {
+                ui.label(&lockstep.status);
+            }
+        });
+}
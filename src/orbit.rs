@@ -0,0 +1,86 @@
+use bevy::prelude::*;
+
+/// Newton iterations allowed when solving Kepler's equation.
+const MAX_ITERATIONS: usize = 50;
+
+/// Moves a body along its Kepler orbit around a point mass with gravitational
+/// parameter `mu`, `dt` seconds on. `position` and `velocity` are relative to
+/// the central body, and so is the result.
+///
+/// Works for every kind of orbit, closed or not, using universal variables
+/// (see Curtis, *Orbital Mechanics for Engineering Students*, chapter 3). If
+/// the solution doesn't converge, the body carries on in a straight line.
+pub fn propagate_kepler(position: Vec3, velocity: Vec3, mu: f32, dt: f32) -> (Vec3, Vec3) {
+    let straight = (position + velocity * dt, velocity);
+    if mu <= 0.0 || dt == 0.0 || position.length_squared() == 0.0 {
+        return straight;
+    }
+
+    // the sums here lose too much in single precision
+    let (r0, v0) = (position.as_dvec3(), velocity.as_dvec3());
+    let (mu, dt) = (mu as f64, dt as f64);
+    let sqrt_mu = mu.sqrt();
+    let r0_len = r0.length();
+    let vr0 = r0.dot(v0) / r0_len;
+    // reciprocal of the semimajor axis
+    let alpha = 2.0 / r0_len - v0.length_squared() / mu;
+
+    // solve the universal Kepler equation for the universal anomaly
+    let mut chi = sqrt_mu * alpha.abs() * dt;
+    let mut converged = false;
+    for _ in 0..MAX_ITERATIONS {
+        let z = alpha * chi * chi;
+        let (c, s) = stumpff(z);
+        let f = r0_len * vr0 / sqrt_mu * chi * chi * c
+            + (1.0 - alpha * r0_len) * chi.powi(3) * s
+            + r0_len * chi
+            - sqrt_mu * dt;
+        let df = r0_len * vr0 / sqrt_mu * chi * (1.0 - z * s)
+            + (1.0 - alpha * r0_len) * chi * chi * c
+            + r0_len;
+        let step = f / df;
+        chi -= step;
+        if !chi.is_finite() {
+            break;
+        }
+        if step.abs() < 1e-8 * chi.abs().max(1.0) {
+            converged = true;
+            break;
+        }
+    }
+    if !converged {
+        return straight;
+    }
+
+    // Lagrange coefficients
+    let z = alpha * chi * chi;
+    let (c, s) = stumpff(z);
+    let f = 1.0 - chi * chi / r0_len * c;
+    let g = dt - chi.powi(3) * s / sqrt_mu;
+    let r = r0 * f + v0 * g;
+    let r_len = r.length();
+    let df = sqrt_mu / (r_len * r0_len) * (z * chi * s - chi);
+    let dg = 1.0 - chi * chi / r_len * c;
+    let v = r0 * df + v0 * dg;
+
+    if !r.is_finite() || !v.is_finite() {
+        return straight;
+    }
+    (r.as_vec3(), v.as_vec3())
+}
+
+/// The Stumpff functions C(z) and S(z).
+fn stumpff(z: f64) -> (f64, f64) {
+    if z > 1e-6 {
+        let root = z.sqrt();
+        ((1.0 - root.cos()) / z, (root - root.sin()) / root.powi(3))
+    } else if z < -1e-6 {
+        let root = (-z).sqrt();
+        (
+            (root.cosh() - 1.0) / -z,
+            (root.sinh() - root) / root.powi(3),
+        )
+    } else {
+        (0.5, 1.0 / 6.0)
+    }
+}
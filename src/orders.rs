@@ -0,0 +1,525 @@
+use std::collections::VecDeque;
+
+use bevy::{prelude::*, window::PrimaryWindow};
+use bevy_egui::{egui, EguiContexts};
+
+use super::autopilot::{Autopilot, Goal};
+use super::control_groups::ControlGroups;
+use super::docking::{Docked, DockingPort, DockingRequest};
+use super::level::AstroObject;
+use super::physics::{Attitude, Kinimatics, PhysicsConfig, SimulationSet};
+use super::ships::{LaunchMissile, MissileLauncher, Ship};
+use super::station::Station;
+use super::user_interface::MapIcon;
+
+pub struct OrdersPlugin;
+
+impl Plugin for OrdersPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<OrdersPanel>()
+            .add_system(
+                order_system
+                    .after(super::ai::ai_control_system)
+                    .before(super::autopilot::autopilot_system)
+                    .in_set(SimulationSet),
+            )
+            .add_system(orders_panel_system)
+            .add_system(order_marker_system.after(orders_panel_system));
+    }
+}
+
+/// How close a ship has to get to where it was ordered to move.
+const ARRIVAL_RADIUS: f32 = 15.0;
+
+/// Distance from its target at which a ship on an attack order holds and
+/// fires.
+const ATTACK_RANGE: f32 = 400.0;
+
+/// Something a ship has been told to do.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Order {
+    /// Fly to a point and stop there.
+    MoveTo(Vec3),
+    /// Hold a circular orbit around a body.
+    Orbit { body: Entity, radius: f32 },
+    /// Close to missile range of a ship, and fire on it until it's gone.
+    Attack(Entity),
+    /// Dock with a ship.
+    Dock(Entity),
+    /// Keep station `distance` behind a ship.
+    Follow { target: Entity, distance: f32 },
+    /// Catch up with a ship or body, and match its velocity. Bodies are met
+    /// just off their surface.
+    Intercept(Entity),
+}
+
+impl Order {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Order::MoveTo(_) => "move",
+            Order::Orbit { .. } => "orbit",
+            Order::Attack(_) => "attack",
+            Order::Dock(_) => "dock",
+            Order::Follow { .. } => "follow",
+            Order::Intercept(_) => "intercept",
+        }
+    }
+}
+
+/// :COMPONENT: Orders for a ship to carry out, one after another, flying with
+/// its [`Autopilot`].
+///
+/// Move, attack, dock and intercept orders are done once they're carried out. Orbit and
+/// follow orders go on until something else is queued behind them, and the
+/// ship has got where it was going.
+#[derive(Component, Clone, Debug, Default)]
+pub struct OrderQueue {
+    pub orders: VecDeque<Order>,
+}
+
+impl OrderQueue {
+    /// Adds `order` to the end of the queue, or with `replace`, in place of it.
+    pub fn issue(&mut self, order: Order, replace: bool) {
+        if replace {
+            self.orders.clear();
+        }
+        self.orders.push_back(order);
+    }
+}
+
+/// :SYSTEM: Carries out the first order in each ship's queue.
+#[allow(clippy::type_complexity)]
+fn order_system(
+    mut ships: Query<(
+        Entity,
+        &mut OrderQueue,
+        &mut Autopilot,
+        &Transform,
+        &mut Attitude,
+        Option<&Kinimatics>,
+        Option<&Docked>,
+        Option<&MissileLauncher>,
+    )>,
+    targets: Query<(
+        &GlobalTransform,
+        &Kinimatics,
+        Option<&DockingPort>,
+        Option<&AstroObject>,
+    )>,
+    mut launches: EventWriter<LaunchMissile>,
+    mut docking: EventWriter<DockingRequest>,
+    physics: Res<PhysicsConfig>,
+    time: Res<Time>,
+) {
+    for (entity, mut queue, mut autopilot, transform, mut attitude, kin, docked, launcher) in
+        ships.iter_mut()
+    {
+        let Some(order) = queue.orders.front().copied() else {
+            continue;
+        };
+        let standing = queue.orders.len() > 1;
+        let position = transform.translation;
+
+        let target = |target: Entity| {
+            targets
+                .get(target)
+                .ok()
+                .map(|(t, k, port, body)| (t.translation(), k, port, body))
+        };
+
+        let done = match order {
+            Order::MoveTo(point) => {
+                autopilot.goal = Some(Goal {
+                    position: point,
+                    velocity: Vec3::ZERO,
+                });
+                let slow = kin.is_some_and(|k| k.velocity.length() < 1.0);
+                position.distance(point) < ARRIVAL_RADIUS && slow
+            }
+            Order::Orbit { body, radius } => match target(body) {
+                Some((center, body_kin, ..)) => {
+                    let out = (position - center).try_normalize().unwrap_or(Vec3::X);
+                    let speed = (physics.mu(body_kin.mass) / radius).sqrt();
+                    let slot = center + out * radius;
+                    autopilot.goal = Some(Goal {
+                        position: slot,
+                        velocity: body_kin.velocity + Vec3::new(-out.y, out.x, 0.0) * speed,
+                    });
+                    standing && position.distance(slot) < ARRIVAL_RADIUS
+                }
+                None => true,
+            },
+            Order::Attack(ship) => match target(ship) {
+                Some((at, ship_kin, ..)) => {
+                    let offset = position - at;
+                    autopilot.goal = Some(Goal {
+                        position: at
+                            + offset.normalize_or_zero() * offset.length().min(ATTACK_RANGE),
+                        velocity: ship_kin.velocity,
+                    });
+                    if offset.length() <= ATTACK_RANGE && launcher.is_some_and(|l| l.ready()) {
+                        launches.send(LaunchMissile {
+                            shooter: entity,
+                            target: ship,
+                        });
+                    }
+                    false
+                }
+                None => true,
+            },
+            Order::Dock(ship) => {
+                if docked.is_some_and(|d| d.to == ship) {
+                    true
+                } else if let (Some((at, ship_kin, Some(port), _)), Some(kin)) = (target(ship), kin)
+                {
+                    let goal = Goal {
+                        position: at,
+                        velocity: ship_kin.velocity,
+                    };
+                    let dt = time.delta_seconds();
+                    if autopilot.approach_for_docking(
+                        transform,
+                        &mut attitude,
+                        kin.velocity,
+                        goal,
+                        port,
+                        dt,
+                    ) {
+                        docking.send(DockingRequest::Dock {
+                            ship: entity,
+                            target: ship,
+                        });
+                    }
+                    false
+                } else {
+                    // the target's gone or can't be docked with, or this ship
+                    // is docked to something else
+                    true
+                }
+            }
+            Order::Follow {
+                target: ship,
+                distance,
+            } => match target(ship) {
+                Some((at, ship_kin, ..)) => {
+                    let behind = (-ship_kin.velocity)
+                        .try_normalize()
+                        .unwrap_or((position - at).normalize_or_zero());
+                    let slot = at + behind * distance;
+                    autopilot.goal = Some(Goal {
+                        position: slot,
+                        velocity: ship_kin.velocity,
+                    });
+                    standing && position.distance(slot) < ARRIVAL_RADIUS
+                }
+                None => true,
+            },
+            Order::Intercept(other) => match target(other) {
+                Some((at, other_kin, _, body)) => {
+                    let standoff = body.map_or(0.0, |b| b.radius) + ARRIVAL_RADIUS;
+                    let slot = at + (position - at).normalize_or_zero() * standoff;
+                    autopilot.goal = Some(Goal {
+                        position: slot,
+                        velocity: other_kin.velocity,
+                    });
+                    let matched =
+                        kin.is_some_and(|k| (k.velocity - other_kin.velocity).length() < 1.0);
+                    position.distance(slot) < ARRIVAL_RADIUS && matched
+                }
+                None => true,
+            },
+        };
+
+        if done {
+            queue.orders.pop_front();
+            if queue.orders.is_empty() {
+                autopilot.goal = None;
+            }
+        }
+    }
+}
+
+/// Resource which holds the state of the orders panel. F7 opens and closes it.
+#[derive(Resource)]
+pub struct OrdersPanel {
+    pub open: bool,
+    /// Ships the next order goes to.
+    pub selected: Vec<Entity>,
+    target: Option<Entity>,
+    /// Radius for orbit orders, or distance for follow orders.
+    pub distance: f32,
+}
+
+impl Default for OrdersPanel {
+    fn default() -> Self {
+        Self {
+            open: false,
+            selected: Vec::new(),
+            target: None,
+            distance: 150.0,
+        }
+    }
+}
+
+/// :SYSTEM: Shows the orders panel, for picking ships and giving them orders.
+///
+/// Orders replace whatever the selected ships were doing, unless shift is
+/// held, in which case they're queued up behind it. Ctrl right clicking on the
+/// map orders the selected ships to move there.
+#[allow(clippy::type_complexity, clippy::too_many_arguments)]
+pub fn orders_panel_system(
+    mut commands: Commands,
+    mut contexts: EguiContexts,
+    mut panel: ResMut<OrdersPanel>,
+    ships: Query<
+        (
+            Entity,
+            Option<&Name>,
+            Option<&OrderQueue>,
+            Option<&ControlGroups>,
+        ),
+        With<Ship>,
+    >,
+    bodies: Query<(Entity, Option<&Name>), With<AstroObject>>,
+    stations: Query<(Entity, Option<&Name>), With<Station>>,
+    mut queues: Query<&mut OrderQueue>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform)>,
+    keys: Res<Input<KeyCode>>,
+    mouse: Res<Input<MouseButton>>,
+) {
+    if keys.just_pressed(KeyCode::F7) {
+        panel.open = !panel.open;
+    }
+    if !panel.open {
+        return;
+    }
+    let panel = &mut *panel;
+    panel.selected.retain(|e| ships.contains(*e));
+    let replace = !keys.any_pressed([KeyCode::LShift, KeyCode::RShift]);
+
+    let mut issued = Vec::new();
+
+    let ctrl = keys.any_pressed([KeyCode::LControl, KeyCode::RControl]);
+    if ctrl && mouse.just_pressed(MouseButton::Right) && !contexts.ctx_mut().wants_pointer_input() {
+        let point = windows
+            .get_single()
+            .ok()
+            .zip(cameras.get_single().ok())
+            .and_then(|(window, (camera, transform))| {
+                let cursor = window.cursor_position()?;
+                camera.viewport_to_world(transform, cursor)
+            });
+        if let Some(ray) = point {
+            issued.push(Order::MoveTo(ray.origin.truncate().extend(0.0)));
+        }
+    }
+
+    let label = |entity: Entity, name: Option<&Name>| match name {
+        Some(name) => format!("{} ({})", name, entity.index()),
+        None => format!(
+            "{} {}",
+            if bodies.contains(entity) {
+                "body"
+            } else {
+                "ship"
+            },
+            entity.index()
+        ),
+    };
+
+    let mut open = true;
+    egui::Window::new("Orders")
+        .open(&mut open)
+        .show(contexts.ctx_mut(), |ui| {
+            egui::ScrollArea::vertical()
+                .max_height(200.0)
+                .show(ui, |ui| {
+                    for (entity, name, queue, groups) in ships.iter() {
+                        let mut selected = panel.selected.contains(&entity);
+                        let mut text = label(entity, name);
+                        for group in groups.iter().flat_map(|g| g.iter()) {
+                            text += &format!(" [{}]", group);
+                        }
+                        if let Some(order) = queue.and_then(|q| q.orders.front()) {
+                            text += &format!(", {}", order.name());
+                        }
+                        if ui.checkbox(&mut selected, text).changed() {
+                            if selected {
+                                panel.selected.push(entity);
+                            } else {
+                                panel.selected.retain(|e| *e != entity);
+                            }
+                        }
+                    }
+                });
+            ui.separator();
+
+            let target_text = panel.target.map_or("none".to_string(), |t| {
+                let name = ships
+                    .get(t)
+                    .ok()
+                    .and_then(|(_, n, ..)| n)
+                    .or(bodies.get(t).ok().and_then(|(_, n)| n))
+                    .or(stations.get(t).ok().and_then(|(_, n)| n));
+                label(t, name)
+            });
+            egui::ComboBox::from_label("target")
+                .selected_text(target_text)
+                .show_ui(ui, |ui| {
+                    for (entity, name) in bodies.iter() {
+                        ui.selectable_value(&mut panel.target, Some(entity), label(entity, name));
+                    }
+                    for (entity, name) in stations.iter() {
+                        ui.selectable_value(&mut panel.target, Some(entity), label(entity, name));
+                    }
+                    for (entity, name, ..) in ships.iter() {
+                        ui.selectable_value(&mut panel.target, Some(entity), label(entity, name));
+                    }
+                });
+            ui.add(
+                egui::DragValue::new(&mut panel.distance)
+                    .clamp_range(10.0..=2000.0)
+                    .prefix("distance "),
+            );
+
+            let target = panel.target;
+            let is_body = target.is_some_and(|t| bodies.contains(t));
+            let is_ship = target.is_some_and(|t| ships.contains(t));
+            let is_station = target.is_some_and(|t| stations.contains(t));
+            ui.horizontal(|ui| {
+                if ui
+                    .add_enabled(is_body, egui::Button::new("Orbit"))
+                    .clicked()
+                {
+                    issued.push(Order::Orbit {
+                        body: target.unwrap(),
+                        radius: panel.distance,
+                    });
+                }
+                if ui
+                    .add_enabled(is_ship, egui::Button::new("Attack"))
+                    .clicked()
+                {
+                    issued.push(Order::Attack(target.unwrap()));
+                }
+                if ui
+                    .add_enabled(is_ship || is_station, egui::Button::new("Dock"))
+                    .clicked()
+                {
+                    issued.push(Order::Dock(target.unwrap()));
+                }
+                if ui
+                    .add_enabled(is_ship, egui::Button::new("Follow"))
+                    .clicked()
+                {
+                    issued.push(Order::Follow {
+                        target: target.unwrap(),
+                        distance: panel.distance,
+                    });
+                }
+                if ui.button("Clear").clicked() {
+                    for ship in panel.selected.iter() {
+                        if let Ok(mut queue) = queues.get_mut(*ship) {
+                            queue.orders.clear();
+                        }
+                    }
+                }
+            });
+            ui.label("Ctrl + right click: move to. Hold shift to queue.");
+            ui.label("Ctrl + number: make group. Shift + number: add to group.");
+        });
+    panel.open = open;
+
+    for order in issued {
+        for ship in panel.selected.iter().copied() {
+            give_order(&mut commands, &mut queues, ship, order, replace);
+        }
+    }
+}
+
+/// Gives `ship` an order, as [`OrderQueue::issue`] does, giving it a queue and
+/// an [`Autopilot`] to carry it out if it doesn't have them yet.
+pub fn give_order(
+    commands: &mut Commands,
+    queues: &mut Query<&mut OrderQueue>,
+    ship: Entity,
+    order: Order,
+    replace: bool,
+) {
+    // ships can't be told to go after themselves
+    if matches!(order, Order::Attack(t) | Order::Dock(t) | Order::Intercept(t) | Order::Follow { target: t, .. } if t == ship)
+    {
+        return;
+    }
+    match queues.get_mut(ship) {
+        Ok(mut queue) => queue.issue(order, replace),
+        Err(_) => {
+            let mut queue = OrderQueue::default();
+            queue.issue(order, true);
+            commands.entity(ship).insert((queue, Autopilot::default()));
+        }
+    }
+}
+
+/// :COMPONENT: Marker for the sprites showing the selected ships' orders.
+#[derive(Component)]
+struct OrderMarker;
+
+/// :SYSTEM: Shows where each selected ship's queued orders will take it.
+fn order_marker_system(
+    mut commands: Commands,
+    panel: Res<OrdersPanel>,
+    queues: Query<&OrderQueue>,
+    targets: Query<&GlobalTransform>,
+    markers: Query<Entity, With<OrderMarker>>,
+) {
+    for marker in markers.iter() {
+        commands.entity(marker).despawn();
+    }
+    if !panel.open {
+        return;
+    }
+
+    for queue in panel.selected.iter().filter_map(|s| queues.get(*s).ok()) {
+        for (i, order) in queue.orders.iter().enumerate() {
+            let (at, color) = match *order {
+                Order::MoveTo(point) => (Some(point), Color::rgb(0.3, 1.0, 0.5)),
+                Order::Orbit { body, .. } => {
+                    (targets.get(body).ok().map(|t| t.translation()), Color::CYAN)
+                }
+                Order::Attack(ship) => {
+                    (targets.get(ship).ok().map(|t| t.translation()), Color::RED)
+                }
+                Order::Dock(ship) => (
+                    targets.get(ship).ok().map(|t| t.translation()),
+                    Color::YELLOW,
+                ),
+                Order::Follow { target, .. } => (
+                    targets.get(target).ok().map(|t| t.translation()),
+                    Color::WHITE,
+                ),
+                Order::Intercept(target) => (
+                    targets.get(target).ok().map(|t| t.translation()),
+                    Color::ORANGE,
+                ),
+            };
+            let Some(at) = at else { continue };
+            // later orders are drawn fainter
+            let color = color.with_a(1.0 / (i + 1) as f32);
+            commands.spawn((
+                OrderMarker,
+                MapIcon,
+                SpriteBundle {
+                    sprite: Sprite {
+                        custom_size: Some(Vec2::splat(8.0)),
+                        color,
+                        ..Default::default()
+                    },
+                    transform: Transform::from_translation(at.truncate().extend(5.0)),
+                    ..Default::default()
+                },
+            ));
+        }
+    }
+}
@@ -0,0 +1,257 @@
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use bevy::window::{PresentMode, PrimaryWindow, WindowMode};
+use bevy_egui::{egui, EguiContexts, EguiSet};
+
+use super::audio::{self, AudioSettings};
+use super::context_menu::ContextMenu;
+use super::cutscene::Cutscene;
+use super::input_map::ControlsPanel;
+use super::level::LevelSprites;
+use super::physics::{Kinimatics, PhysicsConfig, SimulationSet};
+use super::presets::{self, Preset};
+use super::profiles::ProfilePanel;
+use super::scenes;
+use super::spawn_menu::SpawnMenu;
+
+pub struct PauseMenuPlugin;
+
+impl Plugin for PauseMenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_state::<GameState>()
+            .init_resource::<PauseMenu>()
+            .configure_set(SimulationSet.run_if(in_state(GameState::Playing)))
+            .add_system(
+                pause_key_system
+                    .in_base_set(CoreSet::PreUpdate)
+                    .after(EguiSet::BeginFrame),
+            )
+            .add_system(pause_menu_system.in_set(OnUpdate(GameState::Paused)))
+            .add_system(main_menu_system.in_set(OnUpdate(GameState::MainMenu)))
+            .add_system(clear_arena_system.in_schedule(OnEnter(GameState::MainMenu)))
+            .add_system(
+                hold_time_system
+                    .in_base_set(CoreSet::Last)
+                    .after(super::debug_tools::step_through_system),
+            );
+    }
+}
+
+/// Window sizes the settings offer, in logical pixels.
+const RESOLUTIONS: [(f32, f32); 4] = [
+    (1280.0, 720.0),
+    (1600.0, 900.0),
+    (1920.0, 1080.0),
+    (2560.0, 1440.0),
+];
+
+/// Scene the main menu loads, relative to the assets folder.
+const MAIN_MENU_SCENE: &str = "scenes/world.scn.ron";
+
+/// Where the game is at. The [`SimulationSet`] only runs while `Playing`;
+/// while paused, or at the main menu, the world holds still and [`Time`]
+/// stands still with it.
+#[derive(States, Default, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum GameState {
+    #[default]
+    Playing,
+    /// The pause menu is up, over the frozen simulation.
+    Paused,
+    /// The arena has been cleared, and the player picks what to play next.
+    MainMenu,
+}
+
+/// Resource which holds the state of the pause menu's settings.
+#[derive(Resource, Default)]
+pub struct PauseMenu {
+    settings: bool,
+    /// Whether [`Time`] was paused by the menu, rather than by anything else.
+    holding: bool,
+}
+
+/// :SYSTEM: Pauses and resumes the game when escape is pressed. Escape goes to
+/// whatever else is using it first: skipping a cutscene, closing the context
+/// menu, stopping placing from the spawn menu, or the controls panel.
+#[allow(clippy::too_many_arguments)]
+fn pause_key_system(
+    state: Res<State<GameState>>,
+    mut next: ResMut<NextState<GameState>>,
+    input: Res<Input<KeyCode>>,
+    mut contexts: EguiContexts,
+    cutscene: Res<Cutscene>,
+    context_menu: Res<ContextMenu>,
+    spawn_menu: Res<SpawnMenu>,
+    controls: Res<ControlsPanel>,
+) {
+    if !input.just_pressed(KeyCode::Escape) || contexts.ctx_mut().wants_keyboard_input() {
+        return;
+    }
+    let busy = cutscene.playing()
+        || context_menu.target.is_some()
+        || (spawn_menu.open && spawn_menu.placing)
+        || controls.open;
+    match state.0 {
+        GameState::Playing if !busy => next.set(GameState::Paused),
+        GameState::Paused if !busy => next.set(GameState::Playing),
+        _ => (),
+    }
+}
+
+/// :SYSTEM: Shows the pause menu, with the settings for the window and the
+/// sound, and the way to the controls and profile panels and the main menu.
+fn pause_menu_system(
+    mut contexts: EguiContexts,
+    mut menu: ResMut<PauseMenu>,
+    mut next: ResMut<NextState<GameState>>,
+    mut windows: Query<&mut Window, With<PrimaryWindow>>,
+    mut audio: ResMut<AudioSettings>,
+    mut controls: ResMut<ControlsPanel>,
+    mut profile: ResMut<ProfilePanel>,
+) {
+    let ctx = contexts.ctx_mut();
+    // dim the frozen game behind the menu
+    let screen = ctx.screen_rect();
+    ctx.layer_painter(egui::LayerId::background()).rect_filled(
+        screen,
+        0.0,
+        egui::Color32::from_black_alpha(160),
+    );
+
+    egui::Window::new("Paused")
+        .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+        .collapsible(false)
+        .resizable(false)
+        .show(ctx, |ui| {
+            if ui.button("Resume").clicked() {
+                next.set(GameState::Playing);
+            }
+            if ui.button("Settings").clicked() {
+                menu.settings = !menu.settings;
+            }
+            if ui.button("Controls").clicked() {
+                controls.open = true;
+            }
+            if ui.button("Profile").clicked() {
+                profile.open = true;
+            }
+            if ui.button("Main menu").clicked() {
+                next.set(GameState::MainMenu);
+            }
+        });
+
+    if !menu.settings {
+        return;
+    }
+    let mut open = true;
+    egui::Window::new("Settings")
+        .open(&mut open)
+        .resizable(false)
+        .show(ctx, |ui| {
+            if let Ok(mut window) = windows.get_single_mut() {
+                ui.heading("Graphics");
+                let current = (window.resolution.width(), window.resolution.height());
+                egui::ComboBox::from_label("resolution")
+                    .selected_text(format!("{} x {}", current.0, current.1))
+                    .show_ui(ui, |ui| {
+                        for (width, height) in RESOLUTIONS {
+                            let label = format!("{} x {}", width, height);
+                            if ui
+                                .selectable_label(current == (width, height), label)
+                                .clicked()
+                            {
+                                window.resolution.set(width, height);
+                            }
+                        }
+                    });
+                let mut fullscreen = window.mode != WindowMode::Windowed;
+                if ui.checkbox(&mut fullscreen, "fullscreen").changed() {
+                    window.mode = match fullscreen {
+                        true => WindowMode::BorderlessFullscreen,
+                        false => WindowMode::Windowed,
+                    };
+                }
+                let mut vsync = window.present_mode != PresentMode::AutoNoVsync;
+                if ui.checkbox(&mut vsync, "vsync").changed() {
+                    window.present_mode = match vsync {
+                        true => PresentMode::AutoVsync,
+                        false => PresentMode::AutoNoVsync,
+                    };
+                }
+                ui.separator();
+            }
+
+            ui.heading("Audio");
+            audio::volume_controls(ui, &mut audio);
+        });
+    menu.settings = open;
+}
+
+/// :SYSTEM: Clears the arena on the way into the main menu, ships and all.
+fn clear_arena_system(
+    mut commands: Commands,
+    world: Query<Entity, (With<Kinimatics>, Without<Parent>)>,
+) {
+    presets::clear_arena(&mut commands, world.iter());
+}
+
+/// :SYSTEM: Shows the main menu, which starts the game again from one of the
+/// [`Preset`]s or the saved scene, or quits.
+#[allow(clippy::too_many_arguments)]
+fn main_menu_system(
+    mut commands: Commands,
+    mut contexts: EguiContexts,
+    mut next: ResMut<NextState<GameState>>,
+    mut exit: EventWriter<AppExit>,
+    sprites: Res<LevelSprites>,
+    physics: Res<PhysicsConfig>,
+    asset_server: Res<AssetServer>,
+    mut profile: ResMut<ProfilePanel>,
+) {
+    egui::Window::new("Programmable Ships")
+        .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+        .collapsible(false)
+        .resizable(false)
+        .show(contexts.ctx_mut(), |ui| {
+            for preset in Preset::ALL {
+                if ui.button(preset.label()).clicked() {
+                    presets::spawn_preset(&mut commands, &sprites, &physics, preset);
+                    next.set(GameState::Playing);
+                }
+                ui.weak(preset.description());
+                ui.add_space(4.0);
+            }
+            ui.separator();
+            if ui.button("Load saved scene").clicked() {
+                scenes::spawn_scene(&mut commands, &asset_server, MAIN_MENU_SCENE);
+                next.set(GameState::Playing);
+            }
+            ui.weak(format!("assets/{}", MAIN_MENU_SCENE));
+            ui.add_space(4.0);
+            if ui.button("Profile").clicked() {
+                profile.open = true;
+            }
+            if ui.button("Quit").clicked() {
+                exit.send(AppExit);
+            }
+        });
+}
+
+/// :SYSTEM: Holds [`Time`] still while the game isn't being played, and lets
+/// it go again after. Runs at the very end of the frame, after the debug
+/// step-through, so it has the last word. Time which something else had
+/// already paused is left paused.
+fn hold_time_system(
+    state: Res<State<GameState>>,
+    mut menu: ResMut<PauseMenu>,
+    mut time: ResMut<Time>,
+) {
+    if state.0 != GameState::Playing {
+        if !time.is_paused() {
+            time.pause();
+            menu.holding = true;
+        }
+    } else if menu.holding {
+        time.unpause();
+        menu.holding = false;
+    }
+}
@@ -0,0 +1,194 @@
+use bevy::prelude::*;
+
+use super::clock::SimulationClock;
+use super::level::AstroObject;
+use super::navigation;
+use super::physics::{Kinimatics, PhysicsConfig, SimulationSet};
+use super::scripting::{BodySnapshot, ScriptEvent, ShipProgram, Value};
+
+pub struct PerturbationPlugin;
+
+impl Plugin for PerturbationPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Perturbations {
+            enabled: std::env::args().any(|a| a == "--perturbations"),
+            ..Default::default()
+        })
+        .register_type::<Perturbations>()
+        .add_systems(
+            (perturbation_system, station_keeping_system)
+                .chain()
+                .after(super::physics::kinimatics_system)
+                .in_set(SimulationSet),
+        );
+    }
+}
+
+/// Area presented to an atmosphere by anything which isn't an astronomical body.
+const DRAG_AREA: f32 = 1.0;
+
+/// Resource which controls the small forces that aren't simulated directly,
+/// and so are approximated: drag from the thin upper atmospheres of bodies,
+/// and the tugging of distant bodies.
+///
+/// These are off by default, since they make every orbit decay eventually.
+/// Start the game with `--perturbations`, or have the mission turn them on.
+#[derive(Resource, Reflect)]
+#[reflect(Resource)]
+pub struct Perturbations {
+    pub enabled: bool,
+    /// Strength of the tugging of distant bodies, as an acceleration.
+    pub third_body: f32,
+}
+
+impl Default for Perturbations {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            third_body: 0.02,
+        }
+    }
+}
+
+/// :COMPONENT: The atmosphere of an astronomical body. It thins out
+/// exponentially with altitude, and slows down anything moving through it.
+#[derive(Reflect, Component, Default, Clone, Copy)]
+#[reflect(Component)]
+pub struct Atmosphere {
+    /// Density at the surface.
+    pub density: f32,
+    /// Altitude over which the density falls by a factor of e.
+    pub scale_height: f32,
+}
+
+impl Atmosphere {
+    pub fn density_at(&self, altitude: f32) -> f32 {
+        self.density * (-altitude.max(0.0) / self.scale_height).exp()
+    }
+}
+
+/// :COMPONENT: An orbit which an entity has been asked to hold.
+///
+/// When the orbit's semi-major axis drifts more than `tolerance` from
+/// `radius`, a `station_drift` event is raised on the entity's program, and as
+/// a [`ScriptEvent`], with the error. `station_held` follows once it has been
+/// brought back.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct StationKeeping {
+    /// The body being orbited.
+    pub body: Entity,
+    pub radius: f32,
+    pub tolerance: f32,
+    pub holding: bool,
+}
+
+impl StationKeeping {
+    pub fn new(body: Entity, radius: f32, tolerance: f32) -> Self {
+        Self {
+            body,
+            radius,
+            tolerance,
+            holding: true,
+        }
+    }
+}
+
+/// :SYSTEM: Applies atmospheric drag, and the tugging of distant bodies, to
+/// everything but the astronomical bodies themselves.
+///
+/// The tugging is modelled as a slow wobble, different for every entity, since
+/// that is what the real thing looks like from a single orbit.
+#[allow(clippy::type_complexity)]
+fn perturbation_system(
+    perturbations: Res<Perturbations>,
+    mut bodies: Query<(Entity, &mut Kinimatics, &Transform), Without<AstroObject>>,
+    atmospheres: Query<(&AstroObject, &Atmosphere, &Kinimatics, &Transform)>,
+    clock: Res<SimulationClock>,
+    time: Res<Time>,
+) {
+    if !perturbations.enabled {
+        return;
+    }
+    let dt = time.delta_seconds();
+    let t = clock.met();
+
+    for (entity, mut kin, transform) in bodies.iter_mut() {
+        if kin.mass <= 0.0 {
+            continue;
+        }
+
+        let mut acceleration = Vec3::ZERO;
+        for (astro, atmosphere, astro_kin, astro_transform) in atmospheres.iter() {
+            let altitude =
+                transform.translation.distance(astro_transform.translation) - astro.radius;
+            let airspeed = kin.velocity - astro_kin.velocity;
+            let density = atmosphere.density_at(altitude);
+            acceleration -= 0.5 * density * airspeed * airspeed.length() * DRAG_AREA / kin.mass;
+        }
+
+        let phase = (entity.index() % 64) as f32;
+        acceleration += Vec3::new(
+            (t * 0.05 + phase).sin(),
+            (t * 0.031 + phase * 1.7).cos(),
+            0.0,
+        ) * perturbations.third_body;
+
+        kin.velocity += acceleration * dt;
+    }
+}
+
+/// :SYSTEM: Checks that everything keeping station is still in its orbit.
+fn station_keeping_system(
+    mut stations: Query<(
+        Entity,
+        &mut StationKeeping,
+        &Kinimatics,
+        &Transform,
+        Option<&mut ShipProgram>,
+    )>,
+    bodies: Query<(&Kinimatics, &Transform)>,
+    mut script_events: EventWriter<ScriptEvent>,
+    physics: Res<PhysicsConfig>,
+) {
+    for (entity, mut station, kin, transform, program) in stations.iter_mut() {
+        let Ok((body_kin, body_transform)) = bodies.get(station.body) else {
+            continue;
+        };
+        let snapshot = |entity, kin: &Kinimatics, transform: &Transform| BodySnapshot {
+            entity,
+            position: transform.translation,
+            velocity: kin.velocity,
+            mass: kin.mass,
+        };
+        let orbit = navigation::orbit(
+            &snapshot(entity, kin, transform),
+            &snapshot(station.body, body_kin, body_transform),
+            &physics,
+        );
+
+        // an escape trajectory has no semi-major axis worth comparing
+        let error = if orbit.eccentricity < 1.0 {
+            orbit.semi_major - station.radius
+        } else {
+            f32::MAX
+        };
+        let holding = error.abs() <= station.tolerance;
+        if holding == station.holding {
+            continue;
+        }
+        station.holding = holding;
+
+        let event = if holding {
+            "station_held"
+        } else {
+            "station_drift"
+        };
+        if let Some(mut program) = program {
+            program.vm.raise(event, vec![Value::Num(error as f64)]);
+        }
+        script_events.send(ScriptEvent {
+            name: event.to_string(),
+            args: vec![Value::from_entity(entity), Value::Num(error as f64)],
+        });
+    }
+}
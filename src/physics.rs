@@ -1,14 +1,103 @@
-use super::ships::{Engine, Throttle};
+use super::debug_tools::TimeDilation;
+use super::determinism::StableId;
+use super::level::OnRails;
+use super::propagation::{self, PropagatedBody};
+use super::ships::Engine;
+use super::star_system::Dormant;
 use bevy::{prelude::*, render::render_resource::AsBindGroupShaderType};
+use serde::{Deserialize, Serialize};
 
-pub struct PhysicsPlugin;
+pub struct PhysicsPlugin {
+    config: PhysicsConfig,
+}
+
+impl PhysicsPlugin {
+    pub fn new(config: PhysicsConfig) -> Self {
+        Self { config }
+    }
+}
 
 impl Plugin for PhysicsPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system(kinimatics_system);
+        app.insert_resource(self.config)
+            .add_event::<ApplyImpulse>()
+            .add_system(
+                impulse_system
+                    .before(kinimatics_system)
+                    .in_set(SimulationSet),
+            )
+            .add_system(kinimatics_system.in_set(SimulationSet));
+    }
+}
+
+/// The real gravitational constant, in m³ kg⁻¹ s⁻².
+pub const GRAVITATIONAL_CONSTANT: f32 = 6.67430e-11;
+
+/// Where the physics settings are kept.
+pub const PHYSICS_CONFIG_PATH: &str = "physics.ron";
+
+/// Resource which holds the constants the physics runs on. Everything which
+/// works out gravity, from the simulation itself to course projections and
+/// orbit helpers, goes through [`g`](Self::g) rather than the real constant,
+/// so either real SI data or game-scaled values can be used.
+#[derive(Resource, Serialize, Deserialize, Clone, Copy, Debug)]
+#[serde(default)]
+pub struct PhysicsConfig {
+    /// In m³ kg⁻¹ s⁻², whatever the distance unit.
+    pub gravitational_constant: f32,
+    /// Meters in one unit of distance in the world.
+    pub distance_unit: f32,
+    /// Seconds the physics moves forward for each second of game time.
+    pub time_scale: f32,
+    /// Distance, in world units, below which gravity stops getting stronger,
+    /// so bodies which pass through each other aren't flung off at infinite
+    /// speed.
+    pub softening: f32,
+}
+
+impl Default for PhysicsConfig {
+    fn default() -> Self {
+        Self {
+            gravitational_constant: GRAVITATIONAL_CONSTANT,
+            distance_unit: 1.0,
+            time_scale: 1.0,
+            softening: 0.01,
+        }
+    }
+}
+
+impl PhysicsConfig {
+    pub fn load(path: &str) -> Result<Self, String> {
+        std::fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|s| ron::from_str(&s).map_err(|e| e.to_string()))
+    }
+
+    /// The settings in `path`, or the defaults if there aren't any there.
+    pub fn load_or_default(path: &str) -> Self {
+        Self::load(path).unwrap_or_else(|e| {
+            info!("using the default physics ({}: {})", path, e);
+            Self::default()
+        })
+    }
+
+    /// The gravitational constant in world units of distance.
+    pub fn g(&self) -> f32 {
+        self.gravitational_constant / self.distance_unit.powi(3)
+    }
+
+    /// The standard gravitational parameter of a body of `mass`.
+    pub fn mu(&self, mass: f32) -> f32 {
+        self.g() * mass
     }
 }
 
+/// Every system which moves the simulation forward belongs in this set, so the
+/// whole simulation can be held still (see the step-through debug tool), while
+/// the UI carries on as normal.
+#[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone)]
+pub struct SimulationSet;
+
 /// :COMPONENT: For entities that abide by the laws of ~~physics~~ my choosing.
 /// Note, currently this is a 2D game, therefore the Z field is not to be used.
 /// A future version of the game might open up a third dimension.
@@ -24,12 +113,181 @@ pub struct Kinimatics {
     pub mass: f32,
 }
 
+/// :COMPONENT: Pulls on every [`GravityAffected`] body. Only heavy bodies, like
+/// planets, should be sources; anything light enough that its pull doesn't
+/// matter is left out, so the number of pairs the physics has to work through
+/// stays small.
+#[derive(Reflect, Default, Clone, Copy, Component)]
+#[reflect(Component)]
+pub struct GravitySource;
+
+/// :COMPONENT: Is pulled on by every [`GravitySource`]. Part of the
+/// [`KinimaticsBundle`], so everything that moves falls unless it's taken off.
+#[derive(Reflect, Default, Clone, Copy, Component)]
+#[reflect(Component)]
+pub struct GravityAffected;
+
+/// Fastest a body's reaction wheels can spin it up, by default, in radians per
+/// second squared.
+pub const MAX_ANGULAR_ACCELERATION: f32 = 8.0 * std::f32::consts::PI;
+
+/// :COMPONENT: How a body turns. The physics integrates its spin along with
+/// its motion, so a body left alone keeps turning at the rate it was, and the
+/// course projection can tell where it'll be pointing.
+///
+/// Nothing turns a body directly. Whatever's flying it asks for a rate of turn
+/// with [`command`](Self::command), and the reaction wheels put out as much
+/// torque, up to `max_torque`, as it takes to reach that rate over the next
+/// step.
+#[derive(Reflect, Clone, Copy, Component, Debug)]
+#[reflect(Component)]
+pub struct Attitude {
+    /// Radians per second, counterclockwise.
+    pub angular_velocity: f32,
+    /// Moment of inertia about the z axis. A body without one can't be spun up
+    /// or slowed down.
+    pub inertia: f32,
+    pub max_torque: f32,
+    /// Rate of turn asked for over the next step, in radians per second.
+    pub commanded_rate: Option<f32>,
+    /// Torque put out over the last step.
+    pub torque: f32,
+}
+
+impl Default for Attitude {
+    fn default() -> Self {
+        Self::new(1.0)
+    }
+}
+
+impl Attitude {
+    /// A body with reaction wheels strong enough to spin it up at
+    /// [`MAX_ANGULAR_ACCELERATION`].
+    pub fn new(inertia: f32) -> Self {
+        Self {
+            angular_velocity: 0.0,
+            inertia,
+            max_torque: inertia * MAX_ANGULAR_ACCELERATION,
+            commanded_rate: None,
+            torque: 0.0,
+        }
+    }
+
+    /// Asks for the body to turn at `rate` radians per second over the next
+    /// step.
+    pub fn command(&mut self, rate: f32) {
+        self.commanded_rate = Some(rate);
+    }
+
+    /// Most the body can speed up or slow down its turn, in radians per second
+    /// squared.
+    pub fn max_angular_acceleration(&self) -> f32 {
+        match self.inertia > 0.0 {
+            true => self.max_torque.abs() / self.inertia,
+            false => 0.0,
+        }
+    }
+
+    /// Rate to ask for to close `error` radians of turn, at no more than
+    /// `max_rate` radians per second, slowing down in time not to overshoot.
+    pub fn rate_towards(&self, error: f32, max_rate: f32, dt: f32) -> f32 {
+        // the fastest it can be turning and still stop in time
+        let braking = (2.0 * self.max_angular_acceleration() * error.abs()).sqrt();
+        let mut rate = max_rate.min(braking);
+        if dt > 0.0 {
+            rate = rate.min(error.abs() / dt);
+        }
+        rate.copysign(error)
+    }
+
+    /// Puts out the torque for a step of `dt` seconds and spins the body up
+    /// by it, returning how far the body turns over the step.
+    pub fn step(&mut self, dt: f32) -> f32 {
+        let max_torque = self.max_torque.abs();
+        self.torque = match self.commanded_rate {
+            Some(rate) if self.inertia > 0.0 && dt > 0.0 => {
+                ((rate - self.angular_velocity) * self.inertia / dt).clamp(-max_torque, max_torque)
+            }
+            _ => 0.0,
+        };
+        if self.inertia > 0.0 {
+            self.angular_velocity += self.torque / self.inertia * dt;
+        }
+        self.angular_velocity * dt
+    }
+
+    pub fn is_finite(&self) -> bool {
+        self.angular_velocity.is_finite()
+            && self.inertia.is_finite()
+            && self.max_torque.is_finite()
+            && self.commanded_rate.is_none_or(f32::is_finite)
+    }
+
+    /// Stops the body turning, and takes away anything that isn't a number.
+    pub fn sanitize(&mut self) {
+        if !self.inertia.is_finite() {
+            self.inertia = 0.0;
+        }
+        if !self.max_torque.is_finite() {
+            self.max_torque = 0.0;
+        }
+        self.angular_velocity = 0.0;
+        self.commanded_rate = None;
+        self.torque = 0.0;
+    }
+}
+
+/// :EVENT: Changes a body's momentum all at once, at the start of the next
+/// physics tick. Blasts, collisions and the like go through this, rather than
+/// setting velocities themselves.
+#[derive(Clone, Copy, Debug)]
+pub struct ApplyImpulse {
+    pub entity: Entity,
+    /// Change in momentum, in world space.
+    pub impulse: Vec3,
+    /// Where, in world space, the impulse lands. Off the body's center, it
+    /// spins the body as well, if it has an [`Attitude`]. At the center if
+    /// left out.
+    pub point: Option<Vec3>,
+}
+
+impl ApplyImpulse {
+    /// An impulse through the body's center.
+    pub fn new(entity: Entity, impulse: Vec3) -> Self {
+        Self {
+            entity,
+            impulse,
+            point: None,
+        }
+    }
+
+    /// The push from a blast at `center` on a body at `position`, falling off
+    /// from `strength` at the center to nothing at `radius`. None if the body
+    /// is out of reach.
+    pub fn from_blast(
+        entity: Entity,
+        position: Vec3,
+        center: Vec3,
+        radius: f32,
+        strength: f32,
+    ) -> Option<Self> {
+        let offset = position - center;
+        let distance = offset.length();
+        if distance >= radius {
+            return None;
+        }
+        let impulse = offset.normalize_or_zero() * strength * (1.0 - distance / radius);
+        Some(Self::new(entity, impulse))
+    }
+}
+
 /// :BUNDLE: Provided for convenience. the Kinimatics component doesn't track
 /// the transform of the entity, so this bundle should be used when creating
 /// a new entity.
 #[derive(Bundle, Default)]
 pub struct KinimaticsBundle {
     pub kinimatics: Kinimatics,
+    pub gravity_affected: GravityAffected,
     #[bundle]
     pub spatial: SpatialBundle,
 }
@@ -73,75 +331,112 @@ impl KinimaticsBundle {
     }
 }
 
+/// :SYSTEM: Applies the impulses sent since the last tick. One on a body which
+/// hasn't got its kinimatics yet, like a ship which is only just undocking, is
+/// held over to the next tick. Bodies without mass can't be pushed.
+pub fn impulse_system(
+    mut impulses: EventReader<ApplyImpulse>,
+    mut held: Local<Vec<ApplyImpulse>>,
+    mut bodies: Query<(Option<&mut Kinimatics>, &Transform, Option<&mut Attitude>)>,
+) {
+    let held_over = std::mem::take(&mut *held);
+    let impulses = held_over
+        .into_iter()
+        .map(|i| (i, true))
+        .chain(impulses.iter().map(|i| (*i, false)));
+    for (impulse, retried) in impulses {
+        let Ok((kin, transform, attitude)) = bodies.get_mut(impulse.entity) else {
+            continue;
+        };
+        let Some(mut kin) = kin else {
+            if !retried {
+                held.push(impulse);
+            }
+            continue;
+        };
+        if !impulse.impulse.is_finite() || !kin.mass.is_finite() || kin.mass <= 0.0 {
+            continue;
+        }
+
+        let mass = kin.mass;
+        kin.velocity += impulse.impulse / mass;
+        if let (Some(point), Some(mut attitude)) = (impulse.point, attitude) {
+            let arm = point - transform.translation;
+            if attitude.inertia > 0.0 && arm.is_finite() {
+                attitude.angular_velocity += arm.cross(impulse.impulse).z / attitude.inertia;
+            }
+        }
+    }
+}
+
 /// :SYSTEM: Iterates through all of the kinimatic entities, and simulates physics
 /// on them, updating their transforms when it is done.
+///
+/// Entities with a [`TimeDilation`] (a debug tool) are integrated with a scaled
+/// time step, but still pull on everything else as normal.
+#[allow(clippy::type_complexity)]
 pub fn kinimatics_system(
-    mut k_bods: Query<(&mut Kinimatics, &mut Transform, Option<&Engine>)>,
+    mut k_bods: Query<
+        (
+            Entity,
+            &mut Kinimatics,
+            &mut Transform,
+            Option<&Engine>,
+            Option<&TimeDilation>,
+            Option<&StableId>,
+            (Option<&GravitySource>, Option<&GravityAffected>),
+            Option<&OnRails>,
+            Option<&mut Attitude>,
+        ),
+        Without<Dormant>,
+    >,
+    config: Res<PhysicsConfig>,
     time: Res<Time>,
 ) {
-    // each element will have a corresponding entry in this list.
-    let num_bods = k_bods.iter_mut().count();
-    let mut all_forces: Vec<Vec<Vec3>> = Vec::new();
-    all_forces.reserve(num_bods);
-
-    // initialize a new vector for each k_bod
-    for _ in 0..num_bods {
-        all_forces.push(Vec::new());
-    }
-
-    let dt = time.delta_seconds();
-
-    const GRAVITATIONAL_CONSTANT: f32 = 6.67430e-11;
-
-    //  Calculate forces from gravity
-    let mut entities: Vec<(Mut<Kinimatics>, Mut<Transform>, Option<&Engine>)> =
-        k_bods.iter_mut().collect();
-
-    for (i, q) in entities.iter().enumerate() {
-        // NOTE do I need to do bounds checking here?
-        entities
-            .split_at(i + 1)
-            .1
-            .iter()
-            .enumerate()
-            .for_each(|(j, o)| {
-                // calculate magnitude of the force
-                let force_mag = GRAVITATIONAL_CONSTANT * (q.0.mass * o.0.mass)
-                    / q.1.translation.distance_squared(o.1.translation);
-
-                // calculate direction and magnitude of the forces for each object.
-                let d1 = (o.1.translation - q.1.translation).normalize() * force_mag;
-                let d2 = (q.1.translation - o.1.translation).normalize() * force_mag;
-
-                // add these forces to a list of forces
-                all_forces[i].push(d1);
-                all_forces[i + j + 1].push(d2);
-            });
-    }
-
-    // ## Calculate other forces and update kinimatics
-    for (i, (kin, tran, engine)) in entities.iter_mut().enumerate() {
-        // handle acceleration from ship engine
-        if let Some(t) = engine {
-            all_forces[i].push(
-                tran.rotation.mul_vec3(Vec3::Y)
-                    * match t.throttle {
-                        Throttle::Fixed(true) => t.max_thrust,
-                        Throttle::Fixed(false) => 0.0,
-                        Throttle::Variable(amount) => amount * t.max_thrust,
-                    },
-            );
-        }
+    let mut entities: Vec<_> = k_bods.iter_mut().collect();
+    // in deterministic mode, forces are always added up in the same order
+    entities.sort_by_key(|e| e.5.copied());
+    let index_of = |entity: Entity| entities.iter().position(|e| e.0 == entity);
 
-        // add up forces, then apply them
-        kin.acceleration = all_forces[i]
-            .iter()
-            .copied()
-            .reduce(|acc, x| acc + x)
-            .expect("0 forces")
-            / kin.mass;
+    let mut bodies: Vec<PropagatedBody> = entities
+        .iter()
+        .map(
+            |(_, kin, tran, engine, dilation, _, (source, affected), rails, attitude)| {
+                PropagatedBody {
+                    thrust: engine.map_or(Vec3::ZERO, |e| e.thrust_vector(Quat::IDENTITY)),
+                    angle: propagation::angle_of(tran.rotation),
+                    attitude: attitude.as_deref().copied(),
+                    time_scale: dilation.map_or(1.0, |d| d.scaled(1.0)),
+                    source: source.is_some(),
+                    affected: affected.is_some(),
+                    rails: rails.and_then(|r| index_of(r.parent)),
+                    ..PropagatedBody::new(tran.translation, kin.velocity, kin.mass)
+                }
+            },
+        )
+        .collect();
+    let sanitized = propagation::step(&mut bodies, time.delta_seconds(), &config);
+    if sanitized > 0 {
+        warn!("physics: {} bodies went bad and were reset", sanitized);
+    }
 
-        kin.velocity = kin.velocity + kin.acceleration * dt;
-        tran.translation = tran.translation + kin.velocity * dt;
+    // bodies on rails are put exactly where they should be afterwards, by the
+    // rails system
+    for ((_, kin, tran, .., attitude), body) in entities.iter_mut().zip(bodies) {
+        kin.acceleration = body.acceleration;
+        kin.velocity = body.velocity;
+        kin.mass = body.mass;
+        tran.translation = body.position;
+        let turned = body.angle - propagation::angle_of(tran.rotation);
+        if turned != 0.0 {
+            tran.rotate_z(turned);
+        }
+        // a command only lasts the step it was given for
+        if let (Some(attitude), Some(after)) = (attitude, body.attitude) {
+            **attitude = Attitude {
+                commanded_rate: None,
+                ..after
+            };
+        }
     }
 }
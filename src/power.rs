@@ -0,0 +1,316 @@
+use bevy::prelude::*;
+
+use super::daylight;
+use super::level::{AstroObject, Star};
+use super::modules::{BeamMount, SensorArray, ShieldGenerator, Thruster, WeaponMount};
+use super::physics::SimulationSet;
+use super::scripting::{ScriptEvent, ShipProgram, Value};
+
+pub struct PowerPlugin;
+
+impl Plugin for PowerPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<PowerRequest>()
+            .add_system(power_request_system.in_set(SimulationSet))
+            .add_system(solar_system.before(power_system).in_set(SimulationSet))
+            .add_system(
+                power_system
+                    .after(power_request_system)
+                    .before(super::modules::loadout_system)
+                    .in_set(SimulationSet),
+            );
+    }
+}
+
+/// :COMPONENT: A module which generates power for its ship.
+#[derive(Reflect, Component, Default, Clone, Copy)]
+#[reflect(Component)]
+pub struct Reactor {
+    /// Power generated, in watts.
+    pub output: f32,
+}
+
+/// Distance from a star at which a [`SolarPanel`] gives its rated output.
+pub const SOLAR_REFERENCE_DISTANCE: f32 = 100.0;
+
+/// Most a [`SolarPanel`] gives right up against a star, as a multiple of its
+/// rated output.
+const MAX_SOLAR_GAIN: f32 = 4.0;
+
+/// :COMPONENT: A module which generates power from starlight. It gives its
+/// rated output facing the nearest [`Star`] squarely from
+/// [`SOLAR_REFERENCE_DISTANCE`]; further out the light falls off with the
+/// square of the distance, and turned away from the star the panel catches
+/// less of it. Nothing at all reaches a panel in a planet's shadow.
+#[derive(Reflect, Component, Default, Clone, Copy)]
+#[reflect(Component)]
+pub struct SolarPanel {
+    /// Power generated facing the star at the reference distance, in watts.
+    pub rated_output: f32,
+    /// Angle the panel faces, in radians anticlockwise from the ship's nose.
+    pub facing: f32,
+    /// Power generated, as of the last tick.
+    pub output: f32,
+    /// Power the panel would generate turned squarely to the star.
+    pub peak: f32,
+}
+
+/// :COMPONENT: A module which stores power. Batteries charge from whatever the
+/// reactors produce beyond what the ship is using, and cover the shortfall when
+/// the reactors can't keep up.
+#[derive(Reflect, Component, Default, Clone, Copy)]
+#[reflect(Component)]
+pub struct Battery {
+    /// Energy stored, in joules.
+    pub charge: f32,
+    pub capacity: f32,
+}
+
+/// :COMPONENT: A module which needs power to work. A module which is switched
+/// off doesn't draw any power, and doesn't do anything either.
+#[derive(Reflect, Component, Clone, Copy)]
+#[reflect(Component)]
+pub struct PowerConsumer {
+    /// Power drawn, in watts.
+    pub draw: f32,
+    pub enabled: bool,
+}
+
+impl Default for PowerConsumer {
+    fn default() -> Self {
+        Self {
+            draw: 0.0,
+            enabled: true,
+        }
+    }
+}
+
+/// :COMPONENT: The balance of power across all of a ship's modules, as of the
+/// last tick.
+#[derive(Reflect, Component, Clone, Copy)]
+#[reflect(Component)]
+pub struct PowerGrid {
+    /// Power generated by the ship's reactors and solar panels, in watts.
+    pub generation: f32,
+    /// The part of the generation which comes from solar panels.
+    pub solar: f32,
+    /// What the solar panels would generate turned squarely to the star.
+    pub solar_peak: f32,
+    /// Power asked for by the ship's enabled modules, in watts.
+    pub demand: f32,
+    /// Energy held in the ship's batteries, in joules.
+    pub stored: f32,
+    pub capacity: f32,
+    /// Fraction of the demand that was met, on \[0,1\]. Anything less than one
+    /// is a brownout, and the ship's modules only work as well as they're powered.
+    pub supply: f32,
+}
+
+impl Default for PowerGrid {
+    fn default() -> Self {
+        Self {
+            generation: 0.0,
+            solar: 0.0,
+            solar_peak: 0.0,
+            demand: 0.0,
+            stored: 0.0,
+            capacity: 0.0,
+            supply: 1.0,
+        }
+    }
+}
+
+impl PowerGrid {
+    pub fn brownout(&self) -> bool {
+        self.supply < 1.0
+    }
+}
+
+/// The kinds of module which can be switched on and off.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Subsystem {
+    Thrusters,
+    Sensors,
+    Weapons,
+    Shields,
+}
+
+impl Subsystem {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "thrusters" => Ok(Self::Thrusters),
+            "sensors" => Ok(Self::Sensors),
+            "weapons" => Ok(Self::Weapons),
+            "shields" => Ok(Self::Shields),
+            other => Err(format!("unknown subsystem `{}`", other)),
+        }
+    }
+}
+
+/// :EVENT: Switches every module of one kind on a ship on or off.
+#[derive(Clone, Copy, Debug)]
+pub struct PowerRequest {
+    pub ship: Entity,
+    pub subsystem: Subsystem,
+    pub enabled: bool,
+}
+
+/// :SYSTEM: Switches modules on and off.
+#[allow(clippy::type_complexity)]
+fn power_request_system(
+    mut requests: EventReader<PowerRequest>,
+    ships: Query<&Children>,
+    mut modules: Query<(
+        &mut PowerConsumer,
+        Option<&Thruster>,
+        Option<&SensorArray>,
+        Option<&WeaponMount>,
+        Option<&BeamMount>,
+        Option<&ShieldGenerator>,
+    )>,
+) {
+    for request in requests.iter() {
+        let Ok(children) = ships.get(request.ship) else {
+            continue;
+        };
+
+        for &child in children {
+            let Ok((mut consumer, thruster, array, mount, beam, shield)) = modules.get_mut(child)
+            else {
+                continue;
+            };
+            let kind = match (thruster, array, mount, beam, shield) {
+                (Some(_), ..) => Subsystem::Thrusters,
+                (_, Some(_), ..) => Subsystem::Sensors,
+                (_, _, Some(_), ..) | (_, _, _, Some(_), _) => Subsystem::Weapons,
+                (.., Some(_)) => Subsystem::Shields,
+                _ => continue,
+            };
+            if kind == request.subsystem {
+                consumer.enabled = request.enabled;
+            }
+        }
+    }
+}
+
+/// :SYSTEM: Works out how much power each solar panel is getting from the
+/// nearest star, given where its ship is and which way it's turned.
+fn solar_system(
+    ships: Query<(&GlobalTransform, &Children)>,
+    mut panels: Query<&mut SolarPanel>,
+    stars: Query<&Transform, With<Star>>,
+    bodies: Query<(&Transform, &AstroObject), Without<Star>>,
+) {
+    for (global, children) in ships.iter() {
+        // landed and docked ships are children, so go by where they are in the world
+        let (_, rotation, position) = global.to_scale_rotation_translation();
+        let light = match daylight::nearest_star(stars.iter(), position) {
+            Some(star) if !daylight::in_shadow(position, star, bodies.iter()) => {
+                let distance = position.distance(star).max(f32::EPSILON);
+                let gain = (SOLAR_REFERENCE_DISTANCE / distance).powi(2);
+                Some(((star - position) / distance, gain.min(MAX_SOLAR_GAIN)))
+            }
+            _ => None,
+        };
+
+        let mut panel_iter = panels.iter_many_mut(children);
+        while let Some(mut panel) = panel_iter.fetch_next() {
+            let (output, peak) = match light {
+                Some((towards, gain)) => {
+                    let normal = (rotation * Quat::from_rotation_z(panel.facing)).mul_vec3(Vec3::Y);
+                    let peak = panel.rated_output * gain;
+                    (peak * normal.dot(towards).max(0.0), peak)
+                }
+                None => (0.0, 0.0),
+            };
+            if panel.output != output || panel.peak != peak {
+                panel.output = output;
+                panel.peak = peak;
+            }
+        }
+    }
+}
+
+/// :SYSTEM: Balances each ship's power generation against its demand, charging
+/// or draining its batteries to make up the difference.
+///
+/// When a ship's batteries run flat it browns out, and a `brownout` event is
+/// raised on its program, and as a [`ScriptEvent`]. A `power_restored` event
+/// follows once the ship can meet its demand again.
+#[allow(clippy::type_complexity)]
+fn power_system(
+    mut ships: Query<(Entity, &Children, &mut PowerGrid, Option<&mut ShipProgram>)>,
+    modules: Query<(
+        Option<&Reactor>,
+        Option<&SolarPanel>,
+        Option<&PowerConsumer>,
+    )>,
+    mut batteries: Query<&mut Battery>,
+    mut script_events: EventWriter<ScriptEvent>,
+    time: Res<Time>,
+) {
+    let dt = time.delta_seconds();
+    if dt <= 0.0 {
+        return;
+    }
+
+    for (entity, children, mut grid, program) in ships.iter_mut() {
+        let (mut generation, mut demand) = (0.0, 0.0);
+        let (mut solar, mut solar_peak) = (0.0, 0.0);
+        for (reactor, panel, consumer) in modules.iter_many(children) {
+            if let Some(r) = reactor {
+                generation += r.output;
+            }
+            if let Some(p) = panel {
+                generation += p.output;
+                solar += p.output;
+                solar_peak += p.peak;
+            }
+            if let Some(c) = consumer.filter(|c| c.enabled) {
+                demand += c.draw;
+            }
+        }
+
+        // positive when there's power to spare, negative when there's a shortfall
+        let mut surplus = (generation - demand) * dt;
+        let (mut stored, mut capacity) = (0.0, 0.0);
+        let mut battery_iter = batteries.iter_many_mut(children);
+        while let Some(mut battery) = battery_iter.fetch_next() {
+            let change = surplus.clamp(-battery.charge, battery.capacity - battery.charge);
+            battery.charge += change;
+            surplus -= change;
+            stored += battery.charge;
+            capacity += battery.capacity;
+        }
+
+        let supply = if demand > 0.0 && surplus < 0.0 {
+            (1.0 + surplus / (demand * dt)).clamp(0.0, 1.0)
+        } else {
+            1.0
+        };
+
+        let was_brownout = grid.brownout();
+        *grid = PowerGrid {
+            generation,
+            solar,
+            solar_peak,
+            demand,
+            stored,
+            capacity,
+            supply,
+        };
+
+        let event = match (was_brownout, grid.brownout()) {
+            (false, true) => "brownout",
+            (true, false) => "power_restored",
+            _ => continue,
+        };
+        if let Some(mut program) = program {
+            program.vm.raise(event, vec![Value::Num(supply as f64)]);
+        }
+        script_events.send(ScriptEvent {
+            name: event.to_string(),
+            args: vec![Value::from_entity(entity), Value::Num(supply as f64)],
+        });
+    }
+}
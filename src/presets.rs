@@ -0,0 +1,270 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use std::f32::consts::{FRAC_PI_3, TAU};
+
+use super::director::MissionDirector;
+use super::level::{self, LevelSprites, Star};
+use super::physics::{Kinimatics, PhysicsConfig};
+use super::ships::PlayerShip;
+
+pub struct PresetsPlugin;
+
+impl Plugin for PresetsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PresetMenu>()
+            .add_system(preset_menu_system);
+    }
+}
+
+/// Mass of a star in the presets, about the same as the default sun.
+const STAR_MASS: f32 = 2e15;
+
+/// The player's ships are put on a circular orbit this many times further out
+/// than the furthest body of a preset, where they can watch without being
+/// pulled about much.
+const OBSERVER_DISTANCE: f32 = 2.5;
+
+/// A classic n-body configuration the sandbox can be reset to.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Preset {
+    /// Two equal stars circling each other, with a planet circling both.
+    BinaryStar,
+    /// Three equal bodies chasing each other round a figure of eight
+    /// (Chenciner and Montgomery's solution).
+    FigureEight,
+    /// A star and a planet, with a trojan at each of the planet's L4 and L5
+    /// points, leading and trailing it by 60 degrees.
+    Trojans,
+}
+
+impl Preset {
+    pub const ALL: [Preset; 3] = [Preset::BinaryStar, Preset::FigureEight, Preset::Trojans];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Preset::BinaryStar => "Binary star",
+            Preset::FigureEight => "Figure eight",
+            Preset::Trojans => "Lagrange trojans",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Preset::BinaryStar => "Two stars in a close orbit, and a planet going round both.",
+            Preset::FigureEight => "Three equal bodies sharing one figure-of-eight orbit.",
+            Preset::Trojans => "Asteroids held 60° ahead of and behind a planet by its star.",
+        }
+    }
+
+    /// Works out the bodies of the preset, with the velocities which keep
+    /// them on their orbits, for gravity as set by `physics`. Everything goes
+    /// round the origin, which is the preset's center of mass.
+    pub fn bodies(self, physics: &PhysicsConfig) -> Vec<PresetBody> {
+        match self {
+            Preset::BinaryStar => {
+                let (mass, separation, radius) = (STAR_MASS / 2.0, 200.0, 700.0);
+                // each star goes round the middle, half the separation out
+                let speed = (physics.mu(mass) / (2.0 * separation)).sqrt();
+                let planet_speed = (physics.mu(2.0 * mass) / radius).sqrt();
+                vec![
+                    PresetBody::star("Star A", mass, 7.5, Vec3::X, separation / 2.0, speed),
+                    PresetBody::star("Star B", mass, 7.5, -Vec3::X, separation / 2.0, speed),
+                    PresetBody::planet("Planet", 3.3e8, 5.0, Vec3::Y, radius, planet_speed),
+                ]
+            }
+            Preset::FigureEight => {
+                // the solution is given for G = m = 1, and scaled to fit
+                let (mass, scale) = (STAR_MASS / 2.0, 150.0);
+                let speed = (physics.mu(mass) / scale).sqrt();
+                let position = Vec3::new(0.970_004_4, -0.243_087_5, 0.0);
+                let velocity = Vec3::new(-0.932_407_4, -0.864_731_5, 0.0);
+                let body = |name, position: Vec3, velocity: Vec3| PresetBody {
+                    name,
+                    mass,
+                    radius: 5.0,
+                    position: position * scale,
+                    velocity: velocity * speed,
+                    star: true,
+                };
+                vec![
+                    body("Body A", position, -velocity / 2.0),
+                    body("Body B", -position, -velocity / 2.0),
+                    body("Body C", Vec3::ZERO, velocity),
+                ]
+            }
+            Preset::Trojans => {
+                // light enough next to the star for the trojans to be stable
+                let (planet_mass, radius) = (STAR_MASS * 1e-3, 400.0_f32);
+                let total = STAR_MASS + planet_mass;
+                let rate = (physics.mu(total) / radius.powi(3)).sqrt();
+                let star_at = -Vec3::X * radius * planet_mass / total;
+                // everything turns together round the center of mass
+                let turning = |position: Vec3| Vec3::new(-position.y, position.x, 0.0) * rate;
+                let body = |name, mass, radius, position: Vec3, star| PresetBody {
+                    name,
+                    mass,
+                    radius,
+                    position,
+                    velocity: turning(position),
+                    star,
+                };
+                let trojan =
+                    |angle: f32| star_at + Vec3::new(angle.cos(), angle.sin(), 0.0) * radius;
+                vec![
+                    body("Star", STAR_MASS, 7.5, star_at, true),
+                    body(
+                        "Planet",
+                        planet_mass,
+                        5.0,
+                        star_at + Vec3::X * radius,
+                        false,
+                    ),
+                    body("L4 trojan", 1.0, 1.5, trojan(FRAC_PI_3), false),
+                    body("L5 trojan", 1.0, 1.5, trojan(-FRAC_PI_3), false),
+                ]
+            }
+        }
+    }
+}
+
+/// One body of a [`Preset`].
+#[derive(Clone, Copy, Debug)]
+pub struct PresetBody {
+    pub name: &'static str,
+    pub mass: f32,
+    pub radius: f32,
+    pub position: Vec3,
+    pub velocity: Vec3,
+    /// Whether it lights the system.
+    pub star: bool,
+}
+
+impl PresetBody {
+    /// A star `distance` out from the origin along `out`, going round it
+    /// anticlockwise at `speed`.
+    fn star(
+        name: &'static str,
+        mass: f32,
+        radius: f32,
+        out: Vec3,
+        distance: f32,
+        speed: f32,
+    ) -> Self {
+        Self {
+            star: true,
+            ..Self::planet(name, mass, radius, out, distance, speed)
+        }
+    }
+
+    /// Like [`PresetBody::star`], for a body which doesn't shine.
+    fn planet(
+        name: &'static str,
+        mass: f32,
+        radius: f32,
+        out: Vec3,
+        distance: f32,
+        speed: f32,
+    ) -> Self {
+        Self {
+            name,
+            mass,
+            radius,
+            position: out * distance,
+            velocity: Vec3::new(-out.y, out.x, 0.0) * speed,
+            star: false,
+        }
+    }
+}
+
+/// Spawns the bodies of `preset`, and returns them.
+pub fn spawn_preset(
+    commands: &mut Commands,
+    sprites: &LevelSprites,
+    physics: &PhysicsConfig,
+    preset: Preset,
+) -> Vec<PresetBody> {
+    let bodies = preset.bodies(physics);
+    for body in bodies.iter() {
+        let entity = level::spawn_planet(
+            commands,
+            sprites,
+            body.radius,
+            body.mass,
+            body.position,
+            body.velocity,
+        );
+        commands.entity(entity).insert(Name::new(body.name));
+        if body.star {
+            commands.entity(entity).insert(Star);
+        }
+    }
+    bodies
+}
+
+/// Clears the arena for a fresh start: the mission is dropped, and `entities`
+/// despawned along with everything attached to them.
+pub fn clear_arena(commands: &mut Commands, entities: impl Iterator<Item = Entity>) {
+    commands.remove_resource::<MissionDirector>();
+    for entity in entities {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Resource which holds whether the presets menu is open. It's opened from the
+/// tray.
+#[derive(Resource, Default)]
+pub struct PresetMenu {
+    pub open: bool,
+}
+
+/// :SYSTEM: Shows the presets menu, and resets the sandbox to whichever
+/// preset is picked.
+///
+/// Everything flying is cleared away, and the mission with it, except for the
+/// player's ships, which are put on a wide orbit round the preset to watch.
+#[allow(clippy::type_complexity)]
+fn preset_menu_system(
+    mut commands: Commands,
+    mut contexts: EguiContexts,
+    mut menu: ResMut<PresetMenu>,
+    world: Query<Entity, (With<Kinimatics>, Without<Parent>, Without<PlayerShip>)>,
+    mut players: Query<(&mut Transform, &mut Kinimatics), (With<PlayerShip>, Without<Parent>)>,
+    sprites: Res<LevelSprites>,
+    physics: Res<PhysicsConfig>,
+) {
+    let mut picked = None;
+    let menu = &mut *menu;
+    egui::Window::new("Presets")
+        .open(&mut menu.open)
+        .resizable(false)
+        .show(contexts.ctx_mut(), |ui| {
+            for preset in Preset::ALL {
+                if ui.button(preset.label()).clicked() {
+                    picked = Some(preset);
+                }
+                ui.weak(preset.description());
+                ui.add_space(4.0);
+            }
+        });
+    let Some(preset) = picked else {
+        return;
+    };
+
+    clear_arena(&mut commands, world.iter());
+    let bodies = spawn_preset(&mut commands, &sprites, &physics, preset);
+
+    let furthest = bodies
+        .iter()
+        .map(|b| b.position.length())
+        .fold(0.0, f32::max);
+    let total: f32 = bodies.iter().map(|b| b.mass).sum();
+    let distance = furthest * OBSERVER_DISTANCE;
+    let speed = (physics.mu(total) / distance).sqrt();
+    let count = players.iter().len();
+    for (i, (mut transform, mut kin)) in players.iter_mut().enumerate() {
+        let angle = TAU * i as f32 / count as f32;
+        let out = Vec3::new(angle.cos(), angle.sin(), 0.0);
+        transform.translation = out * distance;
+        kin.velocity = Vec3::new(-out.y, out.x, 0.0) * speed;
+    }
+}
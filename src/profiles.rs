@@ -0,0 +1,298 @@
+//! Player profiles, which carry the player's progress from one game to the
+//! next: their library of ship programs, their best result in each mission,
+//! and the ship classes they've unlocked by winning missions.
+//!
+//! Profiles are kept in `profiles/<name>.ron`. Start the game with
+//! `--profile=<name>` to play as someone other than the default pilot, or
+//! switch from the profile panel.
+
+use std::collections::BTreeMap;
+
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use serde::{Deserialize, Serialize};
+
+use super::clock::format_met;
+use super::director::MissionDirector;
+use super::missions::{ObjectiveStatus, Objectives};
+
+pub struct ProfilesPlugin;
+
+impl Plugin for ProfilesPlugin {
+    fn build(&self, app: &mut App) {
+        let name = std::env::args()
+            .find_map(|a| a.strip_prefix("--profile=").map(|n| n.to_string()))
+            .unwrap_or_else(|| DEFAULT_PROFILE.to_string());
+        app.insert_resource(Profile::load_or_new(&name))
+            .init_resource::<ProfilePanel>()
+            .add_system(
+                mission_result_system
+                    .in_base_set(CoreSet::PostUpdate)
+                    .after(super::missions::objective_system),
+            )
+            .add_system(profile_panel_system);
+    }
+}
+
+/// Folder the profiles are kept in.
+const PROFILES_DIR: &str = "profiles";
+
+/// Profile played when none is given on the command line.
+const DEFAULT_PROFILE: &str = "pilot";
+
+/// A kind of ship the player can spawn. Only the shuttle is there from the
+/// start; the rest are unlocked by winning missions.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ShipClass {
+    Shuttle,
+    Courier,
+    Gunship,
+    Hauler,
+}
+
+impl ShipClass {
+    pub const ALL: [ShipClass; 4] = [
+        ShipClass::Shuttle,
+        ShipClass::Courier,
+        ShipClass::Gunship,
+        ShipClass::Hauler,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ShipClass::Shuttle => "Shuttle",
+            ShipClass::Courier => "Courier",
+            ShipClass::Gunship => "Gunship",
+            ShipClass::Hauler => "Hauler",
+        }
+    }
+
+    /// Different missions which have to be won to unlock the class.
+    pub fn wins_to_unlock(self) -> usize {
+        match self {
+            ShipClass::Shuttle => 0,
+            ShipClass::Courier => 1,
+            ShipClass::Gunship => 2,
+            ShipClass::Hauler => 4,
+        }
+    }
+
+    /// Frame mass, max thrust, burn rate and fuel of a ship of the class.
+    pub fn stats(self) -> (f32, f32, f32, f32) {
+        match self {
+            ShipClass::Shuttle => (20.0, 1000.0, 10.0, 1000.0),
+            ShipClass::Courier => (12.0, 1400.0, 12.0, 800.0),
+            ShipClass::Gunship => (35.0, 1800.0, 18.0, 1200.0),
+            ShipClass::Hauler => (60.0, 2200.0, 20.0, 3000.0),
+        }
+    }
+}
+
+/// How the player has done in one mission.
+#[derive(Serialize, Deserialize, Default, Clone, Copy, Debug)]
+pub struct MissionRecord {
+    pub attempts: u32,
+    pub wins: u32,
+    /// Shortest mission time the mission has been won in, in seconds.
+    pub best_time: Option<f64>,
+}
+
+/// Resource which holds the profile being played, as it is on disk. Anything
+/// that changes it saves it straight away.
+#[derive(Resource, Serialize, Deserialize, Clone, Debug)]
+pub struct Profile {
+    pub name: String,
+    /// Saved ship programs, by name.
+    pub programs: BTreeMap<String, String>,
+    /// Results, by the path of the mission's script.
+    pub results: BTreeMap<String, MissionRecord>,
+    pub unlocked: Vec<ShipClass>,
+}
+
+impl Profile {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            programs: BTreeMap::new(),
+            results: BTreeMap::new(),
+            unlocked: vec![ShipClass::Shuttle],
+        }
+    }
+
+    fn path(name: &str) -> String {
+        format!("{}/{}.ron", PROFILES_DIR, name)
+    }
+
+    pub fn load(name: &str) -> Result<Self, String> {
+        std::fs::read_to_string(Self::path(name))
+            .map_err(|e| e.to_string())
+            .and_then(|s| ron::from_str(&s).map_err(|e| e.to_string()))
+    }
+
+    /// The profile called `name`, or a fresh one if there isn't one yet.
+    pub fn load_or_new(name: &str) -> Self {
+        Self::load(name).unwrap_or_else(|e| {
+            info!("starting a new profile {} ({})", name, e);
+            Self::new(name)
+        })
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let text = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|e| e.to_string())?;
+        std::fs::create_dir_all(PROFILES_DIR).map_err(|e| e.to_string())?;
+        std::fs::write(Self::path(&self.name), text).map_err(|e| e.to_string())
+    }
+
+    /// Number of different missions won.
+    pub fn missions_won(&self) -> usize {
+        self.results.values().filter(|r| r.wins > 0).count()
+    }
+
+    pub fn is_unlocked(&self, class: ShipClass) -> bool {
+        self.unlocked.contains(&class)
+    }
+
+    /// Records an attempt at `mission`, and returns the ship classes it
+    /// unlocked.
+    pub fn record(&mut self, mission: &str, won: bool, time: f64) -> Vec<ShipClass> {
+        let record = self.results.entry(mission.to_string()).or_default();
+        record.attempts += 1;
+        if won {
+            record.wins += 1;
+            record.best_time = Some(record.best_time.map_or(time, |t| t.min(time)));
+        }
+
+        let won = self.missions_won();
+        let unlocked: Vec<_> = ShipClass::ALL
+            .into_iter()
+            .filter(|c| !self.is_unlocked(*c) && c.wins_to_unlock() <= won)
+            .collect();
+        self.unlocked.extend(unlocked.iter().copied());
+        unlocked
+    }
+}
+
+/// Resource which holds the state of the profile panel, which is opened from
+/// the pause menu.
+#[derive(Resource, Default)]
+pub struct ProfilePanel {
+    pub open: bool,
+    /// Name typed in to switch profiles.
+    switch_to: String,
+    status: String,
+}
+
+/// :SYSTEM: Records the result of each mission in the profile once it's won
+/// or lost, unlocking any ship classes the player has earned.
+fn mission_result_system(
+    objectives: Res<Objectives>,
+    director: Option<Res<MissionDirector>>,
+    asset_server: Res<AssetServer>,
+    mut profile: ResMut<Profile>,
+    mut panel: ResMut<ProfilePanel>,
+    mut recorded: Local<Option<f64>>,
+) {
+    let Some(ended_at) = objectives.ended_at else {
+        return;
+    };
+    if *recorded == Some(ended_at) {
+        return;
+    }
+    *recorded = Some(ended_at);
+    let Some(mission) = director
+        .and_then(|d| asset_server.get_handle_path(&d.source))
+        .map(|p| p.path().display().to_string())
+    else {
+        return;
+    };
+
+    let won = objectives.outcome() == ObjectiveStatus::Complete;
+    let unlocked = profile.record(&mission, won, ended_at);
+    if !unlocked.is_empty() {
+        let names: Vec<_> = unlocked.iter().map(|c| c.label()).collect();
+        panel.status = format!("unlocked {}", names.join(", "));
+    }
+    if let Err(e) = profile.save() {
+        warn!("couldn't save profile {}: {}", profile.name, e);
+    }
+}
+
+/// :SYSTEM: Shows the profile's results, ship classes and program library,
+/// and switches between profiles.
+fn profile_panel_system(
+    mut contexts: EguiContexts,
+    mut panel: ResMut<ProfilePanel>,
+    mut profile: ResMut<Profile>,
+) {
+    if !panel.open {
+        return;
+    }
+    let panel = &mut *panel;
+    let mut open = true;
+    egui::Window::new(format!("Profile: {}", profile.name))
+        .open(&mut open)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.horizontal(|ui| {
+                ui.text_edit_singleline(&mut panel.switch_to);
+                if ui.button("Switch").clicked() && !panel.switch_to.is_empty() {
+                    *profile = Profile::load_or_new(&panel.switch_to);
+                    panel.status = format!("playing as {}", profile.name);
+                }
+            });
+            if !panel.status.is_empty() {
+                ui.label(&panel.status);
+            }
+
+            ui.separator();
+            ui.heading("Missions");
+            if profile.results.is_empty() {
+                ui.weak("none played yet");
+            }
+            egui::Grid::new("results").striped(true).show(ui, |ui| {
+                for (mission, record) in profile.results.iter() {
+                    ui.label(mission);
+                    ui.label(format!("{}/{} won", record.wins, record.attempts));
+                    ui.label(record.best_time.map_or("-".to_string(), |t| {
+                        format!("best {}", format_met(t as f32))
+                    }));
+                    ui.end_row();
+                }
+            });
+
+            ui.separator();
+            ui.heading("Ship classes");
+            let won = profile.missions_won();
+            for class in ShipClass::ALL {
+                if profile.is_unlocked(class) {
+                    ui.label(class.label());
+                } else {
+                    let needed = class.wins_to_unlock().saturating_sub(won);
+                    ui.weak(format!("{} (win {} more)", class.label(), needed));
+                }
+            }
+
+            ui.separator();
+            ui.heading("Program library");
+            if profile.programs.is_empty() {
+                ui.weak("save programs from the program editor");
+            }
+            let mut delete = None;
+            for (name, source) in profile.programs.iter() {
+                ui.horizontal(|ui| {
+                    ui.label(name);
+                    ui.weak(format!("{} lines", source.lines().count()));
+                    if ui.small_button("Delete").clicked() {
+                        delete = Some(name.clone());
+                    }
+                });
+            }
+            if let Some(name) = delete {
+                profile.programs.remove(&name);
+                if let Err(e) = profile.save() {
+                    panel.status = format!("couldn't save: {}", e);
+                }
+            }
+        });
+    panel.open = open;
+}
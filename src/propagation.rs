@@ -0,0 +1,291 @@
+use bevy::prelude::*;
+
+use super::orbit::propagate_kepler;
+use super::physics::{Attitude, PhysicsConfig};
+use super::ships::{Engine, ThrottleProgram};
+
+/// A body as the propagator sees it. Everything the physics needs to move it
+/// forward, and nothing else, so the live simulation and anything predicting
+/// it work from exactly the same numbers.
+#[derive(Clone, Copy, Debug)]
+pub struct PropagatedBody {
+    pub position: Vec3,
+    pub velocity: Vec3,
+    /// Acceleration over the last step.
+    pub acceleration: Vec3,
+    pub mass: f32,
+    /// Force from the body's own engine, if it has one, as if the body were
+    /// pointing straight up. It turns with the body, but is held constant over
+    /// a step.
+    pub thrust: Vec3,
+    /// Radians counterclockwise about the z axis.
+    pub angle: f32,
+    /// How the body turns, if it can (see [`Attitude`]).
+    pub attitude: Option<Attitude>,
+    /// Scales the time step for this body alone (see
+    /// [`TimeDilation`](super::debug_tools::TimeDilation)). It still pulls on
+    /// everything else as normal.
+    pub time_scale: f32,
+    /// Whether the body pulls on others (see
+    /// [`GravitySource`](super::physics::GravitySource)).
+    pub source: bool,
+    /// Whether the body is pulled on by sources (see
+    /// [`GravityAffected`](super::physics::GravityAffected)).
+    pub affected: bool,
+    /// Index of the body this one is on rails around (see
+    /// [`OnRails`](super::level::OnRails)). It keeps exactly to its Kepler
+    /// orbit around that body, whatever else pulls on it.
+    pub rails: Option<usize>,
+}
+
+impl PropagatedBody {
+    pub fn new(position: Vec3, velocity: Vec3, mass: f32) -> Self {
+        Self {
+            position,
+            velocity,
+            acceleration: Vec3::ZERO,
+            mass,
+            thrust: Vec3::ZERO,
+            angle: 0.0,
+            attitude: None,
+            time_scale: 1.0,
+            source: true,
+            affected: true,
+            rails: None,
+        }
+    }
+
+    fn is_finite(&self) -> bool {
+        self.position.is_finite()
+            && self.velocity.is_finite()
+            && self.thrust.is_finite()
+            && self.angle.is_finite()
+            && self.attitude.is_none_or(|a| a.is_finite())
+            && self.mass.is_finite()
+            && self.time_scale.is_finite()
+    }
+
+    /// Whether the body pulls on others, and can be pulled on.
+    fn has_mass(&self) -> bool {
+        self.mass.is_finite() && self.mass > 0.0
+    }
+
+    /// Makes a body which has gone bad safe to step: anything that isn't a
+    /// number is zeroed, and a body with no usable position stays put. One
+    /// without a usable mass is left massless, so it only coasts.
+    fn sanitize(&mut self) {
+        if !self.mass.is_finite() {
+            self.mass = 0.0;
+        }
+        if !self.position.is_finite() {
+            self.position = Vec3::ZERO;
+            self.velocity = Vec3::ZERO;
+        }
+        if !self.velocity.is_finite() {
+            self.velocity = Vec3::ZERO;
+        }
+        if !self.thrust.is_finite() {
+            self.thrust = Vec3::ZERO;
+        }
+        if !self.time_scale.is_finite() {
+            self.time_scale = 1.0;
+        }
+        if !self.angle.is_finite() {
+            self.angle = 0.0;
+        }
+        if let Some(attitude) = &mut self.attitude {
+            attitude.sanitize();
+        }
+        self.acceleration = Vec3::ZERO;
+    }
+}
+
+/// Moves every body forward by `dt` seconds of game time, under their mutual
+/// gravity and their own thrust, turns them as their reaction wheels say, and
+/// returns how many of them had to be sanitized.
+///
+/// Forces are added up in the order the bodies are given, so the same bodies
+/// in the same order always come out the same. Gravity is softened by
+/// [`PhysicsConfig::softening`], so bodies on top of each other feel no pull
+/// rather than an infinite one. A body whose state isn't a number is zeroed
+/// rather than stepped, and a body without a positive mass coasts without
+/// pulling or being pulled, so one bad body can't poison the rest.
+///
+/// Bodies on rails are moved along their orbits once everything else has
+/// been, parents first. Any left over, because they're on rails around each
+/// other, coast.
+pub fn step(bodies: &mut [PropagatedBody], dt: f32, config: &PhysicsConfig) -> usize {
+    let dt = dt * config.time_scale;
+    let g = config.g();
+    let softening = config.softening * config.softening;
+
+    let mut sanitized = 0;
+    for body in bodies.iter_mut().filter(|b| !b.is_finite()) {
+        body.sanitize();
+        sanitized += 1;
+    }
+    let len = bodies.len();
+    for (i, body) in bodies.iter_mut().enumerate() {
+        if body
+            .rails
+            .is_some_and(|parent| parent >= len || parent == i)
+        {
+            body.rails = None;
+        }
+    }
+    let before = bodies.to_vec();
+
+    // only sources pull, so each pair with a source in it is worked out once,
+    // and pairs of light bodies not at all
+    let mut forces = vec![Vec3::ZERO; bodies.len()];
+    let sources = bodies
+        .iter()
+        .enumerate()
+        .filter(|(_, a)| a.source && a.has_mass());
+    for (i, a) in sources {
+        for (j, b) in bodies.iter().enumerate() {
+            if j == i || !b.has_mass() || (b.source && j < i) {
+                continue;
+            }
+            // the masses are kept apart, since real ones overflow an f32 when
+            // multiplied
+            let offset = b.position - a.position;
+            let distance_squared = offset.length_squared() + softening;
+            let force =
+                offset * (g * a.mass * (b.mass / distance_squared)) / distance_squared.sqrt();
+            if !force.is_finite() {
+                continue;
+            }
+            if b.affected {
+                forces[j] -= force;
+            }
+            if b.source && a.affected {
+                forces[i] += force;
+            }
+        }
+    }
+
+    for (body, force) in bodies.iter_mut().zip(forces) {
+        if body.rails.is_some() {
+            continue;
+        }
+        let before = *body;
+        let dt = dt * body.time_scale;
+        let thrust = Quat::from_rotation_z(body.angle).mul_vec3(body.thrust);
+        body.acceleration = match body.has_mass() {
+            true => (force + thrust) / body.mass,
+            false => Vec3::ZERO,
+        };
+        body.velocity += body.acceleration * dt;
+        body.position += body.velocity * dt;
+        if let Some(attitude) = &mut body.attitude {
+            body.angle += attitude.step(dt);
+        }
+        if !body.is_finite() || !body.acceleration.is_finite() {
+            *body = before;
+            body.sanitize();
+            sanitized += 1;
+        }
+        debug_assert!(body.position.is_finite() && body.velocity.is_finite());
+    }
+
+    let mut moved: Vec<bool> = bodies.iter().map(|b| b.rails.is_none()).collect();
+    loop {
+        let mut progress = false;
+        for i in 0..len {
+            let Some(parent) = bodies[i].rails else {
+                continue;
+            };
+            if moved[i] || !moved[parent] {
+                continue;
+            }
+            moved[i] = true;
+            progress = true;
+
+            let (body, parent_before) = (before[i], before[parent]);
+            let (position, velocity) = propagate_kepler(
+                body.position - parent_before.position,
+                body.velocity - parent_before.velocity,
+                config.mu(parent_before.mass),
+                dt,
+            );
+            let parent = bodies[parent];
+            let body = &mut bodies[i];
+            body.position = parent.position + position;
+            body.velocity = parent.velocity + velocity;
+            body.acceleration = match dt {
+                dt if dt != 0.0 => (body.velocity - before[i].velocity) / dt,
+                _ => Vec3::ZERO,
+            };
+            if !body.is_finite() || !body.acceleration.is_finite() {
+                *body = before[i];
+                body.sanitize();
+                sanitized += 1;
+            }
+        }
+        if !progress {
+            break;
+        }
+    }
+    for (body, _) in bodies.iter_mut().zip(moved).filter(|(_, moved)| !moved) {
+        body.position += body.velocity * dt;
+    }
+    sanitized
+}
+
+/// A [`ThrottleProgram`] one of the bodies follows as it's propagated.
+pub struct PlannedControls<'a> {
+    /// Index of the body.
+    pub body: usize,
+    pub program: &'a ThrottleProgram,
+    /// The body's engine, as it is now.
+    pub engine: Engine,
+}
+
+impl PlannedControls<'_> {
+    /// Sets the body's thrust and turn for a step of `dt` seconds starting at
+    /// `elapsed`, if the program has started by then.
+    fn fly(&self, bodies: &mut [PropagatedBody], elapsed: f64, dt: f32) {
+        let (Some(step), Some(body)) = (self.program.active(elapsed), bodies.get_mut(self.body))
+        else {
+            return;
+        };
+        let mut engine = self.engine.clone();
+        let mut attitude = body.attitude.unwrap_or_default();
+        step.fly(body.angle, &mut attitude, &mut engine, dt);
+        body.thrust = engine.thrust_vector(Quat::IDENTITY);
+        if body.attitude.is_some() {
+            body.attitude = Some(attitude);
+        }
+    }
+}
+
+/// Takes `n` steps of `dt` seconds from `bodies`, starting at `elapsed` on the
+/// [`SimulationClock`](super::clock::SimulationClock), and returns where
+/// everything is after each of them. Bodies with `plans` follow them along
+/// the way; the rest hold their thrust.
+pub fn propagate_n_steps(
+    bodies: &[PropagatedBody],
+    plans: &[PlannedControls],
+    elapsed: f64,
+    dt: f32,
+    n: usize,
+    config: &PhysicsConfig,
+) -> Vec<Vec<PropagatedBody>> {
+    let mut current = bodies.to_vec();
+    let mut steps = Vec::with_capacity(n);
+    for i in 0..n {
+        let at = elapsed + i as f64 * dt as f64;
+        for plan in plans {
+            plan.fly(&mut current, at, dt);
+        }
+        let _ = step(&mut current, dt, config);
+        steps.push(current.clone());
+    }
+    steps
+}
+
+/// Radians counterclockwise about the z axis that `rotation` turns through.
+pub fn angle_of(rotation: Quat) -> f32 {
+    rotation.to_euler(EulerRot::XYZ).2
+}
@@ -0,0 +1,145 @@
+use std::time::{Duration, Instant};
+
+use bevy::prelude::*;
+use bevy::time::TimeUpdateStrategy;
+use bevy_egui::{egui, EguiContexts};
+
+use super::clock::SimulationClock;
+
+pub struct RealTimePlugin;
+
+impl Plugin for RealTimePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(RealTime::new(std::env::args().any(|a| a == "--realtime")))
+            .add_system(
+                pacing_system
+                    .in_base_set(CoreSet::Last)
+                    .after(super::debug_tools::step_through_system),
+            )
+            .add_system(deadline_panel_system);
+    }
+}
+
+/// Wall clock time between ticks in real-time mode.
+pub const TICK: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+/// A tick finishing later than this past its deadline counts as missed.
+const DEADLINE_SLACK: Duration = Duration::from_millis(2);
+
+/// When a tick ended, for anything outside the game which needs to line its
+/// own clock up with the simulation.
+#[derive(Clone, Copy, Debug)]
+pub struct TickStamp {
+    pub tick: u64,
+    /// Mission elapsed time, in seconds.
+    pub simulation: f64,
+    /// Wall clock time since real-time mode started.
+    pub wall: Duration,
+}
+
+/// Resource which paces the game for hardware-in-the-loop use, where external
+/// controllers need the simulation to keep to the wall clock. Start the game
+/// with `--realtime` to turn it on.
+///
+/// Every frame advances the simulation by exactly [`TICK`], and then waits for
+/// the wall clock to catch up. A frame which takes too long misses its
+/// deadline; the simulation doesn't rush to make up for it, so missed
+/// deadlines show up as the simulation falling behind the wall clock.
+#[derive(Resource)]
+pub struct RealTime {
+    pub enabled: bool,
+    started: Instant,
+    deadline: Instant,
+    pub ticks: u64,
+    pub missed: u64,
+    /// How late the most recently missed deadline was.
+    pub last_overrun: Duration,
+    pub last_stamp: Option<TickStamp>,
+}
+
+impl RealTime {
+    pub fn new(enabled: bool) -> Self {
+        let now = Instant::now();
+        Self {
+            enabled,
+            started: now,
+            deadline: now + TICK,
+            ticks: 0,
+            missed: 0,
+            last_overrun: Duration::ZERO,
+            last_stamp: None,
+        }
+    }
+}
+
+/// :SYSTEM: Holds each frame until its deadline, and fixes the simulation's
+/// time step, while real-time mode is on. Runs at the very end of the frame.
+fn pacing_system(
+    mut realtime: ResMut<RealTime>,
+    mut strategy: ResMut<TimeUpdateStrategy>,
+    clock: Res<SimulationClock>,
+) {
+    if !realtime.enabled {
+        return;
+    }
+    *strategy = TimeUpdateStrategy::ManualDuration(TICK);
+
+    let now = Instant::now();
+    if now > realtime.deadline + DEADLINE_SLACK {
+        realtime.missed += 1;
+        realtime.last_overrun = now - realtime.deadline;
+        warn!(
+            "real-time: tick {} missed its deadline by {:?}",
+            realtime.ticks, realtime.last_overrun
+        );
+        realtime.deadline = now;
+    } else {
+        // sleep through most of the wait, then spin, since sleeps overshoot
+        let remaining = realtime.deadline.saturating_duration_since(now);
+        if remaining > Duration::from_millis(2) {
+            std::thread::sleep(remaining - Duration::from_millis(1));
+        }
+        while Instant::now() < realtime.deadline {
+            std::hint::spin_loop();
+        }
+    }
+
+    realtime.ticks += 1;
+    realtime.last_stamp = Some(TickStamp {
+        tick: realtime.ticks,
+        simulation: clock.elapsed,
+        wall: realtime.deadline - realtime.started,
+    });
+    realtime.deadline += TICK;
+}
+
+/// :SYSTEM: Shows how well real-time mode is keeping to its deadlines.
+fn deadline_panel_system(mut contexts: EguiContexts, realtime: Res<RealTime>) {
+    if !realtime.enabled {
+        return;
+    }
+
+    egui::Area::new("realtime")
+        .anchor(egui::Align2::RIGHT_BOTTOM, [-8.0, -8.0])
+        .show(contexts.ctx_mut(), |ui| {
+            if let Some(stamp) = realtime.last_stamp {
+                // how far the simulation has fallen behind the wall clock
+                let lag = stamp.wall.as_secs_f64() - stamp.simulation;
+                ui.label(format!("REAL-TIME tick {}", stamp.tick));
+                ui.label(format!("behind by {:.1} ms", lag * 1000.0));
+            }
+            let color = if realtime.missed > 0 {
+                egui::Color32::YELLOW
+            } else {
+                egui::Color32::LIGHT_GRAY
+            };
+            ui.colored_label(
+                color,
+                format!(
+                    "{} missed, last by {:.1} ms",
+                    realtime.missed,
+                    realtime.last_overrun.as_secs_f64() * 1000.0
+                ),
+            );
+        });
+}
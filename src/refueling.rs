@@ -0,0 +1,502 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+use super::balance::Balance;
+use super::docking::Docked;
+use super::physics::SimulationSet;
+use super::scripting::{ScriptEvent, ShipProgram, Value};
+use super::ships::{CargoHold, Commodity, Controlled, Engine, MissileLauncher, Ship};
+
+pub struct RefuelingPlugin;
+
+impl Plugin for RefuelingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<TransferRequest>()
+            .add_system(transfer_request_system.in_set(SimulationSet))
+            .add_system(
+                transfer_system
+                    .after(transfer_request_system)
+                    .after(super::physics::kinimatics_system)
+                    .in_set(SimulationSet),
+            )
+            .add_system(transfer_panel_system);
+    }
+}
+
+/// Ships further apart than this can't pass stores between them, unless they
+/// are docked to each other.
+pub const TRANSFER_RANGE: f32 = 50.0;
+
+/// The things ships can hand over to each other.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Stores {
+    /// Fuel for the [`Engine`].
+    Fuel,
+    /// Missiles for the [`MissileLauncher`]. These only move whole.
+    Ammo,
+    /// Whatever is in the [`CargoHold`].
+    Cargo,
+}
+
+impl Stores {
+    pub const ALL: [Stores; 3] = [Stores::Fuel, Stores::Ammo, Stores::Cargo];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Stores::Fuel => "fuel",
+            Stores::Ammo => "ammo",
+            Stores::Cargo => "cargo",
+        }
+    }
+
+    pub fn parse(name: &str) -> Result<Self, String> {
+        Stores::ALL
+            .into_iter()
+            .find(|s| s.name() == name)
+            .ok_or_else(|| format!("can't transfer \"{}\"", name))
+    }
+
+    /// The fastest these stores can be moved, in units per second.
+    pub fn max_rate(self) -> f32 {
+        match self {
+            Stores::Fuel => 100.0,
+            Stores::Ammo => 0.5,
+            Stores::Cargo => 20.0,
+        }
+    }
+
+    /// Seconds it takes to hook up a line before anything flows.
+    pub fn connect_time(self) -> f32 {
+        match self {
+            Stores::Fuel => 2.0,
+            Stores::Ammo => 5.0,
+            Stores::Cargo => 4.0,
+        }
+    }
+}
+
+/// :COMPONENT: A line running from this ship to `other`, carrying `stores`.
+///
+/// A positive rate moves stores from this ship into `other`, a negative rate
+/// pulls them out of `other` into this ship. Nothing moves until the line has
+/// been connected for [`Stores::connect_time`]. The line is removed when the
+/// giving ship runs out, the receiving ship fills up, or the ships drift apart.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct TransferLine {
+    pub other: Entity,
+    pub stores: Stores,
+    /// Units per second.
+    pub rate: f32,
+    /// Seconds until the line is connected.
+    pub connecting: f32,
+    /// Stores owed to the receiver, but not moved yet, because only whole
+    /// missiles can be.
+    owed: f32,
+}
+
+/// :EVENT: Asks for a ship to start or stop moving stores.
+#[derive(Clone, Copy, Debug)]
+pub enum TransferRequest {
+    Start {
+        ship: Entity,
+        other: Entity,
+        stores: Stores,
+        rate: f32,
+    },
+    Stop {
+        ship: Entity,
+    },
+}
+
+/// Whether two ships are close enough, or docked, to pass stores between them.
+pub fn can_transfer(
+    a: (Entity, &GlobalTransform, Option<&Docked>),
+    b: (Entity, &GlobalTransform, Option<&Docked>),
+) -> bool {
+    let (e1, t1, d1) = a;
+    let (e2, t2, d2) = b;
+
+    let docked = d1.is_some_and(|d| d.to == e2) || d2.is_some_and(|d| d.to == e1);
+    docked || t1.translation().distance(t2.translation()) <= TRANSFER_RANGE
+}
+
+/// How much of `stores` a ship has, and how much it can hold, or `None` if it
+/// has nowhere to keep them. Launchers hold a full load of missiles, as set by
+/// the [`Balance`].
+pub fn level(
+    stores: Stores,
+    engine: Option<&Engine>,
+    launcher: Option<&MissileLauncher>,
+    hold: Option<&CargoHold>,
+    balance: &Balance,
+) -> Option<(f32, f32)> {
+    match stores {
+        Stores::Fuel => engine.map(|e| (e.fuel, e.fuel_capacity)),
+        Stores::Ammo => launcher.map(|l| (l.ammo as f32, balance.launcher.ammo as f32)),
+        Stores::Cargo => hold.map(|h| (h.cargo, h.capacity)),
+    }
+}
+
+/// :SYSTEM: Connects and disconnects transfer lines.
+fn transfer_request_system(mut commands: Commands, mut requests: EventReader<TransferRequest>) {
+    for request in requests.iter() {
+        match *request {
+            TransferRequest::Start {
+                ship,
+                other,
+                stores,
+                rate,
+            } => {
+                if ship == other {
+                    continue;
+                }
+                let Some(mut ship) = commands.get_entity(ship) else {
+                    continue;
+                };
+                ship.insert(TransferLine {
+                    other,
+                    stores,
+                    rate: rate.clamp(-stores.max_rate(), stores.max_rate()),
+                    connecting: stores.connect_time(),
+                    owed: 0.0,
+                });
+            }
+            TransferRequest::Stop { ship } => {
+                if let Some(mut ship) = commands.get_entity(ship) {
+                    ship.remove::<TransferLine>();
+                }
+            }
+        }
+    }
+}
+
+/// :SYSTEM: Moves stores along every connected transfer line. Stores are only
+/// ever moved, never created or destroyed, and the lines are closed with a
+/// `tank_full`, `tank_empty` or `transfer_stopped` [`ScriptEvent`] once they
+/// can't carry on. The ship which ran the line gets the same event on its
+/// program, with the other ship and the stores.
+#[allow(clippy::type_complexity)]
+fn transfer_system(
+    mut commands: Commands,
+    mut lines: Query<(Entity, &mut TransferLine)>,
+    mut ships: Query<(
+        Option<&mut Engine>,
+        Option<&mut MissileLauncher>,
+        Option<&mut CargoHold>,
+        &GlobalTransform,
+        Option<&Docked>,
+        Option<&mut ShipProgram>,
+        Option<&Commodity>,
+    )>,
+    mut script_events: EventWriter<ScriptEvent>,
+    balance: Res<Balance>,
+    time: Res<Time>,
+) {
+    // holds taking on a new commodity
+    let mut adopted = Vec::new();
+    for (entity, mut line) in lines.iter_mut() {
+        let stores = line.stores;
+        let mut close =
+            |event: &str, subject: Entity, other: Entity, program: Option<Mut<ShipProgram>>| {
+                commands.entity(entity).remove::<TransferLine>();
+                if let Some(mut program) = program {
+                    let partner = if subject == entity { other } else { subject };
+                    program.vm.raise(
+                        event,
+                        vec![
+                            Value::from_entity(partner),
+                            Value::Str(stores.name().to_string()),
+                        ],
+                    );
+                }
+                script_events.send(ScriptEvent {
+                    name: event.to_string(),
+                    args: vec![
+                        Value::from_entity(subject),
+                        Value::from_entity(other),
+                        Value::Str(stores.name().to_string()),
+                    ],
+                });
+            };
+
+        let Ok([a, b]) = ships.get_many_mut([entity, line.other]) else {
+            close("transfer_stopped", entity, line.other, None);
+            continue;
+        };
+        let (a_engine, a_launcher, a_hold, a_transform, a_docked, program, a_commodity) = a;
+        let (b_engine, b_launcher, b_hold, b_transform, b_docked, _, b_commodity) = b;
+
+        let a_level = level(
+            stores,
+            a_engine.as_deref(),
+            a_launcher.as_deref(),
+            a_hold.as_deref(),
+            &balance,
+        );
+        let b_level = level(
+            stores,
+            b_engine.as_deref(),
+            b_launcher.as_deref(),
+            b_hold.as_deref(),
+            &balance,
+        );
+        let (Some(a_level), Some(b_level)) = (a_level, b_level) else {
+            close("transfer_stopped", entity, line.other, program);
+            continue;
+        };
+        if !can_transfer(
+            (entity, a_transform, a_docked),
+            (line.other, b_transform, b_docked),
+        ) {
+            close("transfer_stopped", entity, line.other, program);
+            continue;
+        }
+
+        let dt = time.delta_seconds();
+        if line.connecting > 0.0 {
+            line.connecting -= dt;
+            continue;
+        }
+
+        let giving = line.rate >= 0.0;
+        let (giver, receiver) = if giving {
+            (entity, line.other)
+        } else {
+            (line.other, entity)
+        };
+        let ((from, _), (to, room)) = if giving {
+            (a_level, b_level)
+        } else {
+            (b_level, a_level)
+        };
+        let (given, held) = if giving {
+            (a_commodity, b_commodity)
+        } else {
+            (b_commodity, a_commodity)
+        };
+        if stores == Stores::Cargo {
+            // different commodities don't go in the same hold
+            if let (Some(given), Some(held)) = (given, held) {
+                if given != held && to > 0.0 {
+                    close("transfer_stopped", entity, line.other, program);
+                    continue;
+                }
+            }
+            if let Some(given) = given.filter(|g| held != Some(*g)) {
+                adopted.push((receiver, given.clone()));
+            }
+        }
+
+        line.owed += line.rate.abs() * dt;
+        let mut amount = line.owed.min(from).min((room - to).max(0.0));
+        if stores == Stores::Ammo {
+            amount = amount.floor();
+        }
+        line.owed -= amount;
+
+        // positive moves stores from a to b
+        let delta = if giving { amount } else { -amount };
+        match stores {
+            Stores::Fuel => {
+                let (Some(mut a), Some(mut b)) = (a_engine, b_engine) else {
+                    unreachable!()
+                };
+                a.fuel -= delta;
+                b.fuel += delta;
+            }
+            Stores::Ammo => {
+                let (Some(mut a), Some(mut b)) = (a_launcher, b_launcher) else {
+                    unreachable!()
+                };
+                a.ammo = (a.ammo as f32 - delta) as u32;
+                b.ammo = (b.ammo as f32 + delta) as u32;
+            }
+            Stores::Cargo => {
+                let (Some(mut a), Some(mut b)) = (a_hold, b_hold) else {
+                    unreachable!()
+                };
+                a.cargo -= delta;
+                b.cargo += delta;
+            }
+        }
+
+        if from - amount <= 0.0 {
+            close("tank_empty", giver, receiver, program);
+        } else if to + amount >= room {
+            close("tank_full", receiver, giver, program);
+        }
+    }
+
+    for (receiver, commodity) in adopted {
+        commands.entity(receiver).insert(commodity);
+    }
+}
+
+/// Which stores the transfer panel is moving, and how fast, as a fraction of
+/// the most they can be moved.
+struct PanelSettings {
+    stores: Stores,
+    rate: f32,
+}
+
+impl Default for PanelSettings {
+    fn default() -> Self {
+        Self {
+            stores: Stores::Fuel,
+            rate: 0.5,
+        }
+    }
+}
+
+/// :SYSTEM: Lets the player move stores between the controlled ship and any
+/// ship it is docked with or close to.
+#[allow(clippy::type_complexity)]
+fn transfer_panel_system(
+    mut contexts: EguiContexts,
+    player: Query<
+        (
+            Entity,
+            &GlobalTransform,
+            Option<&Docked>,
+            Option<&TransferLine>,
+        ),
+        With<Controlled>,
+    >,
+    ships: Query<
+        (
+            Entity,
+            Option<&Engine>,
+            Option<&MissileLauncher>,
+            Option<&CargoHold>,
+            &GlobalTransform,
+            Option<&Docked>,
+            Option<&Name>,
+        ),
+        With<Ship>,
+    >,
+    mut requests: EventWriter<TransferRequest>,
+    balance: Res<Balance>,
+    mut settings: Local<PanelSettings>,
+) {
+    let Ok((ship, transform, docked, line)) = player.get_single() else {
+        return;
+    };
+
+    let partners: Vec<_> = ships
+        .iter()
+        .filter(|(e, .., t, d, _)| {
+            *e != ship && can_transfer((ship, transform, docked), (*e, t, *d))
+        })
+        .collect();
+    if partners.is_empty() && line.is_none() {
+        return;
+    }
+
+    let stores = settings.stores;
+    let level_of = |entity: Entity, stores: Stores| {
+        ships
+            .get(entity)
+            .ok()
+            .and_then(|(_, e, l, h, ..)| level(stores, e, l, h, &balance))
+    };
+    let gauge = |ui: &mut egui::Ui, level: Option<(f32, f32)>| match level {
+        Some((amount, capacity)) => {
+            let fill = if capacity > 0.0 {
+                amount / capacity
+            } else {
+                0.0
+            };
+            ui.add(egui::ProgressBar::new(fill).text(format!("{:.0} / {:.0}", amount, capacity)));
+        }
+        None => {
+            ui.label(format!("no {}", stores.name()));
+        }
+    };
+
+    egui::Window::new("Transfer").show(contexts.ctx_mut(), |ui| {
+        ui.horizontal(|ui| {
+            for option in Stores::ALL {
+                ui.selectable_value(&mut settings.stores, option, option.name());
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label("This ship");
+            gauge(ui, level_of(ship, stores));
+        });
+        let rate = settings.rate * stores.max_rate();
+        ui.add(
+            egui::Slider::new(&mut settings.rate, 0.05..=1.0)
+                .show_value(false)
+                .text(format!("{:.1}/s", rate)),
+        );
+        ui.separator();
+
+        for (other, .., name) in partners {
+            ui.horizontal(|ui| {
+                match name {
+                    Some(name) => ui.label(name.as_str()),
+                    None => ui.label(format!("{:?}", other)),
+                };
+                gauge(ui, level_of(other, stores));
+
+                if ui.button("Give").clicked() {
+                    requests.send(TransferRequest::Start {
+                        ship,
+                        other,
+                        stores,
+                        rate,
+                    });
+                }
+                if ui.button("Take").clicked() {
+                    requests.send(TransferRequest::Start {
+                        ship,
+                        other,
+                        stores,
+                        rate: -rate,
+                    });
+                }
+            });
+        }
+
+        if let Some(line) = line {
+            ui.separator();
+            ui.horizontal(|ui| {
+                let direction = if line.rate >= 0.0 { "to" } else { "from" };
+                if line.connecting > 0.0 {
+                    ui.label(format!(
+                        "Connecting {} line {} {:?}, {:.0}s",
+                        line.stores.name(),
+                        direction,
+                        line.other,
+                        line.connecting.ceil()
+                    ));
+                } else {
+                    ui.label(format!(
+                        "Moving {} {} {:?} at {:.1}/s",
+                        line.stores.name(),
+                        direction,
+                        line.other,
+                        line.rate.abs()
+                    ));
+                }
+                if ui.button("Stop").clicked() {
+                    requests.send(TransferRequest::Stop { ship });
+                }
+            });
+
+            // how long until one side runs out or fills up
+            let ours = level_of(ship, line.stores);
+            let theirs = level_of(line.other, line.stores);
+            let ((from, _), (to, room)) = match (ours, theirs) {
+                (Some(a), Some(b)) if line.rate >= 0.0 => (a, b),
+                (Some(a), Some(b)) => (b, a),
+                _ => return,
+            };
+            let remaining = from.min(room - to).max(0.0) / line.rate.abs().max(f32::EPSILON);
+            ui.label(format!(
+                "done in {:.0}s",
+                line.connecting.max(0.0) + remaining
+            ));
+        }
+    });
+}
@@ -0,0 +1,383 @@
+use std::io::ErrorKind;
+use std::net::TcpListener;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::clock::SimulationClock;
+use super::net::Connection;
+use super::physics::{Attitude, Kinimatics, SimulationSet};
+use super::sensors::{ContactKind, Contacts, Sensor, SensorBundle};
+use super::ships::{Engine, Hull, LaunchMissile, PlayerShip, Throttle};
+
+pub struct RemotePlugin;
+
+impl Plugin for RemotePlugin {
+    fn build(&self, app: &mut App) {
+        let address = std::env::args().find_map(|a| a.strip_prefix("--listen=").map(String::from));
+        app.insert_resource(RemoteServer::new(address.as_deref()))
+            .add_system(remote_request_system.before(SimulationSet))
+            .add_system(
+                remote_turn_system
+                    .before(super::physics::kinimatics_system)
+                    .in_set(SimulationSet),
+            )
+            .add_system(remote_frame_system.after(SimulationSet));
+    }
+}
+
+/// Fastest a remote program may turn its ship, in radians per second. The same
+/// as for ship programs.
+const MAX_TURN_RATE: f32 = std::f32::consts::PI;
+
+/// A message from a client.
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Request {
+    /// Takes over one of the player's ships.
+    Claim { ship: u64 },
+    /// Hands the ship back.
+    Release,
+    /// Flies the claimed ship. Anything left out stays as it was.
+    Command {
+        throttle: Option<f32>,
+        turn: Option<f32>,
+        gimbal: Option<f32>,
+        /// Fires a missile at this id.
+        fire: Option<u64>,
+    },
+}
+
+/// A contact, as sent to clients.
+#[derive(Serialize, Debug)]
+struct ContactFrame {
+    id: u64,
+    kind: &'static str,
+    faction: Option<u32>,
+    position: [f32; 2],
+    velocity: [f32; 2],
+    distance: f32,
+    /// For missiles, which kind their signature shows.
+    signature: Option<&'static str>,
+    /// Whether jamming has thrown the position off.
+    jammed: bool,
+}
+
+/// A message to a client.
+#[derive(Serialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Reply {
+    /// Sent on connecting, with the ships which can be claimed.
+    Hello {
+        ships: Vec<u64>,
+    },
+    Claimed {
+        ship: u64,
+    },
+    Released {
+        ship: u64,
+    },
+    Error {
+        message: String,
+    },
+    /// What the claimed ship knows, sent once every tick.
+    Frame {
+        tick: u64,
+        met: f64,
+        ship: u64,
+        position: [f32; 2],
+        velocity: [f32; 2],
+        /// Radians counterclockwise from up, like the `heading` builtin.
+        heading: f32,
+        fuel: f32,
+        hull: f32,
+        contacts: Vec<ContactFrame>,
+    },
+}
+
+/// A connected external program.
+struct Client {
+    id: u64,
+    connection: Connection,
+    ship: Option<Entity>,
+}
+
+/// Resource which lets programs outside the game fly the player's ships, in
+/// any language. Start the game with `--listen=ADDRESS` (like
+/// `--listen=127.0.0.1:7878`) to turn it on.
+///
+/// Every message, both ways, is a JSON object prefixed with its length in
+/// bytes, as a big endian `u32`. Each has a `type`:
+///
+/// - the server sends `hello` with the ids of the ships which can be claimed;
+/// - the client sends `claim` with a `ship` id, and gets back `claimed`, or an
+///   `error`;
+/// - the server then sends a `frame` every tick, with the ship's position,
+///   velocity, heading, fuel, hull and sensor contacts;
+/// - the client sends `command` with any of `throttle` (0 to 1), `turn` (radians
+///   per second), `gimbal` (radians) and `fire` (an id to launch a missile at);
+/// - the client sends `release` to hand the ship back.
+///
+/// A ship can only be flown by one client at once. A client which disconnects
+/// leaves its ship with the engine cut.
+#[derive(Resource)]
+pub struct RemoteServer {
+    listener: Option<TcpListener>,
+    clients: Vec<Client>,
+    next_id: u64,
+    tick: u64,
+    last_elapsed: f64,
+}
+
+impl RemoteServer {
+    pub fn new(address: Option<&str>) -> Self {
+        let listener = address.and_then(|address| {
+            match TcpListener::bind(address).and_then(|l| l.set_nonblocking(true).map(|_| l)) {
+                Ok(listener) => {
+                    info!("remote: listening on {}", address);
+                    Some(listener)
+                }
+                Err(e) => {
+                    error!("remote: couldn't listen on {}: {}", address, e);
+                    None
+                }
+            }
+        });
+        Self {
+            listener,
+            clients: Vec::new(),
+            next_id: 0,
+            tick: 0,
+            last_elapsed: 0.0,
+        }
+    }
+}
+
+/// :COMPONENT: Marks a ship which is being flown by a client of the
+/// [`RemoteServer`].
+#[derive(Component, Clone, Copy, Debug)]
+pub struct RemoteControl {
+    pub client: u64,
+    /// Rate of rotation commanded by the client, in radians per second.
+    pub turn_rate: f32,
+}
+
+/// :SYSTEM: Accepts new clients, and carries out what they've asked for.
+#[allow(clippy::type_complexity)]
+fn remote_request_system(
+    mut commands: Commands,
+    mut server: ResMut<RemoteServer>,
+    mut ships: Query<
+        (
+            Entity,
+            Option<&mut Engine>,
+            Option<&mut RemoteControl>,
+            Option<&Sensor>,
+        ),
+        With<PlayerShip>,
+    >,
+    mut launches: EventWriter<LaunchMissile>,
+) {
+    let server = &mut *server;
+    let Some(listener) = &server.listener else {
+        return;
+    };
+
+    loop {
+        match listener.accept() {
+            Ok((stream, address)) => {
+                let Ok(connection) = Connection::new(stream) else {
+                    continue;
+                };
+                info!(
+                    "remote: client {} connected from {}",
+                    server.next_id, address
+                );
+                let mut client = Client {
+                    id: server.next_id,
+                    connection,
+                    ship: None,
+                };
+                server.next_id += 1;
+                client.connection.send(&Reply::Hello {
+                    ships: ships.iter().map(|(e, ..)| e.to_bits()).collect(),
+                });
+                server.clients.push(client);
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+            Err(e) => {
+                warn!("remote: couldn't accept a client: {}", e);
+                break;
+            }
+        }
+    }
+
+    for client in server.clients.iter_mut() {
+        // a ship which has been destroyed can't be flown any more
+        if let Some(ship) = client.ship {
+            if !ships.contains(ship) {
+                client.ship = None;
+                client.connection.send(&Reply::Released {
+                    ship: ship.to_bits(),
+                });
+            }
+        }
+
+        for request in client.connection.receive() {
+            let reply = match request {
+                Err(e) => Some(Reply::Error { message: e }),
+                Ok(Request::Claim { ship }) => {
+                    let entity = Entity::from_bits(ship);
+                    match ships.get(entity) {
+                        Err(_) => Some(Reply::Error {
+                            message: format!("there's no ship {} to claim", ship),
+                        }),
+                        Ok((.., Some(remote), _)) if remote.client != client.id => {
+                            Some(Reply::Error {
+                                message: format!("ship {} is already claimed", ship),
+                            })
+                        }
+                        Ok((.., sensor)) => {
+                            if let Some(old) = client.ship.replace(entity) {
+                                commands.entity(old).remove::<RemoteControl>();
+                            }
+                            commands.entity(entity).insert(RemoteControl {
+                                client: client.id,
+                                turn_rate: 0.0,
+                            });
+                            if sensor.is_none() {
+                                commands.entity(entity).insert(SensorBundle::default());
+                            }
+                            Some(Reply::Claimed { ship })
+                        }
+                    }
+                }
+                Ok(Request::Release) => client.ship.take().map(|ship| {
+                    commands.entity(ship).remove::<RemoteControl>();
+                    Reply::Released {
+                        ship: ship.to_bits(),
+                    }
+                }),
+                Ok(Request::Command {
+                    throttle,
+                    turn,
+                    gimbal,
+                    fire,
+                }) => match client.ship.and_then(|s| ships.get_mut(s).ok()) {
+                    None => Some(Reply::Error {
+                        message: "claim a ship first".to_string(),
+                    }),
+                    Some((ship, engine, remote, _)) => {
+                        if let Some(mut engine) = engine {
+                            if let Some(throttle) = throttle {
+                                engine.throttle = Throttle::Variable(throttle.clamp(0.0, 1.0));
+                            }
+                            if let Some(gimbal) = gimbal {
+                                engine.gimbal =
+                                    gimbal.clamp(-engine.gimbal_limit, engine.gimbal_limit);
+                            }
+                        }
+                        if let (Some(mut remote), Some(turn)) = (remote, turn) {
+                            remote.turn_rate = turn.clamp(-MAX_TURN_RATE, MAX_TURN_RATE);
+                        }
+                        if let Some(target) = fire {
+                            launches.send(LaunchMissile {
+                                shooter: ship,
+                                target: Entity::from_bits(target),
+                            });
+                        }
+                        None
+                    }
+                },
+            };
+            if let Some(reply) = reply {
+                client.connection.send(&reply);
+            }
+        }
+    }
+
+    // whoever's left behind when a client goes cuts its engine
+    for client in server.clients.iter().filter(|c| c.connection.closed) {
+        info!("remote: client {} disconnected", client.id);
+        if let Some(ship) = client.ship {
+            if let Ok((_, Some(mut engine), ..)) = ships.get_mut(ship) {
+                engine.throttle = Throttle::Variable(0.0);
+            }
+            commands.entity(ship).remove::<RemoteControl>();
+        }
+    }
+    server.clients.retain(|c| !c.connection.closed);
+}
+
+/// :SYSTEM: Turns remotely flown ships at the rate their clients asked for.
+fn remote_turn_system(mut ships: Query<(&mut Attitude, &RemoteControl)>) {
+    for (mut attitude, remote) in ships.iter_mut() {
+        attitude.command(remote.turn_rate);
+    }
+}
+
+/// :SYSTEM: Sends each client a frame for its ship, once for each tick the
+/// simulation takes, and sends on whatever's waiting to go out.
+#[allow(clippy::type_complexity)]
+fn remote_frame_system(
+    mut server: ResMut<RemoteServer>,
+    clock: Res<SimulationClock>,
+    ships: Query<(
+        &Transform,
+        &Kinimatics,
+        Option<&Engine>,
+        Option<&Hull>,
+        Option<&Contacts>,
+    )>,
+) {
+    if server.listener.is_none() {
+        return;
+    }
+    let ticked = clock.elapsed != server.last_elapsed;
+    if ticked {
+        server.last_elapsed = clock.elapsed;
+        server.tick += 1;
+    }
+    let tick = server.tick;
+
+    for client in server.clients.iter_mut() {
+        if let Some((ship, Ok((transform, kin, engine, hull, contacts)))) =
+            client.ship.map(|s| (s, ships.get(s)))
+        {
+            if ticked {
+                let (_, _, heading) = transform.rotation.to_euler(EulerRot::XYZ);
+                let contacts = contacts.map_or(Vec::new(), |c| {
+                    c.0.iter()
+                        .map(|c| ContactFrame {
+                            id: c.entity.to_bits(),
+                            kind: match c.kind {
+                                ContactKind::Body => "body",
+                                ContactKind::Ship => "ship",
+                                ContactKind::Missile => "missile",
+                                ContactKind::Countermeasure => "countermeasure",
+                            },
+                            faction: c.faction.map(|f| f.0),
+                            position: [c.position.x, c.position.y],
+                            velocity: [c.velocity.x, c.velocity.y],
+                            distance: c.distance,
+                            signature: c.signature.map(|k| k.label()),
+                            jammed: c.jammed,
+                        })
+                        .collect()
+                });
+                client.connection.send(&Reply::Frame {
+                    tick,
+                    met: clock.elapsed,
+                    ship: ship.to_bits(),
+                    position: [transform.translation.x, transform.translation.y],
+                    velocity: [kin.velocity.x, kin.velocity.y],
+                    heading,
+                    fuel: engine.map_or(0.0, |e| e.fuel),
+                    hull: hull.map_or(0.0, |h| h.integrity),
+                    contacts,
+                });
+            }
+        }
+        client.connection.flush();
+    }
+}
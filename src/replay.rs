@@ -0,0 +1,313 @@
+use bevy::prelude::*;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+use bevy::utils::HashMap;
+use bevy_egui::{egui, EguiContexts};
+
+use super::clock::SimulationClock;
+use super::level::AstroObject;
+use super::missions::Objectives;
+use super::physics::SimulationSet;
+use super::scripting::ScriptEvent;
+use super::ships::{LaunchMissile, Ship};
+
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<FlightRecorder>()
+            .add_system(record_system.after(SimulationSet))
+            .add_system(flight_map_panel_system);
+    }
+}
+
+/// Seconds of simulation time between samples of each track.
+const RECORD_INTERVAL: f64 = 1.0;
+
+/// Size of the longer side of an exported flight map, in pixels.
+const FLIGHT_MAP_SIZE: u32 = 4096;
+
+/// Where flight maps are written.
+const FLIGHT_MAP_PATH: &str = "flight_map.png";
+
+/// The recorded path of a ship or body.
+#[derive(Clone, Debug)]
+pub struct Track {
+    pub name: String,
+    pub body: bool,
+    pub points: Vec<Vec3>,
+}
+
+/// Something which happened somewhere, marked on the flight map.
+#[derive(Clone, Debug)]
+pub struct Marker {
+    pub name: String,
+    pub position: Vec3,
+    pub met: f64,
+}
+
+/// Resource which records where every ship and body went over the scenario,
+/// and what happened along the way, so the whole flight can be drawn
+/// afterwards.
+#[derive(Resource, Default)]
+pub struct FlightRecorder {
+    pub tracks: Vec<Track>,
+    pub markers: Vec<Marker>,
+    index: HashMap<Entity, usize>,
+    last_sample: Option<f64>,
+    /// What happened to the last export.
+    status: String,
+}
+
+impl FlightRecorder {
+    /// Where `entity` was last seen, if it's been recorded.
+    fn last_seen(&self, entity: Entity) -> Option<Vec3> {
+        self.index
+            .get(&entity)
+            .and_then(|i| self.tracks[*i].points.last().copied())
+    }
+
+    /// The color each track is drawn in. Bodies are grey, and each ship gets
+    /// its own color.
+    pub fn colors(&self) -> Vec<Color> {
+        let ships = self.tracks.iter().filter(|t| !t.body).count().max(1);
+        let mut ship_index = 0;
+        self.tracks
+            .iter()
+            .map(|track| {
+                if track.body {
+                    Color::rgb(0.45, 0.45, 0.45)
+                } else {
+                    ship_index += 1;
+                    Color::hsl(360.0 * ship_index as f32 / ships as f32, 0.8, 0.6)
+                }
+            })
+            .collect()
+    }
+
+    /// Draws every track and marker into an image `size` pixels on its longer
+    /// side. Markers are rings colored by what happened.
+    pub fn render(&self, size: u32) -> Image {
+        let points = self.tracks.iter().flat_map(|t| t.points.iter());
+        let (min, max) = points.fold(
+            (Vec2::splat(f32::MAX), Vec2::splat(f32::MIN)),
+            |(min, max), p| (min.min(p.truncate()), max.max(p.truncate())),
+        );
+        let (min, max) = if min.x > max.x {
+            (Vec2::splat(-1.0), Vec2::splat(1.0))
+        } else {
+            // leave a margin around the edge
+            let margin = (max - min).max_element().max(1.0) * 0.05;
+            (min - margin, max + margin)
+        };
+        let extent = max - min;
+        let scale = (size - 1) as f32 / extent.max_element();
+        let (width, height) = (
+            ((extent.x * scale) as u32 + 1).max(1),
+            ((extent.y * scale) as u32 + 1).max(1),
+        );
+
+        let mut canvas = Canvas::new(width, height, [8, 8, 16, 255]);
+        // the map has +Y up, the image has it down
+        let to_pixel = |p: Vec3| {
+            Vec2::new(
+                (p.x - min.x) * scale,
+                (height - 1) as f32 - (p.y - min.y) * scale,
+            )
+        };
+
+        for (track, color) in self.tracks.iter().zip(self.colors()) {
+            let color = rgba8(color);
+            for pair in track.points.windows(2) {
+                canvas.line(to_pixel(pair[0]), to_pixel(pair[1]), color);
+            }
+        }
+
+        for marker in &self.markers {
+            let color = match marker.name.as_str() {
+                "module_destroyed" | "impact" => Color::RED,
+                "docked" | "undocked" | "serviced" => Color::YELLOW,
+                "launch" => Color::ORANGE,
+                _ => Color::WHITE,
+            };
+            canvas.ring(to_pixel(marker.position), 6.0, rgba8(color));
+        }
+
+        Image::new(
+            Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            canvas.pixels,
+            TextureFormat::Rgba8UnormSrgb,
+        )
+    }
+
+    /// Renders the flight map and writes it to `path` as a PNG.
+    pub fn export(&self, path: &str) -> Result<(), String> {
+        self.render(FLIGHT_MAP_SIZE)
+            .try_into_dynamic()
+            .map_err(|e| e.to_string())?
+            .save(path)
+            .map_err(|e| e.to_string())
+    }
+}
+
+fn rgba8(color: Color) -> [u8; 4] {
+    color
+        .as_rgba_f32()
+        .map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8)
+}
+
+/// An RGBA image being drawn into.
+struct Canvas {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+impl Canvas {
+    fn new(width: u32, height: u32, background: [u8; 4]) -> Self {
+        Self {
+            width,
+            height,
+            pixels: background.repeat((width * height) as usize),
+        }
+    }
+
+    fn plot(&mut self, x: i64, y: i64, color: [u8; 4]) {
+        if x < 0 || y < 0 || x >= self.width as i64 || y >= self.height as i64 {
+            return;
+        }
+        let i = 4 * (y as usize * self.width as usize + x as usize);
+        self.pixels[i..i + 4].copy_from_slice(&color);
+    }
+
+    fn line(&mut self, from: Vec2, to: Vec2, color: [u8; 4]) {
+        let steps = (to - from).abs().max_element().ceil().max(1.0) as usize;
+        for i in 0..=steps {
+            let p = from.lerp(to, i as f32 / steps as f32);
+            self.plot(p.x.round() as i64, p.y.round() as i64, color);
+        }
+    }
+
+    fn ring(&mut self, center: Vec2, radius: f32, color: [u8; 4]) {
+        let steps = (radius * 8.0) as usize;
+        for i in 0..steps {
+            let angle = std::f32::consts::TAU * i as f32 / steps as f32;
+            for r in [radius, radius + 1.0] {
+                let p = center + Vec2::new(angle.cos(), angle.sin()) * r;
+                self.plot(p.x.round() as i64, p.y.round() as i64, color);
+            }
+        }
+    }
+}
+
+/// :SYSTEM: Samples every ship and body's position once per
+/// [`RECORD_INTERVAL`] of simulation time, and marks where mission events and
+/// launches happen.
+#[allow(clippy::type_complexity)]
+fn record_system(
+    mut recorder: ResMut<FlightRecorder>,
+    tracked: Query<
+        (
+            Entity,
+            &GlobalTransform,
+            Option<&Name>,
+            Option<&AstroObject>,
+        ),
+        Or<(With<Ship>, With<AstroObject>)>,
+    >,
+    clock: Res<SimulationClock>,
+    mut script_events: EventReader<ScriptEvent>,
+    mut launches: EventReader<LaunchMissile>,
+) {
+    let recorder = &mut *recorder;
+    let met = clock.elapsed;
+
+    if recorder
+        .last_sample
+        .is_none_or(|last| met - last >= RECORD_INTERVAL)
+    {
+        recorder.last_sample = Some(met);
+        for (entity, transform, name, body) in tracked.iter() {
+            let i = *recorder.index.entry(entity).or_insert_with(|| {
+                recorder.tracks.push(Track {
+                    name: name.map_or(format!("{}", entity.index()), |n| n.to_string()),
+                    body: body.is_some(),
+                    points: Vec::new(),
+                });
+                recorder.tracks.len() - 1
+            });
+            recorder.tracks[i].points.push(transform.translation());
+        }
+    }
+
+    // events are marked where their subject is, or was last seen
+    let position = |entity: Entity| tracked.get(entity).ok().map(|(_, t, ..)| t.translation());
+    for event in script_events.iter() {
+        let Some(subject) = event.args.first().and_then(|a| a.as_entity().ok()) else {
+            continue;
+        };
+        if let Some(at) = position(subject).or(recorder.last_seen(subject)) {
+            recorder.markers.push(Marker {
+                name: event.name.clone(),
+                position: at,
+                met,
+            });
+        }
+    }
+    for launch in launches.iter() {
+        if let Some(at) = position(launch.shooter) {
+            recorder.markers.push(Marker {
+                name: "launch".to_string(),
+                position: at,
+                met,
+            });
+        }
+    }
+}
+
+/// :SYSTEM: Once the mission is over, offers to export the
+/// flight map.
+fn flight_map_panel_system(
+    mut contexts: EguiContexts,
+    mut recorder: ResMut<FlightRecorder>,
+    objectives: Res<Objectives>,
+) {
+    if objectives.ended_at.is_none() {
+        return;
+    }
+
+    egui::Window::new("Flight map").show(contexts.ctx_mut(), |ui| {
+        ui.label(format!(
+            "{} tracks, {} events recorded",
+            recorder.tracks.len(),
+            recorder.markers.len()
+        ));
+        egui::ScrollArea::vertical()
+            .max_height(200.0)
+            .show(ui, |ui| {
+                for (track, color) in recorder.tracks.iter().zip(recorder.colors()) {
+                    if !track.body {
+                        let [r, g, b, _] = rgba8(color);
+                        ui.colored_label(egui::Color32::from_rgb(r, g, b), &track.name);
+                    }
+                }
+                ui.separator();
+                for marker in &recorder.markers {
+                    ui.weak(format!("{:.0} s: {}", marker.met, marker.name));
+                }
+            });
+        if ui.button("Export flight map").clicked() {
+            recorder.status = match recorder.export(FLIGHT_MAP_PATH) {
+                Ok(()) => format!("saved to {}", FLIGHT_MAP_PATH),
+                Err(e) => format!("couldn't export: {}", e),
+            };
+        }
+        if !recorder.status.is_empty() {
+            ui.label(&recorder.status);
+        }
+    });
+}
@@ -0,0 +1,225 @@
+use std::collections::VecDeque;
+
+use bevy::{prelude::*, window::PrimaryWindow};
+use bevy_egui::EguiContexts;
+
+use super::context_menu::ContextMenu;
+use super::physics::{Attitude, Kinimatics, SimulationSet};
+use super::scripting::{ShipProgram, Value};
+use super::ships::{burn_towards, Controlled, Engine, Throttle};
+use super::user_interface::MapIcon;
+
+pub struct RoutePlugin;
+
+impl Plugin for RoutePlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Route>()
+            .add_system(
+                route_system
+                    .after(super::ai::ai_control_system)
+                    .after(super::scripting::ship_program_system)
+                    .before(super::autopilot::autopilot_system)
+                    .in_set(SimulationSet),
+            )
+            .add_system(player_route_system.after(super::context_menu::context_menu_system))
+            .add_system(route_marker_system.after(player_route_system));
+    }
+}
+
+/// How close a ship has to get to a waypoint to reach it, unless it says
+/// otherwise.
+pub const ARRIVAL_RADIUS: f32 = 10.0;
+
+/// Fastest a ship can be going and still count as having reached a waypoint,
+/// unless it says otherwise.
+pub const ARRIVAL_SPEED: f32 = 5.0;
+
+/// Fraction of the engine's acceleration the guidance plans its braking
+/// around. The rest is kept back for turning and steering.
+const BRAKING_MARGIN: f32 = 0.7;
+
+/// Velocity error which maps to full throttle.
+const FULL_THROTTLE_ERROR: f32 = 10.0;
+
+/// A point on a [`Route`].
+#[derive(Reflect, FromReflect, Clone, Copy, Debug)]
+pub struct Waypoint {
+    pub position: Vec3,
+    /// How close the ship has to get.
+    pub radius: f32,
+    /// Fastest the ship may be going when it gets there.
+    pub arrival_speed: f32,
+}
+
+impl Waypoint {
+    pub fn new(position: Vec3) -> Self {
+        Self {
+            position,
+            radius: ARRIVAL_RADIUS,
+            arrival_speed: ARRIVAL_SPEED,
+        }
+    }
+}
+
+/// :COMPONENT: Waypoints for a ship to fly through, in order.
+///
+/// While it has waypoints, the route flies the ship: it burns towards the next
+/// one, flips, and burns retrograde to get there no faster than the waypoint
+/// allows. Reaching a waypoint raises `waypoint` on the ship's program, with
+/// the number left, and reaching the last raises `route_done`.
+#[derive(Reflect, Component, Clone, Debug)]
+#[reflect(Component)]
+pub struct Route {
+    pub waypoints: VecDeque<Waypoint>,
+    /// Fastest the ship flies between waypoints.
+    pub cruise_speed: f32,
+}
+
+impl Default for Route {
+    fn default() -> Self {
+        Self {
+            waypoints: VecDeque::new(),
+            cruise_speed: 60.0,
+        }
+    }
+}
+
+/// :SYSTEM: Flies each ship with waypoints towards the next one.
+#[allow(clippy::type_complexity)]
+fn route_system(
+    mut ships: Query<(
+        &mut Route,
+        &Kinimatics,
+        &Transform,
+        &mut Attitude,
+        &mut Engine,
+        Option<&mut ShipProgram>,
+    )>,
+    time: Res<Time>,
+) {
+    for (mut route, kin, transform, mut attitude, mut engine, program) in ships.iter_mut() {
+        let Some(waypoint) = route.waypoints.front().copied() else {
+            continue;
+        };
+
+        let offset = waypoint.position - transform.translation;
+        let distance = offset.length();
+        if distance <= waypoint.radius && kin.velocity.length() <= waypoint.arrival_speed {
+            route.waypoints.pop_front();
+            let left = route.waypoints.len();
+            if left == 0 {
+                engine.throttle = Throttle::Fixed(false);
+                engine.gimbal = 0.0;
+            }
+            if let Some(mut program) = program {
+                match left {
+                    0 => program.vm.raise("route_done", vec![]),
+                    _ => program.vm.raise("waypoint", vec![Value::Num(left as f64)]),
+                }
+            }
+            continue;
+        }
+
+        // the fastest the ship can be going here, and still slow down in time
+        let braking = BRAKING_MARGIN * engine.max_thrust / kin.mass;
+        let speed = (waypoint.arrival_speed.powi(2) + 2.0 * braking * distance)
+            .sqrt()
+            .min(route.cruise_speed);
+        let dv = offset.normalize_or_zero() * speed - kin.velocity;
+
+        burn_towards(
+            transform,
+            &mut attitude,
+            &mut engine,
+            dv,
+            FULL_THROTTLE_ERROR,
+            time.delta_seconds(),
+        );
+    }
+}
+
+/// :SYSTEM: Right clicking on the map adds a waypoint to the end of the
+/// player's route. Shift right click clears the route first. Right clicking a
+/// ship or body opens the [`ContextMenu`] instead.
+#[allow(clippy::too_many_arguments)]
+fn player_route_system(
+    mut routes: Query<&mut Route, With<Controlled>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform)>,
+    mouse: Res<Input<MouseButton>>,
+    keys: Res<Input<KeyCode>>,
+    mut contexts: EguiContexts,
+    menu: Res<ContextMenu>,
+) {
+    // ctrl right click is for orders
+    let ctrl = keys.any_pressed([KeyCode::LControl, KeyCode::RControl]);
+    if !mouse.just_pressed(MouseButton::Right) || ctrl || contexts.ctx_mut().wants_pointer_input() {
+        return;
+    }
+    if menu.target.is_some() {
+        return;
+    }
+    let Ok(mut route) = routes.get_single_mut() else {
+        return;
+    };
+    let (Ok(window), Ok((camera, camera_transform))) = (windows.get_single(), cameras.get_single())
+    else {
+        return;
+    };
+    let Some(ray) = window
+        .cursor_position()
+        .and_then(|cursor| camera.viewport_to_world(camera_transform, cursor))
+    else {
+        return;
+    };
+
+    if keys.any_pressed([KeyCode::LShift, KeyCode::RShift]) {
+        route.waypoints.clear();
+    }
+    let position = ray.origin.truncate().extend(0.0);
+    route.waypoints.push_back(Waypoint::new(position));
+}
+
+/// :COMPONENT: Marker for the sprites showing the player's route.
+#[derive(Component)]
+struct RouteMarker;
+
+/// :SYSTEM: Shows the player's waypoints on the map, with the next one
+/// highlighted.
+fn route_marker_system(
+    mut commands: Commands,
+    routes: Query<Ref<Route>, With<Controlled>>,
+    markers: Query<Entity, With<RouteMarker>>,
+) {
+    let route = routes.get_single().ok();
+    if route.as_ref().is_some_and(|r| !r.is_changed()) {
+        return;
+    }
+    for marker in markers.iter() {
+        commands.entity(marker).despawn();
+    }
+    let Some(route) = route else {
+        return;
+    };
+
+    for (i, waypoint) in route.waypoints.iter().enumerate() {
+        let color = if i == 0 {
+            Color::rgb(0.3, 1.0, 0.5)
+        } else {
+            Color::rgb(0.2, 0.6, 0.3)
+        };
+        commands.spawn((
+            RouteMarker,
+            MapIcon,
+            SpriteBundle {
+                sprite: Sprite {
+                    custom_size: Some(Vec2::splat(6.0)),
+                    color,
+                    ..Default::default()
+                },
+                transform: Transform::from_translation(waypoint.position.truncate().extend(5.0)),
+                ..Default::default()
+            },
+        ));
+    }
+}
@@ -0,0 +1,56 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+pub struct SandboxPlugin;
+
+impl Plugin for SandboxPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Sandbox {
+            enabled: std::env::args().any(|a| a == "--sandbox"),
+        })
+        .add_system(toggle_sandbox_system)
+        .add_system(sandbox_indicator_system);
+    }
+}
+
+/// Resource which controls sandbox mode.
+///
+/// While sandbox mode is on, the player's ships don't use up fuel or ammo and
+/// don't take damage. Physics is left alone, so everything still flies exactly
+/// as it would otherwise. Start the game with `--sandbox`, or press F2 to toggle it.
+#[derive(Resource, Default)]
+pub struct Sandbox {
+    pub enabled: bool,
+}
+
+impl Sandbox {
+    /// Whether an entity should skip resource consumption and damage. Only the
+    /// player's ships are exempt, whether or not they're the one being flown;
+    /// everything else plays by the normal rules.
+    pub fn exempts(&self, player: bool) -> bool {
+        self.enabled && player
+    }
+}
+
+fn toggle_sandbox_system(mut sandbox: ResMut<Sandbox>, input: Res<Input<KeyCode>>) {
+    if input.just_pressed(KeyCode::F2) {
+        sandbox.enabled = !sandbox.enabled;
+        info!(
+            "sandbox mode {}",
+            if sandbox.enabled { "on" } else { "off" }
+        );
+    }
+}
+
+/// :SYSTEM: Reminds the player that sandbox mode is on.
+fn sandbox_indicator_system(mut contexts: EguiContexts, sandbox: Res<Sandbox>) {
+    if !sandbox.enabled {
+        return;
+    }
+
+    egui::Area::new("sandbox_indicator")
+        .anchor(egui::Align2::CENTER_TOP, [0.0, 8.0])
+        .show(contexts.ctx_mut(), |ui| {
+            ui.colored_label(egui::Color32::YELLOW, "SANDBOX");
+        });
+}
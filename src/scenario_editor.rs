@@ -0,0 +1,457 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+use super::level::{self, AstroObject, LevelSprites};
+use super::modules::Frame;
+use super::physics::{Kinimatics, SimulationSet};
+use super::ships::{self, Controlled, Faction, Ship, ShipSprites};
+use super::star_system::Dormant;
+use super::trails::Trail;
+
+pub struct ScenarioEditorPlugin;
+
+impl Plugin for ScenarioEditorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ScenarioEditor>()
+            .configure_set(SimulationSet.run_if(not_editing))
+            .add_system(editor_panel_system)
+            .add_system(editor_pointer_system.after(editor_panel_system));
+    }
+}
+
+/// Where scenarios are exported to, unless the editor is told otherwise.
+const DEFAULT_EXPORT_PATH: &str = "assets/missions/scenario.sasm";
+
+/// How close the pointer has to be to grab something, in pixels.
+const GRAB_RADIUS: f32 = 10.0;
+
+/// Velocity arrows point to where a body will be this many seconds from now.
+const ARROW_SECONDS: f32 = 1.0;
+
+/// Something the editor can put down.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Placeable {
+    Ship,
+    Body,
+}
+
+/// What the pointer is doing to the selected entity.
+#[derive(Clone, Copy, Debug)]
+enum Drag {
+    /// Moving it, holding on `offset` from its center.
+    Move { offset: Vec3 },
+    /// Pulling its velocity arrow around.
+    Velocity,
+}
+
+/// Resource which holds the state of the scenario editor. F1 opens and closes
+/// it.
+///
+/// While editing, the simulation holds still. Bodies and ships are picked and
+/// moved with the left mouse button, and their velocity arrows dragged by the
+/// tip (or from the body itself, holding shift). Delete removes whatever's
+/// selected.
+#[derive(Resource)]
+pub struct ScenarioEditor {
+    pub active: bool,
+    pub selected: Option<Entity>,
+    drag: Option<Drag>,
+    /// Dragged out of the palette, to be put down where it's dropped.
+    placing: Option<Placeable>,
+    path: String,
+    status: String,
+}
+
+impl Default for ScenarioEditor {
+    fn default() -> Self {
+        Self {
+            active: false,
+            selected: None,
+            drag: None,
+            placing: None,
+            path: DEFAULT_EXPORT_PATH.to_string(),
+            status: String::new(),
+        }
+    }
+}
+
+fn not_editing(editor: Res<ScenarioEditor>) -> bool {
+    !editor.active
+}
+
+/// Writes out a director script which sets up the bodies and ships in
+/// `entities` as they are. The player's ship is moved into place, rather than
+/// spawned, and everything else the level starts with is cleared away first.
+fn export(
+    entities: impl Iterator<
+        Item = (
+            Entity,
+            Vec3,
+            Vec3,
+            f32,
+            Option<String>,
+            Option<f32>,
+            Option<Faction>,
+            bool,
+        ),
+    >,
+) -> String {
+    let mut script = String::from("; Scenario exported from the editor.\n\n    call clear\n");
+    for (entity, at, v, mass, name, radius, faction, controlled) in entities {
+        let id = format!("e{}", entity.index());
+        match (radius, controlled) {
+            (Some(radius), _) => {
+                script += &format!(
+                    "    call spawn_body {} {} {} {} {} {} -> {}\n",
+                    at.x, at.y, v.x, v.y, mass, radius, id
+                )
+            }
+            (None, true) => {
+                script += &format!("    call player -> {}\n", id);
+                script += &format!("    call set_position {} {} {}\n", id, at.x, at.y);
+                script += &format!("    call set_velocity {} {} {}\n", id, v.x, v.y);
+            }
+            (None, false) => {
+                let spawn = if faction == Some(Faction::HOSTILE) {
+                    "spawn_enemy"
+                } else {
+                    "spawn_ship"
+                };
+                script += &format!(
+                    "    call {} {} {} {} {} -> {}\n",
+                    spawn, at.x, at.y, v.x, v.y, id
+                );
+            }
+        }
+        if let Some(name) = name {
+            script += &format!("    call name {} \"{}\"\n", id, name.replace('"', "'"));
+        }
+    }
+    script
+}
+
+/// :SYSTEM: Shows the editor's palette, the inspector for whatever's selected,
+/// and exports the scenario.
+#[allow(clippy::type_complexity)]
+fn editor_panel_system(
+    mut commands: Commands,
+    mut contexts: EguiContexts,
+    mut editor: ResMut<ScenarioEditor>,
+    input: Res<Input<KeyCode>>,
+    mut entities: Query<
+        (
+            Entity,
+            &mut Transform,
+            &mut Kinimatics,
+            Option<&mut Name>,
+            Option<&mut AstroObject>,
+            Option<&mut Frame>,
+            Option<&Faction>,
+            Option<&Controlled>,
+        ),
+        (Or<(With<AstroObject>, With<Ship>)>, Without<Dormant>),
+    >,
+) {
+    let ctx = contexts.ctx_mut();
+    if input.just_pressed(KeyCode::F1) && !ctx.wants_keyboard_input() {
+        editor.active = !editor.active;
+    }
+    if !editor.active {
+        editor.selected = None;
+        editor.drag = None;
+        editor.placing = None;
+        return;
+    }
+    let editor = &mut *editor;
+    if editor.selected.is_some_and(|e| !entities.contains(e)) {
+        editor.selected = None;
+    }
+
+    let mut open = true;
+    egui::Window::new("Scenario editor")
+        .open(&mut open)
+        .resizable(false)
+        .show(ctx, |ui| {
+            ui.label("drag onto the map to place");
+            ui.horizontal(|ui| {
+                for (kind, text) in [(Placeable::Ship, "Ship"), (Placeable::Body, "Body")] {
+                    let button = ui.add(egui::Button::new(text).sense(egui::Sense::drag()));
+                    if button.drag_started() {
+                        editor.placing = Some(kind);
+                    }
+                }
+            });
+            ui.separator();
+
+            match editor.selected.and_then(|e| entities.get_mut(e).ok()) {
+                Some((entity, mut transform, mut kin, name, body, frame, ..)) => {
+                    egui::Grid::new("inspector").show(ui, |ui| {
+                        if let Some(mut name) = name {
+                            ui.label("name");
+                            let mut text = name.to_string();
+                            if ui.text_edit_singleline(&mut text).changed() {
+                                name.set(text);
+                            }
+                            ui.end_row();
+                        }
+
+                        ui.label("position");
+                        ui.horizontal(|ui| {
+                            let at = &mut transform.translation;
+                            ui.add(egui::DragValue::new(&mut at.x).prefix("x "));
+                            ui.add(egui::DragValue::new(&mut at.y).prefix("y "));
+                        });
+                        ui.end_row();
+
+                        ui.label("velocity");
+                        ui.horizontal(|ui| {
+                            let v = &mut kin.velocity;
+                            ui.add(egui::DragValue::new(&mut v.x).prefix("x ").speed(0.1));
+                            ui.add(egui::DragValue::new(&mut v.y).prefix("y ").speed(0.1));
+                        });
+                        ui.end_row();
+
+                        // a ship's mass is worked out from its frame and
+                        // modules, so only the frame's is up for editing
+                        match frame {
+                            Some(mut frame) => {
+                                ui.label("frame mass");
+                                ui.add(
+                                    egui::DragValue::new(&mut frame.dry_mass)
+                                        .clamp_range(0.0..=f32::MAX),
+                                );
+                            }
+                            None => {
+                                ui.label("mass");
+                                let speed = kin.mass.abs().max(1.0) * 0.01;
+                                ui.add(
+                                    egui::DragValue::new(&mut kin.mass)
+                                        .speed(speed)
+                                        .clamp_range(0.0..=f32::MAX),
+                                );
+                            }
+                        }
+                        ui.end_row();
+
+                        if let Some(mut body) = body {
+                            ui.label("radius");
+                            ui.add(
+                                egui::DragValue::new(&mut body.radius)
+                                    .speed(0.1)
+                                    .clamp_range(0.1..=f32::MAX),
+                            );
+                            ui.end_row();
+                        }
+                    });
+                    if ui.button("Delete").clicked() {
+                        commands.entity(entity).despawn_recursive();
+                        editor.selected = None;
+                    }
+                }
+                None => {
+                    ui.weak("nothing selected");
+                }
+            }
+            ui.separator();
+
+            ui.horizontal(|ui| {
+                ui.text_edit_singleline(&mut editor.path);
+                if ui.button("Export").clicked() {
+                    let script = export(entities.iter().map(
+                        |(e, transform, kin, name, body, _, faction, controlled)| {
+                            (
+                                e,
+                                transform.translation,
+                                kin.velocity,
+                                kin.mass,
+                                name.map(|n| n.to_string()),
+                                body.map(|b| b.radius),
+                                faction.copied(),
+                                controlled.is_some(),
+                            )
+                        },
+                    ));
+                    editor.status = match std::fs::write(&editor.path, script) {
+                        Ok(()) => format!("exported to {}", editor.path),
+                        Err(e) => format!("couldn't export: {}", e),
+                    };
+                }
+            });
+            if !editor.status.is_empty() {
+                ui.label(&editor.status);
+            }
+        });
+    if !open {
+        editor.active = false;
+    }
+}
+
+/// :SYSTEM: Picks, moves and deletes bodies and ships with the mouse, drags
+/// their velocity arrows, and puts down whatever's dropped from the palette.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn editor_pointer_system(
+    mut commands: Commands,
+    mut contexts: EguiContexts,
+    mut editor: ResMut<ScenarioEditor>,
+    mouse: Res<Input<MouseButton>>,
+    keys: Res<Input<KeyCode>>,
+    windows: Query<&Window>,
+    cameras: Query<(&Camera, &GlobalTransform), With<Camera2d>>,
+    mut entities: Query<
+        (Entity, &mut Transform, &mut Kinimatics, Option<&mut Trail>),
+        (Or<(With<AstroObject>, With<Ship>)>, Without<Dormant>),
+    >,
+    ship_sprites: Res<ShipSprites>,
+    level_sprites: Res<LevelSprites>,
+) {
+    if !editor.active {
+        return;
+    }
+    let Ok((camera, camera_transform)) = cameras.get_single() else {
+        return;
+    };
+    let Some(viewport) = camera.logical_viewport_size() else {
+        return;
+    };
+    // the viewport has +Y up, egui has it down
+    let to_screen = |p: Vec3| {
+        camera
+            .world_to_viewport(camera_transform, p)
+            .map(|at| egui::pos2(at.x, viewport.y - at.y))
+    };
+    let cursor = windows.get_single().ok().and_then(|w| w.cursor_position());
+    let pointer = cursor
+        .and_then(|c| camera.viewport_to_world(camera_transform, c))
+        .map(|ray| ray.origin.truncate().extend(0.0));
+    let pointer_screen = cursor.map(|c| egui::pos2(c.x, viewport.y - c.y));
+    let editor = &mut *editor;
+    let ctx = contexts.ctx_mut();
+
+    let painter = ctx.layer_painter(egui::LayerId::background());
+    for (entity, transform, kin, _) in entities.iter() {
+        let at = transform.translation;
+        let (Some(from), Some(to)) = (to_screen(at), to_screen(at + kin.velocity * ARROW_SECONDS))
+        else {
+            continue;
+        };
+        let selected = editor.selected == Some(entity);
+        let color = if selected {
+            egui::Color32::YELLOW
+        } else {
+            egui::Color32::from_gray(160)
+        };
+        painter.arrow(from, to - from, egui::Stroke::new(1.0, color));
+        if selected {
+            painter.circle_stroke(from, GRAB_RADIUS, (1.0, color));
+            painter.circle_filled(to, 3.0, color);
+        }
+    }
+
+    if let Some(kind) = editor.placing {
+        if let Some(at) = pointer_screen {
+            let text = match kind {
+                Placeable::Ship => "ship",
+                Placeable::Body => "body",
+            };
+            painter.circle_stroke(at, GRAB_RADIUS, (1.0, egui::Color32::WHITE));
+            painter.text(
+                at + egui::vec2(GRAB_RADIUS + 4.0, 0.0),
+                egui::Align2::LEFT_CENTER,
+                text,
+                egui::FontId::proportional(12.0),
+                egui::Color32::WHITE,
+            );
+        }
+        if mouse.just_released(MouseButton::Left) {
+            editor.placing = None;
+            if let (Some(at), false) = (pointer, ctx.is_pointer_over_area()) {
+                let placed = match kind {
+                    Placeable::Ship => {
+                        ships::spawn_ship(&mut commands, &ship_sprites, at, Vec3::ZERO)
+                    }
+                    Placeable::Body => level::spawn_planet(
+                        &mut commands,
+                        &level_sprites,
+                        7.5,
+                        3.285e8,
+                        at,
+                        Vec3::ZERO,
+                    ),
+                };
+                editor.selected = Some(placed);
+            }
+        }
+        return;
+    }
+
+    if let Some(selected) = editor.selected {
+        if keys.just_pressed(KeyCode::Delete) && !ctx.wants_keyboard_input() {
+            commands.entity(selected).despawn_recursive();
+            editor.selected = None;
+            editor.drag = None;
+            return;
+        }
+    }
+
+    let (Some(pointer), Some(pointer_screen)) = (pointer, pointer_screen) else {
+        return;
+    };
+    if mouse.just_pressed(MouseButton::Left) && !ctx.wants_pointer_input() {
+        let shift = keys.any_pressed([KeyCode::LShift, KeyCode::RShift]);
+        let near = |p: Vec3| to_screen(p).is_some_and(|s| s.distance(pointer_screen) < GRAB_RADIUS);
+
+        // the tip of the selected arrow comes first, so long as it's clear of
+        // the body it's pointing away from
+        let tip = editor
+            .selected
+            .and_then(|e| entities.get(e).ok())
+            .is_some_and(|(_, t, k, _)| {
+                let tip = t.translation + k.velocity * ARROW_SECONDS;
+                near(tip) && !near(t.translation)
+            });
+        editor.drag = if tip {
+            Some(Drag::Velocity)
+        } else {
+            let picked = entities
+                .iter()
+                .filter(|(_, t, ..)| near(t.translation))
+                .min_by(|(_, a, ..), (_, b, ..)| {
+                    let a = a.translation.distance_squared(pointer);
+                    let b = b.translation.distance_squared(pointer);
+                    a.total_cmp(&b)
+                })
+                .map(|(e, t, ..)| (e, t.translation));
+            editor.selected = picked.map(|(e, _)| e);
+            picked.map(|(_, at)| {
+                if shift {
+                    Drag::Velocity
+                } else {
+                    Drag::Move {
+                        offset: at - pointer,
+                    }
+                }
+            })
+        };
+    }
+    if !mouse.pressed(MouseButton::Left) {
+        editor.drag = None;
+    }
+
+    let (Some(drag), Some(selected)) = (editor.drag, editor.selected) else {
+        return;
+    };
+    let Ok((_, mut transform, mut kin, trail)) = entities.get_mut(selected) else {
+        return;
+    };
+    match drag {
+        Drag::Move { offset } => {
+            transform.translation = pointer + offset;
+            if let Some(mut trail) = trail {
+                trail.clear();
+            }
+        }
+        Drag::Velocity => {
+            kin.velocity = (pointer - transform.translation) / ARROW_SECONDS;
+        }
+    }
+}
@@ -0,0 +1,159 @@
+use bevy::prelude::*;
+use bevy::scene::DynamicSceneBuilder;
+use bevy_egui::{egui, EguiContexts};
+
+use super::orders::OrdersPanel;
+use super::physics::Kinimatics;
+use super::scenario_editor::ScenarioEditor;
+use super::vfx::Effect;
+
+pub struct ScenesPlugin;
+
+impl Plugin for ScenesPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ScenePanel>()
+            .add_system(scene_panel_system)
+            .add_system(scene_save_system.after(scene_panel_system));
+    }
+}
+
+/// Scene file the panel starts out with, relative to the assets folder.
+const DEFAULT_SCENE_PATH: &str = "scenes/world.scn.ron";
+
+/// What to put in a scene file.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum SaveRequest {
+    /// Everything that moves.
+    World,
+    /// Whatever's selected in the scenario editor or the orders panel.
+    Selection,
+}
+
+/// Resource which holds the state of the scenes panel, which is shown
+/// alongside the scenario editor.
+///
+/// Scenes are Bevy's own format, so unlike exported scenarios they keep every
+/// reflected component, modules and all, and can be spawned on top of any
+/// level.
+#[derive(Resource)]
+pub struct ScenePanel {
+    path: String,
+    save: Option<SaveRequest>,
+    status: String,
+}
+
+impl Default for ScenePanel {
+    fn default() -> Self {
+        Self {
+            path: DEFAULT_SCENE_PATH.to_string(),
+            save: None,
+            status: String::new(),
+        }
+    }
+}
+
+/// Spawns the scene at `path`, relative to the assets folder, once it has
+/// loaded. Its entities are added to the world as they are, rather than under
+/// a parent of their own.
+pub fn spawn_scene(commands: &mut Commands, asset_server: &AssetServer, path: &str) {
+    let scene: Handle<DynamicScene> = asset_server.load(path);
+    commands.add(move |world: &mut World| {
+        world.resource_mut::<SceneSpawner>().spawn_dynamic(scene);
+    });
+}
+
+/// Writes `roots`, and everything under them, to the scene file at `path`.
+fn save_scene(world: &World, roots: &[Entity], path: &str) -> Result<(), String> {
+    let mut entities = Vec::new();
+    let mut stack = roots.to_vec();
+    while let Some(entity) = stack.pop() {
+        if world.get_entity(entity).is_none() || world.get::<Effect>(entity).is_some() {
+            continue;
+        }
+        entities.push(entity);
+        if let Some(children) = world.get::<Children>(entity) {
+            stack.extend(children.iter().copied());
+        }
+    }
+    if entities.is_empty() {
+        return Err("there's nothing to save".to_string());
+    }
+
+    let mut builder = DynamicSceneBuilder::from_world(world);
+    builder.extract_entities(entities.into_iter());
+    builder.remove_empty_entities();
+    let registry = world.resource::<AppTypeRegistry>();
+    let text = builder
+        .build()
+        .serialize_ron(registry)
+        .map_err(|e| e.to_string())?;
+
+    let path = std::path::Path::new("assets").join(path);
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    std::fs::write(path, text).map_err(|e| e.to_string())
+}
+
+/// :SYSTEM: Shows the scenes panel while the scenario editor is open, and
+/// spawns scene files.
+fn scene_panel_system(
+    mut commands: Commands,
+    mut contexts: EguiContexts,
+    mut panel: ResMut<ScenePanel>,
+    editor: Res<ScenarioEditor>,
+    asset_server: Res<AssetServer>,
+) {
+    if !editor.active {
+        return;
+    }
+    let panel = &mut *panel;
+    egui::Window::new("Scenes")
+        .resizable(false)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.horizontal(|ui| {
+                ui.label("assets/");
+                ui.text_edit_singleline(&mut panel.path);
+            });
+            ui.horizontal(|ui| {
+                if ui.button("Save world").clicked() {
+                    panel.save = Some(SaveRequest::World);
+                }
+                if ui.button("Save selection").clicked() {
+                    panel.save = Some(SaveRequest::Selection);
+                }
+                if ui.button("Spawn").clicked() {
+                    spawn_scene(&mut commands, &asset_server, &panel.path);
+                    panel.status = format!("spawning {}", panel.path);
+                }
+            });
+            if !panel.status.is_empty() {
+                ui.label(&panel.status);
+            }
+        });
+}
+
+/// :SYSTEM: Saves the scene asked for in the scenes panel. It needs the whole
+/// world to build the scene from, so it runs on its own.
+fn scene_save_system(world: &mut World) {
+    let Some(request) = world.resource_mut::<ScenePanel>().save.take() else {
+        return;
+    };
+    let roots: Vec<Entity> = match request {
+        SaveRequest::World => world
+            .query_filtered::<Entity, (With<Kinimatics>, Without<Parent>)>()
+            .iter(world)
+            .collect(),
+        SaveRequest::Selection => {
+            let editor = world.resource::<ScenarioEditor>().selected;
+            let orders = &world.resource::<OrdersPanel>().selected;
+            editor.into_iter().chain(orders.iter().copied()).collect()
+        }
+    };
+    let path = world.resource::<ScenePanel>().path.clone();
+    let status = match save_scene(world, &roots, &path) {
+        Ok(()) => format!("saved to assets/{}", path),
+        Err(e) => format!("couldn't save: {}", e),
+    };
+    world.resource_mut::<ScenePanel>().status = status;
+}
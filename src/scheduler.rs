@@ -0,0 +1,184 @@
+use bevy::prelude::*;
+
+use super::clock::SimulationClock;
+use super::physics::SimulationSet;
+use super::scripting::{ScriptEvent, ShipProgram};
+
+pub struct SchedulerPlugin;
+
+impl Plugin for SchedulerPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Scheduler>()
+            .add_event::<Alarm>()
+            .add_system(
+                scheduler_system
+                    .after(super::clock::clock_system)
+                    .in_set(SimulationSet),
+            );
+    }
+}
+
+/// Most times a periodic task may fire in a single tick, so a huge time step
+/// can't flood the game with events.
+const MAX_FIRINGS_PER_TICK: u32 = 16;
+
+/// :EVENT: Sent by the [`Scheduler`] when an alarm set by a system goes off.
+#[derive(Clone, Copy, Debug)]
+pub struct Alarm {
+    pub entity: Entity,
+    pub name: &'static str,
+}
+
+/// What happens when a scheduled task comes due.
+#[derive(Clone, Debug)]
+pub enum Action {
+    /// Raises a [`ScriptEvent`], for the mission director.
+    Script(ScriptEvent),
+    /// Raises an event on one ship's program. The task is dropped if the ship
+    /// no longer has a program.
+    Program(Entity, ScriptEvent),
+    /// Sends an [`Alarm`], for systems.
+    Alarm(Alarm),
+}
+
+#[derive(Clone, Debug)]
+struct Task {
+    id: u64,
+    /// Mission elapsed time the task is next due.
+    at: f64,
+    every: Option<f64>,
+    action: Action,
+}
+
+/// Resource which runs actions at set times on the [`SimulationClock`], once or
+/// periodically. Since it follows the simulation clock, anything scheduled
+/// waits while the simulation is paused, and comes around sooner under time warp.
+#[derive(Resource, Default)]
+pub struct Scheduler {
+    next_id: u64,
+    tasks: Vec<Task>,
+    /// Mission elapsed time as of the last tick.
+    now: f64,
+}
+
+impl Scheduler {
+    fn add(&mut self, at: f64, every: Option<f64>, action: Action) -> u64 {
+        self.next_id += 1;
+        self.tasks.push(Task {
+            id: self.next_id,
+            at,
+            every,
+            action,
+        });
+        self.next_id
+    }
+
+    /// Runs `action` once, at mission elapsed time `time`. Returns an id which
+    /// can be used to cancel it.
+    pub fn at(&mut self, time: f64, action: Action) -> u64 {
+        self.add(time, None, action)
+    }
+
+    /// Runs `action` once, `delay` seconds from now.
+    pub fn after(&mut self, delay: f64, action: Action) -> u64 {
+        self.add(self.now + delay.max(0.0), None, action)
+    }
+
+    /// Runs `action` `delay` seconds from now, then every `period` seconds.
+    pub fn every(&mut self, delay: f64, period: f64, action: Action) -> Result<u64, String> {
+        if period <= 0.0 {
+            return Err(format!("can't repeat every {} seconds", period));
+        }
+        Ok(self.add(self.now + delay.max(0.0), Some(period), action))
+    }
+
+    /// Stops a task from running (again). Returns whether there was such a task.
+    pub fn cancel(&mut self, id: u64) -> bool {
+        let before = self.tasks.len();
+        self.tasks.retain(|t| t.id != id);
+        self.tasks.len() != before
+    }
+
+    /// Like [`Scheduler::cancel`], but only stops the task if it would raise an
+    /// event on `entity`'s program, so programs can't cancel each other's.
+    pub fn cancel_for(&mut self, id: u64, entity: Entity) -> bool {
+        let before = self.tasks.len();
+        self.tasks
+            .retain(|t| t.id != id || !matches!(t.action, Action::Program(e, _) if e == entity));
+        self.tasks.len() != before
+    }
+
+    /// Stops every task which would raise an event on `entity`'s program.
+    pub fn cancel_program(&mut self, entity: Entity) {
+        self.tasks
+            .retain(|t| !matches!(t.action, Action::Program(e, _) if e == entity));
+    }
+
+    /// Takes every action which has come due by mission elapsed time `now`.
+    pub fn take_due(&mut self, now: f64) -> Vec<Action> {
+        self.now = now;
+
+        let mut due = Vec::new();
+        self.tasks.retain_mut(|task| {
+            let mut firings = 0;
+            while task.at <= now && firings < MAX_FIRINGS_PER_TICK {
+                due.push(task.action.clone());
+                firings += 1;
+                match task.every {
+                    Some(period) => task.at += period,
+                    None => return false,
+                }
+            }
+            // skip whatever a long tick left over, rather than firing it late
+            if let Some(period) = task.every.filter(|_| task.at <= now) {
+                task.at += ((now - task.at) / period).floor() * period + period;
+            }
+            true
+        });
+        due
+    }
+}
+
+/// Carries out actions taken from the scheduler.
+pub fn dispatch(
+    scheduler: &mut Scheduler,
+    due: Vec<Action>,
+    programs: &mut Query<&mut ShipProgram>,
+    script_events: &mut EventWriter<ScriptEvent>,
+    alarms: &mut EventWriter<Alarm>,
+) {
+    let mut lost = Vec::new();
+    for action in due {
+        match action {
+            Action::Script(event) => script_events.send(event),
+            Action::Program(entity, event) => match programs.get_mut(entity) {
+                Ok(mut program) => program.vm.raise(&event.name, event.args),
+                Err(_) => lost.push(entity),
+            },
+            Action::Alarm(alarm) => alarms.send(alarm),
+        }
+    }
+
+    // tasks for programs which have gone away won't ever have anywhere to go
+    for entity in lost {
+        scheduler.cancel_program(entity);
+    }
+}
+
+/// :SYSTEM: Runs every task which has come due.
+fn scheduler_system(
+    mut scheduler: ResMut<Scheduler>,
+    clock: Res<SimulationClock>,
+    mut programs: Query<&mut ShipProgram>,
+    mut script_events: EventWriter<ScriptEvent>,
+    mut alarms: EventWriter<Alarm>,
+) {
+    let due = scheduler.take_due(clock.elapsed);
+    dispatch(
+        &mut scheduler,
+        due,
+        &mut programs,
+        &mut script_events,
+        &mut alarms,
+    );
+}
@@ -0,0 +1,171 @@
+use std::collections::BTreeSet;
+
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+use super::code_editor::CodeEditor;
+use super::physics::SimulationSet;
+use super::scripting::{ProgramContext, ScriptSource, ShipProgram, Value, VmState};
+
+pub struct ScriptDebuggerPlugin;
+
+impl Plugin for ScriptDebuggerPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ScriptDebugger>()
+            .configure_set(SimulationSet.run_if(no_breakpoint_hit))
+            .add_system(debugger_panel_system)
+            .add_system(
+                debug_step_system
+                    .after(debugger_panel_system)
+                    .run_if(not(no_breakpoint_hit)),
+            );
+    }
+}
+
+/// Resource which holds the state of the script debugger. F6 opens and closes
+/// it.
+///
+/// When any ship's program reaches one of its breakpoints, the whole simulation
+/// holds still until it is continued, so nothing changes under the program
+/// while it is being looked at. Stepping runs the held program one instruction
+/// at a time, without any time passing.
+#[derive(Resource, Default)]
+pub struct ScriptDebugger {
+    pub open: bool,
+}
+
+fn no_breakpoint_hit(programs: Query<&ShipProgram>) -> bool {
+    !programs.iter().any(|p| p.vm.state() == &VmState::Break)
+}
+
+/// :SYSTEM: Runs programs which the debugger has asked to step.
+fn debug_step_system(mut programs: ProgramContext) {
+    programs.run(0.0, true);
+}
+
+/// :SYSTEM: Shows the script debugger, for the ship held at a breakpoint, or
+/// failing that, the ship selected in the program editor.
+fn debugger_panel_system(
+    mut contexts: EguiContexts,
+    mut debugger: ResMut<ScriptDebugger>,
+    mut programs: Query<(Entity, &mut ShipProgram, Option<&Name>)>,
+    sources: Res<Assets<ScriptSource>>,
+    editor: Res<CodeEditor>,
+    input: Res<Input<KeyCode>>,
+) {
+    if input.just_pressed(KeyCode::F6) {
+        debugger.open = !debugger.open;
+    }
+    let held = programs
+        .iter()
+        .find(|(_, p, _)| p.vm.state() == &VmState::Break)
+        .map(|(entity, ..)| entity);
+    // a breakpoint opens the debugger, so the game doesn't seem to freeze
+    if held.is_some() {
+        debugger.open = true;
+    }
+    if !debugger.open {
+        return;
+    }
+
+    let mut open = true;
+    egui::Window::new("Debugger")
+        .open(&mut open)
+        .default_size([360.0, 480.0])
+        .show(contexts.ctx_mut(), |ui| {
+            let Some(Ok((entity, mut program, name))) =
+                held.or(editor.ship()).map(|s| programs.get_mut(s))
+            else {
+                ui.label("No ship is selected in the program editor.");
+                return;
+            };
+            let program = &mut *program;
+            match name {
+                Some(name) => ui.heading(format!("{} ({})", name, entity.index())),
+                None => ui.heading(format!("ship {}", entity.index())),
+            };
+
+            let at_break = program.vm.state() == &VmState::Break;
+            ui.horizontal(|ui| {
+                if ui
+                    .add_enabled(at_break, egui::Button::new("Continue"))
+                    .clicked()
+                {
+                    program.vm.resume();
+                }
+                if ui
+                    .add_enabled(at_break, egui::Button::new("Step"))
+                    .clicked()
+                {
+                    program.vm.step();
+                }
+                if ui.button("Clear breakpoints").clicked() {
+                    program.vm.breakpoints.clear();
+                }
+            });
+
+            let (Some(compiled), Some(source)) = (program.compiled(), sources.get(&program.source))
+            else {
+                ui.label("The program hasn't been compiled yet.");
+                return;
+            };
+            // breakpoints only ever catch lines with an instruction on them
+            let code_lines: BTreeSet<usize> =
+                (0..compiled.len()).map(|pc| compiled.line_of(pc)).collect();
+            let current = at_break.then(|| compiled.line_of(program.vm.pc()));
+
+            egui::ScrollArea::vertical()
+                .id_source("debug_source")
+                .max_height(280.0)
+                .show(ui, |ui| {
+                    for (i, text) in source.0.lines().enumerate() {
+                        let line = i + 1;
+                        ui.horizontal(|ui| {
+                            let set = program.vm.breakpoints.contains(&line);
+                            let marker = if set { "●" } else { " " };
+                            let toggle = ui.add_enabled(
+                                code_lines.contains(&line),
+                                egui::SelectableLabel::new(
+                                    set,
+                                    egui::RichText::new(format!("{} {:>3}", marker, line))
+                                        .monospace()
+                                        .color(egui::Color32::RED),
+                                ),
+                            );
+                            if toggle.clicked() && !program.vm.breakpoints.remove(&line) {
+                                program.vm.breakpoints.insert(line);
+                            }
+
+                            let text = egui::RichText::new(text).monospace();
+                            if current == Some(line) {
+                                ui.label(text.background_color(egui::Color32::from_rgb(80, 80, 0)));
+                            } else {
+                                ui.label(text);
+                            }
+                        });
+                    }
+                });
+
+            ui.separator();
+            ui.label("Variables");
+            let mut vars: Vec<_> = program.vm.vars().iter().collect();
+            vars.sort_by(|a, b| a.0.cmp(b.0));
+            egui::ScrollArea::vertical()
+                .id_source("debug_vars")
+                .show(ui, |ui| {
+                    egui::Grid::new("debug_vars_grid")
+                        .striped(true)
+                        .show(ui, |ui| {
+                            for (name, value) in vars {
+                                ui.monospace(name);
+                                match value {
+                                    Value::Num(n) => ui.monospace(n.to_string()),
+                                    Value::Str(s) => ui.monospace(format!("{:?}", s)),
+                                };
+                                ui.end_row();
+                            }
+                        });
+                });
+        });
+    debugger.open = open;
+}
@@ -0,0 +1,1926 @@
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    ecs::system::SystemParam,
+    prelude::*,
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+
+use super::blackboard::{Blackboard, Publish};
+use super::boarding::BoardShip;
+use super::clock::SimulationClock;
+use super::comms::{Message, Transceiver};
+use super::damage_control::{DamageControl, Repair};
+use super::determinism::Determinism;
+use super::docking::DockingRequest;
+use super::heat::Thermal;
+use super::jamming::{Jammer, Jamming};
+use super::navigation;
+use super::physics::{Attitude, Kinimatics, PhysicsConfig, SimulationSet};
+use super::power::{PowerGrid, PowerRequest, Subsystem};
+use super::refueling::{Stores, TransferRequest};
+use super::route::{Route, Waypoint};
+use super::scheduler::{Action, Scheduler};
+use super::sensors::{self, Contacts, Sensor};
+use super::shields::{Quadrant, Shield};
+use super::ships::{CargoHold, Engine, Hull, Throttle, ThrottleProgram, ThrottleStep};
+use super::star_system::JumpRequest;
+use super::stealth::Emissions;
+use super::tournament::Tournament;
+use super::trade::{Market, TradeRequest};
+
+pub struct ScriptingPlugin;
+
+impl Plugin for ScriptingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<ScriptSource>()
+            .init_asset_loader::<ScriptLoader>()
+            .add_event::<ScriptEvent>()
+            .add_event::<CpuOverrun>()
+            .add_system(reload_program_system.before(ship_program_system))
+            .add_system(
+                ship_program_system
+                    .before(super::physics::kinimatics_system)
+                    .in_set(SimulationSet),
+            )
+            .add_system(
+                cpu_overrun_system
+                    .after(ship_program_system)
+                    .in_set(SimulationSet),
+            );
+    }
+}
+
+/// Maximum number of instructions a ship program may execute in a single frame.
+pub const SHIP_INSTRUCTION_BUDGET: usize = 256;
+
+/// Maximum time a ship program may run for in a single frame. Deterministic
+/// runs and tournaments leave it out, and only count instructions.
+pub const SHIP_TIME_BUDGET: Duration = Duration::from_micros(500);
+
+/// A value which can be stored in a script variable.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Num(f64),
+    Str(String),
+}
+
+impl Value {
+    pub fn as_num(&self) -> Result<f64, String> {
+        match self {
+            Value::Num(n) => Ok(*n),
+            Value::Str(s) => Err(format!("expected a number, found \"{}\"", s)),
+        }
+    }
+
+    pub fn as_str(&self) -> Result<&str, String> {
+        match self {
+            Value::Str(s) => Ok(s),
+            Value::Num(n) => Err(format!("expected a string, found {}", n)),
+        }
+    }
+
+    /// Entities are handed to scripts as plain numbers.
+    pub fn from_entity(e: Entity) -> Self {
+        Value::Num(e.to_bits() as f64)
+    }
+
+    pub fn as_entity(&self) -> Result<Entity, String> {
+        Ok(Entity::from_bits(self.as_num()? as u64))
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Num(n) => write!(f, "{}", n),
+            Value::Str(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+/// :EVENT: A named game event, with arguments, which scripts can handle with `on`.
+#[derive(Clone, Debug)]
+pub struct ScriptEvent {
+    pub name: String,
+    pub args: Vec<Value>,
+}
+
+/// An error raised while parsing or executing a script. `line` is 1 based.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScriptError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Operand {
+    Literal(Value),
+    Var(String),
+}
+
+#[derive(Clone, Copy, Debug)]
+enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Min,
+    Max,
+    Atan2,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum UnOp {
+    Neg,
+    Abs,
+    Sqrt,
+    Sin,
+    Cos,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Cmp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Clone, Debug)]
+enum Instruction {
+    Set(String, Operand),
+    Binary(BinOp, String, Operand, Operand),
+    Unary(UnOp, String, Operand),
+    Jump(usize),
+    Branch(Cmp, Operand, Operand, usize),
+    Gosub(usize),
+    Return,
+    Call(String, Vec<Operand>, Vec<String>),
+    Print(Vec<Operand>),
+    On(String, usize),
+    Wait(Operand),
+    Yield,
+    Halt,
+}
+
+/// A parsed script, ready to be executed by a [`Vm`].
+///
+/// Scripts are written in a small assembly-like language. Each line holds a
+/// single instruction, labels end in a colon, and `;` starts a comment:
+///
+/// ```text
+/// loop:
+///     call position self -> x y
+///     jgt y 1000 done
+///     call throttle 1.0
+///     wait 0.5
+///     jmp loop
+/// done:
+///     call throttle 0
+///     halt
+/// ```
+///
+/// A label named `on_` and an event's name is that event's handler, as if set
+/// with `on`, from the start. `on_event` handles every event without a handler
+/// of its own, with the event's name in `arg0` and its arguments after it.
+#[derive(Clone, Debug, Default)]
+pub struct Program {
+    instructions: Vec<Instruction>,
+    /// Source line of each instruction, used for error reporting.
+    lines: Vec<usize>,
+    /// Events handled by `on_` labels, and where their handlers start.
+    callbacks: Vec<(String, usize)>,
+}
+
+impl Program {
+    pub fn parse(source: &str) -> Result<Self, ScriptError> {
+        // first pass: find all of the labels, so that jumps can go forward.
+        let mut labels = HashMap::new();
+        let mut count = 0;
+        for (n, line) in source.lines().enumerate() {
+            let tokens = tokenize(line, n + 1)?;
+            let mut tokens = tokens.as_slice();
+            while let Some(label) = tokens.first().and_then(|t| t.strip_suffix(':')) {
+                if labels.insert(label.to_string(), count).is_some() {
+                    return Err(ScriptError {
+                        line: n + 1,
+                        message: format!("duplicate label `{}`", label),
+                    });
+                }
+                tokens = &tokens[1..];
+            }
+            if !tokens.is_empty() {
+                count += 1;
+            }
+        }
+
+        let mut program = Program {
+            callbacks: labels
+                .iter()
+                .filter_map(|(label, pc)| Some((label.strip_prefix("on_")?.to_string(), *pc)))
+                .collect(),
+            ..Default::default()
+        };
+        for (n, line) in source.lines().enumerate() {
+            let tokens = tokenize(line, n + 1)?;
+            let tokens: Vec<&str> = tokens
+                .iter()
+                .map(String::as_str)
+                .skip_while(|t| t.ends_with(':') && !t.starts_with('"'))
+                .collect();
+
+            if tokens.is_empty() {
+                continue;
+            }
+
+            let instruction =
+                parse_instruction(&tokens, &labels).map_err(|message| ScriptError {
+                    line: n + 1,
+                    message,
+                })?;
+            program.instructions.push(instruction);
+            program.lines.push(n + 1);
+        }
+
+        Ok(program)
+    }
+
+    pub fn len(&self) -> usize {
+        self.instructions.len()
+    }
+
+    /// The source line of the instruction at `pc`.
+    pub fn line_of(&self, pc: usize) -> usize {
+        self.lines.get(pc).copied().unwrap_or(0)
+    }
+}
+
+/// Splits a line into tokens. Quoted strings are kept together (quotes included),
+/// and everything after a `;` is ignored.
+fn tokenize(line: &str, line_no: usize) -> Result<Vec<String>, ScriptError> {
+    let mut tokens = Vec::new();
+    let mut chars = line.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == ';' {
+            break;
+        } else if c == '"' {
+            let mut token = String::from('"');
+            chars.next();
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some(c) => token.push(c),
+                    None => {
+                        return Err(ScriptError {
+                            line: line_no,
+                            message: "unterminated string".to_string(),
+                        })
+                    }
+                }
+            }
+            token.push('"');
+            tokens.push(token);
+        } else {
+            let mut token = String::new();
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() || c == ';' {
+                    break;
+                }
+                token.push(c);
+                chars.next();
+            }
+            tokens.push(token);
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn parse_operand(token: &str) -> Result<Operand, String> {
+    if let Some(s) = token.strip_prefix('"') {
+        return Ok(Operand::Literal(Value::Str(
+            s.strip_suffix('"').unwrap_or(s).to_string(),
+        )));
+    }
+
+    if let Ok(n) = token.parse::<f64>() {
+        return Ok(Operand::Literal(Value::Num(n)));
+    }
+
+    parse_var(token).map(Operand::Var)
+}
+
+fn parse_var(token: &str) -> Result<String, String> {
+    let valid = token
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_alphabetic() || c == '_')
+        && token.chars().all(|c| c.is_alphanumeric() || c == '_');
+
+    if valid {
+        Ok(token.to_string())
+    } else {
+        Err(format!("`{}` is not a valid variable name", token))
+    }
+}
+
+fn parse_label(token: &str, labels: &HashMap<String, usize>) -> Result<usize, String> {
+    labels
+        .get(token)
+        .copied()
+        .ok_or_else(|| format!("unknown label `{}`", token))
+}
+
+fn expect_args(tokens: &[&str], n: usize) -> Result<(), String> {
+    if tokens.len() - 1 != n {
+        Err(format!(
+            "`{}` takes {} argument(s), found {}",
+            tokens[0],
+            n,
+            tokens.len() - 1
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+fn parse_instruction(
+    tokens: &[&str],
+    labels: &HashMap<String, usize>,
+) -> Result<Instruction, String> {
+    let binary = |op| -> Result<Instruction, String> {
+        expect_args(tokens, 3)?;
+        Ok(Instruction::Binary(
+            op,
+            parse_var(tokens[1])?,
+            parse_operand(tokens[2])?,
+            parse_operand(tokens[3])?,
+        ))
+    };
+    let unary = |op| -> Result<Instruction, String> {
+        expect_args(tokens, 2)?;
+        Ok(Instruction::Unary(
+            op,
+            parse_var(tokens[1])?,
+            parse_operand(tokens[2])?,
+        ))
+    };
+    let branch = |cmp| -> Result<Instruction, String> {
+        expect_args(tokens, 3)?;
+        Ok(Instruction::Branch(
+            cmp,
+            parse_operand(tokens[1])?,
+            parse_operand(tokens[2])?,
+            parse_label(tokens[3], labels)?,
+        ))
+    };
+
+    match tokens[0] {
+        "set" => {
+            expect_args(tokens, 2)?;
+            Ok(Instruction::Set(
+                parse_var(tokens[1])?,
+                parse_operand(tokens[2])?,
+            ))
+        }
+        "add" => binary(BinOp::Add),
+        "sub" => binary(BinOp::Sub),
+        "mul" => binary(BinOp::Mul),
+        "div" => binary(BinOp::Div),
+        "mod" => binary(BinOp::Mod),
+        "min" => binary(BinOp::Min),
+        "max" => binary(BinOp::Max),
+        "atan2" => binary(BinOp::Atan2),
+        "neg" => unary(UnOp::Neg),
+        "abs" => unary(UnOp::Abs),
+        "sqrt" => unary(UnOp::Sqrt),
+        "sin" => unary(UnOp::Sin),
+        "cos" => unary(UnOp::Cos),
+        "jmp" => {
+            expect_args(tokens, 1)?;
+            Ok(Instruction::Jump(parse_label(tokens[1], labels)?))
+        }
+        "jeq" => branch(Cmp::Eq),
+        "jne" => branch(Cmp::Ne),
+        "jlt" => branch(Cmp::Lt),
+        "jle" => branch(Cmp::Le),
+        "jgt" => branch(Cmp::Gt),
+        "jge" => branch(Cmp::Ge),
+        "gosub" => {
+            expect_args(tokens, 1)?;
+            Ok(Instruction::Gosub(parse_label(tokens[1], labels)?))
+        }
+        "return" => {
+            expect_args(tokens, 0)?;
+            Ok(Instruction::Return)
+        }
+        "call" => {
+            if tokens.len() < 2 {
+                return Err("`call` needs a function name".to_string());
+            }
+            let (args, results) = match tokens.iter().position(|t| *t == "->") {
+                Some(i) => (&tokens[2..i], &tokens[i + 1..]),
+                None => (&tokens[2..], &tokens[..0]),
+            };
+            Ok(Instruction::Call(
+                tokens[1].to_string(),
+                args.iter()
+                    .map(|t| parse_operand(t))
+                    .collect::<Result<_, _>>()?,
+                results
+                    .iter()
+                    .map(|t| parse_var(t))
+                    .collect::<Result<_, _>>()?,
+            ))
+        }
+        "print" => Ok(Instruction::Print(
+            tokens[1..]
+                .iter()
+                .map(|t| parse_operand(t))
+                .collect::<Result<_, _>>()?,
+        )),
+        "on" => {
+            expect_args(tokens, 2)?;
+            Ok(Instruction::On(
+                tokens[1].to_string(),
+                parse_label(tokens[2], labels)?,
+            ))
+        }
+        "wait" => {
+            expect_args(tokens, 1)?;
+            Ok(Instruction::Wait(parse_operand(tokens[1])?))
+        }
+        "yield" => {
+            expect_args(tokens, 0)?;
+            Ok(Instruction::Yield)
+        }
+        "halt" => {
+            expect_args(tokens, 0)?;
+            Ok(Instruction::Halt)
+        }
+        other => Err(format!("unknown instruction `{}`", other)),
+    }
+}
+
+/// :COMPONENT: Limits on how much of the CPU a script may use each time it
+/// runs. Ships without one get the default budget.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct CpuBudget {
+    pub instructions: usize,
+    pub time: Duration,
+}
+
+impl Default for CpuBudget {
+    fn default() -> Self {
+        Self {
+            instructions: SHIP_INSTRUCTION_BUDGET,
+            time: SHIP_TIME_BUDGET,
+        }
+    }
+}
+
+/// :EVENT: Sent when a ship's program runs out of CPU budget before it is done
+/// for the frame. It is suspended, and carries on from where it was next frame.
+#[derive(Clone, Copy, Debug)]
+pub struct CpuOverrun {
+    pub entity: Entity,
+    pub meter: Meter,
+}
+
+/// How much of the CPU a script used when it last ran.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Meter {
+    pub instructions: usize,
+    pub time: Duration,
+    /// Whether the script ran out of budget before it was done, and was
+    /// suspended.
+    pub overran: bool,
+}
+
+/// Implemented by anything which exposes functions to scripts through `call`.
+///
+/// The host decides which capabilities a script has: ship programs only get to
+/// fly their own ship, while the mission director may reshape the scenario.
+pub trait ScriptHost {
+    fn call(&mut self, function: &str, args: &[Value]) -> Result<Vec<Value>, String>;
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum VmState {
+    Running,
+    /// Sleeping for the given number of seconds.
+    Waiting(f32),
+    Halted,
+    Faulted(ScriptError),
+    /// Stopped at a breakpoint, until the debugger resumes or steps it.
+    Break,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Frame {
+    return_pc: usize,
+    /// Time left on a `wait` which was interrupted by an event handler.
+    interrupted_wait: f32,
+}
+
+/// Execution state of a single script.
+#[derive(Clone, Debug)]
+pub struct Vm {
+    pc: usize,
+    state: VmState,
+    vars: HashMap<String, Value>,
+    call_stack: Vec<Frame>,
+    handlers: HashMap<String, usize>,
+    pending_events: VecDeque<(usize, Vec<Value>)>,
+    /// Everything the script has `print`ed. Drained by the owner.
+    pub output: Vec<String>,
+    /// Source lines the script stops at, before running them.
+    pub breakpoints: BTreeSet<usize>,
+    /// Set when leaving a breakpoint, so the script doesn't stop at it again
+    /// straight away.
+    skip_breakpoint: bool,
+    /// Set to run a single instruction from a breakpoint.
+    single_step: bool,
+}
+
+impl Default for Vm {
+    fn default() -> Self {
+        Self {
+            pc: 0,
+            state: VmState::Running,
+            vars: HashMap::new(),
+            call_stack: Vec::new(),
+            handlers: HashMap::new(),
+            pending_events: VecDeque::new(),
+            output: Vec::new(),
+            breakpoints: BTreeSet::new(),
+            skip_breakpoint: false,
+            single_step: false,
+        }
+    }
+}
+
+impl Vm {
+    pub fn state(&self) -> &VmState {
+        &self.state
+    }
+
+    pub fn vars(&self) -> &HashMap<String, Value> {
+        &self.vars
+    }
+
+    /// Index of the next instruction to run.
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    /// Carries on from a breakpoint.
+    pub fn resume(&mut self) {
+        if self.state == VmState::Break {
+            self.state = VmState::Running;
+            self.skip_breakpoint = true;
+        }
+    }
+
+    /// Runs one instruction from a breakpoint, the next time the script runs,
+    /// then stops again.
+    pub fn step(&mut self) {
+        if self.state == VmState::Break {
+            self.single_step = true;
+        }
+    }
+
+    pub fn set_var(&mut self, name: &str, value: Value) {
+        self.vars.insert(name.to_string(), value);
+    }
+
+    /// Queues an event. If the script registered a handler for it with `on`, the
+    /// handler will be run the next time the script executes, with the event's
+    /// arguments in the variables `arg0`, `arg1`, ...
+    pub fn raise(&mut self, event: &str, args: Vec<Value>) {
+        if let Some(&pc) = self.handlers.get(event) {
+            self.pending_events.push_back((pc, args));
+        } else if let Some(&pc) = self.handlers.get("event") {
+            let args = std::iter::once(Value::Str(event.to_string()))
+                .chain(args)
+                .collect();
+            self.pending_events.push_back((pc, args));
+        }
+    }
+
+    /// Like [`Vm::raise`], but if the event's handler is already waiting to
+    /// run, only updates its arguments. For events raised every frame, which
+    /// would otherwise pile up behind a slow handler. Falls back on the `event`
+    /// catch-all the same way, which only holds one of each event at a time.
+    pub fn raise_unless_pending(&mut self, event: &str, args: Vec<Value>) {
+        if let Some(&pc) = self.handlers.get(event) {
+            match self.pending_events.iter_mut().find(|(p, _)| *p == pc) {
+                Some(pending) => pending.1 = args,
+                None => self.pending_events.push_back((pc, args)),
+            }
+        } else if let Some(&pc) = self.handlers.get("event") {
+            let name = Value::Str(event.to_string());
+            let args: Vec<_> = std::iter::once(name.clone()).chain(args).collect();
+            match self
+                .pending_events
+                .iter_mut()
+                .find(|(p, a)| *p == pc && a.first() == Some(&name))
+            {
+                Some(pending) => pending.1 = args,
+                None => self.pending_events.push_back((pc, args)),
+            }
+        }
+    }
+
+    /// Sets the handlers `program` declares with `on_` labels.
+    pub fn bind_callbacks(&mut self, program: &Program) {
+        for (event, pc) in &program.callbacks {
+            self.handlers.insert(event.clone(), *pc);
+        }
+    }
+
+    /// Stops the script for good.
+    pub fn halt(&mut self) {
+        self.state = VmState::Halted;
+    }
+
+    /// Puts the vm into the faulted state.
+    pub fn fault(&mut self, error: ScriptError) {
+        self.state = VmState::Faulted(error);
+    }
+
+    /// Runs the script until it yields, waits, halts, faults, or executes
+    /// `budget` instructions. `dt` is the time since the last run.
+    pub fn run(&mut self, program: &Program, host: &mut dyn ScriptHost, dt: f32, budget: usize) {
+        let budget = CpuBudget {
+            instructions: budget,
+            time: Duration::MAX,
+        };
+        self.run_metered(program, host, dt, &budget);
+    }
+
+    /// Like [`Vm::run`], but also stops once the script has run for longer than
+    /// the budget allows, and reports how much it used.
+    pub fn run_metered(
+        &mut self,
+        program: &Program,
+        host: &mut dyn ScriptHost,
+        dt: f32,
+        budget: &CpuBudget,
+    ) -> Meter {
+        let started = Instant::now();
+        let mut meter = Meter::default();
+        let mut limit = budget.instructions;
+        let mut stepping = false;
+        match self.state {
+            VmState::Halted | VmState::Faulted(_) => return meter,
+            VmState::Break if self.single_step => {
+                self.state = VmState::Running;
+                self.skip_breakpoint = true;
+                self.single_step = false;
+                stepping = true;
+                limit = 1;
+            }
+            VmState::Break => return meter,
+            VmState::Waiting(t) => {
+                if t - dt > 0.0 {
+                    self.state = VmState::Waiting(t - dt);
+                    if self.pending_events.is_empty() {
+                        return meter;
+                    }
+                } else {
+                    self.state = VmState::Running;
+                }
+            }
+            VmState::Running => {}
+        }
+
+        if let Some((handler, args)) = self.pending_events.pop_front() {
+            let interrupted_wait = match self.state {
+                VmState::Waiting(t) => t,
+                _ => 0.0,
+            };
+            self.call_stack.push(Frame {
+                return_pc: self.pc,
+                interrupted_wait,
+            });
+            for (i, arg) in args.into_iter().enumerate() {
+                self.vars.insert(format!("arg{}", i), arg);
+            }
+            self.pc = handler;
+            self.state = VmState::Running;
+        }
+
+        loop {
+            if self.state != VmState::Running {
+                break;
+            }
+            if meter.instructions >= limit
+                || (meter.instructions % 16 == 0 && started.elapsed() >= budget.time)
+            {
+                // out of budget, carry on from here next time
+                meter.overran = true;
+                break;
+            }
+
+            if self.pc >= program.len() {
+                self.state = VmState::Halted;
+                break;
+            }
+
+            if !std::mem::take(&mut self.skip_breakpoint)
+                && self.breakpoints.contains(&program.line_of(self.pc))
+            {
+                self.state = VmState::Break;
+                break;
+            }
+
+            meter.instructions += 1;
+            if let Err(message) = self.execute(program, host) {
+                self.state = VmState::Faulted(ScriptError {
+                    line: program.line_of(self.pc),
+                    message,
+                });
+                break;
+            }
+
+            // `yield` is implemented by stepping with the state set to a zero wait.
+            if self.state == VmState::Waiting(0.0) {
+                self.state = VmState::Running;
+                break;
+            }
+        }
+
+        // a single step stops at the next instruction, breakpoint or not
+        if stepping && self.state == VmState::Running {
+            self.state = VmState::Break;
+            meter.overran = false;
+        }
+
+        meter.time = started.elapsed();
+        meter
+    }
+
+    fn eval(&self, operand: &Operand) -> Result<Value, String> {
+        match operand {
+            Operand::Literal(v) => Ok(v.clone()),
+            Operand::Var(name) => self
+                .vars
+                .get(name)
+                .cloned()
+                .ok_or_else(|| format!("variable `{}` is not set", name)),
+        }
+    }
+
+    /// Executes the instruction at the program counter.
+    fn execute(&mut self, program: &Program, host: &mut dyn ScriptHost) -> Result<(), String> {
+        let mut next = self.pc + 1;
+
+        match &program.instructions[self.pc] {
+            Instruction::Set(dst, a) => {
+                let v = self.eval(a)?;
+                self.vars.insert(dst.clone(), v);
+            }
+            Instruction::Binary(op, dst, a, b) => {
+                let a = self.eval(a)?.as_num()?;
+                let b = self.eval(b)?.as_num()?;
+                let v = match op {
+                    BinOp::Add => a + b,
+                    BinOp::Sub => a - b,
+                    BinOp::Mul => a * b,
+                    BinOp::Div if b == 0.0 => return Err("division by zero".to_string()),
+                    BinOp::Div => a / b,
+                    BinOp::Mod if b == 0.0 => return Err("division by zero".to_string()),
+                    BinOp::Mod => a.rem_euclid(b),
+                    BinOp::Min => a.min(b),
+                    BinOp::Max => a.max(b),
+                    BinOp::Atan2 => a.atan2(b),
+                };
+                self.vars.insert(dst.clone(), Value::Num(v));
+            }
+            Instruction::Unary(op, dst, a) => {
+                let a = self.eval(a)?.as_num()?;
+                let v = match op {
+                    UnOp::Neg => -a,
+                    UnOp::Abs => a.abs(),
+                    UnOp::Sqrt => a.sqrt(),
+                    UnOp::Sin => a.sin(),
+                    UnOp::Cos => a.cos(),
+                };
+                self.vars.insert(dst.clone(), Value::Num(v));
+            }
+            Instruction::Jump(target) => next = *target,
+            Instruction::Branch(cmp, a, b, target) => {
+                let a = self.eval(a)?;
+                let b = self.eval(b)?;
+                let taken = match cmp {
+                    Cmp::Eq => a == b,
+                    Cmp::Ne => a != b,
+                    Cmp::Lt => a.as_num()? < b.as_num()?,
+                    Cmp::Le => a.as_num()? <= b.as_num()?,
+                    Cmp::Gt => a.as_num()? > b.as_num()?,
+                    Cmp::Ge => a.as_num()? >= b.as_num()?,
+                };
+                if taken {
+                    next = *target;
+                }
+            }
+            Instruction::Gosub(target) => {
+                self.call_stack.push(Frame {
+                    return_pc: next,
+                    interrupted_wait: 0.0,
+                });
+                next = *target;
+            }
+            Instruction::Return => {
+                let frame = self
+                    .call_stack
+                    .pop()
+                    .ok_or_else(|| "`return` without `gosub`".to_string())?;
+                next = frame.return_pc;
+                if frame.interrupted_wait > 0.0 {
+                    self.state = VmState::Waiting(frame.interrupted_wait);
+                }
+            }
+            Instruction::Call(function, args, results) => {
+                let args = args
+                    .iter()
+                    .map(|a| self.eval(a))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let values = host.call(function, &args)?;
+                if values.len() < results.len() {
+                    return Err(format!(
+                        "`{}` returns {} value(s), but {} were expected",
+                        function,
+                        values.len(),
+                        results.len()
+                    ));
+                }
+                for (dst, v) in results.iter().zip(values) {
+                    self.vars.insert(dst.clone(), v);
+                }
+            }
+            Instruction::Print(args) => {
+                let line = args
+                    .iter()
+                    .map(|a| self.eval(a).map(|v| v.to_string()))
+                    .collect::<Result<Vec<_>, _>>()?
+                    .join(" ");
+                self.output.push(line);
+            }
+            Instruction::On(event, handler) => {
+                self.handlers.insert(event.clone(), *handler);
+            }
+            Instruction::Wait(t) => {
+                let t = self.eval(t)?.as_num()? as f32;
+                if t > 0.0 {
+                    self.state = VmState::Waiting(t);
+                }
+            }
+            Instruction::Yield => self.state = VmState::Waiting(0.0),
+            Instruction::Halt => self.state = VmState::Halted,
+        }
+
+        self.pc = next;
+        Ok(())
+    }
+}
+
+/// Raw source code of a script, loaded from a `.sasm` file.
+#[derive(TypeUuid, Debug)]
+#[uuid = "a3c3bd0e-9a39-4f2e-8a8a-2d5a1f3c7c41"]
+pub struct ScriptSource(pub String);
+
+#[derive(Default)]
+pub struct ScriptLoader;
+
+impl AssetLoader for ScriptLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let source = std::str::from_utf8(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(ScriptSource(source.to_string())));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["sasm"]
+    }
+}
+
+/// :COMPONENT: A program which flies the ship it is attached to.
+///
+/// The source is compiled once the asset has loaded. Anything the program
+/// prints, along with runtime errors, ends up in `console`.
+#[derive(Component)]
+pub struct ShipProgram {
+    pub source: Handle<ScriptSource>,
+    program: Option<Program>,
+    pub vm: Vm,
+    pub console: Vec<String>,
+    /// CPU used the last time the program ran.
+    pub meter: Meter,
+    /// Rate of rotation commanded by the program, in radians per second.
+    turn_rate: f32,
+}
+
+impl ShipProgram {
+    pub fn new(source: Handle<ScriptSource>) -> Self {
+        Self {
+            source,
+            program: None,
+            vm: Vm::default(),
+            console: Vec::new(),
+            meter: Meter::default(),
+            turn_rate: 0.0,
+        }
+    }
+
+    /// Throws away the compiled program and everything the VM was doing, so
+    /// the program starts over from the top once its source is next available.
+    pub fn reset(&mut self) {
+        self.program = None;
+        self.vm = Vm::default();
+        self.turn_rate = 0.0;
+    }
+
+    /// The compiled program, once the source has loaded.
+    pub fn compiled(&self) -> Option<&Program> {
+        self.program.as_ref()
+    }
+
+    /// Halts the program where it is.
+    pub fn stop(&mut self) {
+        self.vm.halt();
+        self.turn_rate = 0.0;
+    }
+}
+
+/// Undoes whatever a ship's program left running when it stopped: the engine is
+/// cut, and anything the program scheduled is cancelled.
+pub fn release_controls(entity: Entity, engine: Option<&mut Engine>, scheduler: &mut Scheduler) {
+    if let Some(engine) = engine {
+        engine.throttle = Throttle::Variable(0.0);
+    }
+    scheduler.cancel_program(entity);
+}
+
+/// A copy of a kinimatic body's state, handed to script hosts so they can
+/// answer questions about the world without holding onto queries.
+#[derive(Clone, Copy, Debug)]
+pub struct BodySnapshot {
+    pub entity: Entity,
+    pub position: Vec3,
+    pub velocity: Vec3,
+    pub mass: f32,
+}
+
+/// Looks up the body an entity argument refers to.
+pub fn find_body(bodies: &[BodySnapshot], arg: &Value) -> Result<BodySnapshot, String> {
+    let entity = arg.as_entity()?;
+    bodies
+        .iter()
+        .find(|b| b.entity == entity)
+        .copied()
+        .ok_or_else(|| format!("no body with id {}", arg))
+}
+
+/// Looks up the body an entity argument refers to as a ship's sensor sees it.
+/// Where jamming has spoofed the contact, the body is where it appears to be,
+/// and bodies the sensor hasn't picked up, quiet ships included, can't be
+/// looked up at all.
+fn sensed(
+    bodies: &[BodySnapshot],
+    contacts: Option<&Contacts>,
+    arg: &Value,
+) -> Result<BodySnapshot, String> {
+    let body = find_body(bodies, arg)?;
+    match contacts.and_then(|c| c.0.iter().find(|c| c.entity == body.entity)) {
+        Some(contact) => Ok(BodySnapshot {
+            position: contact.position,
+            velocity: contact.velocity,
+            ..body
+        }),
+        None => Err(format!("body {} not detected", arg)),
+    }
+}
+
+/// The functions a ship program may call. These only ever touch the ship the
+/// program is running on.
+struct ShipHost<'a> {
+    entity: Entity,
+    bodies: &'a [BodySnapshot],
+    transform: &'a Transform,
+    engine: Option<&'a mut Engine>,
+    turn_rate: &'a mut f32,
+    docking: &'a mut Vec<DockingRequest>,
+    boards: &'a mut Vec<BoardShip>,
+    transfers: &'a mut Vec<TransferRequest>,
+    power: Option<&'a PowerGrid>,
+    thermal: Option<&'a Thermal>,
+    shield: Option<&'a mut Shield>,
+    damage_control: Option<&'a mut DamageControl>,
+    jammer: Option<&'a mut Jammer>,
+    jamming: Option<&'a Jamming>,
+    emissions: Option<&'a mut Emissions>,
+    sensor: Option<&'a Sensor>,
+    contacts: Option<&'a Contacts>,
+    power_requests: &'a mut Vec<PowerRequest>,
+    scheduler: &'a mut Scheduler,
+    blackboard: Option<&'a mut Blackboard>,
+    publishes: &'a mut Vec<Publish>,
+    transceiver: Option<&'a mut Transceiver>,
+    hold: Option<&'a CargoHold>,
+    hull: Option<&'a Hull>,
+    route: Option<&'a mut Route>,
+    throttle_program: Option<&'a mut ThrottleProgram>,
+    jumps: &'a mut Vec<JumpRequest>,
+    trades: &'a mut Vec<TradeRequest>,
+    markets: &'a Query<'a, 'a, &'static Market>,
+    physics: &'a PhysicsConfig,
+    elapsed: f32,
+}
+
+impl<'a> ScriptHost for ShipHost<'a> {
+    fn call(&mut self, function: &str, args: &[Value]) -> Result<Vec<Value>, String> {
+        // other ships are only known through the sensor, spoofing and all
+        let sense = |id: &Value| match id.as_entity() {
+            Ok(entity) if entity == self.entity => find_body(self.bodies, id),
+            _ => sensed(self.bodies, self.contacts, id),
+        };
+        let target = |args: &[Value]| match args.first() {
+            Some(id) => sense(id),
+            None => find_body(self.bodies, &Value::from_entity(self.entity)),
+        };
+
+        match function {
+            "throttle" => {
+                let amount = args.first().ok_or("`throttle` needs an amount")?.as_num()? as f32;
+                let engine = self.engine.as_mut().ok_or("this ship has no engine")?;
+                engine.throttle = Throttle::Variable(amount.clamp(0.0, 1.0));
+                Ok(vec![])
+            }
+            "turn" => {
+                *self.turn_rate = args.first().ok_or("`turn` needs a rate")?.as_num()? as f32;
+                Ok(vec![])
+            }
+            // gimbal angle
+            //
+            // Swivels the engine, in radians from the ship's nose. Angles past the
+            // engine's gimbal limit are clamped to it.
+            "gimbal" => {
+                let angle = args.first().ok_or("`gimbal` needs an angle")?.as_num()? as f32;
+                let engine = self.engine.as_mut().ok_or("this ship has no engine")?;
+                engine.gimbal = angle.clamp(-engine.gimbal_limit, engine.gimbal_limit);
+                Ok(vec![])
+            }
+            "heading" => {
+                let (_, _, angle) = self.transform.rotation.to_euler(EulerRot::XYZ);
+                Ok(vec![Value::Num(angle as f64)])
+            }
+            "position" => {
+                let b = target(args)?;
+                Ok(vec![
+                    Value::Num(b.position.x as f64),
+                    Value::Num(b.position.y as f64),
+                ])
+            }
+            "velocity" => {
+                let b = target(args)?;
+                Ok(vec![
+                    Value::Num(b.velocity.x as f64),
+                    Value::Num(b.velocity.y as f64),
+                ])
+            }
+            "mass" => Ok(vec![Value::Num(target(args)?.mass as f64)]),
+            // closest_approach id -> seconds distance
+            "closest_approach" => {
+                let other = sense(args.first().ok_or("`closest_approach` needs a target")?)?;
+                let approach = navigation::closest_approach(&target(&[])?, &other);
+                Ok(vec![
+                    Value::Num(approach.time as f64),
+                    Value::Num(approach.distance as f64),
+                ])
+            }
+            // orbit id -> semi_major eccentricity
+            //
+            // The orbit this ship is on around `id`.
+            "orbit" => {
+                let central = find_body(self.bodies, args.first().ok_or("`orbit` needs a body")?)?;
+                let orbit = navigation::orbit(&target(&[])?, &central, self.physics);
+                Ok(vec![
+                    Value::Num(orbit.semi_major as f64),
+                    Value::Num(orbit.eccentricity as f64),
+                ])
+            }
+            // intercept id seconds -> dvx dvy
+            //
+            // The change in velocity needed to meet the target in `seconds`.
+            "intercept" => {
+                let [other, time] = args else {
+                    return Err("`intercept` needs a target and a time".to_string());
+                };
+                let other = sense(other)?;
+                let dv =
+                    navigation::intercept_delta_v(&target(&[])?, &other, time.as_num()? as f32)?;
+                Ok(vec![Value::Num(dv.x as f64), Value::Num(dv.y as f64)])
+            }
+            // hohmann id radius -> departure arrival seconds
+            //
+            // Estimates the burns to go from a circular orbit around `id` at
+            // this ship's current distance, to one of `radius`.
+            "hohmann" => {
+                let [central, radius] = args else {
+                    return Err("`hohmann` needs a body and a radius".to_string());
+                };
+                let central = find_body(self.bodies, central)?;
+                let from = central.position.distance(self.transform.translation);
+                let transfer =
+                    navigation::hohmann(&central, from, radius.as_num()? as f32, self.physics)?;
+                Ok(vec![
+                    Value::Num(transfer.departure as f64),
+                    Value::Num(transfer.arrival as f64),
+                    Value::Num(transfer.time as f64),
+                ])
+            }
+            // lead id speed -> heading seconds
+            //
+            // The heading to fire something at `speed` so that it hits the target.
+            "lead" => {
+                let [other, speed] = args else {
+                    return Err("`lead` needs a target and a speed".to_string());
+                };
+                let other = sense(other)?;
+                let (direction, time) =
+                    navigation::lead(&target(&[])?, &other, speed.as_num()? as f32)
+                        .ok_or("the target can't be caught at that speed")?;
+                Ok(vec![
+                    Value::Num(navigation::heading_of(direction) as f64),
+                    Value::Num(time as f64),
+                ])
+            }
+            "fuel" => {
+                let engine = self.engine.as_ref().ok_or("this ship has no engine")?;
+                Ok(vec![Value::Num(engine.fuel as f64)])
+            }
+            "fuel_capacity" => {
+                let engine = self.engine.as_ref().ok_or("this ship has no engine")?;
+                Ok(vec![Value::Num(engine.fuel_capacity as f64)])
+            }
+            // delta_v -> dv isp seconds
+            //
+            // What the fuel left is worth: the change in velocity it can make,
+            // the engine's specific impulse, and how long it takes to burn.
+            "delta_v" => {
+                let engine = self.engine.as_ref().ok_or("this ship has no engine")?;
+                let budget = navigation::delta_v(engine, target(&[])?.mass);
+                Ok(vec![
+                    Value::Num(budget.total as f64),
+                    Value::Num(budget.isp as f64),
+                    Value::Num(budget.burn_time as f64),
+                ])
+            }
+            // burn_time dv -> seconds fuel
+            //
+            // How long a burn of `dv` takes at full throttle, and the fuel it
+            // uses.
+            "burn_time" => {
+                let dv = args
+                    .first()
+                    .ok_or("`burn_time` needs a delta-v")?
+                    .as_num()? as f32;
+                let engine = self.engine.as_ref().ok_or("this ship has no engine")?;
+                let burn = navigation::burn(engine, target(&[])?.mass, dv)?;
+                Ok(vec![
+                    Value::Num(burn.time as f64),
+                    Value::Num(burn.fuel as f64),
+                ])
+            }
+            "dock" => {
+                let target = args.first().ok_or("`dock` needs a target")?.as_entity()?;
+                self.docking.push(DockingRequest::Dock {
+                    ship: self.entity,
+                    target,
+                });
+                Ok(vec![])
+            }
+            "undock" => {
+                self.docking
+                    .push(DockingRequest::Undock { ship: self.entity });
+                Ok(vec![])
+            }
+            // board id
+            //
+            // Sends a boarding party over to a crippled hostile ship alongside.
+            // Raises `boarding_started`, then `captured` or `boarding_failed`.
+            "board" => {
+                let prize = args.first().ok_or("`board` needs a target")?.as_entity()?;
+                self.boards.push(BoardShip {
+                    captor: self.entity,
+                    prize,
+                });
+                Ok(vec![])
+            }
+            "pump" => {
+                let [other, rate] = args else {
+                    return Err("`pump` needs a ship and a rate".to_string());
+                };
+                self.transfers.push(TransferRequest::Start {
+                    ship: self.entity,
+                    other: other.as_entity()?,
+                    stores: Stores::Fuel,
+                    rate: rate.as_num()? as f32,
+                });
+                Ok(vec![])
+            }
+            // transfer id stores rate
+            //
+            // Like `pump`, but for any stores: "fuel", "ammo" or "cargo".
+            "transfer" => {
+                let [other, stores, rate] = args else {
+                    return Err("`transfer` needs a ship, the stores and a rate".to_string());
+                };
+                self.transfers.push(TransferRequest::Start {
+                    ship: self.entity,
+                    other: other.as_entity()?,
+                    stores: Stores::parse(stores.as_str()?)?,
+                    rate: rate.as_num()? as f32,
+                });
+                Ok(vec![])
+            }
+            "stop_pump" | "stop_transfer" => {
+                self.transfers
+                    .push(TransferRequest::Stop { ship: self.entity });
+                Ok(vec![])
+            }
+            // jump system x y
+            //
+            // Starts the ship's jump drive charging, to jump to (x, y) in
+            // another star system.
+            "jump" => {
+                let (to, x, y) = match args {
+                    [to, x, y, ..] => (to.as_num()?, x.as_num()? as f32, y.as_num()? as f32),
+                    _ => return Err("`jump` needs a system, an x and a y".to_string()),
+                };
+                self.jumps.push(JumpRequest {
+                    ship: self.entity,
+                    to: to as usize,
+                    arrival: Vec3::new(x, y, 0.0),
+                });
+                Ok(vec![])
+            }
+            // buy commodity tonnes
+            // sell commodity tonnes
+            //
+            // Trades with the market of the station the ship is docked to.
+            // Raises `traded` once done.
+            "buy" | "sell" => {
+                let (commodity, tonnes) = match args {
+                    [commodity, tonnes, ..] => (commodity.as_str()?, tonnes.as_num()? as f32),
+                    _ => return Err(format!("`{}` needs a commodity and tonnes", function)),
+                };
+                self.trades.push(TradeRequest {
+                    ship: self.entity,
+                    commodity: commodity.to_string(),
+                    tonnes: if function == "buy" { tonnes } else { -tonnes },
+                });
+                Ok(vec![])
+            }
+            // price station commodity -> buy sell stock
+            //
+            // What `station`'s market charges and pays for a commodity, and
+            // how much it has.
+            "price" => {
+                let station = args.first().ok_or("`price` needs a station")?.as_entity()?;
+                let commodity = args.get(1).ok_or("`price` needs a commodity")?.as_str()?;
+                self.markets
+                    .get(station)
+                    .map_err(|_| format!("{} has no market", station.index()))?
+                    .quote(commodity)
+            }
+            // waypoint x y [arrival_speed]
+            //
+            // Adds a waypoint to the end of the ship's route.
+            "waypoint" => {
+                let route = self
+                    .route
+                    .as_mut()
+                    .ok_or("this ship can't follow a route")?;
+                let (x, y) = match args {
+                    [x, y, ..] => (x.as_num()? as f32, y.as_num()? as f32),
+                    _ => return Err("`waypoint` needs an x and a y".to_string()),
+                };
+                let mut waypoint = Waypoint::new(Vec3::new(x, y, 0.0));
+                if let Some(speed) = args.get(2) {
+                    waypoint.arrival_speed = speed.as_num()? as f32;
+                }
+                route.waypoints.push_back(waypoint);
+                Ok(vec![])
+            }
+            // plan_burn seconds throttle [heading]
+            //
+            // Plans for the throttle to go to `throttle` in `seconds`, turning
+            // to `heading` if one's given. The course projection follows the
+            // plan too.
+            "plan_burn" => {
+                let program = self
+                    .throttle_program
+                    .as_mut()
+                    .ok_or("this ship can't plan burns")?;
+                let (seconds, throttle) = match args {
+                    [seconds, throttle, ..] => (seconds.as_num()?, throttle.as_num()? as f32),
+                    _ => return Err("`plan_burn` needs a time and a throttle".to_string()),
+                };
+                let heading = args.get(2).map(|h| h.as_num()).transpose()?;
+                program.add(ThrottleStep {
+                    at: self.elapsed as f64 + seconds.max(0.0),
+                    throttle: throttle.clamp(0.0, 1.0),
+                    heading: heading.map(|h| h as f32),
+                });
+                Ok(vec![])
+            }
+            "clear_plan" => {
+                let program = self
+                    .throttle_program
+                    .as_mut()
+                    .ok_or("this ship can't plan burns")?;
+                program.steps.clear();
+                Ok(vec![])
+            }
+            "clear_route" => {
+                let route = self
+                    .route
+                    .as_mut()
+                    .ok_or("this ship can't follow a route")?;
+                route.waypoints.clear();
+                Ok(vec![])
+            }
+            // route -> waypoints_left
+            "route" => {
+                let route = self
+                    .route
+                    .as_ref()
+                    .ok_or("this ship can't follow a route")?;
+                Ok(vec![Value::Num(route.waypoints.len() as f64)])
+            }
+            // cargo -> tonnes capacity
+            "cargo" => {
+                let hold = self.hold.ok_or("this ship has no cargo hold")?;
+                Ok(vec![
+                    Value::Num(hold.cargo as f64),
+                    Value::Num(hold.capacity as f64),
+                ])
+            }
+            // power -> generation demand stored capacity supply
+            "power" => {
+                let grid = self.power.ok_or("this ship has no power grid")?;
+                Ok(vec![
+                    Value::Num(grid.generation as f64),
+                    Value::Num(grid.demand as f64),
+                    Value::Num(grid.stored as f64),
+                    Value::Num(grid.capacity as f64),
+                    Value::Num(grid.supply as f64),
+                ])
+            }
+            // solar -> output peak
+            //
+            // Power the ship's solar panels are generating, and what they would
+            // generate turned squarely to the star.
+            "solar" => {
+                let grid = self.power.ok_or("this ship has no power grid")?;
+                Ok(vec![
+                    Value::Num(grid.solar as f64),
+                    Value::Num(grid.solar_peak as f64),
+                ])
+            }
+            // temperature -> temperature overheat critical
+            "temperature" => {
+                let thermal = self.thermal.ok_or("this ship has no thermal model")?;
+                Ok(vec![
+                    Value::Num(thermal.temperature as f64),
+                    Value::Num(thermal.overheat as f64),
+                    Value::Num(thermal.critical as f64),
+                ])
+            }
+            // shields -> front right back left
+            "shields" => {
+                let shield = self.shield.as_ref().ok_or("this ship has no shield")?;
+                Ok(shield
+                    .strength
+                    .iter()
+                    .map(|s| Value::Num(*s as f64))
+                    .collect())
+            }
+            // shield_boost [quadrant|id]
+            //
+            // Puts the shield's whole recharge into one quadrant: "front",
+            // "right", "back" or "left", or whichever faces the body `id`.
+            // Without an argument, the recharge is shared out evenly again.
+            "shield_boost" => {
+                let facing = match args.first() {
+                    Some(Value::Str(name)) => Some(Quadrant::parse(name)?),
+                    Some(id) => {
+                        let towards = sense(id)?.position - self.transform.translation;
+                        Some(Quadrant::facing(self.transform.rotation, towards))
+                    }
+                    None => None,
+                };
+                let shield = self.shield.as_mut().ok_or("this ship has no shield")?;
+                shield.boost = facing;
+                Ok(vec![])
+            }
+            // repairs -> hull max_hull seconds_until_repairs
+            "repairs" => {
+                let control = self
+                    .damage_control
+                    .as_ref()
+                    .ok_or("this ship has no damage control")?;
+                let hull = self.hull.ok_or("this ship has no hull")?;
+                Ok(vec![
+                    Value::Num(hull.integrity as f64),
+                    Value::Num(hull.max_integrity as f64),
+                    Value::Num((control.calm_time - control.since_hit).max(0.0) as f64),
+                ])
+            }
+            // repair_priority name...
+            //
+            // Has damage control see to these first, in order: "hull",
+            // "thrusters", "sensors", "weapons", "shields" or "modules".
+            "repair_priority" => {
+                let repairs = args
+                    .iter()
+                    .map(|a| Repair::parse(a.as_str()?))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let control = self
+                    .damage_control
+                    .as_mut()
+                    .ok_or("this ship has no damage control")?;
+                control.prioritize(&repairs);
+                Ok(vec![])
+            }
+            "jam_on" | "jam_off" => {
+                let jammer = self.jammer.as_mut().ok_or("this ship has no jammer")?;
+                jammer.enabled = function == "jam_on";
+                Ok(vec![])
+            }
+            // jamming -> level burn_through
+            //
+            // How hard this ship is being jammed, and how far its sensor still
+            // sees clearly. Contacts further out than that are only roughly
+            // where they appear.
+            "jamming" => {
+                let level = self.jamming.map_or(0.0, |j| j.0);
+                let sensor = self.sensor.ok_or("this ship has no sensor")?;
+                Ok(vec![
+                    Value::Num(level as f64),
+                    Value::Num(sensors::burn_through(sensor.range, level) as f64),
+                ])
+            }
+            // silent_on | silent_off
+            //
+            // Silent running: the radar and jammer go off and the thrusters are
+            // held back, so the ship is much harder to pick up.
+            "silent_on" | "silent_off" => {
+                let emissions = self
+                    .emissions
+                    .as_mut()
+                    .ok_or("this ship has no emissions")?;
+                emissions.silent = function == "silent_on";
+                Ok(vec![])
+            }
+            // signature -> signature silent
+            //
+            // How loud this ship is: sensors pick it up out to their range
+            // times its signature.
+            "signature" => {
+                let emissions = self
+                    .emissions
+                    .as_ref()
+                    .ok_or("this ship has no emissions")?;
+                Ok(vec![
+                    Value::Num(emissions.signature as f64),
+                    Value::Num(emissions.silent as u8 as f64),
+                ])
+            }
+            "power_on" | "power_off" => {
+                let subsystem = args
+                    .first()
+                    .ok_or_else(|| format!("`{}` needs a subsystem", function))?
+                    .as_str()?;
+                self.power_requests.push(PowerRequest {
+                    ship: self.entity,
+                    subsystem: Subsystem::parse(subsystem)?,
+                    enabled: function == "power_on",
+                });
+                Ok(vec![])
+            }
+            // schedule event seconds [period] -> id
+            //
+            // Raises `event` on this program after `seconds` of simulation
+            // time, then every `period` seconds after that, if given.
+            "schedule" => {
+                let entity = self.entity;
+                schedule(self.scheduler, args, |event| Action::Program(entity, event))
+            }
+            "cancel" => {
+                let id = args.first().ok_or("`cancel` needs an id")?.as_num()?;
+                Ok(vec![Value::Num(
+                    self.scheduler.cancel_for(id as u64, self.entity) as u8 as f64,
+                )])
+            }
+            // publish key value...
+            //
+            // Shares values with the rest of the faction, replacing whatever
+            // was under `key`. Other ships see them once the signal reaches them.
+            "publish" => {
+                let key = args.first().ok_or("`publish` needs a key")?.as_str()?;
+                let board = self
+                    .blackboard
+                    .as_mut()
+                    .ok_or("this ship has no blackboard")?;
+                let update =
+                    board.publish(self.entity, key, args[1..].to_vec(), self.elapsed as f64);
+                self.publishes.push(update);
+                Ok(vec![])
+            }
+            // entries key -> count
+            "entries" => {
+                let key = args.first().ok_or("`entries` needs a key")?.as_str()?;
+                let board = self
+                    .blackboard
+                    .as_ref()
+                    .ok_or("this ship has no blackboard")?;
+                let count = board.entries.get(key).map_or(0, |e| e.values.len());
+                Ok(vec![Value::Num(count as f64)])
+            }
+            // read key [index] -> value
+            "read" => {
+                let key = args.first().ok_or("`read` needs a key")?.as_str()?;
+                let index = match args.get(1) {
+                    Some(i) => i.as_num()? as usize,
+                    None => 0,
+                };
+                let board = self
+                    .blackboard
+                    .as_ref()
+                    .ok_or("this ship has no blackboard")?;
+                let value = board
+                    .entries
+                    .get(key)
+                    .and_then(|e| e.values.get(index))
+                    .ok_or_else(|| format!("nothing at `{}` {}", key, index))?;
+                Ok(vec![value.clone()])
+            }
+            // subscribe key
+            //
+            // Raises `board_update` with the key whenever another ship's
+            // update to it arrives.
+            "subscribe" | "unsubscribe" => {
+                let key = args
+                    .first()
+                    .ok_or_else(|| format!("`{}` needs a key", function))?
+                    .as_str()?;
+                let board = self
+                    .blackboard
+                    .as_mut()
+                    .ok_or("this ship has no blackboard")?;
+                if function == "subscribe" {
+                    board.subscriptions.insert(key.to_string());
+                } else {
+                    board.subscriptions.remove(key);
+                }
+                Ok(vec![])
+            }
+            // send id payload
+            // broadcast payload
+            //
+            // Queues a message for one ship, or for every ship in range. The
+            // payload is sent as text.
+            "send" | "broadcast" => {
+                let (to, payload) = match (function, args) {
+                    ("send", [id, payload, ..]) => (Some(id.as_entity()?), payload),
+                    ("broadcast", [payload, ..]) => (None, payload),
+                    _ => return Err(format!("`{}` is missing arguments", function)),
+                };
+                let radio = self
+                    .transceiver
+                    .as_mut()
+                    .ok_or("this ship has no transceiver")?;
+                radio.send(Message {
+                    from: self.entity,
+                    to,
+                    payload: payload.to_string().into_bytes(),
+                })?;
+                Ok(vec![])
+            }
+            // pending -> count
+            "pending" => {
+                let radio = self
+                    .transceiver
+                    .as_ref()
+                    .ok_or("this ship has no transceiver")?;
+                Ok(vec![Value::Num(radio.inbox.len() as f64)])
+            }
+            // receive -> from payload
+            //
+            // Takes the oldest message which has arrived.
+            "receive" => {
+                let radio = self
+                    .transceiver
+                    .as_mut()
+                    .ok_or("this ship has no transceiver")?;
+                let message = radio.inbox.pop_front().ok_or("no messages waiting")?;
+                Ok(vec![
+                    Value::from_entity(message.from),
+                    Value::Str(String::from_utf8_lossy(&message.payload).into_owned()),
+                ])
+            }
+            "time" => Ok(vec![Value::Num(self.elapsed as f64)]),
+            other => Err(format!("unknown function `{}`", other)),
+        }
+    }
+}
+
+/// Implements the `schedule event seconds [period] -> id` builtin, for any host.
+pub fn schedule(
+    scheduler: &mut Scheduler,
+    args: &[Value],
+    action: impl FnOnce(ScriptEvent) -> Action,
+) -> Result<Vec<Value>, String> {
+    let (Some(name), Some(delay)) = (args.first(), args.get(1)) else {
+        return Err("`schedule` needs an event and a delay".to_string());
+    };
+    let action = action(ScriptEvent {
+        name: name.as_str()?.to_string(),
+        args: vec![],
+    });
+    let id = match args.get(2) {
+        Some(period) => scheduler.every(delay.as_num()?, period.as_num()?, action)?,
+        None => scheduler.after(delay.as_num()?, action),
+    };
+    Ok(vec![Value::Num(id as f64)])
+}
+
+/// :SYSTEM: Restarts every ship program whose source file has changed on disk.
+/// The ship's engine is cut, and anything the old program scheduled is
+/// cancelled, so nothing it was doing carries over into the new program.
+fn reload_program_system(
+    mut asset_events: EventReader<AssetEvent<ScriptSource>>,
+    mut programs: Query<(Entity, &mut ShipProgram, Option<&mut Engine>)>,
+    mut scheduler: ResMut<Scheduler>,
+) {
+    for event in asset_events.iter() {
+        let AssetEvent::Modified { handle } = event else {
+            continue;
+        };
+
+        for (entity, mut program, engine) in programs.iter_mut() {
+            if program.source != *handle {
+                continue;
+            }
+            program.reset();
+            program.console.push("reloaded".to_string());
+            release_controls(entity, engine.map(|e| e.into_inner()), &mut scheduler);
+        }
+    }
+}
+
+/// Everything needed to run ship programs.
+#[allow(clippy::type_complexity)]
+#[derive(SystemParam)]
+pub struct ProgramContext<'w, 's> {
+    ships: Query<
+        'w,
+        's,
+        (
+            Entity,
+            Option<&'static Kinimatics>,
+            &'static Transform,
+            Option<&'static mut Attitude>,
+            Option<&'static mut Engine>,
+            Option<&'static PowerGrid>,
+            Option<&'static Thermal>,
+            Option<&'static mut ShipProgram>,
+            Option<&'static mut Blackboard>,
+            Option<&'static CpuBudget>,
+            Option<&'static mut Transceiver>,
+            Option<&'static CargoHold>,
+            Option<&'static mut Route>,
+            Option<&'static mut ThrottleProgram>,
+            (
+                Option<&'static mut Shield>,
+                Option<&'static mut DamageControl>,
+                Option<&'static Hull>,
+                Option<&'static mut Jammer>,
+                Option<&'static Jamming>,
+                Option<&'static mut Emissions>,
+                Option<&'static Sensor>,
+                Option<&'static Contacts>,
+            ),
+        ),
+    >,
+    sources: Res<'w, Assets<ScriptSource>>,
+    docking: EventWriter<'w, DockingRequest>,
+    boards: EventWriter<'w, BoardShip>,
+    transfers: EventWriter<'w, TransferRequest>,
+    jumps: EventWriter<'w, JumpRequest>,
+    trades: EventWriter<'w, TradeRequest>,
+    markets: Query<'w, 's, &'static Market>,
+    power: EventWriter<'w, PowerRequest>,
+    scheduler: ResMut<'w, Scheduler>,
+    blackboard: EventWriter<'w, Publish>,
+    overruns: EventWriter<'w, CpuOverrun>,
+    clock: Res<'w, SimulationClock>,
+    physics: Res<'w, PhysicsConfig>,
+    determinism: Res<'w, Determinism>,
+    tournament: Option<Res<'w, Tournament>>,
+}
+
+impl ProgramContext<'_, '_> {
+    /// Runs every ship program for `dt` seconds, or with `only_at_break`, just
+    /// the ones stopped at a breakpoint.
+    pub fn run(&mut self, dt: f32, only_at_break: bool) {
+        // docked ships don't have kinimatics of their own, so they don't show up here
+        let bodies: Vec<BodySnapshot> = self
+            .ships
+            .iter()
+            .filter_map(|(entity, kin, transform, ..)| {
+                kin.map(|kin| BodySnapshot {
+                    entity,
+                    position: transform.translation,
+                    velocity: kin.velocity,
+                    mass: kin.mass,
+                })
+            })
+            .collect();
+        let mut docking_requests = Vec::new();
+        let mut board_requests = Vec::new();
+        let mut transfer_requests = Vec::new();
+        let mut jump_requests = Vec::new();
+        let mut trade_requests = Vec::new();
+        let mut power_requests = Vec::new();
+        let mut publishes = Vec::new();
+        let mut overruns = Vec::new();
+        // wall-clock limits would make runs play out differently from one
+        // machine to the next, so only instructions are counted
+        let deterministic = self.determinism.enabled || self.tournament.is_some();
+
+        for (
+            entity,
+            _,
+            transform,
+            attitude,
+            engine,
+            grid,
+            thermal,
+            program,
+            blackboard,
+            budget,
+            transceiver,
+            hold,
+            route,
+            throttle_program,
+            (shield, damage_control, hull, jammer, jamming, emissions, sensor, contacts),
+        ) in self.ships.iter_mut()
+        {
+            let Some(mut program) = program else { continue };
+            let program = &mut *program;
+            if only_at_break && program.vm.state() != &VmState::Break {
+                continue;
+            }
+
+            if program.program.is_none() {
+                let Some(source) = self.sources.get(&program.source) else {
+                    continue;
+                };
+                match Program::parse(&source.0) {
+                    Ok(p) => {
+                        program.vm.bind_callbacks(&p);
+                        program.program = Some(p);
+                    }
+                    Err(e) => {
+                        program.console.push(format!("error: {}", e));
+                        program.program = Some(Program::default());
+                        program.vm.fault(e);
+                        continue;
+                    }
+                }
+                program.vm.set_var("self", Value::from_entity(entity));
+            }
+
+            let mut host = ShipHost {
+                entity,
+                bodies: &bodies,
+                transform,
+                engine: engine.map(|e| e.into_inner()),
+                turn_rate: &mut program.turn_rate,
+                docking: &mut docking_requests,
+                boards: &mut board_requests,
+                transfers: &mut transfer_requests,
+                jumps: &mut jump_requests,
+                trades: &mut trade_requests,
+                markets: &self.markets,
+                hold,
+                route: route.map(|r| r.into_inner()),
+                throttle_program: throttle_program.map(|p| p.into_inner()),
+                power: grid,
+                thermal,
+                shield: shield.map(|s| s.into_inner()),
+                damage_control: damage_control.map(|d| d.into_inner()),
+                hull,
+                jammer: jammer.map(|j| j.into_inner()),
+                jamming,
+                emissions: emissions.map(|e| e.into_inner()),
+                sensor,
+                contacts,
+                power_requests: &mut power_requests,
+                scheduler: &mut self.scheduler,
+                blackboard: blackboard.map(|b| b.into_inner()),
+                publishes: &mut publishes,
+                transceiver: transceiver.map(|t| t.into_inner()),
+                physics: &self.physics,
+                elapsed: self.clock.met(),
+            };
+
+            let mut budget = budget.copied().unwrap_or_default();
+            if deterministic {
+                budget.time = Duration::MAX;
+            }
+            let was_faulted = matches!(program.vm.state(), VmState::Faulted(_));
+            program.meter =
+                program
+                    .vm
+                    .run_metered(program.program.as_ref().unwrap(), &mut host, dt, &budget);
+            if program.meter.overran {
+                overruns.push(CpuOverrun {
+                    entity,
+                    meter: program.meter,
+                });
+            }
+
+            program.console.append(&mut program.vm.output);
+            if let (false, VmState::Faulted(e)) = (was_faulted, program.vm.state()) {
+                program.console.push(format!("error: {}", e));
+            }
+
+            let max_rate = std::f32::consts::PI;
+            if let Some(mut attitude) = attitude {
+                attitude.command(program.turn_rate.clamp(-max_rate, max_rate));
+            }
+        }
+
+        self.docking.send_batch(docking_requests);
+        self.boards.send_batch(board_requests);
+        self.transfers.send_batch(transfer_requests);
+        self.jumps.send_batch(jump_requests);
+        self.trades.send_batch(trade_requests);
+        self.power.send_batch(power_requests);
+        self.blackboard.send_batch(publishes);
+        self.overruns.send_batch(overruns);
+    }
+}
+
+/// :SYSTEM: Compiles ship programs once their source has loaded, then runs each
+/// of them for one frame.
+pub fn ship_program_system(mut programs: ProgramContext, time: Res<Time>) {
+    programs.run(time.delta_seconds(), false);
+}
+
+/// :SYSTEM: Passes CPU overruns on to the mission as `cpu_overrun` events, with
+/// the ship, and the instructions and microseconds it used.
+fn cpu_overrun_system(
+    mut overruns: EventReader<CpuOverrun>,
+    mut script_events: EventWriter<ScriptEvent>,
+) {
+    for overrun in overruns.iter() {
+        script_events.send(ScriptEvent {
+            name: "cpu_overrun".to_string(),
+            args: vec![
+                Value::from_entity(overrun.entity),
+                Value::Num(overrun.meter.instructions as f64),
+                Value::Num(overrun.meter.time.as_micros() as f64),
+            ],
+        });
+    }
+}
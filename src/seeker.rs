@@ -0,0 +1,170 @@
+use bevy::prelude::*;
+
+use super::balance::Balance;
+use super::physics::SimulationSet;
+use super::scripting::{ScriptEvent, ShipProgram, Value};
+use super::ships::Missile;
+
+pub struct SeekerPlugin;
+
+impl Plugin for SeekerPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(
+            seeker_system
+                .before(super::physics::kinimatics_system)
+                .in_set(SimulationSet),
+        );
+    }
+}
+
+/// Where a missile's [`Seeker`] is up to.
+#[derive(Reflect, FromReflect, Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SeekerState {
+    /// Nothing to look for. Mines and decoys stay here.
+    #[default]
+    Idle,
+    /// Flying towards where it was told the target is, and holding it in view
+    /// long enough to lock on.
+    Acquiring,
+    /// Homing in on the target.
+    Locked,
+    /// The target slipped out of view, or went away. The missile coasts.
+    Lost,
+}
+
+impl SeekerState {
+    /// Whether the missile is steering for its target.
+    pub fn guiding(self) -> bool {
+        matches!(self, SeekerState::Acquiring | SeekerState::Locked)
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            SeekerState::Idle => "idle",
+            SeekerState::Acquiring => "acquiring",
+            SeekerState::Locked => "locked",
+            SeekerState::Lost => "lost",
+        }
+    }
+}
+
+/// :COMPONENT: A missile's seeker head. It only sees what's within the
+/// [`SeekerBalance`](super::balance::SeekerBalance)'s range, and within its
+/// field of view either side of the nose.
+///
+/// Given a target, the seeker starts acquiring it, and locks on once it's held
+/// it in view for the lock time. Until then the missile steers for the target
+/// as the launcher handed it over. A locked seeker which loses sight of its
+/// target loses the lock for good: the missile's target is cleared, and it
+/// coasts. When a flare pulls the missile away, the seeker starts acquiring
+/// the flare instead, and loses it once it burns out.
+///
+/// Each change is raised on the target's program, as `lock_acquiring`,
+/// `lock_locked` or `lock_lost` with the missile, and for the mission as
+/// `seeker_acquiring`, `seeker_locked` or `seeker_lost`, with the missile and
+/// the target.
+#[derive(Reflect, Component, Default, Clone, Copy, Debug)]
+#[reflect(Component)]
+pub struct Seeker {
+    pub state: SeekerState,
+    /// What the seeker is looking for, or what it lost.
+    pub target: Option<Entity>,
+    /// Seconds the target has been held in view while acquiring.
+    pub progress: f32,
+}
+
+/// :SYSTEM: Steps each seeker's state machine along.
+pub fn seeker_system(
+    mut missiles: Query<(Entity, &mut Missile, &mut Seeker, &Transform)>,
+    targets: Query<&Transform, Without<Missile>>,
+    mut programs: Query<&mut ShipProgram>,
+    mut script_events: EventWriter<ScriptEvent>,
+    balance: Res<Balance>,
+    time: Res<Time>,
+) {
+    let stats = &balance.seeker;
+    let dt = time.delta_seconds();
+
+    for (entity, mut missile, mut seeker, transform) in missiles.iter_mut() {
+        let before = seeker.state;
+        let retargeted = missile.target.is_some() && missile.target != seeker.target;
+        // a new target, from the launcher or a flare
+        if retargeted {
+            // whatever it was chasing has shaken it off
+            if seeker.state.guiding() {
+                let lost = seeker.target;
+                announce(
+                    entity,
+                    SeekerState::Lost,
+                    lost,
+                    &mut programs,
+                    &mut script_events,
+                );
+            }
+            seeker.target = missile.target;
+            seeker.state = SeekerState::Acquiring;
+            seeker.progress = 0.0;
+        }
+
+        let in_view = missile.target.and_then(|t| targets.get(t).ok()).map(|t| {
+            let offset = t.translation - transform.translation;
+            let nose = transform.rotation.mul_vec3(Vec3::Y);
+            offset.length() <= stats.range && nose.angle_between(offset) <= stats.fov
+        });
+
+        seeker.state = match (seeker.state, in_view) {
+            (SeekerState::Acquiring | SeekerState::Locked, None) => SeekerState::Lost,
+            (SeekerState::Acquiring, Some(true)) => {
+                seeker.progress += dt;
+                match seeker.progress >= stats.lock_time {
+                    true => SeekerState::Locked,
+                    false => SeekerState::Acquiring,
+                }
+            }
+            (SeekerState::Acquiring, Some(false)) => {
+                seeker.progress = 0.0;
+                SeekerState::Acquiring
+            }
+            (SeekerState::Locked, Some(false)) => SeekerState::Lost,
+            (state, _) => state,
+        };
+        if seeker.state == SeekerState::Lost {
+            missile.target = None;
+        }
+
+        if seeker.state != before || retargeted {
+            announce(
+                entity,
+                seeker.state,
+                seeker.target,
+                &mut programs,
+                &mut script_events,
+            );
+        }
+    }
+}
+
+/// Raises `missile`'s seeker moving to `state` on `target`'s program and for
+/// the mission.
+fn announce(
+    missile: Entity,
+    state: SeekerState,
+    target: Option<Entity>,
+    programs: &mut Query<&mut ShipProgram>,
+    script_events: &mut EventWriter<ScriptEvent>,
+) {
+    let name = state.label();
+    if let Some(mut program) = target.and_then(|t| programs.get_mut(t).ok()) {
+        program
+            .vm
+            .raise(&format!("lock_{}", name), vec![Value::from_entity(missile)]);
+    }
+    script_events.send(ScriptEvent {
+        name: format!("seeker_{}", name),
+        args: [Some(missile), target]
+            .into_iter()
+            .flatten()
+            .map(Value::from_entity)
+            .collect(),
+    });
+}
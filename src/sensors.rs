@@ -0,0 +1,164 @@
+use bevy::prelude::*;
+
+use super::asteroid::Rng;
+use super::countermeasures::Flare;
+use super::jamming::Jamming;
+use super::physics::{Kinimatics, SimulationSet};
+use super::ships::{Faction, Missile, MissileKind, Ship};
+use super::stealth::Emissions;
+
+pub struct SensorsPlugin;
+
+impl Plugin for SensorsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(SensorNoise(Rng(0)))
+            .add_system(sensor_system.in_set(SimulationSet));
+    }
+}
+
+/// How much jamming shortens the range a sensor burns through to. At a
+/// jamming level of one, it burns through to a third of its range.
+const BURN_THROUGH_FALLOFF: f32 = 2.0;
+
+/// How far off a spoofed contact can appear, as a fraction of how far it is
+/// beyond the burn through range, at a jamming level of one.
+const SPOOF_ERROR: f32 = 0.2;
+
+/// The range a sensor reaching `range` still sees clearly through `jamming`.
+pub fn burn_through(range: f32, jamming: f32) -> f32 {
+    range / (1.0 + jamming * BURN_THROUGH_FALLOFF)
+}
+
+/// :COMPONENT: Lets an entity detect other kinimatic bodies within `range`.
+/// Detected bodies are written to the entity's [`Contacts`] every frame.
+#[derive(Reflect, Component, Clone, Copy)]
+#[reflect(Component)]
+pub struct Sensor {
+    pub range: f32,
+}
+
+impl Default for Sensor {
+    fn default() -> Self {
+        Self { range: 800.0 }
+    }
+}
+
+/// What kind of thing a sensor contact appears to be.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ContactKind {
+    Body,
+    Ship,
+    Missile,
+    /// A flare or chaff (see [`Flare`]).
+    Countermeasure,
+}
+
+/// A single body picked up by a [`Sensor`].
+#[derive(Clone, Copy, Debug)]
+pub struct Contact {
+    pub entity: Entity,
+    pub kind: ContactKind,
+    pub faction: Option<Faction>,
+    pub position: Vec3,
+    pub velocity: Vec3,
+    pub distance: f32,
+    /// For missiles, whatever the missile is chasing.
+    pub target: Option<Entity>,
+    /// For missiles, what kind they are, going by their signature. Decoys
+    /// pass for ships by their kind, but their signature gives them away to
+    /// anything which checks it.
+    pub signature: Option<MissileKind>,
+    /// Whether jamming has spoofed the contact, leaving its position and
+    /// distance only roughly right.
+    pub jammed: bool,
+}
+
+/// :COMPONENT: Everything an entity's [`Sensor`] detected on the last frame,
+/// sorted from nearest to furthest.
+#[derive(Component, Default, Clone)]
+pub struct Contacts(pub Vec<Contact>);
+
+impl Contacts {
+    /// The nearest contact which is hostile to `faction` and matches `kind`.
+    pub fn nearest_hostile(&self, faction: &Faction, kind: ContactKind) -> Option<&Contact> {
+        self.0
+            .iter()
+            .find(|c| c.kind == kind && c.faction.is_some_and(|f| f.is_hostile_to(faction)))
+    }
+}
+
+/// Resource which holds the dice rolled to spoof jammed contacts.
+#[derive(Resource)]
+pub struct SensorNoise(Rng);
+
+/// :BUNDLE: Provided for convenience.
+#[derive(Bundle, Default)]
+pub struct SensorBundle {
+    pub sensor: Sensor,
+    pub contacts: Contacts,
+}
+
+/// :SYSTEM: Refreshes the [`Contacts`] of every entity with a [`Sensor`].
+/// Under [`Jamming`], contacts beyond the [`burn_through`] range are spoofed.
+/// Quiet ships are only picked up closer in (see [`Emissions`]).
+#[allow(clippy::type_complexity)]
+pub fn sensor_system(
+    mut sensors: Query<(Entity, &Sensor, &Transform, &mut Contacts, Option<&Jamming>)>,
+    bodies: Query<(
+        Entity,
+        &Transform,
+        &Kinimatics,
+        Option<&Faction>,
+        Option<&Missile>,
+        Option<&Ship>,
+        Option<&Flare>,
+        Option<&Emissions>,
+    )>,
+    mut noise: ResMut<SensorNoise>,
+) {
+    for (entity, sensor, transform, mut contacts, jamming) in sensors.iter_mut() {
+        contacts.0.clear();
+        let jamming = jamming.map_or(0.0, |j| j.0);
+        let clear = burn_through(sensor.range, jamming);
+
+        for (other, t, kin, faction, missile, ship, flare, emissions) in bodies.iter() {
+            let distance = t.translation.distance(transform.translation);
+            let signature = emissions.map_or(1.0, |e| e.signature);
+            if other == entity || distance > sensor.range * signature {
+                continue;
+            }
+
+            let kind = match (missile, ship) {
+                (Some(m), _) if m.kind == MissileKind::Decoy => ContactKind::Ship,
+                (Some(_), _) => ContactKind::Missile,
+                (None, Some(_)) => ContactKind::Ship,
+                (None, None) if flare.is_some() => ContactKind::Countermeasure,
+                (None, None) => ContactKind::Body,
+            };
+
+            // loud ships are heard through the noise from further out too
+            let clear = clear * signature;
+            let jammed = distance > clear;
+            let mut position = t.translation;
+            if jammed {
+                let error = (distance - clear) * SPOOF_ERROR * jamming.min(1.0);
+                let angle = noise.0.next() * std::f32::consts::TAU;
+                position += Vec3::new(angle.cos(), angle.sin(), 0.0) * error * noise.0.next();
+            }
+
+            contacts.0.push(Contact {
+                entity: other,
+                kind,
+                faction: faction.copied(),
+                position,
+                velocity: kin.velocity,
+                distance: position.distance(transform.translation),
+                target: missile.and_then(|m| m.target),
+                signature: missile.map(|m| m.kind),
+                jammed,
+            });
+        }
+
+        contacts.0.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+    }
+}
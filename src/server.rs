@@ -0,0 +1,688 @@
+use std::io::ErrorKind;
+use std::net::{TcpListener, TcpStream};
+
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use bevy_egui::{egui, EguiContexts};
+use serde::{Deserialize, Serialize};
+
+use super::input_map::InputMap;
+use super::level::{self, AstroObject, LevelSprites};
+use super::net::{self, Connection, TickInput, FIRST_PLAYER_FACTION};
+use super::physics::{Attitude, Kinimatics, SimulationSet};
+use super::ships::{
+    self, Controlled, Engine, Faction, LaunchMissile, Missile, MissileLauncher, PlayerShip, Ship,
+    ShipSprites, Throttle,
+};
+use super::star_system::Dormant;
+
+pub struct ServerPlugin;
+
+impl Plugin for ServerPlugin {
+    fn build(&self, app: &mut App) {
+        let args: Vec<String> = std::env::args().collect();
+        let value = |flag: &str| args.iter().find_map(|a| a.strip_prefix(flag));
+        let session = match (value("--serve="), value("--connect=")) {
+            (Some(address), _) => Session::serve(address),
+            (_, Some(address)) => Session::connect(address),
+            _ => Session::default(),
+        };
+        app.insert_resource(session)
+            .configure_set(SimulationSet.run_if(not_a_client))
+            .add_system(server_request_system.before(SimulationSet))
+            .add_system(
+                server_input_system
+                    .before(super::physics::kinimatics_system)
+                    .before(ships::launch_missile_system)
+                    .in_set(SimulationSet),
+            )
+            .add_system(server_snapshot_system.after(SimulationSet))
+            .add_system(client_snapshot_system)
+            .add_system(client_interpolation_system.after(client_snapshot_system))
+            .add_system(session_panel_system);
+    }
+}
+
+/// Snapshots sent to clients per second.
+const SNAPSHOT_RATE: f64 = 20.0;
+
+/// Largest snapshot a client accepts, in bytes.
+const MAX_SNAPSHOT: usize = 4 * 1024 * 1024;
+
+/// How far behind on snapshots a slow client may fall before it's dropped, in
+/// bytes.
+const MAX_SNAPSHOT_BACKLOG: usize = 16 * 1024 * 1024;
+
+/// Longest a replica takes to catch up with a snapshot, in seconds, however
+/// long the gap before it was.
+const MAX_INTERPOLATION: f32 = 0.5;
+
+/// Distance between the first player ship and the ships spawned for clients
+/// once there are no spare ones.
+const CLIENT_SHIP_SPACING: f32 = 40.0;
+
+/// A message from a client.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ClientMessage {
+    /// Asks for a ship to fly.
+    Join,
+    /// Flies one of the client's own ships, until the next command.
+    Command { ship: u64, input: TickInput },
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+enum ReplicaKind {
+    Ship,
+    Missile,
+    Body,
+}
+
+/// The state of one entity, as of a snapshot.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct EntityState {
+    id: u64,
+    kind: ReplicaKind,
+    name: Option<String>,
+    position: [f32; 2],
+    /// Radians counterclockwise from up.
+    rotation: f32,
+    velocity: [f32; 2],
+    mass: f32,
+    radius: Option<f32>,
+    fuel: Option<f32>,
+    faction: Option<u32>,
+    /// The player whose ship it is, if it's a client's.
+    owner: Option<usize>,
+}
+
+/// A message from the server.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ServerMessage {
+    /// Which player the client is, and the ship it flies.
+    Welcome {
+        player: usize,
+        ship: u64,
+    },
+    Error {
+        message: String,
+    },
+    /// Everything that moves.
+    Snapshot {
+        met: f64,
+        entities: Vec<EntityState>,
+    },
+}
+
+/// A connected client, on the server.
+struct Peer {
+    player: usize,
+    connection: Connection,
+    ship: Option<Entity>,
+    input: TickInput,
+    /// Set when the client fires, until the next tick runs; a frame's input
+    /// may be replaced before then.
+    fire: bool,
+}
+
+#[derive(Default)]
+enum Role {
+    /// Playing alone.
+    #[default]
+    Off,
+    Server {
+        listener: TcpListener,
+        peers: Vec<Peer>,
+        next_player: usize,
+        last_snapshot: f64,
+    },
+    Client {
+        server: Connection,
+        player: Option<usize>,
+        ship: Option<u64>,
+        /// The local stand-in for each of the server's entities.
+        replicas: HashMap<u64, Entity>,
+        last_snapshot: f32,
+    },
+}
+
+/// Resource which runs a client-server game. One player serves with
+/// `--serve=ADDRESS`, and the others connect with `--connect=ADDRESS`.
+///
+/// Unlike lockstep, only the server runs the simulation and the ship programs.
+/// It sends every client a snapshot of everything that moves
+/// [`SNAPSHOT_RATE`] times a second, which the client moves its replicas
+/// smoothly towards. Clients send the server their player's input, and the
+/// server only lets them fly the ships they [`Owner`].
+#[derive(Resource, Default)]
+pub struct Session {
+    role: Role,
+    status: String,
+}
+
+impl Session {
+    fn serve(address: &str) -> Self {
+        let listener = TcpListener::bind(address).and_then(|l| l.set_nonblocking(true).map(|_| l));
+        match listener {
+            Ok(listener) => {
+                info!("server: serving on {}", address);
+                Self {
+                    role: Role::Server {
+                        listener,
+                        peers: Vec::new(),
+                        next_player: 1,
+                        last_snapshot: f64::NEG_INFINITY,
+                    },
+                    status: format!("serving on {}", address),
+                }
+            }
+            Err(e) => {
+                error!("server: couldn't serve on {}: {}", address, e);
+                Self::default()
+            }
+        }
+    }
+
+    fn connect(address: &str) -> Self {
+        let connection = TcpStream::connect(address)
+            .and_then(Connection::new)
+            .map(|c| c.with_limits(MAX_SNAPSHOT, MAX_SNAPSHOT));
+        match connection {
+            Ok(mut server) => {
+                info!("server: connecting to {}", address);
+                server.send(&ClientMessage::Join);
+                Self {
+                    role: Role::Client {
+                        server,
+                        player: None,
+                        ship: None,
+                        replicas: HashMap::default(),
+                        last_snapshot: 0.0,
+                    },
+                    status: format!("connecting to {}", address),
+                }
+            }
+            Err(e) => {
+                error!("server: couldn't connect to {}: {}", address, e);
+                Self::default()
+            }
+        }
+    }
+
+    pub fn is_client(&self) -> bool {
+        matches!(self.role, Role::Client { .. })
+    }
+}
+
+/// :COMPONENT: Marks a ship which belongs to one of a server's clients. Only
+/// that client may fly it.
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Owner(pub usize);
+
+/// :COMPONENT: Marks the local stand-in for one of the server's entities, on a
+/// client. Moves from where it was towards where the latest snapshot put it.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct Replica {
+    from: (Vec3, Quat),
+    to: (Vec3, Quat),
+    /// Seconds since the latest snapshot.
+    since: f32,
+    /// Seconds the move from `from` to `to` takes.
+    interval: f32,
+}
+
+fn not_a_client(session: Res<Session>) -> bool {
+    !session.is_client()
+}
+
+/// Picks a ship for a new client: a player ship nobody's flying, or a new one
+/// alongside the others.
+#[allow(clippy::type_complexity)]
+fn ship_for_client(
+    commands: &mut Commands,
+    sprites: &ShipSprites,
+    ships: &Query<
+        (
+            Entity,
+            &Transform,
+            &Kinimatics,
+            Option<&Owner>,
+            Option<&Controlled>,
+        ),
+        With<PlayerShip>,
+    >,
+    taken: &[Entity],
+) -> Entity {
+    let spare = ships
+        .iter()
+        .filter(|(e, ..)| !taken.contains(e))
+        .find(|(.., own, controlled)| own.is_none() && controlled.is_none());
+    if let Some((ship, ..)) = spare {
+        return ship;
+    }
+    let (origin, velocity) = ships
+        .iter()
+        .next()
+        .map_or((Vec3::ZERO, Vec3::ZERO), |(_, t, k, ..)| {
+            (t.translation, k.velocity)
+        });
+    let offset = Vec3::X * CLIENT_SHIP_SPACING * (ships.iter().count() + taken.len()) as f32;
+    let ship = ships::spawn_ship(commands, sprites, origin + offset, velocity);
+    commands
+        .entity(ship)
+        .insert((PlayerShip, MissileLauncher::default()));
+    ship
+}
+
+/// :SYSTEM: Accepts new clients on the server, hands each a ship, and takes in
+/// their input.
+#[allow(clippy::type_complexity)]
+fn server_request_system(
+    mut commands: Commands,
+    mut session: ResMut<Session>,
+    ships: Query<
+        (
+            Entity,
+            &Transform,
+            &Kinimatics,
+            Option<&Owner>,
+            Option<&Controlled>,
+        ),
+        With<PlayerShip>,
+    >,
+    mut engines: Query<&mut Engine>,
+    sprites: Res<ShipSprites>,
+) {
+    let Role::Server {
+        listener,
+        peers,
+        next_player,
+        ..
+    } = &mut session.role
+    else {
+        return;
+    };
+
+    loop {
+        match listener.accept() {
+            Ok((stream, address)) => {
+                let Ok(connection) = Connection::new(stream) else {
+                    continue;
+                };
+                let connection = connection.with_limits(net::MAX_MESSAGE, MAX_SNAPSHOT_BACKLOG);
+                info!("server: player {} connected from {}", next_player, address);
+                peers.push(Peer {
+                    player: *next_player,
+                    connection,
+                    ship: None,
+                    input: TickInput::default(),
+                    fire: false,
+                });
+                *next_player += 1;
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+            Err(e) => {
+                warn!("server: couldn't accept a player: {}", e);
+                break;
+            }
+        }
+    }
+
+    let mut taken: Vec<Entity> = peers.iter().filter_map(|p| p.ship).collect();
+    for peer in peers.iter_mut() {
+        for message in peer.connection.receive::<ClientMessage>() {
+            match message {
+                Ok(ClientMessage::Join) if peer.ship.is_none() => {
+                    let ship = ship_for_client(&mut commands, &sprites, &ships, &taken);
+                    commands.entity(ship).insert((
+                        Owner(peer.player),
+                        Faction(FIRST_PLAYER_FACTION + peer.player as u32),
+                    ));
+                    taken.push(ship);
+                    peer.ship = Some(ship);
+                    peer.connection.send(&ServerMessage::Welcome {
+                        player: peer.player,
+                        ship: ship.to_bits(),
+                    });
+                }
+                Ok(ClientMessage::Join) => {}
+                Ok(ClientMessage::Command { ship, input }) => {
+                    if peer.ship.map(Entity::to_bits) != Some(ship) {
+                        peer.connection.send(&ServerMessage::Error {
+                            message: format!("ship {} isn't yours", ship),
+                        });
+                        continue;
+                    }
+                    peer.fire |= input.fire;
+                    peer.input = input;
+                }
+                Err(e) => peer.connection.send(&ServerMessage::Error { message: e }),
+            }
+        }
+    }
+
+    // whoever leaves has their engine cut, and their ship freed up
+    for peer in peers.iter().filter(|p| p.connection.closed) {
+        info!("server: player {} disconnected", peer.player);
+        if let Some(ship) = peer.ship {
+            if let Ok(mut engine) = engines.get_mut(ship) {
+                engine.throttle = Throttle::Fixed(false);
+            }
+            if let Some(mut ship) = commands.get_entity(ship) {
+                ship.remove::<Owner>();
+            }
+        }
+    }
+    peers.retain(|p| !p.connection.closed);
+}
+
+/// :SYSTEM: Flies each client's ship with its latest input, on the server.
+#[allow(clippy::type_complexity)]
+fn server_input_system(
+    mut session: ResMut<Session>,
+    mut ships: ParamSet<(
+        Query<(Entity, &Transform, Option<&Faction>), (With<Ship>, Without<Missile>)>,
+        Query<(&Transform, &mut Attitude, &mut Engine, &Faction, &Owner)>,
+    )>,
+    mut launches: EventWriter<LaunchMissile>,
+) {
+    let Role::Server { peers, .. } = &mut session.role else {
+        return;
+    };
+    let targets: Vec<_> = ships
+        .p0()
+        .iter()
+        .map(|(e, t, f)| (e, t.translation, f.copied().unwrap_or_default()))
+        .collect();
+
+    let mut owned = ships.p1();
+    for peer in peers.iter_mut() {
+        let Some(ship) = peer.ship else {
+            continue;
+        };
+        let Ok((transform, mut attitude, mut engine, faction, own)) = owned.get_mut(ship) else {
+            continue;
+        };
+        if own.0 != peer.player {
+            continue;
+        }
+        peer.input.steer(&mut attitude, &mut engine);
+        if std::mem::take(&mut peer.fire) {
+            if let Some(target) =
+                net::nearest_hostile(&targets, ship, transform.translation, *faction)
+            {
+                launches.send(LaunchMissile {
+                    shooter: ship,
+                    target,
+                });
+            }
+        }
+    }
+}
+
+/// :SYSTEM: Sends every client a snapshot of everything that moves, on the
+/// server, and sends on whatever's waiting to go out.
+#[allow(clippy::type_complexity)]
+fn server_snapshot_system(
+    mut session: ResMut<Session>,
+    clock: Res<super::clock::SimulationClock>,
+    time: Res<Time>,
+    bodies: Query<
+        (
+            Entity,
+            Option<&Name>,
+            &Transform,
+            &Kinimatics,
+            Option<&Engine>,
+            Option<&Faction>,
+            Option<&Owner>,
+            (Option<&Ship>, Option<&Missile>, Option<&AstroObject>),
+        ),
+        Without<Dormant>,
+    >,
+) {
+    let Role::Server {
+        peers,
+        last_snapshot,
+        ..
+    } = &mut session.role
+    else {
+        return;
+    };
+
+    let now = time.raw_elapsed_seconds_f64();
+    if now - *last_snapshot >= 1.0 / SNAPSHOT_RATE && !peers.is_empty() {
+        *last_snapshot = now;
+        let entities: Vec<_> = bodies
+            .iter()
+            .filter_map(
+                |(entity, name, transform, kin, engine, faction, own, kind)| {
+                    let (kind, radius) = match kind {
+                        (Some(_), ..) => (ReplicaKind::Ship, None),
+                        (_, Some(_), _) => (ReplicaKind::Missile, None),
+                        (.., Some(body)) => (ReplicaKind::Body, Some(body.radius)),
+                        _ => return None,
+                    };
+                    let (_, _, rotation) = transform.rotation.to_euler(EulerRot::XYZ);
+                    Some(EntityState {
+                        id: entity.to_bits(),
+                        kind,
+                        name: name.map(|n| n.to_string()),
+                        position: [transform.translation.x, transform.translation.y],
+                        rotation,
+                        velocity: [kin.velocity.x, kin.velocity.y],
+                        mass: kin.mass,
+                        radius,
+                        fuel: engine.map(|e| e.fuel),
+                        faction: faction.map(|f| f.0),
+                        owner: own.map(|o| o.0),
+                    })
+                },
+            )
+            .collect();
+        let snapshot = ServerMessage::Snapshot {
+            met: clock.elapsed,
+            entities,
+        };
+        for peer in peers.iter_mut() {
+            peer.connection.send(&snapshot);
+        }
+    }
+
+    for peer in peers.iter_mut() {
+        peer.connection.flush();
+    }
+}
+
+/// Spawns the local stand-in for one of the server's entities.
+fn spawn_replica(
+    commands: &mut Commands,
+    ship_sprites: &ShipSprites,
+    level_sprites: &LevelSprites,
+    state: &EntityState,
+) -> Entity {
+    let position = Vec3::new(state.position[0], state.position[1], 0.0);
+    let velocity = Vec3::new(state.velocity[0], state.velocity[1], 0.0);
+    let entity = match state.kind {
+        ReplicaKind::Ship => ships::spawn_ship(commands, ship_sprites, position, velocity),
+        ReplicaKind::Body => level::spawn_planet(
+            commands,
+            level_sprites,
+            state.radius.unwrap_or(1.0),
+            state.mass,
+            position,
+            velocity,
+        ),
+        ReplicaKind::Missile => commands
+            .spawn(
+                super::physics::KinimaticsBundle::build()
+                    .insert_translation(position)
+                    .insert_velocity(velocity),
+            )
+            .with_children(|p| {
+                p.spawn((ship_sprites.missile.clone(), super::user_interface::MapIcon));
+            })
+            .id(),
+    };
+    let rotation = Quat::from_rotation_z(state.rotation);
+    commands.entity(entity).insert(Replica {
+        from: (position, rotation),
+        to: (position, rotation),
+        since: 0.0,
+        interval: 0.0,
+    });
+    if let Some(name) = &state.name {
+        commands.entity(entity).insert(Name::new(name.clone()));
+    }
+    entity
+}
+
+/// :SYSTEM: On a client, keeps the replicas in line with the server's
+/// snapshots, and sends the server the player's input.
+#[allow(clippy::type_complexity, clippy::too_many_arguments)]
+fn client_snapshot_system(
+    mut commands: Commands,
+    mut session: ResMut<Session>,
+    mut replicas: Query<(
+        &mut Replica,
+        &Transform,
+        &mut Kinimatics,
+        Option<&mut Engine>,
+    )>,
+    local: Query<Entity, (With<Kinimatics>, Without<Replica>, Without<Parent>)>,
+    ship_sprites: Res<ShipSprites>,
+    level_sprites: Res<LevelSprites>,
+    input: Res<Input<KeyCode>>,
+    map: Res<InputMap>,
+    time: Res<Time>,
+) {
+    let session = &mut *session;
+    let Role::Client {
+        server,
+        player,
+        ship,
+        replicas: ids,
+        last_snapshot,
+    } = &mut session.role
+    else {
+        return;
+    };
+
+    // the server's world replaces whatever the mission spawned locally
+    for entity in local.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    let now = time.raw_elapsed_seconds();
+    let mut latest = None;
+    for message in server.receive::<ServerMessage>() {
+        match message {
+            Ok(ServerMessage::Welcome { player: p, ship: s }) => {
+                info!("server: joined as player {}", p);
+                *player = Some(p);
+                *ship = Some(s);
+                session.status = format!("player {}", p);
+            }
+            Ok(ServerMessage::Error { message }) => warn!("server: {}", message),
+            Ok(ServerMessage::Snapshot { entities, .. }) => latest = Some(entities),
+            Err(e) => warn!("server: the server sent nonsense: {}", e),
+        }
+    }
+
+    // only the newest snapshot matters; any before it are already out of date
+    if let Some(entities) = latest {
+        let interval = (now - *last_snapshot).min(MAX_INTERPOLATION);
+        *last_snapshot = now;
+
+        let mut seen = Vec::with_capacity(entities.len());
+        for state in entities.iter() {
+            seen.push(state.id);
+            let position = Vec3::new(state.position[0], state.position[1], 0.0);
+            let rotation = Quat::from_rotation_z(state.rotation);
+            let existing = ids.get(&state.id).and_then(|e| replicas.get_mut(*e).ok());
+            match existing {
+                Some((mut replica, transform, mut kin, engine)) => {
+                    replica.from = (transform.translation, transform.rotation);
+                    replica.to = (position, rotation);
+                    replica.since = 0.0;
+                    replica.interval = interval;
+                    kin.velocity = Vec3::new(state.velocity[0], state.velocity[1], 0.0);
+                    kin.mass = state.mass;
+                    if let (Some(mut engine), Some(fuel)) = (engine, state.fuel) {
+                        engine.fuel = fuel;
+                    }
+                }
+                None if !ids.contains_key(&state.id) => {
+                    let entity = spawn_replica(&mut commands, &ship_sprites, &level_sprites, state);
+                    if *ship == Some(state.id) {
+                        commands.entity(entity).insert((PlayerShip, Controlled));
+                    }
+                    ids.insert(state.id, entity);
+                }
+                // spawned last frame, and not in the world yet
+                None => {}
+            }
+            if let (Some(faction), Some(entity)) = (state.faction, ids.get(&state.id)) {
+                commands.entity(*entity).insert(Faction(faction));
+            }
+        }
+        ids.retain(|id, entity| {
+            let keep = seen.contains(id);
+            if !keep {
+                commands.entity(*entity).despawn_recursive();
+            }
+            keep
+        });
+    }
+
+    if let Some(ship) = *ship {
+        server.send(&ClientMessage::Command {
+            ship,
+            input: TickInput::sample(&map, &input),
+        });
+    }
+    server.flush();
+
+    if server.closed {
+        error!("server: lost the server");
+        session.status = "lost the server".to_string();
+        session.role = Role::Off;
+    }
+}
+
+/// :SYSTEM: Moves each replica smoothly from where it was towards where the
+/// latest snapshot put it.
+fn client_interpolation_system(
+    mut replicas: Query<(&mut Replica, &mut Transform)>,
+    time: Res<Time>,
+) {
+    for (mut replica, mut transform) in replicas.iter_mut() {
+        replica.since += time.raw_delta_seconds();
+        let t = if replica.interval > 0.0 {
+            (replica.since / replica.interval).min(1.0)
+        } else {
+            1.0
+        };
+        transform.translation = replica.from.0.lerp(replica.to.0, t);
+        transform.rotation = replica.from.1.slerp(replica.to.1, t);
+    }
+}
+
+/// :SYSTEM: Shows whether this is a server or a client, and who's connected.
+fn session_panel_system(mut contexts: EguiContexts, session: Res<Session>) {
+    let text = match &session.role {
+        Role::Off if session.status.is_empty() => return,
+        Role::Off => session.status.clone(),
+        Role::Server { peers, .. } => {
+            format!("SERVER {} ({} connected)", session.status, peers.len())
+        }
+        Role::Client { .. } => format!("CLIENT {}", session.status),
+    };
+    egui::Area::new("session")
+        .anchor(egui::Align2::CENTER_TOP, [0.0, 24.0])
+        .show(contexts.ctx_mut(), |ui| {
+            ui.label(text);
+        });
+}
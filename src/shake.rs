@@ -0,0 +1,185 @@
+//! Feedback for hits on the controlled ship: the camera shakes and the edges of
+//! the screen flash red.
+//!
+//! The shake is an offset laid over the camera's transform just before it's
+//! propagated, and taken back off at the start of the next frame, so every
+//! system which moves the camera works from where it really is.
+
+use bevy::{prelude::*, transform::TransformSystem};
+use bevy_egui::{egui, EguiContexts};
+
+use super::physics::SimulationSet;
+use super::ships::{Controlled, Detonation, Hull};
+
+pub struct ShakePlugin;
+
+impl Plugin for ShakePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CameraShake>()
+            .add_system(unshake_system.in_base_set(CoreSet::First))
+            .add_system(hit_system.after(SimulationSet))
+            .add_system(vignette_system.after(hit_system))
+            .add_system(
+                shake_system
+                    .in_base_set(CoreSet::PostUpdate)
+                    .before(TransformSystem::TransformPropagate),
+            );
+    }
+}
+
+/// Furthest the camera is thrown by the hardest shake, in pixels.
+const MAX_SHAKE: f32 = 16.0;
+
+/// Trauma (see [`CameraShake`]) lost each second.
+const TRAUMA_DECAY: f32 = 1.5;
+
+/// Flash lost each second.
+const FLASH_DECAY: f32 = 2.5;
+
+/// How many blast radii away a detonation is still felt.
+const FELT_RADII: f32 = 3.0;
+
+/// Fraction of the screen's shorter side the vignette reaches in from its
+/// edges.
+const VIGNETTE_DEPTH: f32 = 0.25;
+
+/// Resource which holds how hard the camera is shaking.
+///
+/// Hits add trauma, from 0 to 1, which wears off over time. The camera is
+/// thrown by the square of it, so small hits barely register and big ones
+/// rattle. The flash is how red the edges of the screen are, from 0 to 1.
+#[derive(Resource)]
+pub struct CameraShake {
+    pub enabled: bool,
+    pub trauma: f32,
+    pub flash: f32,
+    /// What was added to the camera's translation this frame, to be taken back
+    /// off at the start of the next.
+    offset: Vec3,
+}
+
+impl Default for CameraShake {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            trauma: 0.0,
+            flash: 0.0,
+            offset: Vec3::ZERO,
+        }
+    }
+}
+
+impl CameraShake {
+    /// Adds a hit of `strength`, from 0 to 1.
+    pub fn hit(&mut self, strength: f32) {
+        let strength = strength.clamp(0.0, 1.0);
+        self.trauma = (self.trauma + strength).min(1.0);
+        self.flash = self.flash.max(strength.sqrt());
+    }
+}
+
+/// :SYSTEM: Shakes the camera when the controlled ship's hull is damaged, or a
+/// missile goes off close to it, harder the more damage it does.
+fn hit_system(
+    mut shake: ResMut<CameraShake>,
+    mut detonations: EventReader<Detonation>,
+    player: Query<(Entity, &Hull, &Transform), With<Controlled>>,
+    mut last: Local<Option<(Entity, f32)>>,
+) {
+    let Ok((ship, hull, transform)) = player.get_single() else {
+        detonations.clear();
+        *last = None;
+        return;
+    };
+    let max = hull.max_integrity.max(1.0);
+
+    // a different ship's hull isn't damage
+    if let Some((_, integrity)) = last.filter(|(e, _)| *e == ship) {
+        let damage = integrity - hull.integrity;
+        if damage > 0.0 {
+            shake.hit(damage / max);
+        }
+    }
+    *last = Some((ship, hull.integrity));
+
+    for blast in detonations.iter() {
+        let felt = blast.blast_radius * FELT_RADII;
+        let distance = blast.position.distance(transform.translation);
+        if felt <= 0.0 || distance >= felt {
+            continue;
+        }
+        // rattled by what it would have done at the center, fading with distance
+        shake.hit(blast.damage / max * (1.0 - distance / felt) / 2.0);
+    }
+}
+
+/// :SYSTEM: Takes last frame's shake back off the camera.
+fn unshake_system(
+    mut shake: ResMut<CameraShake>,
+    mut cameras: Query<&mut Transform, With<Camera2d>>,
+) {
+    let offset = std::mem::take(&mut shake.offset);
+    if let Ok(mut transform) = cameras.get_single_mut() {
+        transform.translation -= offset;
+    }
+}
+
+/// :SYSTEM: Throws the camera off where it's looking, for this frame only, and
+/// wears the shake off. It runs on the wall clock, so it settles even while
+/// the game is paused.
+fn shake_system(
+    mut shake: ResMut<CameraShake>,
+    mut cameras: Query<(&mut Transform, &OrthographicProjection), With<Camera2d>>,
+    time: Res<Time>,
+) {
+    let dt = time.raw_delta_seconds();
+    shake.trauma = (shake.trauma - TRAUMA_DECAY * dt).max(0.0);
+    shake.flash = (shake.flash - FLASH_DECAY * dt).max(0.0);
+    if !shake.enabled || shake.trauma <= 0.0 {
+        return;
+    }
+    let Ok((mut transform, ortho)) = cameras.get_single_mut() else {
+        return;
+    };
+    // a few sines out of step with each other wander without repeating
+    let t = time.raw_elapsed_seconds();
+    let wander = Vec2::new(
+        (t * 47.0).sin() + 0.5 * (t * 83.0).sin(),
+        (t * 53.0).cos() + 0.5 * (t * 71.0).cos(),
+    ) / 1.5;
+    let offset = (wander * shake.trauma * shake.trauma * MAX_SHAKE * ortho.scale).extend(0.0);
+    transform.translation += offset;
+    shake.offset = offset;
+}
+
+/// :SYSTEM: Flashes the edges of the screen red, fading towards the middle.
+fn vignette_system(mut contexts: EguiContexts, shake: Res<CameraShake>) {
+    if !shake.enabled || shake.flash <= 0.0 {
+        return;
+    }
+    let ctx = contexts.ctx_mut();
+    let outer = ctx.screen_rect();
+    let inner = outer.shrink(outer.width().min(outer.height()) * VIGNETTE_DEPTH);
+    let red = egui::Color32::from_rgba_unmultiplied(200, 0, 0, (shake.flash * 160.0) as u8);
+
+    // a ring of quads, solid at the edge of the screen and clear inside
+    let mut mesh = egui::Mesh::default();
+    let corners = |r: egui::Rect| {
+        [
+            r.left_top(),
+            r.right_top(),
+            r.right_bottom(),
+            r.left_bottom(),
+        ]
+    };
+    for (o, i) in corners(outer).into_iter().zip(corners(inner)) {
+        mesh.colored_vertex(o, red);
+        mesh.colored_vertex(i, egui::Color32::TRANSPARENT);
+    }
+    for side in 0..4 {
+        let (a, b) = (side * 2, (side * 2 + 2) % 8);
+        mesh.add_triangle(a, b, a + 1);
+        mesh.add_triangle(a + 1, b, b + 1);
+    }
+    ctx.layer_painter(egui::LayerId::background()).add(mesh);
+}
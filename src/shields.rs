@@ -0,0 +1,242 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use std::f32::consts::{FRAC_PI_2, FRAC_PI_4, PI};
+
+use super::modules::ShieldGenerator;
+use super::physics::SimulationSet;
+use super::power::{PowerConsumer, PowerGrid};
+
+pub struct ShieldsPlugin;
+
+impl Plugin for ShieldsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(
+            shield_system
+                .before(super::modules::loadout_system)
+                .in_set(SimulationSet),
+        )
+        .add_system(shield_flare_system);
+    }
+}
+
+/// A boosted quadrant holds this much more than the shield's capacity.
+const BOOST_CAPACITY: f32 = 1.5;
+
+/// Seconds a quadrant flares for after it's hit.
+const FLARE_TIME: f32 = 0.4;
+
+/// Radius the shield is drawn at, in world units.
+const BUBBLE_RADIUS: f32 = 12.0;
+
+/// One side of a [`Shield`], relative to the ship's nose.
+#[derive(Reflect, FromReflect, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Quadrant {
+    Front,
+    Right,
+    Back,
+    Left,
+}
+
+impl Quadrant {
+    pub const ALL: [Quadrant; 4] = [
+        Quadrant::Front,
+        Quadrant::Right,
+        Quadrant::Back,
+        Quadrant::Left,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Quadrant::Front => "front",
+            Quadrant::Right => "right",
+            Quadrant::Back => "back",
+            Quadrant::Left => "left",
+        }
+    }
+
+    pub fn parse(name: &str) -> Result<Self, String> {
+        Self::ALL
+            .into_iter()
+            .find(|q| q.label() == name)
+            .ok_or_else(|| format!("unknown quadrant `{}`", name))
+    }
+
+    /// The quadrant of a ship turned to `rotation` which faces `towards`.
+    pub fn facing(rotation: Quat, towards: Vec3) -> Self {
+        let local = rotation.inverse().mul_vec3(towards);
+        // clockwise from the nose, as the ship's +Y is its nose and +X its right
+        let angle = local.x.atan2(local.y);
+        match angle.abs() {
+            a if a <= FRAC_PI_4 => Quadrant::Front,
+            a if a >= PI - FRAC_PI_4 => Quadrant::Back,
+            _ if angle > 0.0 => Quadrant::Right,
+            _ => Quadrant::Left,
+        }
+    }
+
+    /// Angle of the middle of the quadrant, anticlockwise from the nose.
+    fn angle(self) -> f32 {
+        -(self as usize as f32) * FRAC_PI_2
+    }
+}
+
+/// :COMPONENT: A bubble around the ship which soaks up damage before it
+/// reaches the hull. It's split into four quadrants, each of which only
+/// covers hits from its own side, and holds its own strength.
+///
+/// The ship's [`ShieldGenerator`]s recharge the quadrants, drawing
+/// `power_draw` between them while they do. A brownout slows the recharge,
+/// and switching the generators off drops the shield altogether. Boosting a
+/// quadrant, towards the threat say, puts the whole recharge into it, and
+/// lets it hold more than the others.
+#[derive(Reflect, Component, Clone, Copy, Debug)]
+#[reflect(Component)]
+pub struct Shield {
+    /// Damage each quadrant can still soak up, in [`Quadrant`] order.
+    pub strength: [f32; 4],
+    /// Most a quadrant holds, when it isn't boosted.
+    pub capacity: f32,
+    /// Strength regained per second, spread across the quadrants.
+    pub recharge_rate: f32,
+    /// Power drawn while recharging, in watts.
+    pub power_draw: f32,
+    pub boost: Option<Quadrant>,
+    /// Seconds each quadrant has left flaring from its last hit.
+    pub flare: [f32; 4],
+}
+
+impl Default for Shield {
+    fn default() -> Self {
+        Self {
+            strength: [40.0; 4],
+            capacity: 40.0,
+            recharge_rate: 4.0,
+            power_draw: 30.0,
+            boost: None,
+            flare: [0.0; 4],
+        }
+    }
+}
+
+impl Shield {
+    /// Most `quadrant` can hold, given the boost.
+    pub fn capacity_of(&self, quadrant: Quadrant) -> f32 {
+        match self.boost == Some(quadrant) {
+            true => self.capacity * BOOST_CAPACITY,
+            false => self.capacity,
+        }
+    }
+
+    /// Soaks up `damage` coming at a ship turned to `rotation` from the
+    /// direction of `towards`, and returns whatever gets through to the hull.
+    pub fn absorb(&mut self, rotation: Quat, towards: Vec3, damage: f32) -> f32 {
+        let index = Quadrant::facing(rotation, towards) as usize;
+        let absorbed = damage.min(self.strength[index]);
+        if absorbed > 0.0 {
+            self.strength[index] -= absorbed;
+            self.flare[index] = FLARE_TIME;
+        }
+        damage - absorbed
+    }
+}
+
+/// :SYSTEM: Recharges each shield from its ship's generators, and charges the
+/// ship's power grid for it.
+#[allow(clippy::type_complexity)]
+fn shield_system(
+    mut ships: Query<(&mut Shield, &Children, Option<&PowerGrid>)>,
+    mut generators: Query<&mut PowerConsumer, With<ShieldGenerator>>,
+    time: Res<Time>,
+) {
+    let dt = time.delta_seconds();
+
+    for (mut shield, children, grid) in ships.iter_mut() {
+        let shield = &mut *shield;
+        for flare in shield.flare.iter_mut() {
+            *flare = (*flare - dt).max(0.0);
+        }
+
+        let working = generators.iter_many(children).filter(|c| c.enabled).count();
+        let charging = working > 0
+            && Quadrant::ALL
+                .into_iter()
+                .any(|q| shield.strength[q as usize] < shield.capacity_of(q));
+
+        let draw = match charging {
+            true => shield.power_draw / working as f32,
+            false => 0.0,
+        };
+        let mut generator_iter = generators.iter_many_mut(children);
+        while let Some(mut consumer) = generator_iter.fetch_next() {
+            if consumer.draw != draw {
+                consumer.draw = draw;
+            }
+        }
+
+        if working == 0 {
+            shield.strength = [0.0; 4];
+            continue;
+        }
+
+        let recharge = shield.recharge_rate * grid.map_or(1.0, |g| g.supply) * dt;
+        for q in Quadrant::ALL {
+            let share = match shield.boost {
+                Some(boosted) if boosted == q => recharge,
+                Some(_) => 0.0,
+                None => recharge / 4.0,
+            };
+            let capacity = shield.capacity_of(q);
+            let strength = &mut shield.strength[q as usize];
+            *strength = (*strength + share).min(capacity);
+        }
+    }
+}
+
+/// :SYSTEM: Draws an arc over each quadrant of a shield which was just hit,
+/// fading as the flare dies down.
+fn shield_flare_system(
+    mut contexts: EguiContexts,
+    shields: Query<(&Shield, &Transform)>,
+    cameras: Query<(&Camera, &GlobalTransform), With<Camera2d>>,
+) {
+    let Ok((camera, camera_transform)) = cameras.get_single() else {
+        return;
+    };
+    let Some(viewport) = camera.logical_viewport_size() else {
+        return;
+    };
+    // the viewport has +Y up, egui has it down
+    let to_screen = |p: Vec3| {
+        camera
+            .world_to_viewport(camera_transform, p)
+            .map(|at| egui::pos2(at.x, viewport.y - at.y))
+    };
+
+    let painter = contexts
+        .ctx_mut()
+        .layer_painter(egui::LayerId::background());
+    for (shield, transform) in shields.iter() {
+        for q in Quadrant::ALL {
+            let flare = shield.flare[q as usize] / FLARE_TIME;
+            if flare <= 0.0 {
+                continue;
+            }
+            // a brighter flare the more the quadrant still holds
+            let held = shield.strength[q as usize] / shield.capacity_of(q);
+            let alpha = (flare * (0.4 + 0.6 * held) * 255.0) as u8;
+            let color = egui::Color32::from_rgba_unmultiplied(120, 200, 255, alpha);
+
+            let points: Option<Vec<_>> = (0..=8)
+                .map(|i| {
+                    let angle = q.angle() + (i as f32 / 8.0 - 0.5) * FRAC_PI_2;
+                    let direction =
+                        (transform.rotation * Quat::from_rotation_z(angle)).mul_vec3(Vec3::Y);
+                    to_screen(transform.translation + direction * BUBBLE_RADIUS)
+                })
+                .collect();
+            if let Some(points) = points {
+                painter.add(egui::Shape::line(points, egui::Stroke::new(2.5, color)));
+            }
+        }
+    }
+}
@@ -1,12 +1,58 @@
-use super::physics::KinimaticsBundle;
+use super::balance::Balance;
+use super::beams::Laser;
+use super::clock::SimulationClock;
+use super::comms::Transceiver;
+use super::countermeasures::Countermeasure;
+use super::damage_control::DamageControl;
+use super::docking::DockingPort;
+use super::heat::Thermal;
+use super::input_map::{self, InputMap};
+use super::jamming::{Jammer, Jamming};
+use super::modules::{self, Frame};
+use super::net::NetPlayer;
+use super::physics::{ApplyImpulse, Attitude, Kinimatics, KinimaticsBundle, SimulationSet};
+use super::power::PowerGrid;
+use super::propagation;
+use super::route::Route;
+use super::sandbox::Sandbox;
+use super::scheduler::{Action, Alarm, Scheduler};
+use super::seeker::Seeker;
+use super::shields::Shield;
+use super::stealth::Emissions;
+use super::targeting::Target;
+use super::trails::Trail;
+use super::user_interface::{MapIcon, ProjectTrajectory};
 use bevy::prelude::*;
+use std::f32::consts::PI;
 
 pub struct ShipsPlugin;
 
 impl Plugin for ShipsPlugin {
     fn build(&self, app: &mut App) {
-        app.add_startup_system(startup_system)
-            .add_system(user_control_system);
+        app.add_event::<LaunchMissile>()
+            .add_event::<Detonation>()
+            .add_startup_system(startup_system)
+            .add_systems(
+                (
+                    user_control_system
+                        .before(super::autopilot::autopilot_system)
+                        .before(super::physics::kinimatics_system),
+                    player_fire_system.before(launch_missile_system),
+                    player_hardpoint_system.before(launch_missile_system),
+                    fuel_system.after(super::physics::kinimatics_system),
+                    launch_missile_system,
+                    missile_guidance_system
+                        .after(super::seeker::seeker_system)
+                        .before(super::physics::kinimatics_system),
+                    throttle_program_system
+                        .after(user_control_system)
+                        .after(super::scripting::ship_program_system)
+                        .before(super::autopilot::autopilot_system),
+                    missile_detonation_system,
+                    hull_system.after(missile_detonation_system),
+                )
+                    .in_set(SimulationSet),
+            );
     }
 }
 
@@ -14,6 +60,11 @@ impl Plugin for ShipsPlugin {
 #[derive(Component)]
 pub struct Controlled;
 
+/// :COMPONENT: Marks a ship the player can take the controls of. Only one of
+/// them is [`Controlled`] at a time.
+#[derive(Component, Default)]
+pub struct PlayerShip;
+
 /// :COMPONENT: Describes how an engine is controlled.
 #[derive(Reflect, Component, Clone, Copy)]
 #[reflect(Component)]
@@ -38,9 +89,113 @@ impl Default for Throttle {
 #[reflect(Component)]
 pub struct Engine {
     pub fuel: f32,
+    /// Most fuel the engine's tank can hold.
+    pub fuel_capacity: f32,
+    /// Fuel burned per second at full throttle.
+    pub burn_rate: f32,
     pub max_thrust: f32,
     /// Units of force
     pub throttle: Throttle,
+    /// Furthest the engine can swivel away from the ship's nose, in radians.
+    pub gimbal_limit: f32,
+    /// Commanded angle of the engine, relative to the ship's nose, in radians.
+    /// Positive angles swivel the thrust counterclockwise.
+    pub gimbal: f32,
+}
+
+impl Engine {
+    /// How much of its maximum thrust the throttle is asking for, on \[0,1\].
+    pub fn throttle_fraction(&self) -> f32 {
+        match self.throttle {
+            Throttle::Fixed(true) => 1.0,
+            Throttle::Fixed(false) => 0.0,
+            Throttle::Variable(amount) => amount,
+        }
+    }
+
+    /// Force currently produced by the engine. An engine without fuel doesn't
+    /// produce any thrust, no matter the throttle.
+    pub fn thrust(&self) -> f32 {
+        if self.fuel > 0.0 {
+            self.throttle_fraction() * self.max_thrust
+        } else {
+            0.0
+        }
+    }
+
+    /// Direction the engine pushes a ship with the given `rotation`: along the
+    /// ship's nose, swivelled by the gimbal (within its limit).
+    pub fn thrust_direction(&self, rotation: Quat) -> Vec3 {
+        let gimbal = self.gimbal.clamp(-self.gimbal_limit, self.gimbal_limit);
+        rotation.mul_vec3(Quat::from_rotation_z(gimbal).mul_vec3(Vec3::Y))
+    }
+
+    /// Force currently produced by the engine, as a vector.
+    pub fn thrust_vector(&self, rotation: Quat) -> Vec3 {
+        self.thrust_direction(rotation) * self.thrust()
+    }
+
+    /// Speed the engine's exhaust leaves at: the thrust it gets out of each
+    /// unit of fuel burned per second. Zero for an engine which burns nothing.
+    pub fn exhaust_velocity(&self) -> f32 {
+        if self.burn_rate > 0.0 {
+            self.max_thrust / self.burn_rate
+        } else {
+            0.0
+        }
+    }
+}
+
+/// One change in a [`ThrottleProgram`].
+#[derive(Reflect, FromReflect, Clone, Copy, Debug, PartialEq)]
+pub struct ThrottleStep {
+    /// [`SimulationClock::elapsed`](super::clock::SimulationClock) when it
+    /// happens.
+    pub at: f64,
+    /// Must be on the range \[0,1\].
+    pub throttle: f32,
+    /// Heading to turn to and hold, in radians counterclockwise from up (like
+    /// the `heading` builtin). The ship turns however it's told to otherwise.
+    pub heading: Option<f32>,
+}
+
+impl ThrottleStep {
+    /// Sets the engine and turns the ship, pointing at `angle` now, for a step
+    /// of `dt` seconds. The engine's thrust goes straight out the back while
+    /// the step is in charge.
+    pub fn fly(&self, angle: f32, attitude: &mut Attitude, engine: &mut Engine, dt: f32) {
+        engine.throttle = Throttle::Variable(self.throttle.clamp(0.0, 1.0));
+        engine.gimbal = 0.0;
+        if let Some(heading) = self.heading {
+            let error = (heading - angle + PI).rem_euclid(2.0 * PI) - PI;
+            attitude.command(attitude.rate_towards(error, PI, dt));
+        }
+    }
+}
+
+/// :COMPONENT: Throttle and heading changes planned ahead of time. The ship
+/// follows each as the clock reaches it, and the course projection does the
+/// same, so the projected path is the one the ship will fly. The last change
+/// holds until the program is cleared. An autopilot goal still takes over
+/// the engine.
+#[derive(Reflect, Component, Clone, Debug, Default)]
+#[reflect(Component)]
+pub struct ThrottleProgram {
+    /// In the order they happen.
+    pub steps: Vec<ThrottleStep>,
+}
+
+impl ThrottleProgram {
+    /// Adds `step`, in its place in time.
+    pub fn add(&mut self, step: ThrottleStep) {
+        let at = self.steps.partition_point(|s| s.at <= step.at);
+        self.steps.insert(at, step);
+    }
+
+    /// The step in charge at `elapsed`, if the program has started.
+    pub fn active(&self, elapsed: f64) -> Option<&ThrottleStep> {
+        self.steps.iter().take_while(|s| s.at <= elapsed).last()
+    }
 }
 
 /// :COMPONENT: Marker component for ships (in general).
@@ -48,40 +203,324 @@ pub struct Engine {
 #[reflect(Component)]
 pub struct Ship;
 
-/// :BUNDLE: Provided for convenience. Describes a generic ship.
+/// :COMPONENT: Which side an entity is on. Entities of different factions are
+/// hostile to each other.
+#[derive(Reflect, Component, Default, Clone, Copy, PartialEq, Eq, Debug)]
+#[reflect(Component)]
+pub struct Faction(pub u32);
+
+impl Faction {
+    pub const PLAYER: Faction = Faction(0);
+    pub const HOSTILE: Faction = Faction(1);
+
+    pub fn is_hostile_to(&self, other: &Faction) -> bool {
+        self != other
+    }
+}
+
+/// :COMPONENT: Structural integrity of a ship. The ship is destroyed when it
+/// reaches zero.
+#[derive(Reflect, Component, Clone, Copy)]
+#[reflect(Component)]
+pub struct Hull {
+    pub integrity: f32,
+    pub max_integrity: f32,
+}
+
+impl Default for Hull {
+    fn default() -> Self {
+        Self {
+            integrity: 100.0,
+            max_integrity: 100.0,
+        }
+    }
+}
+
+/// :BUNDLE: Provided for convenience. Describes the frame of a generic ship.
+///
+/// A ship's capabilities come from the modules attached to it (see
+/// [`modules`]), which fill in the engine's thrust and fuel capacity, and the
+/// ship's mass.
 #[derive(Bundle, Default)]
 pub struct ShipBundle {
     pub ship: Ship,
+    pub frame: Frame,
+    pub power: PowerGrid,
+    pub thermal: Thermal,
     pub engine: Engine,
+    pub hull: Hull,
+    pub damage_control: DamageControl,
+    pub faction: Faction,
+    pub docking_port: DockingPort,
+    pub transceiver: Transceiver,
+    pub jamming: Jamming,
+    pub emissions: Emissions,
+    pub hold: CargoHold,
+    pub route: Route,
+    pub attitude: Attitude,
+    pub throttle_program: ThrottleProgram,
+    pub project_trajectory: ProjectTrajectory,
 
     #[bundle]
     pub kinimatics_bundle: KinimaticsBundle,
 }
 
+/// :COMPONENT: Room for cargo. Cargo is counted in tonnes, and doesn't yet
+/// add to the ship's mass.
+#[derive(Reflect, Component, Clone, Copy, Debug)]
+#[reflect(Component)]
+pub struct CargoHold {
+    pub cargo: f32,
+    pub capacity: f32,
+}
+
+impl Default for CargoHold {
+    fn default() -> Self {
+        Self {
+            cargo: 0.0,
+            capacity: 100.0,
+        }
+    }
+}
+
+/// :COMPONENT: What's in a ship's [`CargoHold`]. A hold carries one commodity
+/// at a time, and once it's empty, takes whatever comes next. Cargo without a
+/// commodity mixes with anything.
+#[derive(Reflect, Component, Clone, Default, PartialEq, Eq, Debug)]
+#[reflect(Component)]
+pub struct Commodity(pub String);
+
+/// What a launcher fires. Each kind has its own numbers in the
+/// [`Balance`](super::balance::Balance), and shows up differently on sensors
+/// (see [`Contact::signature`](super::sensors::Contact)).
+#[derive(Reflect, FromReflect, Default, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum MissileKind {
+    /// Homes in on its target.
+    #[default]
+    Missile,
+    /// Like a missile, but slower and with a much bigger warhead.
+    Torpedo,
+    /// Dropped rather than fired, and left to drift. It goes off when a ship
+    /// hostile to it comes close.
+    Mine,
+    /// Flies straight, and passes for a ship on sensors to draw fire away from
+    /// the ship that launched it. It has no warhead.
+    Decoy,
+}
+
+impl MissileKind {
+    pub const ALL: &[MissileKind] = &[
+        MissileKind::Missile,
+        MissileKind::Torpedo,
+        MissileKind::Mine,
+        MissileKind::Decoy,
+    ];
+
+    /// Whether it homes in on a target. The rest are never given one.
+    pub fn seeks(self) -> bool {
+        matches!(self, MissileKind::Missile | MissileKind::Torpedo)
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            MissileKind::Missile => "Missile",
+            MissileKind::Torpedo => "Torpedo",
+            MissileKind::Mine => "Mine",
+            MissileKind::Decoy => "Decoy",
+        }
+    }
+}
+
 /// :COMPONENT: Missiles which can be spawned in from ships.
 /// When launched, if they have a target, the missile will
 /// do its best to navigate to that target.
 #[derive(Reflect, Default, Component)]
 #[reflect(Component)]
 pub struct Missile {
+    pub kind: MissileKind,
+    /// Mines and decoys never have one.
     pub target: Option<Entity>,
     pub blast_radius: f32,
+    /// Damage dealt to a hull at the center of the blast. Falls off linearly
+    /// to zero at the edge of the blast radius.
+    pub damage: f32,
+    /// Seconds after launch that the missile self destructs.
+    pub lifetime: f32,
 }
 
 /// :BUNDLE: Provided for convenience. Describes a generic missile.
 #[derive(Bundle, Default)]
 pub struct MissileBundle {
     pub missile: Missile,
+    pub seeker: Seeker,
     pub engine: Engine,
+    pub attitude: Attitude,
 
     #[bundle]
     pub kinimatics_bundle: KinimaticsBundle,
 }
 
+/// :COMPONENT: Launches missiles at targets.
+#[derive(Reflect, Component, Clone)]
+#[reflect(Component)]
+pub struct MissileLauncher {
+    pub ammo: u32,
+    /// Seconds between launches.
+    pub reload_time: f32,
+    /// Seconds until the next missile is ready.
+    pub cooldown: f32,
+    /// Speed of the missile relative to the launching ship.
+    pub launch_speed: f32,
+    /// Number of weapon mounts the launcher can fire from.
+    pub mounts: u32,
+    /// Heat dumped into the ship by each launch, in joules.
+    pub launch_heat: f32,
+    /// What each working weapon mount is loaded with. A launcher without
+    /// modules fires plain missiles.
+    pub hardpoints: Vec<MissileKind>,
+    /// Index of the hardpoint which fires next.
+    pub selected: usize,
+}
+
+impl Default for MissileLauncher {
+    fn default() -> Self {
+        Self {
+            ammo: 4,
+            reload_time: 5.0,
+            cooldown: 0.0,
+            launch_speed: 20.0,
+            mounts: 1,
+            launch_heat: 600.0,
+            hardpoints: Vec::new(),
+            selected: 0,
+        }
+    }
+}
+
+impl MissileLauncher {
+    pub fn ready(&self) -> bool {
+        self.mounts > 0 && self.ammo > 0 && self.cooldown <= 0.0
+    }
+
+    /// What the selected hardpoint fires.
+    pub fn ordnance(&self) -> MissileKind {
+        self.hardpoints
+            .get(self.selected)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Selects the next hardpoint along.
+    pub fn next_hardpoint(&mut self) {
+        self.selected = match self.hardpoints.len() {
+            0 => 0,
+            len => (self.selected + 1) % len,
+        };
+    }
+}
+
+/// :EVENT: Requests that `shooter` fires a missile from its [`MissileLauncher`] at `target`.
+pub struct LaunchMissile {
+    pub shooter: Entity,
+    pub target: Entity,
+}
+
 /// Resource which holds all the sprites used to represent ships on the display.
 #[derive(Clone, Resource)]
-struct ShipSprites {
+pub struct ShipSprites {
     generic_ship: SpriteBundle,
+    pub missile: SpriteBundle,
+}
+
+/// Spawns a generic ship with the standard loadout, along with its sprite.
+pub fn spawn_ship(
+    commands: &mut Commands,
+    sprites: &ShipSprites,
+    translation: Vec3,
+    velocity: Vec3,
+) -> Entity {
+    let ship = commands
+        .spawn(ShipBundle {
+            frame: Frame { dry_mass: 20.0 },
+            kinimatics_bundle: KinimaticsBundle::build()
+                .insert_translation(translation)
+                .insert_velocity(velocity),
+            engine: Engine {
+                fuel: 1000.0,
+                gimbal_limit: 0.3,
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .with_children(|p| {
+            p.spawn((sprites.generic_ship.clone(), MapIcon));
+            modules::standard_loadout(p);
+        })
+        .id();
+    commands.entity(ship).insert((
+        Name::new(format!("Ship {}", ship.index())),
+        Trail::new(120, 0.5, Color::rgb(0.4, 0.6, 0.9)),
+    ));
+    ship
+}
+
+/// Turns and burns to change a ship's velocity by `dv`, the way the AI flies.
+/// The gimbal makes up whatever the turn hasn't yet, and the throttle opens up
+/// in proportion to `dv`, reaching full at `full_throttle`.
+pub fn burn_towards(
+    transform: &Transform,
+    attitude: &mut Attitude,
+    engine: &mut Engine,
+    dv: Vec3,
+    full_throttle: f32,
+    dt: f32,
+) {
+    if dv.length() < 1.0 {
+        engine.throttle = Throttle::Fixed(false);
+        engine.gimbal = 0.0;
+        attitude.command(0.0);
+        return;
+    }
+
+    let error = steer_towards(transform, attitude, dv, PI, dt);
+
+    let nose = transform.rotation.mul_vec3(Vec3::Y).truncate();
+    let offset = nose.angle_between(dv.truncate());
+    engine.gimbal = if offset.is_nan() {
+        0.0
+    } else {
+        offset.clamp(-engine.gimbal_limit, engine.gimbal_limit)
+    };
+
+    engine.throttle = if error < 0.2 + engine.gimbal_limit {
+        Throttle::Variable((dv.length() / full_throttle).clamp(0.0, 1.0))
+    } else {
+        Throttle::Fixed(false)
+    };
+}
+
+/// Turns a ship towards `direction` at no more than `max_rate` radians per
+/// second, slowing down in time not to overshoot, and returns the angle
+/// between the ship's nose and `direction` left after this step.
+pub fn steer_towards(
+    transform: &Transform,
+    attitude: &mut Attitude,
+    direction: Vec3,
+    max_rate: f32,
+    dt: f32,
+) -> f32 {
+    let heading = transform.rotation.mul_vec3(Vec3::Y).truncate();
+    let error = heading.angle_between(direction.truncate());
+
+    if error.is_nan() {
+        attitude.command(0.0);
+        return 0.0;
+    }
+
+    let rate = attitude.rate_towards(error, max_rate, dt);
+    attitude.command(rate);
+
+    (error - rate * dt).abs()
 }
 
 fn startup_system(
@@ -92,56 +531,456 @@ fn startup_system(
     let sprite_resource = ShipSprites {
         generic_ship: SpriteBundle {
             sprite: Sprite {
-                custom_size: Some(Vec2::new(20.0, 20.0)),
+                custom_size: Some(Vec2::new(15.0, 15.0)),
                 ..Default::default()
             },
-            transform: Transform::from_scale(Vec3::new(0.75, 0.75, 0.0)),
             texture: asset_server.load("../assets/ship_1.png"),
             ..Default::default()
         },
+        missile: SpriteBundle {
+            sprite: Sprite {
+                custom_size: Some(Vec2::new(4.0, 4.0)),
+                color: Color::rgb_u8(230, 80, 60),
+                ..Default::default()
+            },
+            texture: asset_server.load("../assets/dot.png"),
+            ..Default::default()
+        },
     };
 
     commands.insert_resource(sprite_resource.clone());
 
     // Add a ship (temporary)
+    let ship = spawn_ship(
+        &mut commands,
+        &sprite_resource,
+        Vec3::new(500.0, 500.0, 0.0),
+        Vec3::ZERO,
+    );
     commands
-        .spawn(ShipBundle {
-            kinimatics_bundle: KinimaticsBundle::build()
-                .insert_mass(100.0)
-                .insert_translation(Vec3::new(500.0, 500.0, 0.0)),
-            engine: Engine {
-                max_thrust: 1000.0,
-                ..Default::default()
-            },
-            ..Default::default()
-        })
-        .insert(Controlled {})
-        .with_children(|p| {
-            p.spawn(sprite_resource.generic_ship.clone());
+        .entity(ship)
+        .insert((
+            Controlled {},
+            PlayerShip,
+            Faction::PLAYER,
+            MissileLauncher::default(),
+            Countermeasure::default(),
+            Laser::default(),
+            Shield::default(),
+            Jammer::default(),
+        ))
+        .with_children(|ship| {
+            modules::laser_loadout(ship);
+            modules::shield_loadout(ship);
+            modules::solar_loadout(ship);
         });
 }
 
 /// Temporary system which give the user control over a ship.
+#[allow(clippy::type_complexity)]
 fn user_control_system(
-    mut query: Query<(&mut Ship, &mut Transform, &mut Engine), With<Controlled>>,
+    mut query: Query<
+        (&mut Ship, &mut Attitude, &mut Engine),
+        (With<Controlled>, Without<NetPlayer>),
+    >,
     input: Res<Input<KeyCode>>,
-    time: Res<Time>,
+    map: Res<InputMap>,
 ) {
-    let drot: f32 = std::f32::consts::PI * time.delta_seconds();
-
-    query.for_each_mut(|(_ship, mut k_bod, mut eng)| {
+    query.for_each_mut(|(_ship, mut attitude, mut eng)| {
         if input.get_pressed().count() == 0 {
             eng.throttle = Throttle::Fixed(false);
         }
 
-        for i in input.get_pressed() {
-            match i {
-                KeyCode::W | KeyCode::Up => eng.throttle = Throttle::Fixed(true),
-                KeyCode::S | KeyCode::Down => eng.throttle = Throttle::Fixed(false),
-                KeyCode::A | KeyCode::Left => k_bod.rotate(Quat::from_rotation_z(drot)),
-                KeyCode::D | KeyCode::Right => k_bod.rotate(Quat::from_rotation_z(-drot)),
-                _ => {}
-            }
+        // the gimbal keys swivel the engine while they're held
+        let pressed = |action| map.pressed(action, &input);
+        eng.gimbal = match (
+            pressed(input_map::Action::GimbalLeft),
+            pressed(input_map::Action::GimbalRight),
+        ) {
+            (true, false) => eng.gimbal_limit,
+            (false, true) => -eng.gimbal_limit,
+            _ => 0.0,
+        };
+
+        if pressed(input_map::Action::ThrustUp) {
+            eng.throttle = Throttle::Fixed(true);
         }
+        if pressed(input_map::Action::ThrustCut) {
+            eng.throttle = Throttle::Fixed(false);
+        }
+        // the reaction wheels hold the ship still when neither is held
+        attitude.command(
+            match (
+                pressed(input_map::Action::RotateLeft),
+                pressed(input_map::Action::RotateRight),
+            ) {
+                (true, false) => PI,
+                (false, true) => -PI,
+                _ => 0.0,
+            },
+        );
     })
 }
+
+/// :SYSTEM: Flies each ship with a throttle program by the step in charge.
+fn throttle_program_system(
+    mut ships: Query<(&ThrottleProgram, &Transform, &mut Attitude, &mut Engine)>,
+    clock: Res<SimulationClock>,
+    time: Res<Time>,
+) {
+    for (program, transform, mut attitude, mut engine) in ships.iter_mut() {
+        if let Some(step) = program.active(clock.elapsed) {
+            let angle = propagation::angle_of(transform.rotation);
+            step.fly(angle, &mut attitude, &mut engine, time.delta_seconds());
+        }
+    }
+}
+
+/// :SYSTEM: Fires the controlled ship's missiles at its [`Target`], or
+/// without one, at the nearest hostile ship.
+#[allow(clippy::type_complexity)]
+fn player_fire_system(
+    player: Query<
+        (Entity, &Transform, Option<&Faction>, Option<&Target>),
+        (With<Controlled>, Without<NetPlayer>),
+    >,
+    ships: Query<(Entity, &Transform, Option<&Faction>), (With<Ship>, Without<Missile>)>,
+    input: Res<Input<KeyCode>>,
+    map: Res<InputMap>,
+    mut launches: EventWriter<LaunchMissile>,
+) {
+    if !map.just_pressed(input_map::Action::FireMissile, &input) {
+        return;
+    }
+    for (shooter, transform, faction, target) in player.iter() {
+        if let Some(target) = target.filter(|t| t.0 != shooter) {
+            launches.send(LaunchMissile {
+                shooter,
+                target: target.0,
+            });
+            continue;
+        }
+        let faction = faction.copied().unwrap_or_default();
+        let nearest = ships
+            .iter()
+            .filter(|(_, _, f)| faction.is_hostile_to(&f.copied().unwrap_or_default()))
+            .min_by(|(_, a, _), (_, b, _)| {
+                let da = a.translation.distance_squared(transform.translation);
+                let db = b.translation.distance_squared(transform.translation);
+                da.total_cmp(&db)
+            });
+        if let Some((target, ..)) = nearest {
+            launches.send(LaunchMissile { shooter, target });
+        }
+    }
+}
+
+/// :SYSTEM: Selects the controlled ship's next hardpoint, and so what it fires.
+fn player_hardpoint_system(
+    mut player: Query<&mut MissileLauncher, (With<Controlled>, Without<NetPlayer>)>,
+    input: Res<Input<KeyCode>>,
+    map: Res<InputMap>,
+) {
+    if !map.just_pressed(input_map::Action::NextHardpoint, &input) {
+        return;
+    }
+    for mut launcher in player.iter_mut() {
+        launcher.next_hardpoint();
+    }
+}
+
+/// Name of the [`Alarm`] which goes off when a missile's lifetime runs out.
+const SELF_DESTRUCT: &str = "self_destruct";
+
+/// Momentum a blast gives a ship right at its center, per point of damage.
+const BLAST_IMPULSE: f32 = 1.0;
+
+/// :SYSTEM: Spawns missiles for each [`LaunchMissile`] request whose launcher is ready.
+#[allow(clippy::type_complexity, clippy::too_many_arguments)]
+pub fn launch_missile_system(
+    mut commands: Commands,
+    mut events: EventReader<LaunchMissile>,
+    mut launchers: Query<(
+        &mut MissileLauncher,
+        &Transform,
+        &Kinimatics,
+        Option<&Faction>,
+        Option<&PlayerShip>,
+        Option<&mut Thermal>,
+    )>,
+    mut scheduler: ResMut<Scheduler>,
+    sandbox: Res<Sandbox>,
+    sprites: Res<ShipSprites>,
+    balance: Res<Balance>,
+    time: Res<Time>,
+) {
+    for (mut launcher, ..) in launchers.iter_mut() {
+        launcher.cooldown = (launcher.cooldown - time.delta_seconds()).max(0.0);
+    }
+
+    for event in events.iter() {
+        let Ok((mut launcher, transform, kinimatics, faction, player, thermal)) =
+            launchers.get_mut(event.shooter)
+        else {
+            continue;
+        };
+
+        if !launcher.ready() {
+            continue;
+        }
+
+        let exempt = sandbox.exempts(player.is_some());
+        if !exempt {
+            launcher.ammo -= 1;
+        }
+        launcher.cooldown = launcher.reload_time;
+        match thermal {
+            Some(mut thermal) if !exempt => thermal.add_heat(launcher.launch_heat),
+            _ => (),
+        }
+
+        let kind = launcher.ordnance();
+        let stats = balance.ordnance(kind);
+        // mines are dropped out of the back, and left behind
+        let out = match kind {
+            MissileKind::Mine => transform.rotation.mul_vec3(Vec3::NEG_Y),
+            _ => transform.rotation.mul_vec3(Vec3::Y),
+        };
+        let target = kind.seeks().then_some(event.target);
+        let missile = spawn_missile(
+            &mut commands,
+            &sprites,
+            &mut scheduler,
+            Missile {
+                kind,
+                target,
+                blast_radius: stats.blast_radius,
+                damage: stats.damage,
+                lifetime: stats.lifetime,
+            },
+            Engine {
+                fuel: stats.fuel,
+                fuel_capacity: stats.fuel,
+                burn_rate: stats.burn_rate,
+                max_thrust: stats.max_thrust,
+                throttle: Throttle::Fixed(true),
+                ..Default::default()
+            },
+            stats.mass,
+            Transform {
+                translation: transform.translation + out * 15.0,
+                rotation: transform.rotation,
+                ..Default::default()
+            },
+            kinimatics.velocity + out * launcher.launch_speed * stats.launch_fraction,
+        );
+        if let Some(faction) = faction {
+            commands.entity(missile).insert(*faction);
+        }
+    }
+}
+
+/// Spawns a missile, set to self destruct once its lifetime is up.
+#[allow(clippy::too_many_arguments)]
+pub fn spawn_missile(
+    commands: &mut Commands,
+    sprites: &ShipSprites,
+    scheduler: &mut Scheduler,
+    missile: Missile,
+    engine: Engine,
+    mass: f32,
+    transform: Transform,
+    velocity: Vec3,
+) -> Entity {
+    let lifetime = missile.lifetime;
+    let missile = commands
+        .spawn(MissileBundle {
+            missile,
+            seeker: Seeker::default(),
+            engine,
+            attitude: Attitude::default(),
+            kinimatics_bundle: KinimaticsBundle::build()
+                .insert_mass(mass)
+                .insert_velocity(velocity)
+                .insert_transform(transform),
+        })
+        .with_children(|p| {
+            p.spawn((sprites.missile.clone(), MapIcon));
+        })
+        .id();
+
+    let alarm = Alarm {
+        entity: missile,
+        name: SELF_DESTRUCT,
+    };
+    scheduler.after(lifetime as f64, Action::Alarm(alarm));
+    missile
+}
+
+/// :SYSTEM: Points missiles at their targets. Missiles try to null out their
+/// velocity relative to the target, plus a closing speed. Decoys just burn
+/// straight ahead, and missiles whose [`Seeker`] has lost its target coast.
+#[allow(clippy::type_complexity)]
+fn missile_guidance_system(
+    mut missiles: Query<(
+        &Missile,
+        &Transform,
+        &mut Attitude,
+        &Kinimatics,
+        &mut Engine,
+        Option<&Seeker>,
+    )>,
+    targets: Query<(&Transform, &Kinimatics), Without<Missile>>,
+    balance: Res<Balance>,
+    time: Res<Time>,
+) {
+    let seeker = &balance.seeker;
+
+    for (missile, transform, mut attitude, kinimatics, mut engine, head) in missiles.iter_mut() {
+        if missile.kind == MissileKind::Decoy {
+            engine.throttle = Throttle::Fixed(true);
+            continue;
+        }
+        if head.is_some_and(|h| !h.state.guiding()) {
+            attitude.command(0.0);
+            engine.throttle = Throttle::Fixed(false);
+            continue;
+        }
+        let Some(Ok((target, target_kin))) = missile.target.map(|t| targets.get(t)) else {
+            engine.throttle = Throttle::Fixed(false);
+            continue;
+        };
+
+        let to_target = (target.translation - transform.translation).normalize_or_zero();
+        let desired = to_target * seeker.closing_speed + target_kin.velocity - kinimatics.velocity;
+
+        let error = steer_towards(
+            transform,
+            &mut attitude,
+            desired,
+            seeker.turn_rate,
+            time.delta_seconds(),
+        );
+        engine.throttle = Throttle::Fixed(error < seeker.burn_cone);
+    }
+}
+
+/// :EVENT: Sent whenever a missile detonates.
+#[derive(Clone, Copy, Debug)]
+pub struct Detonation {
+    pub position: Vec3,
+    pub blast_radius: f32,
+    pub damage: f32,
+}
+
+/// :SYSTEM: Detonates missiles which are close to their target, or have run out
+/// of time, damaging every hull inside the blast radius and pushing it away.
+/// Mines go off when any ship hostile to them comes close, and decoys fizzle
+/// out without a blast.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn missile_detonation_system(
+    mut commands: Commands,
+    missiles: Query<(Entity, &Missile, &Transform, Option<&Faction>)>,
+    targets: Query<&Transform, Without<Missile>>,
+    ships: Query<(&Transform, Option<&Faction>), (With<Ship>, Without<Missile>)>,
+    mut hulls: Query<(
+        Entity,
+        &mut Hull,
+        &Transform,
+        Option<&PlayerShip>,
+        Option<&mut Shield>,
+    )>,
+    mut alarms: EventReader<Alarm>,
+    mut detonations: EventWriter<Detonation>,
+    mut impulses: EventWriter<ApplyImpulse>,
+    sandbox: Res<Sandbox>,
+    balance: Res<Balance>,
+) {
+    let expired: Vec<Entity> = alarms
+        .iter()
+        .filter(|a| a.name == SELF_DESTRUCT)
+        .map(|a| a.entity)
+        .collect();
+
+    for (entity, missile, transform, faction) in missiles.iter() {
+        let stats = balance.ordnance(missile.kind);
+        let fuse = missile.blast_radius * stats.proximity_fuse;
+        let near_target = match missile.kind {
+            MissileKind::Mine => {
+                let faction = faction.copied().unwrap_or_default();
+                ships.iter().any(|(t, f)| {
+                    faction.is_hostile_to(&f.copied().unwrap_or_default())
+                        && t.translation.distance(transform.translation) < fuse
+                })
+            }
+            _ => missile
+                .target
+                .and_then(|t| targets.get(t).ok())
+                .is_some_and(|t| t.translation.distance(transform.translation) < fuse),
+        };
+
+        if !near_target && !expired.contains(&entity) {
+            continue;
+        }
+        if missile.kind == MissileKind::Decoy {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+
+        for (ship, mut hull, t, player, shield) in hulls.iter_mut() {
+            let distance = t.translation.distance(transform.translation);
+            if distance >= missile.blast_radius {
+                continue;
+            }
+            if let Some(push) = ApplyImpulse::from_blast(
+                ship,
+                t.translation,
+                transform.translation,
+                missile.blast_radius,
+                missile.damage * BLAST_IMPULSE,
+            ) {
+                impulses.send(push);
+            }
+            if sandbox.exempts(player.is_some()) {
+                continue;
+            }
+            let mut damage = missile.damage * stats.damage_fraction(distance, missile.blast_radius);
+            if let Some(mut shield) = shield {
+                damage = shield.absorb(t.rotation, transform.translation - t.translation, damage);
+            }
+            hull.integrity -= damage;
+        }
+        detonations.send(Detonation {
+            position: transform.translation,
+            blast_radius: missile.blast_radius,
+            damage: missile.damage,
+        });
+
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// :SYSTEM: Burns fuel from every engine, according to its throttle.
+fn fuel_system(
+    mut engines: Query<(&mut Engine, Option<&PlayerShip>)>,
+    sandbox: Res<Sandbox>,
+    time: Res<Time>,
+) {
+    for (mut engine, player) in engines.iter_mut() {
+        if engine.fuel <= 0.0 || sandbox.exempts(player.is_some()) {
+            continue;
+        }
+
+        let burned = engine.throttle_fraction() * engine.burn_rate * time.delta_seconds();
+        engine.fuel = (engine.fuel - burned).max(0.0);
+    }
+}
+
+/// :SYSTEM: Removes ships whose hull has been destroyed.
+fn hull_system(mut commands: Commands, hulls: Query<(Entity, &Hull), Changed<Hull>>) {
+    for (entity, hull) in hulls.iter() {
+        if hull.integrity <= 0.0 {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
@@ -0,0 +1,384 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+use super::balance::Balance;
+use super::level::{self, LevelSprites};
+use super::modules::{Frame, FuelTank, Thruster};
+use super::profiles::Profile;
+use super::scheduler::Scheduler;
+use super::ships::{self, Engine, Faction, Missile, MissileKind, Ship, ShipSprites, Throttle};
+use super::star_system::Dormant;
+
+pub struct SpawnMenuPlugin;
+
+impl Plugin for SpawnMenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SpawnMenu>()
+            .add_system(spawn_menu_system)
+            .add_system(spawn_click_system.after(spawn_menu_system));
+    }
+}
+
+/// What the spawn menu puts down.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SpawnKind {
+    Ship,
+    Missile,
+    Planet,
+}
+
+/// Resource which holds the state of the spawn menu. F11 opens and closes it.
+///
+/// Once placing, the next click on the map spawns whatever's set up there.
+#[derive(Resource)]
+pub struct SpawnMenu {
+    pub open: bool,
+    pub placing: bool,
+    /// Keep placing after each click, rather than stopping after the first.
+    pub repeat: bool,
+    pub kind: SpawnKind,
+    pub mass: f32,
+    pub velocity: Vec2,
+    /// Degrees clockwise from up.
+    pub heading: f32,
+    pub max_thrust: f32,
+    pub burn_rate: f32,
+    pub fuel: f32,
+    pub radius: f32,
+    pub hostile: bool,
+    /// Kind of missile, which sets its warhead and how it behaves.
+    pub ordnance: MissileKind,
+    /// Missiles home on the nearest ship they're hostile to.
+    pub seek: bool,
+}
+
+impl Default for SpawnMenu {
+    fn default() -> Self {
+        Self {
+            open: false,
+            placing: false,
+            repeat: false,
+            kind: SpawnKind::Ship,
+            mass: 20.0,
+            velocity: Vec2::ZERO,
+            heading: 0.0,
+            max_thrust: 1000.0,
+            burn_rate: 10.0,
+            fuel: 1000.0,
+            radius: 7.5,
+            hostile: false,
+            ordnance: MissileKind::Missile,
+            seek: true,
+        }
+    }
+}
+
+impl SpawnMenu {
+    /// Fills in the fields with the usual values for `kind`.
+    fn reset(&mut self, kind: SpawnKind, balance: &Balance) {
+        let stats = balance.ordnance(self.ordnance);
+        let (mass, max_thrust, burn_rate, fuel) = match kind {
+            SpawnKind::Ship => (20.0, 1000.0, 10.0, 1000.0),
+            SpawnKind::Missile => (stats.mass, stats.max_thrust, stats.burn_rate, stats.fuel),
+            SpawnKind::Planet => (3.285e8, 0.0, 0.0, 0.0),
+        };
+        self.kind = kind;
+        self.mass = mass;
+        self.max_thrust = max_thrust;
+        self.burn_rate = burn_rate;
+        self.fuel = fuel;
+    }
+}
+
+/// :SYSTEM: Shows the spawn menu, for setting up what gets spawned.
+fn spawn_menu_system(
+    mut contexts: EguiContexts,
+    mut menu: ResMut<SpawnMenu>,
+    input: Res<Input<KeyCode>>,
+    balance: Res<Balance>,
+    profile: Res<Profile>,
+) {
+    let ctx = contexts.ctx_mut();
+    if input.just_pressed(KeyCode::F11) && !ctx.wants_keyboard_input() {
+        menu.open = !menu.open;
+    }
+    if !menu.open {
+        menu.placing = false;
+        return;
+    }
+    if input.just_pressed(KeyCode::Escape) {
+        menu.placing = false;
+    }
+
+    let menu = &mut *menu;
+    let mut open = true;
+    egui::Window::new("Spawn")
+        .open(&mut open)
+        .resizable(false)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                for (kind, text) in [
+                    (SpawnKind::Ship, "Ship"),
+                    (SpawnKind::Missile, "Missile"),
+                    (SpawnKind::Planet, "Planet"),
+                ] {
+                    if ui.selectable_label(menu.kind == kind, text).clicked() && menu.kind != kind {
+                        menu.reset(kind, &balance);
+                    }
+                }
+            });
+            if menu.kind == SpawnKind::Ship {
+                ui.horizontal(|ui| {
+                    for class in profile.unlocked.iter() {
+                        if ui.small_button(class.label()).clicked() {
+                            (menu.mass, menu.max_thrust, menu.burn_rate, menu.fuel) = class.stats();
+                        }
+                    }
+                });
+            }
+            ui.separator();
+
+            egui::Grid::new("spawn").show(ui, |ui| {
+                let mass = if menu.kind == SpawnKind::Ship {
+                    "frame mass"
+                } else {
+                    "mass"
+                };
+                ui.label(mass);
+                ui.add(
+                    egui::DragValue::new(&mut menu.mass)
+                        .speed(1.0)
+                        .clamp_range(0.0..=f32::MAX),
+                );
+                ui.end_row();
+
+                ui.label("velocity");
+                ui.horizontal(|ui| {
+                    ui.add(
+                        egui::DragValue::new(&mut menu.velocity.x)
+                            .prefix("x ")
+                            .speed(0.5),
+                    );
+                    ui.add(
+                        egui::DragValue::new(&mut menu.velocity.y)
+                            .prefix("y ")
+                            .speed(0.5),
+                    );
+                });
+                ui.end_row();
+
+                if menu.kind == SpawnKind::Planet {
+                    ui.label("radius");
+                    ui.add(
+                        egui::DragValue::new(&mut menu.radius)
+                            .speed(0.1)
+                            .clamp_range(0.1..=f32::MAX),
+                    );
+                    ui.end_row();
+                    return;
+                }
+
+                ui.label("heading");
+                ui.add(
+                    egui::DragValue::new(&mut menu.heading)
+                        .suffix("°")
+                        .clamp_range(0.0..=360.0),
+                );
+                ui.end_row();
+
+                ui.label("max thrust");
+                ui.add(
+                    egui::DragValue::new(&mut menu.max_thrust)
+                        .speed(1.0)
+                        .clamp_range(0.0..=f32::MAX),
+                );
+                ui.end_row();
+
+                ui.label("burn rate");
+                ui.add(
+                    egui::DragValue::new(&mut menu.burn_rate)
+                        .speed(0.1)
+                        .clamp_range(0.0..=f32::MAX),
+                );
+                ui.end_row();
+
+                ui.label("fuel");
+                ui.add(
+                    egui::DragValue::new(&mut menu.fuel)
+                        .speed(1.0)
+                        .clamp_range(0.0..=f32::MAX),
+                );
+                ui.end_row();
+
+                ui.label("hostile");
+                ui.checkbox(&mut menu.hostile, "");
+                ui.end_row();
+
+                if menu.kind == SpawnKind::Missile {
+                    ui.label("kind");
+                    ui.horizontal(|ui| {
+                        for &ordnance in MissileKind::ALL {
+                            let selected = menu.ordnance == ordnance;
+                            if ui.selectable_label(selected, ordnance.label()).clicked()
+                                && !selected
+                            {
+                                menu.ordnance = ordnance;
+                                menu.reset(SpawnKind::Missile, &balance);
+                            }
+                        }
+                    });
+                    ui.end_row();
+
+                    if menu.ordnance.seeks() {
+                        ui.label("seek nearest");
+                        ui.checkbox(&mut menu.seek, "");
+                        ui.end_row();
+                    }
+                }
+            });
+            ui.separator();
+
+            ui.horizontal(|ui| {
+                if menu.placing {
+                    if ui.button("Cancel").clicked() {
+                        menu.placing = false;
+                    }
+                    ui.weak("click the map to place");
+                } else if ui.button("Place").clicked() {
+                    menu.placing = true;
+                }
+                ui.checkbox(&mut menu.repeat, "repeat");
+            });
+        });
+    menu.open = open;
+}
+
+/// :SYSTEM: Spawns whatever the spawn menu is set up for where the map is
+/// clicked, while it's placing.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn spawn_click_system(
+    mut commands: Commands,
+    mut contexts: EguiContexts,
+    mut menu: ResMut<SpawnMenu>,
+    mouse: Res<Input<MouseButton>>,
+    windows: Query<&Window>,
+    cameras: Query<(&Camera, &GlobalTransform), With<Camera2d>>,
+    ships: Query<(Entity, &GlobalTransform, Option<&Faction>), (With<Ship>, Without<Dormant>)>,
+    ship_sprites: Res<ShipSprites>,
+    level_sprites: Res<LevelSprites>,
+    mut scheduler: ResMut<Scheduler>,
+    balance: Res<Balance>,
+) {
+    if !menu.placing || !mouse.just_pressed(MouseButton::Left) {
+        return;
+    }
+    if contexts.ctx_mut().wants_pointer_input() {
+        return;
+    }
+    let point = windows
+        .get_single()
+        .ok()
+        .zip(cameras.get_single().ok())
+        .and_then(|(window, (camera, transform))| {
+            let cursor = window.cursor_position()?;
+            camera.viewport_to_world(transform, cursor)
+        });
+    let Some(ray) = point else {
+        return;
+    };
+    let at = ray.origin.truncate().extend(0.0);
+    let velocity = menu.velocity.extend(0.0);
+    let rotation = Quat::from_rotation_z(-menu.heading.to_radians());
+    let faction = if menu.hostile {
+        Faction::HOSTILE
+    } else {
+        Faction::PLAYER
+    };
+
+    match menu.kind {
+        SpawnKind::Ship => {
+            let ship = ships::spawn_ship(&mut commands, &ship_sprites, at, velocity);
+            let (max_thrust, burn_rate, fuel) = (menu.max_thrust, menu.burn_rate, menu.fuel);
+            commands.entity(ship).insert((
+                Frame {
+                    dry_mass: menu.mass,
+                },
+                Transform::from_translation(at).with_rotation(rotation),
+                faction,
+            ));
+            // the engine is worked out from the modules, so that's where the
+            // numbers go
+            commands
+                .entity(ship)
+                .add(move |ship: Entity, world: &mut World| {
+                    let children = world
+                        .get::<Children>(ship)
+                        .map(|c| c.to_vec())
+                        .unwrap_or_default();
+                    for child in children {
+                        if let Some(mut thruster) = world.get_mut::<Thruster>(child) {
+                            thruster.max_thrust = max_thrust;
+                            thruster.burn_rate = burn_rate;
+                        }
+                        if let Some(mut tank) = world.get_mut::<FuelTank>(child) {
+                            tank.capacity = fuel;
+                        }
+                    }
+                    if let Some(mut engine) = world.get_mut::<Engine>(ship) {
+                        engine.fuel = fuel;
+                    }
+                });
+        }
+        SpawnKind::Missile => {
+            let target = ships
+                .iter()
+                .filter(|_| menu.seek && menu.ordnance.seeks())
+                .filter(|(_, _, f)| f.is_none_or(|f| faction.is_hostile_to(f)))
+                .min_by(|(_, a, _), (_, b, _)| {
+                    let a = a.translation().distance_squared(at);
+                    let b = b.translation().distance_squared(at);
+                    a.total_cmp(&b)
+                })
+                .map(|(e, ..)| e);
+            let stats = balance.ordnance(menu.ordnance);
+            let missile = ships::spawn_missile(
+                &mut commands,
+                &ship_sprites,
+                &mut scheduler,
+                Missile {
+                    kind: menu.ordnance,
+                    target,
+                    blast_radius: stats.blast_radius,
+                    damage: stats.damage,
+                    lifetime: stats.lifetime,
+                },
+                Engine {
+                    fuel: menu.fuel,
+                    fuel_capacity: menu.fuel,
+                    burn_rate: menu.burn_rate,
+                    max_thrust: menu.max_thrust,
+                    throttle: Throttle::Fixed(true),
+                    ..Default::default()
+                },
+                menu.mass,
+                Transform::from_translation(at).with_rotation(rotation),
+                velocity,
+            );
+            commands.entity(missile).insert(faction);
+        }
+        SpawnKind::Planet => {
+            level::spawn_planet(
+                &mut commands,
+                &level_sprites,
+                menu.radius,
+                menu.mass,
+                at,
+                velocity,
+            );
+        }
+    }
+
+    if !menu.repeat {
+        menu.placing = false;
+    }
+}
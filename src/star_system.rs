@@ -0,0 +1,294 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+use super::asteroid::Asteroid;
+use super::docking::Docked;
+use super::physics::{Kinimatics, SimulationSet};
+use super::scripting::{ScriptEvent, Value};
+use super::ships::{Controlled, Ship};
+use super::trails::Trail;
+
+pub struct StarSystemPlugin;
+
+impl Plugin for StarSystemPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<StarSystems>()
+            .register_type::<InSystem>()
+            .register_type::<JumpPoint>()
+            .register_type::<JumpDrive>()
+            .add_event::<JumpRequest>()
+            .add_system(
+                jump_point_system
+                    .after(super::physics::kinimatics_system)
+                    .in_set(SimulationSet),
+            )
+            .add_system(
+                jump_drive_system
+                    .after(jump_point_system)
+                    .in_set(SimulationSet),
+            )
+            .add_system(settle_system.in_base_set(CoreSet::PostUpdate))
+            .add_system(star_system_label_system);
+    }
+}
+
+/// Resource which holds the names of the star systems, and which one the
+/// player is in.
+///
+/// Only the active system is simulated and drawn. The rest hold still until
+/// the player comes back.
+#[derive(Resource)]
+pub struct StarSystems {
+    pub names: Vec<String>,
+    pub active: usize,
+}
+
+impl Default for StarSystems {
+    fn default() -> Self {
+        Self {
+            names: vec!["Home".to_string()],
+            active: 0,
+        }
+    }
+}
+
+impl StarSystems {
+    /// Adds a system, returning its index.
+    pub fn add(&mut self, name: String) -> usize {
+        self.names.push(name);
+        self.names.len() - 1
+    }
+
+    pub fn check(&self, system: usize) -> Result<usize, String> {
+        if system < self.names.len() {
+            Ok(system)
+        } else {
+            Err(format!("no star system {}", system))
+        }
+    }
+}
+
+/// :COMPONENT: The star system an entity is in. Anything which moves, and
+/// isn't given one, is put in the active system.
+#[derive(Reflect, Component, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[reflect(Component)]
+pub struct InSystem(pub usize);
+
+/// :COMPONENT: Marks entities in a system other than the active one, which
+/// physics leaves alone.
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct Dormant;
+
+/// :COMPONENT: A point ships can jump to another system from, by flying
+/// within `radius` of it. They come out at `arrival` in the other system,
+/// keeping their velocity.
+#[derive(Reflect, Component, Clone, Copy, Debug, Default)]
+#[reflect(Component)]
+pub struct JumpPoint {
+    pub to: usize,
+    pub arrival: Vec3,
+    pub radius: f32,
+}
+
+/// :COMPONENT: Lets a ship jump to another system from anywhere, after
+/// charging for `charge_time` seconds.
+#[derive(Reflect, Component, Clone, Copy, Debug)]
+#[reflect(Component)]
+pub struct JumpDrive {
+    pub charge_time: f32,
+    /// Seconds spent charging for the jump underway, if any.
+    pub charge: Option<f32>,
+    /// Where the jump underway is headed.
+    pub to: usize,
+    pub arrival: Vec3,
+}
+
+impl Default for JumpDrive {
+    fn default() -> Self {
+        Self {
+            charge_time: 10.0,
+            charge: None,
+            to: 0,
+            arrival: Vec3::ZERO,
+        }
+    }
+}
+
+/// :EVENT: Asks for a ship's [`JumpDrive`] to start charging for a jump to
+/// `arrival` in system `to`.
+#[derive(Clone, Copy, Debug)]
+pub struct JumpRequest {
+    pub ship: Entity,
+    pub to: usize,
+    pub arrival: Vec3,
+}
+
+/// Moves `ship` to `arrival` in system `to`, along with anything docked to it,
+/// taking the player along if it's their ship. Raises `jumped`, with the ship
+/// and the systems it left and arrived in.
+#[allow(clippy::too_many_arguments)]
+fn jump(
+    commands: &mut Commands,
+    systems: &mut StarSystems,
+    script_events: &mut EventWriter<ScriptEvent>,
+    ship: Entity,
+    transform: &mut Transform,
+    from: usize,
+    to: usize,
+    arrival: Vec3,
+    passengers: impl Iterator<Item = Entity>,
+    controlled: bool,
+) {
+    transform.translation = arrival;
+    commands
+        .entity(ship)
+        .insert(InSystem(to))
+        .add(|ship: Entity, world: &mut World| {
+            // don't draw a line all the way back to where it jumped from
+            if let Some(mut trail) = world.get_mut::<Trail>(ship) {
+                trail.clear();
+            }
+        });
+    for passenger in passengers {
+        commands.entity(passenger).insert(InSystem(to));
+    }
+    if controlled {
+        systems.active = to;
+    }
+    script_events.send(ScriptEvent {
+        name: "jumped".to_string(),
+        args: vec![
+            Value::from_entity(ship),
+            Value::Num(from as f64),
+            Value::Num(to as f64),
+        ],
+    });
+}
+
+/// :SYSTEM: Jumps ships which fly into a jump point.
+#[allow(clippy::type_complexity)]
+fn jump_point_system(
+    mut commands: Commands,
+    mut systems: ResMut<StarSystems>,
+    mut ships: Query<
+        (Entity, &mut Transform, &InSystem, Option<&Controlled>),
+        (With<Ship>, With<Kinimatics>, Without<Dormant>),
+    >,
+    points: Query<(&JumpPoint, &GlobalTransform, &InSystem), Without<Dormant>>,
+    docked: Query<(Entity, &Docked)>,
+    mut script_events: EventWriter<ScriptEvent>,
+) {
+    for (ship, mut transform, system, controlled) in ships.iter_mut() {
+        let Some((point, ..)) = points.iter().find(|(p, at, s)| {
+            *s == system && at.translation().distance(transform.translation) < p.radius
+        }) else {
+            continue;
+        };
+        let passengers = docked.iter().filter(|(_, d)| d.to == ship).map(|(e, _)| e);
+        jump(
+            &mut commands,
+            &mut systems,
+            &mut script_events,
+            ship,
+            &mut transform,
+            system.0,
+            point.to,
+            point.arrival,
+            passengers,
+            controlled.is_some(),
+        );
+    }
+}
+
+/// :SYSTEM: Charges jump drives, and jumps the ships once they're charged.
+#[allow(clippy::type_complexity)]
+fn jump_drive_system(
+    mut commands: Commands,
+    mut systems: ResMut<StarSystems>,
+    mut requests: EventReader<JumpRequest>,
+    mut ships: Query<
+        (
+            Entity,
+            &mut JumpDrive,
+            &mut Transform,
+            &InSystem,
+            Option<&Controlled>,
+        ),
+        (With<Kinimatics>, Without<Dormant>),
+    >,
+    docked: Query<(Entity, &Docked)>,
+    mut script_events: EventWriter<ScriptEvent>,
+    time: Res<Time>,
+) {
+    for request in requests.iter() {
+        if request.to >= systems.names.len() {
+            continue;
+        }
+        if let Ok((_, mut drive, ..)) = ships.get_mut(request.ship) {
+            drive.charge = Some(0.0);
+            drive.to = request.to;
+            drive.arrival = request.arrival;
+        }
+    }
+
+    for (ship, mut drive, mut transform, system, controlled) in ships.iter_mut() {
+        let Some(charge) = drive.charge.as_mut() else {
+            continue;
+        };
+        *charge += time.delta_seconds();
+        if *charge < drive.charge_time {
+            continue;
+        }
+        drive.charge = None;
+        let passengers = docked.iter().filter(|(_, d)| d.to == ship).map(|(e, _)| e);
+        jump(
+            &mut commands,
+            &mut systems,
+            &mut script_events,
+            ship,
+            &mut transform,
+            system.0,
+            drive.to,
+            drive.arrival,
+            passengers,
+            controlled.is_some(),
+        );
+    }
+}
+
+/// :SYSTEM: Puts new arrivals in the active system, and puts everything
+/// outside the active system to sleep, out of sight.
+#[allow(clippy::type_complexity)]
+fn settle_system(
+    mut commands: Commands,
+    systems: Res<StarSystems>,
+    unplaced: Query<Entity, (Or<(With<Kinimatics>, With<Asteroid>)>, Without<InSystem>)>,
+    mut placed: Query<(Entity, &InSystem, &mut Visibility, Option<&Dormant>)>,
+) {
+    for entity in unplaced.iter() {
+        commands.entity(entity).insert(InSystem(systems.active));
+    }
+
+    for (entity, system, mut visibility, dormant) in placed.iter_mut() {
+        let active = system.0 == systems.active;
+        if active && dormant.is_some() {
+            commands.entity(entity).remove::<Dormant>();
+            *visibility = Visibility::Inherited;
+        } else if !active && dormant.is_none() {
+            commands.entity(entity).insert(Dormant);
+            *visibility = Visibility::Hidden;
+        }
+    }
+}
+
+/// :SYSTEM: Shows which system the player is in, once there's more than one.
+fn star_system_label_system(mut contexts: EguiContexts, systems: Res<StarSystems>) {
+    if systems.names.len() < 2 {
+        return;
+    }
+    egui::Area::new("star_system")
+        .anchor(egui::Align2::CENTER_TOP, egui::vec2(0.0, 8.0))
+        .show(contexts.ctx_mut(), |ui| {
+            ui.heading(&systems.names[systems.active]);
+        });
+}
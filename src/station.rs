@@ -0,0 +1,197 @@
+use bevy::prelude::*;
+
+use super::docking::{Docked, DockingPort};
+use super::physics::{KinimaticsBundle, SimulationSet};
+use super::scripting::{ScriptEvent, Value};
+use super::ships::{CargoHold, Engine, Hull};
+use super::user_interface::MapIcon;
+
+pub struct StationPlugin;
+
+impl Plugin for StationPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Station>()
+            .add_system(station_service_system.in_set(SimulationSet));
+    }
+}
+
+/// :COMPONENT: A large structure which ships dock with to refuel and be
+/// repaired.
+///
+/// Unlike a ship, a station has room for several ships at once, one per port.
+/// Refueling draws on the station's own stock of fuel, while repairs are free.
+/// Its [`CargoHold`] is its inventory, which freighters can trade with if it's
+/// also a depot.
+#[derive(Reflect, Component, Clone, Copy, Debug)]
+#[reflect(Component)]
+pub struct Station {
+    /// How many ships can be docked at once.
+    pub ports: u32,
+    pub fuel: f32,
+    /// Fuel pumped into each docked ship per second.
+    pub refuel_rate: f32,
+    /// Hull integrity restored to each docked ship per second.
+    pub repair_rate: f32,
+}
+
+impl Default for Station {
+    fn default() -> Self {
+        Self {
+            ports: 4,
+            fuel: 20_000.0,
+            refuel_rate: 50.0,
+            repair_rate: 2.0,
+        }
+    }
+}
+
+/// :BUNDLE: Provided for convenience. Describes a generic station.
+#[derive(Bundle)]
+pub struct StationBundle {
+    pub station: Station,
+    pub hull: Hull,
+    pub hold: CargoHold,
+    pub docking_port: DockingPort,
+
+    #[bundle]
+    pub kinimatics_bundle: KinimaticsBundle,
+}
+
+impl Default for StationBundle {
+    fn default() -> Self {
+        Self {
+            station: Station::default(),
+            hull: Hull {
+                integrity: 2000.0,
+                max_integrity: 2000.0,
+            },
+            hold: CargoHold {
+                cargo: 0.0,
+                capacity: 5000.0,
+            },
+            // a big target, which is easy to line up with
+            docking_port: DockingPort {
+                range: 60.0,
+                max_misalignment: 0.6,
+                ..Default::default()
+            },
+            kinimatics_bundle: KinimaticsBundle::build().insert_mass(2000.0),
+        }
+    }
+}
+
+/// Spawns a station on a circular orbit of `radius` around a body whose
+/// gravitational parameter is `mu`,
+/// `angle` radians counterclockwise from the body's +X, going around it
+/// counterclockwise.
+#[allow(clippy::too_many_arguments)]
+pub fn spawn_station(
+    commands: &mut Commands,
+    texture: Handle<Image>,
+    center: Vec3,
+    center_velocity: Vec3,
+    mu: f32,
+    radius: f32,
+    angle: f32,
+    ports: u32,
+) -> Entity {
+    let out = Vec3::new(angle.cos(), angle.sin(), 0.0);
+    let speed = (mu / radius).sqrt();
+    let mut bundle = StationBundle::default();
+    bundle.station.ports = ports;
+    bundle.kinimatics_bundle = bundle
+        .kinimatics_bundle
+        .insert_translation(center + out * radius)
+        .insert_velocity(center_velocity + Vec3::new(-out.y, out.x, 0.0) * speed);
+
+    commands
+        .spawn((Name::new("Station"), bundle))
+        .with_children(|p| {
+            p.spawn((
+                SpriteBundle {
+                    sprite: Sprite {
+                        custom_size: Some(Vec2::splat(30.0)),
+                        color: Color::rgb(0.7, 0.75, 0.8),
+                        ..Default::default()
+                    },
+                    texture,
+                    ..Default::default()
+                },
+                MapIcon,
+            ));
+        })
+        .id()
+}
+
+/// Whether `station` has a free port, given every docked ship.
+pub fn has_free_port<'a>(
+    station: Entity,
+    ports: u32,
+    docked: impl Iterator<Item = &'a Docked>,
+) -> bool {
+    docked.filter(|d| d.to == station).count() < ports as usize
+}
+
+/// Refuels a ship from `fuel` at `refuel_rate`, and repairs it at
+/// `repair_rate`, for `dt` seconds. Returns whether that finished the job, so
+/// `serviced` is only raised once.
+pub fn service(
+    fuel: &mut f32,
+    refuel_rate: f32,
+    repair_rate: f32,
+    engine: Option<Mut<Engine>>,
+    hull: Option<Mut<Hull>>,
+    dt: f32,
+) -> bool {
+    let mut serviced = false;
+    let mut done = true;
+    if let Some(mut engine) = engine {
+        let room = engine.fuel_capacity - engine.fuel;
+        let amount = room.min(refuel_rate * dt).min(*fuel).max(0.0);
+        engine.fuel += amount;
+        *fuel -= amount;
+        serviced |= amount > 0.0;
+        done &= engine.fuel >= engine.fuel_capacity;
+    }
+    if let Some(mut hull) = hull {
+        if hull.integrity < hull.max_integrity {
+            hull.integrity = (hull.integrity + repair_rate * dt).min(hull.max_integrity);
+            serviced = true;
+        }
+        done &= hull.integrity >= hull.max_integrity;
+    }
+    serviced && done
+}
+
+/// :SYSTEM: Refuels and repairs the ships docked at each station. Raises
+/// `serviced`, with the ship and the station, once a ship is full and fully
+/// repaired.
+fn station_service_system(
+    mut stations: Query<&mut Station>,
+    mut ships: Query<(Entity, &Docked, Option<&mut Engine>, Option<&mut Hull>)>,
+    mut script_events: EventWriter<ScriptEvent>,
+    time: Res<Time>,
+) {
+    let dt = time.delta_seconds();
+    for (ship, docked, engine, hull) in ships.iter_mut() {
+        let Ok(mut station) = stations.get_mut(docked.to) else {
+            continue;
+        };
+
+        let station = &mut *station;
+        let (refuel_rate, repair_rate) = (station.refuel_rate, station.repair_rate);
+        if service(
+            &mut station.fuel,
+            refuel_rate,
+            repair_rate,
+            engine,
+            hull,
+            dt,
+        ) {
+            script_events.send(ScriptEvent {
+                name: "serviced".to_string(),
+                args: vec![Value::from_entity(ship), Value::from_entity(docked.to)],
+            });
+        }
+    }
+}
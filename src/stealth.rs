@@ -0,0 +1,124 @@
+use bevy::prelude::*;
+
+use super::heat::{Thermal, AMBIENT_TEMPERATURE};
+use super::input_map::{Action, InputMap};
+use super::jamming::Jammer;
+use super::net::NetPlayer;
+use super::physics::SimulationSet;
+use super::ships::{Controlled, Engine};
+
+pub struct StealthPlugin;
+
+impl Plugin for StealthPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            (
+                player_silent_running_system.before(emissions_system),
+                emissions_system.before(super::sensors::sensor_system),
+            )
+                .in_set(SimulationSet),
+        );
+    }
+}
+
+/// Fraction of their thrust a ship's thrusters give while running silent.
+pub const SILENT_THRUST: f32 = 0.25;
+
+/// Fraction of its range a ship's sensor reaches while running silent, with
+/// its radar off and only listening.
+pub const PASSIVE_RANGE: f32 = 0.5;
+
+/// Signature added by an engine at full throttle...
+const ENGINE_SIGNATURE: f32 = 0.5;
+/// ...by a hull at its overheat temperature...
+const HEAT_SIGNATURE: f32 = 0.3;
+/// ...by an active radar...
+const RADAR_SIGNATURE: f32 = 0.5;
+/// ...and by a running jammer, which can be heard from a long way off.
+const JAMMER_SIGNATURE: f32 = 1.0;
+
+/// :COMPONENT: How loud a ship is. Sensors pick a ship up out to their range
+/// times its `signature`, so a quiet ship has to be approached closely to be
+/// seen, and a loud one can be seen from beyond a sensor's range.
+///
+/// Everything adds to the signature: the hull's radar cross-section, the
+/// engines, the heat the ship is carrying, its radar and its jammer. Running
+/// silent turns the radar and jammer off and holds the thrusters back (see
+/// [`SILENT_THRUST`] and [`PASSIVE_RANGE`]), trading speed and sight for
+/// stealth.
+#[derive(Reflect, Component, Clone, Copy)]
+#[reflect(Component)]
+pub struct Emissions {
+    /// Signature of the bare hull, cold and quiet.
+    pub cross_section: f32,
+    pub silent: bool,
+    /// As of the last tick.
+    pub signature: f32,
+}
+
+impl Default for Emissions {
+    fn default() -> Self {
+        Self {
+            cross_section: 0.5,
+            silent: false,
+            signature: 1.0,
+        }
+    }
+}
+
+/// :SYSTEM: Switches silent running on and off on the controlled ship.
+#[allow(clippy::type_complexity)]
+fn player_silent_running_system(
+    mut player: Query<&mut Emissions, (With<Controlled>, Without<NetPlayer>)>,
+    input: Res<Input<KeyCode>>,
+    map: Res<InputMap>,
+) {
+    if !map.just_pressed(Action::SilentRunning, &input) {
+        return;
+    }
+    for mut emissions in player.iter_mut() {
+        emissions.silent = !emissions.silent;
+    }
+}
+
+/// :SYSTEM: Works out each ship's signature, switching off the jammers of
+/// ships running silent.
+#[allow(clippy::type_complexity)]
+fn emissions_system(
+    mut ships: Query<(
+        &mut Emissions,
+        Option<&Engine>,
+        Option<&Thermal>,
+        Option<&mut Jammer>,
+    )>,
+) {
+    for (mut emissions, engine, thermal, jammer) in ships.iter_mut() {
+        let jamming = match jammer {
+            Some(mut jammer) if emissions.silent && jammer.enabled => {
+                jammer.enabled = false;
+                false
+            }
+            Some(jammer) => jammer.enabled,
+            None => false,
+        };
+
+        let thrust = match emissions.silent {
+            true => SILENT_THRUST,
+            false => 1.0,
+        };
+        let engine = engine.map_or(0.0, |e| e.throttle_fraction() * thrust);
+        let heat = thermal.map_or(0.0, |t| {
+            ((t.temperature - AMBIENT_TEMPERATURE) / (t.overheat - AMBIENT_TEMPERATURE)).max(0.0)
+        });
+        let radar = !emissions.silent as u8 as f32;
+
+        let signature = emissions.cross_section
+            + engine * ENGINE_SIGNATURE
+            + heat * HEAT_SIGNATURE
+            + radar * RADAR_SIGNATURE
+            + jamming as u8 as f32 * JAMMER_SIGNATURE;
+        if emissions.signature != signature {
+            emissions.signature = signature;
+        }
+    }
+}
@@ -0,0 +1,184 @@
+use bevy::prelude::*;
+use std::f32::consts::FRAC_PI_2;
+
+use super::landing::{self, Landed};
+use super::physics::SimulationSet;
+use super::scripting::{ScriptEvent, Value};
+use super::ships::{Engine, Hull};
+use super::station;
+use super::user_interface::MapIcon;
+
+pub struct SurfaceBasePlugin;
+
+impl Plugin for SurfaceBasePlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<SurfaceBase>().add_system(
+            base_service_system
+                .after(super::landing::landing_system)
+                .in_set(SimulationSet),
+        );
+    }
+}
+
+/// :COMPONENT: A base on the surface of a planet, `angle` radians round from
+/// the body's own +X. It's a child of the body, so it turns with it.
+///
+/// Ships which land within `range` of the base are refueled from its stock
+/// and repaired, like at a [`Station`](super::station::Station). Missions
+/// can spawn ships on its pad, and send ships to land there.
+#[derive(Reflect, Component, Clone, Copy, Debug)]
+#[reflect(Component)]
+pub struct SurfaceBase {
+    pub angle: f32,
+    /// Furthest a ship can land from the base and still be serviced.
+    pub range: f32,
+    pub fuel: f32,
+    /// Fuel pumped into each landed ship per second.
+    pub refuel_rate: f32,
+    /// Hull integrity restored to each landed ship per second.
+    pub repair_rate: f32,
+    /// Whether the base is on the day side of its planet (see
+    /// [`daylight`](super::daylight)).
+    pub sunlit: bool,
+}
+
+impl Default for SurfaceBase {
+    fn default() -> Self {
+        Self {
+            angle: 0.0,
+            range: 40.0,
+            fuel: 20_000.0,
+            refuel_rate: 50.0,
+            repair_rate: 2.0,
+            sunlit: true,
+        }
+    }
+}
+
+/// Where a ship sits on a base's pad, and how fast it's moving there.
+#[derive(Clone, Copy, Debug)]
+pub struct Pad {
+    pub base: Entity,
+    pub position: Vec3,
+    pub velocity: Vec3,
+}
+
+impl Pad {
+    /// The pad of the base at `local` on a body of `radius`, at `body`, moving
+    /// at `velocity`.
+    pub fn new(
+        base: Entity,
+        local: &Transform,
+        radius: f32,
+        body: &Transform,
+        velocity: Vec3,
+    ) -> Self {
+        let up = body
+            .rotation
+            .mul_vec3(local.translation.normalize_or_zero());
+        Self {
+            base,
+            position: body.translation + up * (radius + landing::SHIP_RADIUS),
+            velocity,
+        }
+    }
+}
+
+/// Spawns a base on the surface of `body`, which has `radius`, `angle`
+/// radians round from its +X.
+pub fn spawn_surface_base(
+    commands: &mut Commands,
+    texture: Handle<Image>,
+    body: Entity,
+    radius: f32,
+    angle: f32,
+) -> Entity {
+    let out = Vec3::new(angle.cos(), angle.sin(), 0.0);
+    let base = commands
+        .spawn((
+            Name::new("Surface base"),
+            SurfaceBase {
+                angle,
+                ..Default::default()
+            },
+            SpatialBundle::from_transform(
+                Transform::from_translation(out * radius)
+                    .with_rotation(Quat::from_rotation_z(angle - FRAC_PI_2)),
+            ),
+        ))
+        .with_children(|p| {
+            p.spawn((
+                SpriteBundle {
+                    sprite: Sprite {
+                        custom_size: Some(Vec2::splat(16.0)),
+                        color: Color::rgb(0.9, 0.7, 0.3),
+                        ..Default::default()
+                    },
+                    texture,
+                    // over the planet
+                    transform: Transform::from_xyz(0.0, 0.0, 1.0),
+                    ..Default::default()
+                },
+                MapIcon,
+            ));
+        })
+        .id();
+    commands.entity(body).add_child(base);
+    base
+}
+
+/// Whether a landed ship is down at the base. Both transforms are relative to
+/// the body.
+pub fn at_base(base: (&SurfaceBase, &Transform, &Parent), ship: (&Landed, &Transform)) -> bool {
+    let (base, local, parent) = base;
+    let (landed, transform) = ship;
+    landed.on == parent.get()
+        && transform
+            .translation
+            .truncate()
+            .distance(local.translation.truncate())
+            <= base.range
+}
+
+/// :SYSTEM: Refuels and repairs the ships landed at each base. Raises
+/// `serviced`, with the ship and the base, once a ship is full and fully
+/// repaired.
+#[allow(clippy::type_complexity)]
+fn base_service_system(
+    mut bases: Query<(Entity, &mut SurfaceBase, &Transform, &Parent)>,
+    mut ships: Query<(
+        Entity,
+        &Landed,
+        &Transform,
+        Option<&mut Engine>,
+        Option<&mut Hull>,
+    )>,
+    mut script_events: EventWriter<ScriptEvent>,
+    time: Res<Time>,
+) {
+    let dt = time.delta_seconds();
+    for (ship, landed, transform, engine, hull) in ships.iter_mut() {
+        let Some((base, mut surface_base, ..)) = bases
+            .iter_mut()
+            .find(|(_, b, t, p)| at_base((b, t, p), (landed, transform)))
+        else {
+            continue;
+        };
+
+        let surface_base = &mut *surface_base;
+        let (refuel_rate, repair_rate) = (surface_base.refuel_rate, surface_base.repair_rate);
+        if station::service(
+            &mut surface_base.fuel,
+            refuel_rate,
+            repair_rate,
+            engine,
+            hull,
+            dt,
+        ) {
+            script_events.send(ScriptEvent {
+                name: "serviced".to_string(),
+                args: vec![Value::from_entity(ship), Value::from_entity(base)],
+            });
+        }
+    }
+}
@@ -0,0 +1,158 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+use super::input_map::{Action, InputMap};
+use super::labels;
+use super::navigation;
+use super::physics::Kinimatics;
+use super::scripting::BodySnapshot;
+use super::ships::{Controlled, Missile, MissileLauncher, Ship};
+use super::star_system::Dormant;
+
+pub struct TargetingPlugin;
+
+impl Plugin for TargetingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(target_hotkey_system)
+            .add_system(target_overlay_system.after(target_hotkey_system));
+    }
+}
+
+/// Half the width of the brackets drawn around the target, in pixels.
+const BRACKET_SIZE: f32 = 14.0;
+
+/// :COMPONENT: What a ship has locked on to. The controlled ship fires its
+/// missiles at it, rather than at the nearest hostile ship.
+///
+/// The player locks on from the [`ContextMenu`](super::context_menu::ContextMenu),
+/// or cycles through the ships around them, nearest first, with the key bound
+/// to [`Action::NextTarget`].
+#[derive(Component, Clone, Copy, Debug)]
+pub struct Target(pub Entity);
+
+/// :SYSTEM: Moves the controlled ship's lock on to the next nearest ship.
+#[allow(clippy::type_complexity)]
+fn target_hotkey_system(
+    mut commands: Commands,
+    player: Query<(Entity, &Transform, Option<&Target>), With<Controlled>>,
+    ships: Query<(Entity, &Transform), (With<Ship>, Without<Missile>, Without<Dormant>)>,
+    input: Res<Input<KeyCode>>,
+    map: Res<InputMap>,
+) {
+    if !map.just_pressed(Action::NextTarget, &input) {
+        return;
+    }
+    let Ok((player, transform, target)) = player.get_single() else {
+        return;
+    };
+    let mut ships: Vec<_> = ships.iter().filter(|(e, _)| *e != player).collect();
+    if ships.is_empty() {
+        return;
+    }
+    ships.sort_by(|(_, a), (_, b)| {
+        let a = a.translation.distance_squared(transform.translation);
+        let b = b.translation.distance_squared(transform.translation);
+        a.total_cmp(&b)
+    });
+    let next = target
+        .and_then(|t| ships.iter().position(|(e, _)| *e == t.0))
+        .map_or(0, |i| (i + 1) % ships.len());
+    commands.entity(player).insert(Target(ships[next].0));
+}
+
+/// :SYSTEM: Brackets the controlled ship's target on the map, with its
+/// distance and closing speed. When the ship has a launcher to fire with, it
+/// also marks where to aim for a shot at the launcher's speed to meet the
+/// target, for firing by hand.
+#[allow(clippy::type_complexity)]
+fn target_overlay_system(
+    mut commands: Commands,
+    mut contexts: EguiContexts,
+    player: Query<
+        (
+            Entity,
+            &Transform,
+            &Kinimatics,
+            &Target,
+            Option<&MissileLauncher>,
+        ),
+        With<Controlled>,
+    >,
+    targets: Query<(&GlobalTransform, Option<&Kinimatics>, Option<&Name>)>,
+    cameras: Query<(&Camera, &GlobalTransform), With<Camera2d>>,
+) {
+    let Ok((player, transform, kin, target, launcher)) = player.get_single() else {
+        return;
+    };
+    let Ok((target_transform, target_kin, name)) = targets.get(target.0) else {
+        commands.entity(player).remove::<Target>();
+        return;
+    };
+    let Ok((camera, camera_transform)) = cameras.get_single() else {
+        return;
+    };
+    let Some(viewport) = camera.logical_viewport_size() else {
+        return;
+    };
+    // the viewport has +Y up, egui has it down
+    let to_screen = |p: Vec3| {
+        camera
+            .world_to_viewport(camera_transform, p)
+            .map(|at| egui::pos2(at.x, viewport.y - at.y))
+    };
+
+    let own = BodySnapshot {
+        entity: player,
+        position: transform.translation,
+        velocity: kin.velocity,
+        mass: kin.mass,
+    };
+    let other = BodySnapshot {
+        entity: target.0,
+        position: target_transform.translation(),
+        velocity: target_kin.map_or(Vec3::ZERO, |k| k.velocity),
+        mass: target_kin.map_or(0.0, |k| k.mass),
+    };
+    let offset = other.position - own.position;
+    let closing = -(other.velocity - own.velocity).dot(offset.normalize_or_zero());
+
+    let color = egui::Color32::from_rgb(255, 80, 80);
+    let stroke = egui::Stroke::new(1.5, color);
+    let painter = contexts
+        .ctx_mut()
+        .layer_painter(egui::LayerId::background());
+    if let Some(at) = to_screen(other.position) {
+        for (x, y) in [(-1.0, -1.0), (1.0, -1.0), (-1.0, 1.0), (1.0, 1.0)] {
+            let corner = at + egui::vec2(x, y) * BRACKET_SIZE;
+            let arm = BRACKET_SIZE / 2.0;
+            painter.line_segment([corner, corner - egui::vec2(x * arm, 0.0)], stroke);
+            painter.line_segment([corner, corner - egui::vec2(0.0, y * arm)], stroke);
+        }
+        let text = match name {
+            Some(name) => format!("{} {}", name, labels::distance_text(offset.length())),
+            None => labels::distance_text(offset.length()),
+        };
+        painter.text(
+            at + egui::vec2(BRACKET_SIZE + 4.0, -BRACKET_SIZE),
+            egui::Align2::LEFT_TOP,
+            format!("{}\n{:+.1} m/s", text, closing),
+            egui::FontId::proportional(12.0),
+            color,
+        );
+    }
+
+    let Some(launcher) = launcher.filter(|l| l.mounts > 0) else {
+        return;
+    };
+    let speed = launcher.launch_speed;
+    let Some((direction, time)) = navigation::lead(&own, &other, speed) else {
+        return;
+    };
+    // where the target will be when the shot meets it, as seen from the ship
+    let aim = own.position + direction * speed * time;
+    if let (Some(from), Some(to)) = (to_screen(other.position), to_screen(aim)) {
+        painter.line_segment([from, to], (1.0, color.linear_multiply(0.5)));
+        painter.circle_stroke(to, 5.0, stroke);
+        painter.circle_filled(to, 1.5, color);
+    }
+}
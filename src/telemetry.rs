@@ -0,0 +1,193 @@
+use std::net::UdpSocket;
+
+use bevy::prelude::*;
+use serde::Serialize;
+
+use super::clock::SimulationClock;
+use super::level::AstroObject;
+use super::physics::{Kinimatics, SimulationSet};
+use super::scripting::ScriptEvent;
+use super::ships::{Engine, Missile, Ship};
+use super::star_system::Dormant;
+
+pub struct TelemetryPlugin;
+
+impl Plugin for TelemetryPlugin {
+    fn build(&self, app: &mut App) {
+        let args: Vec<String> = std::env::args().collect();
+        let value = |flag: &str| args.iter().find_map(|a| a.strip_prefix(flag));
+        let rate = value("--telemetry-rate=")
+            .and_then(|r| r.parse().ok())
+            .unwrap_or(DEFAULT_RATE);
+        app.insert_resource(Telemetry::new(value("--telemetry="), rate))
+            .add_system(telemetry_system.after(SimulationSet));
+    }
+}
+
+/// Samples sent per second of simulation time, unless `--telemetry-rate` says
+/// otherwise.
+const DEFAULT_RATE: f64 = 10.0;
+
+/// Most entities sent in one datagram, to keep each under the size of a UDP
+/// packet.
+const ENTITIES_PER_DATAGRAM: usize = 64;
+
+#[derive(Serialize, Debug)]
+struct EntitySample {
+    id: u64,
+    name: Option<String>,
+    kind: &'static str,
+    position: [f32; 2],
+    velocity: [f32; 2],
+    acceleration: [f32; 2],
+    mass: f32,
+    fuel: Option<f32>,
+}
+
+#[derive(Serialize, Debug)]
+struct EventSample {
+    met: f64,
+    name: String,
+    args: Vec<String>,
+}
+
+/// One datagram of a sample. A sample with many entities is split over several
+/// datagrams, numbered `part` of `parts`; events go out with the first.
+#[derive(Serialize, Debug)]
+struct Datagram<'a> {
+    sample: u64,
+    part: usize,
+    parts: usize,
+    met: f64,
+    entities: &'a [EntitySample],
+    events: &'a [EventSample],
+}
+
+/// Resource which streams the state of the simulation to analysis tools, like
+/// plotting dashboards or tournament spectators. Start the game with
+/// `--telemetry=ADDRESS` (like `--telemetry=127.0.0.1:9000`) to turn it on,
+/// and `--telemetry-rate=HZ` to change how often it samples.
+///
+/// Each sample is sent as JSON over UDP, with the position, velocity,
+/// acceleration, mass and fuel of everything that moves, and the game events
+/// since the last sample. Nothing is resent, so a dropped datagram is lost.
+#[derive(Resource)]
+pub struct Telemetry {
+    socket: Option<UdpSocket>,
+    /// Samples sent per second of simulation time.
+    pub rate: f64,
+    pub samples: u64,
+    last_sample: Option<f64>,
+    events: Vec<EventSample>,
+}
+
+impl Telemetry {
+    pub fn new(address: Option<&str>, rate: f64) -> Self {
+        let socket = address.and_then(|address| {
+            let socket = UdpSocket::bind("0.0.0.0:0").and_then(|s| s.connect(address).map(|_| s));
+            match socket {
+                Ok(socket) => {
+                    info!("telemetry: sending to {} at {} Hz", address, rate);
+                    Some(socket)
+                }
+                Err(e) => {
+                    error!("telemetry: couldn't send to {}: {}", address, e);
+                    None
+                }
+            }
+        });
+        Self {
+            socket,
+            rate,
+            samples: 0,
+            last_sample: None,
+            events: Vec::new(),
+        }
+    }
+}
+
+/// :SYSTEM: Collects game events, and sends a sample once the cadence comes
+/// around.
+#[allow(clippy::type_complexity)]
+fn telemetry_system(
+    mut telemetry: ResMut<Telemetry>,
+    mut events: EventReader<ScriptEvent>,
+    clock: Res<SimulationClock>,
+    bodies: Query<
+        (
+            Entity,
+            Option<&Name>,
+            &Transform,
+            &Kinimatics,
+            Option<&Engine>,
+            Option<&Ship>,
+            Option<&Missile>,
+            Option<&AstroObject>,
+        ),
+        Without<Dormant>,
+    >,
+) {
+    if telemetry.socket.is_none() {
+        events.clear();
+        return;
+    }
+    telemetry
+        .events
+        .extend(events.iter().map(|event| EventSample {
+            met: clock.elapsed,
+            name: event.name.clone(),
+            args: event.args.iter().map(|a| a.to_string()).collect(),
+        }));
+
+    let interval = 1.0 / telemetry.rate.max(0.001);
+    if telemetry
+        .last_sample
+        .is_some_and(|last| clock.elapsed - last < interval)
+    {
+        return;
+    }
+    telemetry.last_sample = Some(clock.elapsed);
+    telemetry.samples += 1;
+
+    let entities: Vec<_> = bodies
+        .iter()
+        .map(
+            |(entity, name, transform, kin, engine, ship, missile, body)| EntitySample {
+                id: entity.to_bits(),
+                name: name.map(|n| n.to_string()),
+                kind: match (ship, missile, body) {
+                    (Some(_), ..) => "ship",
+                    (_, Some(_), _) => "missile",
+                    (.., Some(_)) => "body",
+                    _ => "other",
+                },
+                position: [transform.translation.x, transform.translation.y],
+                velocity: [kin.velocity.x, kin.velocity.y],
+                acceleration: [kin.acceleration.x, kin.acceleration.y],
+                mass: kin.mass,
+                fuel: engine.map(|e| e.fuel),
+            },
+        )
+        .collect();
+    let events = std::mem::take(&mut telemetry.events);
+
+    let chunks: Vec<_> = entities.chunks(ENTITIES_PER_DATAGRAM).collect();
+    let parts = chunks.len().max(1);
+    for part in 0..parts {
+        let datagram = Datagram {
+            sample: telemetry.samples,
+            part,
+            parts,
+            met: clock.elapsed,
+            entities: chunks.get(part).copied().unwrap_or(&[]),
+            events: if part == 0 { &events } else { &[] },
+        };
+        let Ok(bytes) = serde_json::to_vec(&datagram) else {
+            continue;
+        };
+        if let Some(socket) = &telemetry.socket {
+            // nobody listening is fine; the feed carries on regardless
+            let _ = socket.send(&bytes);
+        }
+    }
+}
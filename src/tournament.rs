@@ -0,0 +1,395 @@
+use std::path::Path;
+
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use serde::Serialize;
+
+use super::clock::SimulationClock;
+use super::director::MissionDirector;
+use super::headless::Headless;
+use super::physics::{Kinimatics, SimulationSet};
+use super::scripting::{ShipProgram, VmState};
+use super::ships::{self, Engine, Faction, Hull, MissileLauncher, ShipSprites};
+
+/// Ticks a match may last before it's called a draw, unless
+/// `--tournament-ticks` says otherwise.
+const DEFAULT_MATCH_TICKS: u64 = 60 * 60 * 3;
+
+/// Distance of each ship from the center of the arena.
+const ARENA_RADIUS: f32 = 150.0;
+
+/// Points for winning a match, and for each survivor of a draw.
+const WIN_POINTS: u32 = 3;
+const DRAW_POINTS: u32 = 1;
+
+/// Settings for a tournament between ship programs. Start the game with
+/// `--tournament=PROGRAMS`, where `PROGRAMS` is either a comma separated list
+/// of programs, which all fight in one match, or a directory, every pair of
+/// whose programs fight a match each. Paths are relative to `assets`.
+///
+/// Each match spawns the programs' ships evenly around an empty arena, facing
+/// the middle, each on its own side. It lasts until at most one is left, or
+/// `--tournament-ticks=N` ticks go by. The results are written to
+/// `--report=PATH`, or printed if that isn't given, and with `--headless` the
+/// game exits once they are.
+#[derive(Resource, Clone, Debug)]
+pub struct Tournament {
+    /// The programs in each match.
+    pub matches: Vec<Vec<String>>,
+    pub max_ticks: u64,
+    pub report: Option<String>,
+}
+
+impl Tournament {
+    /// The tournament given on the command line, if there is one.
+    pub fn from_args() -> Option<Self> {
+        let args: Vec<String> = std::env::args().collect();
+        let value = |flag: &str| args.iter().find_map(|a| a.strip_prefix(flag));
+        let programs = value("--tournament=")?;
+        let matches = match Self::round_robin(programs) {
+            Ok(matches) => matches,
+            Err(e) => {
+                error!("tournament: {}", e);
+                return None;
+            }
+        };
+        Some(Self {
+            matches,
+            max_ticks: value("--tournament-ticks=")
+                .and_then(|n| n.parse().ok())
+                .unwrap_or(DEFAULT_MATCH_TICKS),
+            report: value("--report=").map(|p| p.to_string()),
+        })
+    }
+
+    fn round_robin(programs: &str) -> Result<Vec<Vec<String>>, String> {
+        let dir = Path::new("assets").join(programs);
+        if !dir.is_dir() {
+            let programs: Vec<String> = programs.split(',').map(|p| p.to_string()).collect();
+            if programs.len() < 2 {
+                return Err("a match needs at least two programs".to_string());
+            }
+            return Ok(vec![programs]);
+        }
+
+        let mut found: Vec<String> = std::fs::read_dir(&dir)
+            .map_err(|e| format!("couldn't read {}: {}", dir.display(), e))?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.file_name().to_string_lossy().to_string())
+            .filter(|name| name.ends_with(".sasm"))
+            .map(|name| format!("{}/{}", programs.trim_end_matches('/'), name))
+            .collect();
+        found.sort();
+        if found.len() < 2 {
+            return Err(format!("{} has fewer than two programs", dir.display()));
+        }
+        let mut matches = Vec::new();
+        for (i, a) in found.iter().enumerate() {
+            for b in found.iter().skip(i + 1) {
+                matches.push(vec![a.clone(), b.clone()]);
+            }
+        }
+        Ok(matches)
+    }
+}
+
+pub struct TournamentPlugin(pub Tournament);
+
+impl Plugin for TournamentPlugin {
+    fn build(&self, app: &mut App) {
+        // the tournament decides when a headless run is over
+        if let Some(mut headless) = app.world.get_resource_mut::<Headless>() {
+            headless.ticks = u64::MAX;
+        }
+        app.insert_resource(self.0.clone())
+            .init_resource::<Standings>()
+            .add_system(tournament_system.after(SimulationSet))
+            .add_system(tournament_panel_system);
+    }
+}
+
+/// How one program did in a match.
+#[derive(Serialize, Clone, Debug)]
+pub struct ContestantResult {
+    pub program: String,
+    pub survived: bool,
+    pub hull: f32,
+    pub fuel_used: f32,
+    /// The error the program stopped with, if it faulted.
+    pub fault: Option<String>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct MatchResult {
+    /// The program left standing, unless the match was a draw.
+    pub winner: Option<String>,
+    pub ticks: u64,
+    /// Seconds of simulation time the match took.
+    pub time: f64,
+    pub contestants: Vec<ContestantResult>,
+}
+
+#[derive(Serialize, Clone, Default, Debug)]
+pub struct Standing {
+    pub program: String,
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+    pub points: u32,
+}
+
+/// A contestant in the match being fought.
+struct Contestant {
+    program: String,
+    ship: Entity,
+    fuel: f32,
+    start_fuel: Option<f32>,
+    hull: f32,
+    fault: Option<String>,
+    alive: bool,
+}
+
+/// Resource which holds the results of the tournament so far.
+#[derive(Resource, Default)]
+pub struct Standings {
+    pub results: Vec<MatchResult>,
+    contestants: Vec<Contestant>,
+    started: f64,
+    ticks: u64,
+    last_elapsed: f64,
+    finished: bool,
+}
+
+impl Standings {
+    pub fn table(&self) -> Vec<Standing> {
+        let mut table: Vec<Standing> = Vec::new();
+        for result in self.results.iter() {
+            for contestant in result.contestants.iter() {
+                let index = match table.iter().position(|s| s.program == contestant.program) {
+                    Some(index) => index,
+                    None => {
+                        table.push(Standing {
+                            program: contestant.program.clone(),
+                            ..Default::default()
+                        });
+                        table.len() - 1
+                    }
+                };
+                let standing = &mut table[index];
+                match &result.winner {
+                    Some(winner) if *winner == contestant.program => {
+                        standing.wins += 1;
+                        standing.points += WIN_POINTS;
+                    }
+                    None if contestant.survived
+                        || result.contestants.iter().all(|c| !c.survived) =>
+                    {
+                        standing.draws += 1;
+                        standing.points += DRAW_POINTS;
+                    }
+                    _ => standing.losses += 1,
+                }
+            }
+        }
+        table.sort_by(|a, b| b.points.cmp(&a.points).then(a.program.cmp(&b.program)));
+        table
+    }
+}
+
+#[derive(Serialize)]
+struct Report {
+    matches: Vec<MatchResult>,
+    standings: Vec<Standing>,
+}
+
+/// :SYSTEM: Sets up each match of the tournament in turn, keeps track of how
+/// the contestants are doing, and once the last match is over writes the
+/// results.
+#[allow(clippy::type_complexity, clippy::too_many_arguments)]
+fn tournament_system(
+    mut commands: Commands,
+    tournament: Res<Tournament>,
+    mut standings: ResMut<Standings>,
+    clock: Res<SimulationClock>,
+    asset_server: Res<AssetServer>,
+    sprites: Res<ShipSprites>,
+    headless: Option<Res<Headless>>,
+    mut exit: EventWriter<AppExit>,
+    world: Query<Entity, (With<Kinimatics>, Without<Parent>)>,
+    ships: Query<(&Engine, &Hull, Option<&ShipProgram>)>,
+) {
+    if standings.finished {
+        return;
+    }
+
+    if standings.contestants.is_empty() {
+        let Some(programs) = tournament.matches.get(standings.results.len()) else {
+            finish(&tournament, &mut standings, headless.is_some(), &mut exit);
+            return;
+        };
+        info!("tournament: {}", programs.join(" vs "));
+
+        // every match starts in an empty arena
+        commands.remove_resource::<MissionDirector>();
+        for entity in world.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+        let count = programs.len();
+        for (i, program) in programs.iter().enumerate() {
+            let angle = std::f32::consts::TAU * i as f32 / count as f32;
+            let at = Vec3::new(angle.cos(), angle.sin(), 0.0) * ARENA_RADIUS;
+            let ship = ships::spawn_ship(&mut commands, &sprites, at, Vec3::ZERO);
+            let name = Path::new(program)
+                .file_stem()
+                .map_or(program.clone(), |s| s.to_string_lossy().to_string());
+            commands.entity(ship).insert((
+                ShipProgram::new(asset_server.load(program.as_str())),
+                Faction(i as u32),
+                MissileLauncher::default(),
+                Name::new(name),
+                // facing the middle, so nobody starts out ahead
+                Transform::from_translation(at)
+                    .with_rotation(Quat::from_rotation_z(angle + std::f32::consts::FRAC_PI_2)),
+            ));
+            standings.contestants.push(Contestant {
+                program: program.clone(),
+                ship,
+                fuel: 0.0,
+                start_fuel: None,
+                hull: 0.0,
+                fault: None,
+                alive: true,
+            });
+        }
+        standings.started = clock.elapsed;
+        standings.last_elapsed = clock.elapsed;
+        standings.ticks = 0;
+        return;
+    }
+
+    if clock.elapsed == standings.last_elapsed {
+        return;
+    }
+    standings.last_elapsed = clock.elapsed;
+    standings.ticks += 1;
+
+    for contestant in standings.contestants.iter_mut() {
+        match ships.get(contestant.ship) {
+            Ok((engine, hull, program)) => {
+                contestant.start_fuel.get_or_insert(engine.fuel);
+                contestant.fuel = engine.fuel;
+                contestant.hull = hull.integrity;
+                if let Some(VmState::Faulted(e)) = program.map(|p| p.vm.state()) {
+                    contestant.fault = Some(e.to_string());
+                }
+            }
+            // ships are despawned once they're destroyed
+            Err(_) if contestant.start_fuel.is_some() => {
+                contestant.alive = false;
+                contestant.hull = 0.0;
+            }
+            Err(_) => {}
+        }
+    }
+
+    let alive = standings.contestants.iter().filter(|c| c.alive).count();
+    if alive > 1 && standings.ticks < tournament.max_ticks {
+        return;
+    }
+
+    let contestants = std::mem::take(&mut standings.contestants);
+    let winner = match alive {
+        1 => contestants
+            .iter()
+            .find(|c| c.alive)
+            .map(|c| c.program.clone()),
+        _ => None,
+    };
+    info!(
+        "tournament: {} after {} ticks",
+        winner.as_deref().unwrap_or("draw"),
+        standings.ticks
+    );
+    let result = MatchResult {
+        winner,
+        ticks: standings.ticks,
+        time: clock.elapsed - standings.started,
+        contestants: contestants
+            .into_iter()
+            .map(|c| ContestantResult {
+                program: c.program,
+                survived: c.alive,
+                hull: c.hull,
+                fuel_used: c.start_fuel.map_or(0.0, |start| start - c.fuel),
+                fault: c.fault,
+            })
+            .collect(),
+    };
+    standings.results.push(result);
+}
+
+/// Writes the tournament's results, and exits if the game is headless.
+fn finish(
+    tournament: &Tournament,
+    standings: &mut Standings,
+    headless: bool,
+    exit: &mut EventWriter<AppExit>,
+) {
+    standings.finished = true;
+    let report = Report {
+        matches: standings.results.clone(),
+        standings: standings.table(),
+    };
+    match ron::ser::to_string_pretty(&report, ron::ser::PrettyConfig::default()) {
+        Ok(text) => match &tournament.report {
+            Some(path) => {
+                if let Err(e) = std::fs::write(path, text) {
+                    error!("tournament: couldn't write the report to {}: {}", path, e);
+                }
+            }
+            None => println!("{}", text),
+        },
+        Err(e) => error!("tournament: couldn't write the report: {}", e),
+    }
+    if headless {
+        exit.send(AppExit);
+    }
+}
+
+/// :SYSTEM: Shows the match being fought, and the standings so far.
+fn tournament_panel_system(
+    mut contexts: EguiContexts,
+    tournament: Res<Tournament>,
+    standings: Res<Standings>,
+) {
+    egui::Window::new("Tournament")
+        .anchor(egui::Align2::RIGHT_TOP, [-8.0, 8.0])
+        .resizable(false)
+        .show(contexts.ctx_mut(), |ui| {
+            if standings.finished {
+                ui.label("Finished");
+            } else {
+                ui.label(format!(
+                    "Match {} of {}, tick {}",
+                    standings.results.len() + 1,
+                    tournament.matches.len(),
+                    standings.ticks
+                ));
+            }
+            egui::Grid::new("standings").striped(true).show(ui, |ui| {
+                for heading in ["Program", "W", "D", "L", "Points"] {
+                    ui.strong(heading);
+                }
+                ui.end_row();
+                for standing in standings.table() {
+                    ui.label(&standing.program);
+                    ui.label(standing.wins.to_string());
+                    ui.label(standing.draws.to_string());
+                    ui.label(standing.losses.to_string());
+                    ui.label(standing.points.to_string());
+                    ui.end_row();
+                }
+            });
+        });
+}
@@ -0,0 +1,279 @@
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use bevy_egui::{egui, EguiContexts};
+
+use super::docking::Docked;
+use super::physics::SimulationSet;
+use super::scripting::{ScriptEvent, Value};
+use super::ships::{CargoHold, Commodity, Controlled, Faction};
+
+pub struct TradePlugin;
+
+impl Plugin for TradePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Accounts>()
+            .register_type::<Market>()
+            .register_type::<Listing>()
+            .add_event::<TradeRequest>()
+            .add_system(trade_system.in_set(SimulationSet))
+            .add_system(market_panel_system);
+    }
+}
+
+/// Resource which holds each faction's credits.
+#[derive(Resource, Default)]
+pub struct Accounts {
+    pub credits: HashMap<u32, f32>,
+}
+
+impl Accounts {
+    pub fn balance(&self, faction: Faction) -> f32 {
+        self.credits.get(&faction.0).copied().unwrap_or(0.0)
+    }
+
+    pub fn balance_mut(&mut self, faction: Faction) -> &mut f32 {
+        self.credits.entry(faction.0).or_default()
+    }
+}
+
+/// A commodity a market deals in.
+#[derive(Reflect, FromReflect, Clone, Default, Debug)]
+pub struct Listing {
+    pub commodity: String,
+    /// Tonnes the market has to sell.
+    pub stock: f32,
+    /// Credits per tonne the market sells at.
+    pub buy: f32,
+    /// Credits per tonne the market pays.
+    pub sell: f32,
+    /// Tonnes the station makes an hour, or if negative, uses up.
+    pub production: f32,
+    /// Stock the station is happy with. Prices rise when there's less, and
+    /// fall when there's more.
+    pub equilibrium: f32,
+    /// Price halfway between buying and selling, at equilibrium.
+    pub base_price: f32,
+    /// How far buying and selling prices are from the middle, as a fraction
+    /// of it.
+    pub spread: f32,
+}
+
+impl Listing {
+    /// A listing which holds its prices while the stock stays where it is.
+    pub fn new(commodity: String, stock: f32, buy: f32, sell: f32) -> Self {
+        let mid = (buy + sell) / 2.0;
+        Self {
+            commodity,
+            stock,
+            buy,
+            sell,
+            production: 0.0,
+            equilibrium: stock,
+            base_price: mid,
+            spread: if mid > 0.0 {
+                (buy - sell) / (2.0 * mid)
+            } else {
+                0.0
+            },
+        }
+    }
+
+    /// Price halfway between buying and selling.
+    pub fn price(&self) -> f32 {
+        (self.buy + self.sell) / 2.0
+    }
+
+    /// Sets the buying and selling prices around `price`.
+    pub fn set_price(&mut self, price: f32) {
+        self.buy = price * (1.0 + self.spread);
+        self.sell = price * (1.0 - self.spread);
+    }
+}
+
+/// :COMPONENT: Lets ships docked to a station buy and sell commodities.
+#[derive(Reflect, Component, Clone, Default, Debug)]
+#[reflect(Component)]
+pub struct Market {
+    pub listings: Vec<Listing>,
+}
+
+impl Market {
+    /// Adds a listing, in place of any other for the same commodity.
+    pub fn list(&mut self, listing: Listing) {
+        self.listings.retain(|l| l.commodity != listing.commodity);
+        self.listings.push(listing);
+    }
+
+    /// `commodity`'s buying price, selling price and stock, for scripts.
+    pub fn quote(&self, commodity: &str) -> Result<Vec<Value>, String> {
+        let listing = self
+            .listings
+            .iter()
+            .find(|l| l.commodity == commodity)
+            .ok_or_else(|| format!("no market for {}", commodity))?;
+        Ok(vec![
+            Value::Num(listing.buy as f64),
+            Value::Num(listing.sell as f64),
+            Value::Num(listing.stock as f64),
+        ])
+    }
+}
+
+/// :EVENT: Asks to buy `tonnes` of `commodity` from the market `ship` is
+/// docked to, or with negative tonnes, to sell them.
+#[derive(Clone, Debug)]
+pub struct TradeRequest {
+    pub ship: Entity,
+    pub commodity: String,
+    pub tonnes: f32,
+}
+
+/// :SYSTEM: Carries out trades with markets, on the ship's faction's account.
+/// Ships buy as much as they can afford, fit, and the market has, and sell as
+/// much as they're carrying. Raises `traded` with the ship, the station, the
+/// commodity, the tonnes bought (negative for sold), and the credits paid
+/// (negative for earned).
+fn trade_system(
+    mut commands: Commands,
+    mut requests: EventReader<TradeRequest>,
+    mut ships: Query<(
+        &Docked,
+        &mut CargoHold,
+        Option<&Commodity>,
+        Option<&Faction>,
+    )>,
+    mut markets: Query<&mut Market>,
+    mut accounts: ResMut<Accounts>,
+    mut script_events: EventWriter<ScriptEvent>,
+) {
+    for request in requests.iter() {
+        let Ok((docked, mut hold, held, faction)) = ships.get_mut(request.ship) else {
+            continue;
+        };
+        let Ok(mut market) = markets.get_mut(docked.to) else {
+            continue;
+        };
+        let Some(listing) = market
+            .listings
+            .iter_mut()
+            .find(|l| l.commodity == request.commodity)
+        else {
+            continue;
+        };
+        let same = held.is_none_or(|h| h.0 == listing.commodity);
+        let credits = accounts.balance_mut(faction.copied().unwrap_or_default());
+
+        let (tonnes, paid) = if request.tonnes >= 0.0 {
+            if !same && hold.cargo > 0.0 {
+                continue;
+            }
+            let affordable = if listing.buy > 0.0 {
+                *credits / listing.buy
+            } else {
+                f32::MAX
+            };
+            let tonnes = request
+                .tonnes
+                .min(listing.stock)
+                .min(hold.capacity - hold.cargo)
+                .min(affordable)
+                .max(0.0);
+            listing.stock -= tonnes;
+            hold.cargo += tonnes;
+            commands
+                .entity(request.ship)
+                .insert(Commodity(listing.commodity.clone()));
+            (tonnes, tonnes * listing.buy)
+        } else {
+            if !same {
+                continue;
+            }
+            let tonnes = (-request.tonnes).min(hold.cargo).max(0.0);
+            listing.stock += tonnes;
+            hold.cargo -= tonnes;
+            (-tonnes, -tonnes * listing.sell)
+        };
+        if tonnes == 0.0 {
+            continue;
+        }
+        *credits -= paid;
+
+        script_events.send(ScriptEvent {
+            name: "traded".to_string(),
+            args: vec![
+                Value::from_entity(request.ship),
+                Value::from_entity(docked.to),
+                Value::Str(request.commodity.clone()),
+                Value::Num(tonnes as f64),
+                Value::Num(paid as f64),
+            ],
+        });
+    }
+}
+
+/// :SYSTEM: Shows the market of the station the controlled ship is docked to,
+/// for the player to buy and sell at.
+#[allow(clippy::type_complexity)]
+fn market_panel_system(
+    mut contexts: EguiContexts,
+    player: Query<
+        (
+            Entity,
+            &Docked,
+            &CargoHold,
+            Option<&Commodity>,
+            Option<&Faction>,
+        ),
+        With<Controlled>,
+    >,
+    markets: Query<(&Market, Option<&Name>)>,
+    accounts: Res<Accounts>,
+    mut requests: EventWriter<TradeRequest>,
+) {
+    let Ok((ship, docked, hold, held, faction)) = player.get_single() else {
+        return;
+    };
+    let Ok((market, name)) = markets.get(docked.to) else {
+        return;
+    };
+    let title = name.map_or("Market".to_string(), |n| format!("{} market", n));
+
+    egui::Window::new(title).show(contexts.ctx_mut(), |ui| {
+        ui.label(format!(
+            "{:.0} credits",
+            accounts.balance(faction.copied().unwrap_or_default())
+        ));
+        ui.label(match held {
+            Some(commodity) if hold.cargo > 0.0 => format!(
+                "hold: {:.0}/{:.0} t of {}",
+                hold.cargo, hold.capacity, commodity.0
+            ),
+            _ => format!("hold: {:.0}/{:.0} t", hold.cargo, hold.capacity),
+        });
+        ui.separator();
+
+        egui::Grid::new("market").show(ui, |ui| {
+            ui.strong("commodity");
+            ui.strong("stock");
+            ui.strong("buy");
+            ui.strong("sell");
+            ui.end_row();
+            for listing in &market.listings {
+                ui.label(&listing.commodity);
+                ui.label(format!("{:.0} t", listing.stock));
+                ui.label(format!("{:.1}", listing.buy));
+                ui.label(format!("{:.1}", listing.sell));
+                for (text, tonnes) in [("Buy 10", 10.0), ("Sell 10", -10.0)] {
+                    if ui.button(text).clicked() {
+                        requests.send(TradeRequest {
+                            ship,
+                            commodity: listing.commodity.clone(),
+                            tonnes,
+                        });
+                    }
+                }
+                ui.end_row();
+            }
+        });
+    });
+}
@@ -0,0 +1,127 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+use super::clock::SimulationClock;
+use super::physics::SimulationSet;
+use super::star_system::Dormant;
+use super::user_interface::UiSettings;
+
+pub struct TrailsPlugin;
+
+impl Plugin for TrailsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(trail_system.after(SimulationSet))
+            .add_system(trail_render_system);
+    }
+}
+
+/// :COMPONENT: Keeps the last `length` positions of an entity, one every
+/// `interval` seconds of simulation time, to draw where it's been.
+#[derive(Component, Clone, Debug)]
+pub struct Trail {
+    pub length: usize,
+    pub interval: f64,
+    pub color: Color,
+    points: VecDeque<Vec3>,
+    last_sample: Option<f64>,
+}
+
+impl Default for Trail {
+    fn default() -> Self {
+        Self::new(120, 0.5, Color::rgb(0.6, 0.6, 0.6))
+    }
+}
+
+impl Trail {
+    pub fn new(length: usize, interval: f64, color: Color) -> Self {
+        Self {
+            length,
+            interval,
+            color,
+            points: VecDeque::with_capacity(length),
+            last_sample: None,
+        }
+    }
+
+    /// Forgets where the entity has been, so the trail starts over from here.
+    pub fn clear(&mut self) {
+        self.points.clear();
+        self.last_sample = None;
+    }
+}
+
+/// :SYSTEM: Adds a point to each trail once its interval is up, dropping the
+/// oldest once it's full.
+fn trail_system(
+    mut trails: Query<(&mut Trail, &GlobalTransform), Without<Dormant>>,
+    clock: Res<SimulationClock>,
+) {
+    for (mut trail, transform) in trails.iter_mut() {
+        let trail = &mut *trail;
+        if trail
+            .last_sample
+            .is_some_and(|last| clock.elapsed - last < trail.interval)
+        {
+            continue;
+        }
+        trail.last_sample = Some(clock.elapsed);
+        trail.points.push_back(transform.translation());
+        while trail.points.len() > trail.length {
+            trail.points.pop_front();
+        }
+    }
+}
+
+/// :SYSTEM: Draws each trail behind its entity, fading out towards its oldest
+/// end. T shows and hides them (see [`UiSettings`]).
+fn trail_render_system(
+    mut contexts: EguiContexts,
+    mut settings: ResMut<UiSettings>,
+    input: Res<Input<KeyCode>>,
+    trails: Query<(&Trail, &GlobalTransform), Without<Dormant>>,
+    cameras: Query<(&Camera, &GlobalTransform), With<Camera2d>>,
+) {
+    let ctx = contexts.ctx_mut();
+    if input.just_pressed(KeyCode::T) && !ctx.wants_keyboard_input() {
+        settings.trails = !settings.trails;
+    }
+    if !settings.trails {
+        return;
+    }
+    let Ok((camera, camera_transform)) = cameras.get_single() else {
+        return;
+    };
+    let Some(viewport) = camera.logical_viewport_size() else {
+        return;
+    };
+    // the viewport has +Y up, egui has it down
+    let to_screen = |p: Vec3| {
+        camera
+            .world_to_viewport(camera_transform, p)
+            .map(|at| egui::pos2(at.x, viewport.y - at.y))
+    };
+
+    let painter = ctx.layer_painter(egui::LayerId::background());
+    for (trail, transform) in trails.iter() {
+        let [r, g, b, a] = trail.color.as_rgba_f32();
+        // the trail runs up to where the entity is now
+        let points = trail
+            .points
+            .iter()
+            .copied()
+            .chain(std::iter::once(transform.translation()))
+            .map(to_screen)
+            .collect::<Vec<_>>();
+        let count = points.len() as f32;
+        for (i, pair) in points.windows(2).enumerate() {
+            let (Some(from), Some(to)) = (pair[0], pair[1]) else {
+                continue;
+            };
+            let fade = a * (i + 1) as f32 / count;
+            let color = egui::Rgba::from_rgba_unmultiplied(r, g, b, fade);
+            painter.line_segment([from, to], (1.0, color));
+        }
+    }
+}
@@ -0,0 +1,421 @@
+//! Recorded tutorials: a play session, with arrows and text callouts drawn over
+//! the screen at set times, which can be played back as a guided demo.
+//!
+//! Start the game with `--record=<file>` to record one. The session starts
+//! recording with the mission, and the authoring panel saves it to
+//! `assets/tutorials/<file>`, along with any annotations placed by holding
+//! Ctrl and clicking (a callout) or dragging (an arrow). Start the game with
+//! `--tutorial=<file>`, and the same mission, to play one back.
+
+use bevy::input::InputSystem;
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use bevy_egui::{egui, EguiContexts};
+use serde::{Deserialize, Serialize};
+
+use super::clock::SimulationClock;
+use super::input_map::{key_name, Action, InputMap};
+use super::physics::SimulationSet;
+
+pub struct TutorialPlugin;
+
+impl Plugin for TutorialPlugin {
+    fn build(&self, app: &mut App) {
+        let arg = |flag: &str| {
+            std::env::args().find_map(|a| a.strip_prefix(flag).map(|path| path.to_string()))
+        };
+        if let Some(path) = arg("--record=") {
+            app.insert_resource(Recorder::new(path));
+        } else if let Some(path) = arg("--tutorial=") {
+            match Tutorial::load(&path) {
+                Ok(tutorial) => {
+                    app.insert_resource(Player::new(tutorial));
+                }
+                Err(e) => warn!("couldn't load tutorial {}: {}", path, e),
+            }
+        }
+
+        app.configure_set(SimulationSet.run_if(not_waiting))
+            .add_system(
+                record_system
+                    .in_base_set(CoreSet::PreUpdate)
+                    .after(super::code_editor::keyboard_capture_system)
+                    .run_if(resource_exists::<Recorder>()),
+            )
+            .add_system(authoring_panel_system.run_if(resource_exists::<Recorder>()))
+            .add_system(
+                playback_system
+                    .in_base_set(CoreSet::PreUpdate)
+                    .after(InputSystem)
+                    .run_if(resource_exists::<Player>()),
+            )
+            .add_system(annotation_system.run_if(resource_exists::<Player>()));
+    }
+}
+
+/// Keys which are recorded, and pressed again on playback. These are the ones
+/// bound to flying the player's ship.
+fn recorded_keys(map: &InputMap) -> Vec<KeyCode> {
+    let mut keys: Vec<KeyCode> = Action::FLIGHT
+        .iter()
+        .flat_map(|a| map.keys(*a).iter().copied())
+        .collect();
+    keys.sort();
+    keys.dedup();
+    keys
+}
+
+/// A recorded play session, and what to point out along the way.
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+pub struct Tutorial {
+    /// The mission the session was recorded in. It has to be played back in the
+    /// same one.
+    pub mission: String,
+    pub inputs: Vec<InputFrame>,
+    pub annotations: Vec<Annotation>,
+}
+
+impl Tutorial {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let path = format!("assets/tutorials/{}", path);
+        std::fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|s| ron::from_str(&s).map_err(|e| e.to_string()))
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let path = format!("assets/tutorials/{}", path);
+        let text = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|e| e.to_string())?;
+        std::fs::create_dir_all("assets/tutorials").map_err(|e| e.to_string())?;
+        std::fs::write(&path, text).map_err(|e| e.to_string())
+    }
+}
+
+/// The keys held down, and where the camera was, from `time` until the next
+/// frame. Frames are only recorded when something changes.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct InputFrame {
+    /// Mission elapsed time, in seconds.
+    pub time: f64,
+    pub pressed: Vec<String>,
+    pub camera: [f32; 2],
+}
+
+/// Something drawn over the screen during a tutorial. Positions are fractions
+/// of the window's size, from its top left corner.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Annotation {
+    /// Mission elapsed time it appears, in seconds.
+    pub time: f64,
+    /// How long it stays up, in seconds.
+    pub duration: f64,
+    /// Holds the demo still until the player continues.
+    pub wait: bool,
+    pub text: String,
+    pub shape: Shape,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub enum Shape {
+    Callout { at: [f32; 2] },
+    Arrow { from: [f32; 2], to: [f32; 2] },
+}
+
+/// Resource which records a tutorial, when the game is started with
+/// `--record=<file>`.
+#[derive(Resource)]
+pub struct Recorder {
+    path: String,
+    tutorial: Tutorial,
+    /// Text, duration, and wait, for the next annotation placed.
+    text: String,
+    duration: f64,
+    wait: bool,
+    /// Where a Ctrl drag started, in window fractions.
+    drag_start: Option<[f32; 2]>,
+    status: String,
+}
+
+impl Recorder {
+    fn new(path: String) -> Self {
+        Self {
+            path,
+            tutorial: Tutorial {
+                mission: std::env::args()
+                    .skip(1)
+                    .find(|a| !a.starts_with("--"))
+                    .unwrap_or_default(),
+                ..Default::default()
+            },
+            text: String::new(),
+            duration: 5.0,
+            wait: false,
+            drag_start: None,
+            status: String::new(),
+        }
+    }
+}
+
+/// Resource which plays back a tutorial, when the game is started with
+/// `--tutorial=<file>`.
+#[derive(Resource)]
+pub struct Player {
+    tutorial: Tutorial,
+    /// Index of the next input frame to apply.
+    next_input: usize,
+    held: Vec<KeyCode>,
+    /// Index of the annotation the demo is waiting on, if any.
+    waiting: Option<usize>,
+    /// Annotations which the player has already continued past.
+    continued: Vec<usize>,
+}
+
+impl Player {
+    fn new(tutorial: Tutorial) -> Self {
+        Self {
+            tutorial,
+            next_input: 0,
+            held: Vec::new(),
+            waiting: None,
+            continued: Vec::new(),
+        }
+    }
+}
+
+fn not_waiting(player: Option<Res<Player>>) -> bool {
+    player.is_none_or(|p| p.waiting.is_none())
+}
+
+/// Where the cursor is, as a fraction of the window's size.
+fn cursor_fraction(window: &Window) -> Option<[f32; 2]> {
+    let cursor = window.cursor_position()?;
+    // bevy measures the cursor from the bottom left
+    Some([cursor.x / window.width(), 1.0 - cursor.y / window.height()])
+}
+
+/// :SYSTEM: Records the player's keys and camera, and places annotations.
+#[allow(clippy::too_many_arguments)]
+fn record_system(
+    mut recorder: ResMut<Recorder>,
+    input: Res<Input<KeyCode>>,
+    mouse: Res<Input<MouseButton>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<&Transform, With<Camera>>,
+    mut contexts: EguiContexts,
+    clock: Res<SimulationClock>,
+    map: Res<InputMap>,
+) {
+    let camera = cameras
+        .get_single()
+        .map_or([0.0, 0.0], |t| [t.translation.x, t.translation.y]);
+    let frame = InputFrame {
+        time: clock.elapsed,
+        pressed: recorded_keys(&map)
+            .into_iter()
+            .filter(|k| input.pressed(*k))
+            .map(key_name)
+            .collect(),
+        camera,
+    };
+    let last = recorder.tutorial.inputs.last();
+    if last.is_none_or(|l| l.pressed != frame.pressed || l.camera != frame.camera) {
+        recorder.tutorial.inputs.push(frame);
+    }
+
+    let Ok(window) = windows.get_single() else {
+        return;
+    };
+    let placing = input.any_pressed([KeyCode::LControl, KeyCode::RControl])
+        && !contexts.ctx_mut().wants_pointer_input();
+    if !placing {
+        recorder.drag_start = None;
+        return;
+    }
+    let Some(cursor) = cursor_fraction(window) else {
+        return;
+    };
+    if mouse.just_pressed(MouseButton::Left) {
+        recorder.drag_start = Some(cursor);
+    }
+    if mouse.just_released(MouseButton::Left) {
+        let Some(start) = recorder.drag_start.take() else {
+            return;
+        };
+        let dragged = (start[0] - cursor[0]).hypot(start[1] - cursor[1]) > 0.01;
+        let shape = if dragged {
+            Shape::Arrow {
+                from: start,
+                to: cursor,
+            }
+        } else {
+            Shape::Callout { at: cursor }
+        };
+        let annotation = Annotation {
+            time: clock.elapsed,
+            duration: recorder.duration,
+            wait: recorder.wait,
+            text: recorder.text.clone(),
+            shape,
+        };
+        recorder.tutorial.annotations.push(annotation);
+    }
+}
+
+/// :SYSTEM: Shows the tutorial authoring panel.
+fn authoring_panel_system(
+    mut contexts: EguiContexts,
+    mut recorder: ResMut<Recorder>,
+    clock: Res<SimulationClock>,
+) {
+    let recorder = &mut *recorder;
+    egui::Window::new("Tutorial recording").show(contexts.ctx_mut(), |ui| {
+        ui.label(format!(
+            "{:.1} s, {} input frames, {} annotations",
+            clock.elapsed,
+            recorder.tutorial.inputs.len(),
+            recorder.tutorial.annotations.len()
+        ));
+        ui.label("Ctrl click for a callout, Ctrl drag for an arrow.");
+        ui.text_edit_multiline(&mut recorder.text);
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::DragValue::new(&mut recorder.duration)
+                    .clamp_range(0.5..=60.0)
+                    .suffix(" s"),
+            );
+            ui.checkbox(&mut recorder.wait, "wait for the player");
+        });
+
+        let mut remove = None;
+        for (i, annotation) in recorder.tutorial.annotations.iter().enumerate() {
+            ui.horizontal(|ui| {
+                if ui.small_button("x").clicked() {
+                    remove = Some(i);
+                }
+                ui.label(format!("{:.1} s: {}", annotation.time, annotation.text));
+            });
+        }
+        if let Some(i) = remove {
+            recorder.tutorial.annotations.remove(i);
+        }
+
+        if ui.button(format!("Save to {}", recorder.path)).clicked() {
+            recorder.status = match recorder.tutorial.save(&recorder.path) {
+                Ok(()) => "saved".to_string(),
+                Err(e) => format!("couldn't save: {}", e),
+            };
+        }
+        ui.label(&recorder.status);
+    });
+}
+
+/// :SYSTEM: Presses the recorded keys, and moves the camera, as the tutorial
+/// plays back. The player's own flying keys are ignored meanwhile.
+fn playback_system(
+    mut player: ResMut<Player>,
+    mut input: ResMut<Input<KeyCode>>,
+    mut cameras: Query<&mut Transform, With<Camera>>,
+    clock: Res<SimulationClock>,
+    map: Res<InputMap>,
+) {
+    let recorded = recorded_keys(&map);
+    let player = &mut *player;
+    while let Some(frame) = player.tutorial.inputs.get(player.next_input) {
+        if frame.time > clock.elapsed {
+            break;
+        }
+        player.held = recorded
+            .iter()
+            .copied()
+            .filter(|k| frame.pressed.contains(&key_name(*k)))
+            .collect();
+        if let Ok(mut camera) = cameras.get_single_mut() {
+            camera.translation.x = frame.camera[0];
+            camera.translation.y = frame.camera[1];
+        }
+        player.next_input += 1;
+    }
+
+    for key in recorded {
+        if player.held.contains(&key) {
+            input.press(key);
+        } else {
+            input.release(key);
+            input.clear_just_pressed(key);
+        }
+    }
+}
+
+/// :SYSTEM: Draws the tutorial's annotations, and holds the demo at the ones
+/// which wait for the player.
+fn annotation_system(
+    mut contexts: EguiContexts,
+    mut player: ResMut<Player>,
+    clock: Res<SimulationClock>,
+) {
+    let player = &mut *player;
+    let now = clock.elapsed;
+    if player.waiting.is_none() {
+        player.waiting = player
+            .tutorial
+            .annotations
+            .iter()
+            .position(|a| a.wait && a.time <= now)
+            .filter(|i| !player.continued.contains(i));
+    }
+
+    let ctx = contexts.ctx_mut();
+    let screen = ctx.screen_rect();
+    let at = |p: [f32; 2]| screen.min + egui::vec2(p[0], p[1]) * screen.size();
+    let painter = ctx.layer_painter(egui::LayerId::new(
+        egui::Order::Foreground,
+        egui::Id::new("tutorial"),
+    ));
+    let color = egui::Color32::from_rgb(255, 200, 60);
+    let stroke = egui::Stroke::new(3.0, color);
+
+    for (i, annotation) in player.tutorial.annotations.iter().enumerate() {
+        let showing = if annotation.wait {
+            player.waiting == Some(i)
+        } else {
+            (annotation.time..annotation.time + annotation.duration).contains(&now)
+        };
+        if !showing {
+            continue;
+        }
+
+        let text_at = match annotation.shape {
+            Shape::Callout { at: p } => at(p),
+            Shape::Arrow { from, to } => {
+                painter.arrow(at(from), at(to) - at(from), stroke);
+                at(from)
+            }
+        };
+        if annotation.text.is_empty() {
+            continue;
+        }
+        let galley = painter.layout(
+            annotation.text.clone(),
+            egui::FontId::proportional(16.0),
+            egui::Color32::WHITE,
+            320.0,
+        );
+        let rect = egui::Align2::CENTER_CENTER
+            .anchor_rect(egui::Rect::from_min_size(text_at, galley.size()))
+            .expand(6.0);
+        painter.rect(rect, 4.0, egui::Color32::from_black_alpha(200), stroke);
+        painter.galley(rect.min + egui::vec2(6.0, 6.0), galley);
+    }
+
+    if let Some(i) = player.waiting {
+        egui::Area::new("tutorial_continue")
+            .anchor(egui::Align2::CENTER_BOTTOM, [0.0, -40.0])
+            .show(ctx, |ui| {
+                if ui.button("Continue").clicked() {
+                    player.continued.push(i);
+                    player.waiting = None;
+                }
+            });
+    }
+}
@@ -2,30 +2,115 @@ use bevy::{
     input::mouse::{MouseButton, MouseMotion, MouseWheel},
     prelude::*,
     render::camera::Camera,
-    render::view::VisibleEntities,
+    transform::TransformSystem,
+    window::PrimaryWindow,
 };
-
-use super::physics::Kinimatics;
-use super::ships::{Engine, Throttle};
+use bevy_egui::EguiContexts;
+
+use super::clock::SimulationClock;
+use super::cutscene::Cutscene;
+use super::input_map::{Action, InputMap};
+use super::level::{AstroObject, OnRails};
+use super::orders::OrdersPanel;
+use super::physics::{Attitude, GravityAffected, GravitySource, Kinimatics, PhysicsConfig};
+use super::propagation::{self, PlannedControls, PropagatedBody};
+use super::scripting::{ScriptEvent, ShipProgram, Value};
+use super::ships::{Controlled, Engine, Ship, ThrottleProgram};
+use super::star_system::Dormant;
 
 pub struct UserInterfacePlugin;
 
 impl Plugin for UserInterfacePlugin {
     fn build(&self, app: &mut App) {
-        app.add_startup_system(startup_system)
+        app.init_resource::<UiSettings>()
+            .init_resource::<CourseProjection>()
+            .add_event::<CollisionWarning>()
+            .add_startup_system(startup_system)
             .add_system(user_interface_system)
-            .add_system(course_projection_system);
+            .add_system(time_warp_system)
+            .add_system(course_projection_system)
+            .add_system(collision_warning_system.after(course_projection_system))
+            .add_system(
+                map_icon_system
+                    .in_base_set(CoreSet::PostUpdate)
+                    .before(TransformSystem::TransformPropagate),
+            );
     }
 }
 
+/// :COMPONENT: Marks a sprite which stays the same size on screen however far
+/// the display is zoomed (see [`user_interface_system`]). Icons should go on a
+/// child of the entity they stand for, so its own transform is left alone.
+#[derive(Reflect, Default, Component, Clone, Copy)]
+#[reflect(Component)]
+pub struct MapIcon;
+
 /// :COMPONENT: Marker component
 #[derive(Default, Component)]
 pub struct ProjectionDot;
 
+/// Resource which says what's drawn over the map, and how fast time goes.
+/// The buttons in the tray (see [`hud`](super::hud)) change it, as do the
+/// keyboard shortcuts: the key bound to [`Action::ToggleProjection`], L for
+/// labels and T for trails.
+#[derive(Resource)]
+pub struct UiSettings {
+    pub projection: bool,
+    pub labels: bool,
+    pub trails: bool,
+    /// Game seconds which pass for each second on the wall clock.
+    pub time_warp: f32,
+}
+
+impl Default for UiSettings {
+    fn default() -> Self {
+        Self {
+            projection: true,
+            labels: true,
+            trails: true,
+            time_warp: 1.0,
+        }
+    }
+}
+
+/// Resource which holds what the course projection found.
+#[derive(Resource, Default)]
+pub struct CourseProjection {
+    /// Bodies the controlled ship comes too close to over the projection.
+    pub warnings: Vec<CollisionWarning>,
+}
+
+/// Closest the controlled ship can come to another body, in world units,
+/// before the projection warns about it.
+pub const CLOSE_APPROACH: f32 = 30.0;
+
+/// :EVENT: Sent when the course projection first finds the controlled ship
+/// coming closer than [`CLOSE_APPROACH`] to another body, or passing inside a
+/// planet.
+#[derive(Clone, Copy, Debug)]
+pub struct CollisionWarning {
+    pub ship: Entity,
+    pub other: Entity,
+    /// Seconds from now to the closest approach.
+    pub time: f32,
+    /// Between their centers, at the closest approach.
+    pub distance: f32,
+    /// Where the ship will be then.
+    pub position: Vec3,
+    /// Whether the ship will be inside the other body.
+    pub impact: bool,
+}
+
+/// :COMPONENT: Marks where the projection has the controlled ship coming too
+/// close to something.
+#[derive(Component)]
+pub struct CollisionMarker;
+
 /// :BUNDLE: Provided for convenience.
 #[derive(Default, Bundle)]
 pub struct ProjectionDotBundle {
     pub projection_dot: ProjectionDot,
+    pub map_icon: MapIcon,
 
     #[bundle]
     pub sprite: SpriteBundle,
@@ -35,6 +120,7 @@ pub struct ProjectionDotBundle {
 #[derive(Resource)]
 pub struct UISprites {
     projection_dot: SpriteBundle,
+    collision_marker: SpriteBundle,
 }
 
 fn startup_system(mut commands: Commands, asset_server: ResMut<AssetServer>) {
@@ -47,7 +133,15 @@ fn startup_system(mut commands: Commands, asset_server: ResMut<AssetServer>) {
                 color: Color::rgb_u8(199, 199, 199),
                 ..Default::default()
             },
-            transform: Transform::from_scale(Vec3::new(1.0, 1.0, 0.0)),
+            texture: asset_server.load("../assets/dot.png"),
+            ..Default::default()
+        },
+        collision_marker: SpriteBundle {
+            sprite: Sprite {
+                custom_size: Some(Vec2::new(6.0, 6.0)),
+                color: Color::rgb_u8(230, 60, 60),
+                ..Default::default()
+            },
             texture: asset_server.load("../assets/dot.png"),
             ..Default::default()
         },
@@ -56,154 +150,308 @@ fn startup_system(mut commands: Commands, asset_server: ResMut<AssetServer>) {
     commands.insert_resource(sprite_resource);
 }
 
+/// :SYSTEM: Sizes every [`MapIcon`] for the current zoom.
+fn map_icon_system(
+    mut icons: Query<&mut Transform, With<MapIcon>>,
+    cameras: Query<&OrthographicProjection, With<Camera2d>>,
+) {
+    let Ok(ortho) = cameras.get_single() else {
+        return;
+    };
+    for mut transform in icons.iter_mut() {
+        if transform.scale.x != ortho.scale || transform.scale.y != ortho.scale {
+            transform.scale = Vec3::new(ortho.scale, ortho.scale, 1.0);
+        }
+    }
+}
+
+/// How far out of the way of the edges of the screen framed entities are kept,
+/// as a fraction of the screen.
+const FRAME_MARGIN: f32 = 0.2;
+
 /// :SYSTEM: Allows the user to scroll, pan, and zoom the display.
 ///
+/// Zooming keeps the point under the cursor where it is. F frames the selected
+/// ships, or the controlled ship if none are selected, and Shift+F frames the
+/// whole system.
+///
 /// Note: zooming does
-/// not visually scale visible entities, because the display is more of a map than a camera.
+/// not visually scale [`MapIcon`]s, because the display is more of a map than a camera.
 /// because of the vast distances of outer space, sprites would be way to small to see if they
 /// zoomed to scale.
-fn user_interface_system(
-    mut cam_query: Query<
-        (
-            &mut OrthographicProjection,
-            &mut Transform,
-            &mut Camera,
-            &mut VisibleEntities,
-        ),
-        With<Camera2d>,
-    >,
-    mut transform_query: Query<&mut Transform, (With<Sprite>, Without<Camera>)>,
+///
+/// The user can't move the display while a cutscene is playing.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn user_interface_system(
+    mut contexts: EguiContexts,
+    mut cam_query: Query<(&mut OrthographicProjection, &mut Transform, &Camera), With<Camera2d>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
     mouse_state: Res<Input<MouseButton>>,
+    keys: Res<Input<KeyCode>>,
     mut motion_evr: EventReader<MouseMotion>,
     mut wheel_evr: EventReader<MouseWheel>,
+    orders: Res<OrdersPanel>,
+    framed: Query<
+        (Entity, &GlobalTransform, Option<&Controlled>),
+        (Or<(With<Ship>, With<AstroObject>)>, Without<Dormant>),
+    >,
+    cutscene: Res<Cutscene>,
 ) {
-    // handle zooming when the user scrolls
+    if cutscene.playing() {
+        return;
+    }
+    let Ok((mut ortho, mut t, camera)) = cam_query.get_single_mut() else {
+        return;
+    };
+    let ctx = contexts.ctx_mut();
+
+    // handle zooming when the user scrolls, around the point under the cursor
+    let cursor = windows.get_single().ok().and_then(|w| w.cursor_position());
+    let viewport = camera.logical_viewport_size().unwrap_or(Vec2::ONE);
     for event in wheel_evr.iter() {
-        for (mut ortho, _transform, mut camera, _entities) in cam_query.iter_mut() {
-            const ZOOM_SPEED: f32 = 0.1;
-            let scale_difference = (10.0 as f32).powf(event.y as f32 * ZOOM_SPEED);
+        if ctx.wants_pointer_input() {
+            continue;
+        }
+        const ZOOM_SPEED: f32 = 0.1;
+        let scale_difference = (10.0 as f32).powf(event.y as f32 * ZOOM_SPEED);
+
+        // the cursor has +Y up and its origin in the corner, like the map
+        let from_center = cursor.map_or(Vec2::ZERO, |c| c - viewport / 2.0);
+        let under_cursor = t.translation.truncate() + from_center * ortho.scale;
 
-            // adjust camera scaling
-            ortho.scale *= scale_difference;
-            //camera.projection_matrix = ortho.get_projection_matrix();
+        let scale = ortho.scale * scale_difference;
+        ortho.scale = scale;
+        let eye = under_cursor - from_center * scale;
+        t.translation = eye.extend(t.translation.z);
+    }
 
+    // handle paning when the user middle clicks and drags
+    if mouse_state.pressed(MouseButton::Middle) {
+        motion_evr
+            .iter()
+            .for_each(|m| t.translation += ortho.scale * Vec3::new(-m.delta.x, m.delta.y, 0.0));
+    }
 
-            // scale visible entities
-            for mut t in transform_query.iter_mut() {
-                t.scale *= Vec3::ONE * scale_difference;
+    // frame the selection, or everything
+    if keys.just_pressed(KeyCode::F) && !ctx.wants_keyboard_input() {
+        let everything = keys.any_pressed([KeyCode::LShift, KeyCode::RShift]);
+        let points: Vec<Vec2> = framed
+            .iter()
+            .filter(|(entity, _, controlled)| {
+                everything
+                    || orders.selected.contains(entity)
+                    || (orders.selected.is_empty() && controlled.is_some())
+            })
+            .map(|(_, transform, _)| transform.translation().truncate())
+            .collect();
+        if points.is_empty() {
+            return;
+        }
+        let (min, max) = points.iter().fold(
+            (Vec2::splat(f32::MAX), Vec2::splat(f32::MIN)),
+            |(min, max), p| (min.min(*p), max.max(*p)),
+        );
+        t.translation = ((min + max) / 2.0).extend(t.translation.z);
+        // a lone ship is centred, without changing the zoom
+        if points.len() > 1 {
+            let fit = ((max - min) / (viewport * (1.0 - FRAME_MARGIN))).max_element();
+            if fit > 0.0 {
+                ortho.scale = fit;
             }
         }
     }
+}
 
-    // handle paning when the user middle clicks and drags
+/// :SYSTEM: Runs time as fast as [`UiSettings::time_warp`] says.
+fn time_warp_system(settings: Res<UiSettings>, mut time: ResMut<Time>) {
+    if time.relative_speed() != settings.time_warp {
+        time.set_relative_speed(settings.time_warp);
+    }
+}
 
-    for (op, mut t, _c, _e) in cam_query.iter_mut() {
-        if mouse_state.pressed(MouseButton::Middle) {
-            motion_evr
-                .iter()
-                .for_each(|m| t.translation += op.scale * Vec3::new(-m.delta.x, m.delta.y, 0.0));
-        }
+/// Seconds of course projection a body gets at the default zoom.
+const PROJECTION_SECONDS: f32 = 1.0;
+
+/// Longest any course projection goes, in seconds.
+const MAX_PROJECTION_SECONDS: f32 = 30.0;
+
+/// Steps the projection takes for each second it looks ahead.
+const PROJECTION_STEPS_PER_SECOND: f32 = 5.0;
+
+/// How much more the controlled ship's projection matters than anything
+/// else's.
+const CONTROLLED_IMPORTANCE: f32 = 2.0;
+
+/// :COMPONENT: Shows a body's projected course. Only bodies with one which
+/// are on screen, and the controlled ship, are projected; anything else is
+/// only simulated if it pulls on something. The projection reaches further
+/// ahead the further out the map is zoomed, and the more `importance` the
+/// body has.
+#[derive(Reflect, Component, Clone, Copy, Debug)]
+#[reflect(Component)]
+pub struct ProjectTrajectory {
+    pub importance: f32,
+}
+
+impl Default for ProjectTrajectory {
+    fn default() -> Self {
+        Self { importance: 1.0 }
     }
 }
 
-/// :SYSTEM: Projects the motion of all kinimatic bodies.
+impl ProjectTrajectory {
+    /// Number of points to project, counting where the body is now, at the
+    /// map's zoom `scale`.
+    pub fn points(&self, scale: f32) -> usize {
+        let seconds = PROJECTION_SECONDS * self.importance * scale.max(1.0);
+        (seconds.clamp(0.0, MAX_PROJECTION_SECONDS) * PROJECTION_STEPS_PER_SECOND).ceil() as usize
+    }
+}
+
+/// :SYSTEM: Projects the motion of the bodies with a [`ProjectTrajectory`],
+/// and warns about anything the controlled ship is coming too close to.
 ///
 /// Currently, the projection is displayed by using a bunch of `ProjectionDot entities which
 /// are moved to the entities projected locations. In the future, the plan is to transition to
 /// a shader to display the dot.
+#[allow(clippy::type_complexity, clippy::too_many_arguments)]
 pub fn course_projection_system(
     mut commands: Commands,
-    k_bods: Query<(&Kinimatics, &Transform, Option<&Engine>), Without<ProjectionDot>>,
+    k_bods: Query<
+        (
+            Entity,
+            &Kinimatics,
+            &Transform,
+            Option<&Engine>,
+            (Option<&GravitySource>, Option<&GravityAffected>),
+            Option<&OnRails>,
+            Option<&Attitude>,
+            Option<&ThrottleProgram>,
+            (Option<&Controlled>, Option<&AstroObject>),
+            Option<&ProjectTrajectory>,
+        ),
+        Without<ProjectionDot>,
+    >,
     mut dots: Query<(Entity, &mut Transform), With<ProjectionDot>>,
+    markers: Query<Entity, With<CollisionMarker>>,
+    cameras: Query<(&OrthographicProjection, &GlobalTransform), With<Camera2d>>,
+    mut warnings: EventWriter<CollisionWarning>,
     sprites: Res<UISprites>,
+    mut projection: ResMut<CourseProjection>,
+    mut settings: ResMut<UiSettings>,
+    input: Res<Input<KeyCode>>,
+    map: Res<InputMap>,
+    config: Res<PhysicsConfig>,
+    clock: Res<SimulationClock>,
 ) {
-    // make a copy of all the entities
-    let entities: Vec<(Kinimatics, Transform, Option<Engine>)> = k_bods
-        .iter()
-        .map(|(kinimatics, transform, engine)| {
-            if let Some(e) = engine {
-                return (kinimatics.clone(), transform.clone(), Some(e.clone()));
-            } else {
-                return (kinimatics.clone(), transform.clone(), None);
-            }
-        })
-        .collect();
-
-    let num_seconds = 1; // number of seconds to look ahead
-    let step_precision = 5; // steps/second
-
-    let mut steps: Vec<Vec<(Kinimatics, Transform, Option<Engine>)>> = Vec::new();
-    steps.reserve(num_seconds * step_precision);
-
-    let mut forces: Vec<Vec3> = Vec::new();
-    forces.reserve(entities.len());
-    for _ in 0..entities.len() {
-        forces.push(Vec3::ZERO);
+    if map.just_pressed(Action::ToggleProjection, &input) {
+        settings.projection = !settings.projection;
+    }
+    for marker in markers.iter() {
+        commands.entity(marker).despawn_recursive();
+    }
+    if !settings.projection {
+        for (dot, _) in dots.iter() {
+            commands.entity(dot).despawn_recursive();
+        }
+        projection.warnings.clear();
+        return;
     }
 
-    // initial state
-    steps.push(entities.clone());
-
-    // account for force due to gravity
-    const GRAVITATIONAL_CONSTANT: f32 = 6.67430e-11;
-    let dt = 1.0 / (step_precision as f32);
-    for step in 1..num_seconds * step_precision {
-        steps.push(steps[step - 1].clone());
-
-        // calculate forces for each body
-        for (i, bod1) in steps[step].iter().enumerate() {
-            let (k1, t1, engine) = bod1;
-
-            // add forces due to gravity
-            steps[step]
-                .split_at(i + 1)
-                .1
-                .iter()
-                .enumerate()
-                .for_each(|(j, bod2)| {
-                    let (k2, t2, _) = bod2;
-
-                    // calculate magnitude of the force
-                    let force_mag = GRAVITATIONAL_CONSTANT * (k1.mass * k2.mass)
-                        / t1.translation.distance_squared(t2.translation);
-
-                    // calculate direction and magnitude of the forces for each object.
-                    let d1 = (t2.translation - t1.translation).normalize() * force_mag;
-                    let d2 = (t1.translation - t2.translation).normalize() * force_mag;
-
-                    forces[i] += d1;
-                    forces[i + j + 1] += d2;
-                });
+    // what's on screen, with a margin so courses coming in from the edges
+    // still show
+    let (scale, view) = cameras.get_single().map_or((1.0, None), |(ortho, camera)| {
+        let center = camera.translation().truncate();
+        let margin = ortho.area.size() / 4.0;
+        let view = (
+            center + ortho.area.min - margin,
+            center + ortho.area.max + margin,
+        );
+        (ortho.scale, Some(view))
+    });
+    let on_screen = |at: Vec3| {
+        view.is_none_or(|(min, max)| {
+            at.truncate().cmpge(min).all() && at.truncate().cmple(max).all()
+        })
+    };
 
-            // handle force from ship engine
-            if let Some(t) = engine {
-                forces[i] += t1.rotation.mul_vec3(Vec3::Y)
-                    * match t.throttle {
-                        Throttle::Fixed(true) => t.max_thrust,
-                        Throttle::Fixed(false) => 0.0,
-                        Throttle::Variable(amount) => amount * t.max_thrust,
-                    };
-            }
-        }
+    // each body worth simulating, with the number of points to project it for
+    let included: Vec<_> = k_bods
+        .iter()
+        .filter_map(|body| {
+            let (_, _, transform, _, (source, _), .., (controlled, _), trajectory) = body;
+            let points = match (trajectory, controlled) {
+                (_, Some(_)) => {
+                    let trajectory = trajectory.copied().unwrap_or_default();
+                    Some(
+                        ProjectTrajectory {
+                            importance: trajectory.importance * CONTROLLED_IMPORTANCE,
+                        }
+                        .points(scale),
+                    )
+                }
+                (Some(trajectory), None) if on_screen(transform.translation) => {
+                    Some(trajectory.points(scale))
+                }
+                _ => None,
+            };
+            (points.is_some() || source.is_some()).then_some((body, points.unwrap_or(0)))
+        })
+        .collect();
 
-        // update kinimatics
-        steps[step]
-            .iter_mut()
-            .enumerate()
-            .for_each(|(j, (kin, trans, _))| {
-                kin.acceleration = forces[j] / kin.mass;
-                kin.velocity = kin.velocity + kin.acceleration * dt;
-                trans.translation = trans.translation + kin.velocity * dt;
-            });
+    let entities: Vec<Entity> = included.iter().map(|((entity, ..), _)| *entity).collect();
+    let bodies: Vec<PropagatedBody> = included
+        .iter()
+        .map(
+            |((_, kinimatics, transform, engine, (source, affected), rails, attitude, ..), _)| {
+                PropagatedBody {
+                    thrust: engine.map_or(Vec3::ZERO, |e| e.thrust_vector(Quat::IDENTITY)),
+                    angle: propagation::angle_of(transform.rotation),
+                    attitude: attitude.copied(),
+                    source: source.is_some(),
+                    affected: affected.is_some(),
+                    rails: rails.and_then(|r| entities.iter().position(|e| *e == r.parent)),
+                    ..PropagatedBody::new(
+                        transform.translation,
+                        kinimatics.velocity,
+                        kinimatics.mass,
+                    )
+                }
+            },
+        )
+        .collect();
+    let plans: Vec<PlannedControls> = included
+        .iter()
+        .enumerate()
+        .filter_map(|(body, ((.., engine, _, _, _, program, _, _), _))| {
+            Some(PlannedControls {
+                body,
+                program: (*program)?,
+                engine: (*engine)?.clone(),
+            })
+        })
+        .collect();
 
-        forces.clear();
-        for _ in 0..entities.len() {
-            forces.push(Vec3::ZERO);
-        }
-    }
+    // the initial state, then each step after it, for as long as the longest
+    // projection
+    let dt = 1.0 / PROJECTION_STEPS_PER_SECOND;
+    let longest = included
+        .iter()
+        .map(|(_, points)| *points)
+        .max()
+        .unwrap_or(0);
+    let mut steps = vec![bodies.clone()];
+    steps.extend(propagation::propagate_n_steps(
+        &bodies,
+        &plans,
+        clock.elapsed,
+        dt,
+        longest.saturating_sub(1),
+        &config,
+    ));
 
     // total number of dots needed for projection
-    let total_dots = steps.len() * entities.len();
+    let total_dots: usize = included.iter().map(|(_, points)| points).sum();
     let available_dots = dots.iter_mut().count();
 
     if available_dots > total_dots {
@@ -218,191 +466,100 @@ pub fn course_projection_system(
         // spawn in missing dots
         for _ in 0..(total_dots - available_dots) {
             commands
-                .spawn(ProjectionDotBundle { ..Default::default()
+                .spawn(ProjectionDotBundle {
+                    ..Default::default()
                 })
                 .with_children(|p| {
-                    p.spawn(sprites.projection_dot.clone());
+                    p.spawn((sprites.projection_dot.clone(), MapIcon));
                 });
         }
     }
 
-    let steps: Vec<Transform> = steps.into_iter().flatten().map(|k_bod| k_bod.1).collect();
-
-    for (i, (_, mut transform)) in dots.iter_mut().enumerate() {
-        *transform = steps[i];
+    // the closest the controlled ship comes to everything else
+    let found: Vec<CollisionWarning> = included
+        .iter()
+        .enumerate()
+        .filter(|(_, ((.., (controlled, _), _), _))| controlled.is_some())
+        .flat_map(|(ship, ((ship_entity, ..), points))| {
+            let steps = &steps[..*points];
+            included
+                .iter()
+                .enumerate()
+                .filter(move |(other, _)| *other != ship)
+                .filter_map(move |(other, ((other_entity, .., (_, astro), _), _))| {
+                    let (time, distance, position) = steps
+                        .iter()
+                        .enumerate()
+                        .map(|(i, step)| {
+                            let position = step[ship].position;
+                            (
+                                i as f32 * dt,
+                                position.distance(step[other].position),
+                                position,
+                            )
+                        })
+                        .min_by(|a, b| a.1.total_cmp(&b.1))?;
+                    let radius = astro.map_or(0.0, |a| a.radius);
+                    (distance < CLOSE_APPROACH.max(radius)).then_some(CollisionWarning {
+                        ship: *ship_entity,
+                        other: *other_entity,
+                        time,
+                        distance,
+                        position,
+                        impact: distance < radius,
+                    })
+                })
+        })
+        .collect();
+    for warning in found.iter() {
+        commands
+            .spawn((
+                CollisionMarker,
+                SpatialBundle::from_transform(Transform::from_translation(warning.position)),
+            ))
+            .with_children(|p| {
+                p.spawn((sprites.collision_marker.clone(), MapIcon));
+            });
+        let same = |w: &CollisionWarning| w.ship == warning.ship && w.other == warning.other;
+        if !projection.warnings.iter().any(same) {
+            warnings.send(*warning);
+        }
     }
-}
-
-/// Temporary init function.
-///
-/// Soon™ this will be unified into normal [startup_system()] system. Currently,
-/// this builds the UI.
-/*
-pub fn init_ui(
-    mut commands: Commands,
-    mut materials: ResMut<Assets<ColorMaterial>>,
-    asset_server: Res<AssetServer>,
-    //button_materials: Res<ButtonStyle>,
-) {
-    commands.spawn_bundle(UiCameraBundle::default());
-
-    let default_button = ButtonStyle {
-        material_normal: materials.add(Color::rgb(0.15, 0.15, 0.15).into()),
-        material_hovered: materials.add(Color::rgb(0.25, 0.25, 0.25).into()),
-        material_pressed: materials.add(Color::rgb(0.35, 0.75, 0.35).into()),
-        style: Style {
-            size: Size::new(Val::Px(100.0), Val::Px(65.0)),
-            align_items: AlignItems::Center,
-            justify_content: JustifyContent::Center,
-            ..Default::default()
-        },
-        text_style: TextStyle {
-            font: asset_server.load("/usr/share/fonts/gnu-free/FreeSans.otf"),
-            font_size: 40.0,
-            color: Color::rgb(0.9, 0.9, 0.9),
-        },
-    };
+    projection.warnings = found;
 
-    // root node
-    commands
-        .spawn_bundle(NodeBundle {
-            style: Style {
-                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
-                justify_content: JustifyContent::FlexStart,
-                ..Default::default()
-            },
-            material: materials.add(Color::NONE.into()),
-            ..Default::default()
+    let points: Vec<Vec3> = included
+        .iter()
+        .enumerate()
+        .flat_map(|(body, (_, points))| {
+            steps[..*points].iter().map(move |step| step[body].position)
         })
-        .with_children(|parent| {
-            // Bottom Tray (border)
-            parent
-                .spawn_bundle(NodeBundle {
-                    style: Style {
-                        size: Size::new(Val::Percent(100.0), Val::Percent(15.0)),
-                        border: Rect::all(Val::Px(2.0)),
-                        ..Default::default()
-                    },
-                    material: materials.add(Color::rgb(0.65, 0.65, 0.65).into()),
-                    ..Default::default()
-                })
-                .with_children(|parent| {
-                    // Bottom Tray Fill
-                    parent
-                        .spawn_bundle(NodeBundle {
-                            style: Style {
-                                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
-                                justify_content: JustifyContent::SpaceAround,
-                                align_items: AlignItems::Center,
-                                ..Default::default()
-                            },
-                            material: materials.add(Color::rgb_u8(57, 67, 74).into()),
-                            ..Default::default()
-                        })
-                        .with_children(|parent| {
-                            create_button(&mut parent.spawn(), &default_button).insert(
-                                CourseProjectionButton {
-                                    is_on: false,
-                                    style: default_button.clone(),
-                                },
-                            );
-                        });
-                });
-        });
-}
-*/
-
-/// :COMPONENT: Material Handles for different button states.
-///
-/// This describes the bare bones style of a button or group of buttons.
-#[derive(Clone, Resource)]
-pub struct ButtonStyle {
-    material_normal: Handle<ColorMaterial>,
-    material_hovered: Handle<ColorMaterial>,
-    material_pressed: Handle<ColorMaterial>,
-    style: Style,
-    text_style: TextStyle,
-}
-
-#[derive(Component)]
-#[allow(dead_code)]
-pub struct CourseProjectionButton {
-    is_on: bool,
-    style: ButtonStyle,
-}
+        .collect();
 
-// example button with functionality. this button toggles on/off the course projection.
-// input parameters: this function will need a list of all objects with paths to predict
-#[allow(dead_code)]
-pub fn course_projection_system_button(
-    mut button_query: Query<
-        (
-            &CourseProjectionButton,
-            &Interaction,
-            &mut Handle<ColorMaterial>,
-        ),
-        Changed<Interaction>,
-    >,
-) {
-    for (state, interaction, mut material) in button_query.iter_mut() {
-        match *interaction {
-            Interaction::Clicked => {
-                *material = state.style.material_pressed.clone();
-            }
-            Interaction::Hovered => {
-                *material = state.style.material_hovered.clone();
-            }
-            Interaction::None => {
-                *material = state.style.material_normal.clone();
-            }
-        }
+    for (i, (_, mut transform)) in dots.iter_mut().enumerate() {
+        transform.translation = points[i];
     }
 }
 
-/// Handles the state transition (think colors) of buttons
-pub fn button_system(
-    button_materials: Res<ButtonStyle>,
-    mut interaction_query: Query<
-        (&Interaction, &mut Handle<ColorMaterial>, &Children),
-        (Changed<Interaction>, With<Button>),
-    >,
-    mut text_query: Query<&mut Text>,
+/// :SYSTEM: Passes collision warnings on to scripts, as a `collision_warning`
+/// event with the ship, the other body, the seconds until the closest approach
+/// and the distance then. The ship's own program gets it without the ship.
+fn collision_warning_system(
+    mut warnings: EventReader<CollisionWarning>,
+    mut programs: Query<&mut ShipProgram>,
+    mut script_events: EventWriter<ScriptEvent>,
 ) {
-    for (interaction, mut material, children) in interaction_query.iter_mut() {
-        let mut text = text_query.get_mut(children[0]).unwrap();
-        match *interaction {
-            Interaction::Clicked => {
-                text.sections[0].value = "Press".to_string();
-                *material = button_materials.material_pressed.clone();
-            }
-            Interaction::Hovered => {
-                *material = button_materials.material_hovered.clone();
-            }
-            Interaction::None => {
-                *material = button_materials.material_normal.clone();
-            }
+    for warning in warnings.iter() {
+        let args = vec![
+            Value::from_entity(warning.other),
+            Value::Num(warning.time as f64),
+            Value::Num(warning.distance as f64),
+        ];
+        if let Ok(mut program) = programs.get_mut(warning.ship) {
+            program.vm.raise("collision_warning", args.clone());
         }
+        script_events.send(ScriptEvent {
+            name: "collision_warning".to_string(),
+            args: [vec![Value::from_entity(warning.ship)], args].concat(),
+        });
     }
 }
-
-/// Helper function to easily create buttons.
-use bevy::ecs::system::EntityCommands;
-/*
-fn create_button<'a, 'b, 'c>(
-    parent: &'c mut EntityCommands<'a, 'b>,
-    style: &ButtonStyle,
-) -> &'c mut EntityCommands<'a, 'b> {
-    parent
-        .insert_bundle(ButtonBundle {
-            style: style.style.clone(),
-            material: style.material_normal.clone(),
-            ..Default::default()
-        })
-        .with_children(|parent| {
-            parent.spawn_bundle(TextBundle {
-                text: Text::with_section("", style.text_style.clone(), Default::default()),
-                ..Default::default()
-            });
-        })
-}
-*/
@@ -0,0 +1,336 @@
+//! Particle effects: exhaust from engines, bursts from missile detonations,
+//! and sparks from hard knocks, along with the flames behind burning engines.
+//! They're purely for show, so they run on the frame's [`Time`] and never feed
+//! back into the simulation.
+
+use std::f32::consts::TAU;
+
+use bevy::{prelude::*, sprite::Anchor};
+
+use super::asteroid::Rng;
+use super::physics::{ApplyImpulse, Kinimatics, SimulationSet};
+use super::ships::{Detonation, Engine};
+use super::star_system::Dormant;
+use super::user_interface::MapIcon;
+
+pub struct VfxPlugin;
+
+impl Plugin for VfxPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Effects::default())
+            .add_system(exhaust_system.after(SimulationSet))
+            .add_system(detonation_effect_system.after(SimulationSet))
+            .add_system(impact_effect_system.after(SimulationSet))
+            .add_system(
+                particle_system
+                    .after(exhaust_system)
+                    .after(detonation_effect_system)
+                    .after(impact_effect_system),
+            )
+            .add_system(flame_spawn_system)
+            .add_system(flame_system.after(flame_spawn_system));
+    }
+}
+
+/// Most particles there can be at once. Nothing new is spawned past this.
+const MAX_PARTICLES: usize = 2000;
+
+/// Particles a full throttle engine gives off each second.
+const EXHAUST_RATE: f32 = 60.0;
+
+/// Speed exhaust leaves the back of a ship at, relative to the ship.
+const EXHAUST_SPEED: f32 = 40.0;
+
+/// Particles thrown out by a detonation, for each unit of blast radius.
+const BURST_PARTICLES: f32 = 2.0;
+
+/// Smallest change in velocity an impulse makes which throws off sparks.
+const SPARK_THRESHOLD: f32 = 2.0;
+
+/// Height particles are drawn at, under ships and above trails.
+const PARTICLE_Z: f32 = 0.5;
+
+/// Resource which holds what the effects need between frames.
+#[derive(Resource)]
+pub struct Effects {
+    pub enabled: bool,
+    rng: Rng,
+}
+
+impl Default for Effects {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            rng: Rng(0),
+        }
+    }
+}
+
+impl Effects {
+    /// A number between `low` and `high`.
+    fn range(&mut self, low: f32, high: f32) -> f32 {
+        low + (high - low) * self.rng.next()
+    }
+
+    /// A unit vector in a random direction.
+    fn direction(&mut self) -> Vec3 {
+        let angle = self.range(0.0, TAU);
+        Vec3::new(angle.cos(), angle.sin(), 0.0)
+    }
+}
+
+/// :COMPONENT: A short lived speck, which drifts, shrinks and fades until its
+/// time is up.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct Particle {
+    pub velocity: Vec3,
+    /// Seconds since it was spawned.
+    pub age: f32,
+    pub lifetime: f32,
+    /// Size when it's spawned, in world units. It shrinks to nothing.
+    pub size: f32,
+    pub color: Color,
+}
+
+/// Spawns a particle at `position`.
+fn spawn_particle(commands: &mut Commands, position: Vec3, particle: Particle) {
+    commands.spawn((
+        particle,
+        SpriteBundle {
+            sprite: Sprite {
+                color: particle.color,
+                custom_size: Some(Vec2::splat(particle.size)),
+                ..Default::default()
+            },
+            transform: Transform::from_translation(position.truncate().extend(PARTICLE_Z)),
+            ..Default::default()
+        },
+    ));
+}
+
+/// :SYSTEM: Blows exhaust out of the back of every burning engine, thicker the
+/// harder it burns.
+fn exhaust_system(
+    mut commands: Commands,
+    mut effects: ResMut<Effects>,
+    engines: Query<(&Transform, &Engine, Option<&Kinimatics>), Without<Dormant>>,
+    particles: Query<(), With<Particle>>,
+    time: Res<Time>,
+) {
+    if !effects.enabled {
+        return;
+    }
+    let mut room = MAX_PARTICLES.saturating_sub(particles.iter().len());
+    let dt = time.delta_seconds();
+    for (transform, engine, kin) in engines.iter() {
+        if engine.max_thrust <= 0.0 {
+            continue;
+        }
+        let throttle = engine.thrust() / engine.max_thrust;
+        // a fraction of a particle this frame is a chance of a whole one
+        let expected = EXHAUST_RATE * throttle * dt;
+        let count = (expected + effects.rng.next()) as usize;
+        let back = -engine.thrust_direction(transform.rotation);
+        let velocity = kin.map_or(Vec3::ZERO, |k| k.velocity);
+        for _ in 0..count.min(room) {
+            let spread = effects.range(-0.2, 0.2);
+            let out = Quat::from_rotation_z(spread).mul_vec3(back);
+            let particle = Particle {
+                velocity: velocity + out * EXHAUST_SPEED * effects.range(0.7, 1.0),
+                age: 0.0,
+                lifetime: effects.range(0.3, 0.6) * (0.5 + throttle),
+                size: 1.0 + 2.0 * throttle,
+                color: Color::rgb(1.0, effects.range(0.5, 0.8), 0.2),
+            };
+            spawn_particle(&mut commands, transform.translation + back * 4.0, particle);
+        }
+        room = room.saturating_sub(count);
+    }
+}
+
+/// :SYSTEM: Throws out a burst of particles from each missile detonation, which
+/// fills its blast radius.
+fn detonation_effect_system(
+    mut commands: Commands,
+    mut effects: ResMut<Effects>,
+    mut detonations: EventReader<Detonation>,
+    particles: Query<(), With<Particle>>,
+) {
+    if !effects.enabled {
+        detonations.clear();
+        return;
+    }
+    let mut room = MAX_PARTICLES.saturating_sub(particles.iter().len());
+    for blast in detonations.iter() {
+        let count = ((blast.blast_radius * BURST_PARTICLES) as usize).min(room);
+        room -= count;
+        for _ in 0..count {
+            let lifetime = effects.range(0.4, 0.8);
+            // the fastest just reach the edge of the blast before fading
+            let speed = blast.blast_radius / lifetime * effects.range(0.2, 1.0);
+            let particle = Particle {
+                velocity: effects.direction() * speed,
+                age: 0.0,
+                lifetime,
+                size: effects.range(1.5, 3.0),
+                color: Color::rgb(1.0, effects.range(0.3, 0.9), 0.1),
+            };
+            spawn_particle(&mut commands, blast.position, particle);
+        }
+        if room > 0 {
+            room -= 1;
+            let flash = Particle {
+                velocity: Vec3::ZERO,
+                age: 0.0,
+                lifetime: 0.25,
+                size: blast.blast_radius * 2.0,
+                color: Color::rgba(1.0, 0.9, 0.6, 0.6),
+            };
+            spawn_particle(&mut commands, blast.position, flash);
+        }
+    }
+}
+
+/// :SYSTEM: Scatters sparks where a hard enough impulse lands, like a crash or
+/// a blast. Gentle pushes, like undocking, don't spark.
+fn impact_effect_system(
+    mut commands: Commands,
+    mut effects: ResMut<Effects>,
+    mut impulses: EventReader<ApplyImpulse>,
+    bodies: Query<(&Transform, &Kinimatics)>,
+    particles: Query<(), With<Particle>>,
+) {
+    if !effects.enabled {
+        impulses.clear();
+        return;
+    }
+    let mut room = MAX_PARTICLES.saturating_sub(particles.iter().len());
+    for impulse in impulses.iter() {
+        let Ok((transform, kin)) = bodies.get(impulse.entity) else {
+            continue;
+        };
+        if kin.mass <= 0.0 {
+            continue;
+        }
+        let dv = impulse.impulse.length() / kin.mass;
+        if !dv.is_finite() || dv < SPARK_THRESHOLD {
+            continue;
+        }
+        let at = impulse.point.unwrap_or(transform.translation);
+        let count = (dv.sqrt() as usize * 3).clamp(3, 24).min(room);
+        room -= count;
+        for _ in 0..count {
+            let particle = Particle {
+                velocity: kin.velocity + effects.direction() * effects.range(10.0, 30.0),
+                age: 0.0,
+                lifetime: effects.range(0.15, 0.35),
+                size: 1.0,
+                color: Color::rgb(1.0, 1.0, effects.range(0.4, 0.9)),
+            };
+            spawn_particle(&mut commands, at, particle);
+        }
+    }
+}
+
+/// :SYSTEM: Moves, shrinks and fades every particle, and clears away the ones
+/// whose time is up.
+fn particle_system(
+    mut commands: Commands,
+    mut particles: Query<(Entity, &mut Particle, &mut Transform, &mut Sprite)>,
+    time: Res<Time>,
+) {
+    let dt = time.delta_seconds();
+    for (entity, mut particle, mut transform, mut sprite) in particles.iter_mut() {
+        particle.age += dt;
+        if particle.age >= particle.lifetime {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        let left = 1.0 - particle.age / particle.lifetime;
+        transform.translation += particle.velocity * dt;
+        sprite.custom_size = Some(Vec2::splat(particle.size * left));
+        sprite.color = particle.color.with_a(particle.color.a() * left);
+    }
+}
+
+/// Length of a flame at full throttle, in pixels.
+const FLAME_LENGTH: f32 = 14.0;
+
+/// Width of a flame at full throttle, in pixels.
+const FLAME_WIDTH: f32 = 5.0;
+
+/// Pixels from the middle of a ship's icon to the start of its flame.
+const FLAME_OFFSET: f32 = 6.0;
+
+/// :COMPONENT: Marks something which is only there for show, along with
+/// everything under it. It's left out of saved scenes, since it's made afresh
+/// for whatever it belongs to.
+#[derive(Component, Default, Clone, Copy, Debug)]
+pub struct Effect;
+
+/// :COMPONENT: The flame drawn behind `ship`'s engine. It grows longer and
+/// brighter with the throttle, flickers, and swings with the gimbal.
+///
+/// It hangs off a [`MapIcon`] child of the ship, so it stays the same size on
+/// screen alongside the ship's icon.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct Flame {
+    pub ship: Entity,
+}
+
+/// :SYSTEM: Gives every new engine a flame.
+fn flame_spawn_system(mut commands: Commands, engines: Query<Entity, Added<Engine>>) {
+    for ship in engines.iter() {
+        commands.entity(ship).with_children(|p| {
+            p.spawn((SpatialBundle::default(), MapIcon, Effect))
+                .with_children(|p| {
+                    p.spawn((
+                        Flame { ship },
+                        SpriteBundle {
+                            sprite: Sprite {
+                                color: Color::NONE,
+                                custom_size: Some(Vec2::ZERO),
+                                anchor: Anchor::TopCenter,
+                                ..Default::default()
+                            },
+                            // just under the ship's icon
+                            transform: Transform::from_xyz(0.0, -FLAME_OFFSET, -0.1),
+                            ..Default::default()
+                        },
+                    ));
+                });
+        });
+    }
+}
+
+/// :SYSTEM: Sizes each flame for its engine's throttle, whether fixed or
+/// variable. An engine out of fuel has no flame, whatever the throttle says.
+fn flame_system(
+    effects: Res<Effects>,
+    engines: Query<&Engine>,
+    mut flames: Query<(&Flame, &mut Sprite, &mut Transform)>,
+    time: Res<Time>,
+) {
+    let t = time.elapsed_seconds();
+    for (flame, mut sprite, mut transform) in flames.iter_mut() {
+        let Ok(engine) = engines.get(flame.ship) else {
+            continue;
+        };
+        let throttle = match engine.fuel > 0.0 && effects.enabled {
+            true => engine.throttle_fraction().clamp(0.0, 1.0),
+            false => 0.0,
+        };
+        // each flame flickers out of step with the rest
+        let phase = flame.ship.index() as f32;
+        let flicker = 1.0 + 0.15 * (t * 40.0 + phase).sin() + 0.05 * (t * 97.0 + phase).sin();
+        sprite.custom_size = Some(Vec2::new(
+            FLAME_WIDTH * (0.5 + throttle / 2.0),
+            FLAME_LENGTH * throttle * flicker,
+        ));
+        sprite.color = Color::rgba(1.0, 0.5 + 0.4 * throttle, 0.2, throttle.sqrt());
+        let gimbal = engine
+            .gimbal
+            .clamp(-engine.gimbal_limit, engine.gimbal_limit);
+        transform.rotation = Quat::from_rotation_z(gimbal);
+    }
+}